@@ -0,0 +1,19 @@
+/// Typed Dart APIs for the custom flutter-rs/* channels.
+library flutter_rs;
+
+export 'src/anchor.dart';
+export 'src/appearance.dart';
+export 'src/assets.dart';
+export 'src/bridge.dart';
+export 'src/channels.g.dart';
+export 'src/diagnostics.dart';
+export 'src/emulated_input.dart';
+export 'src/fonts.dart';
+export 'src/keyboard_repeat.dart';
+export 'src/latency.dart';
+export 'src/logging.dart';
+export 'src/ready.dart';
+export 'src/screensaver.dart';
+export 'src/text_toolbar.dart';
+export 'src/tray.dart';
+export 'src/window.dart';
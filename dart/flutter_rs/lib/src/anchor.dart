@@ -0,0 +1,110 @@
+import 'dart:ui' show Offset;
+
+/// Handle of a widget rect, see [FlutterRsWindow.widgetRectToAnchor].
+///
+/// Tokens are invalidated when the size or the pixel ratio of their view
+/// changes (see [FlutterRsWindow.onAnchorsInvalidated]). Popups shown with a
+/// stale token fail with a `stale-anchor` error.
+class FlutterRsAnchorToken {
+  const FlutterRsAnchorToken(this.value);
+
+  final int value;
+
+  @override
+  bool operator ==(Object other) =>
+      other is FlutterRsAnchorToken && other.value == value;
+
+  @override
+  int get hashCode => value.hashCode;
+}
+
+/// Edge or corner of the anchor rect the popup is attached to.
+enum FlutterRsPopupAnchor {
+  none,
+  top,
+  bottom,
+  left,
+  right,
+  topLeft,
+  bottomLeft,
+  topRight,
+  bottomRight,
+}
+
+/// Direction the popup extends to from its anchor point.
+enum FlutterRsPopupGravity {
+  none,
+  top,
+  bottom,
+  left,
+  right,
+  topLeft,
+  bottomLeft,
+  topRight,
+  bottomRight,
+}
+
+/// How the compositor may move or resize a popup which would otherwise end
+/// up (partially) off screen.
+enum FlutterRsPopupConstraintAdjustment {
+  slideX,
+  slideY,
+  flipX,
+  flipY,
+  resizeX,
+  resizeY,
+}
+
+/// Placement of a popup relative to its anchor rect. The default places the
+/// popup centered above the rect, flipped below it when there isn't enough
+/// room.
+class FlutterRsPopupPlacement {
+  const FlutterRsPopupPlacement({
+    this.anchor = FlutterRsPopupAnchor.top,
+    this.gravity = FlutterRsPopupGravity.top,
+    this.constraintAdjustment = const [
+      FlutterRsPopupConstraintAdjustment.flipY,
+      FlutterRsPopupConstraintAdjustment.slideX,
+    ],
+    this.offset = const Offset(0, -8),
+  });
+
+  final FlutterRsPopupAnchor anchor;
+  final FlutterRsPopupGravity gravity;
+  final List<FlutterRsPopupConstraintAdjustment> constraintAdjustment;
+
+  /// Offset of the popup from its anchor point, in logical pixels.
+  final Offset offset;
+
+  Map<String, Object?> toJson() {
+    return {
+      'anchor': anchor.name,
+      'gravity': gravity.name,
+      'constraintAdjustment': [
+        for (final adjustment in constraintAdjustment) adjustment.name,
+      ],
+      'offset': {'x': offset.dx, 'y': offset.dy},
+    };
+  }
+}
+
+/// Tokens invalidated by a change of the geometry of a view.
+class FlutterRsAnchorsInvalidated {
+  const FlutterRsAnchorsInvalidated({
+    required this.viewId,
+    required this.tokens,
+  });
+
+  factory FlutterRsAnchorsInvalidated.fromJson(Map<String, Object?> json) {
+    return FlutterRsAnchorsInvalidated(
+      viewId: json['viewId']! as int,
+      tokens: [
+        for (final token in json['tokens']! as List<Object?>)
+          FlutterRsAnchorToken(token! as int),
+      ],
+    );
+  }
+
+  final int viewId;
+  final List<FlutterRsAnchorToken> tokens;
+}
@@ -0,0 +1,23 @@
+import 'dart:ui' show Color;
+
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Appearance preferences of the desktop which `MediaQuery` doesn't expose.
+/// See the `flutter-rs/appearance` channel.
+abstract final class FlutterRsAppearance {
+  static final _channel = FlutterRsChannel(AppearanceChannel.name);
+
+  /// `null` when the desktop has no accent color (e.g.: the portal doesn't
+  /// provide one), in which case apps should use their own seed color.
+  static Future<Color?> getAccentColor() async {
+    return _toColor(await _channel.invoke(AppearanceChannel.getAccentColor));
+  }
+
+  static Stream<Color?> get onAccentColorChanged =>
+      _channel.events(AppearanceChannel.onAccentColorChanged).map(_toColor);
+
+  static Color? _toColor(Object? value) {
+    return value == null ? null : Color(value as int);
+  }
+}
@@ -0,0 +1,20 @@
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Scales of the outputs the window touches, for picking resolution-aware
+/// assets. See the `flutter-rs/assets` channel.
+abstract final class FlutterRsAssets {
+  static final _channel = FlutterRsChannel(AssetsChannel.name);
+
+  /// Sorted, without duplicates.
+  static Future<List<double>> getScales() async {
+    return _toScales(await _channel.invoke(AssetsChannel.getScales));
+  }
+
+  static Stream<List<double>> get onScalesChanged =>
+      _channel.events(AssetsChannel.scalesChanged).map(_toScales);
+
+  static List<double> _toScales(Object? scales) {
+    return (scales as List<Object?>? ?? const []).map(toDouble).toList();
+  }
+}
@@ -0,0 +1,88 @@
+import 'dart:async';
+
+import 'package:flutter/foundation.dart';
+import 'package:flutter/services.dart';
+
+import 'channel.dart';
+
+/// Client of a state bridged by the embedder (see `flutter_plugins::bridge`),
+/// whose value is `null` until [load] completes, then kept up to date with
+/// the changes sent by the embedder.
+///
+/// The subclasses are generated along with the data class of the state by
+/// `flutter_plugins::bridge::generate_dart`.
+abstract class FlutterRsBridge<T> extends ValueNotifier<T?> {
+  FlutterRsBridge(String name)
+      : _channel = FlutterRsChannel(name),
+        _changes = EventChannel('$name/state', const JSONMethodCodec()),
+        super(null);
+
+  final FlutterRsChannel _channel;
+  final EventChannel _changes;
+  StreamSubscription<Object?>? _subscription;
+  Map<String, Object?>? _fields;
+  int _version = -1;
+
+  /// Changes received while the state is being fetched.
+  final List<Map<String, Object?>> _pending = [];
+
+  /// The state from its top-level [fields].
+  @protected
+  T decode(Map<String, Object?> fields);
+
+  /// Fetch the state, and start following its changes.
+  Future<T> load() async {
+    _subscription ??= _changes.receiveBroadcastStream().listen(_onChanged);
+
+    final result = toMap(await _channel.invoke<Object?>('getState'));
+    final version = result['version'] as int;
+    if (_fields == null || version > _version) {
+      _version = version;
+      _fields = Map.of(toMap(result['state']));
+    }
+
+    // The changes sent while the state was being fetched may be newer.
+    final pending = List.of(_pending);
+    _pending.clear();
+    pending.forEach(_apply);
+
+    value = decode(_fields!);
+    return value as T;
+  }
+
+  /// Run [command] in the embedder, with [args] as its arguments.
+  @protected
+  Future<Object?> invoke(String command, Map<String, Object?> args) {
+    return _channel.invoke<Object?>(command, args);
+  }
+
+  @override
+  void dispose() {
+    _subscription?.cancel();
+    super.dispose();
+  }
+
+  void _onChanged(Object? event) {
+    final changed = toMap(event);
+    if (_fields == null) {
+      _pending.add(changed);
+      return;
+    }
+
+    if (_apply(changed)) {
+      value = decode(_fields!);
+    }
+  }
+
+  /// Whether the changes were newer than the state.
+  bool _apply(Map<String, Object?> changed) {
+    final version = changed['version'] as int;
+    if (version <= _version) {
+      return false;
+    }
+
+    _version = version;
+    _fields!.addAll(toMap(changed['changes']));
+    return true;
+  }
+}
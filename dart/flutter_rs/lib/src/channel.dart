@@ -0,0 +1,46 @@
+import 'dart:async';
+import 'dart:ui' show Rect;
+
+import 'package:flutter/services.dart';
+
+/// Channel of a flutter-rs plugin. The methods invoked by the embedder are
+/// dispatched to broadcast streams, one per method.
+class FlutterRsChannel {
+  FlutterRsChannel(String name)
+      : _channel = MethodChannel(name, const JSONMethodCodec()) {
+    _channel.setMethodCallHandler(_handleMethodCall);
+  }
+
+  final MethodChannel _channel;
+  final Map<String, StreamController<Object?>> _events = {};
+
+  Future<T?> invoke<T>(String method, [Object? arguments]) {
+    return _channel.invokeMethod<T>(method, arguments);
+  }
+
+  /// Arguments of the calls of [method] by the embedder.
+  Stream<Object?> events(String method) {
+    return _events
+        .putIfAbsent(method, () => StreamController<Object?>.broadcast())
+        .stream;
+  }
+
+  Future<void> _handleMethodCall(MethodCall call) async {
+    _events[call.method]?.add(call.arguments);
+  }
+}
+
+double toDouble(Object? value) => (value as num).toDouble();
+
+Map<String, Object?> toMap(Object? value) =>
+    (value as Map<Object?, Object?>).cast<String, Object?>();
+
+/// [rect] as the `{x, y, width, height}` map of the channels.
+Map<String, Object?> rectToJson(Rect rect) {
+  return {
+    'x': rect.left,
+    'y': rect.top,
+    'width': rect.width,
+    'height': rect.height,
+  };
+}
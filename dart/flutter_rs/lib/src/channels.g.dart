@@ -0,0 +1,370 @@
+// Generated from flutter-plugins/src/contract.rs, do not edit.
+// Regenerate with:
+// `UPDATE_DART_CONTRACT=1 cargo test -p flutter-plugins contract`
+
+/// Contract of the `flutter-rs/appearance` channel.
+abstract final class AppearanceChannel {
+  static const String name = 'flutter-rs/appearance';
+
+  /// Arguments: `null`.
+  /// Result: `int?`.
+  static const String getAccentColor = 'getAccentColor';
+
+  /// Invoked by the embedder with `int?`.
+  static const String onAccentColorChanged = 'onAccentColorChanged';
+}
+
+/// Contract of the `flutter-rs/assets` channel.
+abstract final class AssetsChannel {
+  static const String name = 'flutter-rs/assets';
+
+  /// Arguments: `null`.
+  /// Result: `List<double>`.
+  static const String getScales = 'getScales';
+
+  /// Invoked by the embedder with `List<double>`.
+  static const String scalesChanged = 'scalesChanged';
+}
+
+/// Contract of the `flutter-rs/diagnostics` channel.
+abstract final class DiagnosticsChannel {
+  static const String name = 'flutter-rs/diagnostics';
+
+  /// Arguments: `null`.
+  /// Result: `List<Map<String, Object?>>`.
+  static const String getChannelStats = 'getChannelStats';
+
+  /// Arguments: `null`.
+  /// Result: `Map<String, Object?>`.
+  static const String getPointerStats = 'getPointerStats';
+
+  /// Arguments: `null`.
+  /// Result: `Object?`.
+  static const String getAccessibilityFeatures = 'getAccessibilityFeatures';
+
+  /// Arguments: `null`.
+  /// Result: `{mouseNaturalScroll: bool, touchpadNaturalScroll: bool, factor: double}?`.
+  static const String getScrollSettings = 'getScrollSettings';
+
+  /// Arguments: `null`.
+  /// Result: `List<{thread: String?, applied: List<String>, failed: List<String>}>`.
+  static const String getThreadPolicies = 'getThreadPolicies';
+
+  /// Arguments: `String?`.
+  /// Result: `void`.
+  static const String setChannelTracer = 'setChannelTracer';
+}
+
+/// Contract of the `flutter-rs/emulated_input` channel.
+abstract final class EmulatedInputChannel {
+  static const String name = 'flutter-rs/emulated_input';
+
+  /// Arguments: `null`.
+  /// Result: `{isAvailable: bool, permission: String, pointer: bool, keyboard: bool, canRestore: bool}`.
+  static const String getStatus = 'getStatus';
+
+  /// Arguments: `null`.
+  /// Result: `{isAvailable: bool, permission: String, pointer: bool, keyboard: bool, canRestore: bool}`.
+  static const String requestPermission = 'requestPermission';
+
+  /// Arguments: `{dx: double, dy: double}`.
+  /// Result: `void`.
+  static const String movePointer = 'movePointer';
+
+  /// Arguments: `{x: double, y: double}`.
+  /// Result: `void`.
+  static const String warpPointer = 'warpPointer';
+
+  /// Arguments: `{code: int, isPressed: bool}`.
+  /// Result: `void`.
+  static const String pointerButton = 'pointerButton';
+
+  /// Arguments: `{dx: double, dy: double}`.
+  /// Result: `void`.
+  static const String scroll = 'scroll';
+
+  /// Arguments: `{code: int, isPressed: bool}`.
+  /// Result: `void`.
+  static const String key = 'key';
+}
+
+/// Contract of the `flutter-rs/fonts` channel.
+abstract final class FontsChannel {
+  static const String name = 'flutter-rs/fonts';
+
+  /// Arguments: `null`.
+  /// Result: `List<String>`.
+  static const String getFontFamilies = 'getFontFamilies';
+
+  /// Arguments: `String`.
+  /// Result: `List<String>`.
+  static const String loadFontFamily = 'loadFontFamily';
+
+  /// Invoked by the embedder with `String`.
+  static const String onFontFamilyAdded = 'onFontFamilyAdded';
+}
+
+/// Contract of the `flutter-rs/keyboard_repeat` channel.
+abstract final class KeyboardRepeatChannel {
+  static const String name = 'flutter-rs/keyboard_repeat';
+
+  /// Arguments: `null`.
+  /// Result: `{enabled: bool, rate: int, delay: int}?`.
+  static const String getRepeatInfo = 'getRepeatInfo';
+
+  /// Invoked by the embedder with `{enabled: bool, rate: int, delay: int}`.
+  static const String onRepeatInfoChanged = 'onRepeatInfoChanged';
+}
+
+/// Contract of the `flutter-rs/latency` channel.
+abstract final class LatencyChannel {
+  static const String name = 'flutter-rs/latency';
+
+  /// Arguments: `int`.
+  /// Result: `void`.
+  static const String echo = 'echo';
+}
+
+/// Contract of the `flutter-rs/logging` channel.
+abstract final class LoggingChannel {
+  static const String name = 'flutter-rs/logging';
+
+  /// Arguments: `String`.
+  /// Result: `void`.
+  static const String setLogFilter = 'setLogFilter';
+
+  /// Arguments: `null`.
+  /// Result: `Object?`.
+  static const String dumpState = 'dumpState';
+}
+
+/// Contract of the `flutter-rs/ready` channel.
+abstract final class ReadyChannel {
+  static const String name = 'flutter-rs/ready';
+
+  /// Arguments: `null`.
+  /// Result: `bool`.
+  static const String extend = 'extend';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String ready = 'ready';
+}
+
+/// Contract of the `flutter-rs/screensaver` channel.
+abstract final class ScreensaverChannel {
+  static const String name = 'flutter-rs/screensaver';
+
+  /// Arguments: `bool`.
+  /// Result: `void`.
+  static const String setInhibited = 'setInhibited';
+
+  /// Arguments: `null`.
+  /// Result: `bool`.
+  static const String isInhibited = 'isInhibited';
+}
+
+/// Contract of the `flutter-rs/text_toolbar` channel.
+abstract final class TextToolbarChannel {
+  static const String name = 'flutter-rs/text_toolbar';
+
+  /// Arguments: `{rect: {x: double, y: double, width: double, height: double}?, anchor: int?, placement: {anchor: String, gravity: String, constraintAdjustment: List<String>, offset: {x: double, y: double}}?, actions: List<String>}`.
+  /// Result: `void`.
+  static const String show = 'show';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String hide = 'hide';
+
+  /// Invoked by the embedder with `String`.
+  static const String onAction = 'onAction';
+
+  /// Invoked by the embedder with `null`.
+  static const String onDismissed = 'onDismissed';
+}
+
+/// Contract of the `flutter-rs/tray` channel.
+abstract final class TrayChannel {
+  static const String name = 'flutter-rs/tray';
+
+  /// Arguments: `null`.
+  /// Result: `bool`.
+  static const String isAvailable = 'isAvailable';
+
+  /// Arguments: `{title: String, iconName: String?, iconPath: String?, tooltip: String?, status: String, menu: List<{id: int, label: String, isEnabled: bool, isSeparator: bool, isChecked: bool?, children: List<Map>}>}`.
+  /// Result: `void`.
+  static const String show = 'show';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String hide = 'hide';
+
+  /// Invoked by the embedder with `{x: int, y: int}`.
+  static const String onActivate = 'onActivate';
+
+  /// Invoked by the embedder with `{x: int, y: int}`.
+  static const String onSecondaryActivate = 'onSecondaryActivate';
+
+  /// Invoked by the embedder with `{delta: int, orientation: String}`.
+  static const String onScroll = 'onScroll';
+
+  /// Invoked by the embedder with `int`.
+  static const String onMenuItemClicked = 'onMenuItemClicked';
+}
+
+/// Contract of the `flutter-rs/window` channel.
+abstract final class WindowChannel {
+  static const String name = 'flutter-rs/window';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String maximize = 'maximize';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String iconify = 'iconify';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String restore = 'restore';
+
+  /// Arguments: `null`.
+  /// Result: `bool`.
+  static const String isMaximized = 'isMaximized';
+
+  /// Arguments: `null`.
+  /// Result: `bool`.
+  static const String isIconified = 'isIconified';
+
+  /// Arguments: `null`.
+  /// Result: `bool`.
+  static const String isVisible = 'isVisible';
+
+  /// Arguments: `null`.
+  /// Result: `bool`.
+  static const String isFullscreen = 'isFullscreen';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String show = 'show';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String hide = 'hide';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String close = 'close';
+
+  /// Arguments: `{x: double, y: double}`.
+  /// Result: `void`.
+  static const String setPos = 'set_pos';
+
+  /// Arguments: `null`.
+  /// Result: `{x: double, y: double}`.
+  static const String getPos = 'get_pos';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String startDrag = 'start_drag';
+
+  /// Arguments: `null`.
+  /// Result: `void`.
+  static const String endDrag = 'end_drag';
+
+  /// Arguments: `null`.
+  /// Result: `{width: double, height: double}?`.
+  static const String getRecommendedMaxSize = 'getRecommendedMaxSize';
+
+  /// Arguments: `int`.
+  /// Result: `void`.
+  static const String setExclusiveZone = 'setExclusiveZone';
+
+  /// Arguments: `{top: int, right: int, bottom: int, left: int}`.
+  /// Result: `void`.
+  static const String setMargin = 'setMargin';
+
+  /// Arguments: `null`.
+  /// Result: `double`.
+  static const String getZoom = 'getZoom';
+
+  /// Arguments: `double`.
+  /// Result: `void`.
+  static const String setZoom = 'setZoom';
+
+  /// Arguments: `null`.
+  /// Result: `bool`.
+  static const String isTransparencyReduced = 'isTransparencyReduced';
+
+  /// Arguments: `bool`.
+  /// Result: `void`.
+  static const String setUrgent = 'setUrgent';
+
+  /// Arguments: `null`.
+  /// Result: `{transparency: bool, alwaysOnTop: bool, skipTaskbar: bool, minMaxSize: bool, maximize: bool, fullscreen: bool, position: bool}`.
+  static const String getCapabilities = 'getCapabilities';
+
+  /// Arguments: `null`.
+  /// Result: `{isWideGamut: bool, approxGamut: String}`.
+  static const String getColorInfo = 'getColorInfo';
+
+  /// Arguments: `null`.
+  /// Result: `String`.
+  static const String getVisibility = 'getVisibility';
+
+  /// Arguments: `bool`.
+  /// Result: `void`.
+  static const String setAlwaysOnTop = 'setAlwaysOnTop';
+
+  /// Arguments: `{width: double, height: double}?`.
+  /// Result: `void`.
+  static const String setMinSize = 'setMinSize';
+
+  /// Arguments: `{width: double, height: double}?`.
+  /// Result: `void`.
+  static const String setMaxSize = 'setMaxSize';
+
+  /// Arguments: `bool`.
+  /// Result: `void`.
+  static const String setFullscreen = 'setFullscreen';
+
+  /// Arguments: `null`.
+  /// Result: `int`.
+  static const String getWindowId = 'getWindowId';
+
+  /// Arguments: `int`.
+  /// Result: `bool`.
+  static const String raiseAbove = 'raiseAbove';
+
+  /// Arguments: `int`.
+  /// Result: `bool`.
+  static const String lowerBelow = 'lowerBelow';
+
+  /// Arguments: `{viewId: int, rect: {x: double, y: double, width: double, height: double}}`.
+  /// Result: `int`.
+  static const String widgetRectToAnchor = 'widgetRectToAnchor';
+
+  /// Invoked by the embedder with `{width: double, height: double}?`.
+  static const String onRecommendedMaxSizeChanged = 'onRecommendedMaxSizeChanged';
+
+  /// Invoked by the embedder with `{maximized: bool, fullscreen: bool, tiled: bool, initiator: String}`.
+  static const String onStateChanged = 'onStateChanged';
+
+  /// Invoked by the embedder with `double`.
+  static const String onZoomChanged = 'onZoomChanged';
+
+  /// Invoked by the embedder with `bool`.
+  static const String onTransparencyReducedChanged = 'onTransparencyReducedChanged';
+
+  /// Invoked by the embedder with `{isWideGamut: bool, approxGamut: String}`.
+  static const String onColorInfoChanged = 'onColorInfoChanged';
+
+  /// Invoked by the embedder with `String`.
+  static const String onVisibilityChanged = 'onVisibilityChanged';
+
+  /// Invoked by the embedder with `{viewId: int, tokens: List<int>}`.
+  static const String onAnchorsInvalidated = 'onAnchorsInvalidated';
+
+  /// Invoked by the embedder with `{order: List<int>, outcome: String}`.
+  static const String onStackingChanged = 'onStackingChanged';
+}
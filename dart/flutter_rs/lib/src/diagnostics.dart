@@ -0,0 +1,53 @@
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Embedder diagnostics, see the `flutter-rs/diagnostics` channel.
+abstract final class FlutterRsDiagnostics {
+  static final _channel = FlutterRsChannel(DiagnosticsChannel.name);
+
+  /// Counters of the channels traced so far, see [setChannelTracer]. Event
+  /// channels also report their flow counters (e.g.: the dropped events)
+  /// under `events`.
+  static Future<List<Map<String, Object?>>> getChannelStats() async {
+    final stats = await _channel.invoke<List<Object?>>(
+      DiagnosticsChannel.getChannelStats,
+    );
+    return (stats ?? const []).map(toMap).toList();
+  }
+
+  /// Counters of the pointer events, including the dropped ones.
+  static Future<Map<String, Object?>> getPointerStats() async {
+    return toMap(await _channel.invoke(DiagnosticsChannel.getPointerStats));
+  }
+
+  /// Accessibility features last sent to the engine.
+  static Future<Object?> getAccessibilityFeatures() {
+    return _channel.invoke(DiagnosticsChannel.getAccessibilityFeatures);
+  }
+
+  /// Scroll settings applied to the pointer events, or `null` when the
+  /// embedder doesn't convert them.
+  static Future<Map<String, Object?>?> getScrollSettings() async {
+    final settings = await _channel.invoke<Object?>(
+      DiagnosticsChannel.getScrollSettings,
+    );
+    return settings == null ? null : toMap(settings);
+  }
+
+  /// What the thread policies of the embedder changed on the engine threads
+  /// (`thread` is `ui`, `raster` or `io`), and the settings which could not
+  /// be applied (`failed`), e.g. for lack of permission. Empty unless the
+  /// embedder configured thread policies.
+  static Future<List<Map<String, Object?>>> getThreadPolicies() async {
+    final reports = await _channel.invoke<List<Object?>>(
+      DiagnosticsChannel.getThreadPolicies,
+    );
+    return (reports ?? const []).map(toMap).toList();
+  }
+
+  /// Trace the channels matching [filter] (e.g.: `flutter/*`), or disable
+  /// tracing with `null`.
+  static Future<void> setChannelTracer(String? filter) {
+    return _channel.invoke(DiagnosticsChannel.setChannelTracer, filter);
+  }
+}
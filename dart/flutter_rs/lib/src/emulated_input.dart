@@ -0,0 +1,101 @@
+import 'channel.dart';
+import 'channels.g.dart';
+
+enum FlutterRsEmulatedInputPermission { notDetermined, granted, denied }
+
+class FlutterRsEmulatedInputStatus {
+  const FlutterRsEmulatedInputStatus({
+    required this.isAvailable,
+    required this.permission,
+    required this.pointer,
+    required this.keyboard,
+    required this.canRestore,
+  });
+
+  factory FlutterRsEmulatedInputStatus.fromJson(Map<String, Object?> json) {
+    return FlutterRsEmulatedInputStatus(
+      isAvailable: json['isAvailable']! as bool,
+      permission: FlutterRsEmulatedInputPermission.values
+          .byName(json['permission']! as String),
+      pointer: json['pointer']! as bool,
+      keyboard: json['keyboard']! as bool,
+      canRestore: json['canRestore']! as bool,
+    );
+  }
+
+  /// Whether the compositor offers input emulation at all.
+  final bool isAvailable;
+  final FlutterRsEmulatedInputPermission permission;
+
+  /// Whether the user granted the emulation of a pointer.
+  final bool pointer;
+
+  /// Whether the user granted the emulation of a keyboard.
+  final bool keyboard;
+
+  /// Whether a granted permission is remembered across restarts.
+  final bool canRestore;
+}
+
+/// Moves the pointer and injects input events once the user granted the
+/// permission. See the `flutter-rs/emulated_input` channel.
+///
+/// The methods injecting events throw a `PlatformException` whose code tells
+/// why the event couldn't be injected (e.g.: `permission-required`).
+abstract final class FlutterRsEmulatedInput {
+  static final _channel = FlutterRsChannel(EmulatedInputChannel.name);
+
+  static Future<FlutterRsEmulatedInputStatus> getStatus() {
+    return _invokeStatus(EmulatedInputChannel.getStatus);
+  }
+
+  /// Completes once the user accepted or dismissed the request.
+  static Future<FlutterRsEmulatedInputStatus> requestPermission() {
+    return _invokeStatus(EmulatedInputChannel.requestPermission);
+  }
+
+  /// Move the pointer by ([dx], [dy]) logical pixels.
+  static Future<void> movePointer(double dx, double dy) {
+    return _channel.invoke(
+      EmulatedInputChannel.movePointer,
+      {'dx': dx, 'dy': dy},
+    );
+  }
+
+  /// Move the pointer to ([x], [y]) in the logical coordinates of the view.
+  /// Throws with the `pointer-outside` code while the pointer isn't within a
+  /// window of the app, as its position is unknown then.
+  static Future<void> warpPointer(double x, double y) {
+    return _channel.invoke(
+      EmulatedInputChannel.warpPointer,
+      {'x': x, 'y': y},
+    );
+  }
+
+  /// Press or release the evdev [button] (e.g.: `0x110` for the left one).
+  static Future<void> pointerButton(int button, {required bool isPressed}) {
+    return _channel.invoke(
+      EmulatedInputChannel.pointerButton,
+      {'code': button, 'isPressed': isPressed},
+    );
+  }
+
+  static Future<void> scroll(double dx, double dy) {
+    return _channel.invoke(EmulatedInputChannel.scroll, {'dx': dx, 'dy': dy});
+  }
+
+  /// Press or release the evdev [keycode].
+  static Future<void> key(int keycode, {required bool isPressed}) {
+    return _channel.invoke(
+      EmulatedInputChannel.key,
+      {'code': keycode, 'isPressed': isPressed},
+    );
+  }
+
+  static Future<FlutterRsEmulatedInputStatus> _invokeStatus(
+    String method,
+  ) async {
+    final status = await _channel.invoke(method);
+    return FlutterRsEmulatedInputStatus.fromJson(toMap(status));
+  }
+}
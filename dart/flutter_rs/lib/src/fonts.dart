@@ -0,0 +1,48 @@
+import 'dart:async';
+import 'dart:convert';
+import 'dart:typed_data';
+
+import 'package:flutter/services.dart';
+
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Fonts provided by the embedder outside of the asset bundle (e.g.:
+/// downloaded by the app). See the `flutter-rs/fonts` channel.
+abstract final class FlutterRsFonts {
+  static final _channel = FlutterRsChannel(FontsChannel.name);
+  static StreamSubscription<String>? _subscription;
+
+  static Future<List<String>> getFontFamilies() async {
+    final families = await _channel.invoke<List<Object?>>(
+      FontsChannel.getFontFamilies,
+    );
+    return (families ?? const []).cast<String>();
+  }
+
+  /// Load the fonts of [family], after which text using the family renders
+  /// with them.
+  static Future<void> loadFontFamily(String family) async {
+    final fonts = await _channel.invoke<List<Object?>>(
+      FontsChannel.loadFontFamily,
+      family,
+    );
+
+    final loader = FontLoader(family);
+    for (final font in fonts ?? const []) {
+      final bytes = base64Decode(font! as String);
+      loader.addFont(Future.value(ByteData.sublistView(bytes)));
+    }
+    await loader.load();
+  }
+
+  /// Load all the font families, and the ones added later on.
+  static Future<void> loadAll() async {
+    _subscription ??= onFontFamilyAdded.listen(loadFontFamily);
+    await Future.wait((await getFontFamilies()).map(loadFontFamily));
+  }
+
+  static Stream<String> get onFontFamilyAdded => _channel
+      .events(FontsChannel.onFontFamilyAdded)
+      .map((family) => family! as String);
+}
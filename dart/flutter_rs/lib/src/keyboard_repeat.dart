@@ -0,0 +1,42 @@
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Key repeat settings of the seat.
+class FlutterRsKeyRepeatInfo {
+  const FlutterRsKeyRepeatInfo({
+    required this.enabled,
+    required this.rate,
+    required this.delay,
+  });
+
+  factory FlutterRsKeyRepeatInfo.fromJson(Map<String, Object?> json) {
+    return FlutterRsKeyRepeatInfo(
+      enabled: json['enabled']! as bool,
+      rate: json['rate']! as int,
+      delay: Duration(milliseconds: json['delay']! as int),
+    );
+  }
+
+  final bool enabled;
+
+  /// Number of repetitions per second.
+  final int rate;
+
+  /// Delay before a held key starts repeating.
+  final Duration delay;
+}
+
+/// See the `flutter-rs/keyboard_repeat` channel.
+abstract final class FlutterRsKeyboardRepeat {
+  static final _channel = FlutterRsChannel(KeyboardRepeatChannel.name);
+
+  /// `null` until the settings are advertised by the platform.
+  static Future<FlutterRsKeyRepeatInfo?> getRepeatInfo() async {
+    final info = await _channel.invoke(KeyboardRepeatChannel.getRepeatInfo);
+    return info == null ? null : FlutterRsKeyRepeatInfo.fromJson(toMap(info));
+  }
+
+  static Stream<FlutterRsKeyRepeatInfo> get onRepeatInfoChanged => _channel
+      .events(KeyboardRepeatChannel.onRepeatInfoChanged)
+      .map((info) => FlutterRsKeyRepeatInfo.fromJson(toMap(info)));
+}
@@ -0,0 +1,11 @@
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Latency probe, when the embedder was built with the `latency-probe`
+/// feature. See the `flutter-rs/latency` channel.
+abstract final class FlutterRsLatency {
+  static final _channel = FlutterRsChannel(LatencyChannel.name);
+
+  /// Report that the probe event [id] reached Dart.
+  static Future<void> echo(int id) => _channel.invoke(LatencyChannel.echo, id);
+}
@@ -0,0 +1,17 @@
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Logging of the embedder, see the `flutter-rs/logging` channel.
+abstract final class FlutterRsLogging {
+  static final _channel = FlutterRsChannel(LoggingChannel.name);
+
+  /// Replace the log filter of the embedder (e.g.: `flutter_sctk=debug`).
+  static Future<void> setLogFilter(String filter) {
+    return _channel.invoke(LoggingChannel.setLogFilter, filter);
+  }
+
+  /// State of the embedder, also written to its log.
+  static Future<Object?> dumpState() {
+    return _channel.invoke(LoggingChannel.dumpState);
+  }
+}
@@ -0,0 +1,23 @@
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Tells the embedder when the app is ready for user input. See the
+/// `flutter-rs/ready` channel.
+///
+/// The input received before the first frame is buffered and replayed
+/// afterwards (see `ApplicationBuilder::with_gate_input_until_first_frame`),
+/// so that it doesn't reach the app before its channel handlers are
+/// registered.
+abstract final class FlutterRsReady {
+  static final _channel = FlutterRsChannel(ReadyChannel.name);
+
+  /// Keep buffering the input past the first frame, until [ready] is called
+  /// (or the gating times out). Returns `false` when the input is no longer
+  /// gated.
+  static Future<bool> extend() async {
+    return await _channel.invoke<bool>(ReadyChannel.extend) ?? false;
+  }
+
+  /// Replay the input buffered so far, and stop gating it.
+  static Future<void> ready() => _channel.invoke(ReadyChannel.ready);
+}
@@ -0,0 +1,16 @@
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Prevents the screen from blanking (e.g.: during media playback). See the
+/// `flutter-rs/screensaver` channel.
+abstract final class FlutterRsScreenSaver {
+  static final _channel = FlutterRsChannel(ScreensaverChannel.name);
+
+  static Future<void> setInhibited(bool inhibited) {
+    return _channel.invoke(ScreensaverChannel.setInhibited, inhibited);
+  }
+
+  static Future<bool> isInhibited() async {
+    return await _channel.invoke<bool>(ScreensaverChannel.isInhibited) ?? false;
+  }
+}
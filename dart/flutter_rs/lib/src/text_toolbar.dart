@@ -0,0 +1,53 @@
+import 'dart:ui' show Rect;
+
+import 'anchor.dart';
+import 'channel.dart';
+import 'channels.g.dart';
+
+enum FlutterRsTextToolbarAction { cut, copy, paste, selectAll }
+
+/// Text selection toolbar presented by the compositor (e.g.: as a popup
+/// outside of the window). See the `flutter-rs/text_toolbar` channel.
+abstract final class FlutterRsTextToolbar {
+  static final _channel = FlutterRsChannel(TextToolbarChannel.name);
+
+  /// Show the toolbar anchored to [rect] (in logical pixels relative to the
+  /// view). Replaces any toolbar that is already visible.
+  static Future<void> show(
+    Rect rect,
+    List<FlutterRsTextToolbarAction> actions, {
+    FlutterRsPopupPlacement placement = const FlutterRsPopupPlacement(),
+  }) {
+    return _channel.invoke(TextToolbarChannel.show, {
+      'rect': rectToJson(rect),
+      'placement': placement.toJson(),
+      'actions': [for (final action in actions) action.name],
+    });
+  }
+
+  /// Show the toolbar anchored to the widget rect of [anchor]. Fails with a
+  /// `stale-anchor` error if the token has been invalidated.
+  static Future<void> showAnchored(
+    FlutterRsAnchorToken anchor,
+    List<FlutterRsTextToolbarAction> actions, {
+    FlutterRsPopupPlacement placement = const FlutterRsPopupPlacement(),
+  }) {
+    return _channel.invoke(TextToolbarChannel.show, {
+      'anchor': anchor.value,
+      'placement': placement.toJson(),
+      'actions': [for (final action in actions) action.name],
+    });
+  }
+
+  static Future<void> hide() => _channel.invoke(TextToolbarChannel.hide);
+
+  /// The toolbar is dismissed once an action was picked.
+  static Stream<FlutterRsTextToolbarAction> get onAction =>
+      _channel.events(TextToolbarChannel.onAction).map(
+            (action) =>
+                FlutterRsTextToolbarAction.values.byName(action! as String),
+          );
+
+  static Stream<void> get onDismissed =>
+      _channel.events(TextToolbarChannel.onDismissed);
+}
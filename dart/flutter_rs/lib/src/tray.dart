@@ -0,0 +1,120 @@
+import 'dart:ui' show Offset;
+
+import 'channel.dart';
+import 'channels.g.dart';
+
+enum FlutterRsTrayStatus { passive, active, needsAttention }
+
+enum FlutterRsTrayScrollOrientation { horizontal, vertical }
+
+class FlutterRsTrayMenuItem {
+  const FlutterRsTrayMenuItem({
+    required this.id,
+    required this.label,
+    this.isEnabled = true,
+    this.isChecked,
+    this.children = const [],
+  }) : isSeparator = false;
+
+  const FlutterRsTrayMenuItem.separator({required this.id})
+      : label = '',
+        isEnabled = true,
+        isSeparator = true,
+        isChecked = null,
+        children = const [];
+
+  /// Reported by [FlutterRsTray.onMenuItemClicked]. Must be positive and
+  /// unique within the menu.
+  final int id;
+  final String label;
+  final bool isEnabled;
+  final bool isSeparator;
+
+  /// Whether the item is checked, `null` for the items which can't be.
+  final bool? isChecked;
+
+  /// Items of the submenu opened by this item.
+  final List<FlutterRsTrayMenuItem> children;
+
+  Map<String, Object?> toJson() {
+    return {
+      'id': id,
+      'label': label,
+      'isEnabled': isEnabled,
+      'isSeparator': isSeparator,
+      'isChecked': isChecked,
+      'children': [for (final child in children) child.toJson()],
+    };
+  }
+}
+
+class FlutterRsTrayScroll {
+  const FlutterRsTrayScroll({required this.delta, required this.orientation});
+
+  final int delta;
+  final FlutterRsTrayScrollOrientation orientation;
+}
+
+/// Status icon shown in the system tray, with a menu. See the
+/// `flutter-rs/tray` channel.
+///
+/// Combined with `ApplicationBuilder::with_start_hidden`, it allows for apps
+/// which only show their window once the icon is clicked.
+abstract final class FlutterRsTray {
+  static final _channel = FlutterRsChannel(TrayChannel.name);
+
+  /// Whether a tray is running, which shows the icon.
+  static Future<bool> isAvailable() async {
+    return await _channel.invoke<bool>(TrayChannel.isAvailable) ?? false;
+  }
+
+  /// Show the icon, or update it if it's already shown. Either [iconName]
+  /// (from the icon theme) or [iconPath] (a PNG file) is required.
+  static Future<void> show({
+    required String title,
+    String? iconName,
+    String? iconPath,
+    String? tooltip,
+    FlutterRsTrayStatus status = FlutterRsTrayStatus.active,
+    List<FlutterRsTrayMenuItem> menu = const [],
+  }) {
+    return _channel.invoke(TrayChannel.show, {
+      'title': title,
+      'iconName': iconName,
+      'iconPath': iconPath,
+      'tooltip': tooltip,
+      'status': status.name,
+      'menu': [for (final item in menu) item.toJson()],
+    });
+  }
+
+  static Future<void> hide() => _channel.invoke(TrayChannel.hide);
+
+  /// The icon was clicked, at the given screen position if known (or at the
+  /// origin).
+  static Stream<Offset> get onActivate =>
+      _channel.events(TrayChannel.onActivate).map(_toOffset);
+
+  /// The icon was middle-clicked.
+  static Stream<Offset> get onSecondaryActivate =>
+      _channel.events(TrayChannel.onSecondaryActivate).map(_toOffset);
+
+  static Stream<FlutterRsTrayScroll> get onScroll =>
+      _channel.events(TrayChannel.onScroll).map((scroll) {
+        final json = toMap(scroll);
+        return FlutterRsTrayScroll(
+          delta: json['delta']! as int,
+          orientation: FlutterRsTrayScrollOrientation.values
+              .byName(json['orientation']! as String),
+        );
+      });
+
+  /// Id of the clicked menu item.
+  static Stream<int> get onMenuItemClicked =>
+      _channel.events(TrayChannel.onMenuItemClicked).map((id) => id! as int);
+
+  static Offset _toOffset(Object? position) {
+    final json = toMap(position);
+    return Offset(toDouble(json['x']), toDouble(json['y']));
+  }
+}
@@ -0,0 +1,396 @@
+import 'dart:ui' show Offset, Rect, Size;
+
+import 'anchor.dart';
+import 'channel.dart';
+import 'channels.g.dart';
+
+/// Who changed the window state.
+enum FlutterRsWindowStateInitiator {
+  /// The app, through [FlutterRsWindow].
+  app,
+
+  /// The compositor, on its own or on behalf of the user.
+  compositor,
+}
+
+/// State of the window reported through [FlutterRsWindow.onStateChanged].
+class FlutterRsWindowState {
+  const FlutterRsWindowState({
+    required this.maximized,
+    required this.fullscreen,
+    required this.tiled,
+    required this.initiator,
+  });
+
+  factory FlutterRsWindowState.fromJson(Map<String, Object?> json) {
+    return FlutterRsWindowState(
+      maximized: json['maximized']! as bool,
+      fullscreen: json['fullscreen']! as bool,
+      tiled: json['tiled']! as bool,
+      initiator: FlutterRsWindowStateInitiator.values.byName(
+        json['initiator']! as String,
+      ),
+    );
+  }
+
+  final bool maximized;
+  final bool fullscreen;
+
+  /// Tiled along at least one edge (e.g.: snapped to half of the screen).
+  final bool tiled;
+  final FlutterRsWindowStateInitiator initiator;
+}
+
+/// Features of the window supported by the embedder, see
+/// [FlutterRsWindow.getCapabilities].
+class FlutterRsWindowCapabilities {
+  const FlutterRsWindowCapabilities({
+    required this.transparency,
+    required this.alwaysOnTop,
+    required this.skipTaskbar,
+    required this.minMaxSize,
+    required this.maximize,
+    required this.fullscreen,
+    required this.position,
+  });
+
+  factory FlutterRsWindowCapabilities.fromJson(Map<String, Object?> json) {
+    return FlutterRsWindowCapabilities(
+      transparency: json['transparency']! as bool,
+      alwaysOnTop: json['alwaysOnTop']! as bool,
+      skipTaskbar: json['skipTaskbar']! as bool,
+      minMaxSize: json['minMaxSize']! as bool,
+      maximize: json['maximize']! as bool,
+      fullscreen: json['fullscreen']! as bool,
+      position: json['position']! as bool,
+    );
+  }
+
+  /// The content is composited over what is behind the window.
+  final bool transparency;
+
+  /// [FlutterRsWindow.setAlwaysOnTop].
+  final bool alwaysOnTop;
+
+  /// The window is kept out of the taskbar, which is only configured by the
+  /// embedder when creating the window.
+  final bool skipTaskbar;
+
+  /// [FlutterRsWindow.setMinSize] and [FlutterRsWindow.setMaxSize].
+  final bool minMaxSize;
+
+  /// [FlutterRsWindow.maximize] and [FlutterRsWindow.restore].
+  final bool maximize;
+
+  /// [FlutterRsWindow.setFullscreen].
+  final bool fullscreen;
+
+  /// [FlutterRsWindow.setPosition] and [FlutterRsWindow.getPosition].
+  final bool position;
+}
+
+/// Well-known color space closest to the gamut of a display.
+enum FlutterRsColorGamut {
+  /// The windowing system doesn't describe the displays.
+  unknown,
+  srgb,
+  displayP3,
+  adobeRgb,
+  bt2020,
+}
+
+/// Color characteristics of the display(s) the window is shown on, see
+/// [FlutterRsWindow.getColorInfo].
+///
+/// The content is always rendered in sRGB, this is only a hint (e.g.: for
+/// telling the user that colors are approximated).
+class FlutterRsColorInfo {
+  const FlutterRsColorInfo({
+    required this.isWideGamut,
+    required this.approxGamut,
+  });
+
+  factory FlutterRsColorInfo.fromJson(Map<String, Object?> json) {
+    return FlutterRsColorInfo(
+      isWideGamut: json['isWideGamut']! as bool,
+      approxGamut: FlutterRsColorGamut.values.byName(
+        json['approxGamut']! as String,
+      ),
+    );
+  }
+
+  final bool isWideGamut;
+  final FlutterRsColorGamut approxGamut;
+}
+
+/// Whether the window is seen by the user, see
+/// [FlutterRsWindow.getVisibility].
+///
+/// The windowing system rarely tells whether a window is covered by others,
+/// so this is an estimate, whose changes are reported with some delay.
+enum FlutterRsWindowVisibility {
+  visible,
+
+  /// The window is likely covered by others, at least partly.
+  partiallyObscuredLikely,
+
+  /// The window is minimized, unmapped, or fully covered by others.
+  hidden,
+}
+
+/// What caused [FlutterRsStacking] to be reported.
+enum FlutterRsStackingOutcome {
+  /// A [FlutterRsWindow.raiseAbove] or [FlutterRsWindow.lowerBelow] request
+  /// was applied.
+  applied,
+
+  /// The windows were already stacked as requested.
+  unchanged,
+
+  /// The compositor didn't raise the window (e.g.: without any recent user
+  /// input).
+  refused,
+
+  /// The windows can't be stacked as requested (e.g.: an attached window
+  /// below its parent).
+  unsupported,
+
+  /// The compositor restacked the windows, on its own or on behalf of the
+  /// user.
+  compositor,
+}
+
+/// Stacking order of the windows of the app, reported through
+/// [FlutterRsWindow.onStackingChanged].
+class FlutterRsStacking {
+  const FlutterRsStacking({required this.order, required this.outcome});
+
+  factory FlutterRsStacking.fromJson(Map<String, dynamic> json) {
+    return FlutterRsStacking(
+      order: [for (final id in json['order'] as List) id as int],
+      outcome: FlutterRsStackingOutcome.values.byName(
+        json['outcome'] as String,
+      ),
+    );
+  }
+
+  /// Identifiers of the windows (see [FlutterRsWindow.getWindowId]), topmost
+  /// first.
+  final List<int> order;
+
+  final FlutterRsStackingOutcome outcome;
+}
+
+/// The window of the view, see the `flutter-rs/window` channel.
+abstract final class FlutterRsWindow {
+  static final _channel = FlutterRsChannel(WindowChannel.name);
+
+  static Future<void> maximize() => _channel.invoke(WindowChannel.maximize);
+
+  static Future<void> iconify() => _channel.invoke(WindowChannel.iconify);
+
+  static Future<void> restore() => _channel.invoke(WindowChannel.restore);
+
+  static Future<void> show() => _channel.invoke(WindowChannel.show);
+
+  static Future<void> hide() => _channel.invoke(WindowChannel.hide);
+
+  static Future<void> close() => _channel.invoke(WindowChannel.close);
+
+  static Future<bool> isMaximized() => _getBool(WindowChannel.isMaximized);
+
+  static Future<bool> isIconified() => _getBool(WindowChannel.isIconified);
+
+  static Future<bool> isVisible() => _getBool(WindowChannel.isVisible);
+
+  static Future<bool> isFullscreen() => _getBool(WindowChannel.isFullscreen);
+
+  static Future<Offset> getPosition() async {
+    final position = toMap(await _channel.invoke(WindowChannel.getPos));
+    return Offset(toDouble(position['x']), toDouble(position['y']));
+  }
+
+  static Future<void> setPosition(Offset position) {
+    return _channel.invoke(
+      WindowChannel.setPos,
+      {'x': position.dx, 'y': position.dy},
+    );
+  }
+
+  static Future<void> startDrag() => _channel.invoke(WindowChannel.startDrag);
+
+  static Future<void> endDrag() => _channel.invoke(WindowChannel.endDrag);
+
+  /// Maximum size suggested by the compositor, if known.
+  static Future<Size?> getRecommendedMaxSize() async {
+    return _toSize(await _channel.invoke(WindowChannel.getRecommendedMaxSize));
+  }
+
+  /// Size (in logical pixels) of the area reserved by a layer shell surface.
+  static Future<void> setExclusiveZone(int exclusiveZone) {
+    return _channel.invoke(WindowChannel.setExclusiveZone, exclusiveZone);
+  }
+
+  /// Margin of a layer shell surface, in logical pixels.
+  static Future<void> setMargin({
+    int top = 0,
+    int right = 0,
+    int bottom = 0,
+    int left = 0,
+  }) {
+    return _channel.invoke(
+      WindowChannel.setMargin,
+      {'top': top, 'right': right, 'bottom': bottom, 'left': left},
+    );
+  }
+
+  static Future<double> getZoom() async {
+    return toDouble(await _channel.invoke(WindowChannel.getZoom));
+  }
+
+  /// The zoom actually applied is reported through [onZoomChanged].
+  static Future<void> setZoom(double zoom) {
+    return _channel.invoke(WindowChannel.setZoom, zoom);
+  }
+
+  static Future<bool> isTransparencyReduced() {
+    return _getBool(WindowChannel.isTransparencyReduced);
+  }
+
+  /// Flags the window as needing the user's attention, until it gets the
+  /// focus or the flag is cleared. Ignored where unsupported.
+  static Future<void> setUrgent(bool urgent) {
+    return _channel.invoke(WindowChannel.setUrgent, urgent);
+  }
+
+  /// What the window supports given the embedder and the windowing system,
+  /// the other methods being ignored where unsupported.
+  static Future<FlutterRsWindowCapabilities> getCapabilities() async {
+    final capabilities = await _channel.invoke(WindowChannel.getCapabilities);
+    return FlutterRsWindowCapabilities.fromJson(toMap(capabilities));
+  }
+
+  static Future<FlutterRsColorInfo> getColorInfo() async {
+    final colorInfo = await _channel.invoke(WindowChannel.getColorInfo);
+    return FlutterRsColorInfo.fromJson(toMap(colorInfo));
+  }
+
+  /// Apps may pause expensive work (e.g.: animations or video decoding)
+  /// while their window isn't [FlutterRsWindowVisibility.visible].
+  static Future<FlutterRsWindowVisibility> getVisibility() async {
+    final visibility = await _channel.invoke<String>(
+      WindowChannel.getVisibility,
+    );
+    return FlutterRsWindowVisibility.values.byName(visibility!);
+  }
+
+  static Future<void> setAlwaysOnTop(bool alwaysOnTop) {
+    return _channel.invoke(WindowChannel.setAlwaysOnTop, alwaysOnTop);
+  }
+
+  /// In logical pixels, or unconstrained when `null`.
+  static Future<void> setMinSize(Size? size) {
+    return _channel.invoke(WindowChannel.setMinSize, _sizeToJson(size));
+  }
+
+  /// In logical pixels, or unconstrained when `null`.
+  static Future<void> setMaxSize(Size? size) {
+    return _channel.invoke(WindowChannel.setMaxSize, _sizeToJson(size));
+  }
+
+  static Future<void> setFullscreen(bool fullscreen) {
+    return _channel.invoke(WindowChannel.setFullscreen, fullscreen);
+  }
+
+  /// Identifier of the window among the ones of the app.
+  static Future<int> getWindowId() async {
+    return await _channel.invoke<int>(WindowChannel.getWindowId) ?? 0;
+  }
+
+  /// Stacks the window above the window [other]. Returns whether the request
+  /// is handled, in which case its outcome is reported through
+  /// [onStackingChanged].
+  static Future<bool> raiseAbove(int other) async {
+    return await _channel.invoke<bool>(WindowChannel.raiseAbove, other) ??
+        false;
+  }
+
+  /// Stacks the window below the window [other], see [raiseAbove].
+  static Future<bool> lowerBelow(int other) async {
+    return await _channel.invoke<bool>(WindowChannel.lowerBelow, other) ??
+        false;
+  }
+
+  /// Anchor for positioning popups (e.g.: [FlutterRsTextToolbar]) relative
+  /// to [rect], in logical pixels relative to the view [viewId].
+  static Future<FlutterRsAnchorToken> widgetRectToAnchor(
+    int viewId,
+    Rect rect,
+  ) async {
+    final token = await _channel.invoke<int>(
+      WindowChannel.widgetRectToAnchor,
+      {'viewId': viewId, 'rect': rectToJson(rect)},
+    );
+    return FlutterRsAnchorToken(token!);
+  }
+
+  static Stream<Size?> get onRecommendedMaxSizeChanged => _channel
+      .events(WindowChannel.onRecommendedMaxSizeChanged)
+      .map(_toSize);
+
+  /// Sent before the window metrics matching the new state.
+  static Stream<FlutterRsWindowState> get onStateChanged => _channel
+      .events(WindowChannel.onStateChanged)
+      .map((state) => FlutterRsWindowState.fromJson(toMap(state)));
+
+  static Stream<double> get onZoomChanged =>
+      _channel.events(WindowChannel.onZoomChanged).map(toDouble);
+
+  static Stream<bool> get onTransparencyReducedChanged => _channel
+      .events(WindowChannel.onTransparencyReducedChanged)
+      .map((reduced) => reduced! as bool);
+
+  /// Sent when the window moves to displays with other color
+  /// characteristics, or when those change.
+  static Stream<FlutterRsColorInfo> get onColorInfoChanged => _channel
+      .events(WindowChannel.onColorInfoChanged)
+      .map((colorInfo) => FlutterRsColorInfo.fromJson(toMap(colorInfo)));
+
+  static Stream<FlutterRsWindowVisibility> get onVisibilityChanged => _channel
+      .events(WindowChannel.onVisibilityChanged)
+      .map(
+        (visibility) =>
+            FlutterRsWindowVisibility.values.byName(visibility! as String),
+      );
+
+  /// Sent after the size or the pixel ratio of a view has changed, with the
+  /// tokens which must be created again.
+  static Stream<FlutterRsAnchorsInvalidated> get onAnchorsInvalidated =>
+      _channel.events(WindowChannel.onAnchorsInvalidated).map(
+            (invalidated) =>
+                FlutterRsAnchorsInvalidated.fromJson(toMap(invalidated)),
+          );
+
+  static Stream<FlutterRsStacking> get onStackingChanged => _channel
+      .events(WindowChannel.onStackingChanged)
+      .map((stacking) => FlutterRsStacking.fromJson(toMap(stacking)));
+
+  static Future<bool> _getBool(String method) async {
+    return await _channel.invoke<bool>(method) ?? false;
+  }
+
+  static Map<String, double>? _sizeToJson(Size? size) {
+    if (size == null) {
+      return null;
+    }
+    return {'width': size.width, 'height': size.height};
+  }
+
+  static Size? _toSize(Object? value) {
+    if (value == null) {
+      return null;
+    }
+    final size = toMap(value);
+    return Size(toDouble(size['width']), toDouble(size['height']));
+  }
+}
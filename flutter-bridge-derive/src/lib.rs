@@ -0,0 +1,402 @@
+//! `#[derive(ChannelBridge)]`, which implements the traits of
+//! `flutter_plugins::bridge` (see there for the protocol):
+//!
+//! - On structs with named fields, `BridgeState`. The struct takes a
+//!   `#[bridge(channel = "...")]` attribute, along with `commands = Type`
+//!   for the enum of its commands and `diff` for sending only the changed
+//!   fields.
+//! - On enums, `BridgeCommand`. The variants are either units or have named
+//!   fields, which are the arguments of the command. They may take a
+//!   `#[bridge(result = "DartType")]` attribute for the result sent back.
+//!
+//! The fields (of both) may take `#[bridge(rename = "...")]`, and
+//! `#[bridge(dart = "DartType")]` for the types which aren't mapped to a Dart
+//! type (they are `Object?` otherwise). Struct fields may also be skipped
+//! with `#[bridge(skip)]`.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Fields,
+    GenericArgument, LitStr, Path, PathArguments, Type,
+};
+
+#[proc_macro_derive(ChannelBridge, attributes(bridge))]
+pub fn derive_channel_bridge(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let expanded = match &input.data {
+        _ if !input.generics.params.is_empty() => Err(Error::new(
+            input.generics.span(),
+            "ChannelBridge doesn't support generic types",
+        )),
+        Data::Struct(_) => derive_state(&input),
+        Data::Enum(_) => derive_command(&input),
+        Data::Union(_) => Err(Error::new(
+            input.ident.span(),
+            "ChannelBridge is only supported on structs and enums",
+        )),
+    };
+    expanded.unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct StateAttributes {
+    channel: Option<LitStr>,
+    commands: Option<Path>,
+    diff: bool,
+}
+
+#[derive(Default)]
+struct ItemAttributes {
+    rename: Option<LitStr>,
+    dart: Option<LitStr>,
+    result: Option<LitStr>,
+    skip: bool,
+}
+
+fn state_attributes(attrs: &[Attribute]) -> syn::Result<StateAttributes> {
+    let mut attributes = StateAttributes::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bridge")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("channel") {
+                attributes.channel = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("commands") {
+                attributes.commands = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("diff") {
+                attributes.diff = true;
+            } else {
+                return Err(meta.error("expected `channel`, `commands` or `diff`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(attributes)
+}
+
+fn item_attributes(attrs: &[Attribute]) -> syn::Result<ItemAttributes> {
+    let mut attributes = ItemAttributes::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bridge")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                attributes.rename = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("dart") {
+                attributes.dart = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("result") {
+                attributes.result = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("skip") {
+                attributes.skip = true;
+            } else {
+                return Err(meta.error("expected `rename`, `dart`, `result` or `skip`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(attributes)
+}
+
+/// A named field, with its name on the channel and its Dart type.
+struct BridgeField {
+    ident: syn::Ident,
+    name: String,
+    dart_type: String,
+}
+
+impl BridgeField {
+    fn schema(&self) -> TokenStream2 {
+        let (name, dart_type) = (&self.name, &self.dart_type);
+        quote! {
+            ::flutter_plugins::bridge::BridgeField {
+                name: #name,
+                dart_type: #dart_type,
+            }
+        }
+    }
+}
+
+/// The fields of a struct or of a variant, which must be named, without the
+/// skipped ones.
+fn bridge_fields(fields: &Fields, span: proc_macro2::Span) -> syn::Result<Vec<BridgeField>> {
+    let named = match fields {
+        Fields::Named(named) => named.named.iter().collect(),
+        Fields::Unit => Vec::new(),
+        Fields::Unnamed(_) => {
+            return Err(Error::new(span, "ChannelBridge requires named fields"));
+        }
+    };
+
+    let mut bridge_fields = Vec::new();
+    for field in named {
+        let attributes = item_attributes(&field.attrs)?;
+        if attributes.result.is_some() {
+            return Err(Error::new(
+                field.span(),
+                "`result` is only allowed on variants",
+            ));
+        }
+        if attributes.skip {
+            continue;
+        }
+
+        let ident = field.ident.clone().expect("named field");
+        let name = attributes
+            .rename
+            .map(|rename| rename.value())
+            .unwrap_or_else(|| camel_case(&ident.to_string()));
+        let dart_type = match attributes.dart {
+            Some(dart) => dart.value(),
+            None => dart_type(&field.ty).unwrap_or_else(|| "Object?".to_string()),
+        };
+        bridge_fields.push(BridgeField {
+            ident,
+            name,
+            dart_type,
+        });
+    }
+    Ok(bridge_fields)
+}
+
+fn derive_state(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        unreachable!()
+    };
+
+    let attributes = state_attributes(&input.attrs)?;
+    let Some(channel) = attributes.channel else {
+        return Err(Error::new(
+            input.ident.span(),
+            "ChannelBridge requires #[bridge(channel = \"...\")] on structs",
+        ));
+    };
+    if matches!(data.fields, Fields::Unit) {
+        return Err(Error::new(
+            input.ident.span(),
+            "ChannelBridge requires named fields",
+        ));
+    }
+
+    let fields = bridge_fields(&data.fields, input.ident.span())?;
+    let field_schemas = fields.iter().map(BridgeField::schema);
+    let field_values = fields.iter().map(|field| {
+        let (ident, name) = (&field.ident, &field.name);
+        quote!(::flutter_plugins::bridge::encode_field(#name, &self.#ident))
+    });
+
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let commands = match attributes.commands {
+        Some(commands) => quote!(#commands),
+        None => quote!(::flutter_plugins::bridge::NoCommand),
+    };
+    let diff = attributes.diff;
+
+    Ok(quote! {
+        impl ::flutter_plugins::bridge::BridgeState for #ident {
+            type Command = #commands;
+
+            const SCHEMA: ::flutter_plugins::bridge::BridgeSchema =
+                ::flutter_plugins::bridge::BridgeSchema {
+                    name: #name,
+                    channel: #channel,
+                    fields: &[#(#field_schemas),*],
+                    commands: <#commands as ::flutter_plugins::bridge::BridgeCommand>::COMMANDS,
+                    diff: #diff,
+                };
+
+            fn to_fields(&self) -> ::std::vec::Vec<::flutter_plugins::bridge::Value> {
+                ::std::vec![#(#field_values),*]
+            }
+        }
+    })
+}
+
+fn derive_command(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        unreachable!()
+    };
+
+    let mut schemas = Vec::new();
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let attributes = item_attributes(&variant.attrs)?;
+        if attributes.dart.is_some() || attributes.skip {
+            return Err(Error::new(
+                variant.span(),
+                "expected `rename` or `result` on variants",
+            ));
+        }
+
+        let name = attributes
+            .rename
+            .map(|rename| rename.value())
+            .unwrap_or_else(|| camel_case(&variant.ident.to_string()));
+        let result = attributes
+            .result
+            .map_or_else(|| "void".to_string(), |result| result.value());
+        let fields = bridge_fields(&variant.fields, variant.span())?;
+        if fields.len() != variant.fields.len() {
+            return Err(Error::new(
+                variant.span(),
+                "command fields can't be skipped",
+            ));
+        }
+
+        let args = fields.iter().map(BridgeField::schema);
+        schemas.push(quote! {
+            ::flutter_plugins::bridge::BridgeCommandSchema {
+                name: #name,
+                args: &[#(#args),*],
+                result: #result,
+            }
+        });
+
+        let variant_ident = &variant.ident;
+        let arm = match variant.fields {
+            Fields::Unit => quote!(::std::result::Result::Ok(Self::#variant_ident)),
+            _ => {
+                let values = fields.iter().map(|field| {
+                    let (ident, name) = (&field.ident, &field.name);
+                    quote!(#ident: ::flutter_plugins::bridge::decode_arg(args, #name)?)
+                });
+                quote! {
+                    (|| -> ::std::result::Result<Self, ::flutter_plugins::bridge::BridgeError> {
+                        ::std::result::Result::Ok(Self::#variant_ident { #(#values),* })
+                    })()
+                }
+            }
+        };
+        arms.push(quote!(#name => ::std::option::Option::Some(#arm),));
+    }
+
+    let ident = &input.ident;
+    Ok(quote! {
+        impl ::flutter_plugins::bridge::BridgeCommand for #ident {
+            const COMMANDS: &'static [::flutter_plugins::bridge::BridgeCommandSchema] =
+                &[#(#schemas),*];
+
+            #[allow(unused_variables)]
+            fn decode(
+                method: &str,
+                args: &::flutter_plugins::bridge::Value,
+            ) -> ::std::option::Option<
+                ::std::result::Result<Self, ::flutter_plugins::bridge::BridgeError>,
+            > {
+                match method {
+                    #(#arms)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+    })
+}
+
+/// `step_size` and `StepSize` -> `stepSize`.
+fn camel_case(name: &str) -> String {
+    let name = name.strip_prefix("r#").unwrap_or(name);
+    let mut camel_case = String::with_capacity(name.len());
+    let mut capitalize = false;
+    for c in name.chars() {
+        if c == '_' {
+            capitalize = !camel_case.is_empty();
+        } else if camel_case.is_empty() {
+            camel_case.extend(c.to_lowercase());
+        } else if capitalize {
+            camel_case.extend(c.to_uppercase());
+            capitalize = false;
+        } else {
+            camel_case.push(c);
+        }
+    }
+    camel_case
+}
+
+/// Dart type of the JSON encoding of |ty|, `None` when it isn't known.
+fn dart_type(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Reference(reference) => dart_type(&reference.elem),
+        Type::Paren(paren) => dart_type(&paren.elem),
+        Type::Group(group) => dart_type(&group.elem),
+        Type::Array(array) => Some(format!("List<{}>", dart_type_or_object(&array.elem))),
+        Type::Slice(slice) => Some(format!("List<{}>", dart_type_or_object(&slice.elem))),
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last()?;
+            let args: Vec<_> = match &segment.arguments {
+                PathArguments::AngleBracketed(args) => args
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            let dart_type = match (segment.ident.to_string().as_str(), args.as_slice()) {
+                ("bool", []) => "bool".to_string(),
+                (
+                    "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+                    | "u128" | "usize",
+                    [],
+                ) => "int".to_string(),
+                ("f32" | "f64", []) => "double".to_string(),
+                ("String" | "str" | "char" | "PathBuf" | "Path", []) => "String".to_string(),
+                ("Option", [inner]) => {
+                    let inner = dart_type_or_object(inner);
+                    match inner.ends_with('?') {
+                        true => inner,
+                        false => format!("{}?", inner),
+                    }
+                }
+                ("Box" | "Rc" | "Arc" | "Cow", [inner]) => dart_type(inner)?,
+                ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [item]) => {
+                    format!("List<{}>", dart_type_or_object(item))
+                }
+                ("HashMap" | "BTreeMap", [_key, value]) => {
+                    format!("Map<String, {}>", dart_type_or_object(value))
+                }
+                _ => return None,
+            };
+            Some(dart_type)
+        }
+        _ => None,
+    }
+}
+
+fn dart_type_or_object(ty: &Type) -> String {
+    dart_type(ty).unwrap_or_else(|| "Object?".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::{camel_case, dart_type};
+
+    #[test]
+    fn converts_names_to_camel_case() {
+        assert_eq!(camel_case("step_size"), "stepSize");
+        assert_eq!(camel_case("StepSize"), "stepSize");
+        assert_eq!(camel_case("value"), "value");
+        assert_eq!(camel_case("r#type"), "type");
+        assert_eq!(camel_case("_private"), "private");
+    }
+
+    #[test]
+    fn maps_the_rust_types_to_dart() {
+        assert_eq!(dart_type(&parse_quote!(u32)).unwrap(), "int");
+        assert_eq!(dart_type(&parse_quote!(Option<f64>)).unwrap(), "double?");
+        assert_eq!(
+            dart_type(&parse_quote!(Vec<String>)).unwrap(),
+            "List<String>"
+        );
+        assert_eq!(
+            dart_type(&parse_quote!(std::collections::HashMap<String, Vec<bool>>)).unwrap(),
+            "Map<String, List<bool>>"
+        );
+        assert_eq!(
+            dart_type(&parse_quote!(Vec<Settings>)).unwrap(),
+            "List<Object?>"
+        );
+        assert_eq!(dart_type(&parse_quote!(Settings)), None);
+    }
+}
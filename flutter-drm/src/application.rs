@@ -0,0 +1,604 @@
+use std::{
+    num::NonZeroUsize,
+    ops::ControlFlow,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    channel::{self, Sender},
+    generic::Generic,
+    EventLoop, Interest, Mode, PostAction,
+};
+use flutter_engine::{
+    builder::FlutterEngineBuilder,
+    ffi::IMPLICIT_VIEW_ID,
+    plugins::{Plugin, PluginRegistrar},
+    prepare::PrepareError,
+    schedule::ScheduleHandle,
+    view::FlutterView,
+    CreateError, FlutterEngine, FlutterEngineWeakRef, RunError,
+};
+use flutter_glutin::handler::GlutinOpenGLHandler;
+use flutter_input::keyboard::edit_text;
+use flutter_plugins::{
+    color::ColorInfo,
+    diagnostics::DiagnosticsPlugin,
+    isolate::IsolatePlugin,
+    keyevent::KeyEventPlugin,
+    lifecycle::LifecyclePlugin,
+    localization::LocalizationPlugin,
+    mousecursor::MouseCursorPlugin,
+    navigation::NavigationPlugin,
+    platform::PlatformPlugin,
+    settings::{PlatformBrightness, SettingsPlugin},
+    shared_preferences::SharedPreferencesPlugin,
+    system::SystemPlugin,
+    textinput::TextInputPlugin,
+    window::WindowPlugin,
+};
+use flutter_runner_api::{
+    ApplicationAttributes, GpuPreference, HostClock, HostContext, PumpResult, VsyncMode, WindowRole,
+};
+use parking_lot::Mutex;
+use sys_locale::get_locale;
+use thiserror::Error;
+use tracing::{error, trace, warn};
+use xkbcommon::xkb::Keysym;
+
+use crate::{
+    device::{self, DrmOutputError},
+    handler::{
+        DrmMouseCursorHandler, DrmOpenGLHandler, DrmPlatformHandler, DrmPlatformTaskHandler,
+        DrmTextInputHandler, DrmVsyncHandler, DrmWindowHandler,
+    },
+    input::{DrmInput, DrmInputError},
+    scanout::{DrmScanout, DrmScanoutError},
+};
+
+/// Requests sent to the event loop (e.g.: from an [`DrmApplicationHandle`] or
+/// the plugins).
+#[derive(Debug)]
+pub enum DrmApplicationRequest {
+    Suspend {
+        drop_caches: bool,
+    },
+    Resume,
+    Quit,
+    /// Quit as requested by Dart, with this exit code.
+    ExitWithCode(i32),
+    /// A key press wasn't handled by the framework, and falls through to the
+    /// text being edited.
+    EditText {
+        keysym: Keysym,
+        utf8: Option<String>,
+        select: bool,
+    },
+}
+
+/// Application rendering fullscreen on a display driven directly through
+/// DRM/KMS (i.e.: without a display server), e.g.: on embedded devices.
+pub struct DrmApplication {
+    event_loop: EventLoop<'static, DrmApplicationState>,
+    state: DrmApplicationState,
+}
+
+struct DrmApplicationState {
+    engine: FlutterEngine,
+    plugins: PluginRegistrar,
+    input: DrmInput,
+    scanout: Arc<DrmScanout>,
+    vsync_handler: Arc<Mutex<DrmVsyncHandler>>,
+    request_sender: Sender<DrmApplicationRequest>,
+    pixel_ratio: f64,
+    /// See `ApplicationAttributes::platform_task_batch_size`.
+    platform_task_batch_size: Option<NonZeroUsize>,
+    next_task_time: Option<Instant>,
+    is_started: bool,
+    is_suspended: bool,
+    is_exiting: bool,
+    has_exited: bool,
+}
+
+/// Thread-safe handle used for controlling a running application.
+#[derive(Clone)]
+pub struct DrmApplicationHandle {
+    request_sender: Sender<DrmApplicationRequest>,
+    engine: FlutterEngineWeakRef,
+}
+
+impl DrmApplicationHandle {
+    /// Notify the app that it is paused and stop scheduling frames. When
+    /// `drop_caches` is set, the engine is also asked to release its caches.
+    pub fn suspend(&self, drop_caches: bool) {
+        self.send(DrmApplicationRequest::Suspend { drop_caches });
+    }
+
+    /// Restore the app activity state after a previous call to `suspend`.
+    pub fn resume(&self) {
+        self.send(DrmApplicationRequest::Resume);
+    }
+
+    pub fn quit(&self) {
+        self.send(DrmApplicationRequest::Quit);
+    }
+
+    /// See [`FlutterEngine::schedule_at`]. Returns `None` if the engine is
+    /// gone.
+    pub fn schedule_at<F>(&self, engine_time_nanos: u64, f: F) -> Option<ScheduleHandle>
+    where
+        F: FnOnce(&FlutterEngine) + 'static + Send,
+    {
+        Some(self.engine.upgrade()?.schedule_at(engine_time_nanos, f))
+    }
+
+    /// See [`FlutterEngine::schedule_repeating`]. Returns `None` if the engine
+    /// is gone.
+    pub fn schedule_repeating<F>(&self, interval: Duration, f: F) -> Option<ScheduleHandle>
+    where
+        F: FnMut(&FlutterEngine) + 'static + Send,
+    {
+        Some(self.engine.upgrade()?.schedule_repeating(interval, f))
+    }
+
+    /// The engine, e.g.: for sending messages from another thread. Returns
+    /// `None` if the engine is gone.
+    pub fn engine(&self) -> Option<FlutterEngine> {
+        self.engine.upgrade()
+    }
+
+    fn send(&self, request: DrmApplicationRequest) {
+        if self.request_sender.send(request).is_err() {
+            warn!("Ignoring application request because the event loop is gone");
+        }
+    }
+}
+
+impl DrmApplication {
+    pub fn new(attributes: ApplicationAttributes) -> Result<Self, DrmApplicationCreateError> {
+        let mut engine_builder = FlutterEngineBuilder::new()
+            .with_asset_path(attributes.assets_path.clone())
+            .with_icu_data_path(attributes.icu_data_path.clone())
+            .with_embedded_icu_data(attributes.icu_data_embedded)
+            .with_persistent_cache_path(attributes.persistent_cache_path.clone())
+            .with_args(attributes.args.clone())
+            .with_vm_service_auth_codes_disabled(attributes.vm_service_auth_codes_disabled);
+        engine_builder = attributes.isolate_callbacks.register(engine_builder);
+
+        if let Some(port) = attributes.vm_service_port {
+            engine_builder = engine_builder.with_vm_service_port(port);
+        }
+
+        if let Some(time_base) = attributes.fake_time_base {
+            engine_builder = engine_builder.with_fake_time_base(time_base);
+        }
+
+        // Read the engine artifacts while the display is being set up.
+        let prepare = attributes
+            .prepare_timeout
+            .map(|timeout| (engine_builder.prepare_in_background(), timeout));
+
+        if attributes.window_role != WindowRole::Toplevel {
+            warn!("Layer shell surfaces are not supported by the drm backend");
+        }
+
+        if attributes.inner_size.is_some() {
+            warn!("Ignoring the window size, the content covers the whole display");
+        }
+
+        if attributes.handle_termination_signals {
+            warn!("Termination signals are not handled by the drm backend");
+        }
+
+        if attributes.content_zoom {
+            warn!("Content zoom is not supported by the drm backend");
+        }
+
+        if attributes.background_color != 0 {
+            warn!("Background colors are not supported by the drm backend");
+        }
+
+        if attributes.min_frame_interval.is_some() {
+            warn!("Frame rate caps are not supported by the drm backend");
+        }
+
+        if attributes.gpu_preference != GpuPreference::Default {
+            warn!("Ignoring the GPU preference, the GPU driving the display is used");
+        }
+
+        let event_loop: EventLoop<DrmApplicationState> = EventLoop::try_new()?;
+
+        let (card, output) = device::open_output(&attributes.drm_output)?;
+        let color_info = output.color_info;
+        let scanout = Arc::new(DrmScanout::new(card, output)?);
+        let pixel_ratio = attributes
+            .pixel_ratio
+            .filter(|ratio| ratio.is_finite() && *ratio > 0.0)
+            .unwrap_or_else(|| {
+                device::pixel_ratio(
+                    scanout.size().width,
+                    scanout.physical_size_mm().map(|(width, _)| width),
+                )
+            });
+
+        let (context, resource_context) =
+            scanout.create_contexts(attributes.robust_context, attributes.gl_debug_output)?;
+        if attributes.robust_context && !context.is_robust() {
+            warn!("Robust contexts are not supported by the driver, using a regular one");
+        }
+        if attributes.gl_debug_output && !context.has_debug_output() {
+            warn!("GL debug output is not supported by the driver, not logging it");
+        }
+
+        let input = DrmInput::new(scanout.size())?;
+
+        let platform_task_handler = Arc::new(DrmPlatformTaskHandler::new(event_loop.get_signal()));
+        let vsync_handler = Arc::new(Mutex::new(DrmVsyncHandler::new(scanout.frame_interval())));
+
+        // Only the OpenGL render path is supported: the frames are rendered
+        // straight into the scanned out buffers.
+        let mut engine_builder = engine_builder.with_platform_handler(platform_task_handler);
+        if attributes.vsync_mode == VsyncMode::Vsync {
+            engine_builder = engine_builder.with_vsync_handler(vsync_handler.clone());
+        }
+
+        // Surface invalid bundles before the mode is set.
+        if let Some((prepare_handle, timeout)) = prepare {
+            prepare_handle.wait(timeout)?;
+        }
+
+        let engine = engine_builder.build()?;
+        vsync_handler.lock().init(engine.downgrade());
+
+        let opengl_handler = DrmOpenGLHandler::new(
+            GlutinOpenGLHandler::new(
+                Arc::new(std::sync::Mutex::new(context)),
+                Arc::new(std::sync::Mutex::new(resource_context)),
+            ),
+            scanout.clone(),
+            vsync_handler.clone(),
+        );
+        engine.add_view(FlutterView::new_without_compositor(
+            IMPLICIT_VIEW_ID,
+            opengl_handler,
+        ));
+
+        let (request_sender, request_channel) = channel::channel();
+        let plugins = PluginRegistrar::with_configs(attributes.plugin_configs)
+            .with_async_runtime(attributes.async_runtime);
+        let plugins = register_plugins(&engine, &request_sender, color_info, plugins);
+
+        let handle = event_loop.handle();
+        handle
+            .insert_source(request_channel, |event, _, state| {
+                if let channel::Event::Msg(request) = event {
+                    state.handle_request(request);
+                }
+            })
+            .map_err(|err| err.error)?;
+
+        handle
+            .insert_source(
+                Generic::new(input.libinput(), Interest::READ, Mode::Level),
+                |_, _, state: &mut DrmApplicationState| {
+                    let DrmApplicationState {
+                        engine,
+                        plugins,
+                        input,
+                        request_sender,
+                        ..
+                    } = state;
+                    input.dispatch(engine, plugins, request_sender)?;
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|err| err.error)?;
+
+        handle
+            .insert_source(
+                Generic::new(scanout.event_fd()?, Interest::READ, Mode::Level),
+                |_, _, state: &mut DrmApplicationState| {
+                    if state.scanout.dispatch_events()? {
+                        state.vsync_handler.lock().notify_flip(&state.engine);
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|err| err.error)?;
+
+        let state = DrmApplicationState {
+            engine,
+            plugins,
+            input,
+            scanout,
+            vsync_handler,
+            request_sender,
+            pixel_ratio,
+            platform_task_batch_size: attributes.platform_task_batch_size,
+            next_task_time: None,
+            is_started: false,
+            is_suspended: false,
+            is_exiting: false,
+            has_exited: false,
+        };
+
+        Ok(Self { event_loop, state })
+    }
+
+    pub fn handle(&self) -> DrmApplicationHandle {
+        DrmApplicationHandle {
+            request_sender: self.state.request_sender.clone(),
+            engine: self.state.engine.downgrade(),
+        }
+    }
+
+    pub fn run(self) -> Result<(), DrmApplicationRunError> {
+        self.run_with(|_context| ControlFlow::Continue(()))
+    }
+
+    /// Run the application, invoking `hook` on the platform thread once per
+    /// event loop iteration (i.e.: after every batch of dispatched events and
+    /// platform tasks). Returning [`ControlFlow::Break`] quits the
+    /// application.
+    pub fn run_with<F>(mut self, mut hook: F) -> Result<(), DrmApplicationRunError>
+    where
+        F: FnMut(&mut HostContext) -> ControlFlow<()>,
+    {
+        self.state.start()?;
+
+        let mut clock = HostClock::default();
+        while !self.state.is_exiting {
+            let timeout = self.state.next_task_timeout();
+            if let Err(err) = self.event_loop.dispatch(timeout, &mut self.state) {
+                self.state.shutdown();
+                return Err(err.into());
+            }
+            self.state.execute_platform_tasks();
+
+            let mut context = clock.tick(&self.state.engine);
+            if hook(&mut context).is_break() || context.is_quit_requested() {
+                self.state.is_exiting = true;
+            }
+        }
+
+        self.state.shutdown();
+        Ok(())
+    }
+
+    /// Run a single event loop iteration: dispatch the pending events (waiting
+    /// at most `timeout` for new ones, or indefinitely if `None`) and execute
+    /// the platform tasks which are due.
+    ///
+    /// Hosts driving their own loop must call this from the thread which
+    /// created the application at least once per frame, otherwise the app
+    /// stops responding.
+    pub fn pump(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<PumpResult, DrmApplicationRunError> {
+        if self.state.has_exited {
+            return Ok(PumpResult::Exit);
+        }
+
+        self.state.start()?;
+
+        let timeout = match (timeout, self.state.next_task_timeout()) {
+            (Some(timeout), Some(next_task)) => Some(timeout.min(next_task)),
+            (timeout, next_task) => timeout.or(next_task),
+        };
+        if let Err(err) = self.event_loop.dispatch(timeout, &mut self.state) {
+            self.state.shutdown();
+            return Err(err.into());
+        }
+        self.state.execute_platform_tasks();
+
+        if self.state.is_exiting {
+            self.state.shutdown();
+            Ok(PumpResult::Exit)
+        } else {
+            Ok(PumpResult::Continue)
+        }
+    }
+}
+
+impl Drop for DrmApplication {
+    fn drop(&mut self) {
+        // The application may be dropped without being run to completion
+        // (e.g.: after an error), but don't make a panic worse.
+        if !std::thread::panicking() {
+            self.state.shutdown();
+        }
+    }
+}
+
+impl DrmApplicationState {
+    fn with_plugin<F, P>(&self, f: F)
+    where
+        F: FnOnce(&P),
+        P: Plugin + 'static,
+    {
+        self.plugins.with_plugin(f)
+    }
+
+    fn start(&mut self) -> Result<(), RunError> {
+        if self.is_started {
+            return Ok(());
+        }
+        self.is_started = true;
+
+        self.engine.run()?;
+        self.send_window_metrics();
+
+        self.with_plugin(|localization: &LocalizationPlugin| {
+            let locale = get_locale().unwrap_or_else(|| String::from("en-US"));
+            localization.send_locale(locale);
+        });
+
+        // There is no desktop to follow the settings of.
+        self.with_plugin(|settings: &SettingsPlugin| {
+            settings
+                .start_message()
+                .set_platform_brightness(PlatformBrightness::Light)
+                .set_use_24_hour_format(true)
+                .set_text_scale_factor(1.0)
+                .send();
+        });
+
+        Ok(())
+    }
+
+    fn send_window_metrics(&self) {
+        let size = self.scanout.size();
+        if let Err(err) =
+            self.engine
+                .send_window_metrics_event(IMPLICIT_VIEW_ID, size, self.pixel_ratio, 0)
+        {
+            error!("Failed to send window metrics: {err}");
+        }
+    }
+
+    fn handle_request(&mut self, request: DrmApplicationRequest) {
+        trace!("Handling {request:?}");
+        match request {
+            DrmApplicationRequest::Suspend { drop_caches } => self.suspend(drop_caches),
+            DrmApplicationRequest::Resume => self.resume(),
+            DrmApplicationRequest::Quit => self.is_exiting = true,
+            DrmApplicationRequest::ExitWithCode(exit_code) => {
+                self.engine.set_exit_code(exit_code);
+                self.is_exiting = true;
+            }
+            DrmApplicationRequest::EditText {
+                keysym,
+                utf8,
+                select,
+            } => self
+                .plugins
+                .with_plugin_mut(|text_input: &mut TextInputPlugin| {
+                    edit_text(text_input, keysym, utf8.as_deref(), select);
+                }),
+        }
+    }
+
+    fn suspend(&mut self, drop_caches: bool) {
+        if self.is_suspended {
+            return;
+        }
+        self.is_suspended = true;
+
+        self.with_plugin(|lifecycle: &LifecyclePlugin| lifecycle.send_app_is_paused());
+        self.vsync_handler.lock().set_paused(true);
+
+        if drop_caches {
+            self.engine.notify_low_memory_warning();
+        }
+    }
+
+    fn resume(&mut self) {
+        if !self.is_suspended {
+            return;
+        }
+        self.is_suspended = false;
+
+        self.with_plugin(|lifecycle: &LifecyclePlugin| lifecycle.send_app_is_resumed());
+        self.vsync_handler.lock().set_paused(false);
+        self.send_window_metrics();
+        self.engine.schedule_frame();
+    }
+
+    fn execute_platform_tasks(&mut self) {
+        // Left over tasks yield a past time, i.e. the pending events are
+        // polled before running the next batch.
+        self.next_task_time = self
+            .engine
+            .execute_platform_tasks_limited(self.platform_task_batch_size);
+    }
+
+    fn next_task_timeout(&self) -> Option<Duration> {
+        self.next_task_time
+            .map(|time| time.saturating_duration_since(Instant::now()))
+    }
+
+    /// Shut the engine down once the application has quit, rather than
+    /// leaving it running until the process exits.
+    fn shutdown(&mut self) {
+        if std::mem::replace(&mut self.has_exited, true) {
+            return;
+        }
+
+        if self.is_started {
+            self.input.remove_pointers(&self.engine);
+        }
+
+        let count = self.plugins.detach_all(&self.engine);
+        self.engine.remove_all_channels();
+        trace!("Detached {} plugins", count);
+
+        self.engine.remove_view(IMPLICIT_VIEW_ID);
+        self.engine.shutdown();
+    }
+}
+
+fn register_plugins(
+    engine: &FlutterEngine,
+    request_sender: &Sender<DrmApplicationRequest>,
+    color_info: ColorInfo,
+    mut plugins: PluginRegistrar,
+) -> PluginRegistrar {
+    let noop_isolate_cb = || trace!("[isolate-plugin] isolate has been created");
+    let platform_handler = Arc::new(Mutex::new(DrmPlatformHandler::new(request_sender.clone())));
+    let window_handler = Arc::new(Mutex::new(DrmWindowHandler::new(
+        request_sender.clone(),
+        color_info,
+    )));
+    let text_input_handler = Arc::new(Mutex::new(DrmTextInputHandler::default()));
+    let mouse_cursor_handler = Arc::new(Mutex::new(DrmMouseCursorHandler::default()));
+
+    plugins.add_plugin(engine, DiagnosticsPlugin::default());
+    plugins.add_plugin(engine, IsolatePlugin::new(noop_isolate_cb));
+    plugins.add_plugin(engine, KeyEventPlugin::default());
+    plugins.add_plugin(engine, LifecyclePlugin::default());
+    plugins.add_plugin(engine, LocalizationPlugin::default());
+    plugins.add_plugin(engine, NavigationPlugin::default());
+    plugins.add_plugin(engine, PlatformPlugin::new(platform_handler));
+    plugins.add_plugin(engine, SettingsPlugin::default());
+    plugins.add_plugin(engine, SharedPreferencesPlugin::default());
+    plugins.add_plugin(engine, SystemPlugin::default());
+    plugins.add_plugin(engine, TextInputPlugin::new(text_input_handler));
+    plugins.add_plugin(engine, WindowPlugin::new(window_handler));
+    plugins.add_plugin(engine, MouseCursorPlugin::new(mouse_cursor_handler));
+    plugins.log_unused_configs();
+    plugins
+}
+
+#[derive(Error, Debug)]
+pub enum DrmApplicationCreateError {
+    #[error(transparent)]
+    CalloopError(#[from] calloop::Error),
+
+    #[error(transparent)]
+    OutputError(#[from] DrmOutputError),
+
+    #[error(transparent)]
+    ScanoutError(#[from] DrmScanoutError),
+
+    #[error(transparent)]
+    InputError(#[from] DrmInputError),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    EngineCreateError(#[from] CreateError),
+
+    #[error(transparent)]
+    EnginePrepareError(#[from] PrepareError),
+}
+
+#[derive(Error, Debug)]
+pub enum DrmApplicationRunError {
+    #[error(transparent)]
+    DispatchError(#[from] calloop::Error),
+
+    #[error(transparent)]
+    StartEngineError(#[from] RunError),
+}
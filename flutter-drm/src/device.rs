@@ -0,0 +1,549 @@
+//! Selection of the card, connector and mode the content is scanned out to.
+//!
+//! Each of them can be configured through [`DrmOutput`], or the
+//! `FLUTTER_DRM_DEVICE`, `FLUTTER_DRM_CONNECTOR` and `FLUTTER_DRM_MODE`
+//! environment variables (e.g.: `/dev/dri/card1`, `HDMI-A-1` and
+//! `1920x1080@60`). Otherwise, the preferred mode of the first connected
+//! display is used.
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io,
+    os::fd::{AsFd, BorrowedFd},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use drm::{
+    control::{
+        connector, crtc, plane, property, Device as ControlDevice, Mode, ModeTypeFlags,
+        ResourceHandle,
+    },
+    Device,
+};
+use flutter_plugins::color::{ColorInfo, Primaries};
+use flutter_runner_api::DrmOutput;
+use thiserror::Error;
+use tracing::{debug, info};
+
+const DEVICE_ENV: &str = "FLUTTER_DRM_DEVICE";
+const CONNECTOR_ENV: &str = "FLUTTER_DRM_CONNECTOR";
+const MODE_ENV: &str = "FLUTTER_DRM_MODE";
+
+/// Fixed header of the EDID base blocks.
+const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+/// `DRM_PLANE_TYPE_PRIMARY`, the value of the `type` property of the planes
+/// the content can be scanned out from.
+const PLANE_TYPE_PRIMARY: u64 = 1;
+
+/// A DRM card node (e.g.: `/dev/dri/card0`).
+#[derive(Debug)]
+pub struct Card(File);
+
+impl Card {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self(file))
+    }
+}
+
+impl AsFd for Card {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl Device for Card {}
+
+impl ControlDevice for Card {}
+
+/// Resolution and (optionally) refresh rate of a mode, as configured (e.g.:
+/// `1920x1080` or `1920x1080@60`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ModeSpec {
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    pub(crate) refresh: Option<u32>,
+}
+
+impl FromStr for ModeSpec {
+    type Err = DrmOutputError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || DrmOutputError::InvalidMode(value.to_string());
+
+        let (size, refresh) = match value.split_once('@') {
+            Some((size, refresh)) => (size, Some(refresh.parse().map_err(|_| invalid())?)),
+            None => (value, None),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+
+        Ok(Self {
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+            refresh,
+        })
+    }
+}
+
+/// The properties of a mode relevant for selecting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ModeInfo {
+    pub(crate) size: (u16, u16),
+    pub(crate) refresh: u32,
+    pub(crate) preferred: bool,
+}
+
+impl From<&Mode> for ModeInfo {
+    fn from(mode: &Mode) -> Self {
+        Self {
+            size: mode.size(),
+            refresh: mode.vrefresh(),
+            preferred: mode.mode_type().contains(ModeTypeFlags::PREFERRED),
+        }
+    }
+}
+
+/// Index of the mode to use among |modes|: the one matching |spec| with the
+/// highest refresh rate (when it isn't specified), or the preferred one.
+/// Displays not reporting a preferred mode get their first one, which is the
+/// best one according to the kernel.
+pub(crate) fn select_mode(modes: &[ModeInfo], spec: Option<ModeSpec>) -> Option<usize> {
+    let Some(spec) = spec else {
+        return modes
+            .iter()
+            .position(|mode| mode.preferred)
+            .or((!modes.is_empty()).then_some(0));
+    };
+
+    modes
+        .iter()
+        .enumerate()
+        .filter(|(_, mode)| mode.size == (spec.width, spec.height))
+        .filter(|(_, mode)| spec.refresh.is_none_or(|refresh| mode.refresh == refresh))
+        .max_by_key(|(index, mode)| (mode.refresh, std::cmp::Reverse(*index)))
+        .map(|(index, _)| index)
+}
+
+/// Name of the connector, as used by the kernel and the compositors (e.g.:
+/// `HDMI-A-1`).
+pub(crate) fn connector_name(info: &connector::Info) -> String {
+    format!("{}-{}", info.interface().as_str(), info.interface_id())
+}
+
+/// Handles of the properties of a KMS object, by name.
+#[derive(Debug, Default)]
+pub(crate) struct PropertyHandles(HashMap<String, property::Handle>);
+
+impl PropertyHandles {
+    pub(crate) fn load(card: &Card, handle: impl ResourceHandle) -> io::Result<Self> {
+        let values = card.get_properties(handle)?;
+        let (handles, _) = values.as_props_and_values();
+
+        let mut properties = HashMap::new();
+        for &handle in handles {
+            let info = card.get_property(handle)?;
+            if let Ok(name) = info.name().to_str() {
+                properties.insert(name.to_string(), handle);
+            }
+        }
+        Ok(Self(properties))
+    }
+
+    pub(crate) fn get(&self, name: &str) -> io::Result<property::Handle> {
+        self.0.get(name).copied().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("Missing {name} property"))
+        })
+    }
+}
+
+/// The display the content is scanned out to, along with the pipeline
+/// driving it.
+#[derive(Debug)]
+pub(crate) struct KmsOutput {
+    pub(crate) name: String,
+    pub(crate) connector: connector::Handle,
+    pub(crate) crtc: crtc::Handle,
+    pub(crate) plane: plane::Handle,
+    pub(crate) mode: Mode,
+    /// Physical size of the display in millimeters, if known.
+    pub(crate) physical_size: Option<(u32, u32)>,
+    /// From the primaries of the display in its EDID, if any.
+    pub(crate) color_info: ColorInfo,
+}
+
+impl KmsOutput {
+    /// Duration of a frame, in nanoseconds.
+    pub(crate) fn frame_interval(&self) -> u64 {
+        1_000_000_000 / u64::from(self.mode.vrefresh().max(1))
+    }
+}
+
+/// Pixel ratio of a display |width| pixels wide, from its physical width
+/// (in millimeters). The content is scaled up on displays denser than the
+/// ~3.8 pixels per millimeter (i.e.: 96 DPI) of regular desktop displays,
+/// but never scaled down.
+pub(crate) fn pixel_ratio(width: u32, physical_width: Option<u32>) -> f64 {
+    const DESKTOP_PIXELS_PER_MM: f64 = 96.0 / 25.4;
+
+    match physical_width {
+        Some(physical_width) if physical_width > 0 => {
+            let pixels_per_mm = f64::from(width) / f64::from(physical_width);
+            (pixels_per_mm / DESKTOP_PIXELS_PER_MM).max(1.0)
+        }
+        // E.g.: projectors, or displays without EDID.
+        _ => 1.0,
+    }
+}
+
+/// Primaries of a display, from the chromaticity coordinates of its |edid|.
+/// They are 10 bits each: the 2 low bits of all the coordinates are packed
+/// in the bytes 25 and 26, followed by the 8 high bits of each one.
+pub(crate) fn edid_primaries(edid: &[u8]) -> Option<Primaries> {
+    if edid.len() < 128 || edid[..8] != EDID_HEADER {
+        return None;
+    }
+
+    let coordinate = |index: usize| {
+        let low_bits = (edid[25 + index / 4] >> (6 - 2 * (index % 4))) & 0b11;
+        let value = (u16::from(edid[27 + index]) << 2) | u16::from(low_bits);
+        f64::from(value) / 1024.0
+    };
+    Some(Primaries::new(
+        (coordinate(0), coordinate(1)),
+        (coordinate(2), coordinate(3)),
+        (coordinate(4), coordinate(5)),
+    ))
+}
+
+/// Open the card and pick the output configured by |config| (or the
+/// environment).
+pub(crate) fn open_output(config: &DrmOutput) -> Result<(Card, KmsOutput), DrmOutputError> {
+    let connector = config
+        .connector
+        .clone()
+        .or_else(|| env::var(CONNECTOR_ENV).ok());
+    let mode = match config.mode.clone().or_else(|| env::var(MODE_ENV).ok()) {
+        Some(mode) => Some(mode.parse::<ModeSpec>()?),
+        None => None,
+    };
+
+    let device = config
+        .device
+        .clone()
+        .or_else(|| env::var_os(DEVICE_ENV).map(PathBuf::from));
+    if let Some(device) = device {
+        let card = Card::open(&device).map_err(|err| DrmOutputError::Open(device.clone(), err))?;
+        let output = find_output(&card, connector.as_deref(), mode)?;
+        return Ok((card, output));
+    }
+
+    // Boards often expose a card without any connector (e.g.: the GPU of a
+    // Raspberry Pi 4 is `card1`, next to the display controller).
+    let mut last_error = DrmOutputError::NoDevice;
+    for path in card_paths() {
+        let card = match Card::open(&path) {
+            Ok(card) => card,
+            Err(err) => {
+                debug!("Skipping {}: {err}", path.display());
+                last_error = DrmOutputError::Open(path, err);
+                continue;
+            }
+        };
+
+        match find_output(&card, connector.as_deref(), mode) {
+            Ok(output) => {
+                info!("Using {} on {}", output.name, path.display());
+                return Ok((card, output));
+            }
+            Err(err) => {
+                debug!("Skipping {}: {err}", path.display());
+                last_error = err;
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+fn card_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir("/dev/dri")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("card"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn find_output(
+    card: &Card,
+    connector_name_filter: Option<&str>,
+    mode_spec: Option<ModeSpec>,
+) -> Result<KmsOutput, DrmOutputError> {
+    // Atomic commits are used for presenting, which don't work with legacy
+    // drivers anyway.
+    card.set_client_capability(drm::ClientCapability::UniversalPlanes, true)
+        .and_then(|_| card.set_client_capability(drm::ClientCapability::Atomic, true))
+        .map_err(DrmOutputError::AtomicUnsupported)?;
+
+    let resources = card.resource_handles()?;
+
+    let mut connectors = Vec::new();
+    for &handle in resources.connectors() {
+        let info = card.get_connector(handle, true)?;
+        if info.state() == connector::State::Connected {
+            connectors.push(info);
+        }
+    }
+
+    let info = match connector_name_filter {
+        Some(name) => connectors
+            .into_iter()
+            .find(|info| connector_name(info) == name)
+            .ok_or_else(|| DrmOutputError::ConnectorNotFound(name.to_string()))?,
+        None => connectors
+            .into_iter()
+            .next()
+            .ok_or(DrmOutputError::NoConnectedDisplay)?,
+    };
+    let name = connector_name(&info);
+
+    let modes: Vec<ModeInfo> = info.modes().iter().map(ModeInfo::from).collect();
+    let mode = select_mode(&modes, mode_spec)
+        .map(|index| info.modes()[index])
+        .ok_or_else(|| DrmOutputError::ModeNotFound(name.clone()))?;
+
+    // Keep the CRTC already driving the connector (e.g.: set up by the boot
+    // splash), which avoids a modeset on some drivers.
+    let current_crtc = info
+        .current_encoder()
+        .and_then(|encoder| card.get_encoder(encoder).ok())
+        .and_then(|encoder| encoder.crtc());
+    let crtc = match current_crtc {
+        Some(crtc) => Some(crtc),
+        None => info
+            .encoders()
+            .iter()
+            .filter_map(|&encoder| card.get_encoder(encoder).ok())
+            .flat_map(|encoder| resources.filter_crtcs(encoder.possible_crtcs()))
+            .next(),
+    };
+    let crtc = crtc.ok_or_else(|| DrmOutputError::NoCrtc(name.clone()))?;
+
+    let plane = find_primary_plane(card, &resources, crtc)?
+        .ok_or_else(|| DrmOutputError::NoPrimaryPlane(name.clone()))?;
+
+    let (width, height) = mode.size();
+    info!("Selected {width}x{height}@{} on {name}", mode.vrefresh());
+
+    let color_info = read_edid(card, info.handle())
+        .and_then(|edid| edid_primaries(&edid))
+        .map(|primaries| ColorInfo::from_primaries(&primaries))
+        .unwrap_or_default();
+    debug!("Color info of {name}: {color_info:?}");
+
+    Ok(KmsOutput {
+        name,
+        connector: info.handle(),
+        crtc,
+        plane,
+        mode,
+        physical_size: info.size(),
+        color_info,
+    })
+}
+
+/// EDID of the display plugged into |connector|, if it has any.
+fn read_edid(card: &Card, connector: connector::Handle) -> Option<Vec<u8>> {
+    let values = card.get_properties(connector).ok()?;
+    let (handles, raw_values) = values.as_props_and_values();
+    handles
+        .iter()
+        .zip(raw_values)
+        .find_map(|(&handle, &value)| {
+            let is_edid = card
+                .get_property(handle)
+                .ok()?
+                .name()
+                .to_str()
+                .is_ok_and(|name| name == "EDID");
+            (is_edid && value != 0).then_some(value)
+        })
+        .and_then(|blob| card.get_property_blob(blob).ok())
+}
+
+fn find_primary_plane(
+    card: &Card,
+    resources: &drm::control::ResourceHandles,
+    crtc: crtc::Handle,
+) -> io::Result<Option<plane::Handle>> {
+    for plane in card.plane_handles()? {
+        let info = card.get_plane(plane)?;
+        if !resources
+            .filter_crtcs(info.possible_crtcs())
+            .contains(&crtc)
+        {
+            continue;
+        }
+
+        let values = card.get_properties(plane)?;
+        let (handles, raw_values) = values.as_props_and_values();
+        for (&handle, &value) in handles.iter().zip(raw_values) {
+            let is_type = card
+                .get_property(handle)?
+                .name()
+                .to_str()
+                .is_ok_and(|name| name == "type");
+            if is_type && value == PLANE_TYPE_PRIMARY {
+                return Ok(Some(plane));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Error, Debug)]
+pub enum DrmOutputError {
+    #[error("No DRM device with a connected display was found")]
+    NoDevice,
+
+    #[error("Failed to open {0}: {1}")]
+    Open(PathBuf, #[source] io::Error),
+
+    #[error("The device doesn't support atomic modesetting")]
+    AtomicUnsupported(#[source] io::Error),
+
+    #[error("No display is connected")]
+    NoConnectedDisplay,
+
+    #[error("No connected display on {0}")]
+    ConnectorNotFound(String),
+
+    #[error("Invalid mode {0} (expected e.g.: 1920x1080 or 1920x1080@60)")]
+    InvalidMode(String),
+
+    #[error("No matching mode on {0}")]
+    ModeNotFound(String),
+
+    #[error("No CRTC can drive {0}")]
+    NoCrtc(String),
+
+    #[error("No primary plane for {0}")]
+    NoPrimaryPlane(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_plugins::color::{ColorGamut, ColorInfo};
+
+    use super::{edid_primaries, pixel_ratio, select_mode, ModeInfo, ModeSpec, EDID_HEADER};
+
+    fn mode(width: u16, height: u16, refresh: u32, preferred: bool) -> ModeInfo {
+        ModeInfo {
+            size: (width, height),
+            refresh,
+            preferred,
+        }
+    }
+
+    #[test]
+    fn parses_mode_specs() {
+        assert_eq!(
+            "1920x1080".parse::<ModeSpec>().unwrap(),
+            ModeSpec {
+                width: 1920,
+                height: 1080,
+                refresh: None
+            }
+        );
+        assert_eq!(
+            "800x480@60".parse::<ModeSpec>().unwrap(),
+            ModeSpec {
+                width: 800,
+                height: 480,
+                refresh: Some(60)
+            }
+        );
+        assert!("1920".parse::<ModeSpec>().is_err());
+        assert!("1920x".parse::<ModeSpec>().is_err());
+        assert!("1920x1080@".parse::<ModeSpec>().is_err());
+    }
+
+    #[test]
+    fn selects_preferred_mode_by_default() {
+        let modes = [
+            mode(3840, 2160, 30, false),
+            mode(1920, 1080, 60, true),
+            mode(1280, 720, 60, false),
+        ];
+        assert_eq!(select_mode(&modes, None), Some(1));
+
+        // Without a preferred mode, the first (i.e.: best) one is used.
+        assert_eq!(select_mode(&modes[2..], None), Some(0));
+        assert_eq!(select_mode(&[], None), None);
+    }
+
+    #[test]
+    fn selects_configured_mode() {
+        let modes = [
+            mode(1920, 1080, 60, true),
+            mode(1280, 720, 50, false),
+            mode(1280, 720, 60, false),
+            mode(1280, 720, 60, false),
+        ];
+
+        let spec = |refresh| ModeSpec {
+            width: 1280,
+            height: 720,
+            refresh,
+        };
+        // The highest refresh rate, and the first of the identical modes.
+        assert_eq!(select_mode(&modes, Some(spec(None))), Some(2));
+        assert_eq!(select_mode(&modes, Some(spec(Some(50)))), Some(1));
+        assert_eq!(select_mode(&modes, Some(spec(Some(30)))), None);
+    }
+
+    #[test]
+    fn scales_dense_displays_up() {
+        // A 24" 1080p monitor.
+        assert_eq!(pixel_ratio(1920, Some(531)), 1.0);
+        // The 7" touch display of the Raspberry Pi.
+        let ratio = pixel_ratio(800, Some(155));
+        assert!((ratio - 1.37).abs() < 0.05, "{ratio}");
+        // A 5.5" 1080p display.
+        let ratio = pixel_ratio(1080, Some(68));
+        assert!((ratio - 4.2).abs() < 0.05, "{ratio}");
+        assert_eq!(pixel_ratio(1920, None), 1.0);
+        assert_eq!(pixel_ratio(1920, Some(0)), 1.0);
+    }
+
+    #[test]
+    fn reads_the_primaries_from_the_edid() {
+        let mut edid = [0; 128];
+        edid[..8].copy_from_slice(&EDID_HEADER);
+        // The chromaticity block of a wide gamut monitor.
+        edid[25..35].copy_from_slice(&[0xe5, 0x40, 0xae, 0x51, 0x45, 0xab, 0x26, 0x0f, 0x50, 0x54]);
+
+        let primaries = edid_primaries(&edid).unwrap();
+        assert!((primaries.red.x - 0.6826).abs() < 0.001, "{primaries:?}");
+        assert!((primaries.green.y - 0.6689).abs() < 0.001, "{primaries:?}");
+        let info = ColorInfo::from_primaries(&primaries);
+        assert!(info.is_wide_gamut);
+        assert_eq!(info.approx_gamut, ColorGamut::DisplayP3);
+
+        assert_eq!(edid_primaries(&edid[..64]), None);
+        edid[0] = 0xff;
+        assert_eq!(edid_primaries(&edid), None);
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    ffi::{c_void, CStr},
+    sync::Arc,
+};
+
+use calloop::{channel::Sender, LoopSignal};
+use dpi::PhysicalSize;
+use flutter_engine::{
+    tasks::TaskRunnerHandler, FlutterEngine, FlutterEngineWeakRef, FlutterVsyncHandler,
+};
+use flutter_engine_api::FlutterOpenGLHandler;
+use flutter_glutin::handler::GlutinOpenGLHandler;
+use flutter_plugins::{
+    color::ColorInfo,
+    mousecursor::{MouseCursorError, MouseCursorHandler, SystemMouseCursor},
+    platform::{AppSwitcherDescription, PlatformError, PlatformHandler},
+    textinput::TextInputHandler,
+    window::{PositionParams, WindowHandler},
+};
+use parking_lot::Mutex;
+use tracing::{error, trace};
+
+use crate::{application::DrmApplicationRequest, scanout::DrmScanout};
+
+pub struct DrmPlatformTaskHandler {
+    signal: LoopSignal,
+}
+
+impl DrmPlatformTaskHandler {
+    pub fn new(signal: LoopSignal) -> Self {
+        Self { signal }
+    }
+}
+
+impl TaskRunnerHandler for DrmPlatformTaskHandler {
+    fn wake(&self) {
+        self.signal.wakeup();
+    }
+}
+
+/// Renders with the glutin contexts, and scans the frames out once swapped.
+pub(crate) struct DrmOpenGLHandler {
+    gl: GlutinOpenGLHandler,
+    scanout: Arc<DrmScanout>,
+    vsync_handler: Arc<Mutex<DrmVsyncHandler>>,
+}
+
+impl DrmOpenGLHandler {
+    pub(crate) fn new(
+        gl: GlutinOpenGLHandler,
+        scanout: Arc<DrmScanout>,
+        vsync_handler: Arc<Mutex<DrmVsyncHandler>>,
+    ) -> Self {
+        Self {
+            gl,
+            scanout,
+            vsync_handler,
+        }
+    }
+}
+
+impl FlutterOpenGLHandler for DrmOpenGLHandler {
+    fn present(&self) -> bool {
+        if !self.gl.present() {
+            return false;
+        }
+
+        // The flip may complete before `present` returns.
+        self.vsync_handler.lock().notify_flip_pending();
+        if !self.scanout.present() {
+            self.vsync_handler.lock().cancel_flip();
+            return false;
+        }
+        true
+    }
+
+    fn make_current(&self) -> bool {
+        self.gl.make_current()
+    }
+
+    fn clear_current(&self) -> bool {
+        self.gl.clear_current()
+    }
+
+    fn fbo_with_frame_info_callback(&self, size: PhysicalSize<u32>) -> u32 {
+        self.gl.fbo_with_frame_info_callback(size)
+    }
+
+    fn make_resource_current(&self) -> bool {
+        self.gl.make_resource_current()
+    }
+
+    fn gl_proc_resolver(&self, proc: &CStr) -> *mut c_void {
+        self.gl.gl_proc_resolver(proc)
+    }
+}
+
+/// Answers the frame requests of the engine on the page flips.
+///
+/// While no flip is pending (e.g.: before the first frame, or when the
+/// content is idle), the requests are answered right away.
+pub struct DrmVsyncHandler {
+    engine: FlutterEngineWeakRef,
+    frame_interval: u64,
+    state: Mutex<DrmVsyncState>,
+}
+
+#[derive(Default)]
+struct DrmVsyncState {
+    pending_baton: Option<isize>,
+    is_flip_pending: bool,
+    is_paused: bool,
+}
+
+impl DrmVsyncHandler {
+    pub(crate) fn new(frame_interval: u64) -> Self {
+        Self {
+            engine: Default::default(),
+            frame_interval,
+            state: Default::default(),
+        }
+    }
+
+    pub(crate) fn init(&mut self, engine: FlutterEngineWeakRef) {
+        if self.engine.upgrade().is_some() {
+            error!("Vsync handler engine was already initialized");
+        }
+        self.engine = engine;
+    }
+
+    /// A frame is being committed, the next frame request is answered once
+    /// it is scanned out.
+    pub(crate) fn notify_flip_pending(&self) {
+        self.state.lock().is_flip_pending = true;
+    }
+
+    /// The frame couldn't be committed, so no flip is coming.
+    pub(crate) fn cancel_flip(&self) {
+        let baton = {
+            let mut state = self.state.lock();
+            state.is_flip_pending = false;
+            if state.is_paused {
+                return;
+            }
+            state.pending_baton.take()
+        };
+
+        if let Some(baton) = baton {
+            self.post_vsync(baton);
+        }
+    }
+
+    /// Called on the platform thread once the pending flip completed.
+    pub(crate) fn notify_flip(&self, engine: &FlutterEngine) {
+        let baton = {
+            let mut state = self.state.lock();
+            state.is_flip_pending = false;
+            if state.is_paused {
+                return;
+            }
+            state.pending_baton.take()
+        };
+
+        if let Some(baton) = baton {
+            self.on_vsync(engine, baton);
+        }
+    }
+
+    /// While paused, frame requests are deferred until the handler is resumed.
+    pub(crate) fn set_paused(&self, paused: bool) {
+        let baton = {
+            let mut state = self.state.lock();
+            state.is_paused = paused;
+            if paused || state.is_flip_pending {
+                return;
+            }
+            state.pending_baton.take()
+        };
+
+        if let Some(baton) = baton {
+            self.post_vsync(baton);
+        }
+    }
+
+    fn on_vsync(&self, engine: &FlutterEngine, baton: isize) {
+        on_vsync(engine, baton, self.frame_interval);
+    }
+
+    /// Answer the frame request from the platform thread.
+    fn post_vsync(&self, baton: isize) {
+        let Some(engine) = self.engine.upgrade() else {
+            error!("Engine upgrade failed while answering frame callback");
+            return;
+        };
+
+        let frame_interval = self.frame_interval;
+        engine.run_on_platform_thread(move |engine| {
+            on_vsync(engine, baton, frame_interval);
+        });
+    }
+}
+
+impl FlutterVsyncHandler for DrmVsyncHandler {
+    // Note: This callback is executed on an internal engine-managed thread.
+    fn request_frame_callback(&self, baton: isize) {
+        trace!("[baton: {}] requesting frame callback", baton);
+
+        {
+            let mut state = self.state.lock();
+            if state.is_paused || state.is_flip_pending {
+                state.pending_baton = Some(baton);
+                return;
+            }
+        }
+
+        self.post_vsync(baton);
+    }
+}
+
+fn on_vsync(engine: &FlutterEngine, baton: isize, frame_interval: u64) {
+    let frame_start_time_nanos = FlutterEngine::get_current_time_nanos();
+    let frame_target_time_nanos = frame_start_time_nanos + frame_interval;
+    engine.on_vsync(baton, frame_start_time_nanos, frame_target_time_nanos);
+}
+
+/// Platform handler without a clipboard manager to talk to: the clipboard
+/// is only shared within the application.
+pub struct DrmPlatformHandler {
+    clipboard: Option<String>,
+    request_sender: Sender<DrmApplicationRequest>,
+}
+
+impl DrmPlatformHandler {
+    pub(crate) fn new(request_sender: Sender<DrmApplicationRequest>) -> Self {
+        Self {
+            clipboard: None,
+            request_sender,
+        }
+    }
+}
+
+impl PlatformHandler for DrmPlatformHandler {
+    fn set_application_switcher_description(&mut self, _description: AppSwitcherDescription) {}
+
+    fn set_clipboard_data(&mut self, text: String) -> Result<(), PlatformError> {
+        self.clipboard = Some(text);
+        Ok(())
+    }
+
+    fn get_clipboard_data(&mut self, mime: &str) -> Result<String, PlatformError> {
+        if mime != "text/plain" {
+            return Err(PlatformError::UnsupportedFormat(mime.to_string()));
+        }
+        Ok(self.clipboard.clone().unwrap_or_default())
+    }
+
+    fn exit_application(&mut self, exit_code: i32) -> bool {
+        self.request_sender
+            .send(DrmApplicationRequest::ExitWithCode(exit_code))
+            .is_ok()
+    }
+}
+
+/// The content always covers the whole display: the window can't be moved,
+/// resized or hidden.
+pub struct DrmWindowHandler {
+    request_sender: Sender<DrmApplicationRequest>,
+    /// Of the display the content is scanned out to, which never changes.
+    color_info: ColorInfo,
+}
+
+impl DrmWindowHandler {
+    pub(crate) fn new(
+        request_sender: Sender<DrmApplicationRequest>,
+        color_info: ColorInfo,
+    ) -> Self {
+        Self {
+            request_sender,
+            color_info,
+        }
+    }
+}
+
+impl WindowHandler for DrmWindowHandler {
+    fn close(&mut self) {
+        self.request_sender.send(DrmApplicationRequest::Quit).ok();
+    }
+
+    fn show(&mut self) {}
+
+    fn hide(&mut self) {}
+
+    fn maximize(&mut self) {}
+
+    fn iconify(&mut self) {}
+
+    fn restore(&mut self) {}
+
+    fn is_maximized(&mut self) -> bool {
+        false
+    }
+
+    fn is_iconified(&mut self) -> bool {
+        false
+    }
+
+    fn is_visible(&mut self) -> bool {
+        true
+    }
+
+    fn is_fullscreen(&mut self) -> bool {
+        true
+    }
+
+    fn set_pos(&mut self, _pos: PositionParams) {}
+
+    fn get_pos(&mut self) -> PositionParams {
+        PositionParams { x: 0.0, y: 0.0 }
+    }
+
+    fn start_drag(&mut self) {}
+
+    fn end_drag(&mut self) {}
+
+    fn color_info(&mut self) -> ColorInfo {
+        self.color_info
+    }
+}
+
+/// No cursor is drawn, which suits the touch screens of most devices.
+#[derive(Default)]
+pub struct DrmMouseCursorHandler {}
+
+impl MouseCursorHandler for DrmMouseCursorHandler {
+    fn activate_system_cursor(&mut self, kind: SystemMouseCursor) -> Result<(), MouseCursorError> {
+        trace!("Ignoring {kind:?} cursor");
+        Ok(())
+    }
+}
+
+/// There is no on-screen keyboard to show.
+#[derive(Default)]
+pub struct DrmTextInputHandler {}
+
+impl TextInputHandler for DrmTextInputHandler {
+    fn show(&mut self) {}
+
+    fn hide(&mut self) {}
+}
@@ -0,0 +1,431 @@
+//! Input of the devices attached to the seat, through libinput.
+//!
+//! The devices are opened directly (i.e.: without a session manager), so the
+//! application needs access to them (e.g.: by being in the `input` group).
+//! Absolute positions (e.g.: of touch screens) are mapped to the whole
+//! display, and the relative motion of mice is clamped to it.
+use std::{
+    collections::HashMap,
+    env,
+    fs::OpenOptions,
+    io,
+    os::{fd::OwnedFd, unix::fs::OpenOptionsExt},
+    path::Path,
+};
+
+use dpi::{PhysicalPosition, PhysicalSize};
+use flutter_engine::{
+    ffi::{
+        FlutterKeyEventDeviceType, FlutterKeyEventType, FlutterPointerButtons,
+        FlutterPointerDeviceKind, FlutterPointerEvent, FlutterPointerPhase,
+        FlutterPointerSignalKind, IMPLICIT_VIEW_ID,
+    },
+    plugins::PluginRegistrar,
+    FlutterEngine,
+};
+use flutter_input::{
+    keyboard::{KeyEventFallthrough, XkbKeyEvent, XkbModifiers},
+    pointer::{motion_phase, mouse_button_from_evdev, press_phase, release_phase},
+};
+use flutter_plugins::keyevent::KeyEventPlugin;
+use calloop::channel::Sender;
+use input::{
+    event::{
+        keyboard::{KeyState, KeyboardEventTrait},
+        pointer::{Axis, ButtonState, PointerScrollEvent},
+        touch::{TouchEventPosition, TouchEventSlot},
+        KeyboardEvent, PointerEvent, TouchEvent,
+    },
+    Event, Libinput, LibinputInterface,
+};
+use thiserror::Error;
+use tracing::{debug, trace};
+
+use crate::application::DrmApplicationRequest;
+use xkbcommon::xkb::{self, Keysym};
+
+const SEAT_ENV: &str = "XDG_SEAT";
+const DEFAULT_SEAT: &str = "seat0";
+
+/// Device of the mouse pointer, the touch points use the next ones.
+const MOUSE_DEVICE: i32 = 0;
+
+/// Distance (in physical pixels) scrolled per wheel step, like the GTK
+/// embedder does.
+const SCROLL_DISTANCE_PER_STEP: f64 = 53.0;
+
+struct DrmInputInterface;
+
+impl LibinputInterface for DrmInputInterface {
+    fn open_restricted(&mut self, path: &Path, flags: i32) -> Result<OwnedFd, i32> {
+        let access_mode = flags & libc::O_ACCMODE;
+        OpenOptions::new()
+            .custom_flags(flags)
+            .read(access_mode == libc::O_RDONLY || access_mode == libc::O_RDWR)
+            .write(access_mode == libc::O_WRONLY || access_mode == libc::O_RDWR)
+            .open(path)
+            .map(OwnedFd::from)
+            .map_err(|err| err.raw_os_error().unwrap_or(libc::EIO))
+    }
+
+    fn close_restricted(&mut self, fd: OwnedFd) {
+        drop(fd);
+    }
+}
+
+#[derive(Default)]
+struct MousePointer {
+    position: PhysicalPosition<f64>,
+    buttons: FlutterPointerButtons,
+    is_added: bool,
+}
+
+pub(crate) struct DrmInput {
+    libinput: Libinput,
+    xkb_state: xkb::State,
+    modifiers: XkbModifiers,
+    /// Keysyms of the keys which are down, by evdev code.
+    pressed_keys: HashMap<u32, Keysym>,
+    mouse: MousePointer,
+    /// Positions of the touch points, by seat slot.
+    touches: HashMap<u32, PhysicalPosition<f64>>,
+    display_size: PhysicalSize<u32>,
+}
+
+impl DrmInput {
+    /// Open the devices of the seat (`XDG_SEAT`, or `seat0`). The keymap is
+    /// the one configured through the `XKB_DEFAULT_*` environment variables.
+    pub(crate) fn new(display_size: PhysicalSize<u32>) -> Result<Self, DrmInputError> {
+        let seat = env::var(SEAT_ENV).unwrap_or_else(|_| DEFAULT_SEAT.to_string());
+
+        let mut libinput = Libinput::new_with_udev(DrmInputInterface);
+        libinput
+            .udev_assign_seat(&seat)
+            .map_err(|_| DrmInputError::AssignSeat(seat.clone()))?;
+        debug!("Reading input from {seat}");
+
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            "",
+            "",
+            "",
+            "",
+            None,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .ok_or(DrmInputError::Keymap)?;
+
+        Ok(Self {
+            libinput,
+            xkb_state: xkb::State::new(&keymap),
+            modifiers: Default::default(),
+            pressed_keys: Default::default(),
+            mouse: Default::default(),
+            touches: Default::default(),
+            display_size,
+        })
+    }
+
+    /// Handle used for polling the input events.
+    pub(crate) fn libinput(&self) -> Libinput {
+        self.libinput.clone()
+    }
+
+    /// Forward the pending input events to the engine.
+    pub(crate) fn dispatch(
+        &mut self,
+        engine: &FlutterEngine,
+        plugins: &PluginRegistrar,
+        request_sender: &Sender<DrmApplicationRequest>,
+    ) -> io::Result<()> {
+        self.libinput.dispatch()?;
+
+        let events: Vec<Event> = self.libinput.by_ref().collect();
+        for event in events {
+            match event {
+                Event::Keyboard(KeyboardEvent::Key(event)) => {
+                    let pressed = event.key_state() == KeyState::Pressed;
+                    self.handle_key(engine, plugins, request_sender, event.key(), pressed);
+                }
+                Event::Pointer(event) => self.handle_pointer(engine, event),
+                Event::Touch(event) => self.handle_touch(engine, event),
+                _ => trace!("Ignoring input event {event:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the pointers from the engine, e.g.: before shutting down.
+    pub(crate) fn remove_pointers(&mut self, engine: &FlutterEngine) {
+        if std::mem::take(&mut self.mouse.is_added) {
+            let position = self.mouse.position;
+            self.send_mouse_event(engine, FlutterPointerPhase::Remove, position);
+        }
+
+        for (slot, position) in std::mem::take(&mut self.touches) {
+            send_touch_event(engine, slot, FlutterPointerPhase::Cancel, position);
+            send_touch_event(engine, slot, FlutterPointerPhase::Remove, position);
+        }
+    }
+
+    fn handle_key(
+        &mut self,
+        engine: &FlutterEngine,
+        plugins: &PluginRegistrar,
+        request_sender: &Sender<DrmApplicationRequest>,
+        raw_code: u32,
+        pressed: bool,
+    ) {
+        // Add 8 to the evdev code to determine the xkb keycode.
+        let keycode = xkb::Keycode::new(raw_code + 8);
+
+        // The keysym and text are the ones of the state *before* the key
+        // (e.g.: `Shift`) is applied.
+        let keysym = self.xkb_state.key_get_one_sym(keycode);
+        let utf8 = Some(self.xkb_state.key_get_utf8(keycode)).filter(|utf8| !utf8.is_empty());
+
+        let direction = if pressed {
+            xkb::KeyDirection::Down
+        } else {
+            xkb::KeyDirection::Up
+        };
+        self.xkb_state.update_key(keycode, direction);
+        self.update_modifiers();
+
+        let (kind, latched_keydown) = if pressed {
+            let kind = match self.pressed_keys.insert(raw_code, keysym) {
+                Some(_) => FlutterKeyEventType::Repeat,
+                None => FlutterKeyEventType::Down,
+            };
+            (kind, None)
+        } else {
+            let Some(keydown) = self.pressed_keys.remove(&raw_code) else {
+                // The key was already down when the device was opened.
+                return;
+            };
+            (FlutterKeyEventType::Up, Some(keydown))
+        };
+
+        let event = XkbKeyEvent {
+            device_type: FlutterKeyEventDeviceType::Keyboard,
+            raw_code,
+            keysym,
+            utf8,
+            kind,
+            modifiers: self.modifiers,
+            synthesized: false,
+            latched_keydown,
+        };
+
+        if !pressed {
+            engine.send_key_event(event.clone().into());
+            // See `SctkApplicationState::send_key_event` on why the legacy
+            // `flutter/keyevent` messages are still needed.
+            plugins.with_plugin(|keyevent: &KeyEventPlugin| {
+                keyevent.key_action(event.into());
+            });
+            return;
+        }
+
+        // Presses which the framework doesn't handle (e.g.: as a shortcut)
+        // fall through to the text being edited, once both paths replied.
+        let request = DrmApplicationRequest::EditText {
+            keysym,
+            utf8: event.utf8.clone(),
+            select: self.modifiers.shift,
+        };
+        let request_sender = request_sender.clone();
+        let fallthrough = KeyEventFallthrough::new(move || {
+            let _ = request_sender.send(request);
+        });
+
+        let key_data_fallthrough = fallthrough.clone();
+        engine.send_key_event_with_callback(event.clone().into(), move |handled| {
+            key_data_fallthrough.respond(handled)
+        });
+        plugins.with_plugin(|keyevent: &KeyEventPlugin| {
+            keyevent.key_action_with_reply(event.into(), move |handled| {
+                fallthrough.respond(handled)
+            });
+        });
+    }
+
+    fn update_modifiers(&mut self) {
+        let is_active = |name: &str| {
+            self.xkb_state
+                .mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE)
+        };
+
+        self.modifiers = XkbModifiers {
+            ctrl: is_active(xkb::MOD_NAME_CTRL),
+            alt: is_active(xkb::MOD_NAME_ALT),
+            shift: is_active(xkb::MOD_NAME_SHIFT),
+            caps_lock: is_active(xkb::MOD_NAME_CAPS),
+            logo: is_active(xkb::MOD_NAME_LOGO),
+            num_lock: is_active(xkb::MOD_NAME_NUM),
+        };
+    }
+
+    fn handle_pointer(&mut self, engine: &FlutterEngine, event: PointerEvent) {
+        let PhysicalSize { width, height } = self.display_size;
+
+        match event {
+            PointerEvent::Motion(event) => {
+                let position = PhysicalPosition::new(
+                    (self.mouse.position.x + event.dx()).clamp(0.0, f64::from(width - 1)),
+                    (self.mouse.position.y + event.dy()).clamp(0.0, f64::from(height - 1)),
+                );
+                self.move_mouse(engine, position);
+            }
+            PointerEvent::MotionAbsolute(event) => {
+                let position = PhysicalPosition::new(
+                    event.absolute_x_transformed(width),
+                    event.absolute_y_transformed(height),
+                );
+                self.move_mouse(engine, position);
+            }
+            PointerEvent::Button(event) => {
+                self.ensure_mouse_added(engine);
+
+                let button = event.button();
+                let phase = match event.button_state() {
+                    ButtonState::Pressed => {
+                        self.mouse.buttons.insert(mouse_button_from_evdev(button));
+                        press_phase(self.mouse.buttons, button)
+                    }
+                    ButtonState::Released => {
+                        self.mouse.buttons.remove(mouse_button_from_evdev(button));
+                        release_phase(self.mouse.buttons)
+                    }
+                };
+                self.send_mouse_event(engine, phase, self.mouse.position);
+            }
+            PointerEvent::ScrollWheel(event) => {
+                let delta = |axis| match event.has_axis(axis) {
+                    true => event.scroll_value_v120(axis) / 120.0 * SCROLL_DISTANCE_PER_STEP,
+                    false => 0.0,
+                };
+                self.scroll(engine, (delta(Axis::Horizontal), delta(Axis::Vertical)));
+            }
+            PointerEvent::ScrollFinger(event) => {
+                let delta = |axis| match event.has_axis(axis) {
+                    true => event.scroll_value(axis),
+                    false => 0.0,
+                };
+                self.scroll(engine, (delta(Axis::Horizontal), delta(Axis::Vertical)));
+            }
+            _ => trace!("Ignoring pointer event {event:?}"),
+        }
+    }
+
+    fn ensure_mouse_added(&mut self, engine: &FlutterEngine) {
+        if !self.mouse.is_added {
+            self.mouse.is_added = true;
+            self.send_mouse_event(engine, FlutterPointerPhase::Add, self.mouse.position);
+        }
+    }
+
+    fn move_mouse(&mut self, engine: &FlutterEngine, position: PhysicalPosition<f64>) {
+        self.ensure_mouse_added(engine);
+        self.mouse.position = position;
+        self.send_mouse_event(engine, motion_phase(self.mouse.buttons), position);
+    }
+
+    fn scroll(&mut self, engine: &FlutterEngine, delta: (f64, f64)) {
+        self.ensure_mouse_added(engine);
+        engine.send_pointer_event(FlutterPointerEvent::new(
+            MOUSE_DEVICE,
+            motion_phase(self.mouse.buttons),
+            self.mouse.position,
+            FlutterPointerSignalKind::Scroll,
+            delta,
+            FlutterPointerDeviceKind::Mouse,
+            self.mouse.buttons,
+            IMPLICIT_VIEW_ID,
+        ));
+    }
+
+    fn send_mouse_event(
+        &self,
+        engine: &FlutterEngine,
+        phase: FlutterPointerPhase,
+        position: PhysicalPosition<f64>,
+    ) {
+        engine.send_pointer_event(FlutterPointerEvent::new(
+            MOUSE_DEVICE,
+            phase,
+            position,
+            FlutterPointerSignalKind::None,
+            (0.0, 0.0),
+            FlutterPointerDeviceKind::Mouse,
+            self.mouse.buttons,
+            IMPLICIT_VIEW_ID,
+        ));
+    }
+
+    fn handle_touch(&mut self, engine: &FlutterEngine, event: TouchEvent) {
+        let PhysicalSize { width, height } = self.display_size;
+
+        match event {
+            TouchEvent::Down(event) => {
+                let slot = event.seat_slot();
+                let position =
+                    PhysicalPosition::new(event.x_transformed(width), event.y_transformed(height));
+                self.touches.insert(slot, position);
+                send_touch_event(engine, slot, FlutterPointerPhase::Add, position);
+                send_touch_event(engine, slot, FlutterPointerPhase::Down, position);
+            }
+            TouchEvent::Motion(event) => {
+                let slot = event.seat_slot();
+                let position =
+                    PhysicalPosition::new(event.x_transformed(width), event.y_transformed(height));
+                if let Some(touch) = self.touches.get_mut(&slot) {
+                    *touch = position;
+                    send_touch_event(engine, slot, FlutterPointerPhase::Move, position);
+                }
+            }
+            TouchEvent::Up(event) => {
+                let slot = event.seat_slot();
+                if let Some(position) = self.touches.remove(&slot) {
+                    send_touch_event(engine, slot, FlutterPointerPhase::Up, position);
+                    send_touch_event(engine, slot, FlutterPointerPhase::Remove, position);
+                }
+            }
+            TouchEvent::Cancel(_) => {
+                for (slot, position) in std::mem::take(&mut self.touches) {
+                    send_touch_event(engine, slot, FlutterPointerPhase::Cancel, position);
+                    send_touch_event(engine, slot, FlutterPointerPhase::Remove, position);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn send_touch_event(
+    engine: &FlutterEngine,
+    slot: u32,
+    phase: FlutterPointerPhase,
+    position: PhysicalPosition<f64>,
+) {
+    engine.send_pointer_event(FlutterPointerEvent::new(
+        MOUSE_DEVICE + 1 + slot as i32,
+        phase,
+        position,
+        FlutterPointerSignalKind::None,
+        (0.0, 0.0),
+        FlutterPointerDeviceKind::Touch,
+        FlutterPointerButtons::default(),
+        IMPLICIT_VIEW_ID,
+    ));
+}
+
+#[derive(Error, Debug)]
+pub enum DrmInputError {
+    #[error("Failed to assign the {0} seat to libinput")]
+    AssignSeat(String),
+
+    #[error("Failed to compile the keymap (see the XKB_DEFAULT_* environment variables)")]
+    Keymap,
+}
@@ -0,0 +1,35 @@
+//! Flutter embedder driving a display directly through DRM/KMS, without a
+//! display server (e.g.: on embedded devices). Rendering goes through
+//! GBM/EGL, and the input devices are read with libinput.
+//!
+//! The devices are opened directly, without a seat manager (i.e.: logind or
+//! seatd), so running as root is a requirement. The group memberships (e.g.:
+//! `video` and `input`) only grant access to the device nodes, while setting
+//! the mode requires being the DRM master, which is otherwise passed over by
+//! the seat manager. No other process may be the DRM master (i.e.: no
+//! compositor must be running), and VT switching isn't handled.
+//!
+//! Known limitations:
+//! - Keys don't repeat, since libinput doesn't report the repeats of the
+//!   kernel and no repeat timer is run.
+//! - No cursor is drawn (there is no hardware cursor plane or software
+//!   cursor), which suits touch screens but not mice.
+//!
+//! The backend is only built by enabling the `flutter-drm` feature of
+//! `flutter-runner`, as it depends on Linux-only system libraries (libinput,
+//! libudev, GBM). It isn't one of the default members of the workspace.
+#![deny(warnings)]
+
+mod application;
+mod device;
+mod handler;
+mod input;
+mod scanout;
+
+pub use application::{
+    DrmApplication, DrmApplicationCreateError, DrmApplicationHandle, DrmApplicationRequest,
+    DrmApplicationRunError,
+};
+pub use device::DrmOutputError;
+pub use input::DrmInputError;
+pub use scanout::DrmScanoutError;
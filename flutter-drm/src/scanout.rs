@@ -0,0 +1,322 @@
+//! Scanout of the frames rendered into a GBM surface, using atomic commits.
+//!
+//! Each presented frame is locked from the surface and flipped to on the next
+//! vblank. At most one flip is pending at any time: presenting a frame waits
+//! for the previous flip to complete, which throttles the raster thread to
+//! the refresh rate of the display. The first commit also sets the mode.
+use std::{
+    ffi::c_void,
+    io,
+    num::NonZeroU32,
+    os::fd::{AsFd, OwnedFd},
+    ptr::NonNull,
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use dpi::PhysicalSize;
+use drm::control::{
+    atomic::AtomicModeReq, framebuffer, property, AtomicCommitFlags, Device as ControlDevice, Event,
+};
+use flutter_glutin::{
+    builder::{self, ContextBuildError, ContextBuilder},
+    context::{Context, ResourceContext},
+};
+use gbm::{AsRaw, BufferObject, BufferObjectFlags, Format};
+use glutin::{
+    config::Config,
+    display::{Display, DisplayApiPreference},
+    prelude::GlDisplay,
+};
+use raw_window_handle::{GbmDisplayHandle, GbmWindowHandle, RawDisplayHandle, RawWindowHandle};
+use thiserror::Error;
+use tracing::{error, warn};
+
+use crate::device::{Card, KmsOutput, PropertyHandles};
+
+/// Format of the scanned out buffers. The alpha channel (if any) of the
+/// content is ignored, since there is nothing behind the primary plane.
+const SCANOUT_FORMAT: Format = Format::Xrgb8888;
+
+/// Upper bound of the wait for a page flip, which only happens when the
+/// display stops responding (e.g.: it was unplugged).
+const PAGE_FLIP_TIMEOUT: Duration = Duration::from_millis(100);
+
+type ScanoutBuffer = (BufferObject<()>, framebuffer::Handle);
+
+struct ScanoutProperties {
+    connector: PropertyHandles,
+    crtc: PropertyHandles,
+    plane: PropertyHandles,
+}
+
+struct DrmScanoutState {
+    gbm: gbm::Device<Card>,
+    surface: gbm::Surface<()>,
+    output: KmsOutput,
+    properties: ScanoutProperties,
+    needs_modeset: bool,
+    /// Buffer currently scanned out.
+    front: Option<ScanoutBuffer>,
+    /// Buffer waiting for the page flip.
+    pending: Option<ScanoutBuffer>,
+}
+
+// SAFETY: The GBM objects aren't bound to a thread, and the lock serializes
+// their use.
+unsafe impl Send for DrmScanoutState {}
+
+pub(crate) struct DrmScanout {
+    state: Mutex<DrmScanoutState>,
+    flip_done: Condvar,
+}
+
+impl DrmScanout {
+    pub(crate) fn new(card: Card, output: KmsOutput) -> Result<Self, DrmScanoutError> {
+        let properties = ScanoutProperties {
+            connector: PropertyHandles::load(&card, output.connector)?,
+            crtc: PropertyHandles::load(&card, output.crtc)?,
+            plane: PropertyHandles::load(&card, output.plane)?,
+        };
+
+        let gbm = gbm::Device::new(card)?;
+        let (width, height) = output.mode.size();
+        let surface = gbm.create_surface::<()>(
+            width.into(),
+            height.into(),
+            SCANOUT_FORMAT,
+            BufferObjectFlags::SCANOUT | BufferObjectFlags::RENDERING,
+        )?;
+
+        Ok(Self {
+            state: Mutex::new(DrmScanoutState {
+                gbm,
+                surface,
+                output,
+                properties,
+                needs_modeset: true,
+                front: None,
+                pending: None,
+            }),
+            flip_done: Condvar::new(),
+        })
+    }
+
+    pub(crate) fn size(&self) -> PhysicalSize<u32> {
+        let (width, height) = self.state.lock().unwrap().output.mode.size();
+        PhysicalSize::new(width.into(), height.into())
+    }
+
+    pub(crate) fn physical_size_mm(&self) -> Option<(u32, u32)> {
+        self.state.lock().unwrap().output.physical_size
+    }
+
+    pub(crate) fn frame_interval(&self) -> u64 {
+        self.state.lock().unwrap().output.frame_interval()
+    }
+
+    /// Duplicate of the card file descriptor, for polling the page flip
+    /// events.
+    pub(crate) fn event_fd(&self) -> io::Result<OwnedFd> {
+        self.state.lock().unwrap().gbm.as_fd().try_clone_to_owned()
+    }
+
+    /// Create the contexts rendering into the GBM surface.
+    pub(crate) fn create_contexts(
+        &self,
+        robust: bool,
+        debug_output: bool,
+    ) -> Result<(Context, ResourceContext), DrmScanoutError> {
+        let state = self.state.lock().unwrap();
+
+        let display_handle = NonNull::new(state.gbm.as_raw() as *mut c_void)
+            .map(|device| RawDisplayHandle::Gbm(GbmDisplayHandle::new(device)))
+            .ok_or(DrmScanoutError::InvalidGbmObject)?;
+        let window_handle = NonNull::new(state.surface.as_raw() as *mut c_void)
+            .map(|surface| RawWindowHandle::Gbm(GbmWindowHandle::new(surface)))
+            .ok_or(DrmScanoutError::InvalidGbmObject)?;
+
+        let display = unsafe { Display::new(display_handle, DisplayApiPreference::Egl)? };
+        let config = choose_config(&display, window_handle)?;
+
+        let (width, height) = state.output.mode.size();
+        let size = NonZeroU32::new(width.into())
+            .zip(NonZeroU32::new(height.into()))
+            .map(|(width, height)| PhysicalSize::new(width, height));
+
+        // The swap interval has no effect on GBM surfaces, the frames are
+        // throttled by the page flips instead.
+        Ok(ContextBuilder::new()
+            .with_raw_window_handle(window_handle)
+            .with_config(config)
+            .with_size(size)
+            .with_robustness(robust)
+            .with_debug_output(debug_output)
+            .build()?)
+    }
+
+    /// Scan out the frame which was just swapped, once the previous flip
+    /// completed.
+    pub(crate) fn present(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        let (mut state, timeout) = self
+            .flip_done
+            .wait_timeout_while(state, PAGE_FLIP_TIMEOUT, |state| state.pending.is_some())
+            .unwrap();
+        if timeout.timed_out() {
+            warn!("Timed out waiting for a page flip");
+        }
+
+        match state.flip() {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Failed to present frame: {err}");
+                false
+            }
+        }
+    }
+
+    /// Handle the pending DRM events. Returns whether a page flip completed.
+    pub(crate) fn dispatch_events(&self) -> io::Result<bool> {
+        let mut state = self.state.lock().unwrap();
+
+        let mut has_flipped = false;
+        for event in state.gbm.receive_events()? {
+            if let Event::PageFlip(event) = event {
+                has_flipped |= event.crtc == state.output.crtc;
+            }
+        }
+
+        if has_flipped {
+            state.complete_flip();
+            self.flip_done.notify_all();
+        }
+        Ok(has_flipped)
+    }
+}
+
+impl DrmScanoutState {
+    fn flip(&mut self) -> io::Result<()> {
+        let bo = unsafe { self.surface.lock_front_buffer() }
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        let framebuffer = self.gbm.add_framebuffer(&bo, 24, 32)?;
+
+        if let Err(err) = self.commit(framebuffer) {
+            let _ = self.gbm.destroy_framebuffer(framebuffer);
+            return Err(err);
+        }
+
+        // A flip still pending (after a timeout) is superseded.
+        if let Some((_, framebuffer)) = self.pending.replace((bo, framebuffer)) {
+            let _ = self.gbm.destroy_framebuffer(framebuffer);
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self, framebuffer: framebuffer::Handle) -> io::Result<()> {
+        let output = &self.output;
+        let properties = &self.properties;
+        let (width, height) = output.mode.size();
+
+        let mut request = AtomicModeReq::new();
+        let mut flags = AtomicCommitFlags::PAGE_FLIP_EVENT | AtomicCommitFlags::NONBLOCK;
+
+        if self.needs_modeset {
+            let mode_blob = self.gbm.create_property_blob(&output.mode)?;
+            request.add_property(
+                output.connector,
+                properties.connector.get("CRTC_ID")?,
+                property::Value::CRTC(Some(output.crtc)),
+            );
+            request.add_property(output.crtc, properties.crtc.get("MODE_ID")?, mode_blob);
+            request.add_property(
+                output.crtc,
+                properties.crtc.get("ACTIVE")?,
+                property::Value::Boolean(true),
+            );
+            flags |= AtomicCommitFlags::ALLOW_MODESET;
+        }
+
+        let plane = output.plane;
+        let plane_properties = [
+            ("FB_ID", property::Value::Framebuffer(Some(framebuffer))),
+            ("CRTC_ID", property::Value::CRTC(Some(output.crtc))),
+            // The source coordinates are in 16.16 fixed point.
+            ("SRC_X", property::Value::UnsignedRange(0)),
+            ("SRC_Y", property::Value::UnsignedRange(0)),
+            (
+                "SRC_W",
+                property::Value::UnsignedRange(u64::from(width) << 16),
+            ),
+            (
+                "SRC_H",
+                property::Value::UnsignedRange(u64::from(height) << 16),
+            ),
+            ("CRTC_X", property::Value::SignedRange(0)),
+            ("CRTC_Y", property::Value::SignedRange(0)),
+            ("CRTC_W", property::Value::UnsignedRange(width.into())),
+            ("CRTC_H", property::Value::UnsignedRange(height.into())),
+        ];
+        for (name, value) in plane_properties {
+            request.add_property(plane, properties.plane.get(name)?, value);
+        }
+
+        // The mode is set again with the next frame if the commit failed.
+        self.gbm.atomic_commit(flags, request)?;
+        self.needs_modeset = false;
+        Ok(())
+    }
+
+    fn complete_flip(&mut self) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+
+        // The previous buffer is released to the surface once it is no
+        // longer scanned out.
+        if let Some((_, framebuffer)) = self.front.replace(pending) {
+            let _ = self.gbm.destroy_framebuffer(framebuffer);
+        }
+    }
+}
+
+/// Pick a config whose visual matches the format of the scanned out buffers,
+/// otherwise the EGL surface can't be created.
+fn choose_config(
+    display: &Display,
+    window_handle: RawWindowHandle,
+) -> Result<Config, DrmScanoutError> {
+    let template = builder::config_template()
+        .compatible_with_native_window(window_handle)
+        .build();
+    let configs: Vec<Config> = unsafe { display.find_configs(template)? }.collect();
+
+    configs
+        .iter()
+        .find(|config| match config {
+            Config::Egl(config) => config.native_visual() == SCANOUT_FORMAT as u32,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        })
+        .cloned()
+        .or_else(|| builder::choose_config(configs.into_iter()))
+        .ok_or(DrmScanoutError::NoAvailableConfigFound)
+}
+
+#[derive(Error, Debug)]
+pub enum DrmScanoutError {
+    #[error("Invalid GBM device or surface")]
+    InvalidGbmObject,
+
+    #[error("No available config was found")]
+    NoAvailableConfigFound,
+
+    #[error(transparent)]
+    ContextBuildError(#[from] ContextBuildError),
+
+    #[error(transparent)]
+    GlutinError(#[from] glutin::error::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
@@ -2,15 +2,88 @@ use std::ffi::{c_void, CStr};
 
 use dpi::PhysicalSize;
 
+/// An area of a frame, in physical pixels, relative to the top-left corner of
+/// the surface.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DamageRect {
+    pub left: f64,
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+}
+
+impl DamageRect {
+    pub fn new(left: f64, top: f64, right: f64, bottom: f64) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.right <= self.left || self.bottom <= self.top
+    }
+
+    /// The smallest rectangle containing both |self| and |other|.
+    pub fn union(&self, other: &DamageRect) -> DamageRect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+
+        DamageRect::new(
+            self.left.min(other.left),
+            self.top.min(other.top),
+            self.right.max(other.right),
+            self.bottom.max(other.bottom),
+        )
+    }
+}
+
+/// Information about a frame rendered into the window surface.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlutterPresentInfo {
+    /// Id of the framebuffer the frame was rendered into.
+    pub fbo_id: u32,
+    /// Areas which changed since the previous frame, i.e. what the compositor
+    /// needs to update.
+    pub frame_damage: Vec<DamageRect>,
+    /// Areas which were repainted in the framebuffer, i.e. the frame damage
+    /// along with the damage the framebuffer accumulated since it was last
+    /// presented.
+    pub buffer_damage: Vec<DamageRect>,
+}
+
 pub trait FlutterOpenGLHandler {
     fn present(&self) -> bool;
 
+    /// Present a frame along with its damage. The default implementation
+    /// ignores the damage.
+    fn present_with_info(&self, _info: FlutterPresentInfo) -> bool {
+        self.present()
+    }
+
     fn make_current(&self) -> bool;
 
     fn clear_current(&self) -> bool;
 
     fn fbo_with_frame_info_callback(&self, size: PhysicalSize<u32>) -> u32;
 
+    /// The area of the |fbo_id| framebuffer which is out of date, i.e. which
+    /// changed in the frames presented since this framebuffer was last
+    /// presented (see `EGL_EXT_buffer_age`). The engine then only repaints
+    /// that area and the damage of the next frame.
+    ///
+    /// Returns `None` if the content of the framebuffer is unknown, which
+    /// forces a full repaint. This is the default.
+    fn existing_damage(&self, _fbo_id: u32) -> Option<DamageRect> {
+        None
+    }
+
     fn make_resource_current(&self) -> bool;
 
     fn gl_proc_resolver(&self, proc: &CStr) -> *mut c_void;
@@ -31,6 +31,8 @@ fn main() {
         .default_enum_style(EnumVariation::Rust {
             non_exhaustive: false,
         })
+        // Sent as a combination of flags.
+        .bitfield_enum("FlutterAccessibilityFeature")
         .clang_args(&clang_args)
         .generate()
         .expect("Unable to generate bindings");
@@ -0,0 +1,101 @@
+//! Stress test of the thread policies: a simulated raster thread renders
+//! frames on a CPU saturated by busy threads (standing in for the other
+//! processes of an embedded device), first with the default scheduling, then
+//! with the policy given to `FlutterEngineBuilder::with_thread_config`.
+//!
+//! Run it with the CAP_SYS_NICE capability (e.g.: as root) for the real-time
+//! policy to apply, otherwise the report lists what was not applied:
+//!
+//! `cargo run --release -p flutter-engine --example thread_pinning -- [cpu]`
+use std::{
+    env,
+    hint::black_box,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use flutter_engine::threads::{RealtimeScheduler, ThreadPolicy};
+
+const FRAMES: usize = 300;
+const FRAME_INTERVAL: Duration = Duration::from_nanos(16_666_667);
+const FRAME_WORK: Duration = Duration::from_millis(4);
+const BUSY_THREADS: usize = 4;
+
+fn main() {
+    let cpu: u32 = env::args()
+        .nth(1)
+        .map_or(0, |cpu| cpu.parse().expect("Invalid CPU"));
+    let cpu_mask = 1 << cpu;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let busy_threads: Vec<_> = (0..BUSY_THREADS)
+        .map(|_| {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                ThreadPolicy::default()
+                    .with_affinity_mask(cpu_mask)
+                    .apply_to_current_thread();
+                while !stop.load(Ordering::Relaxed) {
+                    black_box(0);
+                }
+            })
+        })
+        .collect();
+
+    let default_policy = ThreadPolicy::default().with_affinity_mask(cpu_mask);
+    let pinned_policy = ThreadPolicy::default()
+        .with_affinity_mask(cpu_mask)
+        .with_nice(-10)
+        .with_realtime(RealtimeScheduler::Fifo, 50);
+
+    for (name, policy) in [("default", default_policy), ("pinned", pinned_policy)] {
+        let (report, mut frame_times) = thread::spawn(move || {
+            let report = policy.apply_to_current_thread();
+            (report, render_frames())
+        })
+        .join()
+        .unwrap();
+
+        frame_times.sort();
+        println!(
+            "{name}: median {:?}, p99 {:?}, worst {:?} (applied {:?}, not applied {:?})",
+            frame_times[FRAMES / 2],
+            frame_times[FRAMES * 99 / 100],
+            frame_times[FRAMES - 1],
+            report.applied,
+            report.failed,
+        );
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for thread in busy_threads {
+        thread.join().unwrap();
+    }
+}
+
+/// Time taken by each frame, from its vsync to the end of its work.
+fn render_frames() -> Vec<Duration> {
+    let start = Instant::now();
+    (0..FRAMES as u32)
+        .map(|frame| {
+            let vsync = start + FRAME_INTERVAL * frame;
+            thread::sleep(vsync.saturating_duration_since(Instant::now()));
+
+            // Burn the CPU for the frame work, as the raster thread would.
+            let mut cpu_time = Duration::ZERO;
+            let mut last = Instant::now();
+            while cpu_time < FRAME_WORK {
+                let now = Instant::now();
+                // Time slices longer than a millisecond went to other threads.
+                cpu_time += (now - last).min(Duration::from_millis(1));
+                last = now;
+            }
+
+            vsync.elapsed()
+        })
+        .collect()
+}
@@ -0,0 +1,15 @@
+//! Decode arbitrary platform messages with the standard codec, which must
+//! fail with an error instead of panicking, overflowing the stack or
+//! allocating more than the decode limits.
+//!
+//! Run with `cargo fuzz run standard_codec` from `flutter-engine/`.
+#![no_main]
+
+use flutter_engine::codec::{MessageCodec, MethodCodec, STANDARD_CODEC};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|buf: &[u8]| {
+    let _ = STANDARD_CODEC.decode_message(buf);
+    let _ = STANDARD_CODEC.decode_method_call(buf);
+    let _ = STANDARD_CODEC.decode_envelope(buf);
+});
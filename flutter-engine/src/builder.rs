@@ -2,9 +2,22 @@ use parking_lot::Mutex;
 
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
+use tracing::{debug, warn};
+
+use crate::fake_time::FakeTimeBase;
+use crate::prepare::{self, PrepareError, PrepareHandle, PrepareReport};
 use crate::tasks::TaskRunnerHandler;
-use crate::{CreateError, FlutterEngine, FlutterVsyncHandler};
+use crate::threads::ThreadConfig;
+use crate::{CreateError, FlutterEngine, FlutterVsyncHandler, IsolateExit};
+
+pub(crate) type RootIsolateCreateCallback = Box<dyn Fn() + Send + Sync>;
+pub(crate) type RootIsolateShutdownCallback = Box<dyn FnOnce(IsolateExit) + Send>;
+pub(crate) type LogMessageCallback = Box<dyn Fn(&str, &str) + Send + Sync>;
+
+const VM_SERVICE_PORT_SWITCHES: [&str; 2] = ["--vm-service-port=", "--observatory-port="];
+const DISABLE_SERVICE_AUTH_CODES_SWITCH: &str = "--disable-service-auth-codes";
 
 pub struct FlutterEngineBuilder {
     pub(crate) platform_handler: Option<Arc<dyn TaskRunnerHandler + Send + Sync>>,
@@ -12,8 +25,16 @@ pub struct FlutterEngineBuilder {
     pub(crate) compositor_enabled: bool,
     pub(crate) assets: PathBuf,
     pub(crate) icu_data: PathBuf,
+    pub(crate) icu_data_embedded: bool,
     pub(crate) persistent_cache: PathBuf,
     pub(crate) args: Vec<String>,
+    pub(crate) vm_service_port: Option<u16>,
+    pub(crate) vm_service_auth_codes_disabled: bool,
+    pub(crate) root_isolate_create_callback: Option<RootIsolateCreateCallback>,
+    pub(crate) root_isolate_shutdown_callback: Option<RootIsolateShutdownCallback>,
+    pub(crate) log_message_callback: Option<LogMessageCallback>,
+    pub(crate) fake_time_base: Option<FakeTimeBase>,
+    pub(crate) thread_config: ThreadConfig,
 }
 
 impl FlutterEngineBuilder {
@@ -25,8 +46,16 @@ impl FlutterEngineBuilder {
             compositor_enabled: false,
             assets: Default::default(),
             icu_data: Default::default(),
+            icu_data_embedded: false,
             persistent_cache: Default::default(),
             args: vec![],
+            vm_service_port: None,
+            vm_service_auth_codes_disabled: false,
+            root_isolate_create_callback: None,
+            root_isolate_shutdown_callback: None,
+            log_message_callback: None,
+            fake_time_base: None,
+            thread_config: Default::default(),
         }
     }
 
@@ -61,6 +90,15 @@ impl FlutterEngineBuilder {
         self
     }
 
+    /// Use the ICU data embedded in the engine library when the ICU data path
+    /// is not set or does not exist. Only engine builds with embedded ICU data
+    /// (i.e.: built with `icu_use_data_file = false`) support this, others are
+    /// unable to shape text.
+    pub fn with_embedded_icu_data(mut self, embedded: bool) -> Self {
+        self.icu_data_embedded = embedded;
+        self
+    }
+
     pub fn with_persistent_cache_path(mut self, path: PathBuf) -> Self {
         self.persistent_cache = path;
         self
@@ -78,7 +116,210 @@ impl FlutterEngineBuilder {
         self
     }
 
+    /// Port the Dart VM service (used by the debugger and DevTools) binds to.
+    /// A port of `0` picks a random one, which is the default. The VM service
+    /// is only available in debug and profile builds.
+    pub fn with_vm_service_port(mut self, port: u16) -> Self {
+        self.vm_service_port = Some(port);
+        self
+    }
+
+    /// Disable the authentication code which is otherwise part of the VM
+    /// service URI.
+    ///
+    /// The VM service allows executing arbitrary code in the application.
+    /// Without the authentication code, any local process (or any host able
+    /// to reach the port) can connect to it just by guessing the port. Only
+    /// disable it for debugging setups on trusted machines and networks.
+    pub fn with_vm_service_auth_codes_disabled(mut self, disabled: bool) -> Self {
+        self.vm_service_auth_codes_disabled = disabled;
+        self
+    }
+
+    /// Invoke |callback| once the root isolate is created, right before the
+    /// Dart entrypoint runs. It is invoked on the UI thread, and again after
+    /// each hot restart.
+    pub fn with_root_isolate_create_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.root_isolate_create_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Invoke |callback| on the platform thread once the root isolate has
+    /// shut down along with the engine (see [`FlutterEngine::shutdown`]). The
+    /// [`IsolateExit`] tells whether Dart requested the exit, and with which
+    /// exit code.
+    pub fn with_root_isolate_shutdown_callback<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(IsolateExit) + Send + 'static,
+    {
+        self.root_isolate_shutdown_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Invoke |callback| with the messages logged by Dart (e.g.: `print`, and
+    /// the errors reported by the framework) along with their tag, instead of
+    /// the engine printing them to the standard output. It is invoked on an
+    /// engine thread, and must not block.
+    pub fn with_log_message_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str, &str) + Send + Sync + 'static,
+    {
+        self.log_message_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Replace the timestamps of the vsync events with the ones of |time_base|,
+    /// so that the Dart animations see the same time on every run (e.g.: for
+    /// golden tests). Each vsync event advances the time by exactly one frame
+    /// interval, whatever the actual display refresh rate.
+    ///
+    /// Only applies to the frames scheduled through the vsync handler: the
+    /// internal timer of the engine (without vsync handler) uses the real
+    /// time.
+    pub fn with_fake_time_base(mut self, time_base: FakeTimeBase) -> Self {
+        self.fake_time_base = Some(time_base);
+        self
+    }
+
+    /// Pin the engine threads to specific CPUs, and adjust their scheduling
+    /// (e.g.: on embedded devices sharing the CPUs with real-time processes).
+    /// The policies are applied by each engine thread when it starts, and
+    /// what could not be applied (e.g.: without `CAP_SYS_NICE`) is logged
+    /// and reported by [`FlutterEngine::thread_policy_reports`].
+    ///
+    /// The engine threads are left untouched by default. See the
+    /// [`crate::threads`] module for the limitations.
+    pub fn with_thread_config(mut self, config: ThreadConfig) -> Self {
+        self.thread_config = config;
+        self
+    }
+
+    /// Read the engine artifacts (kernel blob, AOT library and ICU data) on a
+    /// background thread so that they are in the page cache by the time the
+    /// engine runs, and validate their headers. This waits for at most
+    /// `timeout`, after which the remaining artifacts keep being read in the
+    /// background.
+    pub fn prepare(&self, timeout: Duration) -> Result<PrepareReport, PrepareError> {
+        self.prepare_in_background().wait(timeout)
+    }
+
+    /// Same as [`Self::prepare`], but returns immediately. This allows reading
+    /// the artifacts while other startup work (e.g.: connecting to the display
+    /// server) is in progress. The asset path, ICU data path and arguments
+    /// must already be set.
+    pub fn prepare_in_background(&self) -> PrepareHandle {
+        // Missing files are reported, unless the embedded ICU data is used.
+        let icu_data = (self.icu_data.is_file() || !self.icu_data_embedded)
+            .then_some(self.icu_data.as_path());
+        let artifacts = prepare::artifacts(&self.assets, icu_data, &self.args);
+        prepare::start(artifacts)
+    }
+
     pub fn build(self) -> Result<FlutterEngine, CreateError> {
         FlutterEngine::new(self)
     }
+
+    /// ICU data file passed to the engine, or `None` to use the embedded ICU
+    /// data.
+    pub(crate) fn resolve_icu_data(&self) -> Result<Option<PathBuf>, CreateError> {
+        if self.icu_data.is_file() {
+            return Ok(Some(self.icu_data.clone()));
+        }
+
+        if self.icu_data_embedded {
+            if !self.icu_data.as_os_str().is_empty() {
+                debug!(
+                    "ICU data file {} not found, using the embedded ICU data",
+                    self.icu_data.display()
+                );
+            }
+            return Ok(None);
+        }
+
+        if self.icu_data.as_os_str().is_empty() {
+            Err(CreateError::NoIcuData)
+        } else {
+            Err(CreateError::IcuDataNotFound(self.icu_data.clone()))
+        }
+    }
+
+    /// Engine switches matching the typed VM service options.
+    pub(crate) fn vm_service_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(port) = self.vm_service_port {
+            if self
+                .args
+                .iter()
+                .any(|arg| VM_SERVICE_PORT_SWITCHES.iter().any(|s| arg.starts_with(s)))
+            {
+                warn!("The VM service port is set both as a raw argument and an option");
+            }
+            args.push(format!("--vm-service-port={}", port));
+        }
+
+        if self.vm_service_auth_codes_disabled
+            && !self.args.iter().any(|arg| arg == DISABLE_SERVICE_AUTH_CODES_SWITCH)
+        {
+            args.push(DISABLE_SERVICE_AUTH_CODES_SWITCH.into());
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::FlutterEngineBuilder;
+    use crate::CreateError;
+
+    #[test]
+    fn maps_vm_service_options_to_switches() {
+        assert!(FlutterEngineBuilder::new().vm_service_args().is_empty());
+
+        let builder = FlutterEngineBuilder::new()
+            .with_vm_service_port(8181)
+            .with_vm_service_auth_codes_disabled(true);
+        assert_eq!(
+            builder.vm_service_args(),
+            vec!["--vm-service-port=8181", "--disable-service-auth-codes"]
+        );
+
+        let builder = FlutterEngineBuilder::new()
+            .with_arg("--disable-service-auth-codes".into())
+            .with_vm_service_auth_codes_disabled(true);
+        assert!(builder.vm_service_args().is_empty());
+    }
+
+    #[test]
+    fn falls_back_to_embedded_icu_data() {
+        let missing = PathBuf::from("/nonexistent/icudtl.dat");
+
+        assert_eq!(
+            FlutterEngineBuilder::new().resolve_icu_data(),
+            Err(CreateError::NoIcuData)
+        );
+        assert_eq!(
+            FlutterEngineBuilder::new()
+                .with_icu_data_path(missing.clone())
+                .resolve_icu_data(),
+            Err(CreateError::IcuDataNotFound(missing.clone()))
+        );
+
+        let builder = FlutterEngineBuilder::new()
+            .with_icu_data_path(missing)
+            .with_embedded_icu_data(true);
+        assert_eq!(builder.resolve_icu_data(), Ok(None));
+        assert_eq!(
+            FlutterEngineBuilder::new()
+                .with_embedded_icu_data(true)
+                .resolve_icu_data(),
+            Ok(None)
+        );
+    }
 }
@@ -1,62 +1,555 @@
-use std::sync::{Arc, RwLock, Weak};
+//! Event channels, streaming events to a Dart `EventChannel` (e.g.: the
+//! samples of a sensor).
+//!
+//! Producers can emit events far faster than Dart handles them when the UI
+//! thread is busy. Rather than queueing them unboundedly in the engine, each
+//! stream is flow controlled (see [`FlowControl`]):
+//!
+//! - At most `max_outstanding` events are sent to Dart without having been
+//!   handled yet, as tracked by the replies of their platform messages.
+//! - Beyond that, up to `max_buffered` events wait in the embedder. Once the
+//!   buffer is full, the [`DropPolicy`] decides whether the oldest or the
+//!   newest event is dropped, or whether the new event is merged into the
+//!   newest buffered one.
+//! - The [`StreamHandler`] is paused once the pending (outstanding and
+//!   buffered) events reach the high watermark, and resumed once they fall to
+//!   the low watermark, so that producers can throttle at the source.
+//!
+//! Errors and the end of the stream are never dropped.
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+};
 
-use tracing::error;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::{error, trace};
 
-use crate::channel::method_channel::MethodCallHandler;
 use crate::{
-    channel::{ChannelImpl, EventHandler, MethodCallHandler, MethodChannel},
-    codec::{standard_codec::CODEC, MethodCall, MethodCodec, Value},
-    error::MethodCallError,
-    FlutterEngine, FlutterEngineWeakRef,
+    channel::{
+        platform_message::{PlatformMessage, PlatformMessageResponseHandle},
+        Channel, DECODE_ERROR_CODE,
+    },
+    codec::{value::to_value, MethodCallResult, MethodCodec, Value},
+    FlutterEngine, FlutterEngineWeakRef, MainThreadCallback,
 };
 
-pub trait EventHandler {
-    fn on_listen(&mut self, args: Value, engine: FlutterEngine) -> Result<Value, MethodCallError>;
-    fn on_cancel(&mut self, engine: FlutterEngine) -> Result<Value, MethodCallError>;
+pub trait StreamHandler {
+    /// Dart started listening to the stream, with the arguments passed to
+    /// `receiveBroadcastStream`. Events are sent through |sink| until
+    /// [`StreamHandler::on_cancel`].
+    fn on_listen(&mut self, args: Value, sink: EventSink) -> Result<(), StreamError>;
+
+    fn on_cancel(&mut self);
+
+    /// Dart doesn't keep up with the events (i.e.: the high watermark has
+    /// been reached). Producers should throttle at the source (e.g.: stop
+    /// reading a device) until [`StreamHandler::on_resume`].
+    fn on_pause(&mut self) {}
+
+    fn on_resume(&mut self) {}
 }
 
-pub struct EventChannel {
-    name: String,
+/// Error reported to Dart when listening to the stream fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamError {
+    pub code: String,
+    pub message: String,
+}
+
+impl StreamError {
+    pub fn new<C: Into<String>, M: Into<String>>(code: C, message: M) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Merges an event into the newest buffered one.
+pub type CoalesceFn = Arc<dyn Fn(&mut Value, Value) + Send + Sync>;
+
+/// What happens to the events once the buffer of a stream is full.
+#[derive(Clone)]
+pub enum DropPolicy {
+    /// Drop the oldest buffered event.
+    DropOldest,
+    /// Drop the new event.
+    DropNewest,
+    /// Merge the new event into the newest buffered one (e.g.: summing
+    /// pointer deltas).
+    Coalesce(CoalesceFn),
+}
+
+impl DropPolicy {
+    pub fn coalesce<F>(merge: F) -> Self
+    where
+        F: Fn(&mut Value, Value) + Send + Sync + 'static,
+    {
+        DropPolicy::Coalesce(Arc::new(merge))
+    }
+
+    /// Coalesce the events by keeping the newest one.
+    pub fn latest() -> Self {
+        Self::coalesce(|buffered, event| *buffered = event)
+    }
+}
+
+impl fmt::Debug for DropPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DropPolicy::DropOldest => write!(f, "DropOldest"),
+            DropPolicy::DropNewest => write!(f, "DropNewest"),
+            DropPolicy::Coalesce(_) => write!(f, "Coalesce"),
+        }
+    }
+}
+
+/// Flow control of the streams of an event channel, see
+/// `EventChannel::with_flow_control`. The defaults keep the latest event
+/// once 8 events are waiting for Dart.
+#[derive(Debug, Clone)]
+pub struct FlowControl {
+    max_outstanding: usize,
+    max_buffered: usize,
+    high_watermark: usize,
+    low_watermark: usize,
+    drop_policy: DropPolicy,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        Self {
+            max_outstanding: 8,
+            max_buffered: 1,
+            high_watermark: 8,
+            low_watermark: 2,
+            drop_policy: DropPolicy::latest(),
+        }
+    }
+}
+
+impl FlowControl {
+    /// Events sent to Dart which haven't been handled yet. At least one.
+    pub fn with_max_outstanding(mut self, max_outstanding: usize) -> Self {
+        self.max_outstanding = max_outstanding.max(1);
+        self
+    }
+
+    /// Events waiting in the embedder once `max_outstanding` is reached.
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.max_buffered = max_buffered;
+        self
+    }
+
+    /// Pending events (outstanding and buffered) from which the handler is
+    /// paused, and to which they must fall for resuming it.
+    pub fn with_watermarks(mut self, high: usize, low: usize) -> Self {
+        self.high_watermark = high.max(1);
+        self.low_watermark = low.min(self.high_watermark - 1);
+        self
+    }
+
+    pub fn with_drop_policy(mut self, drop_policy: DropPolicy) -> Self {
+        self.drop_policy = drop_policy;
+        self
+    }
+}
+
+/// Flow counters of an event channel, over all of its streams.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventFlowStats {
+    pub sent: u64,
+    pub dropped: u64,
+    pub coalesced: u64,
+    /// Events of the current stream which haven't been handled by Dart yet.
+    pub outstanding: u64,
+    pub buffered: u64,
+}
+
+#[derive(Default)]
+struct FlowCounters {
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Success(Value),
+    Error {
+        code: String,
+        message: String,
+        details: Value,
+    },
+    End,
+}
+
+/// Delivers the messages of a stream. Abstracted for testing the flow
+/// control without an engine.
+pub(crate) trait EventMessenger: Send + Sync {
+    /// Send |message| from the platform thread, invoking |on_reply| on the
+    /// platform thread once Dart has handled it.
+    fn send(&self, message: Vec<u8>, on_reply: Box<dyn FnOnce() + Send>);
+
+    /// Run |task| on the platform thread, right away when already on it.
+    fn run(&self, task: Box<dyn FnOnce() + Send>);
+
+    /// Run |task| on the platform thread, after the current task.
+    fn post(&self, task: Box<dyn FnOnce() + Send>);
+}
+
+struct EngineMessenger {
     engine: FlutterEngineWeakRef,
-    method_handler: Arc<RwLock<dyn MethodCallHandler + Send + Sync>>,
-    plugin_name: Option<&'static str>,
+    channel: String,
+}
+
+impl EventMessenger for EngineMessenger {
+    fn send(&self, message: Vec<u8>, on_reply: Box<dyn FnOnce() + Send>) {
+        let Some(engine) = self.engine.upgrade() else {
+            return;
+        };
+
+        let handle = PlatformMessageResponseHandle::new(engine.clone(), move |_reply| on_reply());
+        engine.send_platform_message(PlatformMessage {
+            channel: Cow::Borrowed(&self.channel),
+            message: &message,
+            response_handle: Some(handle),
+        });
+    }
+
+    fn run(&self, task: Box<dyn FnOnce() + Send>) {
+        if let Some(engine) = self.engine.upgrade() {
+            engine.run_on_platform_thread(move |_| task());
+        }
+    }
+
+    fn post(&self, task: Box<dyn FnOnce() + Send>) {
+        if let Some(engine) = self.engine.upgrade() {
+            engine.post_platform_callback(MainThreadCallback::Engine(Box::new(move |_| task())));
+        }
+    }
+}
+
+#[derive(Default)]
+struct StreamState {
+    /// Whether events are still accepted, until the stream is cancelled or
+    /// ended.
+    is_open: bool,
+    is_cancelled: bool,
+    is_paused: bool,
+    outstanding: usize,
+    buffer: VecDeque<Event>,
+}
+
+impl StreamState {
+    fn pending(&self) -> usize {
+        self.outstanding + self.buffer.len()
+    }
+}
+
+/// A single listen of the stream, from `listen` to `cancel`.
+struct EventStream {
+    codec: &'static dyn MethodCodec,
+    flow: FlowControl,
+    messenger: Arc<dyn EventMessenger>,
+    handler: Weak<Mutex<dyn StreamHandler + Send>>,
+    counters: Arc<FlowCounters>,
+    state: Mutex<StreamState>,
+}
+
+impl EventStream {
+    fn push(self: &Arc<Self>, event: Event) {
+        let mut state = self.state.lock();
+        if !state.is_open {
+            trace!("Dropping an event sent after the end of the stream");
+            return;
+        }
+
+        if event == Event::End {
+            state.is_open = false;
+        }
+
+        let is_control = !matches!(event, Event::Success(_));
+        let has_room = state.outstanding < self.flow.max_outstanding
+            || state.buffer.len() < self.flow.max_buffered;
+        if is_control || has_room {
+            state.buffer.push_back(event);
+        } else {
+            self.apply_drop_policy(&mut state, event);
+        }
+
+        let messages = self.take_sendable(&mut state);
+        let paused = !state.is_paused && state.pending() >= self.flow.high_watermark;
+        if paused {
+            state.is_paused = true;
+        }
+        drop(state);
+
+        self.send(messages);
+        if paused {
+            self.notify_flow(true);
+        }
+    }
+
+    fn apply_drop_policy(&self, state: &mut StreamState, event: Event) {
+        let newest = state.buffer.iter_mut().rev().find_map(|event| match event {
+            Event::Success(value) => Some(value),
+            _ => None,
+        });
+
+        match (&self.flow.drop_policy, newest) {
+            (DropPolicy::Coalesce(merge), Some(newest)) => {
+                let Event::Success(value) = event else {
+                    unreachable!("Only the success events are coalesced");
+                };
+                merge(newest, value);
+                self.counters.coalesced.fetch_add(1, Ordering::Relaxed);
+            }
+            (DropPolicy::DropOldest, Some(_)) => {
+                let oldest = state
+                    .buffer
+                    .iter()
+                    .position(|event| matches!(event, Event::Success(_)));
+                if let Some(index) = oldest {
+                    state.buffer.remove(index);
+                }
+                state.buffer.push_back(event);
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn take_sendable(&self, state: &mut StreamState) -> Vec<Vec<u8>> {
+        let mut messages = Vec::new();
+        while state.outstanding < self.flow.max_outstanding && !state.is_cancelled {
+            let Some(event) = state.buffer.pop_front() else {
+                break;
+            };
+            state.outstanding += 1;
+            messages.push(self.encode(event));
+        }
+        messages
+    }
+
+    fn encode(&self, event: Event) -> Vec<u8> {
+        match event {
+            Event::Success(value) => self.codec.encode_success_envelope(&value),
+            Event::Error {
+                code,
+                message,
+                details,
+            } => self.codec.encode_error_envelope(&code, &message, &details),
+            // An empty message ends the stream.
+            Event::End => Vec::new(),
+        }
+    }
+
+    fn send(self: &Arc<Self>, messages: Vec<Vec<u8>>) {
+        for message in messages {
+            self.counters.sent.fetch_add(1, Ordering::Relaxed);
+            let stream = Arc::clone(self);
+            self.messenger
+                .send(message, Box::new(move || stream.on_reply()));
+        }
+    }
+
+    fn on_reply(self: &Arc<Self>) {
+        let mut state = self.state.lock();
+        state.outstanding = state.outstanding.saturating_sub(1);
+
+        let messages = self.take_sendable(&mut state);
+        let resumed = state.is_paused && state.pending() <= self.flow.low_watermark;
+        if resumed {
+            state.is_paused = false;
+        }
+        drop(state);
+
+        self.send(messages);
+        if resumed {
+            self.notify_flow(false);
+        }
+    }
+
+    fn cancel(&self) {
+        let mut state = self.state.lock();
+        state.is_open = false;
+        state.is_cancelled = true;
+        state.is_paused = false;
+        state.buffer.clear();
+    }
+
+    /// Pause or resume the handler. Always deferred, as the producer may be
+    /// the handler itself.
+    fn notify_flow(self: &Arc<Self>, paused: bool) {
+        let stream = Arc::clone(self);
+        self.messenger.post(Box::new(move || {
+            if stream.state.lock().is_cancelled {
+                return;
+            }
+
+            if let Some(handler) = stream.handler.upgrade() {
+                let mut handler = handler.lock();
+                if paused {
+                    handler.on_pause();
+                } else {
+                    handler.on_resume();
+                }
+            }
+        }));
+    }
+}
+
+/// Sends the events of a stream, from any thread. Events sent after the
+/// stream was cancelled or ended are ignored.
+#[derive(Clone)]
+pub struct EventSink {
+    stream: Arc<EventStream>,
+}
+
+impl EventSink {
+    pub fn success<T: Serialize>(&self, event: T) {
+        self.push(Event::Success(
+            to_value(event).expect("Failed to encode event"),
+        ));
+    }
+
+    pub fn error<C: Into<String>, M: Into<String>>(&self, code: C, message: M, details: Value) {
+        self.push(Event::Error {
+            code: code.into(),
+            message: message.into(),
+            details,
+        });
+    }
+
+    /// End the stream, once the events already sent are delivered.
+    pub fn end(&self) {
+        self.push(Event::End);
+    }
+
+    /// Whether the events are still delivered.
+    pub fn is_open(&self) -> bool {
+        self.stream.state.lock().is_open
+    }
+
+    /// Whether the producer should throttle (see [`StreamHandler::on_pause`]).
+    pub fn is_paused(&self) -> bool {
+        self.stream.state.lock().is_paused
+    }
+
+    fn push(&self, event: Event) {
+        let stream = Arc::clone(&self.stream);
+        self.stream
+            .messenger
+            .run(Box::new(move || stream.push(event)));
+    }
 }
 
-struct EventChannelMethodCallHandler {
-    event_handler: Weak<RwLock<dyn EventHandler + Send + Sync>>,
+pub struct EventChannel {
+    name: String,
+    engine: FlutterEngineWeakRef,
+    handler: Arc<Mutex<dyn StreamHandler + Send>>,
+    codec: &'static dyn MethodCodec,
+    flow: FlowControl,
+    stream: RefCell<Option<Arc<EventStream>>>,
+    counters: Arc<FlowCounters>,
 }
 
 impl EventChannel {
-    pub fn new<N: AsRef<str>>(
-        name: N,
-        handler: Weak<RwLock<dyn EventHandler + Send + Sync>>,
-    ) -> Self {
+    pub fn new<N, H>(name: N, handler: H, codec: &'static dyn MethodCodec) -> Self
+    where
+        N: AsRef<str>,
+        H: StreamHandler + Send + 'static,
+    {
         Self {
             name: name.as_ref().to_owned(),
             engine: Default::default(),
-            method_handler: Arc::new(RwLock::new(EventChannelMethodCallHandler::new(handler))),
-            plugin_name: None,
+            handler: Arc::new(Mutex::new(handler)),
+            codec,
+            flow: FlowControl::default(),
+            stream: Default::default(),
+            counters: Default::default(),
         }
     }
 
-    /// When flutter listen to a stream of events using EventChannel.
-    /// This method send back a success event.
-    /// It can be call multiple times to simulate stream.
-    pub fn send_success_event(&self, data: &Value) {
-        let buf = self.codec().encode_success_envelope(data);
-        self.send_buffer(&buf);
+    /// Override the default flow control of the streams.
+    pub fn with_flow_control(mut self, flow: FlowControl) -> Self {
+        self.flow = flow;
+        self
+    }
+
+    pub fn flow_stats(&self) -> EventFlowStats {
+        let (outstanding, buffered) = self
+            .stream
+            .borrow()
+            .as_ref()
+            .map(|stream| {
+                let state = stream.state.lock();
+                (state.outstanding as u64, state.buffer.len() as u64)
+            })
+            .unwrap_or_default();
+
+        EventFlowStats {
+            sent: self.counters.sent.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            coalesced: self.counters.coalesced.load(Ordering::Relaxed),
+            outstanding,
+            buffered,
+        }
     }
 
-    /// When flutter listen to a stream of events using EventChannel.
-    /// This method send back a error event.
-    /// It can be call multiple times to simulate stream.
-    pub fn send_error_event(&self, code: &str, message: &str, data: &Value) {
-        let buf = self.codec().encode_error_envelope(code, message, data);
-        self.send_buffer(&buf);
+    fn listen(&self, args: Value, messenger: Arc<dyn EventMessenger>) -> MethodCallResult {
+        // Listening again replaces the previous stream.
+        self.cancel();
+
+        let stream = Arc::new(EventStream {
+            codec: self.codec,
+            flow: self.flow.clone(),
+            messenger,
+            handler: Arc::downgrade(&self.handler),
+            counters: self.counters.clone(),
+            state: Mutex::new(StreamState {
+                is_open: true,
+                ..Default::default()
+            }),
+        });
+        self.stream.replace(Some(stream.clone()));
+
+        let sink = EventSink { stream };
+        match self.handler.lock().on_listen(args, sink) {
+            Ok(()) => MethodCallResult::Ok(Value::Null),
+            Err(err) => {
+                if let Some(stream) = self.stream.take() {
+                    stream.cancel();
+                }
+                MethodCallResult::Err {
+                    code: err.code,
+                    message: err.message,
+                    details: Value::Null,
+                }
+            }
+        }
+    }
+
+    fn cancel(&self) {
+        if let Some(stream) = self.stream.take() {
+            stream.cancel();
+            self.handler.lock().on_cancel();
+        }
     }
 }
 
-impl ChannelImpl for EventChannel {
+impl Channel for EventChannel {
     fn name(&self) -> &str {
         self.name.as_str()
     }
@@ -65,52 +558,338 @@ impl ChannelImpl for EventChannel {
         self.engine.upgrade()
     }
 
-    fn init(&mut self, engine: FlutterEngineWeakRef, plugin_name: &'static str) {
+    fn init(&mut self, engine: FlutterEngineWeakRef) {
         if self.engine.upgrade().is_some() {
             error!("Channel {} was already initialized", self.name);
         }
         self.engine = engine;
-        self.plugin_name.replace(plugin_name);
     }
 
-    fn plugin_name(&self) -> &'static str {
-        self.plugin_name.unwrap()
+    fn handle_platform_message(&self, msg: PlatformMessage) {
+        debug_assert_eq!(msg.channel, self.name());
+        let result = match self.codec.decode_method_call(msg.message) {
+            Ok(call) if call.method == "listen" => {
+                let messenger = Arc::new(EngineMessenger {
+                    engine: self.engine.clone(),
+                    channel: self.name.clone(),
+                });
+                self.listen(call.args, messenger)
+            }
+            Ok(call) if call.method == "cancel" => {
+                self.cancel();
+                MethodCallResult::Ok(Value::Null)
+            }
+            Ok(_) => MethodCallResult::NotImplemented,
+            Err(err) => {
+                error!(
+                    "Failed to decode method call on channel {}: {}",
+                    self.name(),
+                    err
+                );
+                MethodCallResult::Err {
+                    code: DECODE_ERROR_CODE.into(),
+                    message: err.to_string(),
+                    details: Value::Null,
+                }
+            }
+        };
+
+        if let Some(handle) = msg.response_handle {
+            self.send_response(handle, &self.codec.encode_method_call_response(&result));
+        }
     }
-}
 
-impl MethodChannel for EventChannel {
-    fn method_handler(&self) -> Option<Arc<RwLock<dyn MethodCallHandler + Send + Sync>>> {
-        Some(Arc::clone(&self.method_handler))
+    fn method_codec(&self) -> Option<&'static dyn MethodCodec> {
+        Some(self.codec)
     }
 
-    fn codec(&self) -> &'static dyn MethodCodec {
-        &CODEC
+    fn event_flow_stats(&self) -> Option<EventFlowStats> {
+        Some(self.flow_stats())
     }
 }
 
-impl EventChannelMethodCallHandler {
-    pub fn new(handler: Weak<RwLock<dyn EventHandler + Send + Sync>>) -> Self {
-        Self {
-            event_handler: handler,
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use parking_lot::Mutex;
+
+    use super::{
+        DropPolicy, EventChannel, EventMessenger, EventSink, FlowControl, StreamError,
+        StreamHandler,
+    };
+    use crate::codec::{MethodCallResult, MethodCodec, Value, JSON_CODEC};
+
+    type Task = Box<dyn FnOnce() + Send>;
+
+    /// Slow consumer: the replies are only delivered when the test says so.
+    #[derive(Default)]
+    struct MockMessenger {
+        sent: Mutex<Vec<Vec<u8>>>,
+        replies: Mutex<Vec<Task>>,
+        posted: Mutex<Vec<Task>>,
+    }
+
+    impl MockMessenger {
+        /// Deliver the |count| oldest replies, one at a time.
+        fn reply(&self, count: usize) {
+            for _ in 0..count {
+                let reply = {
+                    let mut replies = self.replies.lock();
+                    (!replies.is_empty()).then(|| replies.remove(0))
+                };
+                if let Some(reply) = reply {
+                    reply();
+                }
+            }
+        }
+
+        fn run_posted(&self) {
+            let posted: Vec<Task> = self.posted.lock().drain(..).collect();
+            posted.into_iter().for_each(|task| task());
+        }
+
+        fn sent_events(&self) -> Vec<Option<Value>> {
+            self.sent
+                .lock()
+                .iter()
+                .map(|message| match message.as_slice() {
+                    [] => None,
+                    message => match JSON_CODEC.decode_envelope(message).unwrap() {
+                        MethodCallResult::Ok(value) => Some(value),
+                        _ => Some(Value::String("error".into())),
+                    },
+                })
+                .collect()
         }
     }
-}
 
-impl MethodCallHandler for EventChannelMethodCallHandler {
-    fn on_method_call(
-        &mut self,
-        call: MethodCall,
-        engine: FlutterEngine,
-    ) -> Result<Value, MethodCallError> {
-        if let Some(handler) = self.event_handler.upgrade() {
-            let mut handler = handler.write().unwrap();
-            match call.method.as_str() {
-                "listen" => handler.on_listen(call.args, engine),
-                "cancel" => handler.on_cancel(engine),
-                _ => Err(MethodCallError::NotImplemented),
+    impl EventMessenger for MockMessenger {
+        fn send(&self, message: Vec<u8>, on_reply: Task) {
+            self.sent.lock().push(message);
+            self.replies.lock().push(on_reply);
+        }
+
+        fn run(&self, task: Task) {
+            task()
+        }
+
+        fn post(&self, task: Task) {
+            self.posted.lock().push(task);
+        }
+    }
+
+    #[derive(Default)]
+    struct Handler {
+        sink: Arc<Mutex<Option<EventSink>>>,
+        pauses: Arc<AtomicUsize>,
+        resumes: Arc<AtomicUsize>,
+    }
+
+    impl StreamHandler for Handler {
+        fn on_listen(&mut self, args: Value, sink: EventSink) -> Result<(), StreamError> {
+            if args == Value::String("fail".into()) {
+                return Err(StreamError::new("unavailable", "No device"));
             }
-        } else {
-            Err(MethodCallError::ChannelClosed)
+            self.sink.lock().replace(sink);
+            Ok(())
+        }
+
+        fn on_cancel(&mut self) {
+            self.sink.lock().take();
+        }
+
+        fn on_pause(&mut self) {
+            self.pauses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_resume(&mut self) {
+            self.resumes.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    struct Harness {
+        channel: EventChannel,
+        messenger: Arc<MockMessenger>,
+        sink: EventSink,
+        pauses: Arc<AtomicUsize>,
+        resumes: Arc<AtomicUsize>,
+    }
+
+    fn listen(flow: FlowControl) -> Harness {
+        let handler = Handler::default();
+        let (sink, pauses, resumes) = (
+            handler.sink.clone(),
+            handler.pauses.clone(),
+            handler.resumes.clone(),
+        );
+        let channel =
+            EventChannel::new("test/events", handler, &JSON_CODEC).with_flow_control(flow);
+        let messenger = Arc::new(MockMessenger::default());
+        let result = channel.listen(Value::Null, messenger.clone());
+        assert!(matches!(result, MethodCallResult::Ok(Value::Null)));
+
+        let sink = sink.lock().clone().unwrap();
+        Harness {
+            channel,
+            messenger,
+            sink,
+            pauses,
+            resumes,
+        }
+    }
+
+    fn flow(max_outstanding: usize, max_buffered: usize, policy: DropPolicy) -> FlowControl {
+        FlowControl::default()
+            .with_max_outstanding(max_outstanding)
+            .with_max_buffered(max_buffered)
+            .with_watermarks(max_outstanding + max_buffered, 1)
+            .with_drop_policy(policy)
+    }
+
+    fn values(values: &[i64]) -> Vec<Option<Value>> {
+        values
+            .iter()
+            .map(|&value| Some(Value::I64(value)))
+            .collect()
+    }
+
+    #[test]
+    fn memory_stays_bounded_with_a_slow_consumer() {
+        let harness = listen(FlowControl::default());
+        for value in 0..10_000i64 {
+            harness.sink.success(value);
+        }
+
+        let stats = harness.channel.flow_stats();
+        assert_eq!(stats.outstanding, 8);
+        assert_eq!(stats.buffered, 1);
+        assert_eq!(stats.sent, 8);
+        assert_eq!(stats.coalesced, 10_000 - 9);
+        assert_eq!(harness.messenger.replies.lock().len(), 8);
+
+        // The latest event is delivered once Dart catches up.
+        harness.messenger.reply(8);
+        assert_eq!(
+            harness.messenger.sent_events().last(),
+            Some(&Some(Value::I64(9_999)))
+        );
+        assert_eq!(harness.channel.flow_stats().outstanding, 1);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_newest_events() {
+        let harness = listen(flow(1, 2, DropPolicy::DropOldest));
+        for value in 0..5 {
+            harness.sink.success(value);
+        }
+        harness.messenger.reply(3);
+
+        assert_eq!(harness.messenger.sent_events(), values(&[0, 3, 4]));
+        assert_eq!(harness.channel.flow_stats().dropped, 2);
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_oldest_events() {
+        let harness = listen(flow(1, 2, DropPolicy::DropNewest));
+        for value in 0..5 {
+            harness.sink.success(value);
+        }
+        harness.messenger.reply(3);
+
+        assert_eq!(harness.messenger.sent_events(), values(&[0, 1, 2]));
+        assert_eq!(harness.channel.flow_stats().dropped, 2);
+    }
+
+    #[test]
+    fn coalesce_merges_into_the_newest_event() {
+        let merge_deltas = DropPolicy::coalesce(|buffered, event| {
+            if let (Value::I64(buffered), Value::I64(delta)) = (buffered, event) {
+                *buffered += delta;
+            }
+        });
+        let harness = listen(flow(1, 1, merge_deltas));
+        for delta in [1, 2, 3, 4] {
+            harness.sink.success(delta);
         }
+        harness.messenger.reply(2);
+
+        // No delta is lost.
+        assert_eq!(harness.messenger.sent_events(), values(&[1, 9]));
+        let stats = harness.channel.flow_stats();
+        assert_eq!((stats.coalesced, stats.dropped), (2, 0));
+    }
+
+    #[test]
+    fn errors_and_end_are_never_dropped() {
+        let harness = listen(flow(1, 0, DropPolicy::DropNewest));
+        harness.sink.success(0);
+        harness.sink.success(1);
+        harness.sink.error("code", "message", Value::Null);
+        harness.sink.end();
+        harness.sink.success(2);
+        assert!(!harness.sink.is_open());
+
+        harness.messenger.reply(3);
+        let events = harness.messenger.sent_events();
+        assert_eq!(events[0], Some(Value::I64(0)));
+        assert_eq!(events[1], Some(Value::String("error".into())));
+        assert_eq!(events[2], None);
+        assert_eq!(events.len(), 3);
+    }
+
+    #[test]
+    fn handler_is_paused_between_the_watermarks() {
+        let flow = flow(2, 2, DropPolicy::DropNewest).with_watermarks(3, 1);
+        let harness = listen(flow);
+        let count = |counter: &Arc<AtomicUsize>| counter.load(Ordering::Relaxed);
+
+        harness.sink.success(0);
+        harness.sink.success(1);
+        assert!(!harness.sink.is_paused());
+        harness.sink.success(2);
+        assert!(harness.sink.is_paused());
+
+        // The notifications are deferred to the next platform task.
+        assert_eq!(count(&harness.pauses), 0);
+        harness.messenger.run_posted();
+        assert_eq!(count(&harness.pauses), 1);
+
+        // Two pending events (the buffered one was sent), then one.
+        harness.messenger.reply(1);
+        assert!(harness.sink.is_paused());
+        harness.messenger.reply(1);
+        assert!(!harness.sink.is_paused());
+        harness.messenger.run_posted();
+        assert_eq!((count(&harness.pauses), count(&harness.resumes)), (1, 1));
+    }
+
+    #[test]
+    fn cancel_drops_the_buffered_events() {
+        let harness = listen(flow(1, 4, DropPolicy::DropNewest));
+        for value in 0..3 {
+            harness.sink.success(value);
+        }
+
+        harness.channel.cancel();
+        harness.messenger.reply(1);
+        harness.sink.success(3);
+
+        assert!(!harness.sink.is_open());
+        assert_eq!(harness.messenger.sent_events(), values(&[0]));
+        assert_eq!(harness.channel.flow_stats().buffered, 0);
+    }
+
+    #[test]
+    fn listen_errors_are_reported() {
+        let channel = EventChannel::new("test/events", Handler::default(), &JSON_CODEC);
+        let messenger = Arc::new(MockMessenger::default());
+        let result = channel.listen(Value::String("fail".into()), messenger);
+
+        assert!(matches!(result, MethodCallResult::Err { code, .. } if code == "unavailable"));
+        assert!(channel.stream.borrow().is_none());
     }
 }
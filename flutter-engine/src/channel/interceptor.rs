@@ -0,0 +1,185 @@
+//! Interception of the platform messages exchanged on the channels, e.g.:
+//! for logging them, recording them or building a message inspector. An
+//! interceptor is installed with [`FlutterEngine::set_message_interceptor`].
+//!
+//! When no interceptor is installed, the only overhead on the message path is
+//! a single atomic load.
+//!
+//! [`FlutterEngine::set_message_interceptor`]: crate::FlutterEngine::set_message_interceptor
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use parking_lot::RwLock;
+
+use crate::codec::{MessageCodec, MethodCall, MethodCodec, Value};
+
+use super::tracer::Direction;
+
+/// A platform message about to be dispatched, either to a channel handler
+/// ([`Direction::Inbound`]) or to Dart ([`Direction::Outbound`]).
+pub struct InterceptedMessage<'a> {
+    pub direction: Direction,
+    pub channel: &'a str,
+    pub bytes: &'a [u8],
+    method_codec: Option<&'static dyn MethodCodec>,
+    message_codec: Option<&'static dyn MessageCodec>,
+}
+
+impl<'a> InterceptedMessage<'a> {
+    pub(crate) fn new(
+        direction: Direction,
+        channel: &'a str,
+        bytes: &'a [u8],
+        method_codec: Option<&'static dyn MethodCodec>,
+        message_codec: Option<&'static dyn MessageCodec>,
+    ) -> Self {
+        Self {
+            direction,
+            channel,
+            bytes,
+            method_codec,
+            message_codec,
+        }
+    }
+
+    /// The codec of the method channel registered for [`Self::channel`], if
+    /// any.
+    pub fn method_codec(&self) -> Option<&'static dyn MethodCodec> {
+        self.method_codec
+    }
+
+    /// The codec of the message channel registered for [`Self::channel`], if
+    /// any.
+    pub fn message_codec(&self) -> Option<&'static dyn MessageCodec> {
+        self.message_codec
+    }
+
+    /// The decoded method call, for the messages of the method channels.
+    pub fn method_call(&self) -> Option<MethodCall> {
+        self.method_codec?.decode_method_call(self.bytes).ok()
+    }
+
+    /// The decoded value, for the messages of the message channels.
+    pub fn value(&self) -> Option<Value> {
+        self.message_codec?.decode_message(self.bytes).ok()
+    }
+}
+
+/// Sees every platform message before it is dispatched. Responses are not
+/// intercepted.
+///
+/// Note: Messages are intercepted on the *platform* thread, so interceptors
+/// should return quickly.
+pub trait MessageInterceptor: Send + Sync {
+    /// Returning some bytes replaces the message (e.g.: a modified method call
+    /// encoded with [`InterceptedMessage::method_codec`]).
+    fn intercept(&self, message: &InterceptedMessage) -> Option<Vec<u8>>;
+}
+
+impl<F> MessageInterceptor for F
+where
+    F: Fn(&InterceptedMessage) -> Option<Vec<u8>> + Send + Sync,
+{
+    fn intercept(&self, message: &InterceptedMessage) -> Option<Vec<u8>> {
+        self(message)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct InterceptorSlot {
+    is_set: AtomicBool,
+    interceptor: RwLock<Option<Arc<dyn MessageInterceptor>>>,
+}
+
+impl InterceptorSlot {
+    pub(crate) fn set(&self, interceptor: Option<Arc<dyn MessageInterceptor>>) {
+        let mut slot = self.interceptor.write();
+        self.is_set.store(interceptor.is_some(), Ordering::Relaxed);
+        *slot = interceptor;
+    }
+
+    #[inline]
+    pub(crate) fn is_set(&self) -> bool {
+        self.is_set.load(Ordering::Relaxed)
+    }
+
+    /// The lock is released before the interceptor is invoked, so that it can
+    /// replace itself.
+    pub(crate) fn get(&self) -> Option<Arc<dyn MessageInterceptor>> {
+        self.interceptor.read().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::codec::{MessageCodec, MethodCall, MethodCodec, Value, JSON_CODEC, STANDARD_CODEC};
+
+    use super::{Direction, InterceptedMessage, InterceptorSlot, MessageInterceptor};
+
+    fn method_call(method: &str) -> Vec<u8> {
+        STANDARD_CODEC.encode_method_call(&MethodCall {
+            method: method.into(),
+            args: Value::Null,
+        })
+    }
+
+    #[test]
+    fn decodes_intercepted_messages() {
+        let bytes = method_call("SystemSound.play");
+        let message = InterceptedMessage::new(
+            Direction::Inbound,
+            "flutter/platform",
+            &bytes,
+            Some(&STANDARD_CODEC),
+            None,
+        );
+        assert_eq!(message.method_call().unwrap().method, "SystemSound.play");
+        assert_eq!(message.value(), None);
+
+        let bytes = JSON_CODEC.encode_message(&Value::String("resumed".into()));
+        let message =
+            InterceptedMessage::new(Direction::Outbound, "test", &bytes, None, Some(&JSON_CODEC));
+        assert_eq!(message.value(), Some(Value::String("resumed".into())));
+        assert!(message.method_call().is_none());
+
+        // Channels without any registered handler.
+        let message = InterceptedMessage::new(Direction::Inbound, "unknown", &bytes, None, None);
+        assert!(message.method_call().is_none() && message.value().is_none());
+    }
+
+    #[test]
+    fn replaces_messages() {
+        let rename = |message: &InterceptedMessage| {
+            let mut call = message.method_call()?;
+            call.method = call.method.replace("play", "stop");
+            Some(message.method_codec()?.encode_method_call(&call))
+        };
+
+        let bytes = method_call("SystemSound.play");
+        let message = InterceptedMessage::new(
+            Direction::Outbound,
+            "flutter/platform",
+            &bytes,
+            Some(&STANDARD_CODEC),
+            None,
+        );
+        assert_eq!(rename.intercept(&message), Some(method_call("SystemSound.stop")));
+    }
+
+    #[test]
+    fn installs_and_removes_interceptors() {
+        let slot = InterceptorSlot::default();
+        assert!(!slot.is_set() && slot.get().is_none());
+
+        let interceptor = |_: &InterceptedMessage| -> Option<Vec<u8>> { None };
+        slot.set(Some(Arc::new(interceptor)));
+        assert!(slot.is_set() && slot.get().is_some());
+
+        slot.set(None);
+        assert!(!slot.is_set() && slot.get().is_none());
+    }
+}
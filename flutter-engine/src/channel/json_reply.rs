@@ -0,0 +1,210 @@
+//! Awaitable replies to the JSON messages sent to the framework (see
+//! `FlutterEngine::send_json_message_with_reply`).
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::JsonMessageError;
+
+pub(crate) fn encode_json_message<T>(value: &T) -> Result<Vec<u8>, JsonMessageError>
+where
+    T: Serialize,
+{
+    serde_json::to_vec(value).map_err(JsonMessageError::Encode)
+}
+
+pub(crate) fn decode_json_reply<V>(data: &[u8]) -> Result<V, JsonMessageError>
+where
+    V: DeserializeOwned,
+{
+    // An empty reply means that no handler is registered for the channel on
+    // the Dart side.
+    if data.is_empty() {
+        return Err(JsonMessageError::NoReply);
+    }
+    serde_json::from_slice(data).map_err(JsonMessageError::Decode)
+}
+
+struct ReplyState<V> {
+    reply: Option<Result<V, JsonMessageError>>,
+    waker: Option<Waker>,
+}
+
+/// The reply to a JSON message, which can be awaited from any thread. It
+/// resolves to [`JsonMessageError::NoReply`] if the engine is shut down
+/// before replying.
+pub struct JsonReply<V> {
+    state: Arc<Mutex<ReplyState<V>>>,
+}
+
+impl<V> JsonReply<V> {
+    pub(crate) fn new() -> (Self, JsonReplySender<V>) {
+        let state = Arc::new(Mutex::new(ReplyState {
+            reply: None,
+            waker: None,
+        }));
+        let sender = JsonReplySender {
+            state: Some(state.clone()),
+        };
+        (Self { state }, sender)
+    }
+
+    /// Take the reply if it has already been received, without waiting (e.g.:
+    /// for polling it from a host hook).
+    pub fn try_take(&mut self) -> Option<Result<V, JsonMessageError>> {
+        self.state.lock().reply.take()
+    }
+}
+
+impl<V> Future for JsonReply<V> {
+    type Output = Result<V, JsonMessageError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+        match state.reply.take() {
+            Some(reply) => Poll::Ready(reply),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Resolves the [`JsonReply`] once the response of the framework arrives, or
+/// with [`JsonMessageError::NoReply`] when dropped before (e.g.: along with
+/// the callback of a response handle the engine never called).
+pub(crate) struct JsonReplySender<V> {
+    state: Option<Arc<Mutex<ReplyState<V>>>>,
+}
+
+impl<V> JsonReplySender<V> {
+    fn resolve(&mut self, reply: Result<V, JsonMessageError>) {
+        let Some(state) = self.state.take() else {
+            return;
+        };
+
+        let waker = {
+            let mut state = state.lock();
+            state.reply = Some(reply);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<V> JsonReplySender<V>
+where
+    V: DeserializeOwned,
+{
+    pub(crate) fn send(mut self, data: &[u8]) {
+        self.resolve(decode_json_reply(data));
+    }
+}
+
+impl<V> Drop for JsonReplySender<V> {
+    fn drop(&mut self) {
+        self.resolve(Err(JsonMessageError::NoReply));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    use super::{encode_json_message, JsonReply};
+    use crate::error::JsonMessageError;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn poll<V>(reply: &mut JsonReply<V>, waker: &Waker) -> Poll<Result<V, JsonMessageError>> {
+        Pin::new(reply).poll(&mut Context::from_waker(waker))
+    }
+
+    #[test]
+    fn replies_are_decoded_and_wake_the_task() {
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let (mut reply, sender) = JsonReply::<HashMap<String, i64>>::new();
+
+        assert!(poll(&mut reply, &waker).is_pending());
+        sender.send(br#"{"count":3}"#);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+
+        let Poll::Ready(Ok(value)) = poll(&mut reply, &waker) else {
+            panic!("Expected a decoded reply");
+        };
+        assert_eq!(value["count"], 3);
+    }
+
+    #[test]
+    fn missing_replies_are_reported() {
+        let (mut reply, sender) = JsonReply::<i64>::new();
+        sender.send(b"");
+        assert!(matches!(
+            reply.try_take(),
+            Some(Err(JsonMessageError::NoReply))
+        ));
+
+        // The engine dropped the response handle without calling it.
+        let (mut reply, sender) = JsonReply::<i64>::new();
+        assert!(reply.try_take().is_none());
+        drop(sender);
+        assert!(matches!(
+            reply.try_take(),
+            Some(Err(JsonMessageError::NoReply))
+        ));
+    }
+
+    #[test]
+    fn malformed_replies_are_reported() {
+        let (mut reply, sender) = JsonReply::<i64>::new();
+        sender.send(b"{\"count\":");
+        assert!(matches!(
+            reply.try_take(),
+            Some(Err(JsonMessageError::Decode(_)))
+        ));
+
+        let (mut reply, sender) = JsonReply::<i64>::new();
+        sender.send(b"\"three\"");
+        assert!(matches!(
+            reply.try_take(),
+            Some(Err(JsonMessageError::Decode(_)))
+        ));
+    }
+
+    #[test]
+    fn unencodable_messages_are_reported() {
+        // JSON object keys must be strings.
+        let value = HashMap::from([((1, 2), "point")]);
+        assert!(matches!(
+            encode_json_message(&value),
+            Err(JsonMessageError::Encode(_))
+        ));
+        assert_eq!(encode_json_message(&[1, 2]).unwrap(), b"[1,2]");
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{codec::MessageCodec, FlutterEngine, FlutterEngineWeakRef};
 
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
-use crate::channel::Channel;
+use crate::channel::{Channel, ResponseWriter};
 use crate::codec::value::{from_value, from_value_owned, to_value};
 use crate::codec::Value;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -44,6 +44,17 @@ impl Message {
         }
     }
 
+    /// Returns a writer which can be used to build the (already encoded)
+    /// response off the platform thread. See [`ResponseWriter`].
+    pub fn response_writer(self) -> Option<ResponseWriter> {
+        let handle = self.response_handle?;
+        Some(ResponseWriter::new(self.engine, handle))
+    }
+
+    pub fn codec(&self) -> &'static dyn MessageCodec {
+        self.codec
+    }
+
     pub fn engine(&self) -> FlutterEngineWeakRef {
         self.engine.clone()
     }
@@ -115,8 +126,13 @@ impl MessageChannel {
             let buf = codec.encode_message(&to_value(value).unwrap());
 
             let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
-                let resp = codec.decode_message(data);
-                let val = resp.unwrap();
+                let val = match codec.decode_message(data) {
+                    Ok(val) => val,
+                    Err(err) => {
+                        error!("Failed to decode message reply: {}", err);
+                        return;
+                    }
+                };
                 callback(from_value_owned(&val).unwrap());
             });
 
@@ -127,6 +143,68 @@ impl MessageChannel {
             });
         }
     }
+
+    /// Send a value on this channel, invoking |callback| with the reply of
+    /// Dart, or `None` if the reply is empty (e.g.: no handler is registered)
+    /// or can't be decoded. The callback is never invoked if the engine is
+    /// gone.
+    pub fn send_with_reply<T, F>(&self, value: T, callback: F)
+    where
+        T: Serialize,
+        F: FnOnce(Option<Value>) + 'static + Send,
+    {
+        if let Some(engine) = self.engine() {
+            if !engine.is_platform_thread() {
+                panic!("Not on platform thread");
+            }
+
+            let codec = self.codec;
+            let buf = codec.encode_message(&to_value(value).expect("Failed to encode value"));
+            let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
+                let reply = match data {
+                    [] => None,
+                    data => codec
+                        .decode_message(data)
+                        .map_err(|err| error!("Failed to decode message reply: {}", err))
+                        .ok(),
+                };
+                callback(reply);
+            });
+
+            engine.send_platform_message(PlatformMessage {
+                channel: Cow::Borrowed(self.name()),
+                message: &buf,
+                response_handle: Some(handle),
+            });
+        }
+    }
+
+    /// Send a value on this channel, invoking |callback| once Dart has handled
+    /// it, whatever the reply (e.g.: an empty one if no handler is registered).
+    /// The callback is never invoked if the engine is gone.
+    pub fn send_with_ack<T, F>(&self, value: T, callback: F)
+    where
+        T: Serialize,
+        F: FnOnce() + 'static + Send,
+    {
+        if let Some(engine) = self.engine() {
+            if !engine.is_platform_thread() {
+                panic!("Not on platform thread");
+            }
+
+            let buf = self
+                .codec()
+                .encode_message(&to_value(value).expect("Failed to encode value"));
+            let handle =
+                PlatformMessageResponseHandle::new(engine.clone(), move |_data| callback());
+
+            engine.send_platform_message(PlatformMessage {
+                channel: Cow::Borrowed(self.name()),
+                message: &buf,
+                response_handle: Some(handle),
+            });
+        }
+    }
 }
 
 impl Channel for MessageChannel {
@@ -149,7 +227,19 @@ impl Channel for MessageChannel {
     fn handle_platform_message(&self, msg: PlatformMessage) {
         debug_assert_eq!(msg.channel, self.name());
         let codec = self.codec;
-        let message = codec.decode_message(msg.message).unwrap();
+        let message = match codec.decode_message(msg.message) {
+            Ok(message) => message,
+            Err(err) => {
+                error!("Failed to decode message on channel {}: {}", self.name(), err);
+
+                // Messages have no error envelope, reply with an empty one
+                // (i.e.: `null`) instead of leaving the send pending.
+                if let Some(handle) = msg.response_handle {
+                    self.send_response(handle, &[]);
+                }
+                return;
+            }
+        };
         let channel = self.name().to_owned();
         trace!("on channel {}, got message {:?}", channel, message);
 
@@ -162,4 +252,8 @@ impl Channel for MessageChannel {
 
         self.message_handler.borrow_mut().on_message(msg);
     }
+
+    fn message_codec(&self) -> Option<&'static dyn MessageCodec> {
+        Some(self.codec)
+    }
 }
@@ -1,7 +1,7 @@
 use tracing::{error, trace};
 
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
-use crate::channel::Channel;
+use crate::channel::{Channel, ResponseWriter};
 use crate::{codec, codec::MethodCodec, FlutterEngine, FlutterEngineWeakRef};
 
 use crate::codec::value::{from_value, from_value_owned, to_value};
@@ -11,6 +11,10 @@ use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cell::RefCell;
 
+/// Error code of the envelopes sent back for method calls which could not
+/// be decoded.
+pub const DECODE_ERROR_CODE: &str = "decode-error";
+
 pub struct MethodCall {
     engine: FlutterEngineWeakRef,
     codec: &'static dyn MethodCodec,
@@ -89,6 +93,17 @@ impl MethodCall {
         }
     }
 
+    /// Returns a writer which can be used to build the (already encoded)
+    /// response off the platform thread. See [`ResponseWriter`].
+    pub fn response_writer(self) -> Option<ResponseWriter> {
+        let handle = self.response_handle?;
+        Some(ResponseWriter::new(self.engine, handle))
+    }
+
+    pub fn codec(&self) -> &'static dyn MethodCodec {
+        self.codec
+    }
+
     pub fn success<T>(self, data: T)
     where
         T: Serialize,
@@ -188,9 +203,13 @@ impl MethodChannel {
             });
 
             let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
-                let result = codec
-                    .decode_envelope(data)
-                    .expect("Failed to decode response envelope");
+                let result = match codec.decode_envelope(data) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        error!("Failed to decode response envelope: {}", err);
+                        return;
+                    }
+                };
 
                 let response = match result {
                     MethodCallResult::Ok(val) => {
@@ -241,7 +260,21 @@ impl Channel for MethodChannel {
     fn handle_platform_message(&self, msg: PlatformMessage) {
         debug_assert_eq!(msg.channel, self.name());
         let codec = self.codec;
-        let call = self.codec.decode_method_call(msg.message).unwrap();
+        let call = match self.codec.decode_method_call(msg.message) {
+            Ok(call) => call,
+            Err(err) => {
+                error!("Failed to decode method call on channel {}: {}", self.name(), err);
+
+                // Let Dart know instead of leaving the call pending.
+                if let Some(handle) = msg.response_handle {
+                    let message = err.to_string();
+                    let buf =
+                        codec.encode_error_envelope(DECODE_ERROR_CODE, &message, &Value::Null);
+                    self.send_response(handle, &buf);
+                }
+                return;
+            }
+        };
         let channel = self.name().to_owned();
         trace!(
             "on channel {}, got method call {} with args {:?}",
@@ -259,4 +292,8 @@ impl Channel for MethodChannel {
 
         self.method_handler.borrow_mut().on_method_call(call);
     }
+
+    fn method_codec(&self) -> Option<&'static dyn MethodCodec> {
+        Some(self.codec)
+    }
 }
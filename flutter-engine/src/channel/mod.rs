@@ -4,22 +4,35 @@
 
 use tracing::error;
 
-use crate::{FlutterEngine, FlutterEngineWeakRef};
+use crate::{
+    codec::{MessageCodec, MethodCodec},
+    FlutterEngine, FlutterEngineWeakRef,
+};
 
 pub use self::{
+    event_channel::{
+        CoalesceFn, DropPolicy, EventChannel, EventFlowStats, EventSink, FlowControl,
+        StreamError, StreamHandler,
+    },
+    interceptor::{InterceptedMessage, MessageInterceptor},
+    json_reply::JsonReply,
     message_channel::{Message, MessageChannel, MessageHandler},
-    // event_channel::EventChannel,
-    method_channel::{MethodCall, MethodCallHandler, MethodChannel, MethodError},
+    method_channel::{MethodCall, MethodCallHandler, MethodChannel, MethodError, DECODE_ERROR_CODE},
     registry::ChannelRegistry,
+    response_writer::{ResponseStream, ResponseWriter, STREAM_CHANNEL},
+    tracer::{ChannelFilter, ChannelStats, Direction, CHANNEL_TRACE_ENV_VAR},
 };
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
 
+mod event_channel;
+pub(crate) mod interceptor;
+pub(crate) mod json_reply;
 mod message_channel;
-// TODO: Reimplement event channel support
-// mod event_channel;
 mod method_channel;
 pub mod platform_message;
 mod registry;
+mod response_writer;
+pub(crate) mod tracer;
 
 pub trait Channel {
     fn name(&self) -> &str;
@@ -27,6 +40,22 @@ pub trait Channel {
     fn init(&mut self, engine: FlutterEngineWeakRef);
     fn handle_platform_message(&self, msg: PlatformMessage);
 
+    /// The codec used for the method calls on this channel, if any. Used for
+    /// decoding the traced messages.
+    fn method_codec(&self) -> Option<&'static dyn MethodCodec> {
+        None
+    }
+
+    /// The codec used for the messages on this channel, if any.
+    fn message_codec(&self) -> Option<&'static dyn MessageCodec> {
+        None
+    }
+
+    /// The flow counters of event channels, reported with the channel stats.
+    fn event_flow_stats(&self) -> Option<EventFlowStats> {
+        None
+    }
+
     /// When flutter call a method using MethodChannel,
     /// it can wait for rust response using await syntax.
     /// This method send a response to flutter. This is a low level method.
@@ -1,3 +1,5 @@
+use crate::channel::tracer::TraceKey;
+use crate::procs::engine_proc;
 use crate::FlutterEngine;
 use flutter_engine_sys::{FlutterPlatformMessage, FlutterPlatformMessageResponseHandle};
 use std::borrow::Cow;
@@ -8,6 +10,8 @@ use tracing::{error, trace};
 #[derive(Debug)]
 pub struct PlatformMessageResponseHandle {
     handle: *const FlutterPlatformMessageResponseHandle,
+    /// Only set for the handles created while the channel tracer is enabled.
+    trace_id: Option<u64>,
 }
 
 unsafe impl Send for PlatformMessageResponseHandle {}
@@ -19,17 +23,40 @@ impl PlatformMessageResponseHandle {
     where
         F: FnOnce(&[u8]) + 'static + Send,
     {
+        let tracer = &engine.inner.channel_tracer;
+        let trace_id = tracer.is_enabled().then(|| tracer.next_trace_id());
+
+        let callback: ResponseType = match trace_id {
+            Some(trace_id) => {
+                let engine = engine.downgrade();
+                Box::new(move |data: &[u8]| {
+                    if let Some(engine) = engine.upgrade() {
+                        let key = TraceKey::Outbound(trace_id);
+                        engine.inner.channel_tracer.record_response(key, data);
+                    }
+                    callback(data);
+                })
+            }
+            None => Box::new(callback),
+        };
+
         unsafe {
-            let callback = Box::new(callback);
             let mut handle: *mut FlutterPlatformMessageResponseHandle = ptr::null_mut();
-            flutter_engine_sys::FlutterPlatformMessageCreateResponseHandle(
+            engine_proc!(PlatformMessageCreateResponseHandle)(
                 engine.engine_ptr(),
                 Some(response_handle_callback),
                 Box::into_raw(Box::new(callback)) as _,
                 &mut handle,
             );
 
-            Self { handle }
+            Self { handle, trace_id }
+        }
+    }
+
+    pub(crate) fn trace_key(&self) -> TraceKey {
+        match self.trace_id {
+            Some(trace_id) => TraceKey::Outbound(trace_id),
+            None => TraceKey::Inbound(self.handle as usize),
         }
     }
 }
@@ -51,7 +78,10 @@ unsafe extern "C" fn response_handle_callback(
 
 impl From<*const FlutterPlatformMessageResponseHandle> for PlatformMessageResponseHandle {
     fn from(val: *const FlutterPlatformMessageResponseHandle) -> Self {
-        PlatformMessageResponseHandle { handle: val }
+        PlatformMessageResponseHandle {
+            handle: val,
+            trace_id: None,
+        }
     }
 }
 
@@ -8,7 +8,8 @@ use tracing::{trace, warn};
 
 use crate::FlutterEngineWeakRef;
 
-use super::Channel;
+use super::{Channel, EventFlowStats};
+use crate::codec::{MessageCodec, MethodCodec};
 use crate::channel::platform_message::PlatformMessage;
 
 #[derive(Default)]
@@ -42,6 +43,12 @@ impl ChannelRegistry {
         self.channels.remove(channel_name)
     }
 
+    pub fn remove_all_channels(&mut self) -> usize {
+        let count = self.channels.len();
+        self.channels.clear();
+        count
+    }
+
     pub fn with_channel<F>(&self, channel_name: &str, f: F)
     where
         F: FnOnce(&dyn Channel),
@@ -51,6 +58,21 @@ impl ChannelRegistry {
         }
     }
 
+    pub(crate) fn method_codec(&self, channel_name: &str) -> Option<&'static dyn MethodCodec> {
+        self.channels.get(channel_name)?.method_codec()
+    }
+
+    pub(crate) fn message_codec(&self, channel_name: &str) -> Option<&'static dyn MessageCodec> {
+        self.channels.get(channel_name)?.message_codec()
+    }
+
+    pub(crate) fn event_flow_stats(&self) -> Vec<(String, EventFlowStats)> {
+        self.channels
+            .iter()
+            .filter_map(|(name, channel)| Some((name.clone(), channel.event_flow_stats()?)))
+            .collect()
+    }
+
     pub fn handle(&self, mut message: PlatformMessage) {
         if let Some(channel) = self.channels.get(message.channel.deref()) {
             trace!("Processing message from channel: {}", message.channel);
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use tracing::warn;
+
+use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
+use crate::codec::{MessageCodec, Value, STANDARD_CODEC};
+use crate::FlutterEngineWeakRef;
+
+/// Channel of the chunked response protocol (see [`ResponseWriter::stream`]).
+pub const STREAM_CHANNEL: &str = "flutter-rs/stream";
+
+static NEXT_STREAM_ID: AtomicI64 = AtomicI64::new(1);
+
+type Respond = Box<dyn FnOnce(Vec<u8>) + Send>;
+type SendChunk = Box<dyn FnMut(Vec<u8>) + Send>;
+
+/// Incrementally builds the response to a platform message.
+///
+/// Unlike [`MethodCall::respond`](crate::channel::MethodCall::respond), the
+/// writer can be moved to a background thread so that large replies (e.g.:
+/// image bytes) can be produced and encoded without blocking the platform
+/// thread. Only the final hand-off to the engine happens on the platform
+/// thread, and the accumulated buffer is moved there without being copied.
+///
+/// The caller is responsible for writing a payload encoded with the codec of
+/// the channel the message was received on.
+pub struct ResponseWriter {
+    engine: FlutterEngineWeakRef,
+    respond: Option<Respond>,
+    buf: Vec<u8>,
+}
+
+impl ResponseWriter {
+    pub(crate) fn new(
+        engine: FlutterEngineWeakRef,
+        response_handle: PlatformMessageResponseHandle,
+    ) -> Self {
+        let weak = engine.clone();
+        let respond = move |buf: Vec<u8>| {
+            let Some(engine) = weak.upgrade() else {
+                // The engine is gone, so there is nobody left to leak the
+                // handle memory to. Avoid triggering the handle drop warning.
+                std::mem::forget(response_handle);
+                return;
+            };
+
+            engine.run_on_platform_thread(move |eng| {
+                eng.send_platform_message_response(response_handle, &buf);
+            });
+        };
+        Self::with_respond(engine, Box::new(respond))
+    }
+
+    fn with_respond(engine: FlutterEngineWeakRef, respond: Respond) -> Self {
+        Self {
+            engine,
+            respond: Some(respond),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more bytes. Useful when the
+    /// final size of the response is known upfront.
+    pub fn reserve(&mut self, additional: usize) {
+        self.buf.reserve(additional);
+    }
+
+    /// Appends a chunk to the response.
+    pub fn write_chunk(&mut self, chunk: &[u8]) {
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Starts streaming the payload over [`STREAM_CHANNEL`], for the consumers
+    /// which opt in to processing it as it is produced rather than receiving
+    /// it at once. The response should then only carry the
+    /// [`ResponseStream::id`], encoded with the codec of the channel, so that
+    /// the consumer can match the chunks of the stream with its request.
+    pub fn stream(&self) -> ResponseStream {
+        let engine = self.engine.clone();
+        let send = move |envelope: Vec<u8>| {
+            let Some(engine) = engine.upgrade() else {
+                return;
+            };
+
+            engine.run_on_platform_thread(move |eng| {
+                eng.send_platform_message(PlatformMessage {
+                    channel: STREAM_CHANNEL.into(),
+                    message: &envelope,
+                    response_handle: None,
+                });
+            });
+        };
+        ResponseStream::with_send(Box::new(send))
+    }
+
+    /// Sends the accumulated response to the engine.
+    pub fn finish(mut self) {
+        let buf = std::mem::take(&mut self.buf);
+        self.send(buf);
+    }
+
+    fn send(&mut self, buf: Vec<u8>) {
+        if let Some(respond) = self.respond.take() {
+            respond(buf);
+        }
+    }
+}
+
+impl io::Write for ResponseWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_chunk(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ResponseWriter {
+    fn drop(&mut self) {
+        if self.respond.is_some() {
+            warn!("A response writer has been dropped without being finished. Sending an empty response.");
+            self.send(vec![]);
+        }
+    }
+}
+
+/// Payload streamed to the framework as a sequence of messages on
+/// [`STREAM_CHANNEL`] (see [`ResponseWriter::stream`]).
+///
+/// Each message is an envelope encoded with the standard message codec: a map
+/// holding the `id` of the stream, the `seq` number of the chunk (starting at
+/// 0), its `data` bytes, and whether it is the `last` one. The last envelope
+/// has no data, and is `cancelled` if the stream was dropped without being
+/// finished.
+pub struct ResponseStream {
+    id: i64,
+    seq: i64,
+    send: Option<SendChunk>,
+}
+
+impl ResponseStream {
+    fn with_send(send: SendChunk) -> Self {
+        Self {
+            id: NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed),
+            seq: 0,
+            send: Some(send),
+        }
+    }
+
+    /// The id of the stream, to be sent back in the response.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Sends the next chunk of the payload. The envelope is encoded on the
+    /// calling thread.
+    pub fn send_chunk(&mut self, chunk: Vec<u8>) {
+        self.send_envelope(chunk, false, false);
+    }
+
+    /// Sends the last envelope, which ends the stream.
+    pub fn finish(mut self) {
+        self.send_envelope(vec![], true, false);
+    }
+
+    fn send_envelope(&mut self, data: Vec<u8>, last: bool, cancelled: bool) {
+        let Some(send) = self.send.as_mut() else {
+            return;
+        };
+
+        send(encode_stream_envelope(
+            self.id, self.seq, data, last, cancelled,
+        ));
+        self.seq += 1;
+        if last {
+            self.send = None;
+        }
+    }
+}
+
+impl Drop for ResponseStream {
+    fn drop(&mut self) {
+        if self.send.is_some() {
+            warn!("A response stream has been dropped without being finished. Cancelling it.");
+            self.send_envelope(vec![], true, true);
+        }
+    }
+}
+
+fn encode_stream_envelope(
+    id: i64,
+    seq: i64,
+    data: Vec<u8>,
+    last: bool,
+    cancelled: bool,
+) -> Vec<u8> {
+    let mut envelope = HashMap::from([
+        ("id".to_owned(), Value::I64(id)),
+        ("seq".to_owned(), Value::I64(seq)),
+        ("data".to_owned(), Value::U8List(data)),
+        ("last".to_owned(), Value::Boolean(last)),
+    ]);
+    if cancelled {
+        envelope.insert("cancelled".to_owned(), Value::Boolean(true));
+    }
+    STANDARD_CODEC.encode_message(&Value::Map(envelope))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use parking_lot::Mutex;
+
+    use super::{ResponseStream, ResponseWriter};
+    use crate::codec::{MessageCodec, Value, STANDARD_CODEC};
+    use crate::FlutterEngineWeakRef;
+
+    type Sent = Arc<Mutex<Vec<Vec<u8>>>>;
+    type Task = Box<dyn FnOnce() + Send>;
+
+    fn writer() -> (ResponseWriter, Sent) {
+        let sent = Sent::default();
+        let respond = {
+            let sent = sent.clone();
+            move |buf| sent.lock().push(buf)
+        };
+        let writer =
+            ResponseWriter::with_respond(FlutterEngineWeakRef::default(), Box::new(respond));
+        (writer, sent)
+    }
+
+    fn stream() -> (ResponseStream, Sent) {
+        let sent = Sent::default();
+        let send = {
+            let sent = sent.clone();
+            move |envelope| sent.lock().push(envelope)
+        };
+        (ResponseStream::with_send(Box::new(send)), sent)
+    }
+
+    fn envelope(buf: &[u8]) -> (i64, i64, Vec<u8>, bool, bool) {
+        let Value::Map(mut map) = STANDARD_CODEC.decode_message(buf).unwrap() else {
+            panic!("Expected a map");
+        };
+        let (Some(Value::I64(id)), Some(Value::I64(seq))) = (map.remove("id"), map.remove("seq"))
+        else {
+            panic!("Expected the id and sequence number");
+        };
+        let Some(Value::U8List(data)) = map.remove("data") else {
+            panic!("Expected the data");
+        };
+        let Some(Value::Boolean(last)) = map.remove("last") else {
+            panic!("Expected the last flag");
+        };
+        let cancelled = map.remove("cancelled") == Some(Value::Boolean(true));
+        (id, seq, data, last, cancelled)
+    }
+
+    /// Stands in for the platform thread: runs the posted tasks in order, and
+    /// returns the time spent running them once the sender is dropped.
+    fn platform_thread() -> (mpsc::Sender<Task>, thread::JoinHandle<Duration>) {
+        let (sender, receiver) = mpsc::channel::<Task>();
+        let handle = thread::spawn(move || {
+            let mut busy = Duration::ZERO;
+            for task in receiver {
+                let start = Instant::now();
+                task();
+                busy += start.elapsed();
+            }
+            busy
+        });
+        (sender, handle)
+    }
+
+    /// Stands in for `FlutterEngineSendPlatformMessageResponse`.
+    fn send_response(buf: Vec<u8>) {
+        std::hint::black_box(&buf);
+    }
+
+    /// Time the platform thread is stalled by a 50MB reply, when it is
+    /// encoded on the platform thread (as `MethodCall::success` does) and
+    /// when it is written to a `ResponseWriter` on a background thread. Run
+    /// with `cargo test -p flutter-engine --release platform_thread_stall --
+    /// --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn platform_thread_stall_of_a_50mb_reply() {
+        const REPLY_SIZE: usize = 50 * 1024 * 1024;
+
+        let (platform, handle) = platform_thread();
+        platform
+            .send(Box::new(|| {
+                let payload = Value::U8List(vec![0x5a; REPLY_SIZE]);
+                send_response(STANDARD_CODEC.encode_message(&payload));
+            }))
+            .unwrap();
+        drop(platform);
+        let direct = handle.join().unwrap();
+
+        let (platform, handle) = platform_thread();
+        let respond = move |buf: Vec<u8>| {
+            platform.send(Box::new(move || send_response(buf))).unwrap();
+        };
+        thread::spawn(move || {
+            let mut writer =
+                ResponseWriter::with_respond(FlutterEngineWeakRef::default(), Box::new(respond));
+            let payload = Value::U8List(vec![0x5a; REPLY_SIZE]);
+            writer.write_chunk(&STANDARD_CODEC.encode_message(&payload));
+            writer.finish();
+        })
+        .join()
+        .unwrap();
+        let written = handle.join().unwrap();
+
+        println!("Platform thread stall: {direct:?} encoding the reply on it, {written:?} with a response writer");
+        assert!(written * 10 < direct);
+    }
+
+    #[test]
+    fn finish_sends_the_written_response_once() {
+        let (mut writer, sent) = writer();
+        writer.reserve(6);
+        writer.write_chunk(b"abc");
+        writer.write_all(b"def").unwrap();
+        assert_eq!(writer.len(), 6);
+        assert!(sent.lock().is_empty());
+
+        writer.finish();
+        assert_eq!(*sent.lock(), [b"abcdef".to_vec()]);
+    }
+
+    #[test]
+    fn dropped_writer_sends_an_empty_response() {
+        let (mut writer, sent) = writer();
+        writer.write_chunk(b"partial");
+        drop(writer);
+        assert_eq!(*sent.lock(), [Vec::<u8>::new()]);
+    }
+
+    #[test]
+    fn stream_envelopes_are_numbered() {
+        let (mut stream, sent) = stream();
+        let id = stream.id();
+        stream.send_chunk(b"abc".to_vec());
+        stream.send_chunk(b"def".to_vec());
+        stream.finish();
+
+        let envelopes: Vec<_> = sent.lock().iter().map(|buf| envelope(buf)).collect();
+        assert_eq!(
+            envelopes,
+            [
+                (id, 0, b"abc".to_vec(), false, false),
+                (id, 1, b"def".to_vec(), false, false),
+                (id, 2, vec![], true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn dropped_stream_is_cancelled() {
+        let (mut first, sent) = stream();
+        let (second, _) = stream();
+        assert_ne!(first.id(), second.id());
+
+        first.send_chunk(b"abc".to_vec());
+        drop(first);
+
+        let envelopes: Vec<_> = sent.lock().iter().map(|buf| envelope(buf)).collect();
+        assert_eq!(envelopes.len(), 2);
+        assert_eq!(envelopes[1], (envelopes[0].0, 1, vec![], true, true));
+    }
+}
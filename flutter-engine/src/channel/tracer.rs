@@ -0,0 +1,483 @@
+//! Tracing of the platform messages exchanged on the channels, for debugging
+//! plugins. It is enabled with the `FLUTTER_RS_CHANNEL_TRACE` environment
+//! variable (e.g.: `FLUTTER_RS_CHANNEL_TRACE=flutter/*`) or at runtime with
+//! [`FlutterEngine::set_channel_tracer`].
+//!
+//! When disabled, the only overhead on the message path is a single atomic
+//! load.
+//!
+//! [`FlutterEngine::set_channel_tracer`]: crate::FlutterEngine::set_channel_tracer
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    channel::EventFlowStats,
+    codec::{MethodCallResult, MethodCodec, Value},
+};
+
+pub const CHANNEL_TRACE_ENV_VAR: &str = "FLUTTER_RS_CHANNEL_TRACE";
+
+const PREVIEW_MAX_CHARS: usize = 160;
+const REDACTED: &str = "<redacted>";
+const MAX_LATENCY_SAMPLES: usize = 256;
+
+/// Channels whose payloads may contain user input (e.g.: obscured text
+/// fields). Only the method names are traced for these.
+const REDACTED_CHANNELS: &[&str] = &["flutter/textinput"];
+const REDACTED_METHODS: &[(&str, &str)] = &[
+    ("flutter/platform", "Clipboard.getData"),
+    ("flutter/platform", "Clipboard.setData"),
+];
+
+/// Comma separated list of channel names, where `*` matches any sequence of
+/// characters (e.g.: `flutter/*,flutter-rs/window`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelFilter {
+    patterns: Vec<String>,
+}
+
+impl ChannelFilter {
+    pub fn new(filter: &str) -> Self {
+        Self {
+            patterns: filter
+                .split(',')
+                .map(str::trim)
+                .filter(|pattern| !pattern.is_empty())
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    pub fn matches(&self, channel: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| glob_matches(pattern, channel))
+    }
+}
+
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = parts.next().and_then(|prefix| name.strip_prefix(prefix)) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((suffix, middle)) = parts.split_last() else {
+        // No wildcard in the pattern.
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(suffix)
+}
+
+/// Traffic counters of a single channel. Only messages received while the
+/// tracer was enabled are accounted for, while the flow counters of the event
+/// channels are always reported.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelStats {
+    pub channel: String,
+    pub calls: u64,
+    /// Error responses, including calls that nobody handled.
+    pub errors: u64,
+    /// Calls that are still waiting for a response.
+    pub unanswered: u64,
+    pub bytes: u64,
+    /// Computed over the most recent responses.
+    pub p95_latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<EventFlowStats>,
+}
+
+impl ChannelStats {
+    pub(crate) fn new(channel: String) -> Self {
+        Self {
+            channel,
+            calls: 0,
+            errors: 0,
+            unanswered: 0,
+            bytes: 0,
+            p95_latency_ms: None,
+            events: None,
+        }
+    }
+}
+
+/// Direction of a platform message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent by Dart to the embedder.
+    Inbound,
+    /// Sent by the embedder to Dart.
+    Outbound,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::Inbound => write!(f, "dart -> rust"),
+            Direction::Outbound => write!(f, "rust -> dart"),
+        }
+    }
+}
+
+/// Correlates a message with its response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum TraceKey {
+    /// Address of the response handle created by the engine.
+    Inbound(usize),
+    /// Id assigned to the response handle created by the embedder.
+    Outbound(u64),
+}
+
+struct PendingCall {
+    channel: String,
+    direction: Direction,
+    method: Option<String>,
+    codec: Option<&'static dyn MethodCodec>,
+    redacted: bool,
+    sent_at: Instant,
+}
+
+#[derive(Default)]
+struct ChannelCounters {
+    calls: u64,
+    errors: u64,
+    bytes: u64,
+    latencies: VecDeque<Duration>,
+}
+
+#[derive(Default)]
+struct TracerState {
+    filter: Option<ChannelFilter>,
+    next_id: u64,
+    pending: HashMap<TraceKey, PendingCall>,
+    channels: HashMap<String, ChannelCounters>,
+}
+
+#[derive(Default)]
+pub(crate) struct ChannelTracer {
+    enabled: AtomicBool,
+    state: Mutex<TracerState>,
+}
+
+impl ChannelTracer {
+    pub(crate) fn from_env() -> Self {
+        let tracer = Self::default();
+        if let Ok(filter) = std::env::var(CHANNEL_TRACE_ENV_VAR) {
+            tracer.set_filter(Some(ChannelFilter::new(&filter)));
+        }
+        tracer
+    }
+
+    pub(crate) fn set_filter(&self, filter: Option<ChannelFilter>) {
+        let mut state = self.state.lock();
+        self.enabled.store(filter.is_some(), Ordering::Relaxed);
+        if filter.is_none() {
+            state.pending.clear();
+        }
+        state.filter = filter;
+    }
+
+    #[inline]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn next_trace_id(&self) -> u64 {
+        let mut state = self.state.lock();
+        state.next_id += 1;
+        state.next_id
+    }
+
+    /// Record a message sent on |channel|. |key| identifies its response
+    /// handle, if a response is expected.
+    pub(crate) fn record_message(
+        &self,
+        direction: Direction,
+        channel: &str,
+        message: &[u8],
+        key: Option<TraceKey>,
+        codec: Option<&'static dyn MethodCodec>,
+    ) {
+        let mut state = self.state.lock();
+        if !state
+            .filter
+            .as_ref()
+            .is_some_and(|filter| filter.matches(channel))
+        {
+            return;
+        }
+
+        let call = codec.and_then(|codec| codec.decode_method_call(message).ok());
+        let method = call.as_ref().map(|call| call.method.clone());
+        let redacted = is_redacted(channel, method.as_deref());
+        let args = match &call {
+            Some(_) if redacted => REDACTED.into(),
+            Some(call) => preview(&call.args),
+            None => String::new(),
+        };
+
+        info!(
+            "{} {} ({} bytes) {} {}",
+            direction,
+            channel,
+            message.len(),
+            method.as_deref().unwrap_or("<message>"),
+            args
+        );
+
+        let counters = state.channels.entry(channel.to_owned()).or_default();
+        counters.calls += 1;
+        counters.bytes += message.len() as u64;
+
+        if let Some(key) = key {
+            state.pending.insert(
+                key,
+                PendingCall {
+                    channel: channel.to_owned(),
+                    direction,
+                    method,
+                    codec,
+                    redacted,
+                    sent_at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Record the response identified by |key|. Responses to messages which
+    /// were not traced are ignored.
+    pub(crate) fn record_response(&self, key: TraceKey, response: &[u8]) {
+        let mut state = self.state.lock();
+        let Some(call) = state.pending.remove(&key) else {
+            return;
+        };
+
+        let latency = call.sent_at.elapsed();
+        let (is_error, result) = describe_response(&call, response);
+
+        info!(
+            "{} {} ({} bytes) response to {} in {:?}: {}",
+            call.direction,
+            call.channel,
+            response.len(),
+            call.method.as_deref().unwrap_or("<message>"),
+            latency,
+            result
+        );
+
+        let counters = state.channels.entry(call.channel).or_default();
+        counters.bytes += response.len() as u64;
+        if is_error {
+            counters.errors += 1;
+        }
+
+        if counters.latencies.len() == MAX_LATENCY_SAMPLES {
+            counters.latencies.pop_front();
+        }
+        counters.latencies.push_back(latency);
+    }
+
+    pub(crate) fn stats(&self) -> Vec<ChannelStats> {
+        let state = self.state.lock();
+
+        let mut stats: Vec<ChannelStats> = state
+            .channels
+            .iter()
+            .map(|(channel, counters)| ChannelStats {
+                channel: channel.clone(),
+                calls: counters.calls,
+                errors: counters.errors,
+                unanswered: state
+                    .pending
+                    .values()
+                    .filter(|call| call.channel == *channel)
+                    .count() as u64,
+                bytes: counters.bytes,
+                p95_latency_ms: p95_ms(&counters.latencies),
+                events: None,
+            })
+            .collect();
+
+        stats.sort_by(|a, b| a.channel.cmp(&b.channel));
+        stats
+    }
+}
+
+fn is_redacted(channel: &str, method: Option<&str>) -> bool {
+    REDACTED_CHANNELS.contains(&channel)
+        || method.is_some_and(|method| REDACTED_METHODS.contains(&(channel, method)))
+}
+
+fn describe_response(call: &PendingCall, response: &[u8]) -> (bool, String) {
+    if response.is_empty() {
+        return (true, "not handled".into());
+    }
+
+    match call.codec.and_then(|codec| codec.decode_envelope(response).ok()) {
+        Some(MethodCallResult::Ok(_)) if call.redacted => (false, REDACTED.into()),
+        Some(MethodCallResult::Ok(value)) => (false, preview(&value)),
+        Some(MethodCallResult::Err { code, .. }) if call.redacted => {
+            (true, format!("error {}", code))
+        }
+        Some(MethodCallResult::Err { code, message, .. }) => {
+            (true, format!("error {}: {}", code, message))
+        }
+        Some(MethodCallResult::NotImplemented) => (true, "not implemented".into()),
+        None => (false, String::new()),
+    }
+}
+
+fn preview(value: &Value) -> String {
+    let mut preview = format!("{:?}", value);
+    if let Some((index, _)) = preview.char_indices().nth(PREVIEW_MAX_CHARS) {
+        preview.truncate(index);
+        preview.push('…');
+    }
+    preview
+}
+
+fn p95_ms(latencies: &VecDeque<Duration>) -> Option<f64> {
+    if latencies.is_empty() {
+        return None;
+    }
+
+    let mut latencies: Vec<Duration> = latencies.iter().copied().collect();
+    latencies.sort();
+    let index = ((latencies.len() - 1) as f64 * 0.95).round() as usize;
+    Some(latencies[index].as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codec::{self, MethodCodec, Value, JSON_CODEC, STANDARD_CODEC};
+
+    use super::{
+        describe_response, is_redacted, ChannelFilter, ChannelTracer, Direction, PendingCall,
+        TraceKey,
+    };
+
+    fn method_call(codec: &dyn MethodCodec, method: &str) -> Vec<u8> {
+        codec.encode_method_call(&codec::MethodCall {
+            method: method.into(),
+            args: Value::String("hunter2".into()),
+        })
+    }
+
+    fn tracer(filter: &str) -> ChannelTracer {
+        let tracer = ChannelTracer::default();
+        tracer.set_filter(Some(ChannelFilter::new(filter)));
+        tracer
+    }
+
+    #[test]
+    fn matches_channel_patterns() {
+        let filter = ChannelFilter::new("flutter/*, flutter-rs/*_toolbar,flutter-rs/window");
+
+        assert!(filter.matches("flutter/platform"));
+        assert!(filter.matches("flutter-rs/text_toolbar"));
+        assert!(filter.matches("flutter-rs/window"));
+        assert!(!filter.matches("flutter-rs/windows"));
+        assert!(!filter.matches("flutter-rs/screensaver"));
+        assert!(ChannelFilter::new("*").matches("anything"));
+    }
+
+    #[test]
+    fn correlates_calls_with_responses() {
+        let tracer = tracer("flutter/*");
+        let codec = &STANDARD_CODEC;
+
+        let call = method_call(codec, "SystemSound.play");
+        let (first, second) = (TraceKey::Inbound(0x10), TraceKey::Outbound(1));
+        for key in [first, second] {
+            tracer.record_message(
+                Direction::Inbound,
+                "flutter/platform",
+                &call,
+                Some(key),
+                Some(codec),
+            );
+        }
+        tracer.record_message(
+            Direction::Inbound,
+            "flutter-rs/window",
+            &call,
+            Some(TraceKey::Inbound(0x20)),
+            Some(codec),
+        );
+
+        // Responses may arrive in any order.
+        let error = codec.encode_error_envelope("code", "message", &Value::Null);
+        tracer.record_response(second, &error);
+        let stats = tracer.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].calls, stats[0].errors, stats[0].unanswered), (2, 1, 1));
+
+        let success = codec.encode_success_envelope(&Value::Null);
+        tracer.record_response(first, &success);
+        // Already answered, and filtered out.
+        tracer.record_response(first, &[]);
+        tracer.record_response(TraceKey::Inbound(0x20), &[]);
+
+        let stats = &tracer.stats()[0];
+        assert_eq!(stats.channel, "flutter/platform");
+        assert_eq!((stats.calls, stats.errors, stats.unanswered), (2, 1, 0));
+        assert_eq!(stats.bytes, (2 * call.len() + error.len() + success.len()) as u64);
+        assert!(stats.p95_latency_ms.is_some());
+    }
+
+    #[test]
+    fn counts_unhandled_calls_as_errors() {
+        let tracer = tracer("*");
+        let key = TraceKey::Outbound(tracer.next_trace_id());
+
+        let call = method_call(&JSON_CODEC, "onAction");
+        let channel = "flutter-rs/text_toolbar";
+        tracer.record_message(Direction::Outbound, channel, &call, Some(key), Some(&JSON_CODEC));
+        tracer.record_response(key, &[]);
+
+        assert_eq!(tracer.stats()[0].errors, 1);
+    }
+
+    #[test]
+    fn redacts_sensitive_channels() {
+        assert!(is_redacted("flutter/textinput", Some("TextInput.setEditingState")));
+        assert!(is_redacted("flutter/textinput", None));
+        assert!(is_redacted("flutter/platform", Some("Clipboard.setData")));
+        assert!(!is_redacted("flutter/platform", Some("SystemSound.play")));
+
+        let call = PendingCall {
+            channel: "flutter/platform".into(),
+            direction: Direction::Inbound,
+            method: Some("Clipboard.getData".into()),
+            codec: Some(&JSON_CODEC),
+            redacted: true,
+            sent_at: std::time::Instant::now(),
+        };
+        let response = JSON_CODEC.encode_success_envelope(&Value::String("hunter2".into()));
+        let (is_error, result) = describe_response(&call, &response);
+        assert!(!is_error);
+        assert!(!result.contains("hunter2"));
+
+        let response = JSON_CODEC.encode_error_envelope("code", "hunter2", &Value::Null);
+        let (is_error, result) = describe_response(&call, &response);
+        assert!(is_error);
+        assert!(!result.contains("hunter2"));
+    }
+}
@@ -1,44 +1,35 @@
-use tracing::error;
 use serde_json::json;
 
-use super::{MessageCodec, MethodCall, MethodCallResult, MethodCodec, Value};
+use super::{CodecError, MessageCodec, MethodCall, MethodCallResult, MethodCodec, Value};
 
 pub struct JsonMethodCodec;
 
 pub const JSON_CODEC: JsonMethodCodec = JsonMethodCodec {};
 
 impl MethodCodec for JsonMethodCodec {
-    fn decode_method_call(&self, buf: &[u8]) -> Option<MethodCall> {
-        unsafe {
-            let s = std::str::from_utf8_unchecked(buf);
-            serde_json::from_str::<MethodCall>(s).ok()
-        }
+    fn decode_method_call(&self, buf: &[u8]) -> Result<MethodCall, CodecError> {
+        from_json(buf)
     }
 
-    fn decode_envelope(&self, buf: &[u8]) -> Option<MethodCallResult> {
-        unsafe {
-            let s = std::str::from_utf8_unchecked(buf);
-            let json: Value = serde_json::from_str(s).unwrap();
-            if let Value::List(mut v) = json {
-                if v.len() == 1 {
-                    return Some(MethodCallResult::Ok(v.swap_remove(0)));
-                } else if v.len() == 3 {
-                    return Some(MethodCallResult::Err {
-                        code: match &v[0] {
-                            Value::String(s) => s.clone(),
-                            _ => "".into(),
-                        },
-                        message: match &v[1] {
-                            Value::String(s) => s.clone(),
-                            _ => "".into(),
-                        },
-                        details: v.swap_remove(2),
-                    });
-                }
+    fn decode_envelope(&self, buf: &[u8]) -> Result<MethodCallResult, CodecError> {
+        if let Value::List(mut v) = from_json(buf)? {
+            if v.len() == 1 {
+                return Ok(MethodCallResult::Ok(v.swap_remove(0)));
+            } else if v.len() == 3 {
+                return Ok(MethodCallResult::Err {
+                    code: match &v[0] {
+                        Value::String(s) => s.clone(),
+                        _ => "".into(),
+                    },
+                    message: match &v[1] {
+                        Value::String(s) => s.clone(),
+                        _ => "".into(),
+                    },
+                    details: v.swap_remove(2),
+                });
             }
-            error!("Invalid envelope: {}", s);
-            None
         }
+        Err(CodecError::InvalidType("envelope is not a list of 1 or 3 items".into()))
     }
 
     fn encode_method_call(&self, v: &MethodCall) -> Vec<u8> {
@@ -67,10 +58,15 @@ impl MessageCodec for JsonMethodCodec {
         s.into_bytes()
     }
 
-    fn decode_message(&self, buf: &[u8]) -> Option<Value> {
-        unsafe {
-            let s = std::str::from_utf8_unchecked(buf);
-            serde_json::from_str::<Value>(s).ok()
-        }
+    fn decode_message(&self, buf: &[u8]) -> Result<Value, CodecError> {
+        from_json(buf)
     }
 }
+
+/// Note: `serde_json` bounds the nesting depth of the decoded values.
+fn from_json<'a, T>(buf: &'a [u8]) -> Result<T, CodecError>
+where
+    T: serde::Deserialize<'a>,
+{
+    serde_json::from_slice(buf).map_err(|err| CodecError::Malformed(err.to_string()))
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 pub use self::value::Value;
 
@@ -9,7 +10,7 @@ mod string_codec;
 pub mod value;
 
 pub use json_codec::JSON_CODEC;
-pub use standard_codec::STANDARD_CODEC;
+pub use standard_codec::{DecodeLimits, StandardMethodCodec, STANDARD_CODEC};
 pub use string_codec::STRING_CODEC;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -18,6 +19,41 @@ pub struct MethodCall {
     pub args: Value,
 }
 
+/// Error returned when decoding a malformed message.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    #[error("The message exceeds the {0} limit")]
+    LimitExceeded(CodecLimit),
+
+    #[error("The message is truncated")]
+    Truncated,
+
+    /// Unknown type, or value of an unexpected type (e.g.: a method name
+    /// which is not a string).
+    #[error("Invalid type: {0}")]
+    InvalidType(String),
+
+    /// The message is not valid for the codec (e.g.: invalid JSON).
+    #[error("Malformed message: {0}")]
+    Malformed(String),
+}
+
+/// See [`DecodeLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecLimit {
+    DecodedSize,
+    Depth,
+}
+
+impl std::fmt::Display for CodecLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecLimit::DecodedSize => write!(f, "decoded size"),
+            CodecLimit::Depth => write!(f, "nesting depth"),
+        }
+    }
+}
+
 pub enum MethodCallResult {
     Ok(Value),
     Err {
@@ -30,7 +66,7 @@ pub enum MethodCallResult {
 
 pub trait MethodCodec: Send + Sync {
     /// Methods for handling dart call
-    fn decode_method_call(&self, buf: &[u8]) -> Option<MethodCall>;
+    fn decode_method_call(&self, buf: &[u8]) -> Result<MethodCall, CodecError>;
     fn encode_success_envelope(&self, v: &Value) -> Vec<u8>;
     fn encode_error_envelope(&self, code: &str, message: &str, details: &Value) -> Vec<u8>;
 
@@ -48,11 +84,11 @@ pub trait MethodCodec: Send + Sync {
 
     /// Methods for calling into dart
     fn encode_method_call(&self, v: &MethodCall) -> Vec<u8>;
-    fn decode_envelope(&self, buf: &[u8]) -> Option<MethodCallResult>;
+    fn decode_envelope(&self, buf: &[u8]) -> Result<MethodCallResult, CodecError>;
 }
 
 pub trait MessageCodec: Send + Sync {
     /// Methods for plain messages
     fn encode_message(&self, v: &Value) -> Vec<u8>;
-    fn decode_message(&self, buf: &[u8]) -> Option<Value>;
+    fn decode_message(&self, buf: &[u8]) -> Result<Value, CodecError>;
 }
@@ -1,9 +1,6 @@
-use std::convert::AsMut;
-use std::{collections::HashMap, slice};
+use std::{collections::HashMap, mem};
 
-use tracing::error;
-
-use super::{MessageCodec, MethodCall, MethodCallResult, MethodCodec, Value};
+use super::{CodecError, CodecLimit, MessageCodec, MethodCall, MethodCallResult, MethodCodec, Value};
 
 const VALUE_NULL: u8 = 0;
 const VALUE_TRUE: u8 = 1;
@@ -20,88 +17,62 @@ const VALUE_FLOAT64LIST: u8 = 11;
 const VALUE_LIST: u8 = 12;
 const VALUE_MAP: u8 = 13;
 
-#[derive(Debug)]
-enum DecodeError {
-    Invalid,
-    Ended,
+/// Limits applied while decoding, so that malformed or malicious messages
+/// (e.g.: declaring huge lengths, or deeply nested lists) can't exhaust the
+/// memory or the stack of the platform thread.
+///
+/// Besides, the declared length of every string, list and map is checked
+/// against the remaining bytes of the message before anything is allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum number of bytes allocated for the decoded values of a message
+    /// (i.e.: the contents of the strings, lists and maps).
+    pub max_decoded_size: usize,
+    /// Maximum nesting depth of the lists and maps.
+    pub max_depth: usize,
+}
+
+impl DecodeLimits {
+    pub const DEFAULT: DecodeLimits = DecodeLimits {
+        max_decoded_size: 256 * 1024 * 1024,
+        max_depth: 128,
+    };
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
 }
 
-pub struct StandardMethodCodec;
+pub struct StandardMethodCodec {
+    limits: DecodeLimits,
+}
 
-pub const STANDARD_CODEC: StandardMethodCodec = StandardMethodCodec {};
+pub const STANDARD_CODEC: StandardMethodCodec = StandardMethodCodec::new();
 
 impl StandardMethodCodec {
-    fn read_value(reader: &mut Reader) -> Result<Value, DecodeError> {
-        if reader.ended() {
-            return Err(DecodeError::Ended);
+    pub const fn new() -> Self {
+        Self {
+            limits: DecodeLimits::DEFAULT,
         }
+    }
 
-        let t = reader.read_u8();
-        Ok(match t {
-            VALUE_NULL => Value::Null,
-            VALUE_FALSE => Value::Boolean(false),
-            VALUE_TRUE => Value::Boolean(true),
-            VALUE_INT32 => Value::I32(reader.read_i32()),
-            VALUE_INT64 => Value::I64(reader.read_i64()),
-            VALUE_LARGEINT => panic!("Not implemented"),
-            VALUE_FLOAT64 => {
-                reader.align_to(8);
-                Value::F64(reader.read_f64())
-            }
-            VALUE_STRING => {
-                let len = reader.read_size();
-                Value::String(reader.read_string(len))
-            }
-            VALUE_UINT8LIST => {
-                let len = reader.read_size();
-                Value::U8List(reader.read_u8_list(len))
-            }
-            VALUE_INT32LIST => {
-                let len = reader.read_size();
-                Value::I32List(reader.read_i32_list(len))
-            }
-            VALUE_INT64LIST => {
-                let len = reader.read_size();
-                Value::I64List(reader.read_i64_list(len))
-            }
-            VALUE_FLOAT64LIST => {
-                let len = reader.read_size();
-                Value::F64List(reader.read_f64_list(len))
-            }
-            VALUE_LIST => {
-                let len = reader.read_size();
-                let mut list = Vec::new();
-                for _ in 0..len {
-                    if let Ok(e) = Self::read_value(reader) {
-                        list.push(e);
-                    } else {
-                        return Err(DecodeError::Invalid);
-                    }
-                }
-                Value::List(list)
-            }
-            VALUE_MAP => {
-                let len = reader.read_size();
-                let mut map = HashMap::new();
-                for _ in 0..len {
-                    let k = Self::read_value(reader);
-                    let v = Self::read_value(reader);
-                    if k.is_err() || v.is_err() {
-                        return Err(DecodeError::Invalid);
-                    }
-                    let k = k.unwrap();
-                    let v = v.unwrap();
-                    if let Value::String(k) = k {
-                        map.insert(k, v);
-                    } else {
-                        return Err(DecodeError::Invalid);
-                    }
-                }
-                Value::Map(map)
-            }
-            _ => Value::Null,
-        })
+    /// Use custom decoding limits, e.g.: for a channel expecting large
+    /// payloads.
+    pub const fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
     }
+
+    fn decoder<'a>(&self, buf: &'a [u8]) -> Decoder<'a> {
+        Decoder {
+            reader: Reader::new(buf),
+            limits: self.limits,
+            decoded_size: 0,
+        }
+    }
+
     fn write_string(writer: &mut Writer, s: &str) {
         writer.write_u8(VALUE_STRING);
         writer.write_size(s.len());
@@ -182,6 +153,12 @@ impl StandardMethodCodec {
     }
 }
 
+impl Default for StandardMethodCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MethodCodec for StandardMethodCodec {
     fn encode_method_call(&self, v: &MethodCall) -> Vec<u8> {
         let mut writer = Writer::new(Vec::new());
@@ -191,16 +168,14 @@ impl MethodCodec for StandardMethodCodec {
         writer.0
     }
 
-    fn decode_method_call(&self, buf: &[u8]) -> Option<MethodCall> {
-        let mut reader = Reader::new(buf);
-        let method: Value = StandardMethodCodec::read_value(&mut reader).unwrap();
-        let args: Value = StandardMethodCodec::read_value(&mut reader).unwrap();
+    fn decode_method_call(&self, buf: &[u8]) -> Result<MethodCall, CodecError> {
+        let mut decoder = self.decoder(buf);
+        let Value::String(method) = decoder.read_value()? else {
+            return Err(CodecError::InvalidType("method name is not a string".into()));
+        };
+        let args = decoder.read_value()?;
 
-        if let Value::String(method) = method {
-            return Some(MethodCall { method, args });
-        }
-        error!("Invalid method call");
-        None
+        Ok(MethodCall { method, args })
     }
 
     fn encode_success_envelope(&self, result: &Value) -> Vec<u8> {
@@ -219,29 +194,27 @@ impl MethodCodec for StandardMethodCodec {
         writer.0
     }
 
-    fn decode_envelope(&self, buf: &[u8]) -> Option<MethodCallResult> {
-        let mut reader = Reader::new(buf);
-        let n = reader.read_u8();
-        if n == 0 {
-            let ret = StandardMethodCodec::read_value(&mut reader).unwrap();
-            Some(MethodCallResult::Ok(ret))
-        } else if n == 1 {
-            let code = StandardMethodCodec::read_value(&mut reader).unwrap();
-            let message = StandardMethodCodec::read_value(&mut reader).unwrap();
-            let details = StandardMethodCodec::read_value(&mut reader).unwrap();
-            Some(MethodCallResult::Err {
-                code: match code {
-                    Value::String(s) => s,
-                    _ => "".into(),
-                },
-                message: match message {
-                    Value::String(s) => s,
-                    _ => "".into(),
-                },
-                details,
-            })
-        } else {
-            None
+    fn decode_envelope(&self, buf: &[u8]) -> Result<MethodCallResult, CodecError> {
+        let mut decoder = self.decoder(buf);
+        match decoder.reader.read_u8()? {
+            0 => Ok(MethodCallResult::Ok(decoder.read_value()?)),
+            1 => {
+                let code = decoder.read_value()?;
+                let message = decoder.read_value()?;
+                let details = decoder.read_value()?;
+                Ok(MethodCallResult::Err {
+                    code: match code {
+                        Value::String(s) => s,
+                        _ => "".into(),
+                    },
+                    message: match message {
+                        Value::String(s) => s,
+                        _ => "".into(),
+                    },
+                    details,
+                })
+            }
+            n => Err(CodecError::InvalidType(format!("unknown envelope {}", n))),
         }
     }
 }
@@ -253,9 +226,211 @@ impl MessageCodec for StandardMethodCodec {
         writer.0
     }
 
-    fn decode_message(&self, buf: &[u8]) -> Option<Value> {
-        let mut reader = Reader::new(buf);
-        StandardMethodCodec::read_value(&mut reader).ok()
+    fn decode_message(&self, buf: &[u8]) -> Result<Value, CodecError> {
+        self.decoder(buf).read_value()
+    }
+}
+
+/// A decoded value, or a list or map whose items are decoded next.
+enum Item {
+    Value(Value),
+    List(usize),
+    Map(usize),
+}
+
+/// List or map being decoded.
+enum Frame {
+    List {
+        remaining: usize,
+        items: Vec<Value>,
+    },
+    Map {
+        remaining: usize,
+        key: Option<String>,
+        entries: HashMap<String, Value>,
+    },
+}
+
+impl Frame {
+    fn list(len: usize) -> Self {
+        Frame::List {
+            remaining: len,
+            items: Vec::with_capacity(len),
+        }
+    }
+
+    fn map(len: usize) -> Self {
+        Frame::Map {
+            remaining: len,
+            key: None,
+            entries: HashMap::with_capacity(len),
+        }
+    }
+
+    /// Add a decoded item. Returns the decoded list or map once complete.
+    fn push(&mut self, value: Value) -> Result<Option<Value>, CodecError> {
+        match self {
+            Frame::List { remaining, items } => {
+                items.push(value);
+                *remaining -= 1;
+                if *remaining > 0 {
+                    return Ok(None);
+                }
+                Ok(Some(Value::List(std::mem::take(items))))
+            }
+            Frame::Map {
+                remaining,
+                key,
+                entries,
+            } => {
+                let Some(key) = key.take() else {
+                    let Value::String(new_key) = value else {
+                        return Err(CodecError::InvalidType("map key is not a string".into()));
+                    };
+                    *key = Some(new_key);
+                    return Ok(None);
+                };
+
+                entries.insert(key, value);
+                *remaining -= 1;
+                if *remaining > 0 {
+                    return Ok(None);
+                }
+                Ok(Some(Value::Map(std::mem::take(entries))))
+            }
+        }
+    }
+}
+
+/// Decodes values without recursion, within the limits of the codec.
+struct Decoder<'a> {
+    reader: Reader<'a>,
+    limits: DecodeLimits,
+    /// See `DecodeLimits::max_decoded_size`.
+    decoded_size: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn read_value(&mut self) -> Result<Value, CodecError> {
+        let mut stack: Vec<Frame> = Vec::new();
+
+        loop {
+            let mut value = match self.read_item()? {
+                Item::Value(value) => value,
+                Item::List(0) => Value::List(Vec::new()),
+                Item::Map(0) => Value::Map(HashMap::new()),
+                Item::List(_) | Item::Map(_) if stack.len() >= self.limits.max_depth => {
+                    return Err(CodecError::LimitExceeded(CodecLimit::Depth));
+                }
+                Item::List(len) => {
+                    stack.push(Frame::list(len));
+                    continue;
+                }
+                Item::Map(len) => {
+                    stack.push(Frame::map(len));
+                    continue;
+                }
+            };
+
+            // Complete the lists and maps the value was the last item of.
+            loop {
+                let Some(frame) = stack.last_mut() else {
+                    return Ok(value);
+                };
+                let Some(complete) = frame.push(value)? else {
+                    break;
+                };
+                stack.pop();
+                value = complete;
+            }
+        }
+    }
+
+    fn read_item(&mut self) -> Result<Item, CodecError> {
+        let value = match self.reader.read_u8()? {
+            VALUE_NULL => Value::Null,
+            VALUE_FALSE => Value::Boolean(false),
+            VALUE_TRUE => Value::Boolean(true),
+            VALUE_INT32 => Value::I32(self.reader.read_i32()?),
+            VALUE_INT64 => Value::I64(self.reader.read_i64()?),
+            VALUE_LARGEINT => {
+                return Err(CodecError::InvalidType("large integers are not supported".into()))
+            }
+            VALUE_FLOAT64 => {
+                self.reader.align_to(8);
+                Value::F64(self.reader.read_f64()?)
+            }
+            VALUE_STRING => {
+                let len = self.read_length(1, 1)?;
+                Value::String(self.reader.read_string(len)?)
+            }
+            VALUE_UINT8LIST => {
+                let len = self.read_length(1, 1)?;
+                Value::U8List(self.reader.read_bytes(len)?.to_vec())
+            }
+            VALUE_INT32LIST => {
+                let len = self.read_aligned_length(4)?;
+                Value::I32List(self.reader.read_list(len, i32::from_ne_bytes)?)
+            }
+            VALUE_INT64LIST => {
+                let len = self.read_aligned_length(8)?;
+                Value::I64List(self.reader.read_list(len, i64::from_ne_bytes)?)
+            }
+            VALUE_FLOAT64LIST => {
+                let len = self.read_aligned_length(8)?;
+                Value::F64List(self.reader.read_list(len, f64::from_ne_bytes)?)
+            }
+            VALUE_LIST => {
+                return Ok(Item::List(self.read_length(1, mem::size_of::<Value>())?));
+            }
+            VALUE_MAP => {
+                let entry_size = mem::size_of::<(String, Value)>();
+                return Ok(Item::Map(self.read_length(2, entry_size)?));
+            }
+            t => return Err(CodecError::InvalidType(format!("unknown value type {}", t))),
+        };
+
+        Ok(Item::Value(value))
+    }
+
+    /// Read the length of a string, list or map, whose items take at least
+    /// |encoded_size| bytes in the message and |decoded_size| bytes once
+    /// decoded.
+    fn read_length(
+        &mut self,
+        encoded_size: usize,
+        decoded_size: usize,
+    ) -> Result<usize, CodecError> {
+        let len = self.reader.read_size()?;
+        self.check_length(len, encoded_size, decoded_size)?;
+        Ok(len)
+    }
+
+    /// Like `read_length`, for typed lists whose items are aligned to their
+    /// |size|.
+    fn read_aligned_length(&mut self, size: usize) -> Result<usize, CodecError> {
+        let len = self.reader.read_size()?;
+        self.reader.align_to(size);
+        self.check_length(len, size, size)?;
+        Ok(len)
+    }
+
+    fn check_length(
+        &mut self,
+        len: usize,
+        encoded_size: usize,
+        decoded_size: usize,
+    ) -> Result<(), CodecError> {
+        if len.saturating_mul(encoded_size) > self.reader.remaining() {
+            return Err(CodecError::Truncated);
+        }
+
+        self.decoded_size = len
+            .checked_mul(decoded_size)
+            .and_then(|size| size.checked_add(self.decoded_size))
+            .filter(|&size| size <= self.limits.max_decoded_size)
+            .ok_or(CodecError::LimitExceeded(CodecLimit::DecodedSize))?;
+        Ok(())
     }
 }
 
@@ -268,94 +443,64 @@ impl<'a> Reader<'a> {
     fn new(buf: &'a [u8]) -> Self {
         Reader { buf, pos: 0 }
     }
-    fn read_u8(&mut self) -> u8 {
-        let n = self.buf[self.pos];
-        self.pos += 1;
-        n
-    }
-    fn read_u16(&mut self) -> u16 {
-        self.pos += 2;
-        let s = &self.buf[self.pos - 2..self.pos];
-        u16::from_ne_bytes(clone_into_array(s))
-    }
-    fn read_u32(&mut self) -> u32 {
-        self.pos += 4;
-        let s = &self.buf[self.pos - 4..self.pos];
-        u32::from_ne_bytes(clone_into_array(s))
-    }
-    fn read_i32(&mut self) -> i32 {
-        self.pos += 4;
-        let s = &self.buf[self.pos - 4..self.pos];
-        i32::from_ne_bytes(clone_into_array(s))
-    }
-    fn read_u64(&mut self) -> u64 {
-        self.pos += 8;
-        let s = &self.buf[self.pos - 8..self.pos];
-        u64::from_ne_bytes(clone_into_array(s))
-    }
-    fn read_i64(&mut self) -> i64 {
-        self.pos += 8;
-        let s = &self.buf[self.pos - 8..self.pos];
-        i64::from_ne_bytes(clone_into_array(s))
-    }
-    fn read_f64(&mut self) -> f64 {
-        let n = self.read_u64();
-        f64::from_bits(n)
-    }
-    fn read_size(&mut self) -> usize {
-        let n = self.read_u8();
-        match n {
-            254 => self.read_u16() as usize,
-            255 => self.read_u32() as usize,
-            _ => n as usize,
-        }
+    fn remaining(&self) -> usize {
+        self.buf.len().saturating_sub(self.pos)
     }
-    fn read_string(&mut self, len: usize) -> String {
-        unsafe {
-            if len == 0 {
-                String::from("")
-            } else {
-                let v = slice::from_raw_parts(&self.buf[self.pos], len);
-                self.pos += len;
-                // TODO: Investigate if `into_owned` is correct in here
-                String::from_utf8_lossy(v).into_owned().to_string()
-            }
-        }
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], CodecError> {
+        let end = self.pos.checked_add(len).ok_or(CodecError::Truncated)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(CodecError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
     }
-    fn read_u8_list(&mut self, len: usize) -> Vec<u8> {
-        let mut v = Vec::with_capacity(len);
-        for _ in 0..len {
-            v.push(self.read_u8());
-        }
-        v
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], CodecError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
     }
-    fn read_i32_list(&mut self, len: usize) -> Vec<i32> {
-        let mut v = Vec::with_capacity(len);
-        self.align_to(4);
-        for _ in 0..len {
-            v.push(self.read_i32());
-        }
-        v
+    fn read_u8(&mut self) -> Result<u8, CodecError> {
+        Ok(u8::from_ne_bytes(self.read_array()?))
     }
-    fn read_i64_list(&mut self, len: usize) -> Vec<i64> {
-        let mut v = Vec::with_capacity(len);
-        self.align_to(8);
-        for _ in 0..len {
-            v.push(self.read_i64());
-        }
-        v
-    }
-    fn read_f64_list(&mut self, len: usize) -> Vec<f64> {
-        let mut v = Vec::with_capacity(len);
-        self.align_to(8);
-        for _ in 0..len {
-            let n = self.read_u64();
-            v.push(f64::from_bits(n));
-        }
-        v
+    fn read_u16(&mut self) -> Result<u16, CodecError> {
+        Ok(u16::from_ne_bytes(self.read_array()?))
+    }
+    fn read_u32(&mut self) -> Result<u32, CodecError> {
+        Ok(u32::from_ne_bytes(self.read_array()?))
+    }
+    fn read_i32(&mut self) -> Result<i32, CodecError> {
+        Ok(i32::from_ne_bytes(self.read_array()?))
+    }
+    fn read_i64(&mut self) -> Result<i64, CodecError> {
+        Ok(i64::from_ne_bytes(self.read_array()?))
     }
-    fn ended(&self) -> bool {
-        self.pos >= self.buf.len()
+    fn read_f64(&mut self) -> Result<f64, CodecError> {
+        Ok(f64::from_ne_bytes(self.read_array()?))
+    }
+    fn read_size(&mut self) -> Result<usize, CodecError> {
+        Ok(match self.read_u8()? {
+            254 => self.read_u16()? as usize,
+            255 => self.read_u32()? as usize,
+            n => n as usize,
+        })
+    }
+    fn read_string(&mut self, len: usize) -> Result<String, CodecError> {
+        let bytes = self.read_bytes(len)?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+    /// Read |len| items of |N| bytes each, converted with |from_bytes|.
+    fn read_list<T, const N: usize>(
+        &mut self,
+        len: usize,
+        from_bytes: fn([u8; N]) -> T,
+    ) -> Result<Vec<T>, CodecError> {
+        let bytes = self.read_bytes(len * N)?;
+        Ok(bytes
+            .chunks_exact(N)
+            .map(|chunk| {
+                let mut array = [0; N];
+                array.copy_from_slice(chunk);
+                from_bytes(array)
+            })
+            .collect())
     }
     fn align_to(&mut self, align: usize) {
         let m = self.pos % align;
@@ -395,10 +540,10 @@ impl Writer {
     fn write_size(&mut self, n: usize) {
         if n < 254 {
             self.write_u8(n as u8);
-        } else if n <= u16::max_value() as usize {
+        } else if n <= u16::MAX as usize {
             self.write_u8(254);
             self.write_u16(n as u16);
-        } else if n < u32::max_value() as usize {
+        } else if n < u32::MAX as usize {
             self.write_u8(255);
             self.write_u32(n as u32);
         } else {
@@ -421,12 +566,181 @@ impl Writer {
     }
 }
 
-fn clone_into_array<A, T>(slice: &[T]) -> A
-where
-    A: Sized + Default + AsMut<[T]>,
-    T: Clone,
-{
-    let mut a = Default::default();
-    <A as AsMut<[T]>>::as_mut(&mut a).clone_from_slice(slice);
-    a
+#[cfg(test)]
+mod tests {
+    use proptest::{
+        collection::{hash_map, vec},
+        prelude::*,
+    };
+
+    use super::{DecodeLimits, StandardMethodCodec, STANDARD_CODEC};
+    use crate::codec::{CodecError, CodecLimit, MessageCodec, MethodCodec, Value};
+
+    /// Size marking a 32 bit length, followed by the largest one.
+    const MAX_SIZE: [u8; 5] = [255, 0xff, 0xff, 0xff, 0xff];
+
+    fn decode(buf: &[u8]) -> Result<Value, CodecError> {
+        STANDARD_CODEC.decode_message(buf)
+    }
+
+    fn nested_lists(depth: usize) -> Vec<u8> {
+        let mut buf = [12, 1].repeat(depth);
+        buf.push(0);
+        buf
+    }
+
+    #[test]
+    fn huge_lengths_are_rejected_before_allocating() {
+        for value_type in [7, 8, 12, 13] {
+            let buf = [&[value_type][..], &MAX_SIZE, &[0; 16]].concat();
+            assert_eq!(decode(&buf), Err(CodecError::Truncated), "type {}", value_type);
+        }
+
+        // The length of typed lists is multiplied by the size of the items.
+        for value_type in [9, 10, 11] {
+            let buf = [&[value_type][..], &MAX_SIZE, &[0; 64]].concat();
+            assert_eq!(decode(&buf), Err(CodecError::Truncated), "type {}", value_type);
+        }
+
+        // Every map entry takes at least two bytes.
+        assert_eq!(decode(&[13, 2, 0, 0, 0]), Err(CodecError::Truncated));
+    }
+
+    #[test]
+    fn nesting_depth_is_limited() {
+        let max_depth = DecodeLimits::DEFAULT.max_depth;
+        assert!(decode(&nested_lists(max_depth)).is_ok());
+
+        let error = Err(CodecError::LimitExceeded(CodecLimit::Depth));
+        assert_eq!(decode(&nested_lists(max_depth + 1)), error);
+        // The decoder doesn't recurse, so it doesn't overflow the stack.
+        assert_eq!(decode(&nested_lists(1_000_000)), error);
+
+        let codec = StandardMethodCodec::new().with_limits(DecodeLimits {
+            max_depth: 2,
+            ..Default::default()
+        });
+        assert_eq!(
+            codec.decode_message(&[13, 1, 7, 1, b'k', 12, 1, 12, 1, 0]),
+            Err(CodecError::LimitExceeded(CodecLimit::Depth))
+        );
+    }
+
+    #[test]
+    fn decoded_size_is_limited() {
+        let codec = StandardMethodCodec::new().with_limits(DecodeLimits {
+            max_decoded_size: 16,
+            ..Default::default()
+        });
+
+        let string = |len: u8| [&[7, len][..], &vec![b'a'; len.into()]].concat();
+        assert!(codec.decode_message(&string(16)).is_ok());
+        assert_eq!(
+            codec.decode_message(&string(17)),
+            Err(CodecError::LimitExceeded(CodecLimit::DecodedSize))
+        );
+
+        // The limit applies to the whole message.
+        let list = [&[12, 2][..], &string(8), &string(9)].concat();
+        assert_eq!(
+            codec.decode_message(&list),
+            Err(CodecError::LimitExceeded(CodecLimit::DecodedSize))
+        );
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        assert_eq!(decode(&[]), Err(CodecError::Truncated));
+        assert_eq!(decode(&[3, 1, 2]), Err(CodecError::Truncated));
+        assert_eq!(decode(&[6, 0, 0]), Err(CodecError::Truncated));
+        assert_eq!(decode(&[12, 2, 0]), Err(CodecError::Truncated));
+        assert!(matches!(decode(&[5]), Err(CodecError::InvalidType(_))));
+        assert!(matches!(decode(&[42]), Err(CodecError::InvalidType(_))));
+        // Map keys are strings.
+        assert!(matches!(decode(&[13, 1, 0, 0]), Err(CodecError::InvalidType(_))));
+
+        assert!(matches!(
+            STANDARD_CODEC.decode_method_call(&[0, 0]),
+            Err(CodecError::InvalidType(_))
+        ));
+        assert!(matches!(
+            STANDARD_CODEC.decode_envelope(&[2, 0]),
+            Err(CodecError::InvalidType(_))
+        ));
+    }
+
+    /// The inputs of the fuzz regression corpus (see `fuzz/`), which used
+    /// to panic or exhaust the memory or the stack.
+    #[test]
+    fn fuzz_corpus_is_rejected() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus/standard_codec");
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let buf = std::fs::read(&path).unwrap();
+            assert!(decode(&buf).is_err(), "{}", path.display());
+            assert!(
+                STANDARD_CODEC.decode_method_call(&buf).is_err(),
+                "{}",
+                path.display()
+            );
+            assert!(
+                STANDARD_CODEC.decode_envelope(&buf).is_err(),
+                "{}",
+                path.display()
+            );
+        }
+    }
+
+    fn value() -> impl Strategy<Value = Value> {
+        let float = any::<f64>().prop_filter("NaN is not equal to itself", |f| !f.is_nan());
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::Boolean),
+            any::<i32>().prop_map(Value::I32),
+            any::<i64>().prop_map(Value::I64),
+            float.clone().prop_map(Value::F64),
+            ".{0,300}".prop_map(Value::String),
+            vec(any::<u8>(), 0..300).prop_map(Value::U8List),
+            vec(any::<i32>(), 0..8).prop_map(Value::I32List),
+            vec(any::<i64>(), 0..8).prop_map(Value::I64List),
+            vec(float, 0..8).prop_map(Value::F64List),
+        ];
+
+        leaf.prop_recursive(8, 256, 8, |inner| {
+            prop_oneof![
+                vec(inner.clone(), 0..8).prop_map(Value::List),
+                hash_map(".{0,8}", inner, 0..8).prop_map(Value::Map),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn values_round_trip(value in value()) {
+            let buf = STANDARD_CODEC.encode_message(&value);
+            prop_assert_eq!(decode(&buf), Ok(value));
+        }
+
+        #[test]
+        fn method_calls_round_trip(method in ".{0,32}", args in value()) {
+            let call = crate::codec::MethodCall { method, args };
+            let decoded = STANDARD_CODEC
+                .decode_method_call(&STANDARD_CODEC.encode_method_call(&call))
+                .unwrap();
+            prop_assert_eq!((decoded.method, decoded.args), (call.method, call.args));
+        }
+
+        #[test]
+        fn arbitrary_bytes_never_panic(buf in vec(any::<u8>(), 0..512)) {
+            let _ = decode(&buf);
+            let _ = STANDARD_CODEC.decode_method_call(&buf);
+            let _ = STANDARD_CODEC.decode_envelope(&buf);
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use tracing::error;
 
-use super::{MessageCodec, Value};
+use super::{CodecError, MessageCodec, Value};
 
 pub struct StringCodec;
 
@@ -18,10 +18,8 @@ impl MessageCodec for StringCodec {
         }
     }
 
-    fn decode_message(&self, buf: &[u8]) -> Option<Value> {
-        unsafe {
-            let s = std::str::from_utf8_unchecked(buf);
-            Some(Value::String(s.to_owned()))
-        }
+    fn decode_message(&self, buf: &[u8]) -> Result<Value, CodecError> {
+        let s = std::str::from_utf8(buf).map_err(|err| CodecError::Malformed(err.to_string()))?;
+        Ok(Value::String(s.to_owned()))
     }
 }
@@ -32,7 +32,7 @@ where
     T::deserialize(&mut Deserializer::new(value))
 }
 
-impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = ValueError;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
@@ -225,3 +225,32 @@ impl serde::de::Error for ValueError {
 }
 
 impl error::Error for ValueError {}
+
+#[derive(Debug)]
+pub enum JsonMessageError {
+    Encode(serde_json::Error),
+    Decode(serde_json::Error),
+    NoReply,
+    /// The engine has been shut down before the message could be sent.
+    EngineGone,
+}
+
+impl fmt::Display for JsonMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonMessageError::Encode(err) => write!(f, "failed to encode json message: {}", err),
+            JsonMessageError::Decode(err) => write!(f, "failed to decode json reply: {}", err),
+            JsonMessageError::NoReply => write!(f, "no handler replied to the message"),
+            JsonMessageError::EngineGone => write!(f, "the engine has been shut down"),
+        }
+    }
+}
+
+impl error::Error for JsonMessageError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            JsonMessageError::Encode(err) | JsonMessageError::Decode(err) => Some(err),
+            JsonMessageError::NoReply | JsonMessageError::EngineGone => None,
+        }
+    }
+}
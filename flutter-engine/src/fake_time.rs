@@ -0,0 +1,120 @@
+//! Deterministic frame times, for reproducible captures (e.g.: golden tests
+//! of full bundles).
+//!
+//! Dart animations are driven by the frame start time passed with the vsync
+//! baton, so replacing the vsync timestamps with a fixed time base and a fixed
+//! interval makes every frame see the same animation time on every run,
+//! however long the frames actually took to render.
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+/// Frame interval of a 60hz display.
+const DEFAULT_FRAME_INTERVAL: Duration = Duration::from_nanos(16_666_667);
+
+/// Time of the first frame, and interval between the following ones. See
+/// `FlutterEngineBuilder::with_fake_time_base`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FakeTimeBase {
+    /// Frame start time of the first frame, on the engine clock. It should be
+    /// in the past (e.g.: zero): the engine doesn't begin a frame before its
+    /// start time.
+    pub start: Duration,
+    pub frame_interval: Duration,
+}
+
+impl FakeTimeBase {
+    pub fn new(start: Duration, frame_interval: Duration) -> Self {
+        Self {
+            start,
+            frame_interval,
+        }
+    }
+}
+
+impl Default for FakeTimeBase {
+    fn default() -> Self {
+        Self::new(Duration::ZERO, DEFAULT_FRAME_INTERVAL)
+    }
+}
+
+/// Hands out the times of the successive frames of a [`FakeTimeBase`].
+#[derive(Debug)]
+pub(crate) struct FakeFrameClock {
+    state: Mutex<FakeFrameClockState>,
+}
+
+#[derive(Debug)]
+struct FakeFrameClockState {
+    next_frame_start_nanos: u64,
+    frame_interval_nanos: u64,
+}
+
+impl FakeFrameClock {
+    pub(crate) fn new(base: FakeTimeBase) -> Self {
+        Self {
+            state: Mutex::new(FakeFrameClockState {
+                next_frame_start_nanos: duration_nanos(base.start),
+                frame_interval_nanos: duration_nanos(base.frame_interval),
+            }),
+        }
+    }
+
+    /// Start and target times of the next frame, in nanoseconds.
+    pub(crate) fn next_frame_time_nanos(&self) -> (u64, u64) {
+        let mut state = self.state.lock();
+        let frame_start_time_nanos = state.next_frame_start_nanos;
+        let frame_target_time_nanos =
+            frame_start_time_nanos.saturating_add(state.frame_interval_nanos);
+        state.next_frame_start_nanos = frame_target_time_nanos;
+
+        (frame_start_time_nanos, frame_target_time_nanos)
+    }
+
+    /// Space the following frames by |frame_interval|. The time keeps
+    /// increasing from the last frame, so that running animations don't jump
+    /// back.
+    pub(crate) fn set_frame_interval(&self, frame_interval: Duration) {
+        self.state.lock().frame_interval_nanos = duration_nanos(frame_interval);
+    }
+}
+
+fn duration_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FakeFrameClock, FakeTimeBase};
+
+    #[test]
+    fn frames_advance_by_the_interval() {
+        let base = FakeTimeBase::new(Duration::from_millis(1), Duration::from_millis(10));
+        let clock = FakeFrameClock::new(base);
+
+        assert_eq!(clock.next_frame_time_nanos(), (1_000_000, 11_000_000));
+        assert_eq!(clock.next_frame_time_nanos(), (11_000_000, 21_000_000));
+        assert_eq!(clock.next_frame_time_nanos(), (21_000_000, 31_000_000));
+    }
+
+    #[test]
+    fn interval_changes_apply_to_the_following_frames() {
+        let base = FakeTimeBase::new(Duration::ZERO, Duration::from_millis(10));
+        let clock = FakeFrameClock::new(base);
+
+        assert_eq!(clock.next_frame_time_nanos(), (0, 10_000_000));
+        clock.set_frame_interval(Duration::from_millis(100));
+        assert_eq!(clock.next_frame_time_nanos(), (10_000_000, 110_000_000));
+        assert_eq!(clock.next_frame_time_nanos(), (110_000_000, 210_000_000));
+    }
+
+    #[test]
+    fn default_base_starts_at_zero() {
+        let clock = FakeFrameClock::new(FakeTimeBase::default());
+
+        assert_eq!(clock.next_frame_time_nanos(), (0, 16_666_667));
+        assert_eq!(clock.next_frame_time_nanos(), (16_666_667, 33_333_334));
+    }
+}
@@ -8,6 +8,7 @@ use dpi::{PhysicalPosition, PhysicalSize};
 use flutter_engine_sys::{
     FlutterBackingStoreType, FlutterEngineDisplayId, FlutterLayerContentType, FlutterSize,
 };
+use serde::Serialize;
 
 pub use flutter_engine_sys::FlutterViewId;
 
@@ -64,6 +65,9 @@ impl From<FlutterPointerDeviceKind> for flutter_engine_sys::FlutterPointerDevice
 pub enum FlutterPointerSignalKind {
     None,
     Scroll,
+    /// Zoom requested by the user (e.g.: trackpad pinch). The zoom factor is
+    /// set using [`FlutterPointerEvent::with_scale`].
+    Scale,
 }
 
 impl From<FlutterPointerSignalKind> for flutter_engine_sys::FlutterPointerSignalKind {
@@ -75,6 +79,9 @@ impl From<FlutterPointerSignalKind> for flutter_engine_sys::FlutterPointerSignal
             FlutterPointerSignalKind::Scroll => {
                 flutter_engine_sys::FlutterPointerSignalKind::kFlutterPointerSignalKindScroll
             }
+            FlutterPointerSignalKind::Scale => {
+                flutter_engine_sys::FlutterPointerSignalKind::kFlutterPointerSignalKindScale
+            }
         }
     }
 }
@@ -95,18 +102,55 @@ impl From<FlutterPointerMouseButtons> for i64 {
     }
 }
 
+/// Set of mouse buttons held down during a pointer event. Flutter expects it
+/// on every event of a mouse device (e.g.: on moves while dragging), not only
+/// on the down and up events.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FlutterPointerButtons(i64);
+
+impl FlutterPointerButtons {
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, button: FlutterPointerMouseButtons) -> bool {
+        let button = i64::from(button);
+        button != 0 && self.0 & button == button
+    }
+
+    pub fn insert(&mut self, button: FlutterPointerMouseButtons) {
+        self.0 |= i64::from(button);
+    }
+
+    pub fn remove(&mut self, button: FlutterPointerMouseButtons) {
+        self.0 &= !i64::from(button);
+    }
+}
+
+impl From<FlutterPointerMouseButtons> for FlutterPointerButtons {
+    fn from(button: FlutterPointerMouseButtons) -> Self {
+        Self(button.into())
+    }
+}
+
+impl From<FlutterPointerButtons> for i64 {
+    fn from(buttons: FlutterPointerButtons) -> Self {
+        buttons.0
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct FlutterPointerEvent {
     timestamp: Duration,
     device: i32,
     phase: FlutterPointerPhase,
-    x: f64,
-    y: f64,
+    position: PhysicalPosition<f64>,
     signal_kind: FlutterPointerSignalKind,
     scroll_delta_x: f64,
     scroll_delta_y: f64,
+    scale: f64,
     device_kind: FlutterPointerDeviceKind,
-    buttons: FlutterPointerMouseButtons,
+    buttons: FlutterPointerButtons,
     view_id: FlutterViewId,
 }
 
@@ -115,11 +159,11 @@ impl FlutterPointerEvent {
     pub fn new(
         device: i32,
         phase: FlutterPointerPhase,
-        (x, y): (f64, f64),
+        position: PhysicalPosition<f64>,
         signal_kind: FlutterPointerSignalKind,
         (scroll_delta_x, scroll_delta_y): (f64, f64),
         device_kind: FlutterPointerDeviceKind,
-        buttons: FlutterPointerMouseButtons,
+        buttons: impl Into<FlutterPointerButtons>,
         view_id: FlutterViewId,
     ) -> Self {
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
@@ -128,16 +172,54 @@ impl FlutterPointerEvent {
             timestamp,
             device,
             phase,
-            x,
-            y,
+            position,
             signal_kind,
             scroll_delta_x,
             scroll_delta_y,
+            scale: 1.0,
             device_kind,
-            buttons,
+            buttons: buttons.into(),
             view_id,
         }
     }
+
+    /// Zoom factor of a [`FlutterPointerSignalKind::Scale`] event, relative to
+    /// the previous scale event.
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn device(&self) -> i32 {
+        self.device
+    }
+
+    /// Time elapsed since the Unix epoch when the event was created.
+    pub fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+
+    /// Replace the creation time of the event, e.g.: when it is delivered
+    /// later than it was received.
+    pub fn with_timestamp(mut self, timestamp: Duration) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn phase(&self) -> FlutterPointerPhase {
+        self.phase
+    }
+
+    pub fn view_id(&self) -> FlutterViewId {
+        self.view_id
+    }
+
+    /// Whether the event only updates the pointer position, as opposed to the
+    /// discrete events (e.g.: downs, ups or scroll signals).
+    pub fn is_motion(&self) -> bool {
+        matches!(self.phase, FlutterPointerPhase::Move | FlutterPointerPhase::Hover)
+            && self.signal_kind == FlutterPointerSignalKind::None
+    }
 }
 
 impl From<FlutterPointerEvent> for flutter_engine_sys::FlutterPointerEvent {
@@ -146,8 +228,8 @@ impl From<FlutterPointerEvent> for flutter_engine_sys::FlutterPointerEvent {
             struct_size: mem::size_of::<flutter_engine_sys::FlutterPointerEvent>(),
             timestamp: event.timestamp.as_micros() as usize,
             phase: event.phase.into(),
-            x: event.x,
-            y: event.y,
+            x: event.position.x,
+            y: event.position.y,
             device: event.device,
             signal_kind: event.signal_kind.into(),
             scroll_delta_x: event.scroll_delta_x,
@@ -156,7 +238,7 @@ impl From<FlutterPointerEvent> for flutter_engine_sys::FlutterPointerEvent {
             buttons: event.buttons.into(),
             pan_x: 0.0,
             pan_y: 0.0,
-            scale: 1.0,
+            scale: event.scale,
             rotation: 0.0,
             view_id: event.view_id,
             #[cfg(all(target_arch = "arm", target_os = "android"))]
@@ -616,8 +698,8 @@ pub struct FlutterLayer {
     pub size: PhysicalSize<f64>,
 
     /// Extra information for the backing store that the embedder may use during
-    /// presentation.
-    pub backing_store_present_info: FlutterBackingStorePresentInfo,
+    /// presentation. This is `None` for platform view layers.
+    pub backing_store_present_info: Option<FlutterBackingStorePresentInfo>,
 }
 
 impl From<flutter_engine_sys::FlutterLayer> for FlutterLayer {
@@ -629,17 +711,18 @@ impl From<flutter_engine_sys::FlutterLayer> for FlutterLayer {
                     FlutterLayerContent::BackingStore(backing_store)
                 }
                 FlutterLayerContentType::kFlutterLayerContentTypePlatformView => {
-                    FlutterLayerContent::PlatformView
+                    let platform_view = unsafe { (*layer.__bindgen_anon_1.platform_view).into() };
+                    FlutterLayerContent::PlatformView(platform_view)
                 }
             },
             offset: PhysicalPosition::new(layer.offset.x, layer.offset.y),
             size: PhysicalSize::new(layer.size.width, layer.size.height),
-            backing_store_present_info: unsafe { (*layer.backing_store_present_info).into() },
+            backing_store_present_info: unsafe { layer.backing_store_present_info.as_ref() }
+                .map(|present_info| (*present_info).into()),
         }
     }
 }
 
-// TODO: Add support for platform view layer content
 pub enum FlutterLayerContent {
     /// Indicates that the contents of this layer are rendered by Flutter into a
     /// backing store.
@@ -647,7 +730,7 @@ pub enum FlutterLayerContent {
 
     /// Indicates that the contents of this layer are determined by the
     /// embedder.
-    PlatformView,
+    PlatformView(FlutterPlatformView),
 }
 
 impl FlutterLayerContent {
@@ -665,6 +748,82 @@ impl FlutterLayerContent {
 
         Some(framebuffer.name)
     }
+
+    /// The texture the framebuffer of an OpenGL backing store renders to.
+    pub fn get_opengl_backing_store_texture_id(&self) -> Option<u32> {
+        let FlutterLayerContent::BackingStore(backing_store) = self else {
+            return None;
+        };
+
+        let FlutterBackingStoreDescription::OpenGL(FlutterOpenGLBackingStore::Framebuffer(
+            framebuffer,
+        )) = backing_store.description
+        else {
+            return None;
+        };
+
+        Some(framebuffer.user_data.texture_id)
+    }
+}
+
+/// A platform view to be composited by the embedder.
+#[derive(Debug, Clone)]
+pub struct FlutterPlatformView {
+    /// The identifier specified by the application when the platform view was
+    /// added to the scene.
+    pub identifier: i64,
+
+    /// The mutations to apply to the platform view, in order, before it is
+    /// composited on-screen.
+    pub mutations: Vec<FlutterPlatformViewMutation>,
+}
+
+impl From<flutter_engine_sys::FlutterPlatformView> for FlutterPlatformView {
+    fn from(platform_view: flutter_engine_sys::FlutterPlatformView) -> Self {
+        let mutations = if platform_view.mutations.is_null() {
+            Vec::new()
+        } else {
+            unsafe {
+                slice::from_raw_parts(platform_view.mutations, platform_view.mutations_count)
+                    .iter()
+                    .map(|mutation| (**mutation).into())
+                    .collect()
+            }
+        };
+
+        Self {
+            identifier: platform_view.identifier,
+            mutations,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FlutterPlatformViewMutation {
+    Opacity(f64),
+    ClipRect(flutter_engine_sys::FlutterRect),
+    ClipRoundedRect(flutter_engine_sys::FlutterRoundedRect),
+    Transformation(flutter_engine_sys::FlutterTransformation),
+}
+
+impl From<flutter_engine_sys::FlutterPlatformViewMutation> for FlutterPlatformViewMutation {
+    fn from(mutation: flutter_engine_sys::FlutterPlatformViewMutation) -> Self {
+        use flutter_engine_sys::FlutterPlatformViewMutationType as Type;
+
+        let value = &mutation.__bindgen_anon_1;
+        unsafe {
+            match mutation.type_ {
+                Type::kFlutterPlatformViewMutationTypeOpacity => Self::Opacity(value.opacity),
+                Type::kFlutterPlatformViewMutationTypeClipRect => Self::ClipRect(value.clip_rect),
+                Type::kFlutterPlatformViewMutationTypeClipRoundedRect => {
+                    Self::ClipRoundedRect(value.clip_rounded_rect)
+                }
+                Type::kFlutterPlatformViewMutationTypeTransformation => {
+                    Self::Transformation(value.transformation)
+                }
+            }
+        }
+    }
 }
 
 /// Contains additional information about the backing store provided during
@@ -755,3 +914,47 @@ impl From<FlutterEngineDisplay> for flutter_engine_sys::FlutterEngineDisplay {
         }
     }
 }
+
+/// Accessibility features requested by the platform, see
+/// [`crate::FlutterEngine::update_accessibility_features`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlutterAccessibilityFeatures {
+    /// An assistive technology (e.g.: a screen reader) is navigating the UI.
+    pub accessible_navigation: bool,
+    pub invert_colors: bool,
+    pub disable_animations: bool,
+    pub bold_text: bool,
+    pub reduce_motion: bool,
+    /// Exposed to Dart as `MediaQuery.highContrast`.
+    pub high_contrast: bool,
+    pub on_off_switch_labels: bool,
+}
+
+impl From<FlutterAccessibilityFeatures> for flutter_engine_sys::FlutterAccessibilityFeature {
+    fn from(features: FlutterAccessibilityFeatures) -> Self {
+        use flutter_engine_sys::FlutterAccessibilityFeature as Feature;
+
+        [
+            (
+                features.accessible_navigation,
+                Feature::kFlutterAccessibilityFeatureAccessibleNavigation,
+            ),
+            (features.invert_colors, Feature::kFlutterAccessibilityFeatureInvertColors),
+            (
+                features.disable_animations,
+                Feature::kFlutterAccessibilityFeatureDisableAnimations,
+            ),
+            (features.bold_text, Feature::kFlutterAccessibilityFeatureBoldText),
+            (features.reduce_motion, Feature::kFlutterAccessibilityFeatureReduceMotion),
+            (features.high_contrast, Feature::kFlutterAccessibilityFeatureHighContrast),
+            (
+                features.on_off_switch_labels,
+                Feature::kFlutterAccessibilityFeatureOnOffSwitchLabels,
+            ),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .fold(Feature(0), |flags, (_, feature)| flags | feature)
+    }
+}
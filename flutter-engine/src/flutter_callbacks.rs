@@ -1,16 +1,95 @@
+use crate::channel::platform_message::PlatformMessage;
+use crate::channel::Direction;
 use crate::ffi::{FlutterFrameInfo, FlutterLayer, FlutterPresentViewInfo, IMPLICIT_VIEW_ID};
 use crate::tasks::{TaskRunner, TaskRunnerInner};
 use crate::FlutterEngineInner;
 use core::slice;
+use flutter_engine_api::{DamageRect, FlutterPresentInfo};
 use tracing::trace;
 use parking_lot::Mutex;
+use std::cell::Cell;
 use std::ffi::{c_char, c_uint, c_void, CStr};
 
-pub extern "C" fn present(user_data: *mut c_void) -> bool {
-    trace!("present");
+pub extern "C" fn present_with_info(
+    user_data: *mut c_void,
+    info: *const flutter_engine_sys::FlutterPresentInfo,
+) -> bool {
+    trace!("present_with_info");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine.implicit_view_opengl_handler().unwrap().present()
+        engine.notify_frame_progress();
+        let info = FlutterPresentInfo {
+            fbo_id: (*info).fbo_id,
+            frame_damage: damage_rects(&(*info).frame_damage),
+            buffer_damage: damage_rects(&(*info).buffer_damage),
+        };
+        engine
+            .implicit_view_opengl_handler()
+            .unwrap()
+            .present_with_info(info)
+    }
+}
+
+unsafe fn damage_rects(damage: &flutter_engine_sys::FlutterDamage) -> Vec<DamageRect> {
+    if damage.damage.is_null() {
+        return Vec::new();
+    }
+
+    slice::from_raw_parts(damage.damage, damage.num_rects)
+        .iter()
+        .map(|rect| DamageRect::new(rect.left, rect.top, rect.right, rect.bottom))
+        .collect()
+}
+
+thread_local! {
+    // The engine reads the existing damage after the callback returns, so it
+    // must outlive the callback. It is only used by the render thread.
+    static EXISTING_DAMAGE: Cell<flutter_engine_sys::FlutterRect> = const {
+        Cell::new(flutter_engine_sys::FlutterRect {
+            left: 0.0,
+            top: 0.0,
+            right: 0.0,
+            bottom: 0.0,
+        })
+    };
+}
+
+pub extern "C" fn populate_existing_damage(
+    user_data: *mut c_void,
+    fbo_id: isize,
+    existing_damage: *mut flutter_engine_sys::FlutterDamage,
+) {
+    trace!("populate_existing_damage");
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        let damage = engine
+            .implicit_view_opengl_handler()
+            .unwrap()
+            .existing_damage(fbo_id as u32);
+
+        let existing_damage = &mut *existing_damage;
+        existing_damage.struct_size = std::mem::size_of::<flutter_engine_sys::FlutterDamage>();
+        match damage {
+            // The engine only supports a single rectangle.
+            Some(damage) => {
+                let rect = EXISTING_DAMAGE.with(|rect| {
+                    rect.set(flutter_engine_sys::FlutterRect {
+                        left: damage.left,
+                        top: damage.top,
+                        right: damage.right,
+                        bottom: damage.bottom,
+                    });
+                    rect.as_ptr()
+                });
+                existing_damage.num_rects = 1;
+                existing_damage.damage = rect;
+            }
+            // No damage forces a full repaint.
+            None => {
+                existing_damage.num_rects = 0;
+                existing_damage.damage = std::ptr::null_mut();
+            }
+        }
     }
 }
 
@@ -78,6 +157,7 @@ pub extern "C" fn vsync_callback(user_data: *mut c_void, baton: isize) {
     trace!("vsync_callback");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
+        engine.notify_frame_progress();
         // `vsync_callback` will only be called when `vsync_handler` is not empty,
         // so using `unwrap()` should be safe in here.
         engine
@@ -138,6 +218,7 @@ pub extern "C" fn compositor_present_view_callback(
             .collect();
 
         let info = FlutterPresentViewInfo::new(info.view_id, layers);
+        engine.notify_frame_progress();
 
         engine
             .compositor_handler_for_view(info.view_id)
@@ -154,22 +235,46 @@ pub extern "C" fn platform_message_callback(
     trace!("platform_message_callback");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine
-            .channel_registry
-            .read()
-            .handle((*platform_message).into());
+        let message: PlatformMessage = (*platform_message).into();
+        let replaced = engine.intercept_message(Direction::Inbound, &message);
+        let message = match &replaced {
+            Some(bytes) => PlatformMessage { message: bytes, ..message },
+            None => message,
+        };
+
+        if engine.channel_tracer.is_enabled() {
+            engine.trace_message(Direction::Inbound, &message);
+        }
+
+        engine.channel_registry.read().handle(message);
     }
 }
 
-pub extern "C" fn root_isolate_create_callback(_user_data: *mut c_void) {
+pub extern "C" fn root_isolate_create_callback(user_data: *mut c_void) {
     trace!("root_isolate_create_callback");
-    // // This callback is executed on the main thread
-    // unsafe {
-    //     let user_data = &mut *(user_data as *mut DesktopUserData);
-    //     if let DesktopUserData::WindowState(window_state) = user_data {
-    //         window_state.set_isolate_created();
-    //     }
-    // }
+    // This callback is executed on the UI thread.
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        if let Some(callback) = &engine.root_isolate_create_callback {
+            callback();
+        }
+    }
+}
+
+pub extern "C" fn log_message_callback(
+    tag: *const c_char,
+    message: *const c_char,
+    user_data: *mut c_void,
+) {
+    // This callback is executed on an engine thread.
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        if let Some(callback) = &engine.log_message_callback {
+            let tag = CStr::from_ptr(tag).to_string_lossy();
+            let message = CStr::from_ptr(message).to_string_lossy();
+            callback(&tag, &message);
+        }
+    }
 }
 
 pub extern "C" fn runs_task_on_current_thread(user_data: *mut c_void) -> bool {
@@ -213,3 +318,13 @@ pub extern "C" fn gl_external_texture_frame(
         false
     }
 }
+
+pub(crate) type KeyEventCallback = Box<dyn FnOnce(bool) + Send>;
+
+pub extern "C" fn key_event_callback(handled: bool, user_data: *mut c_void) {
+    trace!("key_event_callback");
+    unsafe {
+        let callback = Box::from_raw(user_data as *mut KeyEventCallback);
+        callback(handled);
+    }
+}
@@ -3,33 +3,57 @@ pub mod channel;
 pub mod codec;
 pub mod compositor;
 pub mod error;
+pub mod fake_time;
 pub mod ffi;
 mod flutter_callbacks;
 pub mod plugins;
+pub mod pointer_queue;
+pub mod prepare;
+mod procs;
+pub mod runtime;
+pub mod schedule;
 pub mod tasks;
+pub mod threads;
 pub mod view;
 
 pub mod texture_registry;
 
-use crate::builder::FlutterEngineBuilder;
-use crate::channel::{Channel, ChannelRegistry};
+use crate::builder::{
+    FlutterEngineBuilder, LogMessageCallback, RootIsolateCreateCallback,
+    RootIsolateShutdownCallback,
+};
+use crate::channel::interceptor::{InterceptedMessage, InterceptorSlot};
+use crate::channel::json_reply::encode_json_message;
+use crate::channel::tracer::ChannelTracer;
+use crate::channel::{
+    Channel, ChannelFilter, ChannelRegistry, ChannelStats, Direction, JsonReply,
+    MessageInterceptor,
+};
 
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
-use crate::tasks::TaskRunner;
-use crate::texture_registry::{Texture, TextureRegistry};
+use crate::fake_time::FakeFrameClock;
+use crate::procs::{engine_proc, optional_engine_proc};
+use crate::pointer_queue::{PointerBackpressure, PointerQueue, PointerQueueStats};
+use crate::schedule::{ScheduleHandle, ScheduledTasks};
+use crate::tasks::{flutter_time_to_instant, TaskRunner};
+use crate::threads::ThreadPolicyReport;
+use crate::texture_registry::{DmabufTextureFrame, Texture, TextureId, TextureRegistry};
 use compositor::FlutterCompositorHandler;
 use crossbeam_channel::{unbounded, Receiver, Sender};
+use dpi::PhysicalSize;
+use error::JsonMessageError;
 use ffi::{
-    FlutterEngineDisplay, FlutterEngineDisplaysUpdateType, FlutterKeyEvent, FlutterPointerEvent,
-    FlutterViewId,
+    FlutterAccessibilityFeatures, FlutterEngineDisplay, FlutterEngineDisplaysUpdateType,
+    FlutterKeyEvent, FlutterPointerEvent, FlutterViewId,
 };
 use flutter_engine_api::FlutterOpenGLHandler;
 use flutter_engine_sys::{
-    FlutterCompositor, FlutterEngineDisplayId, FlutterEngineGetCurrentTime, FlutterEngineResult,
-    FlutterTask, VsyncCallback,
+    FlutterCompositor, FlutterEngineDisplayId, FlutterEngineResult, FlutterTask, VsyncCallback,
 };
 use parking_lot::{Mutex, RwLock};
-use std::ffi::{c_void, CString};
+use serde::{de::DeserializeOwned, Serialize};
+use std::ffi::{c_void, CStr, CString};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::ptr;
 use std::sync::{Arc, Weak};
@@ -51,14 +75,33 @@ struct FlutterEngineInner {
     vsync_handler: Option<Arc<Mutex<dyn FlutterVsyncHandler + Send>>>,
     engine_ptr: flutter_engine_sys::FlutterEngine,
     channel_registry: RwLock<ChannelRegistry>,
+    channel_tracer: ChannelTracer,
+    message_interceptor: InterceptorSlot,
+    pointer_queue: Mutex<PointerQueue>,
+    /// Time at which the oldest vsync baton which did not lead to any engine
+    /// progress (i.e.: a new vsync request or a frame) was answered.
+    vsync_answered_at: Mutex<Option<Instant>>,
+    /// Replaces the vsync timestamps, see
+    /// `FlutterEngineBuilder::with_fake_time_base`.
+    fake_frame_clock: Option<FakeFrameClock>,
     platform_runner: TaskRunner,
     platform_receiver: Receiver<MainThreadCallback>,
     platform_sender: Sender<MainThreadCallback>,
+    /// Closures scheduled with [`FlutterEngine::schedule_at`] and
+    /// [`FlutterEngine::schedule_repeating`].
+    scheduled_tasks: Arc<Mutex<ScheduledTasks<FlutterEngine>>>,
     texture_registry: TextureRegistry,
     assets: PathBuf,
-    icu_data: PathBuf,
+    /// `None` when using the ICU data embedded in the engine library.
+    icu_data: Option<PathBuf>,
     persistent_cache: PathBuf,
     arguments: Vec<String>,
+    root_isolate_create_callback: Option<RootIsolateCreateCallback>,
+    root_isolate_shutdown_callback: Mutex<Option<RootIsolateShutdownCallback>>,
+    log_message_callback: Option<LogMessageCallback>,
+    /// Exit code requested by Dart, see [`FlutterEngine::set_exit_code`].
+    exit_code: Mutex<Option<i32>>,
+    accessibility_features: Mutex<FlutterAccessibilityFeatures>,
 }
 
 impl FlutterEngineInner {
@@ -74,6 +117,74 @@ impl FlutterEngineInner {
             .read()
             .compositor_handler_for_view(view_id)
     }
+
+    /// Called when the engine requests a vsync baton or presents a frame,
+    /// which means that it is not stalled.
+    fn notify_frame_progress(&self) {
+        self.vsync_answered_at.lock().take();
+
+        if !self.pointer_queue.lock().is_stalled() {
+            return;
+        }
+
+        // Forward the latest pointer positions without waiting for new events.
+        let callback = MainThreadCallback::Engine(Box::new(|engine| engine.resume_pointer_events()));
+        self.platform_sender.send(callback).unwrap();
+        self.platform_runner.wake();
+    }
+
+    /// Whether the last answered vsync baton did not lead to any engine
+    /// progress in time. An engine which skipped that frame looks stalled as
+    /// well, until its next frame request.
+    fn is_stalled(&self, now: Instant, stall_timeout: Duration) -> bool {
+        self.vsync_answered_at
+            .lock()
+            .is_some_and(|answered_at| now.duration_since(answered_at) >= stall_timeout)
+    }
+
+    fn trace_message(&self, direction: Direction, message: &PlatformMessage) {
+        // Messages may be sent from within a channel handler, while the
+        // registry is already locked.
+        let codec = self
+            .channel_registry
+            .read_recursive()
+            .method_codec(&message.channel);
+
+        self.channel_tracer.record_message(
+            direction,
+            &message.channel,
+            message.message,
+            message.response_handle.as_ref().map(|handle| handle.trace_key()),
+            codec,
+        );
+    }
+
+    /// Returns the bytes replacing the message, if the interceptor modified it.
+    fn intercept_message(
+        &self,
+        direction: Direction,
+        message: &PlatformMessage,
+    ) -> Option<Vec<u8>> {
+        if !self.message_interceptor.is_set() {
+            return None;
+        }
+        let interceptor = self.message_interceptor.get()?;
+
+        let (method_codec, message_codec) = {
+            let registry = self.channel_registry.read_recursive();
+            (
+                registry.method_codec(&message.channel),
+                registry.message_codec(&message.channel),
+            )
+        };
+        interceptor.intercept(&InterceptedMessage::new(
+            direction,
+            &message.channel,
+            message.message,
+            method_codec,
+            message_codec,
+        ))
+    }
 }
 
 pub struct FlutterEngineWeakRef {
@@ -132,8 +243,23 @@ pub trait FlutterVsyncHandler {
     fn request_frame_callback(&self, baton: isize);
 }
 
+/// How the root isolate exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolateExit {
+    /// Dart requested the application to exit (e.g.:
+    /// `ServicesBinding.exitApplication`) with this exit code.
+    Requested { exit_code: i32 },
+    /// The embedder shut the engine down (e.g.: the window was closed).
+    Shutdown,
+}
+
 impl FlutterEngine {
     pub(crate) fn new(builder: FlutterEngineBuilder) -> Result<Self, CreateError> {
+        let missing_procs = procs::missing_required_procs();
+        if !missing_procs.is_empty() {
+            return Err(CreateError::MissingEngineProcs(missing_procs));
+        }
+
         // Convert arguments into flutter compatible
         //
         // FlutterProjectArgs expects a full argv, so when processing it for flags
@@ -144,6 +270,7 @@ impl FlutterEngine {
             dummy_args,
             FlutterEngine::args_from_env_vars(),
             builder.args.clone(),
+            builder.vm_service_args(),
         ]
         .concat();
 
@@ -152,6 +279,8 @@ impl FlutterEngine {
             .map(|arg| CString::new(arg.as_str()).unwrap().into_raw())
             .collect();
 
+        let icu_data = builder.resolve_icu_data()?;
+
         let (main_tx, main_rx) = unbounded();
 
         let engine = Self {
@@ -161,16 +290,27 @@ impl FlutterEngine {
                 vsync_handler: builder.vsync_handler,
                 engine_ptr: ptr::null_mut(),
                 channel_registry: RwLock::new(ChannelRegistry::new()),
+                channel_tracer: ChannelTracer::from_env(),
+                message_interceptor: Default::default(),
+                pointer_queue: Default::default(),
+                vsync_answered_at: Default::default(),
+                fake_frame_clock: builder.fake_time_base.map(FakeFrameClock::new),
                 platform_runner: TaskRunner::new(
                     builder.platform_handler.expect("No platform runner set"),
                 ),
                 platform_receiver: main_rx,
                 platform_sender: main_tx,
+                scheduled_tasks: Default::default(),
                 texture_registry: TextureRegistry::new(),
                 assets: builder.assets,
-                icu_data: builder.icu_data,
+                icu_data,
                 persistent_cache: builder.persistent_cache,
                 arguments: builder.args,
+                root_isolate_create_callback: builder.root_isolate_create_callback,
+                root_isolate_shutdown_callback: Mutex::new(builder.root_isolate_shutdown_callback),
+                log_message_callback: builder.log_message_callback,
+                exit_code: Default::default(),
+                accessibility_features: Default::default(),
             }),
         };
 
@@ -187,7 +327,8 @@ impl FlutterEngine {
                     ),
                     make_current: Some(flutter_callbacks::make_current),
                     clear_current: Some(flutter_callbacks::clear_current),
-                    present: Some(flutter_callbacks::present),
+                    // Only one of `present` and `present_with_info` may be set.
+                    present: None,
                     fbo_callback: None,
                     make_resource_current: Some(flutter_callbacks::make_resource_current),
                     fbo_reset_after_present: false,
@@ -199,8 +340,8 @@ impl FlutterEngine {
                     fbo_with_frame_info_callback: Some(
                         flutter_callbacks::fbo_with_frame_info_callback,
                     ),
-                    present_with_info: None,
-                    populate_existing_damage: None,
+                    present_with_info: Some(flutter_callbacks::present_with_info),
+                    populate_existing_damage: Some(flutter_callbacks::populate_existing_damage),
                 },
             },
         };
@@ -225,7 +366,10 @@ impl FlutterEngine {
             platform_task_runner: &platform_task_runner
                 as *const flutter_engine_sys::FlutterTaskRunnerDescription,
             render_task_runner: std::ptr::null(),
-            thread_priority_setter: None,
+            thread_priority_setter: match threads::set_thread_config(builder.thread_config) {
+                true => Some(threads::thread_priority_setter),
+                false => None,
+            },
         };
 
         let vsync_callback: VsyncCallback = match inner.vsync_handler {
@@ -256,7 +400,10 @@ impl FlutterEngine {
             assets_path: path_to_cstring(&inner.assets).into_raw(),
             main_path__unused__: std::ptr::null(),
             packages_path__unused__: std::ptr::null(),
-            icu_data_path: path_to_cstring(&inner.icu_data).into_raw(),
+            icu_data_path: match &inner.icu_data {
+                Some(icu_data) => path_to_cstring(icu_data).into_raw(),
+                None => std::ptr::null_mut(),
+            },
             command_line_argc: args.len() as i32,
             command_line_argv: args.as_mut_ptr() as _,
             platform_message_callback: Some(flutter_callbacks::platform_message_callback),
@@ -284,7 +431,10 @@ impl FlutterEngine {
             compute_platform_resolved_locale_callback: None,
             dart_entrypoint_argc: 0,
             dart_entrypoint_argv: std::ptr::null(),
-            log_message_callback: None,
+            log_message_callback: match inner.log_message_callback {
+                Some(_) => Some(flutter_callbacks::log_message_callback),
+                None => None,
+            },
             log_tag: std::ptr::null(),
             on_pre_engine_restart_callback: None,
             update_semantics_callback: None,
@@ -296,7 +446,7 @@ impl FlutterEngine {
         unsafe {
             let inner_ptr = Weak::into_raw(Arc::downgrade(inner)) as *mut std::ffi::c_void;
 
-            if flutter_engine_sys::FlutterEngineInitialize(
+            if engine_proc!(Initialize)(
                 1,
                 &renderer_config,
                 &project_args,
@@ -314,8 +464,51 @@ impl FlutterEngine {
     }
 
     pub fn get_current_time_duration() -> Duration {
-        let current_time_nanos = unsafe { FlutterEngineGetCurrentTime() };
-        Duration::from_nanos(current_time_nanos)
+        Duration::from_nanos(Self::get_current_time_nanos())
+    }
+
+    /// Current time of the engine clock (i.e.: the clock used for the vsync
+    /// and task target times), in nanoseconds.
+    pub fn get_current_time_nanos() -> u64 {
+        unsafe { engine_proc!(GetCurrentTime)() }
+    }
+
+    /// Begin a duration event named |name| on the engine timeline, so that
+    /// native work shows up in the DevTools timeline along with the frames.
+    /// It must be ended with `trace_event_end` on the same thread.
+    ///
+    /// Can be called from any thread. Like the other timeline events, it is
+    /// a no-op unless the engine is running in debug or profile mode.
+    #[inline]
+    pub fn trace_event_begin(name: &CStr) {
+        #[cfg(feature = "timeline")]
+        if let Some(trace_event) = optional_engine_proc!(TraceEventDurationBegin) {
+            unsafe { trace_event(name.as_ptr()) };
+        }
+        #[cfg(not(feature = "timeline"))]
+        let _ = name;
+    }
+
+    /// End the duration event named |name| begun with `trace_event_begin`.
+    #[inline]
+    pub fn trace_event_end(name: &CStr) {
+        #[cfg(feature = "timeline")]
+        if let Some(trace_event) = optional_engine_proc!(TraceEventDurationEnd) {
+            unsafe { trace_event(name.as_ptr()) };
+        }
+        #[cfg(not(feature = "timeline"))]
+        let _ = name;
+    }
+
+    /// Add an instant event named |name| to the engine timeline.
+    #[inline]
+    pub fn trace_event_instant(name: &CStr) {
+        #[cfg(feature = "timeline")]
+        if let Some(trace_event) = optional_engine_proc!(TraceEventInstant) {
+            unsafe { trace_event(name.as_ptr()) };
+        }
+        #[cfg(not(feature = "timeline"))]
+        let _ = name;
     }
 
     #[inline]
@@ -355,6 +548,17 @@ impl FlutterEngine {
             .remove_channel(channel_name)
     }
 
+    /// Remove every channel (e.g.: once the plugins are detached). The
+    /// messages received afterwards get an empty reply, which Dart reports as
+    /// a missing plugin.
+    pub fn remove_all_channels(&self) -> usize {
+        trace!("remove all channels");
+        self.inner
+            .channel_registry
+            .write()
+            .remove_all_channels()
+    }
+
     pub fn with_channel<F>(&self, channel_name: &str, f: F)
     where
         F: FnOnce(&dyn Channel),
@@ -365,6 +569,36 @@ impl FlutterEngine {
             .with_channel(channel_name, f)
     }
 
+    /// Trace the messages sent on the channels matching |filter|, or disable
+    /// tracing with `None`. See [`ChannelFilter`] for the filter syntax.
+    pub fn set_channel_tracer(&self, filter: Option<ChannelFilter>) {
+        self.inner.channel_tracer.set_filter(filter);
+    }
+
+    /// Traffic counters of the channels traced so far, along with the flow
+    /// counters of the event channels.
+    pub fn channel_stats(&self) -> Vec<ChannelStats> {
+        let mut stats = self.inner.channel_tracer.stats();
+        for (channel, flow) in self.inner.channel_registry.read_recursive().event_flow_stats() {
+            match stats.iter_mut().find(|stats| stats.channel == channel) {
+                Some(stats) => stats.events = Some(flow),
+                None => stats.push(ChannelStats {
+                    events: Some(flow),
+                    ..ChannelStats::new(channel)
+                }),
+            }
+        }
+
+        stats.sort_by(|a, b| a.channel.cmp(&b.channel));
+        stats
+    }
+
+    /// Install an interceptor seeing every platform message before it is
+    /// dispatched, or remove it with `None`. See [`MessageInterceptor`].
+    pub fn set_message_interceptor(&self, interceptor: Option<Arc<dyn MessageInterceptor>>) {
+        self.inner.message_interceptor.set(interceptor);
+    }
+
     pub fn downgrade(&self) -> FlutterEngineWeakRef {
         FlutterEngineWeakRef {
             inner: Arc::downgrade(&self.inner),
@@ -375,6 +609,10 @@ impl FlutterEngine {
         &self.inner.assets
     }
 
+    pub fn persistent_cache_path(&self) -> &Path {
+        &self.inner.persistent_cache
+    }
+
     pub fn arguments(&self) -> &Vec<String> {
         &self.inner.arguments
     }
@@ -385,7 +623,7 @@ impl FlutterEngine {
         }
 
         unsafe {
-            match flutter_engine_sys::FlutterEngineRunInitialized(self.engine_ptr()) {
+            match engine_proc!(RunInitialized)(self.engine_ptr()) {
                 FlutterEngineResult::kSuccess => Ok(()),
                 FlutterEngineResult::kInvalidLibraryVersion => Err(RunError::InvalidLibraryVersion),
                 FlutterEngineResult::kInvalidArguments => Err(RunError::InvalidArguments),
@@ -425,6 +663,47 @@ impl FlutterEngine {
         }
     }
 
+    /// Run |f| on the platform thread once the engine clock (see
+    /// [`FlutterEngine::get_current_time_nanos`]) reaches |engine_time_nanos|.
+    ///
+    /// Scheduled closures share a single timer with the engine tasks, so this
+    /// is cheaper and more precise than a timer per closure. Like the engine
+    /// tasks, they only run while the platform tasks are executed.
+    pub fn schedule_at<F>(&self, engine_time_nanos: u64, f: F) -> ScheduleHandle
+    where
+        F: FnOnce(&FlutterEngine) + 'static + Send,
+    {
+        trace!("schedule_at");
+        let id = self
+            .inner
+            .scheduled_tasks
+            .lock()
+            .schedule_at(engine_time_nanos, Box::new(f));
+        self.inner.platform_runner.wake();
+        ScheduleHandle::new(id, Arc::downgrade(&self.inner.scheduled_tasks))
+    }
+
+    /// Run |f| on the platform thread every |interval|, starting one interval
+    /// from now, until the returned handle is used for cancelling it.
+    ///
+    /// Late runs don't delay the next ones, and runs which were missed
+    /// entirely (e.g.: while the platform thread was busy) are skipped.
+    pub fn schedule_repeating<F>(&self, interval: Duration, f: F) -> ScheduleHandle
+    where
+        F: FnMut(&FlutterEngine) + 'static + Send,
+    {
+        trace!("schedule_repeating");
+        let interval_nanos = u64::try_from(interval.as_nanos()).unwrap_or(u64::MAX);
+        let first_time_nanos = Self::get_current_time_nanos().saturating_add(interval_nanos);
+        let id = self.inner.scheduled_tasks.lock().schedule_repeating(
+            first_time_nanos,
+            interval_nanos,
+            Box::new(f),
+        );
+        self.inner.platform_runner.wake();
+        ScheduleHandle::new(id, Arc::downgrade(&self.inner.scheduled_tasks))
+    }
+
     pub fn run_on_render_thread<F>(&self, f: F)
     where
         F: FnOnce(&FlutterEngine) + 'static + Send,
@@ -438,6 +717,18 @@ impl FlutterEngine {
         // }
     }
 
+    /// Space the following frames by |frame_interval| in the fake time base
+    /// (e.g.: for capturing an animation at a given rate). Returns whether
+    /// the engine was built with a fake time base, see
+    /// [`FlutterEngineBuilder::with_fake_time_base`].
+    pub fn set_fake_frame_interval(&self, frame_interval: Duration) -> bool {
+        let Some(clock) = &self.inner.fake_frame_clock else {
+            return false;
+        };
+        clock.set_frame_interval(frame_interval);
+        true
+    }
+
     pub fn on_vsync(
         &self,
         baton: isize,
@@ -449,8 +740,19 @@ impl FlutterEngine {
             panic!("Not on platform thread");
         }
 
+        self.inner
+            .vsync_answered_at
+            .lock()
+            .get_or_insert_with(Instant::now);
+
+        let (frame_start_time_nanos, frame_target_time_nanos) =
+            match &self.inner.fake_frame_clock {
+                Some(clock) => clock.next_frame_time_nanos(),
+                None => (frame_start_time_nanos, frame_target_time_nanos),
+            };
+
         unsafe {
-            flutter_engine_sys::FlutterEngineOnVsync(
+            engine_proc!(OnVsync)(
                 self.engine_ptr(),
                 baton,
                 frame_start_time_nanos,
@@ -459,23 +761,99 @@ impl FlutterEngine {
         }
     }
 
+    /// Schedule a new frame, even if no vsync request is pending.
+    pub fn schedule_frame(&self) {
+        trace!("schedule_frame");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if let Some(schedule_frame) = optional_engine_proc!(ScheduleFrame) {
+            unsafe { schedule_frame(self.engine_ptr()) };
+        }
+    }
+
+    /// Notify the engine that the application is running low on memory, so
+    /// that it can release its caches.
+    pub fn notify_low_memory_warning(&self) {
+        trace!("notify_low_memory_warning");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if let Some(notify) = optional_engine_proc!(NotifyLowMemoryWarning) {
+            unsafe { notify(self.engine_ptr()) };
+        }
+    }
+
+    /// Make the engine pick up the fonts installed on the system since it
+    /// started. The fonts provided by the app are loaded by Dart instead
+    /// (see the fonts plugin).
+    pub fn reload_system_fonts(&self) {
+        trace!("reload_system_fonts");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if let Some(reload) = optional_engine_proc!(ReloadSystemFonts) {
+            unsafe { reload(self.engine_ptr()) };
+        }
+    }
+
+    /// Notify Dart about the accessibility features requested by the
+    /// platform (e.g.: `MediaQuery.highContrast`).
+    pub fn update_accessibility_features(&self, features: FlutterAccessibilityFeatures) {
+        trace!("update_accessibility_features");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        *self.inner.accessibility_features.lock() = features;
+        if let Some(update) = optional_engine_proc!(UpdateAccessibilityFeatures) {
+            unsafe { update(self.engine_ptr(), features.into()) };
+        }
+    }
+
+    /// The accessibility features last sent to the engine.
+    pub fn accessibility_features(&self) -> FlutterAccessibilityFeatures {
+        *self.inner.accessibility_features.lock()
+    }
+
+    /// Send the metrics of |view_id| directly, independently of any window
+    /// (e.g.: for offscreen rendering at a fixed size, or tests).
+    pub fn send_window_metrics(
+        &self,
+        view_id: FlutterViewId,
+        width: u32,
+        height: u32,
+        pixel_ratio: f64,
+    ) -> Result<(), WindowMetricsError> {
+        // No display is associated with the view.
+        self.send_window_metrics_event(view_id, PhysicalSize::new(width, height), pixel_ratio, 0)
+    }
+
     pub fn send_window_metrics_event(
         &self,
         view_id: FlutterViewId,
-        width: usize,
-        height: usize,
+        size: PhysicalSize<u32>,
         pixel_ratio: f64,
         display_id: FlutterEngineDisplayId,
-    ) {
+    ) -> Result<(), WindowMetricsError> {
         trace!("send_window_metrics_event");
         if !self.is_platform_thread() {
             panic!("Not on platform thread");
         }
 
+        // Rejected by the engine, which also logs a "viewport metrics invalid"
+        // error.
+        if size.width == 0 || size.height == 0 || !pixel_ratio.is_normal() || pixel_ratio < 0.0 {
+            return Err(WindowMetricsError::InvalidMetrics);
+        }
+
         let event = flutter_engine_sys::FlutterWindowMetricsEvent {
             struct_size: std::mem::size_of::<flutter_engine_sys::FlutterWindowMetricsEvent>(),
-            width,
-            height,
+            width: size.width as usize,
+            height: size.height as usize,
             pixel_ratio,
             left: 0,
             top: 0,
@@ -488,29 +866,82 @@ impl FlutterEngine {
             #[cfg(all(target_arch = "arm", target_os = "android"))]
             __bindgen_padding_0: 0,
         };
-        unsafe {
-            flutter_engine_sys::FlutterEngineSendWindowMetricsEvent(self.engine_ptr(), &event);
+        let result = unsafe {
+            engine_proc!(SendWindowMetricsEvent)(self.engine_ptr(), &event)
+        };
+
+        match result {
+            FlutterEngineResult::kSuccess => Ok(()),
+            FlutterEngineResult::kInvalidArguments => Err(WindowMetricsError::InvalidMetrics),
+            _ => Err(WindowMetricsError::InternalInconsistency),
         }
     }
 
+    /// Send a pointer event to the engine. While the engine is stalled (i.e.:
+    /// a vsync baton was answered without any progress for longer than
+    /// [`PointerBackpressure::stall_timeout`]), motion events are throttled as
+    /// described in [`pointer_queue`].
     pub fn send_pointer_event(&self, event: FlutterPointerEvent) {
         if !self.is_platform_thread() {
             panic!("Not on platform thread");
         }
 
+        let now = Instant::now();
+        let events = {
+            let mut queue = self.inner.pointer_queue.lock();
+            let is_stalled = self.inner.is_stalled(now, queue.config().stall_timeout);
+            queue.push(event, is_stalled, now)
+        };
+
+        self.send_pointer_events(events);
+    }
+
+    fn resume_pointer_events(&self) {
+        let events = self.inner.pointer_queue.lock().resume();
+        self.send_pointer_events(events);
+    }
+
+    fn send_pointer_events(&self, events: Vec<FlutterPointerEvent>) {
+        if events.is_empty() {
+            return;
+        }
+
+        let events: Vec<flutter_engine_sys::FlutterPointerEvent> =
+            events.into_iter().map(Into::into).collect();
+
         unsafe {
-            flutter_engine_sys::FlutterEngineSendPointerEvent(self.engine_ptr(), &event.into(), 1);
+            engine_proc!(SendPointerEvent)(
+                self.engine_ptr(),
+                events.as_ptr(),
+                events.len(),
+            );
         }
     }
 
-    // TODO: Add support for key event callbacks
+    /// Configure the throttling of the pointer events sent while the engine
+    /// is stalled.
+    pub fn set_pointer_backpressure(&self, config: PointerBackpressure) {
+        self.inner.pointer_queue.lock().set_config(config);
+    }
+
+    /// What the thread policies (see `FlutterEngineBuilder::with_thread_config`)
+    /// changed on the engine threads started so far, and what they couldn't.
+    pub fn thread_policy_reports(&self) -> Vec<ThreadPolicyReport> {
+        threads::thread_policy_reports()
+    }
+
+    /// Counters of the pointer events sent so far.
+    pub fn pointer_stats(&self) -> PointerQueueStats {
+        self.inner.pointer_queue.lock().stats()
+    }
+
     pub fn send_key_event(&self, event: FlutterKeyEvent) {
         if !self.is_platform_thread() {
             panic!("Not on platform thread");
         }
 
         unsafe {
-            flutter_engine_sys::FlutterEngineSendKeyEvent(
+            engine_proc!(SendKeyEvent)(
                 self.engine_ptr(),
                 &event.as_ptr(),
                 None,
@@ -519,6 +950,37 @@ impl FlutterEngine {
         }
     }
 
+    /// Same as [`Self::send_key_event`], invoking |callback| on the platform
+    /// thread with whether the framework handled the event. Unhandled events
+    /// are expected to fall through to the embedder (e.g.: to the text input).
+    ///
+    /// |callback| is invoked with `false` if the event can't be sent.
+    pub fn send_key_event_with_callback<F>(&self, event: FlutterKeyEvent, callback: F)
+    where
+        F: FnOnce(bool) + Send + 'static,
+    {
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        let callback: flutter_callbacks::KeyEventCallback = Box::new(callback);
+        let user_data = Box::into_raw(Box::new(callback));
+
+        let result = unsafe {
+            engine_proc!(SendKeyEvent)(
+                self.engine_ptr(),
+                &event.as_ptr(),
+                Some(flutter_callbacks::key_event_callback),
+                user_data as *mut c_void,
+            )
+        };
+
+        if result != FlutterEngineResult::kSuccess {
+            let callback = unsafe { Box::from_raw(user_data) };
+            callback(false);
+        }
+    }
+
     pub fn notify_display_update(
         &self,
         update_type: FlutterEngineDisplaysUpdateType,
@@ -532,13 +994,15 @@ impl FlutterEngine {
         let displays: Vec<flutter_engine_sys::FlutterEngineDisplay> =
             displays.iter().map(|display| (*display).into()).collect();
 
-        unsafe {
-            flutter_engine_sys::FlutterEngineNotifyDisplayUpdate(
-                self.engine_ptr(),
-                update_type.into(),
-                displays.as_ptr(),
-                displays.len(),
-            );
+        if let Some(notify) = optional_engine_proc!(NotifyDisplayUpdate) {
+            unsafe {
+                notify(
+                    self.engine_ptr(),
+                    update_type.into(),
+                    displays.as_ptr(),
+                    displays.len(),
+                );
+            }
         }
     }
 
@@ -548,8 +1012,18 @@ impl FlutterEngine {
             panic!("Not on platform thread");
         }
 
+        let replaced = self.inner.intercept_message(Direction::Outbound, &message);
+        let message = match &replaced {
+            Some(bytes) => PlatformMessage { message: bytes, ..message },
+            None => message,
+        };
+
+        if self.inner.channel_tracer.is_enabled() {
+            self.inner.trace_message(Direction::Outbound, &message);
+        }
+
         unsafe {
-            flutter_engine_sys::FlutterEngineSendPlatformMessage(
+            engine_proc!(SendPlatformMessage)(
                 self.engine_ptr(),
                 &message.into(),
             );
@@ -566,8 +1040,14 @@ impl FlutterEngine {
             panic!("Not on platform thread");
         }
 
+        if self.inner.channel_tracer.is_enabled() {
+            self.inner
+                .channel_tracer
+                .record_response(response_handle.trace_key(), bytes);
+        }
+
         unsafe {
-            flutter_engine_sys::FlutterEngineSendPlatformMessageResponse(
+            engine_proc!(SendPlatformMessageResponse)(
                 self.engine_ptr(),
                 response_handle.into(),
                 bytes.as_ptr(),
@@ -576,6 +1056,57 @@ impl FlutterEngine {
         }
     }
 
+    /// Sends a JSON encoded message on the given channel, without waiting for
+    /// a reply. Can be called from any thread.
+    pub fn send_json_message<T>(&self, channel: &str, value: &T) -> Result<(), JsonMessageError>
+    where
+        T: Serialize,
+    {
+        let buf = encode_json_message(value)?;
+        let channel = channel.to_owned();
+
+        self.run_on_platform_thread(move |engine| {
+            engine.send_platform_message(PlatformMessage {
+                channel: channel.into(),
+                message: &buf,
+                response_handle: None,
+            });
+        });
+
+        Ok(())
+    }
+
+    /// Sends a JSON encoded message on the given channel, and returns its
+    /// decoded JSON reply, which can be awaited. Can be called from any
+    /// thread, but the reply must not be blocked on from the platform thread,
+    /// which is the one receiving it.
+    pub fn send_json_message_with_reply<T, V>(
+        &self,
+        channel: &str,
+        value: &T,
+    ) -> Result<JsonReply<V>, JsonMessageError>
+    where
+        T: Serialize,
+        V: DeserializeOwned + Send + 'static,
+    {
+        let buf = encode_json_message(value)?;
+        let channel = channel.to_owned();
+        let (reply, sender) = JsonReply::new();
+
+        self.run_on_platform_thread(move |engine| {
+            let handle =
+                PlatformMessageResponseHandle::new(engine.clone(), move |data| sender.send(data));
+
+            engine.send_platform_message(PlatformMessage {
+                channel: channel.into(),
+                message: &buf,
+                response_handle: Some(handle),
+            });
+        });
+
+        Ok(reply)
+    }
+
     pub fn shutdown(&self) {
         trace!("shutdown");
         if !self.is_platform_thread() {
@@ -583,16 +1114,51 @@ impl FlutterEngine {
         }
 
         unsafe {
-            flutter_engine_sys::FlutterEngineShutdown(self.engine_ptr());
+            engine_proc!(Shutdown)(self.engine_ptr());
         }
+
+        if let Some(callback) = self.inner.root_isolate_shutdown_callback.lock().take() {
+            let exit = match *self.inner.exit_code.lock() {
+                Some(exit_code) => IsolateExit::Requested { exit_code },
+                None => IsolateExit::Shutdown,
+            };
+            callback(exit);
+        }
+    }
+
+    /// Record that Dart requested the application to exit with |exit_code|.
+    /// The embedder is then expected to quit, the exit code being reported
+    /// once the engine is shut down.
+    pub fn set_exit_code(&self, exit_code: i32) {
+        *self.inner.exit_code.lock() = Some(exit_code);
+    }
+
+    /// The exit code requested by Dart, if any.
+    pub fn exit_code(&self) -> Option<i32> {
+        *self.inner.exit_code.lock()
     }
 
     pub fn execute_platform_tasks(&self) -> Option<Instant> {
+        self.execute_platform_tasks_limited(None)
+    }
+
+    /// Like `execute_platform_tasks`, but run at most |max_engine_tasks| of
+    /// the engine tasks which are due, so that the run loop can handle input
+    /// between batches during bursts of Dart-originated work. The returned
+    /// time is in the past when due tasks are left over.
+    pub fn execute_platform_tasks_limited(
+        &self,
+        max_engine_tasks: Option<NonZeroUsize>,
+    ) -> Option<Instant> {
         if !self.is_platform_thread() {
             panic!("Not on platform thread")
         }
 
-        let next_task = self.inner.platform_runner.execute_tasks();
+        let next_engine_task = self
+            .inner
+            .platform_runner
+            .execute_tasks_limited(max_engine_tasks);
+        let next_scheduled_task = self.run_scheduled_tasks();
 
         let mut render_thread_fns = Vec::new();
         let callbacks: Vec<MainThreadCallback> = self.inner.platform_receiver.try_iter().collect();
@@ -611,13 +1177,34 @@ impl FlutterEngine {
             });
         }
 
-        next_task
+        // The run loop only needs a timer for the earliest of both.
+        next_engine_task.into_iter().chain(next_scheduled_task).min()
+    }
+
+    /// Run the scheduled closures which are due, and return when the next one
+    /// is.
+    fn run_scheduled_tasks(&self) -> Option<Instant> {
+        let scheduled_tasks = &self.inner.scheduled_tasks;
+
+        // Don't keep the queue locked while running the closures, since they
+        // may schedule (or cancel) other ones.
+        let due_tasks = scheduled_tasks.lock().pop_due(Self::get_current_time_nanos());
+        for task in due_tasks {
+            if let Some(task) = task.run(self) {
+                scheduled_tasks
+                    .lock()
+                    .reschedule(task, Self::get_current_time_nanos());
+            }
+        }
+
+        let next_time_nanos = scheduled_tasks.lock().next_time_nanos()?;
+        Some(flutter_time_to_instant(next_time_nanos))
     }
 
     pub(crate) fn run_task(&self, task: &FlutterTask) {
         trace!("run_task");
         unsafe {
-            flutter_engine_sys::FlutterEngineRunTask(self.engine_ptr(), task as *const FlutterTask);
+            engine_proc!(RunTask)(self.engine_ptr(), task as *const FlutterTask);
         }
     }
 
@@ -630,7 +1217,7 @@ impl FlutterEngine {
             let cbk = CallbackBox { cbk: Box::new(f) };
             let b = Box::new(cbk);
             let ptr = Box::into_raw(b);
-            flutter_engine_sys::FlutterEnginePostRenderThreadTask(
+            engine_proc!(PostRenderThreadTask)(
                 self.engine_ptr(),
                 Some(render_thread_task),
                 ptr as *mut c_void,
@@ -651,6 +1238,12 @@ impl FlutterEngine {
     pub fn create_texture(&self) -> Texture {
         self.inner.texture_registry.create_texture(self.clone())
     }
+
+    /// The latest dmabuf frame posted to the |texture_id| texture (see
+    /// [`Texture::post_dmabuf_frame`]).
+    pub fn dmabuf_texture_frame(&self, texture_id: TextureId) -> Option<Arc<DmabufTextureFrame>> {
+        self.inner.texture_registry.get_dmabuf_frame(texture_id)
+    }
 }
 
 #[cfg(unix)]
@@ -668,15 +1261,32 @@ fn path_to_cstring(path: &Path) -> CString {
 pub enum CreateError {
     NoHandler,
     EnginePtrNull,
+    /// Neither an ICU data file nor the embedded ICU data is configured.
+    NoIcuData,
+    IcuDataNotFound(PathBuf),
+    /// The engine library doesn't provide these procs, e.g.: because it is
+    /// too old for this embedder (see the `proc-table` feature).
+    MissingEngineProcs(Vec<&'static str>),
 }
 
 impl core::fmt::Display for CreateError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        let msg = match self {
-            CreateError::NoHandler => "No handler set.",
-            CreateError::EnginePtrNull => "Engine ptr is null.",
-        };
-        writeln!(f, "{}", msg)
+        match self {
+            CreateError::NoHandler => writeln!(f, "No handler set."),
+            CreateError::EnginePtrNull => writeln!(f, "Engine ptr is null."),
+            CreateError::NoIcuData => writeln!(
+                f,
+                "No ICU data path set and the embedded ICU data is not enabled."
+            ),
+            CreateError::IcuDataNotFound(path) => {
+                writeln!(f, "ICU data file {} not found.", path.display())
+            }
+            CreateError::MissingEngineProcs(procs) => writeln!(
+                f,
+                "The Flutter engine library doesn't provide {}.",
+                procs.join(", ")
+            ),
+        }
     }
 }
 
@@ -693,3 +1303,15 @@ pub enum RunError {
     #[error("Internal inconsistency")]
     InternalInconsistency,
 }
+
+#[derive(Error, Debug)]
+pub enum WindowMetricsError {
+    /// The metrics are invalid (e.g.: empty size or invalid pixel ratio) or
+    /// were rejected by the engine. Sending valid metrics again is expected
+    /// to succeed.
+    #[error("Invalid window metrics")]
+    InvalidMetrics,
+
+    #[error("Internal inconsistency")]
+    InternalInconsistency,
+}
@@ -2,16 +2,33 @@
 
 use std::{
     any::Any,
-    collections::HashMap,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
     ops::{Deref, DerefMut},
+    rc::Rc,
     sync::{Arc, RwLock},
 };
 
-use crate::FlutterEngine;
+use tracing::debug;
+
+use crate::{runtime::AsyncRuntime, FlutterEngine};
+
+type PluginDetacher = fn(&mut dyn Any, &FlutterEngine);
+
+/// Handles of the windowing system, specific to the embedder (see
+/// [`PluginContext::platform_handles`]).
+pub type PlatformHandles = Arc<dyn Any + Send + Sync>;
 
 #[derive(Default)]
 pub struct PluginRegistrar {
     plugins: HashMap<String, Arc<RwLock<dyn Any>>>,
+    detachers: HashMap<String, PluginDetacher>,
+    names: PluginNames,
+    deferred_sends: DeferredSends,
+    configs: PluginConfigMap,
+    async_runtime: Option<AsyncRuntime>,
+    platform_handles: Option<PlatformHandles>,
 }
 
 impl PluginRegistrar {
@@ -19,19 +36,90 @@ impl PluginRegistrar {
         Default::default()
     }
 
+    /// Registrar delivering `configs` to the plugins when they are added.
+    pub fn with_configs(configs: PluginConfigMap) -> Self {
+        Self {
+            configs,
+            ..Default::default()
+        }
+    }
+
+    /// Hand the host runtime to the plugins when they are added (see
+    /// [`PluginContext::async_runtime`]).
+    pub fn with_async_runtime(mut self, async_runtime: Option<AsyncRuntime>) -> Self {
+        self.async_runtime = async_runtime;
+        self
+    }
+
+    /// Hand the handles of the windowing system to the plugins when they are
+    /// added (see [`PluginContext::platform_handles`]).
+    pub fn with_platform_handles(mut self, platform_handles: PlatformHandles) -> Self {
+        self.platform_handles = Some(platform_handles);
+        self
+    }
+
     pub fn add_plugin<P>(&mut self, engine: &FlutterEngine, plugin: P) -> &mut Self
     where
         P: Plugin + 'static,
     {
         let arc = Arc::new(RwLock::new(plugin));
         {
-            let mut plugin = arc.write().unwrap();
-            plugin.init(engine);
+            let context = PluginContext {
+                engine,
+                configs: &self.configs,
+                deferred_sends: &self.deferred_sends,
+                async_runtime: self.async_runtime.as_ref(),
+                platform_handles: self.platform_handles.as_ref(),
+            };
+            arc.write().unwrap().init(&context);
         }
         self.plugins.insert(P::plugin_name().to_owned(), arc);
+        self.detachers
+            .insert(P::plugin_name().to_owned(), detach_plugin::<P>);
+        self.names.insert(P::plugin_name());
         self
     }
 
+    /// Detach all the plugins (see [`Plugin::detach`]) and remove them from
+    /// the registrar. Returns the number of detached plugins.
+    pub fn detach_all(&mut self, engine: &FlutterEngine) -> usize {
+        let count = self.plugins.len();
+        for (name, arc) in self.plugins.drain() {
+            if let Some(detach) = self.detachers.remove(&name) {
+                detach(&mut *arc.write().unwrap(), engine);
+            }
+        }
+        self.names.clear();
+        count
+    }
+
+    /// Messages which can be coalesced by the plugins (see
+    /// [`DeferredSends`]).
+    pub fn deferred_sends(&self) -> &DeferredSends {
+        &self.deferred_sends
+    }
+
+    pub fn plugin_names(&self) -> impl Iterator<Item = &str> {
+        self.plugins.keys().map(String::as_str)
+    }
+
+    /// Names of the plugins, kept up to date as they are added and detached.
+    /// Unlike the registrar, they can be read from any thread (e.g.: by a
+    /// plugin handler reporting them).
+    pub fn shared_plugin_names(&self) -> PluginNames {
+        self.names.clone()
+    }
+
+    /// Log the configs of the plugins which haven't been added, e.g.: once
+    /// the built-in plugins are registered. Such configs are ignored.
+    pub fn log_unused_configs(&self) {
+        for plugin_name in self.configs.plugin_names() {
+            if !self.plugins.contains_key(plugin_name) {
+                debug!("Ignoring the config of the unregistered {plugin_name} plugin");
+            }
+        }
+    }
+
     pub fn with_plugin<F, P>(&self, f: F)
     where
         F: FnOnce(&P),
@@ -57,7 +145,377 @@ impl PluginRegistrar {
     }
 }
 
+/// Names of the plugins of a registrar, see
+/// [`PluginRegistrar::shared_plugin_names`].
+#[derive(Clone, Debug, Default)]
+pub struct PluginNames {
+    names: Arc<RwLock<BTreeSet<&'static str>>>,
+}
+
+impl PluginNames {
+    fn insert(&self, name: &'static str) {
+        self.names.write().unwrap().insert(name);
+    }
+
+    fn clear(&self) {
+        self.names.write().unwrap().clear();
+    }
+
+    /// The current names, sorted.
+    pub fn get(&self) -> Vec<&'static str> {
+        self.names.read().unwrap().iter().copied().collect()
+    }
+}
+
 pub trait Plugin {
     fn plugin_name() -> &'static str;
-    fn init(&mut self, engine: &FlutterEngine);
+    fn init(&mut self, context: &PluginContext);
+
+    /// Called when the application shuts down, before the engine. Plugins
+    /// must stop using the engine (e.g.: cancel their background tasks).
+    fn detach(&mut self, _engine: &FlutterEngine) {}
+}
+
+fn detach_plugin<P>(plugin: &mut dyn Any, engine: &FlutterEngine)
+where
+    P: Plugin + 'static,
+{
+    if let Some(plugin) = plugin.downcast_mut::<P>() {
+        plugin.detach(engine);
+    }
+}
+
+/// What plugins are given when they are added to a [`PluginRegistrar`].
+pub struct PluginContext<'a> {
+    engine: &'a FlutterEngine,
+    configs: &'a PluginConfigMap,
+    deferred_sends: &'a DeferredSends,
+    async_runtime: Option<&'a AsyncRuntime>,
+    platform_handles: Option<&'a PlatformHandles>,
+}
+
+impl<'a> PluginContext<'a> {
+    /// The engine the channels of the plugin are registered with, which is
+    /// also used for running tasks on the platform thread.
+    pub fn engine(&self) -> &'a FlutterEngine {
+        self.engine
+    }
+
+    /// Messages which can be coalesced by the plugin (see [`DeferredSends`]).
+    pub fn deferred_sends(&self) -> &'a DeferredSends {
+        self.deferred_sends
+    }
+
+    /// Runtime of the host application for the I/O of the plugin, if it
+    /// provided one. Plugins fall back to their own means otherwise.
+    pub fn async_runtime(&self) -> Option<&'a AsyncRuntime> {
+        self.async_runtime
+    }
+
+    /// Handles of the windowing system provided by the embedder, if they are
+    /// of type `T` (e.g.: `flutter_sctk::extension::WaylandHandles`). This is
+    /// an escape hatch for plugins integrating with a specific platform, see
+    /// the documentation of the handles for what they may be used for.
+    pub fn platform_handles<T: Any>(&self) -> Option<&'a T> {
+        self.platform_handles
+            .and_then(|platform_handles| platform_handles.downcast_ref())
+    }
+
+    pub fn config<C: PluginConfig>(&self) -> Option<&'a C> {
+        self.configs.get()
+    }
+
+    /// The configured `C`, or its default when the application didn't
+    /// configure the plugin.
+    pub fn config_or_default<C: PluginConfig + Default>(&self) -> C {
+        self.configs.get_or_default()
+    }
+}
+
+/// Configuration of a plugin, set by the application (e.g.: with
+/// `ApplicationBuilder::with_plugin_config`) and delivered to the plugin when
+/// it is added (see [`PluginContext::config`]).
+///
+/// Configs are shared by the engines of the application, so they must be
+/// cloneable and thread-safe.
+pub trait PluginConfig: Any + Clone + fmt::Debug + Send + Sync {
+    /// The configured plugin.
+    type Plugin: Plugin;
+}
+
+trait AnyPluginConfig: Any + fmt::Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<C: PluginConfig> AnyPluginConfig for C {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Plugin configs, keyed by plugin. There is at most one config per plugin.
+#[derive(Clone, Default)]
+pub struct PluginConfigMap {
+    configs: HashMap<&'static str, Arc<dyn AnyPluginConfig>>,
+}
+
+impl PluginConfigMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the config of `C::Plugin`, replacing the previous one.
+    pub fn insert<C: PluginConfig>(&mut self, config: C) {
+        self.configs.insert(C::Plugin::plugin_name(), Arc::new(config));
+    }
+
+    pub fn get<C: PluginConfig>(&self) -> Option<&C> {
+        self.configs
+            .get(C::Plugin::plugin_name())
+            .and_then(|config| config.as_any().downcast_ref())
+    }
+
+    pub fn get_or_default<C: PluginConfig + Default>(&self) -> C {
+        self.get().cloned().unwrap_or_default()
+    }
+
+    /// Names of the configured plugins.
+    pub fn plugin_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.configs.keys().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.configs.is_empty()
+    }
+}
+
+impl fmt::Debug for PluginConfigMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.configs.iter()).finish()
+    }
+}
+
+/// Kind of the messages which can be deferred, in the order they are sent
+/// when flushed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeferredMessage {
+    Locales,
+    Settings,
+    Displays,
+    Lifecycle,
+}
+
+type DeferredSend = Box<dyn FnOnce()>;
+
+#[derive(Default)]
+struct DeferredSendsState {
+    is_deferring: bool,
+    pending: BTreeMap<DeferredMessage, DeferredSend>,
+}
+
+/// Coalesces the messages sent to the engine while deferring (e.g.: during
+/// startup, where each message delays the first frame). Each message fully
+/// replaces the previous one of the same kind, so only the latest one is
+/// kept and the pending messages are sent in a deterministic order once
+/// flushed. Messages are sent immediately when not deferring.
+///
+/// Note: Like the channels, this must only be used on the *platform* thread.
+#[derive(Clone, Default)]
+pub struct DeferredSends {
+    state: Rc<RefCell<DeferredSendsState>>,
+}
+
+impl DeferredSends {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Start coalescing the messages until the next [`Self::flush`].
+    pub fn defer(&self) {
+        self.state.borrow_mut().is_deferring = true;
+    }
+
+    pub fn is_deferring(&self) -> bool {
+        self.state.borrow().is_deferring
+    }
+
+    /// Run `send` now, or once flushed when deferring (replacing any pending
+    /// message of the same `kind`).
+    pub fn send<F>(&self, kind: DeferredMessage, send: F)
+    where
+        F: FnOnce() + 'static,
+    {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.is_deferring {
+                state.pending.insert(kind, Box::new(send));
+                return;
+            }
+        }
+
+        send();
+    }
+
+    /// Stop deferring and send the pending messages. Returns the number of
+    /// messages sent.
+    pub fn flush(&self) -> usize {
+        let pending = {
+            let mut state = self.state.borrow_mut();
+            state.is_deferring = false;
+            std::mem::take(&mut state.pending)
+        };
+
+        let count = pending.len();
+        for send in pending.into_values() {
+            send();
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::BTreeMap, rc::Rc, thread};
+
+    use super::{
+        DeferredMessage, DeferredSends, Plugin, PluginConfig, PluginConfigMap, PluginContext,
+        PluginNames,
+    };
+
+    struct GreeterPlugin;
+
+    impl Plugin for GreeterPlugin {
+        fn plugin_name() -> &'static str {
+            "greeter"
+        }
+
+        fn init(&mut self, _context: &PluginContext) {}
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct GreeterConfig {
+        greeting: String,
+    }
+
+    impl Default for GreeterConfig {
+        fn default() -> Self {
+            Self {
+                greeting: String::from("hello"),
+            }
+        }
+    }
+
+    impl PluginConfig for GreeterConfig {
+        type Plugin = GreeterPlugin;
+    }
+
+    #[derive(Debug, Clone)]
+    struct OtherGreeterConfig;
+
+    impl PluginConfig for OtherGreeterConfig {
+        type Plugin = GreeterPlugin;
+    }
+
+    fn greeter_config(greeting: &str) -> GreeterConfig {
+        GreeterConfig {
+            greeting: greeting.into(),
+        }
+    }
+
+    #[test]
+    fn delivers_configs_by_plugin() {
+        let mut configs = PluginConfigMap::new();
+        assert_eq!(configs.get::<GreeterConfig>(), None);
+
+        configs.insert(greeter_config("hi"));
+        configs.insert(greeter_config("hey"));
+        assert_eq!(configs.get(), Some(&greeter_config("hey")));
+        assert_eq!(configs.plugin_names().collect::<Vec<_>>(), ["greeter"]);
+
+        // A plugin has a single config.
+        configs.insert(OtherGreeterConfig);
+        assert_eq!(configs.get::<GreeterConfig>(), None);
+        assert!(configs.get::<OtherGreeterConfig>().is_some());
+    }
+
+    #[test]
+    fn defaults_missing_configs() {
+        let mut configs = PluginConfigMap::new();
+        assert_eq!(configs.get_or_default::<GreeterConfig>(), GreeterConfig::default());
+
+        configs.insert(greeter_config("hi"));
+        assert_eq!(configs.get_or_default::<GreeterConfig>(), greeter_config("hi"));
+    }
+
+    #[test]
+    fn shares_configs_across_threads() {
+        let mut configs = PluginConfigMap::new();
+        configs.insert(greeter_config("hi"));
+
+        let cloned = configs.clone();
+        let config = thread::spawn(move || cloned.get::<GreeterConfig>().cloned())
+            .join()
+            .unwrap();
+        assert_eq!(config, Some(greeter_config("hi")));
+        assert_eq!(format!("{configs:?}"), r#"{"greeter": GreeterConfig { greeting: "hi" }}"#);
+    }
+
+    #[test]
+    fn shares_plugin_names_across_threads() {
+        let names = PluginNames::default();
+        let shared = names.clone();
+        names.insert("textinput");
+        names.insert("keyboard");
+        names.insert("textinput");
+
+        let read = thread::spawn(move || shared.get()).join().unwrap();
+        assert_eq!(read, ["keyboard", "textinput"]);
+
+        names.clear();
+        assert!(names.get().is_empty());
+    }
+
+    type Sent = Rc<RefCell<Vec<(DeferredMessage, &'static str)>>>;
+
+    fn send_startup_messages(deferred_sends: &DeferredSends, sent: &Sent) {
+        let messages = [
+            (DeferredMessage::Lifecycle, "inactive"),
+            (DeferredMessage::Displays, "one display"),
+            (DeferredMessage::Lifecycle, "resumed"),
+            (DeferredMessage::Displays, "two displays"),
+            (DeferredMessage::Settings, "dark"),
+        ];
+
+        for (kind, value) in messages {
+            let sent = sent.clone();
+            deferred_sends.send(kind, move || sent.borrow_mut().push((kind, value)));
+        }
+    }
+
+    #[test]
+    fn coalesces_deferred_messages() {
+        let immediate = Sent::default();
+        send_startup_messages(&DeferredSends::new(), &immediate);
+        assert_eq!(immediate.borrow().len(), 5);
+
+        let coalesced = Sent::default();
+        let deferred_sends = DeferredSends::new();
+        deferred_sends.defer();
+        send_startup_messages(&deferred_sends, &coalesced);
+        assert!(coalesced.borrow().is_empty());
+
+        assert_eq!(deferred_sends.flush(), 3);
+        assert!(!deferred_sends.is_deferring());
+
+        // The final values are the same as when sending every message.
+        let final_values: BTreeMap<_, _> = immediate.borrow().iter().copied().collect();
+        assert_eq!(
+            *coalesced.borrow(),
+            final_values.into_iter().collect::<Vec<_>>()
+        );
+
+        // Messages are sent immediately once flushed.
+        send_startup_messages(&deferred_sends, &coalesced);
+        assert_eq!(coalesced.borrow().len(), 8);
+    }
 }
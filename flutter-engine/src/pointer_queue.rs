@@ -0,0 +1,254 @@
+//! Back-pressure for pointer events sent while the engine is stalled (e.g.:
+//! heavy synchronous Dart work, or a paused debugger).
+//!
+//! Pointer events are forwarded as is while the engine keeps up. Once a stall
+//! is detected (see [`FlutterEngine::send_pointer_event`]), only the latest
+//! position of each device is forwarded at a low rate, while discrete events
+//! (e.g.: downs, ups, scroll ticks) are always forwarded. When the engine
+//! resumes, the latest position is sent right away instead of replaying the
+//! stale motion events.
+//!
+//! [`FlutterEngine::send_pointer_event`]: crate::FlutterEngine::send_pointer_event
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::ffi::FlutterPointerEvent;
+
+/// Thresholds of the pointer event back-pressure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerBackpressure {
+    /// Motion events of a single device queued while the engine is stalled.
+    /// The oldest ones are dropped first.
+    pub max_pending_motion_events: usize,
+    /// Delay after which an unanswered vsync baton means that the engine is
+    /// stalled.
+    pub stall_timeout: Duration,
+    /// Interval at which the latest position of each device is forwarded
+    /// while the engine is stalled.
+    pub stalled_motion_interval: Duration,
+}
+
+impl Default for PointerBackpressure {
+    fn default() -> Self {
+        Self {
+            max_pending_motion_events: 64,
+            stall_timeout: Duration::from_millis(250),
+            stalled_motion_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Counters of the pointer events sent to the engine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PointerQueueStats {
+    pub sent_events: u64,
+    /// Motion events superseded by a newer position while the engine was
+    /// stalled.
+    pub dropped_motion_events: u64,
+    /// Number of stalls detected so far.
+    pub stalls: u64,
+    pub is_stalled: bool,
+}
+
+#[derive(Default)]
+struct PendingMotion {
+    /// Oldest first.
+    events: VecDeque<FlutterPointerEvent>,
+    last_forwarded_at: Option<Instant>,
+}
+
+#[derive(Default)]
+pub(crate) struct PointerQueue {
+    config: PointerBackpressure,
+    pending: HashMap<i32, PendingMotion>,
+    stats: PointerQueueStats,
+}
+
+impl PointerQueue {
+    pub(crate) fn config(&self) -> PointerBackpressure {
+        self.config
+    }
+
+    pub(crate) fn set_config(&mut self, config: PointerBackpressure) {
+        self.config = config;
+    }
+
+    pub(crate) fn stats(&self) -> PointerQueueStats {
+        self.stats
+    }
+
+    pub(crate) fn is_stalled(&self) -> bool {
+        self.stats.is_stalled
+    }
+
+    /// Returns the events to forward to the engine, in order.
+    pub(crate) fn push(
+        &mut self,
+        event: FlutterPointerEvent,
+        is_stalled: bool,
+        now: Instant,
+    ) -> Vec<FlutterPointerEvent> {
+        if is_stalled && !self.stats.is_stalled {
+            self.stats.is_stalled = true;
+            self.stats.stalls += 1;
+        }
+
+        let mut events = Vec::new();
+        if !is_stalled {
+            events.extend(self.take_latest_positions());
+            events.push(event);
+        } else if event.is_motion() {
+            events.extend(self.push_motion(event, now));
+        } else {
+            // Discrete events carry their own position, so the pending motion
+            // events of the device are superseded.
+            if let Some(pending) = self.pending.get_mut(&event.device()) {
+                self.stats.dropped_motion_events += pending.events.len() as u64;
+                pending.events.clear();
+            }
+            events.push(event);
+        }
+
+        self.stats.sent_events += events.len() as u64;
+        events
+    }
+
+    /// Leave the stalled state, if needed. Returns the latest position of the
+    /// devices that moved during the stall.
+    pub(crate) fn resume(&mut self) -> Vec<FlutterPointerEvent> {
+        let events = self.take_latest_positions();
+        self.stats.sent_events += events.len() as u64;
+        events
+    }
+
+    fn take_latest_positions(&mut self) -> Vec<FlutterPointerEvent> {
+        if !self.stats.is_stalled {
+            return Vec::new();
+        }
+        self.stats.is_stalled = false;
+
+        let mut events = Vec::new();
+        for (_, mut pending) in self.pending.drain() {
+            if let Some(event) = pending.events.pop_back() {
+                self.stats.dropped_motion_events += pending.events.len() as u64;
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn push_motion(
+        &mut self,
+        event: FlutterPointerEvent,
+        now: Instant,
+    ) -> Option<FlutterPointerEvent> {
+        let pending = self.pending.entry(event.device()).or_default();
+
+        pending.events.push_back(event);
+        while pending.events.len() > self.config.max_pending_motion_events.max(1) {
+            pending.events.pop_front();
+            self.stats.dropped_motion_events += 1;
+        }
+
+        let is_due = pending.last_forwarded_at.is_none_or(|last_forwarded_at| {
+            now.duration_since(last_forwarded_at) >= self.config.stalled_motion_interval
+        });
+        if !is_due {
+            return None;
+        }
+
+        let latest = pending.events.pop_back();
+        self.stats.dropped_motion_events += pending.events.len() as u64;
+        pending.events.clear();
+        pending.last_forwarded_at = Some(now);
+        latest
+    }
+
+    #[cfg(test)]
+    fn pending_len(&self) -> usize {
+        self.pending.values().map(|pending| pending.events.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use dpi::PhysicalPosition;
+
+    use super::{PointerBackpressure, PointerQueue};
+    use crate::ffi::{
+        FlutterPointerDeviceKind, FlutterPointerEvent, FlutterPointerMouseButtons,
+        FlutterPointerPhase, FlutterPointerSignalKind, IMPLICIT_VIEW_ID,
+    };
+
+    fn event(phase: FlutterPointerPhase, x: f64) -> FlutterPointerEvent {
+        FlutterPointerEvent::new(
+            1,
+            phase,
+            PhysicalPosition::new(x, 0.0),
+            FlutterPointerSignalKind::None,
+            (0.0, 0.0),
+            FlutterPointerDeviceKind::Mouse,
+            FlutterPointerMouseButtons::None,
+            IMPLICIT_VIEW_ID,
+        )
+    }
+
+    fn position_x(event: &FlutterPointerEvent) -> f64 {
+        flutter_engine_sys::FlutterPointerEvent::from(*event).x
+    }
+
+    #[test]
+    fn stalled_motion_is_bounded_and_not_replayed() {
+        let mut queue = PointerQueue::default();
+        let config = PointerBackpressure::default();
+        let start = Instant::now();
+
+        // 1000Hz motion while the engine is stalled for 2 seconds.
+        let mut forwarded = 0;
+        for ms in 0..2000u64 {
+            let now = start + Duration::from_millis(ms);
+            forwarded += queue
+                .push(event(FlutterPointerPhase::Hover, ms as f64), true, now)
+                .len();
+            assert!(queue.pending_len() <= config.max_pending_motion_events);
+        }
+
+        // Only the latest position is forwarded at the stalled rate.
+        assert_eq!(forwarded, 20);
+        assert!(queue.stats().is_stalled);
+
+        let events = queue.resume();
+        assert_eq!(events.len(), 1);
+        assert_eq!(position_x(&events[0]), 1999.0);
+        assert_eq!(queue.pending_len(), 0);
+
+        let stats = queue.stats();
+        assert!(!stats.is_stalled);
+        assert_eq!(stats.stalls, 1);
+        assert_eq!(stats.sent_events, 21);
+        assert_eq!(stats.dropped_motion_events, 2000 - 21);
+    }
+
+    #[test]
+    fn discrete_events_are_always_forwarded() {
+        let mut queue = PointerQueue::default();
+        let now = Instant::now();
+
+        assert_eq!(queue.push(event(FlutterPointerPhase::Hover, 1.0), true, now).len(), 1);
+        assert!(queue.push(event(FlutterPointerPhase::Hover, 2.0), true, now).is_empty());
+
+        let events = queue.push(event(FlutterPointerPhase::Down, 3.0), true, now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(position_x(&events[0]), 3.0);
+
+        // Nothing is left to replay once the engine resumes.
+        let events = queue.push(event(FlutterPointerPhase::Up, 3.0), false, now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(queue.stats().dropped_motion_events, 1);
+    }
+}
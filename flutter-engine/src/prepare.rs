@@ -0,0 +1,237 @@
+//! Pre-loading of the engine artifacts (kernel blob, AOT library and ICU
+//! data) prior to running the engine.
+//!
+//! The artifacts are read sequentially on a background thread so that they
+//! are in the page cache by the time `FlutterEngineRun` loads them. Their
+//! headers are also validated so that invalid bundles are reported before
+//! any window is shown.
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::Receiver;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+const KERNEL_BLOB_FILE_NAME: &str = "kernel_blob.bin";
+const AOT_LIBRARY_ARG_PREFIX: &str = "--aot-shared-library-name=";
+
+const KERNEL_MAGIC: [u8; 4] = [0x90, 0xAB, 0xCD, 0xEF];
+const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+/// ICU data files start with the header size (2 bytes) followed by these
+/// magic bytes.
+const ICU_MAGIC: [u8; 2] = [0xDA, 0x27];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    KernelBlob,
+    AotLibrary,
+    IcuData,
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArtifactKind::KernelBlob => write!(f, "kernel blob"),
+            ArtifactKind::AotLibrary => write!(f, "AOT library"),
+            ArtifactKind::IcuData => write!(f, "ICU data"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtifactTiming {
+    pub kind: ArtifactKind,
+    pub path: PathBuf,
+    pub size: u64,
+    pub duration: Duration,
+}
+
+/// Timings of the artifacts read by [`FlutterEngineBuilder::prepare`].
+///
+/// [`FlutterEngineBuilder::prepare`]: crate::builder::FlutterEngineBuilder::prepare
+#[derive(Debug, Clone, Default)]
+pub struct PrepareReport {
+    pub artifacts: Vec<ArtifactTiming>,
+    /// Whether the timeout elapsed before all the artifacts were read. The
+    /// remaining artifacts are still read in the background.
+    pub timed_out: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum PrepareError {
+    #[error("Failed to read {kind} from {}: {source}", path.display())]
+    Read {
+        kind: ArtifactKind,
+        path: PathBuf,
+        source: io::Error,
+    },
+
+    #[error("Invalid {kind} file: {}", path.display())]
+    InvalidHeader { kind: ArtifactKind, path: PathBuf },
+}
+
+pub(crate) fn artifacts(
+    assets: &Path,
+    icu_data: Option<&Path>,
+    args: &[String],
+) -> Vec<(ArtifactKind, PathBuf)> {
+    let mut artifacts = Vec::new();
+
+    // The kernel blob is only bundled with JIT (debug) builds.
+    let kernel_blob = assets.join(KERNEL_BLOB_FILE_NAME);
+    if kernel_blob.is_file() {
+        artifacts.push((ArtifactKind::KernelBlob, kernel_blob));
+    }
+
+    artifacts.extend(args.iter().filter_map(|arg| {
+        let path = arg.strip_prefix(AOT_LIBRARY_ARG_PREFIX)?;
+        Some((ArtifactKind::AotLibrary, PathBuf::from(path)))
+    }));
+
+    if let Some(icu_data) = icu_data {
+        artifacts.push((ArtifactKind::IcuData, icu_data.to_path_buf()));
+    }
+    artifacts
+}
+
+/// Handle of the artifacts pre-loading started by
+/// [`FlutterEngineBuilder::prepare_in_background`].
+///
+/// [`FlutterEngineBuilder::prepare_in_background`]: crate::builder::FlutterEngineBuilder::prepare_in_background
+pub struct PrepareHandle {
+    receiver: Receiver<Result<ArtifactTiming, PrepareError>>,
+    count: usize,
+}
+
+impl PrepareHandle {
+    /// Wait for the artifacts to be read, for at most `timeout`. The
+    /// remaining artifacts keep being read in the background afterwards.
+    pub fn wait(self, timeout: Duration) -> Result<PrepareReport, PrepareError> {
+        let deadline = Instant::now() + timeout;
+
+        let mut report = PrepareReport::default();
+        for _ in 0..self.count {
+            let Ok(result) = self.receiver.recv_deadline(deadline) else {
+                warn!("Timed out while pre-loading engine artifacts, proceeding anyway");
+                report.timed_out = true;
+                break;
+            };
+
+            let timing = result?;
+            debug!(
+                "Pre-loaded {} ({} bytes) in {:?}: {}",
+                timing.kind,
+                timing.size,
+                timing.duration,
+                timing.path.display()
+            );
+            report.artifacts.push(timing);
+        }
+
+        Ok(report)
+    }
+}
+
+pub(crate) fn start(artifacts: Vec<(ArtifactKind, PathBuf)>) -> PrepareHandle {
+    let count = artifacts.len();
+    let (sender, receiver) = crossbeam_channel::unbounded();
+
+    let spawn_result = thread::Builder::new()
+        .name("flutter-prepare".into())
+        .spawn(move || {
+            for (kind, path) in artifacts {
+                // Keep reading even if the caller stopped waiting, so that the
+                // remaining artifacts still end up in the page cache.
+                let _ = sender.send(read_artifact(kind, path));
+            }
+        });
+
+    match spawn_result {
+        Ok(_) => PrepareHandle { receiver, count },
+        Err(err) => {
+            warn!("Skipping engine artifacts pre-loading: {}", err);
+            PrepareHandle { receiver, count: 0 }
+        }
+    }
+}
+
+fn read_artifact(kind: ArtifactKind, path: PathBuf) -> Result<ArtifactTiming, PrepareError> {
+    let start = Instant::now();
+
+    let read = || -> io::Result<Option<u64>> {
+        let mut file = File::open(&path)?;
+
+        let mut header = [0; 4];
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        if !is_valid_header(kind, &header) {
+            return Ok(None);
+        }
+
+        let size = io::copy(&mut file, &mut io::sink())?;
+        Ok(Some(header.len() as u64 + size))
+    };
+
+    match read() {
+        Ok(Some(size)) => Ok(ArtifactTiming {
+            kind,
+            path,
+            size,
+            duration: start.elapsed(),
+        }),
+        Ok(None) => Err(PrepareError::InvalidHeader { kind, path }),
+        Err(source) => Err(PrepareError::Read { kind, path, source }),
+    }
+}
+
+fn is_valid_header(kind: ArtifactKind, header: &[u8; 4]) -> bool {
+    match kind {
+        ArtifactKind::KernelBlob => *header == KERNEL_MAGIC,
+        ArtifactKind::AotLibrary => *header == ELF_MAGIC,
+        ArtifactKind::IcuData => header[2..] == ICU_MAGIC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{artifacts, is_valid_header, ArtifactKind};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn validates_headers() {
+        assert!(is_valid_header(ArtifactKind::KernelBlob, &[0x90, 0xAB, 0xCD, 0xEF]));
+        assert!(is_valid_header(ArtifactKind::AotLibrary, b"\x7FELF"));
+        assert!(is_valid_header(ArtifactKind::IcuData, &[0x20, 0x00, 0xDA, 0x27]));
+
+        assert!(!is_valid_header(ArtifactKind::KernelBlob, b"\x7FELF"));
+        assert!(!is_valid_header(ArtifactKind::IcuData, &[0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn collects_aot_library_from_args() {
+        let args = vec![
+            String::from("--verbose-logging"),
+            String::from("--aot-shared-library-name=/app/lib/libapp.so"),
+        ];
+        let icu_data = Some(Path::new("/app/icudtl.dat"));
+        let artifacts = artifacts(Path::new("/nonexistent"), icu_data, &args);
+
+        assert_eq!(
+            artifacts,
+            vec![
+                (ArtifactKind::AotLibrary, PathBuf::from("/app/lib/libapp.so")),
+                (ArtifactKind::IcuData, PathBuf::from("/app/icudtl.dat")),
+            ]
+        );
+    }
+}
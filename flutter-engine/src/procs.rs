@@ -0,0 +1,202 @@
+//! Entry points of the engine library.
+//!
+//! By default, they are the symbols linked directly. With the `proc-table`
+//! feature, they are resolved at runtime with `FlutterEngineGetProcAddresses`
+//! instead: the engine only fills the entries it knows of (as bounded by the
+//! `struct_size` of the table), so that an engine built from a slightly older
+//! or newer `embedder.h` still works. The linked symbols are used when the
+//! engine can't provide the table.
+//!
+//! The procs without which no app can run are checked when the engine is
+//! created (see [`CreateError::MissingEngineProcs`]). The calls of the other
+//! ones are skipped when the engine lacks them.
+//!
+//! [`CreateError::MissingEngineProcs`]: crate::CreateError::MissingEngineProcs
+use std::sync::OnceLock;
+
+use flutter_engine_sys::*;
+#[cfg(feature = "proc-table")]
+use tracing::warn;
+
+/// The |name| proc of the engine, which must be checked by
+/// [`missing_required_procs`].
+macro_rules! engine_proc {
+    ($name:ident) => {
+        $crate::procs::engine_procs()
+            .$name
+            .unwrap_or_else(|| $crate::procs::missing_proc(stringify!($name)))
+    };
+}
+
+/// The optional |name| proc of the engine, `None` when the engine lacks it.
+macro_rules! optional_engine_proc {
+    ($name:ident) => {
+        $crate::procs::engine_procs().$name
+    };
+}
+
+pub(crate) use {engine_proc, optional_engine_proc};
+
+/// Names of the entries of `FlutterEngineProcTable` which are missing.
+macro_rules! missing_procs {
+    ($table:expr, [$($name:ident),* $(,)?]) => {{
+        let mut missing = Vec::new();
+        $(
+            if $table.$name.is_none() {
+                missing.push(stringify!($name));
+            }
+        )*
+        missing
+    }};
+}
+
+/// The procs which `engine_proc!` may be used for, since the engine is not
+/// created without them.
+fn required_missing_procs(table: &FlutterEngineProcTable) -> Vec<&'static str> {
+    missing_procs!(
+        table,
+        [
+            Initialize,
+            RunInitialized,
+            Shutdown,
+            SendWindowMetricsEvent,
+            SendPointerEvent,
+            SendKeyEvent,
+            SendPlatformMessage,
+            PlatformMessageCreateResponseHandle,
+            SendPlatformMessageResponse,
+            OnVsync,
+            GetCurrentTime,
+            RunTask,
+            PostRenderThreadTask,
+        ]
+    )
+}
+
+#[cfg(feature = "proc-table")]
+fn optional_missing_procs(table: &FlutterEngineProcTable) -> Vec<&'static str> {
+    missing_procs!(
+        table,
+        [
+            RegisterExternalTexture,
+            UnregisterExternalTexture,
+            MarkExternalTextureFrameAvailable,
+            UpdateAccessibilityFeatures,
+            ReloadSystemFonts,
+            TraceEventDurationBegin,
+            TraceEventDurationEnd,
+            TraceEventInstant,
+            NotifyLowMemoryWarning,
+            NotifyDisplayUpdate,
+            ScheduleFrame,
+        ]
+    )
+}
+
+static ENGINE_PROCS: OnceLock<FlutterEngineProcTable> = OnceLock::new();
+
+pub(crate) fn engine_procs() -> &'static FlutterEngineProcTable {
+    ENGINE_PROCS.get_or_init(load_procs)
+}
+
+/// The required procs which the engine lacks.
+pub(crate) fn missing_required_procs() -> Vec<&'static str> {
+    required_missing_procs(engine_procs())
+}
+
+#[cold]
+pub(crate) fn missing_proc(name: &str) -> ! {
+    panic!("The Flutter engine library doesn't provide the {name} proc");
+}
+
+#[cfg(feature = "proc-table")]
+fn load_procs() -> FlutterEngineProcTable {
+    // SAFETY: the entries are nullable function pointers, and the engine
+    // expects a zeroed table besides its size.
+    let mut table: FlutterEngineProcTable = unsafe { std::mem::zeroed() };
+    table.struct_size = std::mem::size_of::<FlutterEngineProcTable>();
+
+    match unsafe { FlutterEngineGetProcAddresses(&mut table) } {
+        FlutterEngineResult::kSuccess => {
+            let missing = optional_missing_procs(&table);
+            if !missing.is_empty() {
+                warn!(
+                    "The Flutter engine library doesn't provide these procs, their \
+                     features are disabled: {}",
+                    missing.join(", ")
+                );
+            }
+            table
+        }
+        result => {
+            warn!(
+                "Failed to get the procs of the Flutter engine library ({:?}), using the \
+                 linked symbols",
+                result
+            );
+            linked_procs()
+        }
+    }
+}
+
+#[cfg(not(feature = "proc-table"))]
+fn load_procs() -> FlutterEngineProcTable {
+    linked_procs()
+}
+
+fn linked_procs() -> FlutterEngineProcTable {
+    FlutterEngineProcTable {
+        struct_size: std::mem::size_of::<FlutterEngineProcTable>(),
+        CreateAOTData: Some(FlutterEngineCreateAOTData),
+        CollectAOTData: Some(FlutterEngineCollectAOTData),
+        Run: Some(FlutterEngineRun),
+        Shutdown: Some(FlutterEngineShutdown),
+        Initialize: Some(FlutterEngineInitialize),
+        Deinitialize: Some(FlutterEngineDeinitialize),
+        RunInitialized: Some(FlutterEngineRunInitialized),
+        SendWindowMetricsEvent: Some(FlutterEngineSendWindowMetricsEvent),
+        SendPointerEvent: Some(FlutterEngineSendPointerEvent),
+        SendKeyEvent: Some(FlutterEngineSendKeyEvent),
+        SendPlatformMessage: Some(FlutterEngineSendPlatformMessage),
+        PlatformMessageCreateResponseHandle: Some(FlutterPlatformMessageCreateResponseHandle),
+        PlatformMessageReleaseResponseHandle: Some(FlutterPlatformMessageReleaseResponseHandle),
+        SendPlatformMessageResponse: Some(FlutterEngineSendPlatformMessageResponse),
+        RegisterExternalTexture: Some(FlutterEngineRegisterExternalTexture),
+        UnregisterExternalTexture: Some(FlutterEngineUnregisterExternalTexture),
+        MarkExternalTextureFrameAvailable: Some(FlutterEngineMarkExternalTextureFrameAvailable),
+        UpdateSemanticsEnabled: Some(FlutterEngineUpdateSemanticsEnabled),
+        UpdateAccessibilityFeatures: Some(FlutterEngineUpdateAccessibilityFeatures),
+        DispatchSemanticsAction: Some(FlutterEngineDispatchSemanticsAction),
+        OnVsync: Some(FlutterEngineOnVsync),
+        ReloadSystemFonts: Some(FlutterEngineReloadSystemFonts),
+        TraceEventDurationBegin: Some(FlutterEngineTraceEventDurationBegin),
+        TraceEventDurationEnd: Some(FlutterEngineTraceEventDurationEnd),
+        TraceEventInstant: Some(FlutterEngineTraceEventInstant),
+        PostRenderThreadTask: Some(FlutterEnginePostRenderThreadTask),
+        GetCurrentTime: Some(FlutterEngineGetCurrentTime),
+        RunTask: Some(FlutterEngineRunTask),
+        UpdateLocales: Some(FlutterEngineUpdateLocales),
+        RunsAOTCompiledDartCode: Some(FlutterEngineRunsAOTCompiledDartCode),
+        PostDartObject: Some(FlutterEnginePostDartObject),
+        NotifyLowMemoryWarning: Some(FlutterEngineNotifyLowMemoryWarning),
+        PostCallbackOnAllNativeThreads: Some(FlutterEnginePostCallbackOnAllNativeThreads),
+        NotifyDisplayUpdate: Some(FlutterEngineNotifyDisplayUpdate),
+        ScheduleFrame: Some(FlutterEngineScheduleFrame),
+        SetNextFrameCallback: Some(FlutterEngineSetNextFrameCallback),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{linked_procs, required_missing_procs};
+
+    #[test]
+    fn reports_the_missing_required_procs() {
+        let mut table = linked_procs();
+        assert!(required_missing_procs(&table).is_empty());
+
+        table.RunTask = None;
+        table.ScheduleFrame = None;
+        assert_eq!(required_missing_procs(&table), ["RunTask"]);
+    }
+}
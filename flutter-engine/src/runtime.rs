@@ -0,0 +1,78 @@
+//! Async runtime of the host application (e.g.: its Tokio runtime), which the
+//! plugins can use for their I/O (e.g.: network or D-Bus requests) rather
+//! than blocking the platform thread or spawning a runtime of their own.
+//!
+//! The futures run on the threads of the host runtime, so they can't touch
+//! the engine or the channels. Their output is handed back to the platform
+//! thread with [`FlutterEngine::run_on_platform_thread`], which queues a
+//! platform task and wakes the event loop of the backend up (through the
+//! `TaskRunnerHandler` of the platform task runner).
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+use tracing::debug;
+
+use crate::FlutterEngine;
+
+/// Future spawned on the host runtime.
+pub type IoFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Handle of the host runtime, set with `ApplicationBuilder::with_async_runtime`
+/// and given to the plugins through `PluginContext::async_runtime`.
+///
+/// ```ignore
+/// let handle = tokio_runtime.handle().clone();
+/// let runtime = AsyncRuntime::new(move |future| {
+///     handle.spawn(future);
+/// });
+/// ```
+#[derive(Clone)]
+pub struct AsyncRuntime {
+    spawn: Arc<dyn Fn(IoFuture) + Send + Sync>,
+}
+
+impl AsyncRuntime {
+    /// |spawn| must run the futures to completion, from any thread.
+    pub fn new<F>(spawn: F) -> Self
+    where
+        F: Fn(IoFuture) + Send + Sync + 'static,
+    {
+        Self {
+            spawn: Arc::new(spawn),
+        }
+    }
+
+    /// Run |future| on the host runtime, then |on_done| with its output on
+    /// the platform thread. |on_done| is dropped if the engine is gone by
+    /// then.
+    pub fn spawn<Fut, D>(&self, engine: &FlutterEngine, future: Fut, on_done: D)
+    where
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+        D: FnOnce(&FlutterEngine, Fut::Output) + Send + 'static,
+    {
+        let engine = engine.downgrade();
+        (self.spawn)(Box::pin(async move {
+            let output = future.await;
+            let Some(engine) = engine.upgrade() else {
+                debug!("Dropping the output of an I/O task: the engine is gone");
+                return;
+            };
+            engine.run_on_platform_thread(move |engine| on_done(engine, output));
+        }));
+    }
+
+    /// Run |future| on the host runtime, without reporting back to the
+    /// platform thread.
+    pub fn spawn_detached<Fut>(&self, future: Fut)
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        (self.spawn)(Box::pin(future));
+    }
+}
+
+impl fmt::Debug for AsyncRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncRuntime").finish_non_exhaustive()
+    }
+}
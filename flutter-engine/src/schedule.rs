@@ -0,0 +1,382 @@
+//! Closures scheduled to run on the platform thread at a given engine time
+//! (see [`FlutterEngine::schedule_at`]).
+//!
+//! All the scheduled closures share a single queue, whose earliest deadline
+//! is merged with the one of the engine tasks (see
+//! [`FlutterEngine::execute_platform_tasks`]), so that the run loop only arms
+//! one timer whatever the number of scheduled closures.
+//!
+//! [`FlutterEngine::schedule_at`]: crate::FlutterEngine::schedule_at
+//! [`FlutterEngine::execute_platform_tasks`]: crate::FlutterEngine::execute_platform_tasks
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::Weak,
+};
+
+use parking_lot::Mutex;
+
+type OnceCallback<T> = Box<dyn FnOnce(&T) + Send>;
+type RepeatingCallback<T> = Box<dyn FnMut(&T) + Send>;
+
+enum ScheduledTask<T> {
+    Once(OnceCallback<T>),
+    /// The callback is taken out of the queue while it runs.
+    Repeating {
+        interval_nanos: u64,
+        callback: Option<RepeatingCallback<T>>,
+    },
+}
+
+/// Queue of the scheduled closures, ordered by their target time (in
+/// nanoseconds, using the engine clock).
+pub(crate) struct ScheduledTasks<T> {
+    /// Target times and ids, earliest first. Entries of cancelled tasks are
+    /// only dropped once they reach the top.
+    queue: BinaryHeap<Reverse<(u64, u64)>>,
+    tasks: HashMap<u64, ScheduledTask<T>>,
+    next_id: u64,
+}
+
+impl<T> Default for ScheduledTasks<T> {
+    fn default() -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+            tasks: HashMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+/// A scheduled closure whose target time has been reached.
+pub(crate) enum DueTask<T> {
+    Once(OnceCallback<T>),
+    Repeating(RepeatingTask<T>),
+}
+
+pub(crate) struct RepeatingTask<T> {
+    id: u64,
+    target_time_nanos: u64,
+    interval_nanos: u64,
+    callback: RepeatingCallback<T>,
+}
+
+impl<T> DueTask<T> {
+    /// Run the closure. Repeating tasks are returned so that they can be
+    /// rescheduled (see [`ScheduledTasks::reschedule`]).
+    pub(crate) fn run(self, target: &T) -> Option<RepeatingTask<T>> {
+        match self {
+            DueTask::Once(callback) => {
+                callback(target);
+                None
+            }
+            DueTask::Repeating(mut task) => {
+                (task.callback)(target);
+                Some(task)
+            }
+        }
+    }
+}
+
+impl<T> ScheduledTasks<T> {
+    pub(crate) fn schedule_at(&mut self, target_time_nanos: u64, callback: OnceCallback<T>) -> u64 {
+        self.insert(target_time_nanos, ScheduledTask::Once(callback))
+    }
+
+    /// Run |callback| every |interval_nanos|, starting at |first_time_nanos|.
+    pub(crate) fn schedule_repeating(
+        &mut self,
+        first_time_nanos: u64,
+        interval_nanos: u64,
+        callback: RepeatingCallback<T>,
+    ) -> u64 {
+        let task = ScheduledTask::Repeating {
+            interval_nanos: interval_nanos.max(1),
+            callback: Some(callback),
+        };
+        self.insert(first_time_nanos, task)
+    }
+
+    fn insert(&mut self, target_time_nanos: u64, task: ScheduledTask<T>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tasks.insert(id, task);
+        self.queue.push(Reverse((target_time_nanos, id)));
+        id
+    }
+
+    /// Returns whether the task was still scheduled. A repeating task which
+    /// is running is not rescheduled.
+    pub(crate) fn cancel(&mut self, id: u64) -> bool {
+        self.tasks.remove(&id).is_some()
+    }
+
+    pub(crate) fn is_scheduled(&self, id: u64) -> bool {
+        self.tasks.contains_key(&id)
+    }
+
+    /// Remove the tasks whose target time is at most |now_nanos|, earliest
+    /// first.
+    pub(crate) fn pop_due(&mut self, now_nanos: u64) -> Vec<DueTask<T>> {
+        let mut due_tasks = Vec::new();
+        while let Some(&Reverse((target_time_nanos, id))) = self.queue.peek() {
+            if target_time_nanos > now_nanos {
+                break;
+            }
+            self.queue.pop();
+
+            let due_task = match self.tasks.remove(&id) {
+                None => continue,
+                Some(ScheduledTask::Once(callback)) => DueTask::Once(callback),
+                Some(ScheduledTask::Repeating {
+                    interval_nanos,
+                    callback,
+                }) => {
+                    // Keep the task while it runs, so that it can be cancelled.
+                    let task = ScheduledTask::Repeating {
+                        interval_nanos,
+                        callback: None,
+                    };
+                    self.tasks.insert(id, task);
+
+                    let Some(callback) = callback else {
+                        continue;
+                    };
+                    DueTask::Repeating(RepeatingTask {
+                        id,
+                        target_time_nanos,
+                        interval_nanos,
+                        callback,
+                    })
+                }
+            };
+            due_tasks.push(due_task);
+        }
+        due_tasks
+    }
+
+    /// Schedule the next run of |task|, unless it was cancelled while it was
+    /// running.
+    ///
+    /// The next target time follows the previous one rather than |now_nanos|,
+    /// so that late runs don't accumulate drift. Runs which were missed
+    /// entirely (e.g.: while the platform thread was busy) are skipped.
+    pub(crate) fn reschedule(&mut self, task: RepeatingTask<T>, now_nanos: u64) {
+        let Some(ScheduledTask::Repeating { callback, .. }) = self.tasks.get_mut(&task.id) else {
+            return;
+        };
+        *callback = Some(task.callback);
+
+        let mut next_time_nanos = task.target_time_nanos.saturating_add(task.interval_nanos);
+        if next_time_nanos <= now_nanos {
+            let missed_intervals = (now_nanos - next_time_nanos) / task.interval_nanos + 1;
+            next_time_nanos = next_time_nanos
+                .saturating_add(missed_intervals.saturating_mul(task.interval_nanos));
+        }
+        self.queue.push(Reverse((next_time_nanos, task.id)));
+    }
+
+    /// Target time of the earliest scheduled task.
+    pub(crate) fn next_time_nanos(&mut self) -> Option<u64> {
+        while let Some(&Reverse((target_time_nanos, id))) = self.queue.peek() {
+            if self.tasks.contains_key(&id) {
+                return Some(target_time_nanos);
+            }
+            self.queue.pop();
+        }
+        None
+    }
+}
+
+/// Handle of a closure scheduled with [`FlutterEngine::schedule_at`] or
+/// [`FlutterEngine::schedule_repeating`]. Dropping the handle doesn't cancel
+/// the closure.
+///
+/// [`FlutterEngine::schedule_at`]: crate::FlutterEngine::schedule_at
+/// [`FlutterEngine::schedule_repeating`]: crate::FlutterEngine::schedule_repeating
+#[derive(Clone)]
+pub struct ScheduleHandle {
+    id: u64,
+    tasks: Weak<Mutex<ScheduledTasks<crate::FlutterEngine>>>,
+}
+
+impl ScheduleHandle {
+    pub(crate) fn new(id: u64, tasks: Weak<Mutex<ScheduledTasks<crate::FlutterEngine>>>) -> Self {
+        Self { id, tasks }
+    }
+
+    /// Cancel the closure. Returns `false` if it already ran (or was already
+    /// cancelled). A repeating closure which is running completes, but isn't
+    /// run again.
+    pub fn cancel(&self) -> bool {
+        self.tasks
+            .upgrade()
+            .is_some_and(|tasks| tasks.lock().cancel(self.id))
+    }
+
+    /// Whether the closure is still going to run.
+    pub fn is_scheduled(&self) -> bool {
+        self.tasks
+            .upgrade()
+            .is_some_and(|tasks| tasks.lock().is_scheduled(self.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::ScheduledTasks;
+
+    const MILLIS: u64 = 1_000_000;
+
+    /// Records the time at which the tasks run.
+    #[derive(Default)]
+    struct Recorder {
+        now_nanos: u64,
+        runs: Mutex<Vec<(&'static str, u64)>>,
+    }
+
+    impl Recorder {
+        fn record(&self, name: &'static str) {
+            self.runs.lock().unwrap().push((name, self.now_nanos));
+        }
+    }
+
+    fn run_due(tasks: &mut ScheduledTasks<Recorder>, recorder: &Recorder) {
+        for task in tasks.pop_due(recorder.now_nanos) {
+            if let Some(task) = task.run(recorder) {
+                tasks.reschedule(task, recorder.now_nanos);
+            }
+        }
+    }
+
+    #[test]
+    fn tasks_run_in_order_once_due() {
+        let mut tasks = ScheduledTasks::<Recorder>::default();
+        tasks.schedule_at(20 * MILLIS, Box::new(|r: &Recorder| r.record("b")));
+        tasks.schedule_at(10 * MILLIS, Box::new(|r: &Recorder| r.record("a")));
+        let cancelled = tasks.schedule_at(5 * MILLIS, Box::new(|_: &Recorder| unreachable!()));
+        tasks.schedule_at(20 * MILLIS, Box::new(|r: &Recorder| r.record("c")));
+
+        assert!(tasks.cancel(cancelled));
+        assert!(!tasks.cancel(cancelled));
+        assert_eq!(tasks.next_time_nanos(), Some(10 * MILLIS));
+
+        let mut recorder = Recorder {
+            now_nanos: 9 * MILLIS,
+            ..Default::default()
+        };
+        run_due(&mut tasks, &recorder);
+        assert!(recorder.runs.lock().unwrap().is_empty());
+
+        recorder.now_nanos = 25 * MILLIS;
+        run_due(&mut tasks, &recorder);
+        let names: Vec<_> = recorder.runs.lock().unwrap().iter().map(|run| run.0).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(tasks.next_time_nanos(), None);
+    }
+
+    #[test]
+    fn repeating_tasks_dont_drift() {
+        let mut tasks = ScheduledTasks::<Recorder>::default();
+        let id = tasks.schedule_repeating(
+            10 * MILLIS,
+            10 * MILLIS,
+            Box::new(|r: &Recorder| r.record("tick")),
+        );
+
+        let mut recorder = Recorder::default();
+        // Every run is 3ms late, which must not delay the next ones.
+        for now in [13, 23, 33] {
+            recorder.now_nanos = now * MILLIS;
+            run_due(&mut tasks, &recorder);
+        }
+        assert_eq!(tasks.next_time_nanos(), Some(40 * MILLIS));
+
+        // The platform thread was busy for a while: the missed runs are
+        // skipped rather than run in a burst.
+        recorder.now_nanos = 75 * MILLIS;
+        run_due(&mut tasks, &recorder);
+        assert_eq!(tasks.next_time_nanos(), Some(80 * MILLIS));
+        assert_eq!(recorder.runs.lock().unwrap().len(), 4);
+
+        assert!(tasks.cancel(id));
+        assert_eq!(tasks.next_time_nanos(), None);
+    }
+
+    #[test]
+    fn repeating_task_cancelled_while_running_stops() {
+        let tasks = Arc::new(Mutex::new(ScheduledTasks::<Recorder>::default()));
+        let id = tasks.lock().unwrap().schedule_repeating(
+            0,
+            MILLIS,
+            Box::new(|r: &Recorder| r.record("tick")),
+        );
+
+        let recorder = Recorder::default();
+        let due_tasks = tasks.lock().unwrap().pop_due(0);
+        assert_eq!(due_tasks.len(), 1);
+        assert!(tasks.lock().unwrap().is_scheduled(id));
+
+        for task in due_tasks {
+            // Cancelled from within the task, while the queue is unlocked.
+            assert!(tasks.lock().unwrap().cancel(id));
+            if let Some(task) = task.run(&recorder) {
+                tasks.lock().unwrap().reschedule(task, 0);
+            }
+        }
+
+        assert_eq!(tasks.lock().unwrap().next_time_nanos(), None);
+        assert_eq!(recorder.runs.lock().unwrap().len(), 1);
+    }
+
+    /// Scheduled and actual execution times of many short tasks, while the
+    /// "platform thread" is kept busy between the polls.
+    #[test]
+    fn precision_under_load() {
+        const TASKS: u64 = 200;
+        const POLL_INTERVAL: Duration = Duration::from_micros(200);
+
+        let start = Instant::now();
+        let now_nanos = || start.elapsed().as_nanos() as u64;
+
+        let mut tasks = ScheduledTasks::<Mutex<Vec<u64>>>::default();
+        for index in 0..TASKS {
+            let target_time_nanos = (index % 20 + 1) * MILLIS;
+            tasks.schedule_at(
+                target_time_nanos,
+                Box::new(move |lateness: &Mutex<Vec<u64>>| {
+                    let now = start.elapsed().as_nanos() as u64;
+                    lateness.lock().unwrap().push(now - target_time_nanos);
+                }),
+            );
+        }
+
+        let lateness = Mutex::new(Vec::new());
+        while let Some(next_time_nanos) = tasks.next_time_nanos() {
+            // Simulated load: some work, then sleeping until the next poll
+            // or the next deadline, like the run loop does.
+            let busy_until = Instant::now() + POLL_INTERVAL / 2;
+            while Instant::now() < busy_until {
+                std::hint::spin_loop();
+            }
+            let wait = next_time_nanos.saturating_sub(now_nanos());
+            thread::sleep(Duration::from_nanos(wait).min(POLL_INTERVAL));
+
+            for task in tasks.pop_due(now_nanos()) {
+                task.run(&lateness);
+            }
+        }
+
+        let lateness = lateness.into_inner().unwrap();
+        assert_eq!(lateness.len() as u64, TASKS);
+        // Generous bound, since the test may run on a loaded machine.
+        let max_lateness = Duration::from_nanos(*lateness.iter().max().unwrap());
+        assert!(max_lateness < Duration::from_millis(50), "{max_lateness:?}");
+    }
+}
@@ -1,10 +1,11 @@
-use crate::FlutterEngineWeakRef;
-use flutter_engine_sys::{FlutterEngineGetCurrentTime, FlutterTask};
-use tracing::debug;
+use crate::{FlutterEngine, FlutterEngineWeakRef};
+use flutter_engine_sys::FlutterTask;
+use tracing::{debug, trace};
 use parking_lot::{Mutex, MutexGuard};
 use priority_queue::PriorityQueue;
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
 use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::thread;
@@ -60,21 +61,21 @@ impl TaskRunner {
     }
 
     pub fn execute_tasks(&self) -> Option<Instant> {
+        self.execute_tasks_limited(None)
+    }
+
+    /// Run at most |max_tasks| of the expired tasks (all of them if `None`).
+    /// The tasks left over are still expired, so the returned time is in the
+    /// past, and the caller is expected to handle its other events before
+    /// running the next batch.
+    pub fn execute_tasks_limited(&self, max_tasks: Option<NonZeroUsize>) -> Option<Instant> {
         let now = Instant::now();
-        let mut expired_tasks = Vec::new();
 
-        let engine = {
+        let (expired_tasks, engine) = {
             let mut inner = self.inner.lock();
-            let tasks = &mut inner.tasks;
-            while let Some((_, priority)) = tasks.peek() {
-                if priority.time > now {
-                    break;
-                }
-                let (task, _) = tasks.pop().unwrap();
-                expired_tasks.push(task);
-            }
+            let expired_tasks = pop_expired_tasks(&mut inner.tasks, now, max_tasks);
             // make sure to unlock mutex before actually running the tasks as they may post another task
-            inner.engine.upgrade().unwrap()
+            (expired_tasks, inner.engine.upgrade().unwrap())
         };
 
         // run tasks
@@ -92,17 +93,6 @@ impl TaskRunner {
         }
     }
 
-    fn flutter_time_to_instant(target_time_nanos: u64) -> Instant {
-        let current_time = unsafe { FlutterEngineGetCurrentTime() };
-        let now = Instant::now();
-        if current_time >= target_time_nanos {
-            return now;
-        }
-        let nanos_timeout = target_time_nanos - current_time;
-        now.checked_add(Duration::from_nanos(nanos_timeout))
-            .unwrap()
-    }
-
     pub(crate) fn post_task(
         guard: &mut MutexGuard<TaskRunnerInner>,
         task: FlutterTask,
@@ -110,7 +100,7 @@ impl TaskRunner {
     ) {
         static GLOBAL_ORDER: AtomicU64 = AtomicU64::new(0);
         let task_priority = TaskPriority {
-            time: Self::flutter_time_to_instant(target_time_nanos),
+            time: flutter_time_to_instant(target_time_nanos),
             order: GLOBAL_ORDER.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
         };
         let task = Task { task };
@@ -135,6 +125,41 @@ impl TaskRunner {
     }
 }
 
+/// Pop the tasks whose time is not after |now|, in order, but at most
+/// |max_tasks| of them.
+fn pop_expired_tasks(
+    tasks: &mut PriorityQueue<Task, TaskPriority>,
+    now: Instant,
+    max_tasks: Option<NonZeroUsize>,
+) -> Vec<Task> {
+    let max_tasks = max_tasks.map_or(usize::MAX, NonZeroUsize::get);
+    let mut expired_tasks = Vec::new();
+    while let Some((_, priority)) = tasks.peek() {
+        if priority.time > now {
+            break;
+        }
+        if expired_tasks.len() == max_tasks {
+            trace!("Deferring the remaining expired platform tasks to the next batch");
+            break;
+        }
+        let (task, _) = tasks.pop().unwrap();
+        expired_tasks.push(task);
+    }
+    expired_tasks
+}
+
+/// Convert an engine time (see `FlutterEngineGetCurrentTime`) to an instant.
+pub(crate) fn flutter_time_to_instant(target_time_nanos: u64) -> Instant {
+    let current_time = FlutterEngine::get_current_time_nanos();
+    let now = Instant::now();
+    if current_time >= target_time_nanos {
+        return now;
+    }
+    let nanos_timeout = target_time_nanos - current_time;
+    now.checked_add(Duration::from_nanos(nanos_timeout))
+        .unwrap()
+}
+
 #[derive(Eq, PartialEq)]
 struct TaskPriority {
     order: u64,
@@ -147,10 +172,12 @@ struct Task {
 
 unsafe impl Send for Task {}
 
+/// The queue pops the greatest priority first, i.e. the earliest task (and
+/// the first posted one among tasks of the same time).
 impl Ord for TaskPriority {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.time.cmp(&other.time) {
-            Ordering::Equal => self.order.cmp(&other.order),
+        match other.time.cmp(&self.time) {
+            Ordering::Equal => other.order.cmp(&self.order),
             ord => ord,
         }
     }
@@ -176,3 +203,46 @@ impl Hash for Task {
         std::ptr::hash(self.task.runner, state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::time::{Duration, Instant};
+
+    use flutter_engine_sys::FlutterTask;
+    use priority_queue::PriorityQueue;
+
+    use super::{pop_expired_tasks, Task, TaskPriority};
+
+    fn push(queue: &mut PriorityQueue<Task, TaskPriority>, id: u64, time: Instant) {
+        let task = Task {
+            task: FlutterTask {
+                runner: std::ptr::null_mut(),
+                task: id,
+            },
+        };
+        queue.push(task, TaskPriority { order: id, time });
+    }
+
+    fn ids(tasks: Vec<Task>) -> Vec<u64> {
+        tasks.into_iter().map(|task| task.task.task).collect()
+    }
+
+    #[test]
+    fn expired_tasks_are_popped_in_order_and_in_batches() {
+        let now = Instant::now();
+        let mut queue = PriorityQueue::new();
+        push(&mut queue, 1, now + Duration::from_secs(1));
+        push(&mut queue, 2, now);
+        push(&mut queue, 3, now - Duration::from_millis(1));
+        push(&mut queue, 4, now);
+
+        let batch = NonZeroUsize::new(2);
+        assert_eq!(ids(pop_expired_tasks(&mut queue, now, batch)), [3, 2]);
+        assert_eq!(ids(pop_expired_tasks(&mut queue, now, batch)), [4]);
+        assert!(pop_expired_tasks(&mut queue, now, batch).is_empty());
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(ids(pop_expired_tasks(&mut queue, later, None)), [1]);
+    }
+}
@@ -1,15 +1,20 @@
+use crate::procs::optional_engine_proc;
 use crate::FlutterEngine;
 use flutter_engine_sys::FlutterOpenGLTexture;
 use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::ffi::c_void;
+use std::os::fd::OwnedFd;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
 use tracing::trace;
 
+type DmabufFrames = Arc<Mutex<HashMap<TextureId, Arc<DmabufTextureFrame>>>>;
+
 pub(crate) struct TextureRegistry {
     last_id: AtomicI64,
     frames: Arc<Mutex<HashMap<TextureId, TextureFrame>>>,
+    dmabuf_frames: DmabufFrames,
 }
 
 impl TextureRegistry {
@@ -17,6 +22,7 @@ impl TextureRegistry {
         Self {
             last_id: AtomicI64::new(1),
             frames: Arc::new(Default::default()),
+            dmabuf_frames: Arc::new(Default::default()),
         }
     }
 
@@ -25,11 +31,8 @@ impl TextureRegistry {
 
         engine.run_on_platform_thread(move |engine| {
             trace!("texture {}: register", texture_id);
-            unsafe {
-                flutter_engine_sys::FlutterEngineRegisterExternalTexture(
-                    engine.engine_ptr(),
-                    texture_id,
-                );
+            if let Some(register) = optional_engine_proc!(RegisterExternalTexture) {
+                unsafe { register(engine.engine_ptr(), texture_id) };
             }
         });
 
@@ -37,6 +40,7 @@ impl TextureRegistry {
             engine,
             texture_id,
             frames: self.frames.clone(),
+            dmabuf_frames: self.dmabuf_frames.clone(),
         }
     }
 
@@ -47,6 +51,10 @@ impl TextureRegistry {
     ) -> Option<TextureFrame> {
         self.frames.lock().remove(&texture_id)
     }
+
+    pub fn get_dmabuf_frame(&self, texture_id: TextureId) -> Option<Arc<DmabufTextureFrame>> {
+        self.dmabuf_frames.lock().get(&texture_id).cloned()
+    }
 }
 
 pub type TextureId = i64;
@@ -55,6 +63,7 @@ pub struct Texture {
     engine: FlutterEngine,
     texture_id: TextureId,
     frames: Arc<Mutex<HashMap<TextureId, TextureFrame>>>,
+    dmabuf_frames: DmabufFrames,
 }
 
 impl Texture {
@@ -65,6 +74,25 @@ impl Texture {
     pub fn post_frame(&self, frame: TextureFrame) {
         post_frame_internal(&self.engine, self.texture_id, &self.frames, frame);
     }
+
+    /// Post a frame backed by a dmabuf. Unlike [`Texture::post_frame`], the
+    /// frame is not sampled by the engine: it is composited by the embedder
+    /// for the platform view whose identifier is the id of this texture (see
+    /// [`FlutterEngine::dmabuf_texture_frame`]).
+    ///
+    /// The previous frame is released once the embedder doesn't use it
+    /// anymore.
+    pub fn post_dmabuf_frame(&self, frame: DmabufTextureFrame) {
+        // The previous frame may still be in use by the embedder, which holds
+        // its own reference until then.
+        self.dmabuf_frames
+            .lock()
+            .insert(self.texture_id, Arc::new(frame));
+
+        // Marking the frame available makes the engine present the last layer
+        // tree again, which gives the embedder a chance to show the new frame.
+        mark_frame_available(&self.engine, self.texture_id);
+    }
 }
 
 fn post_frame_internal(
@@ -79,13 +107,14 @@ fn post_frame_internal(
         });
     }
 
+    mark_frame_available(engine, texture_id);
+}
+
+fn mark_frame_available(engine: &FlutterEngine, texture_id: TextureId) {
     engine.run_on_platform_thread(move |engine| {
         trace!("texture {}: marking frame available", texture_id);
-        unsafe {
-            flutter_engine_sys::FlutterEngineMarkExternalTextureFrameAvailable(
-                engine.engine_ptr(),
-                texture_id,
-            );
+        if let Some(mark) = optional_engine_proc!(MarkExternalTextureFrameAvailable) {
+            unsafe { mark(engine.engine_ptr(), texture_id) };
         }
     });
 }
@@ -93,13 +122,11 @@ fn post_frame_internal(
 impl Drop for Texture {
     fn drop(&mut self) {
         let texture_id = self.texture_id;
+        self.dmabuf_frames.lock().remove(&texture_id);
         self.engine.run_on_platform_thread(move |engine| {
             trace!("texture {}: unregister", texture_id);
-            unsafe {
-                flutter_engine_sys::FlutterEngineUnregisterExternalTexture(
-                    engine.engine_ptr(),
-                    texture_id,
-                );
+            if let Some(unregister) = optional_engine_proc!(UnregisterExternalTexture) {
+                unsafe { unregister(engine.engine_ptr(), texture_id) };
             }
         });
     }
@@ -136,6 +163,60 @@ impl TextureFrame {
     }
 }
 
+/// A plane of a [`DmabufTextureFrame`].
+#[derive(Debug)]
+pub struct DmabufPlane {
+    pub fd: OwnedFd,
+    pub offset: u32,
+    pub stride: u32,
+}
+
+/// A frame backed by a dmabuf (e.g.: decoded by a hardware video decoder).
+pub struct DmabufTextureFrame {
+    pub width: u32,
+    pub height: u32,
+    /// DRM fourcc code of the pixel format (e.g.: `NV12`).
+    pub fourcc: u32,
+    /// DRM format modifier shared by all the planes.
+    pub modifier: u64,
+    pub planes: Vec<DmabufPlane>,
+    release_callback: Mutex<Option<DestructorType>>,
+}
+
+impl DmabufTextureFrame {
+    /// The |release_callback| is called once neither the engine nor the
+    /// embedder use the frame anymore, i.e.: the dmabuf can be reused.
+    pub fn new<F>(
+        width: u32,
+        height: u32,
+        fourcc: u32,
+        modifier: u64,
+        planes: Vec<DmabufPlane>,
+        release_callback: F,
+    ) -> Self
+    where
+        F: FnOnce() + 'static + Send,
+    {
+        Self {
+            width,
+            height,
+            fourcc,
+            modifier,
+            planes,
+            release_callback: Mutex::new(Some(Box::new(release_callback))),
+        }
+    }
+}
+
+impl Drop for DmabufTextureFrame {
+    fn drop(&mut self) {
+        if let Some(release_callback) = self.release_callback.get_mut().take() {
+            trace!("dmabuf texture frame: release");
+            release_callback();
+        }
+    }
+}
+
 unsafe extern "C" fn texture_destruction_callback(user_data: *mut c_void) {
     trace!("texture_destruction_callback");
     let user_data = user_data as *mut DestructorType;
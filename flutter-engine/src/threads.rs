@@ -0,0 +1,369 @@
+//! CPU affinity and scheduling of the threads created by the engine (UI,
+//! raster and IO), for deployments sharing the CPUs with real-time processes
+//! (e.g.: embedded HMIs) which need to guarantee the frame deadlines.
+//!
+//! The engine threads are only reachable through the thread priority setter
+//! of the custom task runners, which the engine invokes on each of its
+//! threads when starting them. The priority it asks for identifies the
+//! thread: `kDisplay` for the UI thread, `kRaster` for the raster thread, and
+//! `kNormal` or `kBackground` for the IO thread.
+//!
+//! The setter doesn't take any user data, so the configuration is global to
+//! the process: the last engine built with a configuration applies it to the
+//! threads started from then on. Without any configuration (the default), no
+//! setter is installed and the engine threads are left untouched.
+use std::{fmt, io};
+
+use flutter_engine_sys::FlutterThreadPriority;
+use parking_lot::{const_mutex, Mutex};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+static THREAD_CONFIG: Mutex<Option<ThreadConfig>> = const_mutex(None);
+static THREAD_REPORTS: Mutex<Vec<ThreadPolicyReport>> = const_mutex(Vec::new());
+
+/// Policies of the engine threads, see `FlutterEngineBuilder::with_thread_config`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadConfig {
+    pub raster: ThreadPolicy,
+    pub ui: ThreadPolicy,
+    pub io: ThreadPolicy,
+}
+
+impl ThreadConfig {
+    pub fn is_empty(&self) -> bool {
+        self.raster.is_empty() && self.ui.is_empty() && self.io.is_empty()
+    }
+
+    fn policy(&self, thread: EngineThread) -> &ThreadPolicy {
+        match thread {
+            EngineThread::Ui => &self.ui,
+            EngineThread::Raster => &self.raster,
+            EngineThread::Io => &self.io,
+        }
+    }
+}
+
+/// Scheduling of a thread. Unset settings are inherited from the thread
+/// which started the engine.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadPolicy {
+    /// CPUs the thread may run on, as a mask (bit `n` for CPU `n`).
+    pub affinity_mask: Option<u64>,
+    /// Nice value, from -20 (highest priority) to 19. Lowering it requires
+    /// `CAP_SYS_NICE`.
+    pub nice: Option<i32>,
+    /// Real-time scheduling, which requires `CAP_SYS_NICE` (or an
+    /// `RLIMIT_RTPRIO` limit high enough).
+    pub realtime: Option<RealtimePolicy>,
+}
+
+impl ThreadPolicy {
+    pub fn with_affinity_mask(mut self, mask: u64) -> Self {
+        self.affinity_mask = Some(mask);
+        self
+    }
+
+    pub fn with_nice(mut self, nice: i32) -> Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    pub fn with_realtime(mut self, scheduler: RealtimeScheduler, priority: i32) -> Self {
+        self.realtime = Some(RealtimePolicy {
+            scheduler,
+            priority,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.affinity_mask.is_none() && self.nice.is_none() && self.realtime.is_none()
+    }
+
+    /// Apply the policy to the calling thread, as far as permitted. The
+    /// settings which could not be applied are listed in the report rather
+    /// than failing the whole policy.
+    pub fn apply_to_current_thread(&self) -> ThreadPolicyReport {
+        let mut report = ThreadPolicyReport::default();
+
+        if let Some(mask) = self.affinity_mask {
+            report.record(format!("affinity {mask:#x}"), set_affinity(mask));
+        }
+        if let Some(nice) = self.nice {
+            report.record(format!("nice {nice}"), set_nice(nice));
+        }
+        if let Some(realtime) = self.realtime {
+            report.record(realtime.to_string(), set_realtime(realtime));
+        }
+
+        report
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RealtimePolicy {
+    pub scheduler: RealtimeScheduler,
+    /// From 1 (lowest) to 99 on Linux.
+    pub priority: i32,
+}
+
+impl fmt::Display for RealtimePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.scheduler {
+            RealtimeScheduler::Fifo => write!(f, "SCHED_FIFO {}", self.priority),
+            RealtimeScheduler::RoundRobin => write!(f, "SCHED_RR {}", self.priority),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RealtimeScheduler {
+    Fifo,
+    RoundRobin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EngineThread {
+    Ui,
+    Raster,
+    Io,
+}
+
+impl EngineThread {
+    fn from_priority(priority: FlutterThreadPriority) -> Self {
+        match priority {
+            FlutterThreadPriority::kDisplay => Self::Ui,
+            FlutterThreadPriority::kRaster => Self::Raster,
+            FlutterThreadPriority::kNormal | FlutterThreadPriority::kBackground => Self::Io,
+        }
+    }
+}
+
+impl fmt::Display for EngineThread {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ui => write!(f, "UI"),
+            Self::Raster => write!(f, "raster"),
+            Self::Io => write!(f, "IO"),
+        }
+    }
+}
+
+/// What a policy changed on a thread, and why the rest of it wasn't applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadPolicyReport {
+    /// `None` for the threads not started by the engine.
+    pub thread: Option<EngineThread>,
+    pub applied: Vec<String>,
+    /// The settings which were not applied, along with the error.
+    pub failed: Vec<String>,
+}
+
+impl ThreadPolicyReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    fn record(&mut self, setting: String, result: io::Result<()>) {
+        match result {
+            Ok(()) => self.applied.push(setting),
+            Err(err) => self.failed.push(format!("{setting}: {err}")),
+        }
+    }
+}
+
+/// Set the configuration applied by [`thread_priority_setter`]. Returns
+/// whether the setter should be installed.
+pub(crate) fn set_thread_config(config: ThreadConfig) -> bool {
+    if config.is_empty() {
+        return false;
+    }
+
+    *THREAD_CONFIG.lock() = Some(config);
+    true
+}
+
+/// Reports of the engine threads started so far with a configuration.
+pub(crate) fn thread_policy_reports() -> Vec<ThreadPolicyReport> {
+    THREAD_REPORTS.lock().clone()
+}
+
+/// Invoked by the engine on each of its threads, when starting them.
+pub(crate) extern "C" fn thread_priority_setter(priority: FlutterThreadPriority) {
+    let thread = EngineThread::from_priority(priority);
+    let Some(policy) = THREAD_CONFIG
+        .lock()
+        .as_ref()
+        .map(|config| config.policy(thread).clone())
+    else {
+        return;
+    };
+
+    if policy.is_empty() {
+        return;
+    }
+
+    let report = ThreadPolicyReport {
+        thread: Some(thread),
+        ..policy.apply_to_current_thread()
+    };
+    if report.is_complete() {
+        debug!(
+            "Applied the policy of the {} thread: {:?}",
+            thread, report.applied
+        );
+    } else {
+        warn!(
+            "Partially applied the policy of the {} thread: applied {:?}, not applied {:?}",
+            thread, report.applied, report.failed
+        );
+    }
+
+    THREAD_REPORTS.lock().push(report);
+}
+
+#[cfg(target_os = "linux")]
+fn check(result: libc::c_int) -> io::Result<()> {
+    match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(mask: u64) -> io::Result<()> {
+    if mask == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "empty CPU mask",
+        ));
+    }
+
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for cpu in (0..u64::BITS as usize).filter(|cpu| mask & (1 << cpu) != 0) {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        check(libc::sched_setaffinity(
+            0,
+            std::mem::size_of::<libc::cpu_set_t>(),
+            &set,
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_nice(nice: i32) -> io::Result<()> {
+    if !(-20..=19).contains(&nice) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "out of -20..=19",
+        ));
+    }
+
+    // On Linux, the nice value is per thread.
+    unsafe {
+        let tid = libc::gettid() as libc::id_t;
+        check(libc::setpriority(libc::PRIO_PROCESS, tid, nice))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_realtime(realtime: RealtimePolicy) -> io::Result<()> {
+    let policy = match realtime.scheduler {
+        RealtimeScheduler::Fifo => libc::SCHED_FIFO,
+        RealtimeScheduler::RoundRobin => libc::SCHED_RR,
+    };
+
+    unsafe {
+        let (min, max) = (
+            libc::sched_get_priority_min(policy),
+            libc::sched_get_priority_max(policy),
+        );
+        if !(min..=max).contains(&realtime.priority) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("out of {min}..={max}"),
+            ));
+        }
+
+        let param = libc::sched_param {
+            sched_priority: realtime.priority,
+        };
+        check(libc::sched_setscheduler(0, policy, &param))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "not supported on this platform",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_mask: u64) -> io::Result<()> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_nice(_nice: i32) -> io::Result<()> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_realtime(_realtime: RealtimePolicy) -> io::Result<()> {
+    unsupported()
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_engine_sys::FlutterThreadPriority;
+
+    use super::{EngineThread, RealtimeScheduler, ThreadConfig, ThreadPolicy};
+
+    #[test]
+    fn identifies_threads_by_priority() {
+        let threads = [
+            (FlutterThreadPriority::kDisplay, EngineThread::Ui),
+            (FlutterThreadPriority::kRaster, EngineThread::Raster),
+            (FlutterThreadPriority::kNormal, EngineThread::Io),
+            (FlutterThreadPriority::kBackground, EngineThread::Io),
+        ];
+        for (priority, thread) in threads {
+            assert_eq!(EngineThread::from_priority(priority), thread);
+        }
+    }
+
+    #[test]
+    fn default_config_is_a_no_op() {
+        assert!(ThreadConfig::default().is_empty());
+        assert!(ThreadPolicy::default()
+            .apply_to_current_thread()
+            .applied
+            .is_empty());
+
+        let config = ThreadConfig {
+            raster: ThreadPolicy::default().with_affinity_mask(0b10),
+            ..Default::default()
+        };
+        assert!(!config.is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_settings() {
+        let report = ThreadPolicy::default()
+            .with_affinity_mask(0)
+            .with_nice(42)
+            .with_realtime(RealtimeScheduler::Fifo, 1000)
+            .apply_to_current_thread();
+
+        assert!(report.applied.is_empty());
+        assert_eq!(report.failed.len(), 3);
+        assert!(!report.is_complete());
+    }
+}
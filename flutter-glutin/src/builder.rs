@@ -1,16 +1,26 @@
 use dpi::PhysicalSize;
 use glutin::{
-    config::{Api, Config, ConfigSurfaceTypes, ConfigTemplateBuilder},
-    context::{ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentGlContext},
-    display::{Display, DisplayApiPreference, GetGlDisplay},
+    config::{Api, Config, ConfigTemplate, ConfigSurfaceTypes, ConfigTemplateBuilder, GlConfig},
+    context::{
+        ContextApi, ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentGlContext,
+        Robustness,
+    },
+    display::{Display, DisplayApiPreference, DisplayFeatures, GetGlDisplay},
     prelude::{GlDisplay, NotCurrentGlContext},
-    surface::{GlSurface, SurfaceAttributesBuilder, SwapInterval, WindowSurface},
+    surface::{
+        GlSurface, SurfaceAttributes, SurfaceAttributesBuilder, SwapInterval, WindowSurface,
+    },
 };
 use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 use std::num::NonZeroU32;
 use thiserror::Error;
 
-use crate::context::{Context, ResourceContext};
+use crate::{
+    context::{Context, ResourceContext},
+    debug,
+    device::{self, DevicePreference},
+    renderer::RendererInfo,
+};
 
 pub type FlutterEGLContext = (Context, ResourceContext);
 
@@ -21,6 +31,9 @@ pub struct ContextBuilderAttributes {
     pub config: Option<Config>,
     pub size: Option<PhysicalSize<NonZeroU32>>,
     pub swap_interval: Option<SwapInterval>,
+    pub device_preference: DevicePreference,
+    pub robust: bool,
+    pub debug_output: bool,
 }
 
 impl ContextBuilderAttributes {
@@ -40,6 +53,28 @@ impl ContextBuilder {
     }
 
     pub fn build(self) -> Result<FlutterEGLContext, ContextBuildError> {
+        // The device can only be selected for displays created by the builder
+        // (`sctk`). With a config, the display already exists (`winit`).
+        let device = match self.attributes.raw_display_handle {
+            Some(_) => device::select_device(&self.attributes.device_preference),
+            None => None,
+        };
+
+        match self.build_context() {
+            Ok((mut context, resource_context)) => {
+                context.set_device(device);
+                Ok((context, resource_context))
+            }
+            Err(_) if device.is_some() => {
+                // Fall back to the default device.
+                device::reset_device_selection();
+                self.build_context()
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn build_context(&self) -> Result<FlutterEGLContext, ContextBuildError> {
         let raw_window_handle = self
             .attributes
             .raw_window_handle
@@ -68,51 +103,96 @@ impl ContextBuilder {
         let config = self
             .attributes
             .config
+            .clone()
             .map_or_else(|| new_default_config(&display, raw_window_handle), Ok)?;
 
-        let render_attributes_gl = ContextAttributesBuilder::new()
-            .with_context_api(glutin::context::ContextApi::OpenGl(None))
-            .build(Some(raw_window_handle));
-
-        let render_attributes_gles = ContextAttributesBuilder::new()
-            .with_context_api(glutin::context::ContextApi::Gles(None))
-            .build(Some(raw_window_handle));
-
-        // Create a context, trying OpenGL and then OpenGL ES.
-        let render_context = unsafe {
-            display
-                .create_context(&config, &render_attributes_gl)
-                .or_else(|_| display.create_context(&config, &render_attributes_gles))
-        }?;
-
-        let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::default().build(
-            raw_window_handle,
-            size.width,
-            size.height,
-        );
-        let surface = unsafe { display.create_window_surface(&config, &surface_attributes)? };
-
-        // Set EGL swap interval (if configured)
-        let render_context = match self.attributes.swap_interval.clone().take() {
-            Some(swap_interval) => {
-                let render_context = render_context.make_current(&surface)?;
-                surface.set_swap_interval(&render_context, swap_interval)?;
-                render_context.make_not_current()?
+        let create_render_context = |robustness: Robustness, is_debug: bool| {
+            let render_attributes_gl = ContextAttributesBuilder::new()
+                .with_context_api(ContextApi::OpenGl(None))
+                .with_robustness(robustness)
+                .with_debug(is_debug)
+                .build(Some(raw_window_handle));
+
+            let render_attributes_gles = ContextAttributesBuilder::new()
+                .with_context_api(ContextApi::Gles(None))
+                .with_robustness(robustness)
+                .with_debug(is_debug)
+                .build(Some(raw_window_handle));
+
+            // Create a context, trying OpenGL and then OpenGL ES.
+            unsafe {
+                display
+                    .create_context(&config, &render_attributes_gl)
+                    .or_else(|_| display.create_context(&config, &render_attributes_gles))
             }
-            None => render_context,
         };
 
+        // Without `EGL_EXT_create_context_robustness` (or when the driver
+        // rejects it for both APIs), fall back to a regular context. Same for
+        // the debug contexts, which are given up first.
+        let supports_robustness = display
+            .supported_features()
+            .contains(DisplayFeatures::CONTEXT_ROBUSTNESS);
+        let robustness = if self.attributes.robust && supports_robustness {
+            Robustness::RobustLoseContextOnReset
+        } else {
+            Robustness::NotRobust
+        };
+        let is_debug = self.attributes.debug_output;
+        let (render_context, robustness, is_debug) =
+            match create_render_context(robustness, is_debug) {
+                Ok(context) => (context, robustness, is_debug),
+                Err(err) => {
+                    let mut fallbacks = vec![(robustness, false), (Robustness::NotRobust, false)];
+                    fallbacks.dedup();
+                    fallbacks.retain(|&fallback| fallback != (robustness, is_debug));
+                    fallbacks
+                        .into_iter()
+                        .find_map(|(robustness, is_debug)| {
+                            let context = create_render_context(robustness, is_debug).ok()?;
+                            Some((context, robustness, is_debug))
+                        })
+                        .ok_or(err)?
+                }
+            };
+
+        let surface_attributes = window_surface_attributes(raw_window_handle, size);
+        let surface = unsafe { display.create_window_surface(&config, &surface_attributes)? };
+
+        // Set EGL swap interval (if configured), enable the debug output, and
+        // query the renderer, which all require the context to be current.
+        let render_context = render_context.make_current(&surface)?;
+        if let Some(swap_interval) = self.attributes.swap_interval {
+            surface.set_swap_interval(&render_context, swap_interval)?;
+        }
+        let has_debug_output = is_debug
+            && unsafe { debug::enable_debug_output(|proc| display.get_proc_address(proc)) };
+        let renderer = unsafe { RendererInfo::query(|proc| display.get_proc_address(proc)) };
+        let render_context = render_context.make_not_current()?;
+
+        // Shared contexts must have the same reset notification strategy.
         let resource_attributes = ContextAttributesBuilder::new()
             .with_sharing(&render_context)
+            .with_robustness(robustness)
+            .with_debug(has_debug_output)
             .build(Some(raw_window_handle));
         let resource_context = unsafe { display.create_context(&config, &resource_attributes)? };
 
-        let NotCurrentContext::Egl(resource_context) = resource_context else {
+        let NotCurrentContext::Egl(mut resource_context) = resource_context else {
             return Err(ContextBuildError::InvalidResourceContextApi);
         };
+        if has_debug_output {
+            let current = resource_context.make_current_surfaceless()?;
+            unsafe { debug::enable_debug_output(|proc| display.get_proc_address(proc)) };
+            resource_context = current.make_not_current()?;
+        }
         let resource_context = ResourceContext::new(resource_context.treat_as_possibly_current());
 
-        let context = Context::new(display, surface, render_context.treat_as_possibly_current());
+        let mut context =
+            Context::new(display, surface, render_context.treat_as_possibly_current());
+        context.set_robust(robustness == Robustness::RobustLoseContextOnReset);
+        context.set_debug_output(has_debug_output);
+        context.set_renderer(renderer);
 
         Ok((context, resource_context))
     }
@@ -137,6 +217,38 @@ impl ContextBuilder {
         self
     }
 
+    /// Select the device rendering the content. This has no effect when a
+    /// config is provided, since its display is already created (see
+    /// [`device::select_device`]).
+    pub fn with_device_preference(mut self, device_preference: DevicePreference) -> Self {
+        self.attributes.device_preference = device_preference;
+        self
+    }
+
+    /// Request robust contexts (`EGL_EXT_create_context_robustness`), which
+    /// report a GPU reset (e.g.: after a driver crash) by losing the context
+    /// rather than crashing or rendering garbage. Recovering from it requires
+    /// recreating the contexts.
+    ///
+    /// When the display or the driver doesn't support it, regular contexts
+    /// are created instead. See [`Context::is_robust`].
+    pub fn with_robustness(mut self, robust: bool) -> Self {
+        self.attributes.robust = robust;
+        self
+    }
+
+    /// Request debug contexts, whose `GL_KHR_debug` messages (e.g.: errors,
+    /// or performance warnings of the driver) are logged with `tracing`,
+    /// mapping their severity to the log level. Debug contexts are slower, so
+    /// this is only meant for diagnosing rendering issues.
+    ///
+    /// When the driver doesn't support it, regular contexts are created
+    /// instead. See [`Context::has_debug_output`].
+    pub fn with_debug_output(mut self, debug_output: bool) -> Self {
+        self.attributes.debug_output = debug_output;
+        self
+    }
+
     pub fn with_size(mut self, size: Option<PhysicalSize<NonZeroU32>>) -> Self {
         self.attributes.size = size;
         self
@@ -167,17 +279,69 @@ pub enum ContextBuildError {
     GlutinError(#[from] glutin::error::Error),
 }
 
+/// Template of the configs suitable for rendering the engine frames into a
+/// window. Backends creating the display themselves (e.g.: `winit`) should
+/// look for configs with it, and pick one with [`choose_config`].
+pub fn config_template() -> ConfigTemplateBuilder {
+    ConfigTemplateBuilder::new()
+        .with_surface_type(ConfigSurfaceTypes::WINDOW)
+        .with_api(Api::GLES2 | Api::GLES3 | Api::OPENGL)
+}
+
+/// Pick the config used for rendering among |configs|, preferring the ones
+/// with an alpha channel (i.e.: transparent windows) and without
+/// multisampling (the engine handles the anti-aliasing). Falls back to the
+/// first config otherwise.
+pub fn choose_config(configs: impl Iterator<Item = Config>) -> Option<Config> {
+    let mut fallback = None;
+    for config in configs {
+        if config.alpha_size() >= 8 && config.num_samples() <= 1 {
+            return Some(config);
+        }
+        fallback.get_or_insert(config);
+    }
+    fallback
+}
+
+/// Attributes of the window surfaces the engine frames are rendered into.
+///
+/// The color space is set explicitly rather than left to the driver: the
+/// engine writes sRGB encoded values, which must not be encoded again (as
+/// with `EGL_GL_COLORSPACE_SRGB`), so the surfaces are linear, i.e.: store
+/// the values as written. The backends tell the compositor that the content
+/// is sRGB where supported (e.g.: with `wp_color_management_v1`).
+pub fn window_surface_attributes(
+    raw_window_handle: RawWindowHandle,
+    size: PhysicalSize<NonZeroU32>,
+) -> SurfaceAttributes<WindowSurface> {
+    SurfaceAttributesBuilder::<WindowSurface>::new()
+        .with_srgb(Some(false))
+        .build(raw_window_handle, size.width, size.height)
+}
+
 fn new_default_config(
     display: &Display,
     raw_window_handle: RawWindowHandle,
 ) -> Result<Config, ContextBuildError> {
-    let config_template = ConfigTemplateBuilder::new()
-        .compatible_with_native_window(raw_window_handle)
-        .with_surface_type(ConfigSurfaceTypes::WINDOW)
-        .with_api(Api::GLES2 | Api::GLES3 | Api::OPENGL)
-        .build();
-
-    unsafe { display.find_configs(config_template) }?
-        .next()
-        .ok_or(ContextBuildError::NoAvailableConfigFound)
+    let find_config = |config_template: ConfigTemplate| {
+        unsafe { display.find_configs(config_template) }
+            .ok()
+            .and_then(choose_config)
+    };
+
+    // Some drivers don't report the supported APIs properly, so fall back to
+    // any config compatible with the window.
+    find_config(
+        config_template()
+            .compatible_with_native_window(raw_window_handle)
+            .build(),
+    )
+    .or_else(|| {
+        find_config(
+            ConfigTemplateBuilder::new()
+                .compatible_with_native_window(raw_window_handle)
+                .build(),
+        )
+    })
+    .ok_or(ContextBuildError::NoAvailableConfigFound)
 }
@@ -1,20 +1,28 @@
 use dpi::PhysicalSize;
 use glutin::{
     api::egl,
+    config::GetGlConfig,
     context::PossiblyCurrentContext,
-    display::Display,
+    display::{AsRawDisplay, Display, RawDisplay},
     prelude::{GlDisplay, NotCurrentGlContext, PossiblyCurrentGlContext},
-    surface::{GlSurface, Surface, WindowSurface},
+    surface::{GlSurface, Rect, Surface, SwapInterval, WindowSurface},
 };
+use raw_window_handle::RawWindowHandle;
 use std::{
     ffi::{c_void, CStr},
     num::NonZeroU32,
 };
 
+use crate::{builder::window_surface_attributes, device::EglDeviceInfo, renderer::RendererInfo};
+
 pub struct Context {
     display: Display,
     surface: Surface<WindowSurface>,
     context: Option<PossiblyCurrentContext>,
+    device: Option<EglDeviceInfo>,
+    renderer: RendererInfo,
+    is_robust: bool,
+    has_debug_output: bool,
 }
 
 impl Context {
@@ -27,9 +35,67 @@ impl Context {
             display,
             surface,
             context: Some(context),
+            device: None,
+            renderer: Default::default(),
+            is_robust: false,
+            has_debug_output: false,
         }
     }
 
+    /// The device selected for rendering, or `None` if the default device of
+    /// the driver is used.
+    pub fn device(&self) -> Option<&EglDeviceInfo> {
+        self.device.as_ref()
+    }
+
+    pub(crate) fn set_device(&mut self, device: Option<EglDeviceInfo>) {
+        self.device = device;
+    }
+
+    /// The renderer of the contexts, queried when they were created.
+    pub fn renderer(&self) -> &RendererInfo {
+        &self.renderer
+    }
+
+    pub(crate) fn set_renderer(&mut self, renderer: RendererInfo) {
+        self.renderer = renderer;
+    }
+
+    /// Whether the contexts render on a GPU, rather than with a software
+    /// rasterizer (see [`RendererInfo::is_hardware_accelerated`]).
+    pub fn is_hardware_accelerated(&self) -> bool {
+        let is_software_device = self
+            .device
+            .as_ref()
+            .is_some_and(|device| device.is_software);
+        !is_software_device && self.renderer.is_hardware_accelerated()
+    }
+
+    /// Whether the contexts are robust, i.e.: a GPU reset loses them instead
+    /// of crashing. Only true when requested and supported (see
+    /// [`ContextBuilder::with_robustness`]).
+    ///
+    /// [`ContextBuilder::with_robustness`]: crate::builder::ContextBuilder::with_robustness
+    pub fn is_robust(&self) -> bool {
+        self.is_robust
+    }
+
+    pub(crate) fn set_robust(&mut self, is_robust: bool) {
+        self.is_robust = is_robust;
+    }
+
+    /// Whether the debug messages of the contexts are logged. Only true when
+    /// requested and supported (see [`ContextBuilder::with_debug_output`]).
+    ///
+    /// [`ContextBuilder::with_debug_output`]: crate::builder::ContextBuilder::with_debug_output
+    pub fn has_debug_output(&self) -> bool {
+        self.has_debug_output
+    }
+
+    pub(crate) fn set_debug_output(&mut self, has_debug_output: bool) {
+        self.has_debug_output = has_debug_output;
+    }
+
     pub fn make_current(&mut self) -> bool {
         match self.context.as_ref() {
             Some(ctx) => ctx.make_current(&self.surface).is_ok(),
@@ -51,6 +117,15 @@ impl Context {
         self.display.get_proc_address(proc)
     }
 
+    /// The underlying `EGLDisplay`, or `None` if the context doesn't use EGL.
+    pub fn egl_display(&self) -> Option<*const c_void> {
+        match self.display.raw_display() {
+            RawDisplay::Egl(display) => Some(display),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
     pub fn resize(&mut self, size: PhysicalSize<NonZeroU32>) {
         if let Some(ctx) = self.context.as_ref() {
             self.surface.resize(ctx, size.width, size.height);
@@ -63,6 +138,52 @@ impl Context {
             None => false,
         }
     }
+
+    /// Number of frames since the back buffer was last presented, or 0 if its
+    /// content is unknown (e.g.: `EGL_EXT_buffer_age` is not supported, or the
+    /// surface was just resized).
+    pub fn buffer_age(&self) -> u32 {
+        self.surface.buffer_age()
+    }
+
+    /// Present the back buffer, telling the compositor that only |damage|
+    /// changed since the previous frame. The rectangles are relative to the
+    /// bottom-left corner of the surface. Falls back to a regular present
+    /// when `EGL_KHR_swap_buffers_with_damage` is not supported.
+    pub fn present_with_damage(&mut self, damage: &[Rect]) -> bool {
+        match (self.context.as_ref(), &self.surface) {
+            (Some(PossiblyCurrentContext::Egl(ctx)), Surface::Egl(surface)) => {
+                surface.swap_buffers_with_damage(ctx, damage).is_ok()
+            }
+            #[allow(unreachable_patterns)]
+            _ => self.present(),
+        }
+    }
+
+    /// Create an additional window surface which can be rendered to with this
+    /// context (e.g.: for mirroring the content of the main surface).
+    pub fn create_secondary_surface(
+        &self,
+        raw_window_handle: RawWindowHandle,
+        size: PhysicalSize<NonZeroU32>,
+        swap_interval: Option<SwapInterval>,
+    ) -> Result<SecondarySurface, glutin::error::Error> {
+        let config = match self.context.as_ref() {
+            Some(ctx) => ctx.config(),
+            None => return Err(glutin::error::ErrorKind::BadContext.into()),
+        };
+
+        let surface_attributes = window_surface_attributes(raw_window_handle, size);
+        let surface = unsafe {
+            self.display
+                .create_window_surface(&config, &surface_attributes)?
+        };
+
+        Ok(SecondarySurface {
+            surface,
+            swap_interval,
+        })
+    }
 }
 
 // `Context` is only `Send` as long as it's used correctly by the engine (e.g.:
@@ -73,6 +194,48 @@ impl Context {
 // TODO: Find a solution that better leverages Rust's type system
 unsafe impl Send for Context {}
 
+/// A window surface sharing the render context of a [`Context`]. Making it
+/// current replaces the main surface, which has to be made current again
+/// afterwards (see [`Context::make_current`]).
+pub struct SecondarySurface {
+    surface: Surface<WindowSurface>,
+    /// Swap interval applied the first time the surface is made current.
+    swap_interval: Option<SwapInterval>,
+}
+
+impl SecondarySurface {
+    pub fn make_current(&mut self, context: &Context) -> bool {
+        let Some(ctx) = context.context.as_ref() else {
+            return false;
+        };
+
+        if ctx.make_current(&self.surface).is_err() {
+            return false;
+        }
+
+        match self.swap_interval.take() {
+            Some(swap_interval) => self.surface.set_swap_interval(ctx, swap_interval).is_ok(),
+            None => true,
+        }
+    }
+
+    pub fn resize(&self, context: &Context, size: PhysicalSize<NonZeroU32>) {
+        if let Some(ctx) = context.context.as_ref() {
+            self.surface.resize(ctx, size.width, size.height);
+        }
+    }
+
+    pub fn present(&self, context: &Context) -> bool {
+        match context.context.as_ref() {
+            Some(ctx) => self.surface.swap_buffers(ctx).is_ok(),
+            None => false,
+        }
+    }
+}
+
+// See `Context`.
+unsafe impl Send for SecondarySurface {}
+
 pub struct ResourceContext {
     context: egl::context::PossiblyCurrentContext,
 }
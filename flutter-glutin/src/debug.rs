@@ -0,0 +1,156 @@
+//! Debug output of the contexts (`GL_KHR_debug`), which logs the messages of
+//! the driver (e.g.: errors, or performance warnings) with `tracing`. See
+//! [`ContextBuilder::with_debug_output`].
+//!
+//! [`ContextBuilder::with_debug_output`]: crate::builder::ContextBuilder::with_debug_output
+use std::{
+    ffi::{c_void, CStr, CString},
+    ptr, slice,
+};
+
+use tracing::{debug, error, info, warn, Level};
+
+use crate::gl::{
+    self,
+    types::{GLchar, GLenum, GLsizei, GLuint},
+};
+
+/// Log the debug messages of the current context, which must have been
+/// created as a debug context. Returns `false` when the driver doesn't
+/// support `GL_KHR_debug`.
+///
+/// # Safety
+///
+/// A context must be current on the calling thread, and |get_proc_address|
+/// must return the functions of its API.
+pub(crate) unsafe fn enable_debug_output(
+    get_proc_address: impl Fn(&CStr) -> *const c_void,
+) -> bool {
+    // OpenGL ES before 3.2 only has the `KHR` suffixed functions.
+    let gl = gl::Gl::load_with(|symbol| {
+        let Ok(symbol) = CString::new(symbol) else {
+            return ptr::null();
+        };
+
+        let address = get_proc_address(&symbol);
+        if !address.is_null() {
+            return address;
+        }
+
+        let mut suffixed = symbol.into_bytes();
+        suffixed.extend_from_slice(b"KHR");
+        match CString::new(suffixed) {
+            Ok(suffixed) => get_proc_address(&suffixed),
+            Err(_) => ptr::null(),
+        }
+    });
+
+    if !gl.DebugMessageCallback.is_loaded() {
+        return false;
+    }
+
+    gl.Enable(gl::DEBUG_OUTPUT);
+    // Messages are logged from the call which caused them, rather than from
+    // any thread of the driver.
+    gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    gl.DebugMessageCallback(Some(log_debug_message), ptr::null());
+    true
+}
+
+extern "system" fn log_debug_message(
+    source: GLenum,
+    kind: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    if message.is_null() {
+        return;
+    }
+
+    // The length excludes the terminating null character, and is negative
+    // when unknown.
+    let message = match usize::try_from(length) {
+        Ok(length) => {
+            let bytes = unsafe { slice::from_raw_parts(message.cast::<u8>(), length) };
+            String::from_utf8_lossy(bytes)
+        }
+        Err(_) => unsafe { CStr::from_ptr(message) }.to_string_lossy(),
+    };
+
+    let source = source_name(source);
+    let kind = kind_name(kind);
+    match severity_level(severity) {
+        Level::ERROR => error!("GL {kind} from {source} ({id}): {message}"),
+        Level::WARN => warn!("GL {kind} from {source} ({id}): {message}"),
+        Level::INFO => info!("GL {kind} from {source} ({id}): {message}"),
+        _ => debug!("GL {kind} from {source} ({id}): {message}"),
+    }
+}
+
+fn source_name(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other source",
+    }
+}
+
+fn kind_name(kind: GLenum) -> &'static str {
+    match kind {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability issue",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance issue",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "group push",
+        gl::DEBUG_TYPE_POP_GROUP => "group pop",
+        _ => "message",
+    }
+}
+
+/// The notifications (e.g.: buffer usage hints) are only logged at the debug
+/// level, as some drivers send lots of them.
+fn severity_level(severity: GLenum) -> Level {
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => Level::ERROR,
+        gl::DEBUG_SEVERITY_MEDIUM => Level::WARN,
+        gl::DEBUG_SEVERITY_LOW => Level::INFO,
+        _ => Level::DEBUG,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+
+    use super::{kind_name, severity_level, source_name};
+    use crate::gl;
+
+    #[test]
+    fn maps_the_severities_to_log_levels() {
+        assert_eq!(severity_level(gl::DEBUG_SEVERITY_HIGH), Level::ERROR);
+        assert_eq!(severity_level(gl::DEBUG_SEVERITY_MEDIUM), Level::WARN);
+        assert_eq!(severity_level(gl::DEBUG_SEVERITY_LOW), Level::INFO);
+        assert_eq!(
+            severity_level(gl::DEBUG_SEVERITY_NOTIFICATION),
+            Level::DEBUG
+        );
+    }
+
+    #[test]
+    fn names_the_sources_and_types() {
+        assert_eq!(
+            source_name(gl::DEBUG_SOURCE_SHADER_COMPILER),
+            "shader compiler"
+        );
+        assert_eq!(kind_name(gl::DEBUG_TYPE_PERFORMANCE), "performance issue");
+        assert_eq!(kind_name(0), "message");
+    }
+}
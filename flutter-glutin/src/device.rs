@@ -0,0 +1,219 @@
+//! Enumeration and selection of the EGL devices (i.e.: GPUs) available for
+//! rendering, through `EGL_EXT_device_enumeration` and `EGL_EXT_device_query`.
+//!
+//! EGL can't select the device of a window system display (e.g.: Wayland), so
+//! the selected device is applied through Mesa's `DRI_PRIME` environment
+//! variable. It must therefore be selected before the display is created, and
+//! it has no effect with other drivers (which keep using their default
+//! device). A `DRI_PRIME` value set by the user always takes precedence.
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use glutin::api::egl::device::Device;
+
+const DRI_PRIME: &str = "DRI_PRIME";
+
+/// An EGL device which may render the Flutter content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EglDeviceInfo {
+    /// Renderer name (e.g.: `AMD Radeon RX 6600`), if reported by the driver.
+    pub name: Option<String>,
+    pub vendor: Option<String>,
+    /// DRM render node of the device (e.g.: `/dev/dri/renderD128`).
+    pub render_node: Option<PathBuf>,
+    /// Whether the firmware used the device for the boot display. This is
+    /// the default device, usually the integrated GPU on hybrid laptops.
+    pub is_boot_vga: bool,
+    /// Whether the device is a software renderer (e.g.: llvmpipe).
+    pub is_software: bool,
+}
+
+impl EglDeviceInfo {
+    fn from_device(device: &Device) -> Self {
+        let render_node = device.drm_render_device_node_path().map(Path::to_path_buf);
+        let is_boot_vga = render_node
+            .as_deref()
+            .and_then(|render_node| read_sysfs_attribute(render_node, "boot_vga"))
+            .is_some_and(|boot_vga| boot_vga == "1");
+
+        Self {
+            name: device.name().map(String::from),
+            vendor: device.vendor().map(String::from),
+            render_node,
+            is_boot_vga,
+            is_software: device.extensions().contains("EGL_MESA_device_software"),
+        }
+    }
+
+    fn is_hardware(&self) -> bool {
+        !self.is_software && self.render_node.is_some()
+    }
+
+    /// The `DRI_PRIME` value selecting this device (e.g.: `pci-0000_01_00_0`).
+    fn dri_prime_tag(&self) -> Option<String> {
+        let render_node = self.render_node.as_deref()?;
+        let device_path = fs::canonicalize(sysfs_device_path(render_node)?).ok()?;
+        let pci_address = device_path.file_name()?.to_str()?;
+        Some(format!("pci-{}", pci_address.replace([':', '.'], "_")))
+    }
+}
+
+impl fmt::Display for EglDeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.name.as_deref().unwrap_or("Unknown device");
+        match (&self.vendor, &self.render_node) {
+            (Some(vendor), Some(render_node)) => {
+                write!(f, "{name} ({vendor}, {})", render_node.display())
+            }
+            (Some(vendor), None) => write!(f, "{name} ({vendor})"),
+            (None, Some(render_node)) => write!(f, "{name} ({})", render_node.display()),
+            (None, None) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Which device should render the Flutter content.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DevicePreference {
+    /// Let the driver decide.
+    #[default]
+    Default,
+    /// Prefer a GPU other than the boot one (e.g.: the discrete or external
+    /// GPU of a hybrid laptop).
+    HighPerformance,
+    /// Prefer the boot GPU (e.g.: the integrated GPU of a hybrid laptop).
+    LowPower,
+    /// The first device whose name or vendor contains the given string (case
+    /// insensitive), or whose render node is the given path.
+    Named(String),
+}
+
+impl DevicePreference {
+    /// Pick the device matching this preference among |devices|. Returns
+    /// `None` if the default device should be used.
+    pub fn select<'a>(&self, devices: &'a [EglDeviceInfo]) -> Option<&'a EglDeviceInfo> {
+        let mut hardware_devices = devices.iter().filter(|device| device.is_hardware());
+
+        match self {
+            DevicePreference::Default => None,
+            DevicePreference::HighPerformance => {
+                hardware_devices.find(|device| !device.is_boot_vga)
+            }
+            DevicePreference::LowPower => hardware_devices.find(|device| device.is_boot_vga),
+            DevicePreference::Named(pattern) => {
+                let lowercase_pattern = pattern.to_lowercase();
+                let contains = |value: &Option<String>| {
+                    value
+                        .as_ref()
+                        .is_some_and(|value| value.to_lowercase().contains(&lowercase_pattern))
+                };
+
+                devices.iter().find(|device| {
+                    contains(&device.name)
+                        || contains(&device.vendor)
+                        || device.render_node.as_deref() == Some(Path::new(pattern))
+                })
+            }
+        }
+    }
+}
+
+/// List the EGL devices. This is empty if device enumeration isn't supported
+/// by the EGL implementation.
+pub fn enumerate_devices() -> Vec<EglDeviceInfo> {
+    match Device::query_devices() {
+        Ok(devices) => devices
+            .map(|device| EglDeviceInfo::from_device(&device))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Select the device matching |preference| for the displays created
+/// afterwards. Returns the selected device, or `None` if the default device
+/// is used (e.g.: no device matches, or `DRI_PRIME` is set by the user).
+pub fn select_device(preference: &DevicePreference) -> Option<EglDeviceInfo> {
+    if *preference == DevicePreference::Default || env::var_os(DRI_PRIME).is_some() {
+        return None;
+    }
+
+    let devices = enumerate_devices();
+    let device = preference.select(&devices)?;
+    let tag = device.dri_prime_tag()?;
+
+    // Note: This happens before the render threads are started.
+    env::set_var(DRI_PRIME, tag);
+    Some(device.clone())
+}
+
+/// Revert a selection made by [`select_device`], e.g.: when the context could
+/// not be created on the selected device.
+pub fn reset_device_selection() {
+    env::remove_var(DRI_PRIME);
+}
+
+fn sysfs_device_path(render_node: &Path) -> Option<PathBuf> {
+    let node_name = render_node.file_name()?;
+    Some(Path::new("/sys/class/drm").join(node_name).join("device"))
+}
+
+fn read_sysfs_attribute(render_node: &Path, attribute: &str) -> Option<String> {
+    let path = sysfs_device_path(render_node)?.join(attribute);
+    fs::read_to_string(path)
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{DevicePreference, EglDeviceInfo};
+
+    fn device(name: &str, render_node: Option<&str>, is_boot_vga: bool) -> EglDeviceInfo {
+        EglDeviceInfo {
+            name: Some(name.into()),
+            vendor: None,
+            render_node: render_node.map(PathBuf::from),
+            is_boot_vga,
+            is_software: render_node.is_none(),
+        }
+    }
+
+    #[test]
+    fn selects_devices_by_preference() {
+        let devices = [
+            device("llvmpipe", None, false),
+            device("Mesa Intel(R) UHD Graphics 630", Some("/dev/dri/renderD128"), true),
+            device("AMD Radeon RX 6600", Some("/dev/dri/renderD129"), false),
+        ];
+
+        let select = |preference: DevicePreference| {
+            preference
+                .select(&devices)
+                .and_then(|device| device.name.clone())
+        };
+
+        assert_eq!(select(DevicePreference::Default), None);
+        assert_eq!(select(DevicePreference::HighPerformance).unwrap(), "AMD Radeon RX 6600");
+        assert_eq!(
+            select(DevicePreference::LowPower).unwrap(),
+            "Mesa Intel(R) UHD Graphics 630"
+        );
+        assert_eq!(
+            select(DevicePreference::Named("radeon".into())).unwrap(),
+            "AMD Radeon RX 6600"
+        );
+        assert_eq!(
+            select(DevicePreference::Named("/dev/dri/renderD128".into())).unwrap(),
+            "Mesa Intel(R) UHD Graphics 630"
+        );
+        assert_eq!(select(DevicePreference::Named("nvidia".into())), None);
+
+        // A single GPU is always the default one.
+        let devices = &devices[..2];
+        assert_eq!(DevicePreference::HighPerformance.select(devices), None);
+    }
+}
@@ -1,6 +1,9 @@
 pub mod builder;
 pub mod context;
+mod debug;
+pub mod device;
 pub mod handler;
+pub mod renderer;
 
 pub mod gl {
     #![allow(clippy::all)]
@@ -0,0 +1,109 @@
+//! Information about the renderer of the contexts, as reported by
+//! `glGetString`. It tells whether rendering is hardware accelerated, e.g.:
+//! for bug reports, or for adjusting the rendering quality.
+use std::{
+    ffi::{c_void, CStr, CString},
+    fmt, ptr,
+};
+
+use crate::gl::{self, types::GLenum};
+
+/// Renderer names of the software rasterizers of the common drivers.
+const SOFTWARE_RENDERERS: &[&str] = &[
+    "llvmpipe",
+    "softpipe",
+    "lavapipe",
+    "swrast",
+    "software rasterizer",
+    "swiftshader",
+    "microsoft basic render driver",
+];
+
+/// The renderer of a context. The strings are `None` when the driver didn't
+/// report them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RendererInfo {
+    /// Renderer name (e.g.: `AMD Radeon RX 6600 (radeonsi, navi23, ...)`).
+    pub renderer: Option<String>,
+    pub vendor: Option<String>,
+    /// Version of the API (e.g.: `4.6 (Compatibility Profile) Mesa 24.0.5`).
+    pub version: Option<String>,
+}
+
+impl RendererInfo {
+    /// Query the renderer of the current context.
+    ///
+    /// # Safety
+    ///
+    /// A context must be current on the calling thread, and
+    /// |get_proc_address| must return the functions of its API.
+    pub(crate) unsafe fn query(get_proc_address: impl Fn(&CStr) -> *const c_void) -> Self {
+        let gl = gl::Gl::load_with(|symbol| match CString::new(symbol) {
+            Ok(symbol) => get_proc_address(&symbol),
+            Err(_) => ptr::null(),
+        });
+
+        if !gl.GetString.is_loaded() {
+            return Self::default();
+        }
+
+        let get_string = |name: GLenum| {
+            let value = gl.GetString(name);
+            (!value.is_null()).then(|| CStr::from_ptr(value.cast()).to_string_lossy().into_owned())
+        };
+
+        Self {
+            renderer: get_string(gl::RENDERER),
+            vendor: get_string(gl::VENDOR),
+            version: get_string(gl::VERSION),
+        }
+    }
+
+    /// Whether the renderer is a GPU, rather than a software rasterizer (e.g.:
+    /// llvmpipe). An unknown renderer is assumed to be a GPU.
+    pub fn is_hardware_accelerated(&self) -> bool {
+        let Some(renderer) = &self.renderer else {
+            return true;
+        };
+
+        let renderer = renderer.to_lowercase();
+        !SOFTWARE_RENDERERS
+            .iter()
+            .any(|software| renderer.contains(software))
+    }
+}
+
+impl fmt::Display for RendererInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let renderer = self.renderer.as_deref().unwrap_or("Unknown renderer");
+        match (&self.vendor, &self.version) {
+            (Some(vendor), Some(version)) => write!(f, "{renderer} ({vendor}, {version})"),
+            (Some(vendor), None) => write!(f, "{renderer} ({vendor})"),
+            (None, Some(version)) => write!(f, "{renderer} ({version})"),
+            (None, None) => write!(f, "{renderer}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RendererInfo;
+
+    fn renderer(name: &str) -> RendererInfo {
+        RendererInfo {
+            renderer: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detects_software_renderers() {
+        assert!(!renderer("llvmpipe (LLVM 17.0.6, 256 bits)").is_hardware_accelerated());
+        assert!(!renderer("Google SwiftShader").is_hardware_accelerated());
+        assert!(!renderer("Mesa X11 Software Rasterizer").is_hardware_accelerated());
+
+        assert!(renderer("AMD Radeon RX 6600 (radeonsi, navi23)").is_hardware_accelerated());
+        assert!(renderer("Mesa Intel(R) UHD Graphics 620 (KBL GT2)").is_hardware_accelerated());
+        assert!(RendererInfo::default().is_hardware_accelerated());
+    }
+}
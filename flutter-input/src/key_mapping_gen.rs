@@ -1,7 +1,7 @@
 use flutter_engine::ffi::{FlutterLogicalKey, FlutterPhysicalKey};
-use smithay_client_toolkit::seat::keyboard::Keysym;
+use xkeysym::Keysym;
 
-use crate::keyboard::{SctkLogicalKey, SctkPhysicalKey};
+use crate::keyboard::{XkbLogicalKey, XkbPhysicalKey};
 
 /// DO NOT EDIT -- DO NOT EDIT -- DO NOT EDIT This file is generated by
 /// flutter/flutter@dev/tools/gen_keycodes/bin/gen_keycodes.dart and should not
@@ -12,8 +12,8 @@ use crate::keyboard::{SctkLogicalKey, SctkPhysicalKey};
 ///
 /// https://github.com/vially/flutter/blob/sctk/dev/tools/gen_keycodes/data/sctk_key_mapping_rs.tmpl
 
-impl From<SctkPhysicalKey> for FlutterPhysicalKey {
-    fn from(value: SctkPhysicalKey) -> Self {
+impl From<XkbPhysicalKey> for FlutterPhysicalKey {
+    fn from(value: XkbPhysicalKey) -> Self {
         let physical: u64 = match value.raw().raw() {
             0x00000009 => 0x00070029, // escape
             0x0000000a => 0x0007001e, // digit1
@@ -239,8 +239,8 @@ impl From<SctkPhysicalKey> for FlutterPhysicalKey {
     }
 }
 
-impl From<SctkLogicalKey> for FlutterLogicalKey {
-    fn from(value: SctkLogicalKey) -> Self {
+impl From<XkbLogicalKey> for FlutterLogicalKey {
+    fn from(value: XkbLogicalKey) -> Self {
         let logical: u64 = match value.raw() {
             Keysym::yen => 0x00200000022,
             Keysym::_3270_EraseEOF => 0x00100000405,
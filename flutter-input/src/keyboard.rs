@@ -0,0 +1,454 @@
+use std::{
+    ffi::CString,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use flutter_engine::{
+    ffi::{FlutterKeyEvent, FlutterKeyEventDeviceType, FlutterKeyEventType, FlutterLogicalKey},
+    FlutterEngine,
+};
+use flutter_plugins::{
+    keyevent::{KeyAction, KeyActionType},
+    textinput::TextInputPlugin,
+};
+use xkeysym::{KeyCode, Keysym};
+
+/// State of the modifiers when a key event happened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XkbModifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub caps_lock: bool,
+    pub logo: bool,
+    pub num_lock: bool,
+}
+
+/// Key event translated by xkb, whatever the source of the evdev code (e.g.:
+/// `wl_keyboard` or libinput).
+#[derive(Clone, Debug)]
+pub struct XkbKeyEvent {
+    pub device_type: FlutterKeyEventDeviceType,
+    /// The evdev code of the key (i.e.: the xkb keycode minus 8).
+    pub raw_code: u32,
+    pub keysym: Keysym,
+    /// Text produced by the key, if any.
+    pub utf8: Option<String>,
+    pub kind: FlutterKeyEventType,
+    pub modifiers: XkbModifiers,
+    pub synthesized: bool,
+
+    /// For `Up` events, this field holds the corresponding down `Keysym`. For
+    /// all other event kinds, this field will be `None`.
+    pub latched_keydown: Option<Keysym>,
+}
+
+impl From<XkbKeyEvent> for FlutterKeyEvent {
+    fn from(value: XkbKeyEvent) -> Self {
+        // Neither `wl_keyboard::key`'s `time` argument, which has an undefined
+        // base [0], nor the libinput timestamps can easily be converted to a
+        // Flutter timestamp (see `FlutterEngineGetCurrentTime()`). Therefore,
+        // the current engine time is used instead.
+        //
+        // [0]: https://wayland.app/protocols/wayland#wl_keyboard:event:key
+        let timestamp = FlutterEngine::get_current_time_duration();
+
+        let character = value.utf8.and_then(|utf8| CString::new(utf8).ok());
+
+        let character = match value.kind {
+            FlutterKeyEventType::Up => None,
+            FlutterKeyEventType::Down => character,
+            FlutterKeyEventType::Repeat => character,
+        };
+
+        // Flutter triggers an assertion failure when the *logical* key of an
+        // `Up` event does not match *exactly* the logical key of its
+        // corresponding `Down` event [0].
+        //
+        // However, there are legitimate reasons why the logical key would
+        // change between the `Down` and `Up` events. This could happen, for
+        // example, when a logical key changes case between the up and down
+        // events.
+        //
+        // A common sequence of events that could lead to this scenario is:
+        // - `XK_Shift` down
+        // - `XK_A` down (upper-case `A`, due to shift being down)
+        // - `XK_Shift` up
+        // - `XK_a` up (lower-case `a`, due to shift no longer being down)
+        //
+        // Therefore, in order to avoid the failed assertion, the logical key of
+        // the `Up` event that gets sent to the engine is built using the keysym
+        // of its corresponding `Down` event (instead of using its own keysym,
+        // which might be different).
+        //
+        // [0](https://github.com/flutter/flutter/blob/3.22.1/packages/flutter/lib/src/services/hardware_keyboard.dart#L512-L515)
+        let keysym = match value.kind {
+            FlutterKeyEventType::Up => value.latched_keydown.unwrap_or(value.keysym),
+            _ => value.keysym,
+        };
+
+        Self::new(
+            timestamp,
+            value.kind,
+            XkbPhysicalKey::new(value.raw_code).into(),
+            XkbLogicalKey::new(keysym).into(),
+            character,
+            value.synthesized,
+            value.device_type,
+        )
+    }
+}
+
+impl From<XkbKeyEvent> for KeyAction {
+    fn from(event: XkbKeyEvent) -> Self {
+        let event_type = match event.kind {
+            FlutterKeyEventType::Up => KeyActionType::Keyup,
+            FlutterKeyEventType::Down => KeyActionType::Keydown,
+            FlutterKeyEventType::Repeat => KeyActionType::Keydown,
+        };
+
+        let modifiers: GtkKeyActionModifiers = event.modifiers.into();
+
+        let logical: FlutterLogicalKey = XkbLogicalKey::new(event.keysym).into();
+        let specified_logical_key: i64 = logical.raw().try_into().unwrap_or(0);
+
+        let unicode_scalar_value: Option<UnicodeScalarValue> = event.utf8.try_into().ok();
+        let unicode_scalar_values = unicode_scalar_value
+            .map(|value| value.0 as i64)
+            .unwrap_or(0);
+
+        Self {
+            toolkit: "gtk".to_string(),
+            key_code: event.keysym.raw() as i32,
+            // Comment in `XkbPhysicalKey::new` provides some context about `+ 8`
+            scan_code: (event.raw_code + 8) as i32,
+            modifiers: modifiers.0,
+            specified_logical_key,
+            unicode_scalar_values,
+            keymap: "linux".to_string(),
+            _type: event_type,
+        }
+    }
+}
+
+struct GtkKeyActionModifiers(i32);
+
+impl From<XkbModifiers> for GtkKeyActionModifiers {
+    fn from(modifiers: XkbModifiers) -> Self {
+        let ctrl: i32 = modifiers.ctrl.into();
+        let alt: i32 = modifiers.alt.into();
+        let shift: i32 = modifiers.shift.into();
+        let caps_lock: i32 = modifiers.caps_lock.into();
+        let logo: i32 = modifiers.logo.into();
+        let num_lock: i32 = modifiers.num_lock.into();
+
+        // These values need to be kept in sync with the same values on the framework side.
+        // https://github.com/flutter/flutter/blob/1fa6f56b/packages/flutter/lib/src/services/raw_keyboard_linux.dart#L371-L411
+        let raw_modifiers =
+            shift | caps_lock << 1 | ctrl << 2 | alt << 3 | num_lock << 4 | logo << 26;
+
+        Self(raw_modifiers)
+    }
+}
+
+pub struct XkbPhysicalKey(KeyCode);
+
+impl XkbPhysicalKey {
+    pub fn new(raw_code: u32) -> Self {
+        // Add 8 to the evdev code to determine the xkb keycode:
+        // https://wayland.app/protocols/wayland#wl_keyboard:enum:keymap_format:entry:xkb_v1
+        Self(KeyCode::new(raw_code + 8))
+    }
+
+    pub fn raw(&self) -> KeyCode {
+        self.0
+    }
+}
+
+pub struct XkbLogicalKey(Keysym);
+
+impl XkbLogicalKey {
+    pub fn new(keysym: Keysym) -> Self {
+        Self(keysym)
+    }
+
+    pub fn raw(&self) -> Keysym {
+        self.0
+    }
+}
+
+struct UnicodeScalarValue(char);
+
+impl TryFrom<Option<String>> for UnicodeScalarValue {
+    type Error = ();
+
+    // Returns `Ok` if the value is *exactly* one `char` long or `Err` otherwise.
+    fn try_from(value: Option<String>) -> Result<Self, Self::Error> {
+        let Some(value) = value else {
+            return Err(());
+        };
+
+        let mut chars = value.chars();
+        let char = chars.next().ok_or(())?;
+
+        match chars.next() {
+            None => Ok(Self(char)),
+            Some(_) => Err(()),
+        }
+    }
+}
+
+pub trait FlutterStringExt {
+    fn is_control_character(&self) -> bool;
+}
+
+impl FlutterStringExt for str {
+    // Implementation is based on similar logic found in the Flutter engine:
+    // https://github.com/flutter/engine/blob/3.22.1/shell/platform/darwin/macos/framework/Source/FlutterEmbedderKeyResponder.mm#L30-L35
+    fn is_control_character(&self) -> bool {
+        let chars = self.as_bytes();
+        if chars.len() != 1 {
+            return false;
+        }
+
+        let character = &chars[0];
+        (0x00..=0x1f).contains(character) || (0x7f..=0x9f).contains(character)
+    }
+}
+
+type KeyEventFallthroughCallback = Box<dyn FnOnce() + Send>;
+
+/// Decides whether a key event falls through to the embedder (e.g.: to the
+/// text input), once the framework replied on both the key data path
+/// (`FlutterEngineSendKeyEvent`) and the legacy `flutter/keyevent` channel.
+/// The framework merges the two into a single event, so the event only falls
+/// through when neither reply handled it, and at most once.
+///
+/// Each path gets a clone, which it drops once it replied: |on_unhandled| is
+/// invoked when the last clone is dropped, unless the event was handled.
+#[derive(Clone)]
+pub struct KeyEventFallthrough(Arc<KeyEventFallthroughState>);
+
+struct KeyEventFallthroughState {
+    is_handled: AtomicBool,
+    on_unhandled: Mutex<Option<KeyEventFallthroughCallback>>,
+}
+
+impl KeyEventFallthrough {
+    pub fn new<F>(on_unhandled: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        Self(Arc::new(KeyEventFallthroughState {
+            is_handled: AtomicBool::new(false),
+            on_unhandled: Mutex::new(Some(Box::new(on_unhandled))),
+        }))
+    }
+
+    /// Record the reply of one of the paths.
+    pub fn respond(self, handled: bool) {
+        if handled {
+            self.0.is_handled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for KeyEventFallthroughState {
+    fn drop(&mut self) {
+        if self.is_handled.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let on_unhandled = self.on_unhandled.get_mut().ok().and_then(Option::take);
+        if let Some(on_unhandled) = on_unhandled {
+            on_unhandled();
+        }
+    }
+}
+
+/// Apply a key press (or repeat) to the text being edited, for the keys which
+/// are not already handled by the framework. The selection is extended when
+/// |select| is set (i.e.: shift is down).
+pub fn edit_text(
+    text_input: &mut TextInputPlugin,
+    keysym: Keysym,
+    utf8: Option<&str>,
+    select: bool,
+) {
+    // See OBS project implementation for a list of alternative key names
+    // that map to the same logical key:
+    // https://github.com/obsproject/obs-browser/blob/b4f724/linux-keyboard-helpers.hpp#L352
+    match keysym {
+        Keysym::Return | Keysym::KP_Enter | Keysym::ISO_Enter => {
+            text_input.enter_pressed();
+        }
+        Keysym::Home | Keysym::KP_Home => {
+            text_input.with_state(|state| state.move_to_beginning(select));
+            text_input.notify_changes();
+        }
+        Keysym::End | Keysym::KP_End => {
+            text_input.with_state(|state| state.move_to_end(select));
+            text_input.notify_changes();
+        }
+        Keysym::BackSpace
+        | Keysym::Delete
+        | Keysym::KP_Delete
+        | Keysym::Left
+        | Keysym::KP_Left
+        | Keysym::Right
+        | Keysym::KP_Right
+        | Keysym::Up
+        | Keysym::KP_Up
+        | Keysym::Down
+        | Keysym::KP_Down => {
+            // No-op: Already handled inside the framework in
+            // `RenderEditable`.
+        }
+        Keysym::Escape
+        | Keysym::Shift_L
+        | Keysym::Shift_R
+        | Keysym::Control_L
+        | Keysym::Control_R
+        | Keysym::Alt_L
+        | Keysym::Alt_R
+        | Keysym::ISO_Level3_Shift // AltGr on european keyboards
+        | Keysym::Super_L
+        | Keysym::Super_R
+        | Keysym::Meta_L
+        | Keysym::Meta_R => {
+            // No-op. A modifier key-down event should *not* be handled
+            // by the fallback code below. Doing so would have
+            // unintended side-effects (e.g.: removing/replacing
+            // selected text).
+        }
+        _ => {
+            let Some(text) = utf8 else {
+                return;
+            };
+
+            if text.is_control_character() {
+                return;
+            }
+
+            text_input.with_state(|state| {
+                state.add_characters(text);
+            });
+            text_input.notify_changes();
+        }
+    }
+}
+
+/// Insert the text committed by the input method (e.g.: the result of a
+/// composition) in the text being edited, in place of the text being
+/// composed.
+pub fn commit_text(text_input: &mut TextInputPlugin, text: &str) {
+    text_input.with_state(|state| {
+        state.delete_composing();
+        state.add_characters(text);
+    });
+    text_input.notify_changes();
+}
+
+/// Show the |text| being composed by the input method (i.e.: its preedit
+/// text) in the text being edited, until it is committed. An empty text
+/// cancels the composition.
+pub fn compose_text(text_input: &mut TextInputPlugin, text: &str) {
+    let mut has_changed = false;
+    text_input.with_state(|state| has_changed = state.set_composing_text(text));
+    if has_changed {
+        text_input.notify_changes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use flutter_plugins::textinput::{
+        conformance::{self, ConformanceBackend, ConformanceKey},
+        TextInputPlugin,
+    };
+    use xkeysym::Keysym;
+
+    use super::{commit_text, compose_text, edit_text, KeyEventFallthrough};
+
+    /// The input path of the xkb backends (i.e.: Wayland and libinput).
+    struct XkbBackend;
+
+    impl ConformanceBackend for XkbBackend {
+        fn key_pressed(&mut self, text_input: &mut TextInputPlugin, key: &ConformanceKey) {
+            edit_text(
+                text_input,
+                Keysym::new(key.keysym),
+                key.utf8.as_deref(),
+                key.shift,
+            );
+        }
+
+        fn preedit(&mut self, text_input: &mut TextInputPlugin, text: &str) {
+            compose_text(text_input, text);
+        }
+
+        fn commit(&mut self, text_input: &mut TextInputPlugin, text: &str) {
+            commit_text(text_input, text);
+        }
+    }
+
+    fn fallthrough() -> (KeyEventFallthrough, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let fallthrough = {
+            let count = count.clone();
+            KeyEventFallthrough::new(move || {
+                count.fetch_add(1, Ordering::Relaxed);
+            })
+        };
+        (fallthrough, count)
+    }
+
+    #[test]
+    fn falls_through_once_both_paths_replied_unhandled() {
+        let (fallthrough, count) = fallthrough();
+        let legacy = fallthrough.clone();
+
+        fallthrough.respond(false);
+        assert_eq!(count.load(Ordering::Relaxed), 0);
+        legacy.respond(false);
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn handled_events_do_not_fall_through() {
+        for replies in [[true, false], [false, true], [true, true]] {
+            let (fallthrough, count) = fallthrough();
+            let legacy = fallthrough.clone();
+
+            fallthrough.respond(replies[0]);
+            legacy.respond(replies[1]);
+            assert_eq!(count.load(Ordering::Relaxed), 0);
+        }
+    }
+
+    #[test]
+    fn dropped_paths_count_as_unhandled() {
+        let (fallthrough, count) = fallthrough();
+        let legacy = fallthrough.clone();
+
+        // E.g.: the legacy plugin isn't registered.
+        drop(legacy);
+        fallthrough.respond(false);
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn text_editing_conforms() {
+        if let Err(err) = conformance::check_all(&mut XkbBackend) {
+            panic!("{}", err);
+        }
+    }
+}
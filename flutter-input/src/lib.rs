@@ -0,0 +1,8 @@
+//! Conversion of the Linux input events to Flutter events, shared by the
+//! backends which receive evdev codes and translate keys with xkb (i.e.:
+//! Wayland and libinput).
+// The header of the generated file is a doc comment not attached to any item.
+#[allow(clippy::empty_line_after_doc_comments)]
+mod key_mapping_gen;
+pub mod keyboard;
+pub mod pointer;
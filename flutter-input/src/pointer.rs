@@ -0,0 +1,53 @@
+use flutter_engine::ffi::{FlutterPointerButtons, FlutterPointerMouseButtons, FlutterPointerPhase};
+
+// Mouse button codes, from `linux/input-event-codes.h`.
+pub const BTN_LEFT: u32 = 0x110;
+pub const BTN_RIGHT: u32 = 0x111;
+pub const BTN_MIDDLE: u32 = 0x112;
+pub const BTN_SIDE: u32 = 0x113;
+pub const BTN_EXTRA: u32 = 0x114;
+pub const BTN_FORWARD: u32 = 0x115;
+pub const BTN_BACK: u32 = 0x116;
+
+/// Flutter button of an evdev button code (e.g.: from `wl_pointer.button` or
+/// libinput).
+pub fn mouse_button_from_evdev(button: u32) -> FlutterPointerMouseButtons {
+    match button {
+        BTN_LEFT => FlutterPointerMouseButtons::Primary,
+        BTN_RIGHT => FlutterPointerMouseButtons::Secondary,
+        BTN_MIDDLE => FlutterPointerMouseButtons::Middle,
+        BTN_BACK | BTN_SIDE => FlutterPointerMouseButtons::Back,
+        BTN_FORWARD | BTN_EXTRA => FlutterPointerMouseButtons::Forward,
+        _ => FlutterPointerMouseButtons::None,
+    }
+}
+
+/// Phase of the events sent while the mouse moves with |buttons| held down.
+pub fn motion_phase(buttons: FlutterPointerButtons) -> FlutterPointerPhase {
+    if buttons.is_empty() {
+        FlutterPointerPhase::Hover
+    } else {
+        FlutterPointerPhase::Move
+    }
+}
+
+/// Phase of the event sent for a press of |button|, once it was added to
+/// |buttons|. Only the first pressed button starts a gesture, pressing more
+/// buttons during a drag only updates the mask.
+pub fn press_phase(buttons: FlutterPointerButtons, button: u32) -> FlutterPointerPhase {
+    if buttons == mouse_button_from_evdev(button).into() {
+        FlutterPointerPhase::Down
+    } else {
+        FlutterPointerPhase::Move
+    }
+}
+
+/// Phase of the event sent for a release, once the button was removed from
+/// |buttons|. The gesture ends when the last button is released.
+pub fn release_phase(buttons: FlutterPointerButtons) -> FlutterPointerPhase {
+    if buttons.is_empty() {
+        FlutterPointerPhase::Up
+    } else {
+        FlutterPointerPhase::Move
+    }
+}
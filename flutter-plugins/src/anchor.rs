@@ -0,0 +1,362 @@
+//! Anchors for positioning popups (e.g.: the text toolbar) relative to the
+//! widgets of a view.
+//!
+//! Wayland doesn't give clients any global coordinates: popups are placed by
+//! the compositor, relative to a rect of their parent surface. Apps turn the
+//! rect of a widget into an [`AnchorToken`] (see `widgetRectToAnchor` on the
+//! window channel), which the embedder maps back to the surface it belongs to
+//! when creating the popup.
+//!
+//! The rects are only valid for the geometry of the view they were created
+//! for, so the tokens of a view are invalidated whenever its size or pixel
+//! ratio (scale factor or zoom) changes. Apps are notified through
+//! `onAnchorsInvalidated`, and must create new tokens for the new layout.
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use flutter_engine::ffi::FlutterViewId;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Handle of a rect registered with [`AnchorRegistry::create`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AnchorToken(pub u64);
+
+/// Rect in logical pixels, relative to the view.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnchorRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl AnchorRect {
+    fn is_valid(&self) -> bool {
+        [self.x, self.y, self.width, self.height]
+            .iter()
+            .all(|value| value.is_finite())
+            && self.width >= 0.0
+            && self.height >= 0.0
+    }
+}
+
+/// Edge or corner of the anchor rect the popup is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PopupAnchor {
+    None,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    BottomLeft,
+    TopRight,
+    BottomRight,
+}
+
+/// Direction the popup extends to from its anchor point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PopupGravity {
+    None,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    BottomLeft,
+    TopRight,
+    BottomRight,
+}
+
+/// How the compositor may move or resize a popup which would otherwise end
+/// up (partially) off screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PopupConstraintAdjustment {
+    SlideX,
+    SlideY,
+    FlipX,
+    FlipY,
+    ResizeX,
+    ResizeY,
+}
+
+/// Offset of the popup from its anchor point, in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct PopupOffset {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Placement of a popup relative to its anchor rect. Mirrors
+/// `xdg_positioner`. The default places the popup centered above the rect,
+/// flipped below it when there isn't enough room.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PopupPlacement {
+    pub anchor: PopupAnchor,
+    pub gravity: PopupGravity,
+    pub constraint_adjustment: Vec<PopupConstraintAdjustment>,
+    pub offset: PopupOffset,
+}
+
+impl Default for PopupPlacement {
+    fn default() -> Self {
+        Self {
+            anchor: PopupAnchor::Top,
+            gravity: PopupGravity::Top,
+            constraint_adjustment: vec![
+                PopupConstraintAdjustment::FlipY,
+                PopupConstraintAdjustment::SlideX,
+            ],
+            offset: PopupOffset { x: 0.0, y: -8.0 },
+        }
+    }
+}
+
+/// Geometry of a view, as last accepted by the engine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewGeometry {
+    /// Size in physical pixels.
+    pub width: u32,
+    pub height: u32,
+    pub pixel_ratio: f64,
+}
+
+/// Tokens invalidated by a change of the geometry of a view, as reported
+/// through `onAnchorsInvalidated`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnchorsInvalidated {
+    pub view_id: FlutterViewId,
+    pub tokens: Vec<AnchorToken>,
+}
+
+/// Errors are reported to Dart as a `PlatformException` (see
+/// [`AnchorError::code`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnchorError {
+    /// The view doesn't exist, or hasn't been laid out yet.
+    UnknownView(FlutterViewId),
+
+    /// The rect has a negative size, or isn't finite.
+    InvalidRect,
+
+    /// The token was invalidated by a change of the view geometry, or was
+    /// never handed out.
+    Stale(AnchorToken),
+}
+
+impl AnchorError {
+    /// Error code of the method channel error response.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AnchorError::UnknownView(_) => "unknown-view",
+            AnchorError::InvalidRect => "invalid-rect",
+            AnchorError::Stale(_) => "stale-anchor",
+        }
+    }
+}
+
+impl fmt::Display for AnchorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnchorError::UnknownView(view_id) => write!(f, "Unknown view: {}", view_id),
+            AnchorError::InvalidRect => write!(f, "The anchor rect is invalid"),
+            AnchorError::Stale(token) => write!(f, "The anchor token {} is stale", token.0),
+        }
+    }
+}
+
+impl std::error::Error for AnchorError {}
+
+#[derive(Default)]
+struct ViewAnchors {
+    geometry: Option<ViewGeometry>,
+    rects: HashMap<AnchorToken, AnchorRect>,
+}
+
+#[derive(Default)]
+struct AnchorRegistryInner {
+    views: HashMap<FlutterViewId, ViewAnchors>,
+    next_token: u64,
+}
+
+/// Anchor rects of the views of an engine, shared between the plugins which
+/// hand out the tokens and the ones which resolve them.
+#[derive(Clone, Default)]
+pub struct AnchorRegistry {
+    inner: Arc<Mutex<AnchorRegistryInner>>,
+}
+
+impl AnchorRegistry {
+    /// Register |rect| of the view |view_id|. The view must have been laid
+    /// out (see [`AnchorRegistry::update_view`]).
+    pub fn create(
+        &self,
+        view_id: FlutterViewId,
+        rect: AnchorRect,
+    ) -> Result<AnchorToken, AnchorError> {
+        if !rect.is_valid() {
+            return Err(AnchorError::InvalidRect);
+        }
+
+        let inner = &mut *self.inner.lock();
+        let view = match inner.views.get_mut(&view_id) {
+            Some(view) if view.geometry.is_some() => view,
+            _ => return Err(AnchorError::UnknownView(view_id)),
+        };
+
+        inner.next_token += 1;
+        let token = AnchorToken(inner.next_token);
+        view.rects.insert(token, rect);
+        Ok(token)
+    }
+
+    /// Returns the view and the rect |token| was created for.
+    pub fn resolve(&self, token: AnchorToken) -> Result<(FlutterViewId, AnchorRect), AnchorError> {
+        let inner = self.inner.lock();
+        inner
+            .views
+            .iter()
+            .find_map(|(&view_id, view)| view.rects.get(&token).map(|&rect| (view_id, rect)))
+            .ok_or(AnchorError::Stale(token))
+    }
+
+    /// Record the geometry accepted by the engine for |view_id|. Returns the
+    /// tokens invalidated by the change, if any.
+    pub fn update_view(
+        &self,
+        view_id: FlutterViewId,
+        geometry: ViewGeometry,
+    ) -> Option<AnchorsInvalidated> {
+        let mut inner = self.inner.lock();
+        let view = inner.views.entry(view_id).or_default();
+        if view.geometry.replace(geometry) == Some(geometry) {
+            return None;
+        }
+
+        Self::invalidate(view_id, view)
+    }
+
+    /// Forget |view_id| (e.g.: its window has been closed). Returns the tokens
+    /// invalidated by the removal, if any.
+    pub fn remove_view(&self, view_id: FlutterViewId) -> Option<AnchorsInvalidated> {
+        let mut view = self.inner.lock().views.remove(&view_id)?;
+        Self::invalidate(view_id, &mut view)
+    }
+
+    fn invalidate(view_id: FlutterViewId, view: &mut ViewAnchors) -> Option<AnchorsInvalidated> {
+        if view.rects.is_empty() {
+            return None;
+        }
+
+        let mut tokens: Vec<AnchorToken> = view.rects.drain().map(|(token, _)| token).collect();
+        tokens.sort_by_key(|token| token.0);
+        Some(AnchorsInvalidated { view_id, tokens })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AnchorError, AnchorRect, AnchorRegistry, AnchorToken, AnchorsInvalidated, ViewGeometry,
+    };
+
+    const RECT: AnchorRect = AnchorRect {
+        x: 10.0,
+        y: 20.0,
+        width: 30.0,
+        height: 40.0,
+    };
+
+    fn geometry(width: u32, height: u32, pixel_ratio: f64) -> ViewGeometry {
+        ViewGeometry {
+            width,
+            height,
+            pixel_ratio,
+        }
+    }
+
+    #[test]
+    fn tokens_resolve_until_the_view_is_resized() {
+        let registry = AnchorRegistry::default();
+        assert_eq!(registry.create(0, RECT), Err(AnchorError::UnknownView(0)));
+
+        assert_eq!(registry.update_view(0, geometry(800, 600, 1.0)), None);
+        let first = registry.create(0, RECT).unwrap();
+        let second = registry.create(0, RECT).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(registry.resolve(first), Ok((0, RECT)));
+
+        // The same geometry keeps the tokens.
+        assert_eq!(registry.update_view(0, geometry(800, 600, 1.0)), None);
+        assert_eq!(registry.resolve(first), Ok((0, RECT)));
+
+        assert_eq!(
+            registry.update_view(0, geometry(1024, 600, 1.0)),
+            Some(AnchorsInvalidated {
+                view_id: 0,
+                tokens: vec![first, second],
+            })
+        );
+        assert_eq!(registry.resolve(first), Err(AnchorError::Stale(first)));
+        assert_eq!(registry.resolve(second), Err(AnchorError::Stale(second)));
+
+        // New tokens are never reused.
+        let third = registry.create(0, RECT).unwrap();
+        assert!(third.0 > second.0);
+        assert_eq!(registry.resolve(third), Ok((0, RECT)));
+    }
+
+    #[test]
+    fn scale_changes_only_invalidate_their_view() {
+        let registry = AnchorRegistry::default();
+        registry.update_view(0, geometry(800, 600, 1.0));
+        registry.update_view(1, geometry(400, 300, 1.0));
+        let token = registry.create(0, RECT).unwrap();
+        let other = registry.create(1, RECT).unwrap();
+
+        // A pixel ratio change (e.g.: moving to a HiDPI output, or zooming)
+        // keeps the logical size but moves the rects on the surface.
+        let invalidated = registry.update_view(0, geometry(1600, 1200, 2.0)).unwrap();
+        assert_eq!(invalidated.tokens, vec![token]);
+        assert_eq!(registry.resolve(token), Err(AnchorError::Stale(token)));
+        assert_eq!(registry.resolve(other), Ok((1, RECT)));
+
+        let invalidated = registry.remove_view(1).unwrap();
+        assert_eq!(invalidated.tokens, vec![other]);
+        assert_eq!(registry.create(1, RECT), Err(AnchorError::UnknownView(1)));
+        assert_eq!(
+            registry.resolve(AnchorToken(42)),
+            Err(AnchorError::Stale(AnchorToken(42)))
+        );
+    }
+
+    #[test]
+    fn invalid_rects_are_rejected() {
+        let registry = AnchorRegistry::default();
+        registry.update_view(0, geometry(800, 600, 1.0));
+
+        for (x, width, height) in [
+            (0.0, -1.0, 1.0),
+            (f64::NAN, 1.0, 1.0),
+            (0.0, 1.0, f64::INFINITY),
+        ] {
+            let rect = AnchorRect {
+                x,
+                width,
+                height,
+                ..RECT
+            };
+            assert_eq!(registry.create(0, rect), Err(AnchorError::InvalidRect));
+        }
+
+        // Collapsed rects (e.g.: a caret) are valid anchors.
+        let caret = AnchorRect { width: 0.0, ..RECT };
+        assert!(registry.create(0, caret).is_ok());
+    }
+}
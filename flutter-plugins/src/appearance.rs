@@ -0,0 +1,123 @@
+//! Plugin exposing the appearance preferences of the desktop that the
+//! `flutter/settings` channel doesn't carry, such as the accent color, so
+//! that dynamic-color apps (e.g.: Material 3) can match the system theme.
+//! It handles flutter-rs/appearance type message.
+use std::sync::{Arc, Weak};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::JSON_CODEC,
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/appearance";
+
+/// Opaque color, as a `0xAARRGGBB` value (i.e.: the value of a Dart
+/// `Color`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct AccentColor(pub u32);
+
+impl AccentColor {
+    /// Build the color from components in `0.0..=1.0`. Returns `None` if any
+    /// of them is out of range, which the desktops use for "no accent color".
+    pub fn from_rgb(red: f64, green: f64, blue: f64) -> Option<Self> {
+        let channel = |value: f64| {
+            (0.0..=1.0)
+                .contains(&value)
+                .then(|| (value * 255.0).round() as u32)
+        };
+
+        Some(Self(
+            0xff00_0000 | channel(red)? << 16 | channel(green)? << 8 | channel(blue)?,
+        ))
+    }
+}
+
+#[derive(Default)]
+pub struct AppearancePlugin {
+    channel: Weak<MethodChannel>,
+    accent_color: Arc<Mutex<Option<AccentColor>>>,
+}
+
+impl AppearancePlugin {
+    /// Store the accent color of the desktop, `None` when it has none.
+    /// Returns whether it differs from the previous one.
+    pub fn set_accent_color(&self, accent_color: Option<AccentColor>) -> bool {
+        let mut current = self.accent_color.lock();
+        let changed = *current != accent_color;
+        *current = accent_color;
+        changed
+    }
+
+    pub fn notify_accent_color_changed(&self) {
+        let accent_color = *self.accent_color.lock();
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onAccentColorChanged", accent_color);
+        }
+    }
+}
+
+impl Plugin for AppearancePlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                accent_color: self.accent_color.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    accent_color: Arc<Mutex<Option<AccentColor>>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            // Returns `null` when the desktop has no accent color, so that
+            // apps fall back to their own seed color.
+            "getAccentColor" => call.success(*self.accent_color.lock()),
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccentColor, AppearancePlugin};
+
+    #[test]
+    fn converts_portal_colors() {
+        assert_eq!(
+            AccentColor::from_rgb(1.0, 0.5, 0.0),
+            Some(AccentColor(0xffff8000))
+        );
+        assert_eq!(
+            AccentColor::from_rgb(0.0, 0.0, 0.0),
+            Some(AccentColor(0xff000000))
+        );
+        assert_eq!(AccentColor::from_rgb(-1.0, 0.5, 0.5), None);
+        assert_eq!(AccentColor::from_rgb(0.5, 0.5, 1.5), None);
+    }
+
+    #[test]
+    fn reports_changed_accent_color() {
+        let plugin = AppearancePlugin::default();
+
+        assert!(!plugin.set_accent_color(None));
+        assert!(plugin.set_accent_color(Some(AccentColor(0xff3584e4))));
+        assert!(!plugin.set_accent_color(Some(AccentColor(0xff3584e4))));
+        assert!(plugin.set_accent_color(None));
+    }
+}
@@ -0,0 +1,68 @@
+//! Plugin to hint which resolution-aware asset variants (e.g.: `2.0x/`) the
+//! app should have at hand.
+//! It handles flutter-rs/assets type message.
+//!
+//! The scales are the ones of every output the window currently touches, so
+//! that apps can precache the matching variants before the window is moved
+//! to (or mostly shown on) an output with a different scale.
+use std::sync::{Arc, Weak};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::JSON_CODEC,
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use tracing::debug;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/assets";
+
+#[derive(Default)]
+pub struct AssetsPlugin {
+    channel: Weak<MethodChannel>,
+    scales: Arc<Mutex<Vec<f64>>>,
+}
+
+impl AssetsPlugin {
+    /// Report the scales (sorted, without duplicates) of the outputs the
+    /// window touches.
+    pub fn notify_scales_changed(&self, scales: Vec<f64>) {
+        debug!("Output scales changed: {scales:?}");
+        *self.scales.lock() = scales.clone();
+
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("scalesChanged", scales);
+        }
+    }
+}
+
+impl Plugin for AssetsPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                scales: self.scales.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    scales: Arc<Mutex<Vec<f64>>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "getScales" => call.success(self.scales.lock().clone()),
+            _ => call.not_implemented(),
+        }
+    }
+}
@@ -0,0 +1,714 @@
+//! State shared between Rust and Dart over a channel, for the structs
+//! deriving [`ChannelBridge`]:
+//!
+//! ```ignore
+//! #[derive(ChannelBridge)]
+//! #[bridge(channel = "example/counter", commands = CounterCommand, diff)]
+//! struct Counter {
+//!     value: i64,
+//!     step_size: i64,
+//! }
+//!
+//! #[derive(ChannelBridge)]
+//! enum CounterCommand {
+//!     #[bridge(result = "int")]
+//!     Increment,
+//!     SetStepSize { step_size: i64 },
+//! }
+//! ```
+//!
+//! The `example/counter` method channel handles `getState` (which returns
+//! `{version: int, state: {value: int, stepSize: int}}`) and the commands,
+//! whose fields are the arguments (e.g.: `setStepSize` with `{stepSize:
+//! int}`). The changes are streamed on the `example/counter/state` event
+//! channel as `{version: int, changes: {...}}`, with only the changed
+//! top-level fields when diffing.
+//!
+//! The state may be updated from any thread with [`Bridge::update`]. The
+//! changes are computed under the lock of the state and delivered to Dart
+//! from the platform thread, in the order of the updates. The versions let
+//! the Dart client tell the changes it already has from the ones it missed
+//! while fetching the state. The commands are run on the async runtime of
+//! the host application (see `PluginContext::async_runtime`), or on a thread
+//! of their own without one, so that they can't block the platform thread.
+//!
+//! The Dart side (a data class per state and a `ValueNotifier` client, on
+//! top of `FlutterRsBridge` of the `flutter_rs` package) is generated with
+//! [`generate_dart`].
+use std::{collections::HashMap, fmt, fmt::Write, future::Future, pin::Pin, sync::Arc, thread};
+
+use flutter_engine::{
+    channel::{
+        DropPolicy, EventChannel, EventSink, FlowControl, MethodCall, MethodCallHandler,
+        MethodChannel, StreamError, StreamHandler,
+    },
+    codec::{
+        value::{from_value_owned, to_value},
+        JSON_CODEC,
+    },
+    plugins::{Plugin, PluginContext},
+    runtime::AsyncRuntime,
+};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{error, warn};
+
+pub use flutter_bridge_derive::ChannelBridge;
+pub use flutter_engine::codec::Value;
+
+pub const GET_STATE_METHOD: &str = "getState";
+/// Suffix of the event channel streaming the changes of the state.
+pub const STATE_CHANNEL_SUFFIX: &str = "/state";
+
+/// A top-level field of a state, or an argument of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BridgeField {
+    /// Name on the channel (i.e.: the Rust name in camel case).
+    pub name: &'static str,
+    /// Dart type of the values, e.g.: `List<int>` or `String?`.
+    pub dart_type: &'static str,
+}
+
+#[derive(Debug)]
+pub struct BridgeCommandSchema {
+    /// Method name of the command.
+    pub name: &'static str,
+    pub args: &'static [BridgeField],
+    /// Dart type of the result, `void` for the commands without any.
+    pub result: &'static str,
+}
+
+#[derive(Debug)]
+pub struct BridgeSchema {
+    /// Name of the Rust struct, also used for the Dart classes.
+    pub name: &'static str,
+    pub channel: &'static str,
+    pub fields: &'static [BridgeField],
+    pub commands: &'static [BridgeCommandSchema],
+    /// Whether only the changed top-level fields are sent on updates,
+    /// rather than the whole state.
+    pub diff: bool,
+}
+
+/// Implemented by `#[derive(ChannelBridge)]` on structs.
+pub trait BridgeState: Send + 'static {
+    type Command: BridgeCommand;
+
+    const SCHEMA: BridgeSchema;
+
+    /// The values of the fields, in the order of `SCHEMA.fields`.
+    fn to_fields(&self) -> Vec<Value>;
+}
+
+/// Implemented by `#[derive(ChannelBridge)]` on enums.
+pub trait BridgeCommand: Sized + Send + 'static {
+    const COMMANDS: &'static [BridgeCommandSchema];
+
+    /// Decode the call of |method|, or `None` if it isn't a command.
+    fn decode(method: &str, args: &Value) -> Option<Result<Self, BridgeError>>;
+}
+
+/// Commands of the states which don't take any.
+#[derive(Debug)]
+pub enum NoCommand {}
+
+impl BridgeCommand for NoCommand {
+    const COMMANDS: &'static [BridgeCommandSchema] = &[];
+
+    fn decode(_method: &str, _args: &Value) -> Option<Result<Self, BridgeError>> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BridgeError {
+    /// The arguments don't match the fields of the command.
+    InvalidArgs(String),
+
+    /// The handler failed to run the command.
+    Failed(String),
+}
+
+impl BridgeError {
+    /// Error code of the method channel error response.
+    pub fn code(&self) -> &'static str {
+        match self {
+            BridgeError::InvalidArgs(_) => "invalid-args",
+            BridgeError::Failed(_) => "failed",
+        }
+    }
+}
+
+impl fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BridgeError::InvalidArgs(reason) => write!(f, "Invalid arguments: {}", reason),
+            BridgeError::Failed(reason) => write!(f, "Command failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+/// Value of the |name| field of a state, used by the derived
+/// [`BridgeState::to_fields`]. Fields which fail to serialize are sent as
+/// `null`.
+pub fn encode_field<T: Serialize>(name: &str, value: &T) -> Value {
+    to_value(value).unwrap_or_else(|err| {
+        error!(
+            "Failed to encode the {} field of a bridged state: {}",
+            name, err
+        );
+        Value::Null
+    })
+}
+
+/// Value of the |name| argument of a command, used by the derived
+/// [`BridgeCommand::decode`]. Missing arguments are decoded from `null`, so
+/// that they are only accepted for the optional fields.
+pub fn decode_arg<T: DeserializeOwned>(args: &Value, name: &str) -> Result<T, BridgeError> {
+    let arg = match args {
+        Value::Map(args) => args.get(name).unwrap_or(&Value::Null),
+        _ => &Value::Null,
+    };
+    from_value_owned(arg).map_err(|err| BridgeError::InvalidArgs(format!("{}: {}", name, err)))
+}
+
+/// Result of a command, sent back to Dart once the future completes.
+pub type BridgeFuture = Pin<Box<dyn Future<Output = Result<Value, BridgeError>> + Send>>;
+
+pub trait BridgeHandler<S: BridgeState>: Send + Sync + 'static {
+    /// Run |command|, which may update the state through |bridge|.
+    fn handle(&self, command: S::Command, bridge: Bridge<S>) -> BridgeFuture;
+}
+
+impl<S, F, Fut> BridgeHandler<S> for F
+where
+    S: BridgeState,
+    F: Fn(S::Command, Bridge<S>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Value, BridgeError>> + Send + 'static,
+{
+    fn handle(&self, command: S::Command, bridge: Bridge<S>) -> BridgeFuture {
+        Box::pin(self(command, bridge))
+    }
+}
+
+struct NoCommandHandler;
+
+impl<S: BridgeState<Command = NoCommand>> BridgeHandler<S> for NoCommandHandler {
+    fn handle(&self, command: NoCommand, _bridge: Bridge<S>) -> BridgeFuture {
+        match command {}
+    }
+}
+
+/// Handle of a bridged state, shared by the engines it is registered with
+/// (see [`BridgePlugin`]).
+pub struct Bridge<S: BridgeState> {
+    inner: Arc<Mutex<BridgeInner<S>>>,
+}
+
+struct BridgeInner<S> {
+    state: S,
+    /// The fields last sent to Dart.
+    fields: Vec<Value>,
+    version: u64,
+    sinks: Vec<EventSink>,
+}
+
+impl<S: BridgeState> Clone for Bridge<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<S: BridgeState> Bridge<S> {
+    pub fn new(state: S) -> Self {
+        let fields = state.to_fields();
+        Self {
+            inner: Arc::new(Mutex::new(BridgeInner {
+                state,
+                fields,
+                version: 0,
+                sinks: Vec::new(),
+            })),
+        }
+    }
+
+    /// Read the state.
+    pub fn read<R>(&self, f: impl FnOnce(&S) -> R) -> R {
+        f(&self.inner.lock().state)
+    }
+
+    /// Update the state, and send the changes to Dart. The state is locked
+    /// while |f| runs, so |f| must not use the bridge.
+    pub fn update<R>(&self, f: impl FnOnce(&mut S) -> R) -> R {
+        let mut inner = self.inner.lock();
+        let result = f(&mut inner.state);
+
+        let fields = inner.state.to_fields();
+        let Some(changes) = changes(&S::SCHEMA, &inner.fields, &fields) else {
+            return result;
+        };
+
+        inner.fields = fields;
+        inner.version += 1;
+        let event = state_event(inner.version, "changes", changes);
+        // The sinks post to the platform thread in order, so sending while
+        // locked keeps the versions ordered.
+        inner.sinks.retain(EventSink::is_open);
+        for sink in &inner.sinks {
+            sink.success(&event);
+        }
+        result
+    }
+
+    /// `{version, state}`, as returned by `getState`.
+    fn snapshot(&self) -> Value {
+        let inner = self.inner.lock();
+        let state = S::SCHEMA
+            .fields
+            .iter()
+            .zip(&inner.fields)
+            .map(|(field, value)| (field.name.to_string(), value.clone()))
+            .collect();
+        state_event(inner.version, "state", state)
+    }
+
+    fn add_sink(&self, sink: EventSink) {
+        let mut inner = self.inner.lock();
+        inner.sinks.retain(EventSink::is_open);
+        inner.sinks.push(sink);
+    }
+}
+
+/// The fields of |fields| which differ from |previous| (or all of them when
+/// the schema isn't diffed), `None` without any change.
+fn changes(
+    schema: &BridgeSchema,
+    previous: &[Value],
+    fields: &[Value],
+) -> Option<HashMap<String, Value>> {
+    if previous == fields {
+        return None;
+    }
+
+    let changes = schema
+        .fields
+        .iter()
+        .zip(fields)
+        .enumerate()
+        .filter(|(index, _)| !schema.diff || previous.get(*index) != fields.get(*index))
+        .map(|(_, (field, value))| (field.name.to_string(), value.clone()))
+        .collect();
+    Some(changes)
+}
+
+fn state_event(version: u64, key: &str, fields: HashMap<String, Value>) -> Value {
+    Value::Map(HashMap::from([
+        ("version".to_string(), Value::I64(version as i64)),
+        (key.to_string(), Value::Map(fields)),
+    ]))
+}
+
+/// Merges the changes of a buffered event with the newer ones, when Dart
+/// doesn't keep up with the updates.
+fn merge_changes(buffered: &mut Value, event: Value) {
+    let (Value::Map(buffered), Value::Map(mut event)) = (buffered, event) else {
+        return;
+    };
+
+    if let Some(version) = event.remove("version") {
+        buffered.insert("version".to_string(), version);
+    }
+
+    match (buffered.get_mut("changes"), event.remove("changes")) {
+        (Some(Value::Map(buffered)), Some(Value::Map(changes))) => buffered.extend(changes),
+        (_, Some(changes)) => {
+            buffered.insert("changes".to_string(), changes);
+        }
+        _ => {}
+    }
+}
+
+/// Plugin of a bridged state, registered with each engine sharing it.
+pub struct BridgePlugin<S: BridgeState> {
+    bridge: Bridge<S>,
+    handler: Arc<dyn BridgeHandler<S>>,
+}
+
+impl<S: BridgeState> BridgePlugin<S> {
+    pub fn new<H: BridgeHandler<S>>(bridge: Bridge<S>, handler: H) -> Self {
+        Self {
+            bridge,
+            handler: Arc::new(handler),
+        }
+    }
+}
+
+impl<S: BridgeState<Command = NoCommand>> BridgePlugin<S> {
+    pub fn without_commands(bridge: Bridge<S>) -> Self {
+        Self::new(bridge, NoCommandHandler)
+    }
+}
+
+impl<S: BridgeState> Plugin for BridgePlugin<S> {
+    fn plugin_name() -> &'static str {
+        S::SCHEMA.channel
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        engine.register_channel(MethodChannel::new(
+            S::SCHEMA.channel,
+            Handler {
+                bridge: self.bridge.clone(),
+                handler: self.handler.clone(),
+                async_runtime: context.async_runtime().cloned(),
+            },
+            &JSON_CODEC,
+        ));
+
+        let flow = FlowControl::default().with_drop_policy(DropPolicy::coalesce(merge_changes));
+        engine.register_channel(
+            EventChannel::new(
+                format!("{}{}", S::SCHEMA.channel, STATE_CHANNEL_SUFFIX),
+                StateStreamHandler {
+                    bridge: self.bridge.clone(),
+                },
+                &JSON_CODEC,
+            )
+            .with_flow_control(flow),
+        );
+    }
+}
+
+struct Handler<S: BridgeState> {
+    bridge: Bridge<S>,
+    handler: Arc<dyn BridgeHandler<S>>,
+    async_runtime: Option<AsyncRuntime>,
+}
+
+impl<S: BridgeState> MethodCallHandler for Handler<S> {
+    fn on_method_call(&mut self, call: MethodCall) {
+        if call.method() == GET_STATE_METHOD {
+            return call.success(self.bridge.snapshot());
+        }
+
+        let command = match S::Command::decode(call.method(), call.raw_args()) {
+            Some(Ok(command)) => command,
+            Some(Err(err)) => return respond_error(call, err),
+            None => return call.not_implemented(),
+        };
+
+        let future = self.handler.handle(command, self.bridge.clone());
+        let run = async move {
+            match future.await {
+                Ok(result) => call.success(result),
+                Err(err) => respond_error(call, err),
+            }
+        };
+        match &self.async_runtime {
+            Some(async_runtime) => async_runtime.spawn_detached(run),
+            None => {
+                thread::spawn(move || futures_lite::future::block_on(run));
+            }
+        }
+    }
+}
+
+fn respond_error(call: MethodCall, err: BridgeError) {
+    warn!("[plugin: bridge] {}: {}", call.method(), err);
+    call.error(err.code(), err.to_string(), Value::Null)
+}
+
+struct StateStreamHandler<S: BridgeState> {
+    bridge: Bridge<S>,
+}
+
+impl<S: BridgeState> StreamHandler for StateStreamHandler<S> {
+    fn on_listen(&mut self, _args: Value, sink: EventSink) -> Result<(), StreamError> {
+        self.bridge.add_sink(sink);
+        Ok(())
+    }
+
+    // The sink is closed along with the stream.
+    fn on_cancel(&mut self) {}
+}
+
+/// Generate the Dart side of the |S| bridge: a data class named after the
+/// struct, and a `FlutterRsBridge` client with a method per command.
+pub fn generate_dart<S: BridgeState>() -> String {
+    let schema = &S::SCHEMA;
+    let mut dart = String::new();
+    writeln!(
+        dart,
+        "// Generated from the `{}` bridge, do not edit.",
+        schema.name
+    )
+    .unwrap();
+    writeln!(
+        dart,
+        "// Regenerate with `flutter_plugins::bridge::generate_dart`."
+    )
+    .unwrap();
+    writeln!(dart).unwrap();
+    writeln!(dart, "import 'package:flutter_rs/flutter_rs.dart';").unwrap();
+
+    writeln!(dart).unwrap();
+    writeln!(dart, "/// State of the `{}` bridge.", schema.channel).unwrap();
+    writeln!(dart, "class {} {{", schema.name).unwrap();
+    writeln!(dart, "  const {}({{", schema.name).unwrap();
+    for field in schema.fields {
+        writeln!(dart, "    required this.{},", field.name).unwrap();
+    }
+    writeln!(dart, "  }});").unwrap();
+    writeln!(dart).unwrap();
+    writeln!(
+        dart,
+        "  factory {}.fromJson(Map<String, Object?> json) {{",
+        schema.name
+    )
+    .unwrap();
+    writeln!(dart, "    return {}(", schema.name).unwrap();
+    for field in schema.fields {
+        let value = dart_decode(&format!("json['{}']", field.name), field.dart_type);
+        writeln!(dart, "      {}: {},", field.name, value).unwrap();
+    }
+    writeln!(dart, "    );").unwrap();
+    writeln!(dart, "  }}").unwrap();
+    writeln!(dart).unwrap();
+    for field in schema.fields {
+        writeln!(dart, "  final {} {};", field.dart_type, field.name).unwrap();
+    }
+    writeln!(dart, "}}").unwrap();
+
+    writeln!(dart).unwrap();
+    writeln!(
+        dart,
+        "/// Client of the `{}` bridge, see [FlutterRsBridge].",
+        schema.channel
+    )
+    .unwrap();
+    writeln!(
+        dart,
+        "class {0}Bridge extends FlutterRsBridge<{0}> {{",
+        schema.name
+    )
+    .unwrap();
+    writeln!(
+        dart,
+        "  {}Bridge() : super('{}');",
+        schema.name, schema.channel
+    )
+    .unwrap();
+    writeln!(dart).unwrap();
+    writeln!(dart, "  @override").unwrap();
+    writeln!(
+        dart,
+        "  {0} decode(Map<String, Object?> fields) => {0}.fromJson(fields);",
+        schema.name
+    )
+    .unwrap();
+    for command in schema.commands {
+        writeln!(dart).unwrap();
+        write_dart_command(&mut dart, command);
+    }
+    writeln!(dart, "}}").unwrap();
+    dart
+}
+
+fn write_dart_command(dart: &mut String, command: &BridgeCommandSchema) {
+    let params: Vec<_> = command
+        .args
+        .iter()
+        .map(|arg| {
+            // Named parameters are optional unless they are required.
+            if arg.dart_type.ends_with('?') {
+                format!("{} {}", arg.dart_type, arg.name)
+            } else {
+                format!("required {} {}", arg.dart_type, arg.name)
+            }
+        })
+        .collect();
+    let params = if params.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", params.join(", "))
+    };
+    let args: Vec<_> = command
+        .args
+        .iter()
+        .map(|arg| format!("'{0}': {0}", arg.name))
+        .collect();
+
+    writeln!(dart, "  /// Runs the `{}` command.", command.name).unwrap();
+    writeln!(
+        dart,
+        "  Future<{}> {}({}) async {{",
+        command.result, command.name, params
+    )
+    .unwrap();
+    let invoke = format!("await invoke('{}', {{{}}})", command.name, args.join(", "));
+    match command.result {
+        "void" => writeln!(dart, "    {};", invoke).unwrap(),
+        result => writeln!(
+            dart,
+            "    return {};",
+            dart_decode(&format!("({})", invoke), result)
+        )
+        .unwrap(),
+    }
+    writeln!(dart, "  }}").unwrap();
+}
+
+/// Dart expression decoding the JSON |value| to |dart_type|. The types
+/// other than the JSON ones (and their lists and maps) are expected to
+/// have a `fromJson` factory.
+fn dart_decode(value: &str, dart_type: &str) -> String {
+    if let Some(inner) = dart_type.strip_suffix('?') {
+        return match inner {
+            "bool" | "int" | "String" | "Object" => format!("{} as {}", value, dart_type),
+            "double" => format!("({} as num?)?.toDouble()", value),
+            _ => format!("{0} == null ? null : {1}", value, dart_decode(value, inner)),
+        };
+    }
+
+    if let Some(item) = generic_arg(dart_type, "List<") {
+        let item = dart_decode("item", item);
+        return format!(
+            "({} as List<Object?>).map((item) => {}).toList()",
+            value, item
+        );
+    }
+
+    if let Some(entry) = generic_arg(dart_type, "Map<String, ") {
+        let entry = dart_decode("entry", entry);
+        return format!(
+            "({} as Map<Object?, Object?>).map((key, entry) => MapEntry(key as String, {}))",
+            value, entry
+        );
+    }
+
+    match dart_type {
+        "bool" | "int" | "String" => format!("{} as {}", value, dart_type),
+        "double" => format!("({} as num).toDouble()", value),
+        "Object" => format!("{}!", value),
+        _ => format!(
+            "{}.fromJson(({} as Map<Object?, Object?>).cast<String, Object?>())",
+            dart_type, value
+        ),
+    }
+}
+
+/// `int` for `List<int>` with the `List<` |prefix|.
+fn generic_arg<'a>(dart_type: &'a str, prefix: &str) -> Option<&'a str> {
+    dart_type.strip_prefix(prefix)?.strip_suffix('>')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use flutter_engine::codec::Value;
+
+    use super::{
+        changes, dart_decode, decode_arg, merge_changes, BridgeError, BridgeField, BridgeSchema,
+    };
+
+    const FIELDS: &[BridgeField] = &[
+        BridgeField {
+            name: "value",
+            dart_type: "int",
+        },
+        BridgeField {
+            name: "label",
+            dart_type: "String",
+        },
+    ];
+
+    fn schema(diff: bool) -> BridgeSchema {
+        BridgeSchema {
+            name: "Counter",
+            channel: "test/counter",
+            fields: FIELDS,
+            commands: &[],
+            diff,
+        }
+    }
+
+    fn map(entries: &[(&str, Value)]) -> HashMap<String, Value> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn sends_the_changed_fields() {
+        let previous = [Value::I64(1), Value::String("a".into())];
+        let fields = [Value::I64(2), Value::String("a".into())];
+
+        assert_eq!(changes(&schema(true), &previous, &previous), None);
+        assert_eq!(
+            changes(&schema(true), &previous, &fields),
+            Some(map(&[("value", Value::I64(2))]))
+        );
+        assert_eq!(
+            changes(&schema(false), &previous, &fields),
+            Some(map(&[
+                ("value", Value::I64(2)),
+                ("label", Value::String("a".into()))
+            ]))
+        );
+    }
+
+    #[test]
+    fn merges_the_buffered_changes() {
+        let event = |version: i64, changes: &[(&str, Value)]| {
+            Value::Map(map(&[
+                ("version", Value::I64(version)),
+                ("changes", Value::Map(map(changes))),
+            ]))
+        };
+
+        let mut buffered = event(1, &[("value", Value::I64(1)), ("label", Value::Null)]);
+        merge_changes(&mut buffered, event(2, &[("value", Value::I64(2))]));
+        assert_eq!(
+            buffered,
+            event(2, &[("value", Value::I64(2)), ("label", Value::Null)])
+        );
+    }
+
+    #[test]
+    fn decodes_the_arguments() {
+        let args = Value::Map(map(&[("step", Value::I64(3))]));
+        assert_eq!(decode_arg::<i64>(&args, "step"), Ok(3));
+        assert_eq!(decode_arg::<Option<String>>(&args, "label"), Ok(None));
+        assert!(matches!(
+            decode_arg::<String>(&args, "label"),
+            Err(BridgeError::InvalidArgs(_))
+        ));
+        assert!(decode_arg::<i64>(&Value::Null, "step").is_err());
+    }
+
+    #[test]
+    fn decodes_the_dart_types() {
+        assert_eq!(dart_decode("v", "int"), "v as int");
+        assert_eq!(dart_decode("v", "double?"), "(v as num?)?.toDouble()");
+        assert_eq!(
+            dart_decode("v", "List<double>"),
+            "(v as List<Object?>).map((item) => (item as num).toDouble()).toList()"
+        );
+        assert_eq!(
+            dart_decode("v", "Map<String, int>?"),
+            "v == null ? null : (v as Map<Object?, Object?>)\
+             .map((key, entry) => MapEntry(key as String, entry as int))"
+        );
+        assert_eq!(
+            dart_decode("v", "Settings"),
+            "Settings.fromJson((v as Map<Object?, Object?>).cast<String, Object?>())"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Color characteristics of the displays, reported to the apps through the
+//! window channel (see `getColorInfo`).
+//!
+//! The content is always rendered in sRGB. On wide gamut displays, it looks
+//! oversaturated unless the compositor converts it to the color space of the
+//! display, so the gamut is only a hint: apps may tell the user that colors
+//! are approximated, but they can't render outside of sRGB.
+use serde::Serialize;
+
+/// Gamuts at least this much larger than sRGB (in the xy chromaticity
+/// diagram) are considered wide. Panels sold as sRGB often deviate from it by
+/// a few percent.
+const WIDE_GAMUT_AREA_RATIO: f64 = 1.15;
+
+/// CIE 1931 xy chromaticity coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chromaticity {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Chromaticity {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    fn distance_squared(&self, other: &Chromaticity) -> f64 {
+        (self.x - other.x).powi(2) + (self.y - other.y).powi(2)
+    }
+}
+
+/// Chromaticities of the red, green and blue primaries of a color space or
+/// of a display. The white point doesn't affect the gamut, and is left out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Primaries {
+    pub red: Chromaticity,
+    pub green: Chromaticity,
+    pub blue: Chromaticity,
+}
+
+impl Primaries {
+    /// Also the primaries of BT.709.
+    pub const SRGB: Self = Self::new((0.64, 0.33), (0.30, 0.60), (0.15, 0.06));
+    /// Also the primaries of DCI-P3, which only differs by its white point.
+    pub const DISPLAY_P3: Self = Self::new((0.68, 0.32), (0.265, 0.69), (0.15, 0.06));
+    pub const ADOBE_RGB: Self = Self::new((0.64, 0.33), (0.21, 0.71), (0.15, 0.06));
+    pub const BT2020: Self = Self::new((0.708, 0.292), (0.17, 0.797), (0.131, 0.046));
+
+    pub const fn new(red: (f64, f64), green: (f64, f64), blue: (f64, f64)) -> Self {
+        Self {
+            red: Chromaticity::new(red.0, red.1),
+            green: Chromaticity::new(green.0, green.1),
+            blue: Chromaticity::new(blue.0, blue.1),
+        }
+    }
+
+    /// Area of the triangle of the primaries in the xy chromaticity diagram.
+    fn area(&self) -> f64 {
+        let (red, green, blue) = (&self.red, &self.green, &self.blue);
+        let cross = (green.x - red.x) * (blue.y - red.y) - (blue.x - red.x) * (green.y - red.y);
+        cross.abs() / 2.0
+    }
+
+    fn distance_squared(&self, other: &Primaries) -> f64 {
+        self.red.distance_squared(&other.red)
+            + self.green.distance_squared(&other.green)
+            + self.blue.distance_squared(&other.blue)
+    }
+}
+
+/// Well-known color space closest to the gamut of a display.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ColorGamut {
+    /// The windowing system doesn't describe the displays (or the display
+    /// doesn't describe itself, e.g.: without EDID).
+    #[default]
+    Unknown,
+    Srgb,
+    DisplayP3,
+    AdobeRgb,
+    Bt2020,
+}
+
+impl ColorGamut {
+    const WIDE: [ColorGamut; 3] = [Self::DisplayP3, Self::AdobeRgb, Self::Bt2020];
+
+    fn primaries(self) -> Option<Primaries> {
+        match self {
+            Self::Unknown => None,
+            Self::Srgb => Some(Primaries::SRGB),
+            Self::DisplayP3 => Some(Primaries::DISPLAY_P3),
+            Self::AdobeRgb => Some(Primaries::ADOBE_RGB),
+            Self::Bt2020 => Some(Primaries::BT2020),
+        }
+    }
+
+    fn area(self) -> f64 {
+        self.primaries().map_or(0.0, |primaries| primaries.area())
+    }
+}
+
+/// Color characteristics of a window, i.e.: of the display(s) it is shown
+/// on. Reported through `getColorInfo` and `onColorInfoChanged`.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorInfo {
+    pub is_wide_gamut: bool,
+    pub approx_gamut: ColorGamut,
+}
+
+impl ColorInfo {
+    /// Classify a display from its |primaries| (e.g.: from its EDID, or as
+    /// described by the compositor).
+    pub fn from_primaries(primaries: &Primaries) -> Self {
+        let ratio = primaries.area() / Primaries::SRGB.area();
+        if !ratio.is_finite() || ratio <= 0.0 {
+            return Self::default();
+        }
+
+        if ratio < WIDE_GAMUT_AREA_RATIO {
+            return Self {
+                is_wide_gamut: false,
+                approx_gamut: ColorGamut::Srgb,
+            };
+        }
+
+        let distance = |gamut: &ColorGamut| {
+            gamut
+                .primaries()
+                .map_or(f64::MAX, |gamut| gamut.distance_squared(primaries))
+        };
+        let approx_gamut = ColorGamut::WIDE
+            .into_iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or(ColorGamut::Unknown);
+
+        Self {
+            is_wide_gamut: true,
+            approx_gamut,
+        }
+    }
+
+    /// Info of a window shown on displays of the given |infos|, i.e.: the
+    /// widest of them, as that's where its colors are stretched the most.
+    pub fn widest(infos: impl IntoIterator<Item = ColorInfo>) -> Self {
+        infos
+            .into_iter()
+            .max_by(|a, b| a.approx_gamut.area().total_cmp(&b.approx_gamut.area()))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorGamut, ColorInfo, Primaries};
+
+    #[test]
+    fn classifies_the_well_known_gamuts() {
+        let srgb = ColorInfo::from_primaries(&Primaries::SRGB);
+        assert!(!srgb.is_wide_gamut);
+        assert_eq!(srgb.approx_gamut, ColorGamut::Srgb);
+
+        for (primaries, gamut) in [
+            (Primaries::DISPLAY_P3, ColorGamut::DisplayP3),
+            (Primaries::ADOBE_RGB, ColorGamut::AdobeRgb),
+            (Primaries::BT2020, ColorGamut::Bt2020),
+        ] {
+            let info = ColorInfo::from_primaries(&primaries);
+            assert!(info.is_wide_gamut);
+            assert_eq!(info.approx_gamut, gamut);
+        }
+    }
+
+    #[test]
+    fn classifies_measured_primaries() {
+        // A laptop panel close to sRGB, as quantized in its EDID.
+        let panel = Primaries::new((0.6396, 0.3447), (0.3213, 0.5898), (0.1533, 0.0595));
+        assert_eq!(
+            ColorInfo::from_primaries(&panel).approx_gamut,
+            ColorGamut::Srgb
+        );
+
+        // A monitor covering most of DCI-P3.
+        let monitor = Primaries::new((0.6806, 0.3184), (0.2724, 0.6718), (0.1503, 0.0595));
+        let info = ColorInfo::from_primaries(&monitor);
+        assert!(info.is_wide_gamut);
+        assert_eq!(info.approx_gamut, ColorGamut::DisplayP3);
+
+        let empty = Primaries::new((0.0, 0.0), (0.0, 0.0), (0.0, 0.0));
+        assert_eq!(ColorInfo::from_primaries(&empty), ColorInfo::default());
+    }
+
+    #[test]
+    fn picks_the_widest_display() {
+        let srgb = ColorInfo::from_primaries(&Primaries::SRGB);
+        let p3 = ColorInfo::from_primaries(&Primaries::DISPLAY_P3);
+        assert_eq!(ColorInfo::widest([srgb, p3, ColorInfo::default()]), p3);
+        assert_eq!(ColorInfo::widest([ColorInfo::default(), srgb]), srgb);
+        assert_eq!(ColorInfo::widest([]), ColorInfo::default());
+    }
+}
@@ -0,0 +1,389 @@
+//! Contract of the custom flutter-rs/* channels, shared with the `flutter_rs`
+//! Dart package (in `dart/flutter_rs`). The Dart constants are generated from
+//! it (see [`generate_dart`]), and the tests make sure that the plugins, this
+//! contract and the generated constants don't drift apart.
+//!
+//! The shapes of the arguments and results use the Dart types, with maps
+//! written as `{key: Type}`. All the channels use the JSON codec.
+//!
+//! Regenerate the Dart constants with:
+//! `UPDATE_DART_CONTRACT=1 cargo test -p flutter-plugins contract`
+use std::fmt::Write;
+
+use crate::{
+    appearance, assets, diagnostics, emulated_input, fonts, keyboard_repeat, logging, ready,
+    screensaver, text_toolbar, tray, window,
+};
+
+/// Path of the generated constants, relative to the workspace.
+pub const DART_CONSTANTS_PATH: &str = "dart/flutter_rs/lib/src/channels.g.dart";
+
+#[derive(Debug)]
+pub struct ChannelContract {
+    pub name: &'static str,
+    /// Methods invoked by Dart.
+    pub methods: &'static [MethodContract],
+    /// Methods invoked by the embedder.
+    pub events: &'static [MethodContract],
+}
+
+#[derive(Debug)]
+pub struct MethodContract {
+    pub name: &'static str,
+    /// `null` when the method takes no arguments.
+    pub args: &'static str,
+    /// `void` for the events, and for the methods without any result.
+    pub result: &'static str,
+}
+
+const fn method(name: &'static str, args: &'static str, result: &'static str) -> MethodContract {
+    MethodContract { name, args, result }
+}
+
+const fn event(name: &'static str, args: &'static str) -> MethodContract {
+    method(name, args, "void")
+}
+
+const EMULATED_INPUT_STATUS: &str =
+    "{isAvailable: bool, permission: String, pointer: bool, keyboard: bool, canRestore: bool}";
+const WINDOW_CAPABILITIES: &str = "{transparency: bool, alwaysOnTop: bool, skipTaskbar: bool, \
+     minMaxSize: bool, maximize: bool, fullscreen: bool, position: bool}";
+const COLOR_INFO: &str = "{isWideGamut: bool, approxGamut: String}";
+const TRAY_ICON: &str = "{title: String, iconName: String?, iconPath: String?, tooltip: String?, \
+     status: String, menu: List<{id: int, label: String, isEnabled: bool, isSeparator: bool, \
+     isChecked: bool?, children: List<Map>}>}";
+
+pub const CHANNELS: &[ChannelContract] = &[
+    ChannelContract {
+        name: appearance::CHANNEL_NAME,
+        methods: &[method("getAccentColor", "null", "int?")],
+        events: &[event("onAccentColorChanged", "int?")],
+    },
+    ChannelContract {
+        name: assets::CHANNEL_NAME,
+        methods: &[method("getScales", "null", "List<double>")],
+        events: &[event("scalesChanged", "List<double>")],
+    },
+    ChannelContract {
+        name: diagnostics::CHANNEL_NAME,
+        methods: &[
+            method("getChannelStats", "null", "List<Map<String, Object?>>"),
+            method("getPointerStats", "null", "Map<String, Object?>"),
+            method("getAccessibilityFeatures", "null", "Object?"),
+            method(
+                "getScrollSettings",
+                "null",
+                "{mouseNaturalScroll: bool, touchpadNaturalScroll: bool, factor: double}?",
+            ),
+            method(
+                "getThreadPolicies",
+                "null",
+                "List<{thread: String?, applied: List<String>, failed: List<String>}>",
+            ),
+            method("setChannelTracer", "String?", "void"),
+        ],
+        events: &[],
+    },
+    ChannelContract {
+        name: emulated_input::CHANNEL_NAME,
+        methods: &[
+            method("getStatus", "null", EMULATED_INPUT_STATUS),
+            method("requestPermission", "null", EMULATED_INPUT_STATUS),
+            method("movePointer", "{dx: double, dy: double}", "void"),
+            method("warpPointer", "{x: double, y: double}", "void"),
+            method("pointerButton", "{code: int, isPressed: bool}", "void"),
+            method("scroll", "{dx: double, dy: double}", "void"),
+            method("key", "{code: int, isPressed: bool}", "void"),
+        ],
+        events: &[],
+    },
+    ChannelContract {
+        name: fonts::CHANNEL_NAME,
+        methods: &[
+            method("getFontFamilies", "null", "List<String>"),
+            method("loadFontFamily", "String", "List<String>"),
+        ],
+        events: &[event("onFontFamilyAdded", "String")],
+    },
+    ChannelContract {
+        name: keyboard_repeat::CHANNEL_NAME,
+        methods: &[method("getRepeatInfo", "null", "{enabled: bool, rate: int, delay: int}?")],
+        events: &[event("onRepeatInfoChanged", "{enabled: bool, rate: int, delay: int}")],
+    },
+    // The plugin is behind the `latency-probe` feature.
+    ChannelContract {
+        name: "flutter-rs/latency",
+        methods: &[method("echo", "int", "void")],
+        events: &[],
+    },
+    ChannelContract {
+        name: logging::CHANNEL_NAME,
+        methods: &[
+            method("setLogFilter", "String", "void"),
+            method("dumpState", "null", "Object?"),
+        ],
+        events: &[],
+    },
+    ChannelContract {
+        name: ready::CHANNEL_NAME,
+        methods: &[
+            method("extend", "null", "bool"),
+            method("ready", "null", "void"),
+        ],
+        events: &[],
+    },
+    ChannelContract {
+        name: screensaver::CHANNEL_NAME,
+        methods: &[
+            method("setInhibited", "bool", "void"),
+            method("isInhibited", "null", "bool"),
+        ],
+        events: &[],
+    },
+    ChannelContract {
+        name: text_toolbar::CHANNEL_NAME,
+        methods: &[
+            method(
+                "show",
+                "{rect: {x: double, y: double, width: double, height: double}?, anchor: int?, \
+                 placement: {anchor: String, gravity: String, constraintAdjustment: List<String>, \
+                 offset: {x: double, y: double}}?, actions: List<String>}",
+                "void",
+            ),
+            method("hide", "null", "void"),
+        ],
+        events: &[event("onAction", "String"), event("onDismissed", "null")],
+    },
+    ChannelContract {
+        name: tray::CHANNEL_NAME,
+        methods: &[
+            method("isAvailable", "null", "bool"),
+            method("show", TRAY_ICON, "void"),
+            method("hide", "null", "void"),
+        ],
+        events: &[
+            event("onActivate", "{x: int, y: int}"),
+            event("onSecondaryActivate", "{x: int, y: int}"),
+            event("onScroll", "{delta: int, orientation: String}"),
+            event("onMenuItemClicked", "int"),
+        ],
+    },
+    ChannelContract {
+        name: window::CHANNEL_NAME,
+        methods: &[
+            method("maximize", "null", "void"),
+            method("iconify", "null", "void"),
+            method("restore", "null", "void"),
+            method("isMaximized", "null", "bool"),
+            method("isIconified", "null", "bool"),
+            method("isVisible", "null", "bool"),
+            method("isFullscreen", "null", "bool"),
+            method("show", "null", "void"),
+            method("hide", "null", "void"),
+            method("close", "null", "void"),
+            method("set_pos", "{x: double, y: double}", "void"),
+            method("get_pos", "null", "{x: double, y: double}"),
+            method("start_drag", "null", "void"),
+            method("end_drag", "null", "void"),
+            method("getRecommendedMaxSize", "null", "{width: double, height: double}?"),
+            method("setExclusiveZone", "int", "void"),
+            method("setMargin", "{top: int, right: int, bottom: int, left: int}", "void"),
+            method("getZoom", "null", "double"),
+            method("setZoom", "double", "void"),
+            method("isTransparencyReduced", "null", "bool"),
+            method("setUrgent", "bool", "void"),
+            method("getCapabilities", "null", WINDOW_CAPABILITIES),
+            method("getColorInfo", "null", COLOR_INFO),
+            method("getVisibility", "null", "String"),
+            method("setAlwaysOnTop", "bool", "void"),
+            method("setMinSize", "{width: double, height: double}?", "void"),
+            method("setMaxSize", "{width: double, height: double}?", "void"),
+            method("setFullscreen", "bool", "void"),
+            method("getWindowId", "null", "int"),
+            method("raiseAbove", "int", "bool"),
+            method("lowerBelow", "int", "bool"),
+            method(
+                "widgetRectToAnchor",
+                "{viewId: int, rect: {x: double, y: double, width: double, height: double}}",
+                "int",
+            ),
+        ],
+        events: &[
+            event("onRecommendedMaxSizeChanged", "{width: double, height: double}?"),
+            event(
+                "onStateChanged",
+                "{maximized: bool, fullscreen: bool, tiled: bool, initiator: String}",
+            ),
+            event("onZoomChanged", "double"),
+            event("onTransparencyReducedChanged", "bool"),
+            event("onColorInfoChanged", COLOR_INFO),
+            event("onVisibilityChanged", "String"),
+            event("onAnchorsInvalidated", "{viewId: int, tokens: List<int>}"),
+            event("onStackingChanged", "{order: List<int>, outcome: String}"),
+        ],
+    },
+];
+
+/// Generate the Dart constants of the channels and their methods, with one
+/// class per channel (e.g.: `WindowChannel.setZoom`).
+pub fn generate_dart() -> String {
+    let mut dart = String::new();
+    writeln!(dart, "// Generated from flutter-plugins/src/contract.rs, do not edit.").unwrap();
+    writeln!(dart, "// Regenerate with:").unwrap();
+    writeln!(dart, "// `UPDATE_DART_CONTRACT=1 cargo test -p flutter-plugins contract`").unwrap();
+
+    for channel in CHANNELS {
+        writeln!(dart).unwrap();
+        writeln!(dart, "/// Contract of the `{}` channel.", channel.name).unwrap();
+        writeln!(dart, "abstract final class {} {{", dart_class_name(channel.name)).unwrap();
+        writeln!(dart, "  static const String name = '{}';", channel.name).unwrap();
+
+        for method in channel.methods {
+            writeln!(dart).unwrap();
+            writeln!(dart, "  /// Arguments: `{}`.", method.args).unwrap();
+            writeln!(dart, "  /// Result: `{}`.", method.result).unwrap();
+            write_dart_constant(&mut dart, method.name);
+        }
+
+        for event in channel.events {
+            writeln!(dart).unwrap();
+            writeln!(dart, "  /// Invoked by the embedder with `{}`.", event.args).unwrap();
+            write_dart_constant(&mut dart, event.name);
+        }
+        writeln!(dart, "}}").unwrap();
+    }
+    dart
+}
+
+fn write_dart_constant(dart: &mut String, name: &str) {
+    writeln!(dart, "  static const String {} = '{}';", dart_identifier(name), name).unwrap();
+}
+
+/// `flutter-rs/emulated_input` -> `EmulatedInputChannel`.
+fn dart_class_name(channel: &str) -> String {
+    let name = channel.rsplit('/').next().unwrap_or(channel);
+    let mut class_name: String = name.split('_').map(capitalize).collect();
+    class_name.push_str("Channel");
+    class_name
+}
+
+/// `set_pos` -> `setPos`.
+fn dart_identifier(method: &str) -> String {
+    let mut words = method.split('_');
+    let first = words.next().unwrap_or_default().to_string();
+    words.fold(first, |identifier, word| identifier + &capitalize(word))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, env, fs, path::Path};
+
+    use super::{dart_class_name, dart_identifier, generate_dart, CHANNELS, DART_CONSTANTS_PATH};
+
+    /// Sources of the plugins handling the channels of the contract.
+    const PLUGIN_SOURCES: &[(&str, &str)] = &[
+        ("flutter-rs/appearance", include_str!("appearance.rs")),
+        ("flutter-rs/assets", include_str!("assets.rs")),
+        ("flutter-rs/diagnostics", include_str!("diagnostics.rs")),
+        ("flutter-rs/emulated_input", include_str!("emulated_input.rs")),
+        ("flutter-rs/fonts", include_str!("fonts.rs")),
+        ("flutter-rs/keyboard_repeat", include_str!("keyboard_repeat.rs")),
+        ("flutter-rs/latency", include_str!("latency.rs")),
+        ("flutter-rs/logging", include_str!("logging.rs")),
+        ("flutter-rs/ready", include_str!("ready.rs")),
+        ("flutter-rs/screensaver", include_str!("screensaver.rs")),
+        ("flutter-rs/text_toolbar", include_str!("text_toolbar.rs")),
+        ("flutter-rs/tray", include_str!("tray.rs")),
+        ("flutter-rs/window", include_str!("window.rs")),
+    ];
+
+    const DART_CONSTANTS: &str = include_str!("../../dart/flutter_rs/lib/src/channels.g.dart");
+    const DART_PUBSPEC: &str = include_str!("../../dart/flutter_rs/pubspec.yaml");
+
+    /// The method names matched by the plugin (i.e.: `"name" => ...` arms).
+    fn handled_methods(source: &str) -> BTreeSet<&str> {
+        source
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with('"'))
+            .filter_map(|line| line.split_once("=>"))
+            .flat_map(|(patterns, _)| patterns.split('|'))
+            .map(|pattern| pattern.trim().trim_matches('"'))
+            .collect()
+    }
+
+    /// The method names invoked by the plugin.
+    fn invoked_methods(source: &str) -> BTreeSet<&str> {
+        source
+            .split("invoke_method(\"")
+            .skip(1)
+            .filter_map(|rest| rest.split_once('"'))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn plugins_match_contract() {
+        let contract_channels: BTreeSet<_> = CHANNELS.iter().map(|channel| channel.name).collect();
+        let plugin_channels: BTreeSet<_> = PLUGIN_SOURCES.iter().map(|(name, _)| *name).collect();
+        assert_eq!(contract_channels, plugin_channels);
+
+        for (name, source) in PLUGIN_SOURCES {
+            assert!(source.contains(&format!("CHANNEL_NAME: &str = \"{name}\"")));
+
+            let channel = CHANNELS.iter().find(|channel| channel.name == *name).unwrap();
+            let methods: BTreeSet<_> = channel.methods.iter().map(|method| method.name).collect();
+            let events: BTreeSet<_> = channel.events.iter().map(|event| event.name).collect();
+            assert_eq!(methods, handled_methods(source), "methods of {name}");
+            assert_eq!(events, invoked_methods(source), "events of {name}");
+        }
+    }
+
+    #[test]
+    fn dart_constants_are_up_to_date() {
+        let generated = generate_dart();
+        if env::var_os("UPDATE_DART_CONTRACT").is_some() {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(DART_CONSTANTS_PATH);
+            fs::write(path, &generated).unwrap();
+            return;
+        }
+
+        assert!(
+            generated == DART_CONSTANTS,
+            "{DART_CONSTANTS_PATH} is outdated, regenerate it with \
+             `UPDATE_DART_CONTRACT=1 cargo test -p flutter-plugins contract`"
+        );
+    }
+
+    #[test]
+    fn dart_package_is_versioned_in_lockstep() {
+        let version = DART_PUBSPEC
+            .lines()
+            .find_map(|line| line.strip_prefix("version: "))
+            .unwrap();
+        assert_eq!(version.trim(), env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn converts_names_to_dart() {
+        assert_eq!(dart_class_name("flutter-rs/emulated_input"), "EmulatedInputChannel");
+        assert_eq!(dart_class_name("flutter-rs/window"), "WindowChannel");
+        assert_eq!(dart_identifier("set_pos"), "setPos");
+        assert_eq!(dart_identifier("getRecommendedMaxSize"), "getRecommendedMaxSize");
+
+        let identifiers: BTreeSet<_> = CHANNELS
+            .iter()
+            .flat_map(|channel| channel.methods.iter().chain(channel.events))
+            .map(|method| dart_identifier(method.name))
+            .collect();
+        assert!(!identifiers.contains("name"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Plugin exposing the embedder diagnostics (e.g.: for an in-app debug
+//! screen).
+//! It handles flutter-rs/diagnostics type message.
+use std::sync::{Arc, Weak};
+
+use flutter_engine::{
+    channel::{ChannelFilter, MethodCall, MethodCallHandler, MethodChannel},
+    codec::{Value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
+    FlutterEngineWeakRef,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/diagnostics";
+
+/// Scroll settings applied by the embedder to the axis events, after the
+/// application override (see `PointerConfig`).
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollSettings {
+    pub mouse_natural_scroll: bool,
+    pub touchpad_natural_scroll: bool,
+    pub factor: f64,
+}
+
+#[derive(Default)]
+pub struct DiagnosticsPlugin {
+    channel: Weak<MethodChannel>,
+    scroll_settings: Option<Arc<Mutex<ScrollSettings>>>,
+}
+
+impl DiagnosticsPlugin {
+    /// Expose the scroll settings kept up to date by the embedder.
+    pub fn with_scroll_settings(mut self, scroll_settings: Arc<Mutex<ScrollSettings>>) -> Self {
+        self.scroll_settings = Some(scroll_settings);
+        self
+    }
+}
+
+impl Plugin for DiagnosticsPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                engine: engine.downgrade(),
+                scroll_settings: self.scroll_settings.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    engine: FlutterEngineWeakRef,
+    scroll_settings: Option<Arc<Mutex<ScrollSettings>>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        let Some(engine) = self.engine.upgrade() else {
+            return call.error("no-engine", "The engine is gone", Value::Null);
+        };
+
+        match call.method().as_str() {
+            // Counters of the channels traced so far (see
+            // `FlutterEngine::set_channel_tracer`).
+            "getChannelStats" => call.success(engine.channel_stats()),
+            // Counters of the pointer events, including the ones dropped
+            // while the engine was stalled.
+            "getPointerStats" => call.success(engine.pointer_stats()),
+            // Accessibility features last sent to the engine (e.g.: whether
+            // high contrast is requested by the system).
+            "getAccessibilityFeatures" => call.success(engine.accessibility_features()),
+            // What the thread policies changed on the engine threads, and the
+            // settings which couldn't be applied (e.g.: without permission).
+            "getThreadPolicies" => call.success(engine.thread_policy_reports()),
+            // `null` when the embedder doesn't convert the scroll events.
+            "getScrollSettings" => {
+                let scroll_settings = self.scroll_settings.as_ref();
+                call.success(scroll_settings.map(|settings| *settings.lock()))
+            }
+            // Takes a channel filter (e.g.: `flutter/*`), or `null` to disable
+            // tracing.
+            "setChannelTracer" => {
+                let filter = match call.raw_args() {
+                    Value::String(filter) => Some(ChannelFilter::new(filter)),
+                    Value::Null => None,
+                    _ => {
+                        return call.error(
+                            "invalid-args",
+                            "Expected a channel filter or null",
+                            Value::Null,
+                        )
+                    }
+                };
+
+                engine.set_channel_tracer(filter);
+                call.success_empty()
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
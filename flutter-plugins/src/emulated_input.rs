@@ -0,0 +1,392 @@
+//! Plugin to move the pointer and inject input events through the compositor
+//! (e.g.: for dwell clicking, or for edge scrolling in games), once the user
+//! granted the permission. It handles flutter-rs/emulated_input type message.
+//!
+//! The permission is requested asynchronously: the compositor usually shows
+//! a dialog, and `requestPermission` is answered once the user made a choice.
+//! Every method reports the current [`EmulatedInputStatus`] or an error, so
+//! that Dart can tell apart unavailable, denied and failed requests.
+//!
+//! The portal only moves the pointer by relative amounts, so `warpPointer`
+//! moves it from its last known position within the view, and fails while
+//! the pointer is outside of the windows of the app.
+use std::{
+    fmt,
+    sync::{Arc, Weak},
+};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{value::from_value, Value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/emulated_input";
+
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EmulatedInputPermission {
+    /// The permission was not requested yet.
+    #[default]
+    NotDetermined,
+    Granted,
+    /// The user dismissed the request, or the session was closed since.
+    Denied,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EmulatedInputStatus {
+    /// Whether the compositor offers input emulation at all.
+    pub is_available: bool,
+    pub permission: EmulatedInputPermission,
+    /// Whether the user granted the emulation of a pointer.
+    pub pointer: bool,
+    /// Whether the user granted the emulation of a keyboard.
+    pub keyboard: bool,
+    /// Whether a granted permission is remembered across restarts.
+    pub can_restore: bool,
+}
+
+impl EmulatedInputStatus {
+    fn check(&self, event: &EmulatedInputEvent) -> Result<(), EmulatedInputError> {
+        if !self.is_available {
+            return Err(EmulatedInputError::Unavailable);
+        }
+        if self.permission != EmulatedInputPermission::Granted {
+            return Err(EmulatedInputError::PermissionRequired);
+        }
+
+        let is_granted = match event {
+            EmulatedInputEvent::Key { .. } => self.keyboard,
+            _ => self.pointer,
+        };
+        if !is_granted {
+            return Err(EmulatedInputError::DeviceNotGranted);
+        }
+        Ok(())
+    }
+}
+
+/// Input event emulated on behalf of the user. Coordinates are in logical
+/// pixels, the buttons and keys use the Linux evdev codes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmulatedInputEvent {
+    PointerMotion { dx: f64, dy: f64 },
+    PointerButton { button: i32, is_pressed: bool },
+    Scroll { dx: f64, dy: f64 },
+    Key { keycode: i32, is_pressed: bool },
+}
+
+/// Errors are reported to Dart as a `PlatformException` (see
+/// [`EmulatedInputError::code`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmulatedInputError {
+    /// The compositor doesn't offer input emulation.
+    Unavailable,
+
+    /// The permission wasn't granted (yet), see `requestPermission`.
+    PermissionRequired,
+
+    /// The user didn't grant the device needed by the event.
+    DeviceNotGranted,
+
+    /// The position of the pointer is unknown, as it isn't within a window of
+    /// the app (see `warpPointer`).
+    PointerOutside,
+
+    /// The request failed (e.g.: the portal connection was lost).
+    Failed(String),
+}
+
+impl EmulatedInputError {
+    /// Error code of the method channel error response.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EmulatedInputError::Unavailable => "unavailable",
+            EmulatedInputError::PermissionRequired => "permission-required",
+            EmulatedInputError::DeviceNotGranted => "device-not-granted",
+            EmulatedInputError::PointerOutside => "pointer-outside",
+            EmulatedInputError::Failed(_) => "failed",
+        }
+    }
+}
+
+impl fmt::Display for EmulatedInputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulatedInputError::Unavailable => write!(f, "Input emulation is not available"),
+            EmulatedInputError::PermissionRequired => {
+                write!(f, "Input emulation permission was not granted")
+            }
+            EmulatedInputError::DeviceNotGranted => {
+                write!(f, "The device needed by the event was not granted")
+            }
+            EmulatedInputError::PointerOutside => {
+                write!(f, "The pointer is not within a window of the app")
+            }
+            EmulatedInputError::Failed(reason) => write!(f, "Input emulation failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EmulatedInputError {}
+
+/// Called with the status once the permission request completed.
+pub type EmulatedInputPermissionReply =
+    Box<dyn FnOnce(Result<EmulatedInputStatus, EmulatedInputError>) + Send>;
+
+/// Called once the pointer was warped, or with the reason it wasn't.
+pub type EmulatedInputWarpReply = Box<dyn FnOnce(Result<(), EmulatedInputError>) + Send>;
+
+pub trait EmulatedInputHandler {
+    /// Current status, without prompting the user.
+    fn status(&mut self) -> EmulatedInputStatus;
+
+    /// Ask the user for the permission to emulate input. |reply| may be
+    /// called from any thread, once the user made a choice.
+    fn request_permission(&mut self, reply: EmulatedInputPermissionReply);
+
+    /// Emulate |event|, which was already checked against [`Self::status`].
+    /// Failures of the asynchronous delivery are reported by the next status.
+    fn inject(&mut self, event: EmulatedInputEvent) -> Result<(), EmulatedInputError>;
+
+    /// Move the pointer to |x|, |y| in the logical coordinates of the view,
+    /// once the status allowed pointer motions. |reply| may be called from
+    /// any thread.
+    fn warp_pointer(&mut self, x: f64, y: f64, reply: EmulatedInputWarpReply);
+}
+
+pub struct EmulatedInputPlugin {
+    channel: Weak<MethodChannel>,
+    handler: Arc<Mutex<dyn EmulatedInputHandler + Send>>,
+}
+
+impl EmulatedInputPlugin {
+    pub fn new(handler: Arc<Mutex<dyn EmulatedInputHandler + Send>>) -> Self {
+        Self {
+            channel: Weak::new(),
+            handler,
+        }
+    }
+}
+
+impl Plugin for EmulatedInputPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: self.handler.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    handler: Arc<Mutex<dyn EmulatedInputHandler + Send>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "getStatus" => call.success(self.handler.lock().status()),
+            "requestPermission" => {
+                self.handler
+                    .lock()
+                    .request_permission(Box::new(move |result| match result {
+                        Ok(status) => call.success(status),
+                        Err(err) => respond_error(call, err),
+                    }))
+            }
+            "movePointer" | "pointerButton" | "scroll" | "key" => {
+                let Some(event) = parse_event(call.method(), call.raw_args()) else {
+                    return call.error("invalid-args", "Invalid event arguments", Value::Null);
+                };
+
+                let mut handler = self.handler.lock();
+                let result = handler
+                    .status()
+                    .check(&event)
+                    .and_then(|()| handler.inject(event));
+                drop(handler);
+
+                match result {
+                    Ok(()) => call.success_empty(),
+                    Err(err) => respond_error(call, err),
+                }
+            }
+            "warpPointer" => {
+                let Some(PositionArgs { x, y }) = parse_position(call.raw_args()) else {
+                    return call.error("invalid-args", "Invalid position arguments", Value::Null);
+                };
+
+                let mut handler = self.handler.lock();
+                let motion = EmulatedInputEvent::PointerMotion { dx: 0.0, dy: 0.0 };
+                if let Err(err) = handler.status().check(&motion) {
+                    drop(handler);
+                    return respond_error(call, err);
+                }
+                handler.warp_pointer(
+                    x,
+                    y,
+                    Box::new(move |result| match result {
+                        Ok(()) => call.success_empty(),
+                        Err(err) => respond_error(call, err),
+                    }),
+                );
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+fn respond_error(call: MethodCall, err: EmulatedInputError) {
+    warn!("[plugin: emulated_input] {}: {}", call.method(), err);
+    call.error(err.code(), err.to_string(), Value::Null)
+}
+
+#[derive(Deserialize)]
+struct DeltaArgs {
+    dx: f64,
+    dy: f64,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct PositionArgs {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PressArgs {
+    code: i32,
+    is_pressed: bool,
+}
+
+fn parse_event(method: &str, args: &Value) -> Option<EmulatedInputEvent> {
+    let event = match method {
+        "movePointer" => {
+            let DeltaArgs { dx, dy } = from_value(args).ok()?;
+            EmulatedInputEvent::PointerMotion { dx, dy }
+        }
+        "scroll" => {
+            let DeltaArgs { dx, dy } = from_value(args).ok()?;
+            EmulatedInputEvent::Scroll { dx, dy }
+        }
+        "pointerButton" => {
+            let PressArgs { code, is_pressed } = from_value(args).ok()?;
+            EmulatedInputEvent::PointerButton {
+                button: code,
+                is_pressed,
+            }
+        }
+        "key" => {
+            let PressArgs { code, is_pressed } = from_value(args).ok()?;
+            EmulatedInputEvent::Key {
+                keycode: code,
+                is_pressed,
+            }
+        }
+        _ => return None,
+    };
+
+    let is_finite = match event {
+        EmulatedInputEvent::PointerMotion { dx, dy } | EmulatedInputEvent::Scroll { dx, dy } => {
+            dx.is_finite() && dy.is_finite()
+        }
+        _ => true,
+    };
+    is_finite.then_some(event)
+}
+
+fn parse_position(args: &Value) -> Option<PositionArgs> {
+    let position: PositionArgs = from_value(args).ok()?;
+    (position.x.is_finite() && position.y.is_finite()).then_some(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use flutter_engine::codec::Value;
+
+    use super::{
+        parse_event, parse_position, EmulatedInputError, EmulatedInputEvent,
+        EmulatedInputPermission, EmulatedInputStatus, PositionArgs,
+    };
+
+    fn args(entries: &[(&str, Value)]) -> Value {
+        let map: HashMap<String, Value> = entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        Value::Map(map)
+    }
+
+    #[test]
+    fn parses_events() {
+        let delta = args(&[("dx", Value::F64(1.5)), ("dy", Value::I64(-2))]);
+        assert_eq!(
+            parse_event("movePointer", &delta),
+            Some(EmulatedInputEvent::PointerMotion { dx: 1.5, dy: -2.0 })
+        );
+
+        let press = args(&[("code", Value::I32(272)), ("isPressed", Value::Boolean(true))]);
+        assert_eq!(
+            parse_event("pointerButton", &press),
+            Some(EmulatedInputEvent::PointerButton {
+                button: 272,
+                is_pressed: true
+            })
+        );
+
+        assert_eq!(parse_event("movePointer", &press), None);
+        let infinite = args(&[("dx", Value::F64(f64::INFINITY)), ("dy", Value::F64(0.0))]);
+        assert_eq!(parse_event("scroll", &infinite), None);
+    }
+
+    #[test]
+    fn parses_positions() {
+        let position = args(&[("x", Value::F64(12.5)), ("y", Value::I64(40))]);
+        assert_eq!(
+            parse_position(&position),
+            Some(PositionArgs { x: 12.5, y: 40.0 })
+        );
+
+        let delta = args(&[("dx", Value::F64(1.0)), ("dy", Value::F64(1.0))]);
+        assert_eq!(parse_position(&delta), None);
+        let nan = args(&[("x", Value::F64(f64::NAN)), ("y", Value::F64(0.0))]);
+        assert_eq!(parse_position(&nan), None);
+    }
+
+    #[test]
+    fn checks_status_before_injecting() {
+        let motion = EmulatedInputEvent::PointerMotion { dx: 1.0, dy: 0.0 };
+        let key = EmulatedInputEvent::Key {
+            keycode: 30,
+            is_pressed: true,
+        };
+
+        let mut status = EmulatedInputStatus::default();
+        assert_eq!(status.check(&motion), Err(EmulatedInputError::Unavailable));
+
+        status.is_available = true;
+        assert_eq!(status.check(&motion), Err(EmulatedInputError::PermissionRequired));
+
+        status.permission = EmulatedInputPermission::Granted;
+        status.pointer = true;
+        assert_eq!(status.check(&motion), Ok(()));
+        assert_eq!(status.check(&key), Err(EmulatedInputError::DeviceNotGranted));
+    }
+}
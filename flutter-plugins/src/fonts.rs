@@ -0,0 +1,156 @@
+//! Plugin providing fonts which are not part of the asset bundle (e.g.:
+//! shipped separately, or downloaded by the app) to Dart, which loads them
+//! with a `FontLoader` so that text renders with them without a restart.
+//! The embedder API has no way of registering font data with the engine, and
+//! reloading the system fonts only picks up the fonts installed on the
+//! system.
+//! It handles flutter-rs/fonts type message.
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fs, io,
+    path::PathBuf,
+    sync::{Arc, Weak},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{Value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use tracing::debug;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/fonts";
+
+/// Data of a font file (TrueType, OpenType or a collection).
+#[derive(Debug, Clone)]
+pub enum FontSource {
+    /// Read when Dart loads the family, so the file may be written (e.g.:
+    /// downloaded) after the font was added.
+    Path(PathBuf),
+    Bytes(Arc<[u8]>),
+}
+
+impl FontSource {
+    fn read(&self) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::Path(path) => fs::read(path).map(Cow::Owned),
+            Self::Bytes(bytes) => Ok(Cow::Borrowed(bytes)),
+        }
+    }
+}
+
+type FontFamilies = Arc<Mutex<BTreeMap<String, Vec<FontSource>>>>;
+
+#[derive(Default)]
+pub struct FontsPlugin {
+    channel: Weak<MethodChannel>,
+    families: FontFamilies,
+}
+
+impl FontsPlugin {
+    /// Add a font file to |family| (e.g.: one file per weight and style), and
+    /// notify Dart that the family needs to be (re)loaded. Once the engine is
+    /// running, this must be called on the platform thread.
+    pub fn add_font<F: Into<String>>(&self, family: F, source: FontSource) {
+        let family = family.into();
+        debug!("Adding a font to the {} family", family);
+        self.families
+            .lock()
+            .entry(family.clone())
+            .or_default()
+            .push(source);
+
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onFontFamilyAdded", family);
+        }
+    }
+}
+
+impl Plugin for FontsPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                families: self.families.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    families: FontFamilies,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "getFontFamilies" => {
+                let families: Vec<_> = self.families.lock().keys().cloned().collect();
+                call.success(families)
+            }
+            // Returns the files of the family, encoded in base64 as the
+            // channel uses the JSON codec.
+            "loadFontFamily" => {
+                let family = match call.raw_args() {
+                    Value::String(family) => family.clone(),
+                    _ => return call.error("invalid-args", "Expected a font family", Value::Null),
+                };
+
+                let Some(sources) = self.families.lock().get(&family).cloned() else {
+                    return call.error(
+                        "unknown-family",
+                        format!("No fonts were added to the {family} family"),
+                        Value::Null,
+                    );
+                };
+
+                let fonts: Result<Vec<_>, _> = sources
+                    .iter()
+                    .map(|source| source.read().map(|data| BASE64.encode(data)))
+                    .collect();
+                match fonts {
+                    Ok(fonts) => call.success(fonts),
+                    Err(err) => call.error(
+                        "read-failed",
+                        format!("Failed to read the fonts of the {family} family: {err}"),
+                        Value::Null,
+                    ),
+                }
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{FontSource, FontsPlugin};
+
+    #[test]
+    fn groups_fonts_by_family() {
+        let plugin = FontsPlugin::default();
+        plugin.add_font("Inter", FontSource::Bytes([1, 2, 3].into()));
+        let missing = FontSource::Path(PathBuf::from("/nonexistent/font.ttf"));
+        plugin.add_font("Inter", missing.clone());
+        plugin.add_font("Mono", missing);
+
+        let families = plugin.families.lock();
+        assert_eq!(families.keys().collect::<Vec<_>>(), ["Inter", "Mono"]);
+
+        let inter = &families["Inter"];
+        assert_eq!(inter[0].read().unwrap().as_ref(), [1, 2, 3]);
+        assert!(inter[1].read().is_err());
+    }
+}
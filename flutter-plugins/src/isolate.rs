@@ -5,8 +5,7 @@ use std::sync::{Arc, Weak};
 use flutter_engine::{
     channel::{MessageChannel, MessageHandler},
     codec::STRING_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{Plugin, PluginContext},
 };
 
 use flutter_engine::channel::Message;
@@ -47,7 +46,8 @@ impl Plugin for IsolatePlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
         self.channel = engine.register_channel(MessageChannel::new(
             CHANNEL_NAME,
             Handler {
@@ -11,8 +11,7 @@ use flutter_engine::{
     channel::{MethodCall, MethodCallHandler, MethodChannel},
     codec::STANDARD_CODEC,
     ffi::{FlutterLogicalKey, FlutterPhysicalKey},
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{Plugin, PluginContext},
 };
 
 use flutter_engine::codec::Value;
@@ -30,7 +29,8 @@ impl Plugin for KeyboardPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
         self.channel = engine.register_channel(MethodChannel::new(
             CHANNEL_NAME,
             Handler {
@@ -0,0 +1,109 @@
+//! Plugin exposing the key repeat settings of the platform, so that apps
+//! implementing their own repeat behavior (e.g.: games, terminals) can match
+//! the system configuration.
+//! It handles flutter-rs/keyboard_repeat type message.
+use std::sync::{Arc, Weak};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::JSON_CODEC,
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/keyboard_repeat";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct KeyRepeatInfo {
+    pub enabled: bool,
+    /// Number of repetitions per second.
+    pub rate: u32,
+    /// Delay before a held key starts repeating, in milliseconds.
+    pub delay: u32,
+}
+
+impl KeyRepeatInfo {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            rate: 0,
+            delay: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct KeyboardRepeatPlugin {
+    channel: Weak<MethodChannel>,
+    repeat_info: Arc<Mutex<Option<KeyRepeatInfo>>>,
+}
+
+impl KeyboardRepeatPlugin {
+    /// Store the repeat settings advertised by the platform. Returns whether
+    /// they differ from the previous ones.
+    pub fn set_repeat_info(&self, repeat_info: KeyRepeatInfo) -> bool {
+        self.repeat_info.lock().replace(repeat_info) != Some(repeat_info)
+    }
+
+    pub fn notify_repeat_info_changed(&self) {
+        let Some(repeat_info) = *self.repeat_info.lock() else {
+            return;
+        };
+
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onRepeatInfoChanged", repeat_info);
+        }
+    }
+}
+
+impl Plugin for KeyboardRepeatPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                repeat_info: self.repeat_info.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    repeat_info: Arc<Mutex<Option<KeyRepeatInfo>>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            // Returns `null` until the platform has advertised its settings.
+            "getRepeatInfo" => call.success(*self.repeat_info.lock()),
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyRepeatInfo, KeyboardRepeatPlugin};
+
+    #[test]
+    fn reports_changed_repeat_info() {
+        let plugin = KeyboardRepeatPlugin::default();
+        let info = KeyRepeatInfo {
+            enabled: true,
+            rate: 25,
+            delay: 600,
+        };
+
+        assert!(plugin.set_repeat_info(info));
+        assert!(!plugin.set_repeat_info(info));
+        assert!(plugin.set_repeat_info(KeyRepeatInfo::disabled()));
+    }
+}
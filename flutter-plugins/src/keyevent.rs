@@ -5,8 +5,7 @@ use serde::{Deserialize, Serialize};
 use flutter_engine::{
     channel::{MessageChannel, MessageHandler},
     codec::JSON_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{Plugin, PluginContext},
 };
 
 use flutter_engine::channel::Message;
@@ -27,7 +26,8 @@ impl Plugin for KeyEventPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
         self.channel =
             engine.register_channel(MessageChannel::new(CHANNEL_NAME, Handler, &JSON_CODEC));
     }
@@ -78,6 +78,19 @@ impl KeyEventPlugin {
             channel.send(action);
         });
     }
+
+    /// Same as [`Self::key_action`], invoking |reply| with whether the
+    /// framework handled the event. An empty reply (e.g.: before the
+    /// framework listens to the channel) counts as unhandled.
+    pub fn key_action_with_reply<F>(&self, action: KeyAction, reply: F)
+    where
+        F: FnOnce(bool) + Send + 'static,
+    {
+        let Some(channel) = self.channel.upgrade() else {
+            return reply(false);
+        };
+        channel.send_with_reply(action, move |value| reply(is_handled(value)));
+    }
 }
 
 impl MessageHandler for Handler {
@@ -85,3 +98,34 @@ impl MessageHandler for Handler {
         msg.respond(Value::Null)
     }
 }
+
+/// Reads the `{"handled": bool}` reply of the framework.
+fn is_handled(reply: Option<Value>) -> bool {
+    match reply {
+        Some(Value::Map(map)) => matches!(map.get("handled"), Some(Value::Boolean(true))),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use flutter_engine::codec::Value;
+
+    use super::is_handled;
+
+    #[test]
+    fn reads_handled_replies() {
+        let reply = |handled: Value| {
+            let map: HashMap<String, Value> = [("handled".to_string(), handled)].into();
+            Some(Value::Map(map))
+        };
+
+        assert!(is_handled(reply(Value::Boolean(true))));
+        assert!(!is_handled(reply(Value::Boolean(false))));
+        assert!(!is_handled(reply(Value::Null)));
+        assert!(!is_handled(Some(Value::Null)));
+        assert!(!is_handled(None));
+    }
+}
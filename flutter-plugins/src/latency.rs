@@ -0,0 +1,327 @@
+//! Plugin used for measuring the input latency of the embedder (only built
+//! with the `latency-probe` feature).
+//! It handles flutter-rs/latency type message.
+//!
+//! The embedder records when each probe input is sent to the engine (see
+//! [`LatencyProbe::record_input`]). The app echoes the probe id back on this
+//! channel as soon as it receives the input, and the embedder then records
+//! when the frame containing the response has been presented and, when
+//! available, displayed.
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{Value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::warn;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/latency";
+
+#[derive(Debug, Default)]
+struct PendingSample {
+    input: Option<Instant>,
+    echo: Option<Instant>,
+    present: Option<Instant>,
+}
+
+/// Latency components of a single probe input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySample {
+    pub input_to_dart: Duration,
+    pub dart_to_present: Duration,
+    /// Only available when the backend supports presentation feedback.
+    pub present_to_display: Option<Duration>,
+}
+
+/// Collects the timestamps of the probe inputs. Cheap to clone.
+#[derive(Clone, Default)]
+pub struct LatencyProbe {
+    pending: Arc<Mutex<BTreeMap<u64, PendingSample>>>,
+    samples: Arc<Mutex<Vec<LatencySample>>>,
+}
+
+impl LatencyProbe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the probe input |id| has just been sent to the engine.
+    pub fn record_input(&self, id: u64) {
+        self.pending.lock().entry(id).or_default().input = Some(Instant::now());
+    }
+
+    fn record_echo(&self, id: u64) {
+        let mut pending = self.pending.lock();
+        match pending.get_mut(&id) {
+            Some(sample) => sample.echo = Some(Instant::now()),
+            None => warn!("[plugin: latency] Ignoring echo for unknown probe {}", id),
+        }
+    }
+
+    /// Record that a frame has been presented. All the echoed inputs which
+    /// were not presented yet are considered part of this frame.
+    pub fn record_present(&self) {
+        let now = Instant::now();
+        for sample in self.pending.lock().values_mut() {
+            if sample.echo.is_some() && sample.present.is_none() {
+                sample.present = Some(now);
+            }
+        }
+    }
+
+    /// Record that the last presented frame has been displayed at |time|, or
+    /// that no presentation feedback is available (`None`). This completes
+    /// the presented samples.
+    pub fn record_display(&self, time: Option<Instant>) {
+        let mut pending = self.pending.lock();
+        let mut samples = self.samples.lock();
+
+        pending.retain(|_, sample| {
+            let (Some(input), Some(echo), Some(present)) =
+                (sample.input, sample.echo, sample.present)
+            else {
+                return true;
+            };
+
+            samples.push(LatencySample {
+                input_to_dart: echo.saturating_duration_since(input),
+                dart_to_present: present.saturating_duration_since(echo),
+                present_to_display: time.map(|time| time.saturating_duration_since(present)),
+            });
+            false
+        });
+    }
+
+    /// Returns the completed samples recorded so far.
+    pub fn samples(&self) -> Vec<LatencySample> {
+        self.samples.lock().clone()
+    }
+}
+
+/// Distribution of a latency component, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl LatencyStats {
+    fn from_durations(mut durations: Vec<Duration>) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort();
+
+        let percentile = |p: f64| {
+            let index = ((durations.len() - 1) as f64 * p).round() as usize;
+            durations[index].as_secs_f64() * 1000.0
+        };
+
+        Some(Self {
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: percentile(1.0),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencySummary {
+    pub iterations: usize,
+    pub input_to_dart: Option<LatencyStats>,
+    pub dart_to_present: Option<LatencyStats>,
+    pub present_to_display: Option<LatencyStats>,
+}
+
+impl LatencySummary {
+    pub fn new(samples: &[LatencySample]) -> Self {
+        Self {
+            iterations: samples.len(),
+            input_to_dart: LatencyStats::from_durations(
+                samples.iter().map(|sample| sample.input_to_dart).collect(),
+            ),
+            dart_to_present: LatencyStats::from_durations(
+                samples.iter().map(|sample| sample.dart_to_present).collect(),
+            ),
+            present_to_display: LatencyStats::from_durations(
+                samples.iter().filter_map(|sample| sample.present_to_display).collect(),
+            ),
+        }
+    }
+
+    fn components(&self) -> [(&'static str, Option<LatencyStats>); 3] {
+        [
+            ("input -> dart", self.input_to_dart),
+            ("dart -> present", self.dart_to_present),
+            ("present -> display", self.present_to_display),
+        ]
+    }
+
+    /// Human readable table of the summary (values in milliseconds).
+    pub fn to_table(&self) -> String {
+        let mut table = format!(
+            "{:<20}{:>10}{:>10}{:>10}{:>10}\n",
+            "component", "p50", "p95", "p99", "max"
+        );
+
+        for (name, stats) in self.components() {
+            let _ = match stats {
+                Some(stats) => writeln!(
+                    table,
+                    "{:<20}{:>10.2}{:>10.2}{:>10.2}{:>10.2}",
+                    name, stats.p50, stats.p95, stats.p99, stats.max
+                ),
+                None => writeln!(table, "{:<20}{:>10}", name, "n/a"),
+            };
+        }
+
+        table
+    }
+
+    /// Check the p95 of each component against |budget|. Returns the name of
+    /// the components exceeding their budget.
+    pub fn check(&self, budget: &LatencyBudget) -> Result<(), Vec<&'static str>> {
+        let budgets = [
+            budget.input_to_dart,
+            budget.dart_to_present,
+            budget.present_to_display,
+        ];
+
+        let violations: Vec<&'static str> = self
+            .components()
+            .into_iter()
+            .zip(budgets)
+            .filter_map(|((name, stats), budget)| {
+                let (stats, budget) = (stats?, budget?);
+                (stats.p95 > budget.as_secs_f64() * 1000.0).then_some(name)
+            })
+            .collect();
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+/// Maximum p95 latency allowed for each component. Components without a
+/// budget are not checked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencyBudget {
+    pub input_to_dart: Option<Duration>,
+    pub dart_to_present: Option<Duration>,
+    pub present_to_display: Option<Duration>,
+}
+
+pub struct LatencyProbePlugin {
+    channel: Weak<MethodChannel>,
+    probe: LatencyProbe,
+}
+
+impl LatencyProbePlugin {
+    pub fn new(probe: LatencyProbe) -> Self {
+        Self {
+            channel: Weak::new(),
+            probe,
+        }
+    }
+}
+
+impl Plugin for LatencyProbePlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                probe: self.probe.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    probe: LatencyProbe,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "echo" => {
+                let id = match call.raw_args() {
+                    Value::I32(id) => u64::try_from(*id).ok(),
+                    Value::I64(id) => u64::try_from(*id).ok(),
+                    _ => None,
+                };
+
+                let Some(id) = id else {
+                    return call.error("invalid-args", "Expected a probe id", Value::Null);
+                };
+
+                self.probe.record_echo(id);
+                call.success_empty()
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{LatencyBudget, LatencySample, LatencySummary};
+
+    fn sample(input_to_dart: u64, dart_to_present: u64) -> LatencySample {
+        LatencySample {
+            input_to_dart: Duration::from_millis(input_to_dart),
+            dart_to_present: Duration::from_millis(dart_to_present),
+            present_to_display: None,
+        }
+    }
+
+    #[test]
+    fn summarizes_samples() {
+        let samples: Vec<_> = (1..=100).map(|ms| sample(ms, 2)).collect();
+        let summary = LatencySummary::new(&samples);
+
+        let input_to_dart = summary.input_to_dart.unwrap();
+        assert_eq!(input_to_dart.p50, 51.0);
+        assert_eq!(input_to_dart.p95, 95.0);
+        assert_eq!(input_to_dart.max, 100.0);
+        assert_eq!(summary.dart_to_present.unwrap().p99, 2.0);
+        assert_eq!(summary.present_to_display, None);
+    }
+
+    #[test]
+    fn checks_budgets() {
+        let summary = LatencySummary::new(&[sample(4, 20), sample(6, 20)]);
+
+        let budget = LatencyBudget {
+            input_to_dart: Some(Duration::from_millis(8)),
+            dart_to_present: Some(Duration::from_millis(16)),
+            present_to_display: Some(Duration::from_millis(1)),
+        };
+        assert_eq!(summary.check(&budget), Err(vec!["dart -> present"]));
+
+        assert_eq!(summary.check(&LatencyBudget::default()), Ok(()));
+    }
+}
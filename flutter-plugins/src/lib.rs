@@ -1,12 +1,30 @@
+pub mod anchor;
+pub mod appearance;
+pub mod assets;
+pub mod bridge;
+pub mod color;
+pub mod contract;
+pub mod diagnostics;
+pub mod emulated_input;
+pub mod fonts;
 pub mod isolate;
 pub mod keyboard;
+pub mod keyboard_repeat;
 pub mod keyevent;
+#[cfg(feature = "latency-probe")]
+pub mod latency;
 pub mod lifecycle;
 pub mod localization;
+pub mod logging;
 pub mod mousecursor;
 pub mod navigation;
 pub mod platform;
+pub mod ready;
+pub mod screensaver;
 pub mod settings;
+pub mod shared_preferences;
 pub mod system;
+pub mod text_toolbar;
 pub mod textinput;
+pub mod tray;
 pub mod window;
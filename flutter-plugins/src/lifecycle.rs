@@ -7,8 +7,7 @@ use std::sync::Weak;
 use flutter_engine::{
     channel::{MessageChannel, MessageHandler},
     codec::STRING_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{DeferredMessage, DeferredSends, Plugin, PluginContext},
 };
 
 use flutter_engine::channel::Message;
@@ -19,12 +18,14 @@ pub const CHANNEL_NAME: &str = "flutter/lifecycle";
 
 pub struct LifecyclePlugin {
     channel: Weak<MessageChannel>,
+    deferred_sends: DeferredSends,
 }
 
 impl Default for LifecyclePlugin {
     fn default() -> Self {
         Self {
             channel: Weak::new(),
+            deferred_sends: DeferredSends::new(),
         }
     }
 }
@@ -34,7 +35,9 @@ impl Plugin for LifecyclePlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        self.deferred_sends = context.deferred_sends().clone();
+        let engine = context.engine();
         self.channel =
             engine.register_channel(MessageChannel::new(CHANNEL_NAME, Handler, &STRING_CODEC));
     }
@@ -43,24 +46,41 @@ impl Plugin for LifecyclePlugin {
 impl LifecyclePlugin {
     pub fn send_app_is_inactive(&self) {
         debug!("Sending app is inactive");
-        if let Some(channel) = self.channel.upgrade() {
-            channel.send("AppLifecycleState.inactive");
-        }
+        self.send_state("AppLifecycleState.inactive");
     }
 
     pub fn send_app_is_resumed(&self) {
         debug!("Sending app is resumed");
-        if let Some(channel) = self.channel.upgrade() {
-            channel.send("AppLifecycleState.resumed");
-        }
+        self.send_state("AppLifecycleState.resumed");
     }
 
     pub fn send_app_is_paused(&self) {
         debug!("Sending app is paused");
-        if let Some(channel) = self.channel.upgrade() {
-            channel.send("AppLifecycleState.paused");
+        self.send_state("AppLifecycleState.paused");
+    }
+
+    /// Notify the app that it is about to be shut down, invoking |on_ack| once
+    /// Dart has handled the notification. Unlike the other states, this is
+    /// never deferred.
+    pub fn send_app_is_detached<F>(&self, on_ack: F)
+    where
+        F: FnOnce() + 'static + Send,
+    {
+        debug!("Sending app is detached");
+        match self.channel.upgrade() {
+            Some(channel) => channel.send_with_ack("AppLifecycleState.detached", on_ack),
+            None => on_ack(),
         }
     }
+
+    fn send_state(&self, state: &'static str) {
+        let channel = self.channel.clone();
+        self.deferred_sends.send(DeferredMessage::Lifecycle, move || {
+            if let Some(channel) = channel.upgrade() {
+                channel.send(state);
+            }
+        });
+    }
 }
 
 struct Handler;
@@ -9,8 +9,7 @@ use flutter_engine::channel::MethodCall;
 use flutter_engine::{
     channel::{MethodCallHandler, MethodChannel},
     codec::JSON_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{DeferredMessage, DeferredSends, Plugin, PluginContext},
 };
 
 pub const PLUGIN_NAME: &str = module_path!();
@@ -18,12 +17,14 @@ pub const CHANNEL_NAME: &str = "flutter/localization";
 
 pub struct LocalizationPlugin {
     channel: Weak<MethodChannel>,
+    deferred_sends: DeferredSends,
 }
 
 impl Default for LocalizationPlugin {
     fn default() -> Self {
         Self {
             channel: Weak::new(),
+            deferred_sends: DeferredSends::new(),
         }
     }
 }
@@ -33,7 +34,9 @@ impl Plugin for LocalizationPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        self.deferred_sends = context.deferred_sends().clone();
+        let engine = context.engine();
         self.channel =
             engine.register_channel(MethodChannel::new(CHANNEL_NAME, Handler, &JSON_CODEC));
     }
@@ -41,33 +44,38 @@ impl Plugin for LocalizationPlugin {
 
 impl LocalizationPlugin {
     pub fn send_locale(&self, locale: String) {
-        debug!("Sending locales to flutter");
-        if let Some(channel) = self.channel.upgrade() {
-            let mut languages = Vec::<String>::new();
-            if let Ok(loc) = locale.parse::<Locale>() {
-                info!("Available locale: {}", loc);
-                if let (Some(region), Some(script)) = (loc.id.region, loc.id.script) {
-                    languages.push(loc.id.language.as_str().to_owned());
-                    languages.push(region.as_str().to_owned());
-                    languages.push(script.as_str().to_owned());
-                    languages.push(
-                        loc.id
-                            .variants
-                            .first()
-                            .map_or("", |v| v.as_str())
-                            .to_owned(),
-                    );
-                } else {
-                    warn!("Failed to unwrap locale region and/or script: {}", locale);
-                }
+        let channel = self.channel.clone();
+        self.deferred_sends.send(DeferredMessage::Locales, move || send_locale(channel, locale));
+    }
+}
+
+fn send_locale(channel: Weak<MethodChannel>, locale: String) {
+    debug!("Sending locales to flutter");
+    if let Some(channel) = channel.upgrade() {
+        let mut languages = Vec::<String>::new();
+        if let Ok(loc) = locale.parse::<Locale>() {
+            info!("Available locale: {}", loc);
+            if let (Some(region), Some(script)) = (loc.id.region, loc.id.script) {
+                languages.push(loc.id.language.as_str().to_owned());
+                languages.push(region.as_str().to_owned());
+                languages.push(script.as_str().to_owned());
+                languages.push(
+                    loc.id
+                        .variants
+                        .first()
+                        .map_or("", |v| v.as_str())
+                        .to_owned(),
+                );
             } else {
-                warn!("Failed to parse locale: {}", locale);
+                warn!("Failed to unwrap locale region and/or script: {}", locale);
             }
-
-            channel.invoke_method("setLocale", languages)
         } else {
-            error!("Failed to upgrade channel to send message");
+            warn!("Failed to parse locale: {}", locale);
         }
+
+        channel.invoke_method("setLocale", languages)
+    } else {
+        error!("Failed to upgrade channel to send message");
     }
 }
 
@@ -0,0 +1,136 @@
+//! Plugin to control the embedder logging at runtime.
+//! It handles flutter-rs/logging type message.
+use std::sync::{Arc, OnceLock, Weak};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{Value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use tracing::{info, warn};
+use tracing_subscriber::{
+    filter::ParseError, fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter,
+    Registry,
+};
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/logging";
+
+const DEFAULT_FILTER: &str = "info";
+
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum LogFilterError {
+    NotInitialized,
+    AlreadyInitialized,
+    Invalid(ParseError),
+    Reload(reload::Error),
+}
+
+impl std::fmt::Display for LogFilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LogFilterError::NotInitialized => write!(f, "logging was not initialized"),
+            LogFilterError::AlreadyInitialized => write!(f, "logging was already initialized"),
+            LogFilterError::Invalid(err) => write!(f, "invalid log filter: {}", err),
+            LogFilterError::Reload(err) => write!(f, "failed to reload log filter: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for LogFilterError {}
+
+/// Install a global tracing subscriber whose filter can be changed at runtime
+/// using [`set_filter`]. The initial filter is read from `RUST_LOG`.
+pub fn init() -> Result<(), LogFilterError> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let (filter, handle) = reload::Layer::new(filter);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .try_init()
+        .or(Err(LogFilterError::AlreadyInitialized))?;
+
+    FILTER_HANDLE
+        .set(handle)
+        .or(Err(LogFilterError::AlreadyInitialized))
+}
+
+/// Replace the current log filter (e.g.: `flutter_sctk=trace`). The filter
+/// string uses the same syntax as `RUST_LOG`.
+pub fn set_filter(filter: &str) -> Result<(), LogFilterError> {
+    let handle = FILTER_HANDLE.get().ok_or(LogFilterError::NotInitialized)?;
+    let filter = EnvFilter::try_new(filter).map_err(LogFilterError::Invalid)?;
+
+    handle.reload(filter).map_err(LogFilterError::Reload)
+}
+
+pub trait LoggingHandler {
+    /// Snapshot of the embedder state, logged when `dumpState` is invoked.
+    fn dump_state(&mut self) -> Value;
+}
+
+pub struct LoggingPlugin {
+    channel: Weak<MethodChannel>,
+    handler: Arc<Mutex<dyn LoggingHandler + Send>>,
+}
+
+impl LoggingPlugin {
+    pub fn new(handler: Arc<Mutex<dyn LoggingHandler + Send>>) -> Self {
+        Self {
+            channel: Weak::new(),
+            handler,
+        }
+    }
+}
+
+impl Plugin for LoggingPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: self.handler.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    handler: Arc<Mutex<dyn LoggingHandler + Send>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "setLogFilter" => {
+                let Value::String(filter) = call.raw_args() else {
+                    return call.error("invalid-args", "Expected a filter string", Value::Null);
+                };
+
+                match set_filter(filter) {
+                    Ok(()) => call.success_empty(),
+                    Err(err) => {
+                        warn!("[plugin: logging] {}", err);
+                        call.error("invalid-filter", err.to_string(), Value::Null)
+                    }
+                }
+            }
+            "dumpState" => {
+                let state = self.handler.lock().dump_state();
+                info!("[plugin: logging] embedder state: {:?}", state);
+                call.success(state)
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
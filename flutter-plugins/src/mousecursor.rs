@@ -8,8 +8,7 @@ use std::{
 use flutter_engine::{
     channel::{MethodCall, MethodCallHandler, MethodChannel},
     codec::STANDARD_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{Plugin, PluginConfig, PluginContext},
 };
 
 use flutter_engine::codec::Value;
@@ -147,6 +146,53 @@ impl std::error::Error for MouseCursorError {}
 
 pub trait MouseCursorHandler {
     fn activate_system_cursor(&mut self, kind: SystemMouseCursor) -> Result<(), MouseCursorError>;
+
+    /// Activate the cursor of a single pointer `device` (as reported in the
+    /// pointer events sent to the engine). Embedders supporting multiple
+    /// pointers (e.g.: multiple seats) should override this method, the
+    /// default implementation ignores the device.
+    fn activate_system_cursor_for_device(
+        &mut self,
+        _device: i32,
+        kind: SystemMouseCursor,
+    ) -> Result<(), MouseCursorError> {
+        self.activate_system_cursor(kind)
+    }
+}
+
+/// Configuration of the pointer devices, applied by the embedder to the
+/// pointer events (e.g.: for kiosks which must scroll the same way whatever
+/// the desktop settings). It is keyed by the mouse cursor plugin, which is
+/// the pointer plugin of the embedders, but only read by the embedders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointerConfig {
+    pub scroll_direction: ScrollDirection,
+    /// Multiplies the scrolled distance of every device.
+    pub scroll_factor: f64,
+}
+
+impl Default for PointerConfig {
+    fn default() -> Self {
+        Self {
+            scroll_direction: ScrollDirection::default(),
+            scroll_factor: 1.0,
+        }
+    }
+}
+
+impl PluginConfig for PointerConfig {
+    type Plugin = MouseCursorPlugin;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScrollDirection {
+    /// Follow the natural scrolling setting of the desktop.
+    #[default]
+    Desktop,
+    /// The content moves along with the fingers, like on touch screens.
+    Natural,
+    /// The content moves against the wheel or the fingers.
+    Traditional,
 }
 
 pub struct MouseCursorPlugin {
@@ -168,7 +214,8 @@ impl Plugin for MouseCursorPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
         self.channel = engine.register_channel(MethodChannel::new(
             CHANNEL_NAME,
             Handler {
@@ -204,7 +251,19 @@ impl MethodCallHandler for Handler {
                     return call.error("unknown-data", "Unknown data type", Value::Null);
                 };
 
-                match self.handler.lock().activate_system_cursor(kind) {
+                let device = match v.get("device") {
+                    Some(Value::I32(device)) => Some(*device),
+                    Some(Value::I64(device)) => i32::try_from(*device).ok(),
+                    _ => None,
+                };
+
+                let mut handler = self.handler.lock();
+                let result = match device {
+                    Some(device) => handler.activate_system_cursor_for_device(device, kind),
+                    None => handler.activate_system_cursor(kind),
+                };
+
+                match result {
                     Ok(_) => call.success_empty(),
                     Err(_) => call.error("unknown-data", "Unknown data type", Value::Null),
                 };
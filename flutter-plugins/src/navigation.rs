@@ -9,8 +9,7 @@ use flutter_engine::codec::Value;
 use flutter_engine::{
     channel::{MethodCallHandler, MethodChannel},
     codec::JSON_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{Plugin, PluginContext},
 };
 
 pub const PLUGIN_NAME: &str = module_path!();
@@ -25,7 +24,8 @@ impl Plugin for NavigationPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
         self.channel =
             engine.register_channel(MethodChannel::new(CHANNEL_NAME, Handler, &JSON_CODEC));
     }
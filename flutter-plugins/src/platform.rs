@@ -1,55 +1,241 @@
 //! Plugin to work with clipboard and various system related functions.
 //! It handles flutter/platform type message.
+//!
+//! The `SystemChrome`, `HapticFeedback` and `SystemSound` methods are mostly
+//! meant for mobile platforms. They succeed without doing anything unless the
+//! [`PlatformHandler`] interprets them, so that code shared with those
+//! platforms runs as is. The latest chrome state requested by Dart is kept
+//! (see [`PlatformPlugin::chrome_state`]).
 use std::sync::{Arc, Weak};
 
 use flutter_engine::{
-    channel::{MethodCallHandler, MethodChannel},
-    codec::JSON_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    channel::{MethodCallHandler, MethodChannel, MethodError},
+    codec::{value::from_value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
 };
 
 use serde::{Deserialize, Serialize};
 
 use flutter_engine::channel::MethodCall;
 use flutter_engine::codec::Value;
-use tracing::debug;
+use tracing::{debug, warn};
 use parking_lot::Mutex;
 
 pub const PLUGIN_NAME: &str = module_path!();
 pub const CHANNEL_NAME: &str = "flutter/platform";
 
-#[derive(Debug)]
-pub struct MimeError;
+/// Error reported by a [`PlatformHandler`]. It is sent back to Dart as a
+/// `PlatformException` (see [`PlatformError::code`] and
+/// [`PlatformError::details`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlatformError {
+    /// The requested clipboard format (mime type) is not supported.
+    UnsupportedFormat(String),
 
-impl std::fmt::Display for MimeError {
+    /// The clipboard could not be accessed.
+    Clipboard(String),
+}
+
+impl PlatformError {
+    /// Error code of the method channel error response.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PlatformError::UnsupportedFormat(_) => "unsupported-format",
+            PlatformError::Clipboard(_) => "clipboard-error",
+        }
+    }
+
+    /// Details of the method channel error response.
+    pub fn details(&self) -> Value {
+        match self {
+            PlatformError::UnsupportedFormat(mime) => Value::String(mime.clone()),
+            PlatformError::Clipboard(_) => Value::Null,
+        }
+    }
+}
+
+impl std::fmt::Display for PlatformError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Mime error")
+        match self {
+            PlatformError::UnsupportedFormat(mime) => {
+                write!(f, "Unsupported clipboard format: {}", mime)
+            }
+            PlatformError::Clipboard(reason) => write!(f, "Clipboard error: {}", reason),
+        }
     }
 }
 
-impl std::error::Error for MimeError {}
+impl std::error::Error for PlatformError {}
+
+/// Exit of the application requested by Dart, once the app confirmed it (see
+/// `AppLifecycleListener.onExitRequested`) when it could be cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppExitRequest {
+    /// The root route was popped (`SystemNavigator.pop`).
+    NavigatorPop,
+    /// `ServicesBinding.exitApplication`.
+    ExitApplication { exit_code: i32 },
+}
+
+impl AppExitRequest {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppExitRequest::NavigatorPop => 0,
+            AppExitRequest::ExitApplication { exit_code } => *exit_code,
+        }
+    }
+}
 
 pub trait PlatformHandler {
     fn set_application_switcher_description(&mut self, description: AppSwitcherDescription);
 
-    fn set_clipboard_data(&mut self, text: String);
+    fn set_clipboard_data(&mut self, text: String) -> Result<(), PlatformError>;
+
+    fn get_clipboard_data(&mut self, mime: &str) -> Result<String, PlatformError>;
 
-    fn get_clipboard_data(&mut self, mime: &str) -> Result<String, MimeError>;
+    /// Exit the application with |exit_code|, as requested by Dart (see
+    /// `ServicesBinding.exitApplication`). Returns whether the application is
+    /// exiting. The default implementation ignores the request.
+    fn exit_application(&mut self, _exit_code: i32) -> bool {
+        false
+    }
+
+    /// Exit the application, as requested by Dart. Returns whether the
+    /// application is exiting. The default implementation exits with the
+    /// exit code of the request (see [`PlatformHandler::exit_application`]).
+    fn request_app_exit(&mut self, request: AppExitRequest) -> bool {
+        self.exit_application(request.exit_code())
+    }
+
+    /// Orientations the app can be displayed in, in order of preference. An
+    /// empty list lets the platform choose. Ignored by default.
+    fn set_preferred_orientations(&mut self, _orientations: &[DeviceOrientation]) {}
+
+    /// Ignored by default.
+    fn set_system_ui_mode(&mut self, _mode: SystemUiMode) {}
+
+    /// System overlays to show when the UI mode is [`SystemUiMode::Manual`].
+    /// Ignored by default.
+    fn set_enabled_system_ui_overlays(&mut self, _overlays: &[SystemUiOverlay]) {}
+
+    /// Style of the system overlays (e.g.: the color of the status bar).
+    /// Ignored by default.
+    fn set_system_ui_overlay_style(&mut self, _style: &SystemUiOverlayStyle) {}
+
+    /// Ignored by default.
+    fn haptic_feedback(&mut self, _feedback: HapticFeedbackType) {}
+
+    /// Ignored by default.
+    fn play_system_sound(&mut self, _sound: SystemSoundType) {}
+}
+
+/// Orientation of the device (`DeviceOrientation` in Dart).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceOrientation {
+    PortraitUp,
+    LandscapeLeft,
+    PortraitDown,
+    LandscapeRight,
+}
+
+impl DeviceOrientation {
+    pub fn is_portrait(&self) -> bool {
+        matches!(
+            self,
+            DeviceOrientation::PortraitUp | DeviceOrientation::PortraitDown
+        )
+    }
+}
+
+/// Mode of the system overlays (`SystemUiMode` in Dart).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemUiMode {
+    LeanBack,
+    Immersive,
+    ImmersiveSticky,
+    EdgeToEdge,
+    /// Only the overlays enabled by
+    /// [`PlatformHandler::set_enabled_system_ui_overlays`] are shown.
+    Manual,
+}
+
+/// System overlay (`SystemUiOverlay` in Dart).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemUiOverlay {
+    /// The status bar.
+    Top,
+    /// The navigation bar.
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Brightness {
+    Light,
+    Dark,
+}
+
+/// Kind of haptic feedback (one method of `HapticFeedback` in Dart each).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HapticFeedbackType {
+    Vibrate,
+    LightImpact,
+    MediumImpact,
+    HeavyImpact,
+    SelectionClick,
+}
+
+/// Sound played by `SystemSound.play`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemSoundType {
+    Click,
+    Alert,
+}
+
+/// Style of the system overlays (`SystemUiOverlayStyle` in Dart). The colors
+/// are ARGB, and unset fields keep their current value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemUiOverlayStyle {
+    pub status_bar_color: Option<u32>,
+    pub status_bar_brightness: Option<Brightness>,
+    pub status_bar_icon_brightness: Option<Brightness>,
+    pub system_status_bar_contrast_enforced: Option<bool>,
+    pub system_navigation_bar_color: Option<u32>,
+    pub system_navigation_bar_divider_color: Option<u32>,
+    pub system_navigation_bar_icon_brightness: Option<Brightness>,
+    pub system_navigation_bar_contrast_enforced: Option<bool>,
+}
+
+/// Latest system chrome state requested by Dart. `None` fields were never
+/// requested.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SystemChromeState {
+    pub preferred_orientations: Vec<DeviceOrientation>,
+    pub ui_mode: Option<SystemUiMode>,
+    pub enabled_overlays: Option<Vec<SystemUiOverlay>>,
+    pub overlay_style: Option<SystemUiOverlayStyle>,
+    pub switcher_description: Option<AppSwitcherDescription>,
 }
 
 pub struct PlatformPlugin {
-    channel: Weak<MethodChannel>,
+    /// Shared with the handler, which asks Dart to confirm the exits.
+    channel: Arc<Mutex<Weak<MethodChannel>>>,
     handler: Arc<Mutex<dyn PlatformHandler + Send>>,
+    chrome_state: Arc<Mutex<SystemChromeState>>,
 }
 
 impl PlatformPlugin {
     pub fn new(handler: Arc<Mutex<dyn PlatformHandler + Send>>) -> Self {
         Self {
-            channel: Weak::new(),
+            channel: Default::default(),
             handler,
+            chrome_state: Default::default(),
         }
     }
+
+    /// Latest system chrome state requested by Dart.
+    pub fn chrome_state(&self) -> SystemChromeState {
+        self.chrome_state.lock().clone()
+    }
 }
 
 impl Plugin for PlatformPlugin {
@@ -57,11 +243,14 @@ impl Plugin for PlatformPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
-        self.channel = engine.register_channel(MethodChannel::new(
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        *self.channel.lock() = engine.register_channel(MethodChannel::new(
             CHANNEL_NAME,
             Handler {
+                channel: self.channel.clone(),
                 handler: self.handler.clone(),
+                chrome_state: self.chrome_state.clone(),
             },
             &JSON_CODEC,
         ));
@@ -69,7 +258,74 @@ impl Plugin for PlatformPlugin {
 }
 
 struct Handler {
+    channel: Arc<Mutex<Weak<MethodChannel>>>,
     handler: Arc<Mutex<dyn PlatformHandler + Send>>,
+    chrome_state: Arc<Mutex<SystemChromeState>>,
+}
+
+impl Handler {
+    /// Record the chrome state of |request|, then pass it to the handler.
+    fn handle_request(&self, request: PlatformRequest) {
+        self.chrome_state.lock().apply(&request);
+
+        let mut handler = self.handler.lock();
+        match request {
+            PlatformRequest::SetPreferredOrientations(orientations) => {
+                handler.set_preferred_orientations(&orientations)
+            }
+            PlatformRequest::SetSystemUiMode(mode) => handler.set_system_ui_mode(mode),
+            PlatformRequest::SetEnabledSystemUiOverlays(overlays) => {
+                handler.set_enabled_system_ui_overlays(&overlays)
+            }
+            PlatformRequest::SetSystemUiOverlayStyle(style) => {
+                handler.set_system_ui_overlay_style(&style)
+            }
+            PlatformRequest::SetApplicationSwitcherDescription(description) => {
+                handler.set_application_switcher_description(description)
+            }
+            PlatformRequest::HapticFeedback(feedback) => handler.haptic_feedback(feedback),
+            PlatformRequest::PlaySystemSound(sound) => handler.play_system_sound(sound),
+            // Only needed on Android, which hides the overlays again after
+            // a while in some modes.
+            PlatformRequest::RestoreSystemUiOverlays
+            | PlatformRequest::SetSystemUiChangeListener => {}
+        }
+    }
+
+    /// Exit once the app confirmed it, as it may have unsaved changes. Apps
+    /// which don't handle `System.requestAppExit` exit right away.
+    fn confirm_app_exit(&self, request: AppExitRequest) {
+        let Some(channel) = self.channel.lock().upgrade() else {
+            return;
+        };
+
+        let handler = self.handler.clone();
+        channel.invoke_method_with_result(
+            "System.requestAppExit".into(),
+            ExitRequestArgs {
+                r#type: "cancelable",
+            },
+            move |result: Result<Value, MethodError<Value>>| {
+                let confirmed = match result {
+                    Ok(Value::Map(response)) => {
+                        response.get("response") == Some(&Value::String("exit".into()))
+                    }
+                    Ok(_) => true,
+                    Err(MethodError::NotImplemented) => true,
+                    Err(MethodError::Err { code, message, .. }) => {
+                        warn!("[plugin: platform] Exit confirmation failed: {code}: {message}");
+                        true
+                    }
+                };
+
+                if confirmed {
+                    handler.lock().request_app_exit(request);
+                } else {
+                    debug!("The app cancelled the exit");
+                }
+            },
+        );
+    }
 }
 
 impl MethodCallHandler for Handler {
@@ -79,47 +335,439 @@ impl MethodCallHandler for Handler {
             call.method(),
             call.raw_args()
         );
+        if let Some(request) = parse_request(call.method(), call.raw_args()) {
+            return match request {
+                Ok(request) => {
+                    self.handle_request(request);
+                    call.success_empty()
+                }
+                Err(message) => {
+                    warn!("[plugin: platform] {}: {}", call.method(), message);
+                    call.error("invalid-args", message, Value::Null)
+                }
+            };
+        }
+
         match call.method().as_str() {
-            "SystemChrome.setApplicationSwitcherDescription" => {
-                let args: AppSwitcherDescription = call.args();
-                self.handler
-                    .lock()
-                    .set_application_switcher_description(args);
-                call.success_empty()
-            }
             "Clipboard.setData" => {
-                if let Value::Map(v) = &call.args() {
-                    if let Some(Value::String(text)) = &v.get("text") {
-                        let text = text.clone();
-                        self.handler.lock().set_clipboard_data(text);
-                        return call.success_empty();
-                    }
+                let Value::Map(v) = &call.args() else {
+                    return call.error("invalid-args", "Expected a map", Value::Null);
+                };
+
+                let Some(Value::String(text)) = &v.get("text") else {
+                    return call.error("invalid-args", "Expected a `text` string", Value::Null);
+                };
+
+                let result = self.handler.lock().set_clipboard_data(text.clone());
+                match result {
+                    Ok(()) => call.success_empty(),
+                    Err(err) => respond_error(call, err),
                 }
-                call.error("unknown-data", "Unknown data type", Value::Null)
             }
             "Clipboard.getData" => {
-                if let Value::String(mime) = call.raw_args() {
-                    match self.handler.lock().get_clipboard_data(mime) {
-                        Ok(text) => call.success(ClipboardData { text }),
-                        Err(_) => call.error("unknown-data", "Unknown data type", Value::Null),
-                    }
-                } else {
-                    call.error("unknown-data", "Unknown data type", Value::Null)
+                let Value::String(mime) = call.raw_args() else {
+                    return call.error("invalid-args", "Expected a mime type", Value::Null);
+                };
+
+                let result = self.handler.lock().get_clipboard_data(mime);
+                match result {
+                    Ok(text) => call.success(ClipboardData { text }),
+                    Err(err) => respond_error(call, err),
                 }
             }
+            "System.exitApplication" => {
+                let Value::Map(v) = &call.args() else {
+                    return call.error("invalid-args", "Expected a map", Value::Null);
+                };
+
+                let exit_code = match v.get("exitCode") {
+                    Some(Value::I32(exit_code)) => *exit_code,
+                    Some(Value::I64(exit_code)) => *exit_code as i32,
+                    _ => 0,
+                };
+                let request = AppExitRequest::ExitApplication { exit_code };
+
+                // Cancelable exits are confirmed by the app first, so they are
+                // not under way yet.
+                if v.get("type") == Some(&Value::String("cancelable".into())) {
+                    call.success(ExitResponse { response: "cancel" });
+                    return self.confirm_app_exit(request);
+                }
+
+                let response = if self.handler.lock().request_app_exit(request) {
+                    "exit"
+                } else {
+                    "cancel"
+                };
+                call.success(ExitResponse { response })
+            }
+            // The root route was popped, which closes the app on desktop.
+            "SystemNavigator.pop" => {
+                call.success_empty();
+                self.confirm_app_exit(AppExitRequest::NavigatorPop)
+            }
             _ => call.not_implemented(),
         }
     }
 }
 
+fn respond_error(call: MethodCall, err: PlatformError) {
+    warn!("[plugin: platform] {}: {}", call.method(), err);
+    call.error(err.code(), err.to_string(), err.details())
+}
+
 #[derive(Serialize, Deserialize)]
 struct ClipboardData {
     text: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppSwitcherDescription {
     pub primary_color: i64,
     pub label: String,
 }
+
+/// Reply to `System.exitApplication`.
+#[derive(Serialize)]
+struct ExitResponse {
+    response: &'static str,
+}
+
+/// Arguments of `System.requestAppExit`.
+#[derive(Serialize)]
+struct ExitRequestArgs {
+    r#type: &'static str,
+}
+
+/// `SystemChrome`, `HapticFeedback` and `SystemSound` method calls.
+#[derive(Debug, Clone, PartialEq)]
+enum PlatformRequest {
+    SetPreferredOrientations(Vec<DeviceOrientation>),
+    SetSystemUiMode(SystemUiMode),
+    SetEnabledSystemUiOverlays(Vec<SystemUiOverlay>),
+    RestoreSystemUiOverlays,
+    SetSystemUiOverlayStyle(SystemUiOverlayStyle),
+    SetSystemUiChangeListener,
+    SetApplicationSwitcherDescription(AppSwitcherDescription),
+    HapticFeedback(HapticFeedbackType),
+    PlaySystemSound(SystemSoundType),
+}
+
+impl SystemChromeState {
+    fn apply(&mut self, request: &PlatformRequest) {
+        match request {
+            PlatformRequest::SetPreferredOrientations(orientations) => {
+                self.preferred_orientations = orientations.clone()
+            }
+            PlatformRequest::SetSystemUiMode(mode) => self.ui_mode = Some(*mode),
+            PlatformRequest::SetEnabledSystemUiOverlays(overlays) => {
+                self.enabled_overlays = Some(overlays.clone())
+            }
+            PlatformRequest::SetSystemUiOverlayStyle(style) => {
+                self.overlay_style = Some(style.clone())
+            }
+            PlatformRequest::SetApplicationSwitcherDescription(description) => {
+                self.switcher_description = Some(description.clone())
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Fields of `SystemUiOverlayStyle`, whose enums are their Dart name (e.g.:
+/// `Brightness.dark`).
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OverlayStyleArgs {
+    status_bar_color: Option<u32>,
+    status_bar_brightness: Option<String>,
+    status_bar_icon_brightness: Option<String>,
+    system_status_bar_contrast_enforced: Option<bool>,
+    system_navigation_bar_color: Option<u32>,
+    system_navigation_bar_divider_color: Option<u32>,
+    system_navigation_bar_icon_brightness: Option<String>,
+    system_navigation_bar_contrast_enforced: Option<bool>,
+}
+
+/// Returns `None` if |method| isn't a chrome, haptic feedback or sound method,
+/// and an error message if its |args| are invalid.
+fn parse_request(method: &str, args: &Value) -> Option<Result<PlatformRequest, String>> {
+    let request = match method {
+        "SystemChrome.setPreferredOrientations" => {
+            parse_list(args, parse_orientation).map(PlatformRequest::SetPreferredOrientations)
+        }
+        "SystemChrome.setEnabledSystemUIMode" => {
+            parse_mode(args).map(PlatformRequest::SetSystemUiMode)
+        }
+        "SystemChrome.setEnabledSystemUIOverlays" => {
+            parse_list(args, parse_overlay).map(PlatformRequest::SetEnabledSystemUiOverlays)
+        }
+        "SystemChrome.restoreSystemUIOverlays" => Ok(PlatformRequest::RestoreSystemUiOverlays),
+        "SystemChrome.setSystemUIOverlayStyle" => {
+            parse_overlay_style(args).map(PlatformRequest::SetSystemUiOverlayStyle)
+        }
+        "SystemChrome.setSystemUIChangeListener" => Ok(PlatformRequest::SetSystemUiChangeListener),
+        "SystemChrome.setApplicationSwitcherDescription" => from_value(args)
+            .map(PlatformRequest::SetApplicationSwitcherDescription)
+            .map_err(|_| "Expected a label and a primary color".to_string()),
+        // Plain vibrations don't have any type.
+        "HapticFeedback.vibrate" => match args {
+            Value::Null => Ok(PlatformRequest::HapticFeedback(HapticFeedbackType::Vibrate)),
+            _ => parse_haptic_feedback(args).map(PlatformRequest::HapticFeedback),
+        },
+        "SystemSound.play" => parse_sound(args).map(PlatformRequest::PlaySystemSound),
+        _ => return None,
+    };
+    Some(request)
+}
+
+fn parse_list<T>(args: &Value, parse: fn(&Value) -> Result<T, String>) -> Result<Vec<T>, String> {
+    match args {
+        Value::List(values) => values.iter().map(parse).collect(),
+        _ => Err("Expected a list".into()),
+    }
+}
+
+/// The variant named |value| (e.g.: `DeviceOrientation.portraitUp`) of the
+/// Dart enum |name|.
+fn parse_enum<T: Copy>(value: &Value, name: &str, variants: &[(&str, T)]) -> Result<T, String> {
+    let variant = match value {
+        Value::String(value) => value
+            .strip_prefix(name)
+            .and_then(|value| value.strip_prefix('.'))
+            .and_then(|value| variants.iter().find(|(variant, _)| *variant == value)),
+        _ => None,
+    };
+    variant
+        .map(|(_, variant)| *variant)
+        .ok_or_else(|| format!("Expected a {}, got {:?}", name, value))
+}
+
+fn parse_orientation(value: &Value) -> Result<DeviceOrientation, String> {
+    parse_enum(
+        value,
+        "DeviceOrientation",
+        &[
+            ("portraitUp", DeviceOrientation::PortraitUp),
+            ("landscapeLeft", DeviceOrientation::LandscapeLeft),
+            ("portraitDown", DeviceOrientation::PortraitDown),
+            ("landscapeRight", DeviceOrientation::LandscapeRight),
+        ],
+    )
+}
+
+fn parse_mode(value: &Value) -> Result<SystemUiMode, String> {
+    parse_enum(
+        value,
+        "SystemUiMode",
+        &[
+            ("leanBack", SystemUiMode::LeanBack),
+            ("immersive", SystemUiMode::Immersive),
+            ("immersiveSticky", SystemUiMode::ImmersiveSticky),
+            ("edgeToEdge", SystemUiMode::EdgeToEdge),
+            ("manual", SystemUiMode::Manual),
+        ],
+    )
+}
+
+fn parse_overlay(value: &Value) -> Result<SystemUiOverlay, String> {
+    parse_enum(
+        value,
+        "SystemUiOverlay",
+        &[
+            ("top", SystemUiOverlay::Top),
+            ("bottom", SystemUiOverlay::Bottom),
+        ],
+    )
+}
+
+fn parse_brightness(value: Option<String>) -> Result<Option<Brightness>, String> {
+    value
+        .map(|value| {
+            parse_enum(
+                &Value::String(value),
+                "Brightness",
+                &[("light", Brightness::Light), ("dark", Brightness::Dark)],
+            )
+        })
+        .transpose()
+}
+
+fn parse_overlay_style(args: &Value) -> Result<SystemUiOverlayStyle, String> {
+    let args: OverlayStyleArgs =
+        from_value(args).map_err(|_| "Expected a system UI overlay style".to_string())?;
+    Ok(SystemUiOverlayStyle {
+        status_bar_color: args.status_bar_color,
+        status_bar_brightness: parse_brightness(args.status_bar_brightness)?,
+        status_bar_icon_brightness: parse_brightness(args.status_bar_icon_brightness)?,
+        system_status_bar_contrast_enforced: args.system_status_bar_contrast_enforced,
+        system_navigation_bar_color: args.system_navigation_bar_color,
+        system_navigation_bar_divider_color: args.system_navigation_bar_divider_color,
+        system_navigation_bar_icon_brightness: parse_brightness(
+            args.system_navigation_bar_icon_brightness,
+        )?,
+        system_navigation_bar_contrast_enforced: args.system_navigation_bar_contrast_enforced,
+    })
+}
+
+fn parse_haptic_feedback(value: &Value) -> Result<HapticFeedbackType, String> {
+    parse_enum(
+        value,
+        "HapticFeedbackType",
+        &[
+            ("lightImpact", HapticFeedbackType::LightImpact),
+            ("mediumImpact", HapticFeedbackType::MediumImpact),
+            ("heavyImpact", HapticFeedbackType::HeavyImpact),
+            ("selectionClick", HapticFeedbackType::SelectionClick),
+        ],
+    )
+}
+
+fn parse_sound(value: &Value) -> Result<SystemSoundType, String> {
+    parse_enum(
+        value,
+        "SystemSoundType",
+        &[
+            ("click", SystemSoundType::Click),
+            ("alert", SystemSoundType::Alert),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_engine::codec::{MethodCodec, JSON_CODEC};
+
+    use super::{
+        parse_request, AppSwitcherDescription, Brightness, DeviceOrientation, HapticFeedbackType,
+        PlatformRequest, SystemChromeState, SystemSoundType, SystemUiMode, SystemUiOverlay,
+        SystemUiOverlayStyle,
+    };
+
+    fn parse(message: &str) -> Option<Result<PlatformRequest, String>> {
+        let call = JSON_CODEC.decode_method_call(message.as_bytes()).unwrap();
+        parse_request(&call.method, &call.args)
+    }
+
+    #[test]
+    fn parses_chrome_requests() {
+        assert_eq!(
+            parse(
+                r#"{"method":"SystemChrome.setPreferredOrientations","args":["DeviceOrientation.portraitUp","DeviceOrientation.landscapeLeft"]}"#
+            ),
+            Some(Ok(PlatformRequest::SetPreferredOrientations(vec![
+                DeviceOrientation::PortraitUp,
+                DeviceOrientation::LandscapeLeft
+            ])))
+        );
+        assert_eq!(
+            parse(
+                r#"{"method":"SystemChrome.setEnabledSystemUIMode","args":"SystemUiMode.edgeToEdge"}"#
+            ),
+            Some(Ok(PlatformRequest::SetSystemUiMode(
+                SystemUiMode::EdgeToEdge
+            )))
+        );
+        assert_eq!(
+            parse(
+                r#"{"method":"SystemChrome.setEnabledSystemUIOverlays","args":["SystemUiOverlay.top"]}"#
+            ),
+            Some(Ok(PlatformRequest::SetEnabledSystemUiOverlays(vec![
+                SystemUiOverlay::Top
+            ])))
+        );
+        assert_eq!(
+            parse(r#"{"method":"SystemChrome.restoreSystemUIOverlays","args":null}"#),
+            Some(Ok(PlatformRequest::RestoreSystemUiOverlays))
+        );
+        assert_eq!(
+            parse(
+                r#"{"method":"SystemChrome.setSystemUIOverlayStyle","args":{"systemNavigationBarColor":4278190080,"systemNavigationBarDividerColor":null,"systemStatusBarContrastEnforced":null,"statusBarColor":null,"statusBarBrightness":"Brightness.dark","statusBarIconBrightness":"Brightness.light","systemNavigationBarIconBrightness":"Brightness.light","systemNavigationBarContrastEnforced":null}}"#
+            ),
+            Some(Ok(PlatformRequest::SetSystemUiOverlayStyle(
+                SystemUiOverlayStyle {
+                    system_navigation_bar_color: Some(0xff00_0000),
+                    status_bar_brightness: Some(Brightness::Dark),
+                    status_bar_icon_brightness: Some(Brightness::Light),
+                    system_navigation_bar_icon_brightness: Some(Brightness::Light),
+                    ..Default::default()
+                }
+            )))
+        );
+        assert_eq!(
+            parse(
+                r#"{"method":"SystemChrome.setApplicationSwitcherDescription","args":{"label":"Flutter Demo","primaryColor":4280391411}}"#
+            ),
+            Some(Ok(PlatformRequest::SetApplicationSwitcherDescription(
+                AppSwitcherDescription {
+                    primary_color: 4280391411,
+                    label: "Flutter Demo".into()
+                }
+            )))
+        );
+    }
+
+    #[test]
+    fn parses_feedback_requests() {
+        assert_eq!(
+            parse(r#"{"method":"HapticFeedback.vibrate","args":null}"#),
+            Some(Ok(PlatformRequest::HapticFeedback(
+                HapticFeedbackType::Vibrate
+            )))
+        );
+        assert_eq!(
+            parse(
+                r#"{"method":"HapticFeedback.vibrate","args":"HapticFeedbackType.selectionClick"}"#
+            ),
+            Some(Ok(PlatformRequest::HapticFeedback(
+                HapticFeedbackType::SelectionClick
+            )))
+        );
+        assert_eq!(
+            parse(r#"{"method":"SystemSound.play","args":"SystemSoundType.click"}"#),
+            Some(Ok(PlatformRequest::PlaySystemSound(SystemSoundType::Click)))
+        );
+        assert_eq!(
+            parse(r#"{"method":"Clipboard.getData","args":"text/plain"}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_args() {
+        let invalid = [
+            r#"{"method":"SystemChrome.setPreferredOrientations","args":["DeviceOrientation.sideways"]}"#,
+            r#"{"method":"SystemChrome.setPreferredOrientations","args":"DeviceOrientation.portraitUp"}"#,
+            r#"{"method":"SystemChrome.setEnabledSystemUIMode","args":"SystemUiOverlay.top"}"#,
+            r#"{"method":"SystemChrome.setSystemUIOverlayStyle","args":{"statusBarBrightness":"dark"}}"#,
+            r#"{"method":"SystemChrome.setSystemUIOverlayStyle","args":{"statusBarColor":-1}}"#,
+            r#"{"method":"SystemChrome.setApplicationSwitcherDescription","args":{"label":"Demo"}}"#,
+            r#"{"method":"SystemSound.play","args":null}"#,
+        ];
+        for message in invalid {
+            assert!(matches!(parse(message), Some(Err(_))), "{}", message);
+        }
+    }
+
+    #[test]
+    fn records_the_latest_chrome_state() {
+        let mut state = SystemChromeState::default();
+        for message in [
+            r#"{"method":"SystemChrome.setPreferredOrientations","args":["DeviceOrientation.portraitUp"]}"#,
+            r#"{"method":"SystemChrome.setEnabledSystemUIMode","args":"SystemUiMode.immersive"}"#,
+            r#"{"method":"SystemChrome.setPreferredOrientations","args":[]}"#,
+            r#"{"method":"HapticFeedback.vibrate","args":"HapticFeedbackType.heavyImpact"}"#,
+        ] {
+            state.apply(&parse(message).unwrap().unwrap());
+        }
+
+        assert_eq!(
+            state,
+            SystemChromeState {
+                ui_mode: Some(SystemUiMode::Immersive),
+                ..Default::default()
+            }
+        );
+    }
+}
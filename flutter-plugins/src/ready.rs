@@ -0,0 +1,76 @@
+//! Plugin to tell the embedder when the Dart app is ready for user input. It
+//! handles flutter-rs/ready type message.
+//!
+//! The input received before the first frame is buffered by the embedder
+//! (see `ApplicationBuilder::with_gate_input_until_first_frame`). Apps which
+//! register their channel handlers later on may extend the gating until they
+//! are initialized, while the others may end it before the first frame.
+use std::sync::{Arc, Weak};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::JSON_CODEC,
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/ready";
+
+pub trait ReadyHandler {
+    /// Keep buffering the input past the first frame, until `ready` is
+    /// invoked or the gating times out. Returns whether the input was still
+    /// gated, i.e.: `false` when it is too late.
+    fn extend(&mut self) -> bool;
+
+    /// Replay the input buffered so far, and stop gating it.
+    fn ready(&mut self);
+}
+
+pub struct ReadyPlugin {
+    channel: Weak<MethodChannel>,
+    handler: Arc<Mutex<dyn ReadyHandler + Send>>,
+}
+
+impl ReadyPlugin {
+    pub fn new(handler: Arc<Mutex<dyn ReadyHandler + Send>>) -> Self {
+        Self {
+            channel: Weak::new(),
+            handler,
+        }
+    }
+}
+
+impl Plugin for ReadyPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: self.handler.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    handler: Arc<Mutex<dyn ReadyHandler + Send>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "extend" => call.success(self.handler.lock().extend()),
+            "ready" => {
+                self.handler.lock().ready();
+                call.success_empty()
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Plugin to prevent the screen from blanking (e.g.: during media playback).
+//! It handles flutter-rs/screensaver type message.
+use std::sync::{Arc, Weak};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{Value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/screensaver";
+
+pub trait ScreenSaverHandler {
+    /// Request the screensaver (and idle actions like screen blanking) to be
+    /// inhibited or released. Implementations should log and ignore requests
+    /// when no inhibition mechanism is available.
+    fn set_inhibited(&mut self, inhibited: bool);
+
+    /// Whether the screensaver is currently inhibited.
+    fn is_inhibited(&mut self) -> bool;
+}
+
+pub struct ScreenSaverPlugin {
+    channel: Weak<MethodChannel>,
+    handler: Arc<Mutex<dyn ScreenSaverHandler + Send>>,
+}
+
+impl ScreenSaverPlugin {
+    pub fn new(handler: Arc<Mutex<dyn ScreenSaverHandler + Send>>) -> Self {
+        Self {
+            channel: Weak::new(),
+            handler,
+        }
+    }
+}
+
+impl Plugin for ScreenSaverPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: self.handler.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    handler: Arc<Mutex<dyn ScreenSaverHandler + Send>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "setInhibited" => {
+                let Value::Boolean(inhibited) = call.raw_args() else {
+                    return call.error("invalid-args", "Expected a boolean", Value::Null);
+                };
+
+                self.handler.lock().set_inhibited(*inhibited);
+                call.success_empty()
+            }
+            "isInhibited" => call.success(self.handler.lock().is_inhibited()),
+            _ => call.not_implemented(),
+        }
+    }
+}
@@ -11,8 +11,7 @@ use serde::{Deserialize, Serialize};
 use flutter_engine::{
     channel::{MessageChannel, MessageHandler},
     codec::JSON_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{DeferredMessage, DeferredSends, Plugin, PluginContext},
 };
 
 use flutter_engine::channel::Message;
@@ -25,6 +24,7 @@ pub const CHANNEL_NAME: &str = "flutter/settings";
 #[derive(Default, Clone)]
 pub struct SettingsPlugin {
     channel: Weak<MessageChannel>,
+    deferred_sends: DeferredSends,
 }
 
 pub struct SettingsMessage<'a> {
@@ -44,7 +44,9 @@ impl Plugin for SettingsPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        self.deferred_sends = context.deferred_sends().clone();
+        let engine = context.engine();
         self.channel =
             engine.register_channel(MessageChannel::new(CHANNEL_NAME, Handler, &JSON_CODEC));
     }
@@ -71,16 +73,22 @@ impl SettingsMessage<'_> {
         self
     }
 
+    /// Note: Each message replaces all the settings, so only the latest one
+    /// is sent when they are deferred during startup.
     pub fn send(self) {
-        if let Some(channel) = self.plugin.channel.upgrade() {
-            debug!("Sending settings: {:?}", self.settings);
-            channel.send(self.settings);
-        }
+        let channel = self.plugin.channel.clone();
+        let settings = self.settings;
+        self.plugin.deferred_sends.send(DeferredMessage::Settings, move || {
+            if let Some(channel) = channel.upgrade() {
+                debug!("Sending settings: {:?}", settings);
+                channel.send(settings);
+            }
+        });
     }
 }
 
 impl SettingsPlugin {
-    pub fn start_message(&self) -> SettingsMessage {
+    pub fn start_message(&self) -> SettingsMessage<'_> {
         debug!("Starting to build message");
         SettingsMessage {
             plugin: self,
@@ -0,0 +1,481 @@
+//! Plugin to persist simple key-value data.
+//! It handles plugins.flutter.io/shared_preferences type message.
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{value::from_value_owned, Value, STANDARD_CODEC},
+    plugins::{Plugin, PluginConfig, PluginContext},
+};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{error, warn};
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "plugins.flutter.io/shared_preferences";
+
+const STORE_FILE_NAME: &str = "shared_preferences.json";
+
+/// Prefix used by the legacy `getAll` and `clear` methods.
+const LEGACY_PREFIX: &str = "flutter.";
+
+#[derive(Debug)]
+pub enum SharedPreferencesError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for SharedPreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SharedPreferencesError::Io(err) => write!(f, "shared preferences io error: {}", err),
+            SharedPreferencesError::Json(err) => {
+                write!(f, "shared preferences json error: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SharedPreferencesError {}
+
+impl From<io::Error> for SharedPreferencesError {
+    fn from(err: io::Error) -> Self {
+        SharedPreferencesError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SharedPreferencesError {
+    fn from(err: serde_json::Error) -> Self {
+        SharedPreferencesError::Json(err)
+    }
+}
+
+/// A value stored in the preferences file. Values are stored along with their
+/// type in order to keep ints and doubles apart when reading them back.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum PreferenceValue {
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    StringList(Vec<String>),
+}
+
+impl From<PreferenceValue> for Value {
+    fn from(value: PreferenceValue) -> Self {
+        match value {
+            PreferenceValue::Bool(value) => Value::Boolean(value),
+            PreferenceValue::Int(value) => Value::I64(value),
+            PreferenceValue::Double(value) => Value::F64(value),
+            PreferenceValue::String(value) => Value::String(value),
+            PreferenceValue::StringList(value) => {
+                Value::List(value.into_iter().map(Value::String).collect())
+            }
+        }
+    }
+}
+
+/// File backed key-value store.
+///
+/// The file is read once when the store is opened and cached in memory. Every
+/// update is performed while holding an exclusive lock on a sidecar lock file,
+/// re-reading the file first so that updates made by other instances of the
+/// application are not lost. The file itself is replaced atomically.
+pub struct SharedPreferencesStore {
+    path: Option<PathBuf>,
+    cache: HashMap<String, PreferenceValue>,
+}
+
+impl SharedPreferencesStore {
+    /// Open the store backed by the file at `path`.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        let cache = read_store_file(&path).unwrap_or_else(|err| {
+            warn!("[plugin: shared_preferences] Unable to read {:?}: {}", path, err);
+            HashMap::new()
+        });
+
+        Self {
+            path: Some(path),
+            cache,
+        }
+    }
+
+    /// Create a store which is not persisted to disk.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&PreferenceValue> {
+        self.cache.get(key)
+    }
+
+    pub fn get_all(
+        &self,
+        prefix: &str,
+        allow_list: Option<&[String]>,
+    ) -> HashMap<String, PreferenceValue> {
+        self.cache
+            .iter()
+            .filter(|(key, _)| matches_filter(key, prefix, allow_list))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    pub fn set(
+        &mut self,
+        key: String,
+        value: PreferenceValue,
+    ) -> Result<(), SharedPreferencesError> {
+        self.update(move |data| {
+            data.insert(key, value);
+        })
+    }
+
+    pub fn remove(&mut self, key: &str) -> Result<(), SharedPreferencesError> {
+        self.update(|data| {
+            data.remove(key);
+        })
+    }
+
+    pub fn clear(
+        &mut self,
+        prefix: &str,
+        allow_list: Option<&[String]>,
+    ) -> Result<(), SharedPreferencesError> {
+        self.update(|data| data.retain(|key, _| !matches_filter(key, prefix, allow_list)))
+    }
+
+    fn update<F>(&mut self, f: F) -> Result<(), SharedPreferencesError>
+    where
+        F: FnOnce(&mut HashMap<String, PreferenceValue>),
+    {
+        let Some(path) = self.path.as_ref() else {
+            f(&mut self.cache);
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // The lock is released once the lock file is closed.
+        let lock_file = File::create(path.with_extension("lock"))?;
+        lock_file.lock()?;
+
+        let mut data = read_store_file(path).unwrap_or_else(|err| {
+            warn!("[plugin: shared_preferences] Discarding unreadable {:?}: {}", path, err);
+            HashMap::new()
+        });
+
+        f(&mut data);
+        write_store_file(path, &data)?;
+
+        self.cache = data;
+        Ok(())
+    }
+}
+
+fn matches_filter(key: &str, prefix: &str, allow_list: Option<&[String]>) -> bool {
+    key.starts_with(prefix)
+        && allow_list.is_none_or(|allow_list| allow_list.iter().any(|item| item == key))
+}
+
+fn read_store_file(
+    path: &Path,
+) -> Result<HashMap<String, PreferenceValue>, SharedPreferencesError> {
+    match fs::read(path) {
+        Ok(buf) if buf.is_empty() => Ok(HashMap::new()),
+        Ok(buf) => Ok(serde_json::from_slice(&buf)?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_store_file(
+    path: &Path,
+    data: &HashMap<String, PreferenceValue>,
+) -> Result<(), SharedPreferencesError> {
+    let tmp_path = path.with_extension("json.tmp");
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)?;
+    file.write_all(&serde_json::to_vec(data)?)?;
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Configuration of the [`SharedPreferencesPlugin`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SharedPreferencesConfig {
+    /// File the preferences are stored in. Defaults to
+    /// `shared_preferences.json` in the persistent cache directory. The
+    /// preferences are only kept in memory when neither is set.
+    pub path: Option<PathBuf>,
+}
+
+impl PluginConfig for SharedPreferencesConfig {
+    type Plugin = SharedPreferencesPlugin;
+}
+
+pub struct SharedPreferencesPlugin {
+    channel: Weak<MethodChannel>,
+    store: Arc<Mutex<SharedPreferencesStore>>,
+}
+
+impl Default for SharedPreferencesPlugin {
+    fn default() -> Self {
+        Self {
+            channel: Weak::new(),
+            store: Arc::new(Mutex::new(SharedPreferencesStore::in_memory())),
+        }
+    }
+}
+
+impl Plugin for SharedPreferencesPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        let config: SharedPreferencesConfig = context.config_or_default();
+        let path = config.path.or_else(|| {
+            let cache_path = engine.persistent_cache_path();
+            (!cache_path.as_os_str().is_empty()).then(|| cache_path.join(STORE_FILE_NAME))
+        });
+
+        match path {
+            Some(path) => *self.store.lock() = SharedPreferencesStore::open(path),
+            None => {
+                warn!("[plugin: shared_preferences] Persistent cache path is not set. Preferences will not be persisted");
+            }
+        }
+
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                store: self.store.clone(),
+            },
+            &STANDARD_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    store: Arc<Mutex<SharedPreferencesStore>>,
+}
+
+impl Handler {
+    fn get_all(&self, call: MethodCall, prefix: &str, allow_list: Option<&[String]>) {
+        let values: HashMap<String, Value> = self
+            .store
+            .lock()
+            .get_all(prefix, allow_list)
+            .into_iter()
+            .map(|(key, value)| (key, value.into()))
+            .collect();
+
+        call.success(Value::Map(values));
+    }
+
+    fn set<T, F>(&self, call: MethodCall, f: F)
+    where
+        T: DeserializeOwned,
+        F: FnOnce(T) -> PreferenceValue,
+    {
+        let Some(args) = parse_args::<SetArgs<T>>(&call) else {
+            return call.error("invalid-args", "Invalid set arguments", Value::Null);
+        };
+
+        let result = self.store.lock().set(args.key, f(args.value));
+        respond_with_result(call, result);
+    }
+
+    fn clear(&self, call: MethodCall, prefix: &str, allow_list: Option<&[String]>) {
+        let result = self.store.lock().clear(prefix, allow_list);
+        respond_with_result(call, result);
+    }
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "getAll" => self.get_all(call, LEGACY_PREFIX, None),
+            "getAllWithPrefix" | "getAllWithParameters" => {
+                let Some(args) = parse_args::<FilterArgs>(&call) else {
+                    return call.error("invalid-args", "Invalid filter arguments", Value::Null);
+                };
+                self.get_all(call, &args.prefix, args.allow_list.as_deref());
+            }
+            "setBool" => self.set(call, PreferenceValue::Bool),
+            "setInt" => self.set(call, PreferenceValue::Int),
+            "setDouble" => self.set(call, PreferenceValue::Double),
+            "setString" => self.set(call, PreferenceValue::String),
+            "setStringList" => self.set(call, PreferenceValue::StringList),
+            "remove" => {
+                let Some(args) = parse_args::<RemoveArgs>(&call) else {
+                    return call.error("invalid-args", "Invalid remove arguments", Value::Null);
+                };
+                let result = self.store.lock().remove(&args.key);
+                respond_with_result(call, result);
+            }
+            "clear" => self.clear(call, LEGACY_PREFIX, None),
+            "clearWithPrefix" | "clearWithParameters" => {
+                let Some(args) = parse_args::<FilterArgs>(&call) else {
+                    return call.error("invalid-args", "Invalid filter arguments", Value::Null);
+                };
+                self.clear(call, &args.prefix, args.allow_list.as_deref());
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+fn parse_args<T: DeserializeOwned>(call: &MethodCall) -> Option<T> {
+    from_value_owned(call.raw_args())
+        .map_err(|err| error!("[plugin: shared_preferences] Invalid arguments: {}", err))
+        .ok()
+}
+
+fn respond_with_result(call: MethodCall, result: Result<(), SharedPreferencesError>) {
+    match result {
+        Ok(()) => call.success(true),
+        Err(err) => {
+            error!("[plugin: shared_preferences] {}", err);
+            call.error("storage-error", err.to_string(), Value::Null);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SetArgs<T> {
+    key: String,
+    value: T,
+}
+
+#[derive(Deserialize)]
+struct RemoveArgs {
+    key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FilterArgs {
+    prefix: String,
+    allow_list: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, thread};
+
+    use super::{PreferenceValue, SharedPreferencesStore};
+
+    fn store_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "flutter-rs-shared-preferences-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("shared_preferences.json")
+    }
+
+    #[test]
+    fn test_round_trip_all_value_types() {
+        let path = store_path("round-trip");
+        let values = [
+            ("flutter.bool", PreferenceValue::Bool(true)),
+            ("flutter.int", PreferenceValue::Int(i64::MAX)),
+            ("flutter.double", PreferenceValue::Double(1.0)),
+            ("flutter.string", PreferenceValue::String("hello".into())),
+            (
+                "flutter.list",
+                PreferenceValue::StringList(vec!["a".into(), "".into(), "c".into()]),
+            ),
+        ];
+
+        let mut store = SharedPreferencesStore::open(&path);
+        for (key, value) in values.iter() {
+            store.set(key.to_string(), value.clone()).unwrap();
+        }
+
+        let store = SharedPreferencesStore::open(&path);
+        for (key, value) in values.iter() {
+            assert_eq!(store.get(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_remove_and_clear_with_prefix() {
+        let path = store_path("clear");
+        let mut store = SharedPreferencesStore::open(&path);
+        store.set("flutter.a".into(), PreferenceValue::Int(1)).unwrap();
+        store.set("flutter.b".into(), PreferenceValue::Int(2)).unwrap();
+        store.set("other.c".into(), PreferenceValue::Int(3)).unwrap();
+
+        store.remove("flutter.a").unwrap();
+        assert_eq!(store.get_all("flutter.", None).len(), 1);
+
+        store.clear("flutter.", None).unwrap();
+
+        let store = SharedPreferencesStore::open(&path);
+        assert_eq!(store.get_all("", None).len(), 1);
+        assert_eq!(store.get("other.c"), Some(&PreferenceValue::Int(3)));
+    }
+
+    #[test]
+    fn test_get_all_with_allow_list() {
+        let mut store = SharedPreferencesStore::in_memory();
+        store.set("flutter.a".into(), PreferenceValue::Bool(true)).unwrap();
+        store.set("flutter.b".into(), PreferenceValue::Bool(false)).unwrap();
+
+        let allow_list = vec!["flutter.b".to_string()];
+        let values = store.get_all("flutter.", Some(&allow_list));
+        assert_eq!(values.len(), 1);
+        assert_eq!(values.get("flutter.b"), Some(&PreferenceValue::Bool(false)));
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let path = store_path("concurrent");
+
+        let writers: Vec<_> = ["a", "b"]
+            .into_iter()
+            .map(|name| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    let mut store = SharedPreferencesStore::open(&path);
+                    for i in 0..50 {
+                        store
+                            .set(format!("flutter.{}{}", name, i), PreferenceValue::Int(i))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let store = SharedPreferencesStore::open(&path);
+        assert_eq!(store.get_all("flutter.", None).len(), 100);
+    }
+}
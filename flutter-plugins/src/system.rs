@@ -7,8 +7,7 @@ use serde::{Deserialize, Serialize};
 use flutter_engine::{
     channel::{MessageChannel, MessageHandler},
     codec::JSON_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{Plugin, PluginContext},
 };
 
 use flutter_engine::channel::Message;
@@ -35,7 +34,8 @@ impl Plugin for SystemPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
         self.channel =
             engine.register_channel(MessageChannel::new(CHANNEL_NAME, Handler, &JSON_CODEC));
     }
@@ -0,0 +1,171 @@
+//! Plugin to show a system-backed text selection toolbar. Apps opt into it by
+//! invoking this channel from their context menu builder instead of drawing
+//! the toolbar inside the view.
+//! It handles flutter-rs/text_toolbar type message.
+use std::sync::{Arc, Weak};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{value::from_value_owned, Value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::anchor::{AnchorRect, AnchorRegistry, AnchorToken, PopupPlacement};
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/text_toolbar";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TextToolbarAction {
+    Cut,
+    Copy,
+    Paste,
+    SelectAll,
+}
+
+impl TextToolbarAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TextToolbarAction::Cut => "Cut",
+            TextToolbarAction::Copy => "Copy",
+            TextToolbarAction::Paste => "Paste",
+            TextToolbarAction::SelectAll => "Select all",
+        }
+    }
+}
+
+/// Selection rect, in logical pixels relative to the view.
+pub type TextToolbarRect = AnchorRect;
+
+/// The toolbar is anchored either to a rect of the view, or to a token of
+/// `widgetRectToAnchor` (see [`crate::anchor`]).
+#[derive(Deserialize)]
+struct ShowArgs {
+    #[serde(default)]
+    rect: Option<TextToolbarRect>,
+    #[serde(default)]
+    anchor: Option<AnchorToken>,
+    #[serde(default)]
+    placement: PopupPlacement,
+    actions: Vec<TextToolbarAction>,
+}
+
+pub trait TextToolbarHandler {
+    /// Show the toolbar anchored to |rect| as specified by |placement|.
+    /// Replaces any toolbar that is already visible.
+    fn show(
+        &mut self,
+        rect: TextToolbarRect,
+        placement: PopupPlacement,
+        actions: Vec<TextToolbarAction>,
+    );
+
+    fn hide(&mut self);
+}
+
+pub struct TextToolbarPlugin {
+    channel: Weak<MethodChannel>,
+    handler: Arc<Mutex<dyn TextToolbarHandler + Send>>,
+    anchors: Option<AnchorRegistry>,
+}
+
+impl TextToolbarPlugin {
+    pub fn new(handler: Arc<Mutex<dyn TextToolbarHandler + Send>>) -> Self {
+        Self {
+            channel: Weak::new(),
+            handler,
+            anchors: None,
+        }
+    }
+
+    /// Resolve the anchor tokens passed to `show` with |anchors|. Without
+    /// it, the toolbar can only be anchored to a rect.
+    pub fn with_anchors(mut self, anchors: AnchorRegistry) -> Self {
+        self.anchors = Some(anchors);
+        self
+    }
+
+    /// Notify the app that |action| has been performed from the toolbar.
+    pub fn notify_action_performed(&self, action: TextToolbarAction) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onAction", action);
+        }
+    }
+
+    /// Notify the app that the toolbar has been dismissed (e.g.: after a
+    /// click outside of the toolbar or after pressing Escape).
+    pub fn notify_dismissed(&self) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onDismissed", Value::Null);
+        }
+    }
+}
+
+impl Plugin for TextToolbarPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: self.handler.clone(),
+                anchors: self.anchors.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    handler: Arc<Mutex<dyn TextToolbarHandler + Send>>,
+    anchors: Option<AnchorRegistry>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "show" => {
+                let args: ShowArgs = match from_value_owned(call.raw_args()) {
+                    Ok(args) => args,
+                    Err(err) => {
+                        error!("[plugin: text_toolbar] Invalid arguments: {}", err);
+                        return call.error("invalid-args", err.to_string(), Value::Null);
+                    }
+                };
+
+                let rect = match (args.anchor, &self.anchors) {
+                    (Some(token), Some(anchors)) => match anchors.resolve(token) {
+                        Ok((_, rect)) => rect,
+                        Err(err) => return call.error(err.code(), err.to_string(), Value::Null),
+                    },
+                    (None, _) => match args.rect {
+                        Some(rect) => rect,
+                        None => {
+                            let message = "Either a rect or an anchor is required";
+                            return call.error("invalid-args", message, Value::Null);
+                        }
+                    },
+                    (Some(_), None) => {
+                        let message = "Anchors are not supported";
+                        return call.error("invalid-args", message, Value::Null);
+                    }
+                };
+
+                self.handler.lock().show(rect, args.placement, args.actions);
+                call.success_empty()
+            }
+            "hide" => {
+                self.handler.lock().hide();
+                call.success_empty()
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Conformance suite of the text editing pipeline. Each fixture is a scripted
+//! sequence of framework calls and platform events (key presses, text
+//! committed by the input method), which is fed to a [`TextInputPlugin`]. The
+//! method calls it sends to the framework are then compared against the
+//! expected ones, committed along with the fixture.
+//!
+//! The platform events are applied by a [`ConformanceBackend`], so that each
+//! embedder runs the same fixtures through its own input path. Backends whose
+//! input path still diverges list the fixtures they fail (see
+//! [`check_all_except`]).
+//!
+//! The fixtures are the `<name>.events.json` files of [`FIXTURES_DIR`], and
+//! their expectations the `<name>.expected.json` files. Running the suite with
+//! the [`UPDATE_VARIABLE`] environment variable set rewrites the expectations
+//! from the actual calls, which can then be reviewed.
+use std::{
+    env, fmt, fs, io, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use flutter_engine::codec::Value;
+use parking_lot::Mutex;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use super::{ClientChannel, Handler, TextInputHandler, TextInputPlugin};
+
+pub const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/conformance/textinput");
+
+/// Environment variable for rewriting the expectations of the fixtures.
+pub const UPDATE_VARIABLE: &str = "FLUTTER_RS_UPDATE_CONFORMANCE";
+
+const EVENTS_SUFFIX: &str = ".events.json";
+const EXPECTED_SUFFIX: &str = ".expected.json";
+
+#[derive(Debug)]
+pub enum ConformanceError {
+    Io(PathBuf, io::Error),
+    Json(PathBuf, serde_json::Error),
+    /// A framework call of the fixture couldn't be applied (e.g.: invalid
+    /// arguments).
+    InvalidCall {
+        fixture: String,
+        method: String,
+        reason: String,
+    },
+    /// The calls sent to the framework differ from the expected ones.
+    Mismatch {
+        fixture: String,
+        expected: Vec<ConformanceCall>,
+        actual: Vec<ConformanceCall>,
+    },
+    /// A fixture listed as an expected failure passes.
+    UnexpectedPass(String),
+}
+
+impl fmt::Display for ConformanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            Self::Json(path, err) => write!(f, "{}: {}", path.display(), err),
+            Self::InvalidCall {
+                fixture,
+                method,
+                reason,
+            } => write!(
+                f,
+                "Fixture {}: invalid call {}: {}",
+                fixture, method, reason
+            ),
+            Self::Mismatch {
+                fixture,
+                expected,
+                actual,
+            } => {
+                let index = expected
+                    .iter()
+                    .zip(actual)
+                    .position(|(expected, actual)| expected != actual)
+                    .unwrap_or(expected.len().min(actual.len()));
+                let json = |calls: &[ConformanceCall]| {
+                    serde_json::to_string_pretty(calls).unwrap_or_default()
+                };
+                write!(
+                    f,
+                    "Fixture {}: the calls differ from call #{}\nexpected: {}\nactual: {}\n\
+                     (set {} for updating the expectations)",
+                    fixture,
+                    index,
+                    json(&expected[index..]),
+                    json(&actual[index..]),
+                    UPDATE_VARIABLE,
+                )
+            }
+            Self::UnexpectedPass(fixture) => write!(
+                f,
+                "Fixture {}: passes, remove it from the expected failures",
+                fixture
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConformanceError {}
+
+/// A key press, as delivered by xkb. Its text is the composed one, so dead
+/// keys don't have any.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConformanceKey {
+    /// Written as a hexadecimal string in the fixtures (e.g.: `"0xff0d"`).
+    #[serde(deserialize_with = "deserialize_keysym")]
+    pub keysym: u32,
+    #[serde(default)]
+    pub utf8: Option<String>,
+    /// Whether shift is down.
+    #[serde(default)]
+    pub shift: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConformanceEvent {
+    /// Method call of the framework, on the `flutter/textinput` channel.
+    Framework {
+        method: String,
+        #[serde(default)]
+        args: serde_json::Value,
+    },
+    Key(ConformanceKey),
+    /// Text being composed by the input method, which is empty once the
+    /// composition is cancelled.
+    Preedit(String),
+    /// Text committed by the input method, which ends the composition.
+    Commit(String),
+}
+
+/// Applies the platform events to the plugin, the way the embedder does.
+pub trait ConformanceBackend {
+    fn key_pressed(&mut self, text_input: &mut TextInputPlugin, key: &ConformanceKey);
+
+    fn preedit(&mut self, text_input: &mut TextInputPlugin, text: &str);
+
+    fn commit(&mut self, text_input: &mut TextInputPlugin, text: &str);
+}
+
+/// A method call sent to the framework.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConformanceCall {
+    pub method: String,
+    pub args: serde_json::Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConformanceFixture {
+    pub name: String,
+    pub events: Vec<ConformanceEvent>,
+    /// `None` when the expectations haven't been recorded yet.
+    pub expected: Option<Vec<ConformanceCall>>,
+    expected_path: PathBuf,
+}
+
+impl ConformanceFixture {
+    /// Load the fixture from its |events_path|, along with its expectations.
+    pub fn load(events_path: &Path) -> Result<Self, ConformanceError> {
+        let file_name = events_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let name = file_name
+            .strip_suffix(EVENTS_SUFFIX)
+            .unwrap_or(file_name)
+            .to_owned();
+        let expected_path = events_path.with_file_name(format!("{}{}", name, EXPECTED_SUFFIX));
+
+        let events = read_json(events_path)?;
+        let expected = if expected_path.exists() {
+            Some(read_json(&expected_path)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            name,
+            events,
+            expected,
+            expected_path,
+        })
+    }
+
+    /// All the fixtures of [`FIXTURES_DIR`], sorted by name.
+    pub fn all() -> Result<Vec<Self>, ConformanceError> {
+        let dir = Path::new(FIXTURES_DIR);
+        let entries = fs::read_dir(dir).map_err(|err| ConformanceError::Io(dir.into(), err))?;
+
+        let mut fixtures = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|err| ConformanceError::Io(dir.into(), err))?
+                .path();
+            if path.to_string_lossy().ends_with(EVENTS_SUFFIX) {
+                fixtures.push(Self::load(&path)?);
+            }
+        }
+        fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(fixtures)
+    }
+
+    /// Feed the events to a new plugin, and return the calls it sent to the
+    /// framework.
+    pub fn run(
+        &self,
+        backend: &mut dyn ConformanceBackend,
+    ) -> Result<Vec<ConformanceCall>, ConformanceError> {
+        let channel = Arc::new(RecordingChannel::default());
+        let mut text_input = TextInputPlugin::new(Arc::new(Mutex::new(NoopHandler)));
+        text_input.channel = Arc::<RecordingChannel>::downgrade(&channel);
+        let mut handler = Handler {
+            data: text_input.data.clone(),
+            handler: text_input.handler.clone(),
+        };
+
+        for event in &self.events {
+            match event {
+                ConformanceEvent::Framework { method, args } => {
+                    let invalid_call = |reason: String| ConformanceError::InvalidCall {
+                        fixture: self.name.clone(),
+                        method: method.clone(),
+                        reason,
+                    };
+                    let args = Value::try_from(args.clone())
+                        .map_err(|err| invalid_call(err.to_string()))?;
+                    match handler.handle(method, &args) {
+                        Ok(true) => {}
+                        Ok(false) => return Err(invalid_call("Not implemented".into())),
+                        Err(err) => return Err(invalid_call(err.to_string())),
+                    }
+                }
+                ConformanceEvent::Key(key) => backend.key_pressed(&mut text_input, key),
+                ConformanceEvent::Preedit(text) => backend.preedit(&mut text_input, text),
+                ConformanceEvent::Commit(text) => backend.commit(&mut text_input, text),
+            }
+        }
+
+        let calls = mem::take(&mut *channel.calls.lock());
+        Ok(calls
+            .into_iter()
+            .map(|(method, args)| ConformanceCall {
+                method,
+                args: serde_json::to_value(Value::List(args)).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Run the fixture, and compare the calls against its expectations (or
+    /// record them, see [`UPDATE_VARIABLE`]).
+    pub fn check(&self, backend: &mut dyn ConformanceBackend) -> Result<(), ConformanceError> {
+        let actual = self.run(backend)?;
+
+        if env::var_os(UPDATE_VARIABLE).is_some() {
+            let json = serde_json::to_string_pretty(&actual)
+                .map_err(|err| ConformanceError::Json(self.expected_path.clone(), err))?;
+            return fs::write(&self.expected_path, json + "\n")
+                .map_err(|err| ConformanceError::Io(self.expected_path.clone(), err));
+        }
+
+        let expected = self.expected.clone().unwrap_or_default();
+        if actual != expected {
+            return Err(ConformanceError::Mismatch {
+                fixture: self.name.clone(),
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Check all the fixtures against the |backend|.
+pub fn check_all(backend: &mut dyn ConformanceBackend) -> Result<(), ConformanceError> {
+    check_all_except(backend, &[])
+}
+
+/// Check all the fixtures against a |backend| known to fail the fixtures
+/// named in |expected_failures|. Those are reported once they pass, so that
+/// the list is kept up to date, and never rewrite the expectations.
+pub fn check_all_except(
+    backend: &mut dyn ConformanceBackend,
+    expected_failures: &[&str],
+) -> Result<(), ConformanceError> {
+    for fixture in ConformanceFixture::all()? {
+        if !expected_failures.contains(&fixture.name.as_str()) {
+            fixture.check(backend)?;
+        } else if fixture.run(backend).ok() == fixture.expected {
+            return Err(ConformanceError::UnexpectedPass(fixture.name));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct RecordingChannel {
+    calls: Mutex<Vec<(String, Vec<Value>)>>,
+}
+
+impl ClientChannel for RecordingChannel {
+    fn send(&self, method: &str, args: Vec<Value>) {
+        self.calls.lock().push((method.to_owned(), args));
+    }
+}
+
+struct NoopHandler;
+
+impl TextInputHandler for NoopHandler {
+    fn show(&mut self) {}
+
+    fn hide(&mut self) {}
+}
+
+fn read_json<T: de::DeserializeOwned>(path: &Path) -> Result<T, ConformanceError> {
+    let json = fs::read_to_string(path).map_err(|err| ConformanceError::Io(path.into(), err))?;
+    serde_json::from_str(&json).map_err(|err| ConformanceError::Json(path.into(), err))
+}
+
+fn deserialize_keysym<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let keysym = String::deserialize(deserializer)?;
+    let digits = keysym
+        .strip_prefix("0x")
+        .ok_or_else(|| de::Error::custom(format!("Expected a hexadecimal keysym: {}", keysym)))?;
+    u32::from_str_radix(digits, 16).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConformanceEvent, ConformanceFixture};
+
+    #[test]
+    fn fixtures_have_expectations() {
+        let fixtures = ConformanceFixture::all().unwrap();
+        assert!(!fixtures.is_empty());
+
+        for fixture in fixtures {
+            assert!(fixture.expected.is_some(), "{}", fixture.name);
+
+            let Some(ConformanceEvent::Framework { method, .. }) = fixture.events.first() else {
+                panic!("{} doesn't start with a framework call", fixture.name);
+            };
+            assert_eq!(method, "TextInput.setClient", "{}", fixture.name);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+/// Autofill hints of the focused field, as defined by the framework
+/// `AutofillHints` class.
+pub const AUTOFILL_HINT_USERNAME: &str = "username";
+pub const AUTOFILL_HINT_PASSWORD: &str = "password";
+pub const AUTOFILL_HINT_NEW_USERNAME: &str = "newUsername";
+pub const AUTOFILL_HINT_NEW_PASSWORD: &str = "newPassword";
+
+/// Rectangle in logical coordinates of the Flutter view.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct TextInputRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl TextInputRect {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Bounding box of the rectangle once transformed by |transform| (a 4x4
+    /// matrix in column-major order, as sent by the framework).
+    fn transform(&self, transform: &[f64; 16]) -> Self {
+        let corners = [
+            (self.x, self.y),
+            (self.x + self.width, self.y),
+            (self.x, self.y + self.height),
+            (self.x + self.width, self.y + self.height),
+        ];
+
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for (x, y) in corners {
+            let w = transform[3] * x + transform[7] * y + transform[15];
+            let w = if w == 0.0 { 1.0 } else { w };
+            let tx = (transform[0] * x + transform[4] * y + transform[12]) / w;
+            let ty = (transform[1] * x + transform[5] * y + transform[13]) / w;
+
+            min_x = min_x.min(tx);
+            min_y = min_y.min(ty);
+            max_x = max_x.max(tx);
+            max_y = max_y.max(ty);
+        }
+
+        Self::new(min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+}
+
+/// Focused text field, as reported to the observer set with
+/// [`super::TextInputPlugin::set_focus_observer`] (e.g.: for password manager
+/// integrations).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextInputFocusInfo {
+    /// `None` once the focus has left the text input.
+    pub client_id: Option<i64>,
+    /// View containing the text field, when reported by the framework.
+    pub view_id: Option<i64>,
+    /// Autofill hints of the field (e.g.: [`AUTOFILL_HINT_PASSWORD`]).
+    pub hints: Vec<String>,
+    /// Unique identifier of the field within its autofill group.
+    pub autofill_id: Option<String>,
+    /// Unique identifiers of all the fields of the autofill group (e.g.: the
+    /// username and password fields of a login form).
+    pub autofill_group: Vec<String>,
+    pub obscure_text: bool,
+    /// Bounds of the editable text, in logical coordinates of the view.
+    pub editable_rect: Option<TextInputRect>,
+    /// Caret bounds, in logical coordinates of the view.
+    pub caret_rect: Option<TextInputRect>,
+}
+
+impl TextInputFocusInfo {
+    pub fn is_focused(&self) -> bool {
+        self.client_id.is_some()
+    }
+
+    pub fn has_hint(&self, hint: &str) -> bool {
+        self.hints.iter().any(|h| h == hint)
+    }
+
+    /// Whether the field expects a password, either from its hints or because
+    /// its text is obscured.
+    pub fn is_password(&self) -> bool {
+        self.obscure_text
+            || self.has_hint(AUTOFILL_HINT_PASSWORD)
+            || self.has_hint(AUTOFILL_HINT_NEW_PASSWORD)
+    }
+
+    pub fn is_username(&self) -> bool {
+        self.has_hint(AUTOFILL_HINT_USERNAME) || self.has_hint(AUTOFILL_HINT_NEW_USERNAME)
+    }
+}
+
+/// Geometry of the focused editable, used for computing the rectangles of
+/// [`TextInputFocusInfo`].
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct EditableGeometry {
+    size_and_transform: Option<EditableSizeAndTransform>,
+    /// Caret rectangle, in the coordinates of the editable.
+    caret_rect: Option<TextInputRect>,
+}
+
+impl EditableGeometry {
+    pub(super) fn set_size_and_transform(&mut self, args: EditableSizeAndTransform) {
+        self.size_and_transform = Some(args);
+    }
+
+    pub(super) fn set_caret_rect(&mut self, caret_rect: TextInputRect) {
+        self.caret_rect = Some(caret_rect);
+    }
+
+    pub(super) fn editable_rect(&self) -> Option<TextInputRect> {
+        let args = self.size_and_transform?;
+        Some(TextInputRect::new(0.0, 0.0, args.width, args.height).transform(&args.transform))
+    }
+
+    pub(super) fn caret_rect(&self) -> Option<TextInputRect> {
+        let args = self.size_and_transform?;
+        Some(self.caret_rect?.transform(&args.transform))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub(super) struct EditableSizeAndTransform {
+    width: f64,
+    height: f64,
+    transform: [f64; 16],
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AutofillConfig {
+    pub(super) unique_identifier: String,
+    #[serde(default)]
+    pub(super) hints: Vec<String>,
+}
+
+/// Field of an autofill group. Only the autofill configuration of the other
+/// fields is needed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct AutofillField {
+    #[serde(default)]
+    pub(super) autofill: Option<AutofillConfig>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EditableGeometry, EditableSizeAndTransform, TextInputRect};
+
+    #[rustfmt::skip]
+    const TRANSLATE_AND_SCALE: [f64; 16] = [
+        2.0, 0.0, 0.0, 0.0,
+        0.0, 2.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        100.0, 50.0, 0.0, 1.0,
+    ];
+
+    #[test]
+    fn rects_are_transformed_to_view_coordinates() {
+        let mut geometry = EditableGeometry::default();
+        geometry.set_caret_rect(TextInputRect::new(10.0, 0.0, 2.0, 16.0));
+        assert_eq!(geometry.caret_rect(), None);
+
+        geometry.set_size_and_transform(EditableSizeAndTransform {
+            width: 200.0,
+            height: 20.0,
+            transform: TRANSLATE_AND_SCALE,
+        });
+
+        assert_eq!(
+            geometry.editable_rect(),
+            Some(TextInputRect::new(100.0, 50.0, 400.0, 40.0))
+        );
+        assert_eq!(
+            geometry.caret_rect(),
+            Some(TextInputRect::new(120.0, 50.0, 4.0, 32.0))
+        );
+    }
+}
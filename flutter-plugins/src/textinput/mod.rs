@@ -2,27 +2,39 @@
 //! It handles flutter/textinput type message.
 
 use tracing::debug;
-use std::sync::{Arc, RwLock, Weak};
+use std::{
+    rc::Rc,
+    sync::{Arc, RwLock, Weak},
+};
 
 use serde::{Deserialize, Serialize};
 
-use flutter_engine::codec::value::VecExt;
+use flutter_engine::codec::value::{from_value, VecExt};
 
 use flutter_engine::{
     channel::{MethodCallHandler, MethodChannel},
     codec::JSON_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    plugins::{Plugin, PluginContext},
 };
 
-use self::text_editing_state::TextEditingState;
+use self::focus::{AutofillConfig, AutofillField, EditableGeometry, EditableSizeAndTransform};
+use self::text_editing_state::{TextEditingDelta, TextEditingState};
 use flutter_engine::channel::MethodCall;
 use flutter_engine::codec::Value;
+use flutter_engine::error::ValueError;
 use parking_lot::Mutex;
 
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
+mod focus;
 mod text_editing_state;
 pub(crate) mod utils;
 
+pub use self::focus::{
+    TextInputFocusInfo, TextInputRect, AUTOFILL_HINT_NEW_PASSWORD, AUTOFILL_HINT_NEW_USERNAME,
+    AUTOFILL_HINT_PASSWORD, AUTOFILL_HINT_USERNAME,
+};
+
 pub const PLUGIN_NAME: &str = module_path!();
 pub const CHANNEL_NAME: &str = "flutter/textinput";
 
@@ -33,10 +45,26 @@ pub trait TextInputHandler {
     fn show(&mut self);
 
     fn hide(&mut self);
+
+    /// Called when the focused text field or its geometry changes (e.g.: for
+    /// positioning an input method popup next to the caret).
+    fn focus_changed(&mut self, _info: &TextInputFocusInfo) {}
+}
+
+/// Where the updates of the text field are sent: the method channel, or a
+/// recording one in the conformance tests.
+trait ClientChannel {
+    fn send(&self, method: &str, args: Vec<Value>);
+}
+
+impl ClientChannel for MethodChannel {
+    fn send(&self, method: &str, args: Vec<Value>) {
+        self.invoke_method(method, args)
+    }
 }
 
 pub struct TextInputPlugin {
-    channel: Weak<MethodChannel>,
+    channel: Weak<dyn ClientChannel>,
     data: Arc<RwLock<Data>>,
     handler: Arc<Mutex<dyn TextInputHandler + Send>>,
 }
@@ -50,6 +78,12 @@ struct Data {
     client_id: Option<i64>,
     client_args: Option<SetClientArgsText>,
     editing_state: Option<TextEditingState>,
+    /// The editing state as last known by the framework, which the deltas
+    /// are computed from.
+    framework_state: Option<TextEditingState>,
+    focus_info: TextInputFocusInfo,
+    geometry: EditableGeometry,
+    focus_observer: Option<Rc<dyn Fn(TextInputFocusInfo)>>,
 }
 
 impl Plugin for TextInputPlugin {
@@ -57,7 +91,8 @@ impl Plugin for TextInputPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
         self.channel = engine.register_channel(MethodChannel::new(
             CHANNEL_NAME,
             Handler {
@@ -70,14 +105,21 @@ impl Plugin for TextInputPlugin {
 }
 
 impl TextInputPlugin {
+    // The data holds the focus observer, which isn't `Send`, but it's only
+    // accessed from the platform thread.
+    #[allow(clippy::arc_with_non_send_sync)]
     pub fn new(handler: Arc<Mutex<dyn TextInputHandler + Send>>) -> Self {
         let data = Arc::new(RwLock::new(Data {
             client_id: None,
             client_args: None,
             editing_state: None,
+            framework_state: None,
+            focus_info: Default::default(),
+            geometry: Default::default(),
+            focus_observer: None,
         }));
         Self {
-            channel: Weak::new(),
+            channel: Weak::<MethodChannel>::new(),
             handler,
             data,
         }
@@ -85,13 +127,23 @@ impl TextInputPlugin {
 
     fn with_channel<F>(&self, f: F)
     where
-        F: FnOnce(&MethodChannel),
+        F: FnOnce(&dyn ClientChannel),
     {
         if let Some(channel) = self.channel.upgrade() {
-            f(&channel);
+            f(&*channel);
         }
     }
 
+    /// Observe the focused text field, along with its autofill hints and
+    /// geometry. The observer is called whenever any of them changes.
+    pub fn set_focus_observer(&mut self, observer: impl Fn(TextInputFocusInfo) + 'static) {
+        self.data.write().unwrap().focus_observer = Some(Rc::new(observer));
+    }
+
+    pub fn focus_info(&self) -> TextInputFocusInfo {
+        self.data.read().unwrap().focus_info.clone()
+    }
+
     pub fn with_state(&mut self, cbk: impl FnOnce(&mut TextEditingState)) {
         let mut data = self.data.write().unwrap();
         if let Some(state) = &mut data.editing_state {
@@ -105,21 +157,15 @@ impl TextInputPlugin {
             let mut args: Vec<Value> = Vec::new();
             args.push_as_value(data.client_id);
             args.push_as_value("TextInputAction.".to_owned() + action);
-            channel.invoke_method("TextInputClient.performAction", args)
+            channel.send("TextInputClient.performAction", args)
         });
     }
 
     pub fn notify_changes(&mut self) {
         let mut data = self.data.write().unwrap();
-        let client_id = data.client_id;
-        if let Some(state) = &mut (data.editing_state) {
-            if let Some(channel) = self.channel.upgrade() {
-                let mut args: Vec<Value> = Vec::new();
-                args.push_as_value(client_id);
-                args.push_as_value(state);
-                channel.invoke_method("TextInputClient.updateEditingState", args)
-            }
-        };
+        if let Some(channel) = self.channel.upgrade() {
+            data.send_editing_state(&*channel);
+        }
     }
 
     // This implementation is based on the official Windows embedder implementation:
@@ -139,10 +185,7 @@ impl TextInputPlugin {
                 state.add_characters("\n");
 
                 if let Some(channel) = self.channel.upgrade() {
-                    let mut args: Vec<Value> = Vec::new();
-                    args.push_as_value(client_id);
-                    args.push_as_value(state);
-                    channel.invoke_method("TextInputClient.updateEditingState", args)
+                    data.send_editing_state(&*channel);
                 }
             }
         }
@@ -156,48 +199,129 @@ impl TextInputPlugin {
                 let mut args: Vec<Value> = Vec::new();
                 args.push_as_value(client_id);
                 args.push_as_value(input_action);
-                channel.invoke_method("TextInputClient.performAction", args)
+                channel.send("TextInputClient.performAction", args)
             });
         }
     }
 }
 
-impl MethodCallHandler for Handler {
-    fn on_method_call(&mut self, call: MethodCall) {
-        debug!(
-            "got method call {} with args {:?}",
-            call.method(),
-            call.raw_args()
-        );
-        match call.method().as_str() {
+impl Data {
+    /// Send the editing state to the framework, or the change since the last
+    /// one it knows about when the client enabled the delta model.
+    fn send_editing_state(&mut self, channel: &dyn ClientChannel) {
+        let Some(state) = &self.editing_state else {
+            return;
+        };
+        let is_delta_model = self
+            .client_args
+            .as_ref()
+            .is_some_and(|args| args.enable_delta_model);
+
+        let mut args: Vec<Value> = Vec::new();
+        args.push_as_value(self.client_id);
+        match &self.framework_state {
+            Some(framework_state) if is_delta_model => {
+                args.push_as_value(TextEditingDeltas {
+                    deltas: vec![state.delta_from(framework_state)],
+                });
+                channel.send("TextInputClient.updateEditingStateWithDeltas", args)
+            }
+            _ => {
+                args.push_as_value(state);
+                channel.send("TextInputClient.updateEditingState", args)
+            }
+        }
+        self.framework_state = Some(state.clone());
+    }
+}
+
+impl Handler {
+    /// Update the focus info and notify the handler and the observer when it
+    /// has changed. The observer is called without holding the data lock, so
+    /// that it can use the plugin (e.g.: for filling the text field).
+    fn update_focus_info(&self, update: impl FnOnce(&mut Data)) {
+        let (info, observer) = {
+            let mut data = self.data.write().unwrap();
+            let previous = data.focus_info.clone();
+
+            update(&mut data);
+            data.focus_info.editable_rect = data.geometry.editable_rect();
+            data.focus_info.caret_rect = data.geometry.caret_rect();
+
+            if data.focus_info == previous {
+                return;
+            }
+
+            (data.focus_info.clone(), data.focus_observer.clone())
+        };
+
+        self.handler.lock().focus_changed(&info);
+
+        if let Some(observer) = observer {
+            observer(info);
+        }
+    }
+}
+
+impl Handler {
+    /// Apply a method call of the framework. Returns `Ok(false)` for the
+    /// methods which aren't implemented.
+    fn handle(&mut self, method: &str, args: &Value) -> Result<bool, ValueError> {
+        match method {
             "TextInput.setClient" => {
-                let mut data = self.data.write().unwrap();
-                let args: SetClientArgs = call.args();
-                data.client_id = Some(args.0);
-                data.client_args = Some(args.1);
-                call.success_empty()
+                let args: SetClientArgs = from_value(args)?;
+                self.update_focus_info(|data| {
+                    data.focus_info = args.1.focus_info(args.0);
+                    data.geometry = Default::default();
+                    data.client_id = Some(args.0);
+                    data.client_args = Some(args.1);
+                    // The new client sends its editing state right after.
+                    data.editing_state.take();
+                    data.framework_state.take();
+                });
             }
             "TextInput.clearClient" => {
-                let mut data = self.data.write().unwrap();
-                data.client_id = None;
-                data.editing_state.take();
-                call.success_empty()
+                self.update_focus_info(|data| {
+                    data.focus_info = Default::default();
+                    data.geometry = Default::default();
+                    data.client_id = None;
+                    data.editing_state.take();
+                    data.framework_state.take();
+                });
+            }
+            "TextInput.setEditableSizeAndTransform" => {
+                let args: EditableSizeAndTransform = from_value(args)?;
+                self.update_focus_info(|data| data.geometry.set_size_and_transform(args));
+            }
+            "TextInput.setCaretRect" => {
+                let caret_rect: TextInputRect = from_value(args)?;
+                self.update_focus_info(|data| data.geometry.set_caret_rect(caret_rect));
             }
             "TextInput.setEditingState" => {
+                let state: TextEditingState = from_value(args)?;
                 let mut data = self.data.write().unwrap();
-                let state: TextEditingState = call.args();
+                data.framework_state.replace(state.clone());
                 data.editing_state.replace(state);
-                call.success_empty()
-            }
-            "TextInput.show" => {
-                self.handler.lock().show();
-                call.success_empty()
             }
-            "TextInput.hide" => {
-                self.handler.lock().hide();
-                call.success_empty()
-            }
-            _ => call.not_implemented(),
+            "TextInput.show" => self.handler.lock().show(),
+            "TextInput.hide" => self.handler.lock().hide(),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        debug!(
+            "got method call {} with args {:?}",
+            call.method(),
+            call.raw_args()
+        );
+        match self.handle(call.method(), call.raw_args()) {
+            Ok(true) => call.success_empty(),
+            Ok(false) => call.not_implemented(),
+            Err(err) => call.error("invalid-args", err.to_string(), Value::Null),
         }
     }
 }
@@ -215,18 +339,143 @@ struct SetClientArgsText {
     action_label: Option<String>,
     text_capitalization: String,
     input_type: SetClientArgsInputType,
+    view_id: Option<i64>,
+    autofill: Option<AutofillConfig>,
+    #[serde(default)]
+    fields: Vec<AutofillField>,
+    /// Whether the changes are sent as deltas, see
+    /// `TextInputClient.updateEditingStateWithDeltas`.
+    #[serde(default)]
+    enable_delta_model: bool,
 }
 
 impl SetClientArgsText {
+    fn focus_info(&self, client_id: i64) -> TextInputFocusInfo {
+        let autofill_group = self
+            .fields
+            .iter()
+            .filter_map(|field| field.autofill.as_ref())
+            .map(|autofill| autofill.unique_identifier.clone())
+            .collect();
+
+        TextInputFocusInfo {
+            client_id: Some(client_id),
+            view_id: self.view_id,
+            hints: self
+                .autofill
+                .as_ref()
+                .map(|autofill| autofill.hints.clone())
+                .unwrap_or_default(),
+            autofill_id: self
+                .autofill
+                .as_ref()
+                .map(|autofill| autofill.unique_identifier.clone()),
+            autofill_group,
+            obscure_text: self.obscure_text,
+            editable_rect: None,
+            caret_rect: None,
+        }
+    }
+
     fn is_multiline_newline_action(&self) -> bool {
         self.input_type.name.as_str() == MULTILINE_INPUT_TYPE
             && self.input_action.as_str() == INPUT_ACTION_NEWLINE
     }
 }
 
+#[derive(Serialize)]
+struct TextEditingDeltas {
+    deltas: Vec<TextEditingDelta>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct SetClientArgsInputType {
     signed: Option<bool>,
     name: String,
     decimal: Option<bool>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use flutter_engine::codec::Value;
+    use parking_lot::Mutex;
+    use serde_json::json;
+
+    use super::{Handler, TextInputHandler, TextInputPlugin};
+
+    struct NoopHandler;
+
+    impl TextInputHandler for NoopHandler {
+        fn show(&mut self) {}
+
+        fn hide(&mut self) {}
+    }
+
+    fn handle(handler: &mut Handler, method: &str, args: serde_json::Value) {
+        let args = Value::try_from(args).unwrap();
+        assert!(handler.handle(method, &args).unwrap(), "{}", method);
+    }
+
+    fn set_client(handler: &mut Handler, client_id: i64) {
+        let args = json!([
+            client_id,
+            {
+                "autocorrect": true,
+                "inputAction": "TextInputAction.done",
+                "obscureText": false,
+                "keyboardAppearance": "Brightness.light",
+                "actionLabel": null,
+                "textCapitalization": "TextCapitalization.none",
+                "inputType": { "name": "TextInputType.text", "signed": null, "decimal": null },
+                "viewId": 0
+            }
+        ]);
+        handle(handler, "TextInput.setClient", args);
+    }
+
+    fn set_editing_state(handler: &mut Handler, text: &str) {
+        let offset = text.encode_utf16().count();
+        let args = json!({
+            "composingBase": -1,
+            "composingExtent": -1,
+            "selectionAffinity": "TextAffinity.downstream",
+            "selectionBase": offset,
+            "selectionExtent": offset,
+            "selectionIsDirectional": false,
+            "text": text,
+        });
+        handle(handler, "TextInput.setEditingState", args);
+    }
+
+    fn editing_text(plugin: &TextInputPlugin) -> Option<serde_json::Value> {
+        let data = plugin.data.read().unwrap();
+        let state = data.editing_state.as_ref()?;
+        Some(serde_json::to_value(state).unwrap()["text"].clone())
+    }
+
+    #[test]
+    fn set_client_drops_the_editing_state_of_the_previous_client() {
+        let mut plugin = TextInputPlugin::new(Arc::new(Mutex::new(NoopHandler)));
+        let mut handler = Handler {
+            data: plugin.data.clone(),
+            handler: plugin.handler.clone(),
+        };
+
+        set_client(&mut handler, 1);
+        set_editing_state(&mut handler, "first");
+        assert_eq!(editing_text(&plugin), Some(json!("first")));
+
+        // The edits made before the new client sends its state are dropped,
+        // instead of being applied to the text of the previous client.
+        set_client(&mut handler, 2);
+        assert_eq!(editing_text(&plugin), None);
+        plugin.with_state(|state| state.add_characters("x"));
+        assert_eq!(editing_text(&plugin), None);
+
+        set_editing_state(&mut handler, "");
+        plugin.with_state(|state| state.add_characters("x"));
+        assert_eq!(editing_text(&plugin), Some(json!("x")));
+    }
+}
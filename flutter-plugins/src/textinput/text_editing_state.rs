@@ -5,8 +5,10 @@ use serde::{Deserialize, Serialize};
 use crate::textinput::utils::{OwnedStringUtils, StringUtils};
 use flutter_engine::codec::{value::from_value, Value};
 
-#[derive(Serialize, Deserialize, Default, Debug)]
-#[serde(rename_all = "camelCase")]
+/// The text being edited. Its offsets are char indices, whereas the ones of
+/// the framework are in UTF-16 code units.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(from = "TextEditingValue", into = "TextEditingValue")]
 pub struct TextEditingState {
     composing_base: i64,
     composing_extent: i64,
@@ -17,6 +19,85 @@ pub struct TextEditingState {
     text: String,
 }
 
+/// The editing state, as exchanged with the framework.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextEditingValue {
+    composing_base: i64,
+    composing_extent: i64,
+    selection_affinity: String,
+    selection_base: i64,
+    selection_extent: i64,
+    selection_is_directional: bool,
+    text: String,
+}
+
+impl From<TextEditingValue> for TextEditingState {
+    fn from(value: TextEditingValue) -> Self {
+        let text = value.text;
+        let char_index = |offset: i64| {
+            if offset < 0 {
+                offset
+            } else {
+                text.char_index_of_utf16(offset as usize) as i64
+            }
+        };
+
+        Self {
+            composing_base: char_index(value.composing_base),
+            composing_extent: char_index(value.composing_extent),
+            selection_affinity: value.selection_affinity,
+            selection_base: char_index(value.selection_base),
+            selection_extent: char_index(value.selection_extent),
+            selection_is_directional: value.selection_is_directional,
+            text,
+        }
+    }
+}
+
+impl From<TextEditingState> for TextEditingValue {
+    fn from(state: TextEditingState) -> Self {
+        let text = state.text;
+        let utf16_offset = |index: i64| {
+            if index < 0 {
+                index
+            } else {
+                text.utf16_offset_of_char(index as usize) as i64
+            }
+        };
+
+        Self {
+            composing_base: utf16_offset(state.composing_base),
+            composing_extent: utf16_offset(state.composing_extent),
+            selection_affinity: state.selection_affinity,
+            selection_base: utf16_offset(state.selection_base),
+            selection_extent: utf16_offset(state.selection_extent),
+            selection_is_directional: state.selection_is_directional,
+            text,
+        }
+    }
+}
+
+/// A change of the editing state, sent to the framework instead of the whole
+/// state when the client enabled the delta model. The offsets are in UTF-16
+/// code units, the replaced range is the one of |old_text|.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TextEditingDelta {
+    old_text: String,
+    delta_text: String,
+    /// -1 when the text didn't change (i.e.: only the selection or the
+    /// composing region did).
+    delta_start: i64,
+    delta_end: i64,
+    selection_base: i64,
+    selection_extent: i64,
+    selection_affinity: String,
+    selection_is_directional: bool,
+    composing_base: i64,
+    composing_extent: i64,
+}
+
 enum Direction {
     Left,
     Right,
@@ -70,6 +151,57 @@ impl TextEditingState {
         }
     }
 
+    /// Range of the text being composed by the input method, if any.
+    fn composing_range(&self) -> Option<Range<usize>> {
+        let count = self.text.char_count() as i64;
+        (0 <= self.composing_base
+            && self.composing_base <= self.composing_extent
+            && self.composing_extent <= count)
+            .then_some(self.composing_base as usize..self.composing_extent as usize)
+    }
+
+    fn clear_composing(&mut self) {
+        self.composing_base = -1;
+        self.composing_extent = -1;
+    }
+
+    /// Remove the text being composed, leaving the caret in its place.
+    /// Returns whether there was any.
+    pub fn delete_composing(&mut self) -> bool {
+        let Some(range) = self.composing_range() else {
+            return false;
+        };
+        self.clear_composing();
+        self.move_to(range.start);
+        self.text.remove_chars(range);
+        true
+    }
+
+    /// Show the |text| being composed by the input method in place of the
+    /// previous one (or of the selection), with the caret at its end. An
+    /// empty text cancels the composition. Returns whether the state changed.
+    pub fn set_composing_text(&mut self, text: &str) -> bool {
+        let previous = self
+            .composing_range()
+            .and_then(|range| self.text.byte_range_of_chars(range))
+            .map(|range| &self.text[range]);
+        if previous.unwrap_or_default() == text {
+            return false;
+        }
+
+        self.delete_composing();
+        if text.is_empty() {
+            return true;
+        }
+
+        self.delete_selected();
+        let start = self.selection_extent.max(0);
+        self.add_characters(text);
+        self.composing_base = start;
+        self.composing_extent = start + text.char_count() as i64;
+        true
+    }
+
     pub fn add_characters(&mut self, c: &str) {
         self.delete_selected();
         let index = self
@@ -159,6 +291,48 @@ impl TextEditingState {
         self.select_or_move_to(p, select);
     }
 
+    /// Change from the |old| state to this one. The replaced range is the
+    /// smallest one, between the common prefix and suffix of the texts.
+    pub(crate) fn delta_from(&self, old: &TextEditingState) -> TextEditingDelta {
+        let old_chars: Vec<char> = old.text.chars().collect();
+        let new_chars: Vec<char> = self.text.chars().collect();
+        let prefix = old_chars
+            .iter()
+            .zip(&new_chars)
+            .take_while(|(old, new)| old == new)
+            .count();
+        let suffix = old_chars[prefix..]
+            .iter()
+            .rev()
+            .zip(new_chars[prefix..].iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count();
+
+        let (delta_text, delta_start, delta_end) = if old.text == self.text {
+            (String::new(), -1, -1)
+        } else {
+            (
+                new_chars[prefix..new_chars.len() - suffix].iter().collect(),
+                old.text.utf16_offset_of_char(prefix) as i64,
+                old.text.utf16_offset_of_char(old_chars.len() - suffix) as i64,
+            )
+        };
+
+        let value = TextEditingValue::from(self.clone());
+        TextEditingDelta {
+            old_text: old.text.clone(),
+            delta_text,
+            delta_start,
+            delta_end,
+            selection_base: value.selection_base,
+            selection_extent: value.selection_extent,
+            selection_affinity: value.selection_affinity,
+            selection_is_directional: value.selection_is_directional,
+            composing_base: value.composing_base,
+            composing_extent: value.composing_extent,
+        }
+    }
+
     pub fn get_selected_text(&self) -> &str {
         if let Some(range) = self.text.byte_range_of_chars(self.get_selection_range()) {
             &self.text[range]
@@ -228,3 +402,103 @@ impl TextEditingState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::TextEditingState;
+
+    fn framework_value(text: &str, selection: i64, composing: (i64, i64)) -> serde_json::Value {
+        json!({
+            "composingBase": composing.0,
+            "composingExtent": composing.1,
+            "selectionAffinity": "TextAffinity.downstream",
+            "selectionBase": selection,
+            "selectionExtent": selection,
+            "selectionIsDirectional": false,
+            "text": text,
+        })
+    }
+
+    #[test]
+    fn framework_offsets_are_converted_to_char_indices() {
+        // The emoji is a surrogate pair, i.e.: 2 UTF-16 code units.
+        let value = framework_value("a😀b", 3, (1, 3));
+        let state: TextEditingState = serde_json::from_value(value).unwrap();
+        assert_eq!(state.selection_base, 2);
+        assert_eq!(state.selection_extent, 2);
+        assert_eq!((state.composing_base, state.composing_extent), (1, 2));
+        assert_eq!(state.get_selected_text(), "");
+    }
+
+    #[test]
+    fn char_indices_are_converted_to_framework_offsets() {
+        let value = framework_value("a😀b", 3, (-1, -1));
+        let mut state: TextEditingState = serde_json::from_value(value).unwrap();
+        state.add_characters("😀");
+        assert_eq!(state.text, "a😀😀b");
+        assert_eq!(state.selection_base, 3);
+
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            framework_value("a😀😀b", 5, (-1, -1))
+        );
+    }
+
+    #[test]
+    fn composing_text_replaces_the_previous_one() {
+        let value = framework_value("ab", 1, (-1, -1));
+        let mut state: TextEditingState = serde_json::from_value(value).unwrap();
+
+        assert!(state.set_composing_text("に"));
+        assert!(state.set_composing_text("日本"));
+        assert!(!state.set_composing_text("日本"));
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            framework_value("a日本b", 3, (1, 3))
+        );
+
+        assert!(state.set_composing_text(""));
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            framework_value("ab", 1, (-1, -1))
+        );
+        assert!(!state.delete_composing());
+    }
+
+    #[test]
+    fn deltas_replace_the_changed_range() {
+        let value = framework_value("a😀c", 3, (-1, -1));
+        let old: TextEditingState = serde_json::from_value(value).unwrap();
+
+        let mut state = old.clone();
+        state.backspace();
+        state.add_characters("b");
+        let delta = serde_json::to_value(state.delta_from(&old)).unwrap();
+        assert_eq!(delta["oldText"], "a😀c");
+        assert_eq!(delta["deltaText"], "b");
+        assert_eq!(delta["deltaStart"], 1);
+        assert_eq!(delta["deltaEnd"], 3);
+        assert_eq!(delta["selectionBase"], 2);
+
+        let mut state = old.clone();
+        state.move_to(0);
+        let delta = serde_json::to_value(state.delta_from(&old)).unwrap();
+        assert_eq!(delta["deltaText"], "");
+        assert_eq!(delta["deltaStart"], -1);
+        assert_eq!(delta["deltaEnd"], -1);
+        assert_eq!(delta["selectionBase"], 0);
+    }
+
+    #[test]
+    fn backspace_removes_whole_surrogate_pairs() {
+        let value = framework_value("a😀b", 3, (-1, -1));
+        let mut state: TextEditingState = serde_json::from_value(value).unwrap();
+        state.backspace();
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            framework_value("ab", 1, (-1, -1))
+        );
+    }
+}
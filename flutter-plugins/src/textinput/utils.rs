@@ -6,6 +6,11 @@ pub trait StringUtils {
     fn char_count(&self) -> usize;
     fn byte_index_of_char(&self, char_index: usize) -> Option<usize>;
     fn byte_range_of_chars(&self, char_range: Range<usize>) -> Option<Range<usize>>;
+    /// Index of the char at the |offset| in UTF-16 code units (i.e.: the
+    /// offsets of the framework). An offset within a surrogate pair is the
+    /// one of its char.
+    fn char_index_of_utf16(&self, offset: usize) -> usize;
+    fn utf16_offset_of_char(&self, char_index: usize) -> usize;
 }
 
 pub trait OwnedStringUtils {
@@ -43,6 +48,18 @@ impl StringUtils for str {
             None => None,
         }
     }
+    fn char_index_of_utf16(&self, offset: usize) -> usize {
+        let mut utf16_offset = 0;
+        self.chars()
+            .take_while(|c| {
+                utf16_offset += c.len_utf16();
+                utf16_offset <= offset
+            })
+            .count()
+    }
+    fn utf16_offset_of_char(&self, char_index: usize) -> usize {
+        self.chars().take(char_index).map(char::len_utf16).sum()
+    }
 }
 
 impl OwnedStringUtils for String {
@@ -96,4 +113,18 @@ mod tests {
         assert_eq!(s.byte_range_of_chars(10..12), Some(10..11));
         assert_eq!(s.byte_range_of_chars(11..11), None);
     }
+
+    #[test]
+    fn test_utf16_offsets() {
+        let s = String::from("a😀b");
+        assert_eq!(s.char_index_of_utf16(0), 0);
+        assert_eq!(s.char_index_of_utf16(1), 1);
+        assert_eq!(s.char_index_of_utf16(2), 1);
+        assert_eq!(s.char_index_of_utf16(3), 2);
+        assert_eq!(s.char_index_of_utf16(4), 3);
+        assert_eq!(s.char_index_of_utf16(7), 3);
+        assert_eq!(s.utf16_offset_of_char(0), 0);
+        assert_eq!(s.utf16_offset_of_char(2), 3);
+        assert_eq!(s.utf16_offset_of_char(5), 4);
+    }
 }
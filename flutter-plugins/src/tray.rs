@@ -0,0 +1,291 @@
+//! Plugin to show a status icon in the system tray (e.g.: the one of a panel
+//! or of a dock), with a menu. It handles flutter-rs/tray type message.
+//!
+//! The icon is described as a whole by `show`, which is invoked again with
+//! the new description when anything changes (e.g.: the label of a menu
+//! item). The clicks on the icon and on the menu items are reported as
+//! events.
+use std::{
+    collections::HashSet,
+    sync::{Arc, Weak},
+};
+
+use flutter_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{value::from_value_owned, Value, JSON_CODEC},
+    plugins::{Plugin, PluginContext},
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter-rs/tray";
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TrayStatus {
+    /// The icon may be hidden by the tray (e.g.: in an overflow menu).
+    Passive,
+    #[default]
+    Active,
+    /// The icon is highlighted (e.g.: for a new message).
+    NeedsAttention,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrayMenuItem {
+    /// Reported when the item is clicked, must be positive and unique within
+    /// the menu.
+    pub id: i32,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default = "default_true")]
+    pub is_enabled: bool,
+    #[serde(default)]
+    pub is_separator: bool,
+    /// Whether the item is checked, `None` for the items which can't be.
+    #[serde(default)]
+    pub is_checked: Option<bool>,
+    /// Items of the submenu opened by this item.
+    #[serde(default)]
+    pub children: Vec<TrayMenuItem>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrayIcon {
+    /// Shown by some trays next to the icon, and used as its accessible name.
+    #[serde(default)]
+    pub title: String,
+    /// Name of the icon in the icon theme (e.g.: `mail-unread`).
+    #[serde(default)]
+    pub icon_name: Option<String>,
+    /// PNG file, for the icons which aren't part of the icon theme.
+    #[serde(default)]
+    pub icon_path: Option<String>,
+    #[serde(default)]
+    pub tooltip: Option<String>,
+    #[serde(default)]
+    pub status: TrayStatus,
+    #[serde(default)]
+    pub menu: Vec<TrayMenuItem>,
+}
+
+impl TrayIcon {
+    fn validate(&self) -> Result<(), String> {
+        if self.icon_name.is_none() && self.icon_path.is_none() {
+            return Err("Either an icon name or an icon path is required".into());
+        }
+
+        let mut ids = HashSet::new();
+        let mut items: Vec<&TrayMenuItem> = self.menu.iter().collect();
+        while let Some(item) = items.pop() {
+            if item.id <= 0 {
+                return Err(format!("Invalid menu item id: {}", item.id));
+            }
+            if !ids.insert(item.id) {
+                return Err(format!("Duplicate menu item id: {}", item.id));
+            }
+            items.extend(&item.children);
+        }
+        Ok(())
+    }
+}
+
+/// Interaction with the icon, in screen coordinates when known (or `0`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayEvent {
+    /// The icon was clicked (e.g.: for showing the main window).
+    Activate {
+        x: i32,
+        y: i32,
+    },
+    /// The icon was middle-clicked.
+    SecondaryActivate {
+        x: i32,
+        y: i32,
+    },
+    Scroll {
+        delta: i32,
+        is_horizontal: bool,
+    },
+    MenuItemClicked {
+        id: i32,
+    },
+}
+
+#[derive(Serialize)]
+struct PositionArgs {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize)]
+struct ScrollArgs {
+    delta: i32,
+    orientation: &'static str,
+}
+
+pub trait TrayHandler {
+    /// Whether a tray is running, which shows the icon.
+    fn is_available(&mut self) -> bool;
+
+    /// Show |icon| in the tray, or update it if it's already shown.
+    fn show(&mut self, icon: TrayIcon);
+
+    fn hide(&mut self);
+}
+
+pub struct TrayPlugin {
+    channel: Weak<MethodChannel>,
+    handler: Arc<Mutex<dyn TrayHandler + Send>>,
+}
+
+impl TrayPlugin {
+    pub fn new(handler: Arc<Mutex<dyn TrayHandler + Send>>) -> Self {
+        Self {
+            channel: Weak::new(),
+            handler,
+        }
+    }
+
+    pub fn notify_event(&self, event: TrayEvent) {
+        let Some(channel) = self.channel.upgrade() else {
+            return;
+        };
+
+        match event {
+            TrayEvent::Activate { x, y } => {
+                channel.invoke_method("onActivate", PositionArgs { x, y });
+            }
+            TrayEvent::SecondaryActivate { x, y } => {
+                channel.invoke_method("onSecondaryActivate", PositionArgs { x, y });
+            }
+            TrayEvent::Scroll {
+                delta,
+                is_horizontal,
+            } => {
+                let orientation = if is_horizontal {
+                    "horizontal"
+                } else {
+                    "vertical"
+                };
+                channel.invoke_method("onScroll", ScrollArgs { delta, orientation });
+            }
+            TrayEvent::MenuItemClicked { id } => {
+                channel.invoke_method("onMenuItemClicked", id);
+            }
+        }
+    }
+}
+
+impl Plugin for TrayPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: self.handler.clone(),
+            },
+            &JSON_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    handler: Arc<Mutex<dyn TrayHandler + Send>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        match call.method().as_str() {
+            "isAvailable" => call.success(self.handler.lock().is_available()),
+            "show" => {
+                let icon = from_value_owned::<TrayIcon>(call.raw_args())
+                    .map_err(|err| err.to_string())
+                    .and_then(|icon| icon.validate().map(|()| icon));
+                match icon {
+                    Ok(icon) => {
+                        self.handler.lock().show(icon);
+                        call.success_empty()
+                    }
+                    Err(err) => {
+                        error!("[plugin: tray] Invalid arguments: {}", err);
+                        call.error("invalid-args", err, Value::Null)
+                    }
+                }
+            }
+            "hide" => {
+                self.handler.lock().hide();
+                call.success_empty()
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use flutter_engine::codec::{value::from_value_owned, Value};
+
+    use super::{TrayIcon, TrayStatus};
+
+    fn map(entries: Vec<(&str, Value)>) -> Value {
+        let map: HashMap<String, Value> = entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        Value::Map(map)
+    }
+
+    fn item(id: i32, children: Vec<Value>) -> Value {
+        map(vec![
+            ("id", Value::I32(id)),
+            ("label", Value::String(format!("Item {id}"))),
+            ("children", Value::List(children)),
+        ])
+    }
+
+    fn parse_icon(menu: Vec<Value>) -> TrayIcon {
+        let args = map(vec![
+            ("iconName", Value::String("mail-unread".into())),
+            ("status", Value::String("needsAttention".into())),
+            ("menu", Value::List(menu)),
+        ]);
+        from_value_owned(&args).unwrap()
+    }
+
+    #[test]
+    fn parses_the_menu() {
+        let icon = parse_icon(vec![item(1, vec![item(2, vec![])])]);
+        assert_eq!(icon.status, TrayStatus::NeedsAttention);
+        assert_eq!(icon.menu[0].children[0].label, "Item 2");
+        assert!(icon.menu[0].is_enabled);
+        assert_eq!(icon.menu[0].is_checked, None);
+        assert_eq!(icon.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_invalid_menu_item_ids() {
+        let icon = parse_icon(vec![item(1, vec![item(1, vec![])])]);
+        assert_eq!(icon.validate(), Err("Duplicate menu item id: 1".into()));
+
+        let icon = parse_icon(vec![item(0, vec![])]);
+        assert_eq!(icon.validate(), Err("Invalid menu item id: 0".into()));
+
+        let no_icon = TrayIcon::default();
+        assert!(no_icon.validate().is_err());
+    }
+}
@@ -6,16 +6,22 @@ use serde::{Deserialize, Serialize};
 
 use flutter_engine::{
     channel::{MethodCallHandler, MethodChannel},
-    codec::JSON_CODEC,
-    plugins::Plugin,
-    FlutterEngine,
+    codec::{value::from_value_owned, Value, JSON_CODEC},
+    ffi::FlutterViewId,
+    plugins::{Plugin, PluginContext},
 };
 
 use flutter_engine::channel::MethodCall;
 use parking_lot::Mutex;
+use tracing::error;
+
+use crate::{
+    anchor::{AnchorRect, AnchorRegistry, AnchorsInvalidated},
+    color::ColorInfo,
+};
 
 const PLUGIN_NAME: &str = module_path!();
-const CHANNEL_NAME: &str = "flutter-rs/window";
+pub const CHANNEL_NAME: &str = "flutter-rs/window";
 
 pub trait WindowHandler {
     fn close(&mut self);
@@ -36,6 +42,10 @@ pub trait WindowHandler {
 
     fn is_visible(&mut self) -> bool;
 
+    fn is_fullscreen(&mut self) -> bool {
+        false
+    }
+
     fn set_pos(&mut self, pos: PositionParams);
 
     fn get_pos(&mut self) -> PositionParams;
@@ -43,11 +53,100 @@ pub trait WindowHandler {
     fn start_drag(&mut self);
 
     fn end_drag(&mut self);
+
+    /// Maximum size suggested by the windowing system for the window (e.g.:
+    /// the output size minus any panels), if known.
+    fn get_recommended_max_size(&mut self) -> Option<SizeParams> {
+        None
+    }
+
+    /// Update the size (in logical pixels) of the area reserved by a layer
+    /// shell surface (e.g.: a status bar). Ignored by regular windows.
+    fn set_exclusive_zone(&mut self, _exclusive_zone: i32) {}
+
+    /// Update the margin of a layer shell surface. Ignored by regular
+    /// windows.
+    fn set_margin(&mut self, _margin: MarginParams) {}
+
+    /// Zoom of the window content applied by the embedder (i.e.: `1.0` when
+    /// not zoomed).
+    fn get_zoom(&mut self) -> f64 {
+        1.0
+    }
+
+    /// Zoom the window content. The change is reported through
+    /// `onZoomChanged`, with the zoom actually applied (i.e.: clamped).
+    fn set_zoom(&mut self, _zoom: f64) {}
+
+    /// Whether the window background is made opaque because the desktop asks
+    /// for reduced transparency (e.g.: with high contrast). Changes are
+    /// reported through `onTransparencyReducedChanged`.
+    fn is_transparency_reduced(&mut self) -> bool {
+        false
+    }
+
+    /// Flag the window as needing the user's attention (e.g.: flashing its
+    /// taskbar entry), or clear the flag. The flag is also cleared once the
+    /// window gets the focus, and ignored where unsupported.
+    fn set_urgent(&mut self, _urgent: bool) {}
+
+    /// What the window actually supports, given the backend and the windowing
+    /// system (e.g.: Wayland doesn't let clients position their windows).
+    /// Nothing is supported by default.
+    fn capabilities(&mut self) -> WindowCapabilities {
+        WindowCapabilities::default()
+    }
+
+    /// Keep the window above the others. Ignored where unsupported.
+    fn set_always_on_top(&mut self, _always_on_top: bool) {}
+
+    /// Minimum size of the window, in logical pixels. Ignored where
+    /// unsupported.
+    fn set_min_size(&mut self, _size: Option<SizeParams>) {}
+
+    /// Maximum size of the window, in logical pixels. Ignored where
+    /// unsupported.
+    fn set_max_size(&mut self, _size: Option<SizeParams>) {}
+
+    fn set_fullscreen(&mut self, _fullscreen: bool) {}
+
+    /// Color characteristics of the display(s) the window is shown on.
+    /// Changes are reported through `onColorInfoChanged`.
+    fn color_info(&mut self) -> ColorInfo {
+        ColorInfo::default()
+    }
+
+    /// Whether the window is seen by the user, as estimated by the backend.
+    /// Changes are reported through `onVisibilityChanged`.
+    fn visibility(&mut self) -> Visibility {
+        Visibility::Visible
+    }
+
+    /// Identifier of the window among the ones of the application (e.g.: for
+    /// [`WindowHandler::raise_above`]). `0` by default, for applications with
+    /// a single window.
+    fn window_id(&mut self) -> u64 {
+        0
+    }
+
+    /// Stack the window above the |other| window of the application. Returns
+    /// whether the request is handled, in which case its outcome is reported
+    /// through `onStackingChanged`. Unsupported by default.
+    fn raise_above(&mut self, _other: u64) -> bool {
+        false
+    }
+
+    /// Stack the window below the |other| window of the application, see
+    /// [`WindowHandler::raise_above`].
+    fn lower_below(&mut self, _other: u64) -> bool {
+        false
+    }
 }
 
 pub struct WindowPlugin {
     channel: Weak<MethodChannel>,
     handler: Arc<Mutex<dyn WindowHandler + Send>>,
+    anchors: Option<AnchorRegistry>,
 }
 
 impl WindowPlugin {
@@ -55,6 +154,69 @@ impl WindowPlugin {
         Self {
             channel: Weak::new(),
             handler,
+            anchors: None,
+        }
+    }
+
+    /// Hand out the anchor tokens of `widgetRectToAnchor` from |anchors|.
+    /// Without it, the method is not implemented.
+    pub fn with_anchors(mut self, anchors: AnchorRegistry) -> Self {
+        self.anchors = Some(anchors);
+        self
+    }
+
+    /// Report the tokens invalidated by a change of the view geometry, so that
+    /// the app can anchor its popups again.
+    pub fn notify_anchors_invalidated(&self, invalidated: AnchorsInvalidated) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onAnchorsInvalidated", invalidated);
+        }
+    }
+
+    pub fn notify_recommended_max_size_changed(&self, size: Option<SizeParams>) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onRecommendedMaxSizeChanged", size);
+        }
+    }
+
+    /// Report a change of the window state, whoever initiated it. Sent
+    /// before the window metrics matching the new state.
+    pub fn notify_state_changed(&self, state: WindowStateParams) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onStateChanged", state);
+        }
+    }
+
+    pub fn notify_zoom_changed(&self, zoom: f64) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onZoomChanged", zoom);
+        }
+    }
+
+    pub fn notify_transparency_reduced_changed(&self, reduced: bool) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onTransparencyReducedChanged", reduced);
+        }
+    }
+
+    pub fn notify_color_info_changed(&self, color_info: ColorInfo) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onColorInfoChanged", color_info);
+        }
+    }
+
+    pub fn notify_visibility_changed(&self, visibility: Visibility) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onVisibilityChanged", visibility);
+        }
+    }
+
+    /// Report the stacking order of the windows of the application, after a
+    /// `raiseAbove` or `lowerBelow` request, or once the compositor restacked
+    /// them.
+    pub fn notify_stacking_changed(&self, stacking: StackingParams) {
+        if let Some(channel) = self.channel.upgrade() {
+            channel.invoke_method("onStackingChanged", stacking);
         }
     }
 }
@@ -64,11 +226,13 @@ impl Plugin for WindowPlugin {
         PLUGIN_NAME
     }
 
-    fn init(&mut self, engine: &FlutterEngine) {
+    fn init(&mut self, context: &PluginContext) {
+        let engine = context.engine();
         self.channel = engine.register_channel(MethodChannel::new(
             CHANNEL_NAME,
             Handler {
                 handler: self.handler.clone(),
+                anchors: self.anchors.clone(),
             },
             &JSON_CODEC,
         ));
@@ -77,6 +241,7 @@ impl Plugin for WindowPlugin {
 
 struct Handler {
     handler: Arc<Mutex<dyn WindowHandler + Send>>,
+    anchors: Option<AnchorRegistry>,
 }
 
 impl MethodCallHandler for Handler {
@@ -97,6 +262,7 @@ impl MethodCallHandler for Handler {
             "isMaximized" => call.success(self.handler.lock().is_maximized()),
             "isIconified" => call.success(self.handler.lock().is_iconified()),
             "isVisible" => call.success(self.handler.lock().is_visible()),
+            "isFullscreen" => call.success(self.handler.lock().is_fullscreen()),
             "show" => {
                 self.handler.lock().show();
                 call.success_empty()
@@ -123,6 +289,87 @@ impl MethodCallHandler for Handler {
                 self.handler.lock().end_drag();
                 call.success_empty()
             }
+            "getRecommendedMaxSize" => call.success(self.handler.lock().get_recommended_max_size()),
+            "setExclusiveZone" => {
+                let exclusive_zone: i32 = call.args();
+                self.handler.lock().set_exclusive_zone(exclusive_zone);
+                call.success_empty()
+            }
+            "setMargin" => {
+                let args: MarginParams = call.args();
+                self.handler.lock().set_margin(args);
+                call.success_empty()
+            }
+            "getZoom" => call.success(self.handler.lock().get_zoom()),
+            "isTransparencyReduced" => call.success(self.handler.lock().is_transparency_reduced()),
+            "setUrgent" => {
+                let urgent: bool = call.args();
+                self.handler.lock().set_urgent(urgent);
+                call.success_empty()
+            }
+            "setZoom" => {
+                let zoom: f64 = call.args();
+                self.handler.lock().set_zoom(zoom);
+                call.success_empty()
+            }
+            "getCapabilities" => call.success(self.handler.lock().capabilities()),
+            "getColorInfo" => call.success(self.handler.lock().color_info()),
+            "getVisibility" => call.success(self.handler.lock().visibility()),
+            "setAlwaysOnTop" => {
+                let always_on_top: bool = call.args();
+                self.handler.lock().set_always_on_top(always_on_top);
+                call.success_empty()
+            }
+            "setMinSize" => {
+                let size: Option<SizeParams> = call.args();
+                self.handler.lock().set_min_size(size);
+                call.success_empty()
+            }
+            "setMaxSize" => {
+                let size: Option<SizeParams> = call.args();
+                self.handler.lock().set_max_size(size);
+                call.success_empty()
+            }
+            "setFullscreen" => {
+                let fullscreen: bool = call.args();
+                self.handler.lock().set_fullscreen(fullscreen);
+                call.success_empty()
+            }
+            "getWindowId" => call.success(self.handler.lock().window_id()),
+            "raiseAbove" | "lowerBelow" => {
+                let other: u64 = match from_value_owned(call.raw_args()) {
+                    Ok(other) => other,
+                    Err(err) => {
+                        error!("[plugin: window] Invalid arguments: {}", err);
+                        return call.error("invalid-args", err.to_string(), Value::Null);
+                    }
+                };
+
+                let is_handled = if call.method() == "raiseAbove" {
+                    self.handler.lock().raise_above(other)
+                } else {
+                    self.handler.lock().lower_below(other)
+                };
+                call.success(is_handled)
+            }
+            "widgetRectToAnchor" => {
+                let Some(anchors) = &self.anchors else {
+                    return call.not_implemented();
+                };
+
+                let args: WidgetRectToAnchorArgs = match from_value_owned(call.raw_args()) {
+                    Ok(args) => args,
+                    Err(err) => {
+                        error!("[plugin: window] Invalid arguments: {}", err);
+                        return call.error("invalid-args", err.to_string(), Value::Null);
+                    }
+                };
+
+                match anchors.create(args.view_id, args.rect) {
+                    Ok(token) => call.success(token),
+                    Err(err) => call.error(err.code(), err.to_string(), Value::Null),
+                }
+            }
             _ => call.not_implemented(),
         }
     }
@@ -133,3 +380,113 @@ pub struct PositionParams {
     pub x: f32,
     pub y: f32,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct SizeParams {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Features of the window supported by the backend, reported through
+/// `getCapabilities`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowCapabilities {
+    /// The content is composited over what is behind the window (see
+    /// `ApplicationAttributes::transparent`).
+    pub transparency: bool,
+    /// `setAlwaysOnTop`, and `ApplicationAttributes::always_on_top`.
+    pub always_on_top: bool,
+    /// `ApplicationAttributes::skip_taskbar`, which can't be changed once the
+    /// window is created.
+    pub skip_taskbar: bool,
+    /// `setMinSize` and `setMaxSize`.
+    pub min_max_size: bool,
+    /// `maximize` and `restore`.
+    pub maximize: bool,
+    /// `setFullscreen`.
+    pub fullscreen: bool,
+    /// `set_pos` and `get_pos`.
+    pub position: bool,
+}
+
+/// Whether the window is seen by the user, reported through
+/// `onVisibilityChanged`. Apps may pause expensive work (e.g.: animations or
+/// video decoding) while their window isn't visible.
+///
+/// Windowing systems rarely tell whether a window is occluded, so this is an
+/// estimate: a window may be reported visible while it is covered, and the
+/// changes are reported with some delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Visibility {
+    Visible,
+    /// The window is likely covered by others, at least partly.
+    PartiallyObscuredLikely,
+    /// The window is minimized, unmapped, or fully covered by others.
+    Hidden,
+}
+
+/// Stacking order of the windows of the application, reported through
+/// `onStackingChanged`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StackingParams {
+    /// Identifiers of the windows (see `getWindowId`), topmost first.
+    pub order: Vec<u64>,
+    pub outcome: StackingOutcome,
+}
+
+/// What caused the stacking order to be reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StackingOutcome {
+    /// A `raiseAbove` or `lowerBelow` request was applied.
+    Applied,
+    /// The windows were already stacked as requested.
+    Unchanged,
+    /// The compositor didn't raise the window (e.g.: without any recent user
+    /// input).
+    Refused,
+    /// The windows can't be stacked as requested (e.g.: an attached window
+    /// below its parent).
+    Unsupported,
+    /// The compositor restacked the windows, on its own or on behalf of the
+    /// user (e.g.: by clicking another window).
+    Compositor,
+}
+
+/// State of the window reported through `onStateChanged`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct WindowStateParams {
+    pub maximized: bool,
+    pub fullscreen: bool,
+    /// Tiled along at least one edge (e.g.: snapped to half of the screen).
+    pub tiled: bool,
+    pub initiator: WindowStateInitiator,
+}
+
+/// Who changed the window state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WindowStateInitiator {
+    /// The app, through the window channel.
+    App,
+    /// The compositor, on its own or on behalf of the user (e.g.: a
+    /// keybinding, or dragging the window to the top edge).
+    Compositor,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WidgetRectToAnchorArgs {
+    view_id: FlutterViewId,
+    rect: AnchorRect,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MarginParams {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
@@ -0,0 +1,161 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+const DEFAULT_FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_LOG_LINES: usize = 12;
+
+pub type StartupFailureCallback = Arc<dyn Fn(&StartupFailure) -> bool + Send + Sync>;
+
+/// Screen drawn by the embedder in place of the content when the Dart app
+/// fails to start, rather than leaving the window blank. See
+/// `ApplicationBuilder::with_failure_screen`.
+#[derive(Clone)]
+pub struct FailureScreenConfig {
+    /// Whether failures are detected at all, `true` by default.
+    pub enabled: bool,
+    /// Time allowed between running the engine and presenting the first
+    /// frame, after which the app is considered as failed.
+    pub first_frame_timeout: Duration,
+    /// Shown at the bottom of the screen (e.g.: a support phone number, or a
+    /// URL to type in).
+    pub support_message: Option<String>,
+    /// Maximum number of lines of the Dart log shown on the screen.
+    pub log_lines: usize,
+    /// Invoked on the platform thread when the app fails to start. Returns
+    /// whether it handled the failure, in which case the screen isn't drawn
+    /// (and the application isn't quit when the engine failed to run).
+    pub on_failure: Option<StartupFailureCallback>,
+}
+
+impl FailureScreenConfig {
+    /// Neither detect nor report the failures.
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_first_frame_timeout(mut self, timeout: Duration) -> Self {
+        self.first_frame_timeout = timeout;
+        self
+    }
+
+    pub fn with_support_message<S: Into<String>>(mut self, message: S) -> Self {
+        self.support_message = Some(message.into());
+        self
+    }
+
+    pub fn with_log_lines(mut self, lines: usize) -> Self {
+        self.log_lines = lines;
+        self
+    }
+
+    pub fn with_on_failure<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&StartupFailure) -> bool + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl Default for FailureScreenConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            first_frame_timeout: DEFAULT_FIRST_FRAME_TIMEOUT,
+            support_message: None,
+            log_lines: DEFAULT_LOG_LINES,
+            on_failure: None,
+        }
+    }
+}
+
+impl fmt::Debug for FailureScreenConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FailureScreenConfig")
+            .field("enabled", &self.enabled)
+            .field("first_frame_timeout", &self.first_frame_timeout)
+            .field("support_message", &self.support_message)
+            .field("log_lines", &self.log_lines)
+            .field("on_failure", &self.on_failure.is_some())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupFailureReason {
+    /// The engine failed to run (e.g.: incompatible or corrupt bundle), with
+    /// the error.
+    RunFailed(String),
+    /// The engine is running, but no frame was presented within the timeout
+    /// (e.g.: `main` threw before `runApp`).
+    NoFirstFrame(Duration),
+}
+
+impl fmt::Display for StartupFailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RunFailed(err) => write!(f, "The engine failed to run: {err}"),
+            Self::NoFirstFrame(timeout) => {
+                write!(f, "No frame was rendered within {}s", timeout.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// Why the Dart app failed to start, along with what it logged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupFailure {
+    pub reason: StartupFailureReason,
+    /// Last lines logged by Dart (e.g.: the error thrown by `main`), oldest
+    /// first.
+    pub log_tail: Vec<String>,
+}
+
+impl StartupFailure {
+    /// The reason, followed by the last error logged by Dart if any.
+    pub fn summary(&self) -> String {
+        let error = self
+            .log_tail
+            .iter()
+            .rev()
+            .find(|line| line.to_lowercase().contains("error") || line.contains("Exception"));
+        match error {
+            Some(error) => format!("{}\n{}", self.reason, error.trim()),
+            None => self.reason.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{StartupFailure, StartupFailureReason};
+
+    #[test]
+    fn summarizes_the_last_dart_error() {
+        let failure = StartupFailure {
+            reason: StartupFailureReason::NoFirstFrame(Duration::from_secs(5)),
+            log_tail: vec![
+                "Starting the app".into(),
+                "Unhandled Exception: Bad state: missing config".into(),
+                "#0      main (package:app/main.dart:4:3)".into(),
+            ],
+        };
+        assert_eq!(
+            failure.summary(),
+            "No frame was rendered within 5s\nUnhandled Exception: Bad state: missing config"
+        );
+
+        let failure = StartupFailure {
+            reason: StartupFailureReason::RunFailed("invalid arguments".into()),
+            log_tail: vec![],
+        };
+        assert_eq!(
+            failure.summary(),
+            "The engine failed to run: invalid arguments"
+        );
+    }
+}
@@ -0,0 +1,20 @@
+/// Timestamps of the last frame events of the implicit window, in engine
+/// time (see `FlutterEngine::get_current_time_nanos`), for apps rendering
+/// their own FPS or latency overlay. See `Application::last_frame_stats`.
+///
+/// The timestamps are recorded independently, so they may belong to
+/// consecutive frames (e.g.: the vsync of the next frame may be signaled
+/// before the current one is presented).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Number of frames presented so far, e.g.: for computing the frame
+    /// rate over an interval.
+    pub presented_frames: u64,
+    /// When the last vsync was signaled to the engine. `None` until the
+    /// window is mapped, as the frames are not paced by the compositor yet.
+    pub vsync_time_nanos: Option<u64>,
+    /// When the engine finished rendering the last frame.
+    pub frame_generated_time_nanos: Option<u64>,
+    /// When the last frame was handed to the compositor.
+    pub frame_presented_time_nanos: Option<u64>,
+}
@@ -0,0 +1,89 @@
+use std::{
+    ops::ControlFlow,
+    time::{Duration, Instant},
+};
+
+use flutter_engine::FlutterEngine;
+
+/// Hook invoked on the platform thread once per event loop iteration (see
+/// `Application::run_with`). Returning [`ControlFlow::Break`] quits the
+/// application.
+pub type HostHook = Box<dyn FnMut(&mut HostContext) -> ControlFlow<()>>;
+
+/// Outcome of a single `Application::pump` call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PumpResult {
+    /// The application is still running and `pump` should be called again.
+    Continue,
+    /// The application has been asked to quit (e.g.: the window was closed).
+    /// Further calls to `pump` are no-ops.
+    Exit,
+}
+
+/// Per-iteration state exposed to a [`HostHook`].
+pub struct HostContext<'a> {
+    engine: &'a FlutterEngine,
+    elapsed: Duration,
+    delta: Duration,
+    quit_requested: bool,
+}
+
+impl HostContext<'_> {
+    /// Time elapsed since the event loop has been started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Time elapsed since the previous iteration.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// The engine, e.g.: for invoking methods on platform channels.
+    pub fn engine(&self) -> &FlutterEngine {
+        self.engine
+    }
+
+    /// Quit the application once the hook returns. This is equivalent to
+    /// returning [`ControlFlow::Break`] from the hook.
+    pub fn request_quit(&mut self) {
+        self.quit_requested = true;
+    }
+
+    pub fn is_quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+}
+
+/// Keeps track of the timings reported through [`HostContext`]. Used by the
+/// backends for driving a [`HostHook`].
+#[derive(Debug, Clone, Copy)]
+pub struct HostClock {
+    started_at: Instant,
+    last_tick: Instant,
+}
+
+impl Default for HostClock {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            started_at: now,
+            last_tick: now,
+        }
+    }
+}
+
+impl HostClock {
+    /// Returns the context of a new loop iteration.
+    pub fn tick<'a>(&mut self, engine: &'a FlutterEngine) -> HostContext<'a> {
+        let now = Instant::now();
+        let context = HostContext {
+            engine,
+            elapsed: now - self.started_at,
+            delta: now - self.last_tick,
+            quit_requested: false,
+        };
+        self.last_tick = now;
+        context
+    }
+}
@@ -1,12 +1,254 @@
-use std::path::PathBuf;
+use std::{fmt, num::NonZeroUsize, path::PathBuf, sync::Arc, time::Duration};
 
-use dpi::Size;
+use dpi::{LogicalPosition, Size};
+use flutter_engine::builder::FlutterEngineBuilder;
+
+mod failure;
+mod frame_stats;
+mod host;
+
+pub use failure::{
+    FailureScreenConfig, StartupFailure, StartupFailureCallback, StartupFailureReason,
+};
+pub use flutter_engine::{
+    fake_time::FakeTimeBase,
+    plugins::{PluginConfig, PluginConfigMap},
+    runtime::AsyncRuntime,
+    schedule::ScheduleHandle,
+    IsolateExit,
+};
+pub use frame_stats::FrameStats;
+pub use host::{HostClock, HostContext, HostHook, PumpResult};
 
 #[derive(Debug, Clone, Default)]
 pub enum Backend {
     #[default]
     Sctk,
     Winit,
+    /// Fullscreen on a display driven directly through DRM/KMS, without a
+    /// display server (e.g.: on embedded devices). See [`DrmOutput`].
+    Drm,
+}
+
+/// Frame scheduling strategy used by the engine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VsyncMode {
+    /// Schedule frames based on the platform frame callbacks (e.g.:
+    /// `wl_surface.frame` on Wayland).
+    #[default]
+    Vsync,
+    /// Let the engine schedule frames using its internal software timer. This
+    /// is mostly useful for headless, offscreen or benchmarking scenarios
+    /// where frames should not be throttled by the compositor.
+    Software,
+}
+
+/// Rendering path used for presenting the engine frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RenderPath {
+    /// Render through the engine compositor (i.e.: backing stores which are
+    /// blitted to the window surface). Required for platform views.
+    #[default]
+    Compositor,
+    /// Let the engine render directly into the window surface. Lighter than
+    /// the compositor path, but platform views are not supported. This is
+    /// always used by the winit and drm backends.
+    OpenGL,
+}
+
+/// GPU used for rendering, on systems with several of them (e.g.: hybrid
+/// laptops). Falls back to the default GPU when the preferred one can't be
+/// selected (e.g.: with non-Mesa drivers).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum GpuPreference {
+    /// Let the driver decide.
+    #[default]
+    Default,
+    /// Prefer the discrete (or external) GPU.
+    HighPerformance,
+    /// Prefer the integrated GPU.
+    LowPower,
+    /// The GPU whose name or vendor contains the given string (e.g.:
+    /// `radeon`), or whose DRM render node is the given path (e.g.:
+    /// `/dev/dri/renderD129`).
+    Named(String),
+}
+
+/// Display used by the drm backend. Unset fields fall back to the
+/// `FLUTTER_DRM_DEVICE`, `FLUTTER_DRM_CONNECTOR` and `FLUTTER_DRM_MODE`
+/// environment variables, then to the first connected display in its
+/// preferred mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DrmOutput {
+    /// DRM device (e.g.: `/dev/dri/card1`).
+    pub device: Option<PathBuf>,
+    /// Connector name (e.g.: `HDMI-A-1`).
+    pub connector: Option<String>,
+    /// Mode as `WIDTHxHEIGHT`, optionally followed by `@REFRESH` in Hz (e.g.:
+    /// `1280x720@60`).
+    pub mode: Option<String>,
+}
+
+/// Role of the application window.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum WindowRole {
+    /// Regular application window (i.e.: `xdg_toplevel` on Wayland).
+    #[default]
+    Toplevel,
+    /// Desktop shell component (e.g.: status bar, launcher, lock screen)
+    /// using the `zwlr_layer_shell_v1` Wayland protocol. Only supported by
+    /// the sctk backend.
+    ///
+    /// The window size is dictated by the compositor along the anchored
+    /// axes; `inner_size` is used for the other ones.
+    LayerShell {
+        layer: ShellLayer,
+        anchors: LayerAnchors,
+        /// Size (in logical pixels) of the area reserved from the anchored
+        /// edge. `0` does not reserve anything, and `-1` ignores the zones
+        /// reserved by other surfaces.
+        exclusive_zone: i32,
+        margin: LayerMargin,
+        keyboard_interactivity: KeyboardInteractivity,
+        /// Name of the output (e.g.: `DP-1`) the surface is shown on. The
+        /// compositor picks one when unset.
+        output: Option<String>,
+    },
+    /// Auxiliary window (e.g.: a tool palette) attached to the window of
+    /// another engine of the application, above which it stays and along
+    /// with which it moves. It doesn't have decorations and can't get the
+    /// keyboard focus. Only supported by the sctk backend, for the engines
+    /// added to an application.
+    Utility {
+        /// Identifier of the window it is attached to (e.g.: from
+        /// `getWindowId` of the `flutter-rs/window` channel), which can't be
+        /// attached itself.
+        attached_to: u64,
+        /// Position (in logical pixels) relative to the window it is
+        /// attached to.
+        position: LogicalPosition<i32>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShellLayer {
+    Background,
+    Bottom,
+    #[default]
+    Top,
+    Overlay,
+}
+
+/// Edges of the output a layer surface is anchored to. Anchoring to two
+/// opposite edges stretches the surface between them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LayerAnchors {
+    pub top: bool,
+    pub bottom: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl LayerAnchors {
+    /// Anchored to the top edge, spanning the whole output width (e.g.: a
+    /// status bar).
+    pub const TOP_EDGE: Self = Self {
+        top: true,
+        bottom: false,
+        left: true,
+        right: true,
+    };
+
+    /// Anchored to the bottom edge, spanning the whole output width.
+    pub const BOTTOM_EDGE: Self = Self {
+        top: false,
+        bottom: true,
+        left: true,
+        right: true,
+    };
+
+    /// Anchored to all the edges, covering the whole output (e.g.: a lock
+    /// screen).
+    pub const FILL: Self = Self {
+        top: true,
+        bottom: true,
+        left: true,
+        right: true,
+    };
+}
+
+/// Distance (in logical pixels) between a layer surface and the anchored
+/// edges.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LayerMargin {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+/// Whether a layer surface can receive the keyboard focus.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyboardInteractivity {
+    #[default]
+    None,
+    /// Grab the keyboard focus while the surface is mapped (e.g.: lock
+    /// screens). Only honored on the top and overlay layers.
+    Exclusive,
+    /// Receive the keyboard focus when clicked, like regular windows.
+    OnDemand,
+}
+
+/// Callbacks notified about the lifecycle of the root isolate.
+#[derive(Clone, Default)]
+pub struct IsolateCallbacks {
+    /// See `FlutterEngineBuilder::with_root_isolate_create_callback`.
+    pub on_created: Option<Arc<dyn Fn() + Send + Sync>>,
+    /// See `FlutterEngineBuilder::with_root_isolate_shutdown_callback`.
+    pub on_shutdown: Option<Arc<dyn Fn(IsolateExit) + Send + Sync>>,
+}
+
+impl IsolateCallbacks {
+    /// Register the callbacks with the engine |builder|.
+    pub fn register(&self, mut builder: FlutterEngineBuilder) -> FlutterEngineBuilder {
+        if let Some(on_created) = self.on_created.clone() {
+            builder = builder.with_root_isolate_create_callback(move || on_created());
+        }
+        if let Some(on_shutdown) = self.on_shutdown.clone() {
+            builder = builder.with_root_isolate_shutdown_callback(move |exit| on_shutdown(exit));
+        }
+        builder
+    }
+}
+
+impl fmt::Debug for IsolateCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IsolateCallbacks")
+            .field("on_created", &self.on_created.is_some())
+            .field("on_shutdown", &self.on_shutdown.is_some())
+            .finish()
+    }
+}
+
+/// Buffering of the user input until the Dart app is ready, see
+/// `ApplicationBuilder::with_gate_input_until_first_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputGateConfig {
+    /// Whether the input is gated at all, `true` by default.
+    pub enabled: bool,
+    /// Time after which the input is released anyway, counted from running
+    /// the engine (e.g.: when the app extended the gating and never ended
+    /// it).
+    pub timeout: Duration,
+}
+
+impl Default for InputGateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout: Duration::from_secs(5),
+        }
+    }
 }
 
 /// Attributes used when creating an application.
@@ -14,10 +256,98 @@ pub enum Backend {
 pub struct ApplicationAttributes {
     pub backend: Backend,
     pub inner_size: Option<Size>,
+    pub min_inner_size: Option<Size>,
+    pub max_inner_size: Option<Size>,
+    /// Create the window maximized.
+    pub maximized: bool,
+    /// Create the window fullscreen.
+    pub fullscreen: bool,
+    /// Composite the content over what is behind the window (e.g.: for
+    /// rounded corners). The sctk windows are always transparent, see
+    /// `background_color`.
+    pub transparent: bool,
+    /// Keep the window above the others. Only supported by the winit backend,
+    /// on X11.
+    pub always_on_top: bool,
+    /// Keep the window out of the taskbar (e.g.: for utility windows). Only
+    /// supported by the winit backend, on X11.
+    pub skip_taskbar: bool,
+    /// Create the window without showing it (e.g.: for tray applications).
+    /// The engine still runs and renders the content, so that the window is
+    /// shown instantly. Not supported by the drm backend.
+    pub start_hidden: bool,
     pub title: Option<String>,
     pub app_id: Option<String>,
+    /// Prepended to each line logged by the engine (e.g.: Dart's `print`),
+    /// for telling apart the engines logging together. Defaults to the app
+    /// id, or to the tag of the engine without it. The lines are logged with
+    /// the `flutter` tracing target. Only supported by the sctk backend.
+    pub log_tag: Option<String>,
     pub args: Vec<String>,
     pub assets_path: PathBuf,
     pub icu_data_path: PathBuf,
+    /// See `FlutterEngineBuilder::with_embedded_icu_data`.
+    pub icu_data_embedded: bool,
     pub persistent_cache_path: PathBuf,
+    pub vsync_mode: VsyncMode,
+    /// Minimum interval between frames, capping the frame rate below the
+    /// refresh rate of the display. Only supported by the sctk backend, with
+    /// [`VsyncMode::Vsync`].
+    pub min_frame_interval: Option<Duration>,
+    pub render_path: RenderPath,
+    pub gpu_preference: GpuPreference,
+    /// Request robust rendering contexts, which survive GPU resets. Regular
+    /// contexts are used when the driver doesn't support them.
+    pub robust_context: bool,
+    /// Log the `GL_KHR_debug` messages of the rendering contexts (e.g.:
+    /// driver warnings), which requires debug contexts. Regular contexts are
+    /// used when the driver doesn't support them.
+    pub gl_debug_output: bool,
+    /// Present every frame as fully damaged, disabling the partial repaints
+    /// (e.g.: for telling whether stale regions come from the damage
+    /// tracking). Only supported by the sctk backend, with the OpenGL render
+    /// path.
+    pub full_damage: bool,
+    /// When set, the engine artifacts are pre-loaded before creating the
+    /// window, waiting at most the given duration.
+    pub prepare_timeout: Option<Duration>,
+    pub window_role: WindowRole,
+    /// See `FlutterEngineBuilder::with_vm_service_port`.
+    pub vm_service_port: Option<u16>,
+    /// See `FlutterEngineBuilder::with_vm_service_auth_codes_disabled`.
+    pub vm_service_auth_codes_disabled: bool,
+    /// See `FlutterEngineBuilder::with_fake_time_base`.
+    pub fake_time_base: Option<FakeTimeBase>,
+    /// Device pixel ratio reported to the engine instead of the one of the
+    /// display (e.g.: for captures which don't depend on the display). Must
+    /// be finite and positive, ignored otherwise. Only supported by the sctk
+    /// and drm backends.
+    pub pixel_ratio: Option<f64>,
+    /// Quit the application when receiving `SIGTERM` or `SIGINT`. Only
+    /// supported by the sctk backend.
+    pub handle_termination_signals: bool,
+    /// Zoom the content on Ctrl+scroll and Ctrl+plus/minus/0, like browsers
+    /// do. Only supported by the sctk backend.
+    pub content_zoom: bool,
+    /// ARGB color drawn behind the content, transparent by default. It is
+    /// made opaque while the desktop asks for reduced transparency (e.g.:
+    /// with high contrast). Only supported by the sctk backend, with the
+    /// compositor render path.
+    pub background_color: u32,
+    /// Maximum number of engine tasks run per event loop iteration, input
+    /// being handled between batches. Unlimited when `None`.
+    pub platform_task_batch_size: Option<NonZeroUsize>,
+    pub isolate_callbacks: IsolateCallbacks,
+    /// Only supported by the sctk backend.
+    pub failure_screen: FailureScreenConfig,
+    /// Only supported by the sctk backend.
+    pub input_gate: InputGateConfig,
+    /// Delivered to the built-in plugins when they are registered. Configs
+    /// of plugins which aren't registered are ignored.
+    pub plugin_configs: PluginConfigMap,
+    /// Runtime of the host application, on which the plugins may run their
+    /// I/O. Plugins use their own means when `None`.
+    pub async_runtime: Option<AsyncRuntime>,
+    /// Only used by the drm backend.
+    pub drm_output: DrmOutput,
 }
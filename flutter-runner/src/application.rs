@@ -1,24 +1,46 @@
-use std::{fs::canonicalize, io::ErrorKind, path::PathBuf};
+use std::{
+    fs::canonicalize, io::ErrorKind, num::NonZeroUsize, ops::ControlFlow, path::PathBuf, sync::Arc,
+    time::Duration,
+};
 
 use dpi::Size;
-use flutter_runner_api::{ApplicationAttributes, Backend};
+use flutter_engine::{channel::JsonReply, error::JsonMessageError, FlutterEngine};
+use flutter_runner_api::{
+    ApplicationAttributes, AsyncRuntime, Backend, DrmOutput, FailureScreenConfig, FakeTimeBase,
+    FrameStats, GpuPreference, HostContext, IsolateExit, PluginConfig, PumpResult, RenderPath,
+    ScheduleHandle, VsyncMode, WindowRole,
+};
+use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
 use tracing::warn;
 
 #[cfg(feature = "flutter-sctk")]
 use flutter_sctk::application::{
-    SctkApplication, SctkApplicationCreateError, SctkApplicationRunError,
+    SctkApplication, SctkApplicationCreateError, SctkApplicationHandle, SctkApplicationRunError,
 };
 
 #[cfg(feature = "flutter-winit")]
-use flutter_winit::{WinitApplication, WinitApplicationBuildError, WinitApplicationRunError};
+use flutter_winit::{
+    WinitApplication, WinitApplicationBuildError, WinitApplicationHandle, WinitApplicationRunError,
+};
+
+#[cfg(feature = "flutter-drm")]
+use flutter_drm::{
+    DrmApplication, DrmApplicationCreateError, DrmApplicationHandle, DrmApplicationRunError,
+};
 
+// Only one application is created per process, so the size of the smaller
+// backends doesn't matter.
+#[allow(clippy::large_enum_variant)]
 pub enum Application {
     #[cfg(feature = "flutter-sctk")]
     Sctk(SctkApplication),
 
     #[cfg(feature = "flutter-winit")]
     Winit(WinitApplication),
+
+    #[cfg(feature = "flutter-drm")]
+    Drm(DrmApplication),
 }
 
 impl Application {
@@ -43,6 +65,47 @@ impl Application {
                 #[cfg(not(feature = "flutter-winit"))]
                 panic!("Failed to initialize winit application. The 'flutter-winit' feature is not enabled");
             }
+
+            Backend::Drm => {
+                #[cfg(feature = "flutter-drm")]
+                return Ok(Application::Drm(DrmApplication::new(attributes)?));
+
+                #[cfg(not(feature = "flutter-drm"))]
+                panic!("Failed to initialize drm application. The 'flutter-drm' feature is not enabled");
+            }
+        }
+    }
+
+    /// Returns a thread-safe handle which can be used for controlling the
+    /// application once it is running.
+    pub fn handle(&self) -> ApplicationHandle {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(app) => ApplicationHandle::Sctk(app.handle()),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(app) => ApplicationHandle::Winit(app.handle()),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(app) => ApplicationHandle::Drm(app.handle()),
+        }
+    }
+
+    /// Timestamps of the last vsync, and of the last frame generated and
+    /// presented in the window (see [`FrameStats`]), e.g.: for an FPS
+    /// counter rendered by the app. Cheap enough to be polled for every
+    /// frame (e.g.: from [`Application::run_with`]). Only supported by the
+    /// sctk backend, `None` otherwise.
+    pub fn last_frame_stats(&self) -> Option<FrameStats> {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(app) => Some(app.last_frame_stats()),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(_) => None,
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(_) => None,
         }
     }
 
@@ -53,6 +116,228 @@ impl Application {
 
             #[cfg(feature = "flutter-winit")]
             Self::Winit(app) => Ok(app.run()?),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(app) => Ok(app.run()?),
+        }
+    }
+
+    /// Run the application, invoking `hook` on the platform thread once per
+    /// event loop iteration. The hook can be used for integrating host logic
+    /// (e.g.: forwarding data to the app through platform channels using
+    /// [`HostContext::engine`]). Returning [`ControlFlow::Break`] or calling
+    /// [`HostContext::request_quit`] quits the application.
+    ///
+    /// Iterations are only triggered by events (input, platform tasks, etc.).
+    /// Hosts which need to run at a fixed rate should drive the application
+    /// using [`Application::pump`] instead.
+    pub fn run_with<F>(self, hook: F) -> Result<(), ApplicationRunError>
+    where
+        F: FnMut(&mut HostContext) -> ControlFlow<()> + 'static,
+    {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(app) => Ok(app.run_with(hook)?),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(app) => Ok(app.run_with(hook)?),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(app) => Ok(app.run_with(hook)?),
+        }
+    }
+
+    /// Run a single event loop iteration, for hosts which drive their own
+    /// loop (e.g.: a 60Hz simulation). Pending events are dispatched, waiting
+    /// at most `timeout` for new ones (or indefinitely if `None`), and the
+    /// platform tasks which are due are executed.
+    ///
+    /// This must be called from the thread which created the application, at
+    /// least once per frame: the engine relies on it for processing platform
+    /// channel messages and for scheduling frames. Stop calling it once it
+    /// returns [`PumpResult::Exit`].
+    ///
+    /// ```no_run
+    /// # use std::time::{Duration, Instant};
+    /// # use flutter_runner::{application::Application, PumpResult};
+    /// # let mut app = Application::builder().build().unwrap();
+    /// let tick = Duration::from_secs(1) / 60;
+    /// let mut next_tick = Instant::now();
+    /// loop {
+    ///     let timeout = next_tick.saturating_duration_since(Instant::now());
+    ///     if app.pump(Some(timeout)).unwrap() == PumpResult::Exit {
+    ///         break;
+    ///     }
+    ///
+    ///     if Instant::now() >= next_tick {
+    ///         // Advance the simulation by `tick`.
+    ///         next_tick += tick;
+    ///     }
+    /// }
+    /// ```
+    pub fn pump(&mut self, timeout: Option<Duration>) -> Result<PumpResult, ApplicationRunError> {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(app) => Ok(app.pump(timeout)?),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(app) => Ok(app.pump(timeout)?),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(app) => Ok(app.pump(timeout)?),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum ApplicationHandle {
+    #[cfg(feature = "flutter-sctk")]
+    Sctk(SctkApplicationHandle),
+
+    #[cfg(feature = "flutter-winit")]
+    Winit(WinitApplicationHandle),
+
+    #[cfg(feature = "flutter-drm")]
+    Drm(DrmApplicationHandle),
+}
+
+impl ApplicationHandle {
+    /// Pause the application (e.g.: while it is hidden or while running heavy
+    /// background operations). The app is notified that it is paused and, on
+    /// backends that support it, frame scheduling is paused as well. When
+    /// `drop_caches` is set, the engine is also asked to release its caches.
+    pub fn suspend(&self, drop_caches: bool) {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(handle) => handle.suspend(drop_caches),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(handle) => handle.suspend(drop_caches),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(handle) => handle.suspend(drop_caches),
+        }
+    }
+
+    /// Resume the application after a previous call to `suspend`. This also
+    /// re-sends the window metrics and schedules a new frame.
+    pub fn resume(&self) {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(handle) => handle.resume(),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(handle) => handle.resume(),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(handle) => handle.resume(),
+        }
+    }
+
+    /// Change the minimum interval between frames (see
+    /// [`ApplicationBuilder::with_min_frame_interval`]), or render on every
+    /// refresh when `None`. Only supported by the sctk backend.
+    pub fn set_min_frame_interval(&self, interval: Option<Duration>) {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(handle) => handle.set_min_frame_interval(interval),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(_) => warn!("Frame rate caps are not supported by the winit backend"),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(_) => warn!("Frame rate caps are not supported by the drm backend"),
+        }
+    }
+
+    /// Quit the application. The engine is shut down before `run` returns.
+    pub fn quit(&self) {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(handle) => handle.quit(),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(handle) => handle.quit(),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(handle) => handle.quit(),
+        }
+    }
+
+    /// Run |f| on the platform thread once the engine clock reaches
+    /// |engine_time_nanos| (see `FlutterEngine::get_current_time_nanos`).
+    /// Returns `None` if the engine is gone.
+    pub fn schedule_at<F>(&self, engine_time_nanos: u64, f: F) -> Option<ScheduleHandle>
+    where
+        F: FnOnce(&FlutterEngine) + 'static + Send,
+    {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(handle) => handle.schedule_at(engine_time_nanos, f),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(handle) => handle.schedule_at(engine_time_nanos, f),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(handle) => handle.schedule_at(engine_time_nanos, f),
+        }
+    }
+
+    /// Run |f| on the platform thread every |interval|, until the returned
+    /// handle is used for cancelling it. Returns `None` if the engine is gone.
+    pub fn schedule_repeating<F>(&self, interval: Duration, f: F) -> Option<ScheduleHandle>
+    where
+        F: FnMut(&FlutterEngine) + 'static + Send,
+    {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(handle) => handle.schedule_repeating(interval, f),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(handle) => handle.schedule_repeating(interval, f),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(handle) => handle.schedule_repeating(interval, f),
+        }
+    }
+
+    /// Send |value| to the framework on |channel|, encoded with the JSON
+    /// codec. Can be called from any thread.
+    pub fn send_json_message<T>(&self, channel: &str, value: &T) -> Result<(), JsonMessageError>
+    where
+        T: Serialize,
+    {
+        let engine = self.engine().ok_or(JsonMessageError::EngineGone)?;
+        engine.send_json_message(channel, value)
+    }
+
+    /// Send |value| to the framework on |channel|, and await its reply. Both
+    /// are encoded with the JSON codec. Can be called from any thread, but the
+    /// reply must not be blocked on from the platform thread (e.g.: from a
+    /// host hook, which can use [`JsonReply::try_take`] instead).
+    pub fn send_json_message_with_reply<T, V>(
+        &self,
+        channel: &str,
+        value: &T,
+    ) -> Result<JsonReply<V>, JsonMessageError>
+    where
+        T: Serialize,
+        V: DeserializeOwned + Send + 'static,
+    {
+        let engine = self.engine().ok_or(JsonMessageError::EngineGone)?;
+        engine.send_json_message_with_reply(channel, value)
+    }
+
+    fn engine(&self) -> Option<FlutterEngine> {
+        match self {
+            #[cfg(feature = "flutter-sctk")]
+            Self::Sctk(handle) => handle.engine(),
+
+            #[cfg(feature = "flutter-winit")]
+            Self::Winit(handle) => handle.engine(),
+
+            #[cfg(feature = "flutter-drm")]
+            Self::Drm(handle) => handle.engine(),
         }
     }
 }
@@ -64,6 +349,26 @@ impl Application {
 pub struct ApplicationBuilder {
     /// The attributes to use to create the application.
     pub(crate) attributes: ApplicationAttributes,
+    /// Templates used for paths that are not explicitly configured.
+    pub(crate) default_paths: DefaultPaths,
+}
+
+/// Placeholder replaced with the application id in [`DefaultPaths`].
+pub const APP_ID_PLACEHOLDER: &str = "{app_id}";
+
+/// Path templates used for resolving the paths that have not been explicitly
+/// set on the [`ApplicationBuilder`].
+///
+/// Templates may contain the [`APP_ID_PLACEHOLDER`], in which case they are
+/// only used when an app id has been configured.
+#[derive(Debug, Clone, Default)]
+pub struct DefaultPaths {
+    /// Directory containing the `flutter_assets` directory and the
+    /// `icudtl.dat` file (e.g.: `/usr/share/{app_id}`). Defaults to the `data`
+    /// directory next to the executable.
+    pub data_dir: Option<PathBuf>,
+    /// Persistent cache directory. Defaults to `~/.cache/{app_id}`.
+    pub persistent_cache_dir: Option<PathBuf>,
 }
 
 impl ApplicationBuilder {
@@ -86,6 +391,50 @@ impl ApplicationBuilder {
         self
     }
 
+    pub fn with_min_inner_size<S: Into<Size>>(mut self, size: S) -> Self {
+        self.attributes.min_inner_size = Some(size.into());
+        self
+    }
+
+    pub fn with_max_inner_size<S: Into<Size>>(mut self, size: S) -> Self {
+        self.attributes.max_inner_size = Some(size.into());
+        self
+    }
+
+    pub fn with_maximized(mut self, maximized: bool) -> Self {
+        self.attributes.maximized = maximized;
+        self
+    }
+
+    pub fn with_fullscreen(mut self, fullscreen: bool) -> Self {
+        self.attributes.fullscreen = fullscreen;
+        self
+    }
+
+    /// See [`ApplicationAttributes::transparent`].
+    pub fn with_transparent(mut self, transparent: bool) -> Self {
+        self.attributes.transparent = transparent;
+        self
+    }
+
+    /// See [`ApplicationAttributes::always_on_top`].
+    pub fn with_always_on_top(mut self, always_on_top: bool) -> Self {
+        self.attributes.always_on_top = always_on_top;
+        self
+    }
+
+    /// See [`ApplicationAttributes::skip_taskbar`].
+    pub fn with_skip_taskbar(mut self, skip_taskbar: bool) -> Self {
+        self.attributes.skip_taskbar = skip_taskbar;
+        self
+    }
+
+    /// See [`ApplicationAttributes::start_hidden`].
+    pub fn with_start_hidden(mut self, start_hidden: bool) -> Self {
+        self.attributes.start_hidden = start_hidden;
+        self
+    }
+
     pub fn with_title<T: Into<String>>(mut self, title: T) -> Self {
         self.attributes.title = Some(title.into());
         self
@@ -96,6 +445,12 @@ impl ApplicationBuilder {
         self
     }
 
+    /// See [`ApplicationAttributes::log_tag`].
+    pub fn with_log_tag<T: Into<String>>(mut self, log_tag: T) -> Self {
+        self.attributes.log_tag = Some(log_tag.into());
+        self
+    }
+
     pub fn with_arg(mut self, arg: String) -> Self {
         self.attributes.args.push(arg);
         self
@@ -118,11 +473,343 @@ impl ApplicationBuilder {
         self
     }
 
+    /// Use the ICU data embedded in the engine library when the ICU data file
+    /// does not exist, for engine builds which support it.
+    pub fn with_embedded_icu_data(mut self, embedded: bool) -> Self {
+        self.attributes.icu_data_embedded = embedded;
+        self
+    }
+
     pub fn with_persistent_cache_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
         self.attributes.persistent_cache_path = path.into();
         self
     }
 
+    pub fn with_vsync_mode(mut self, vsync_mode: VsyncMode) -> Self {
+        self.attributes.vsync_mode = vsync_mode;
+        self
+    }
+
+    /// Render at most one frame per `interval`, e.g.: at most 60 frames per
+    /// second on a 240Hz display for typical UIs, which saves power. Frames
+    /// stay aligned on the display refreshes, so the effective rate is the
+    /// highest fraction of the refresh rate which doesn't exceed the cap.
+    ///
+    /// Unlike [`VsyncMode::Software`], frames are still driven by the
+    /// display, and the cap can be changed at runtime (see
+    /// [`ApplicationHandle::set_min_frame_interval`]). Only supported by the
+    /// sctk backend.
+    pub fn with_min_frame_interval(mut self, interval: Duration) -> Self {
+        self.attributes.min_frame_interval = Some(interval);
+        self
+    }
+
+    pub fn with_render_path(mut self, render_path: RenderPath) -> Self {
+        self.attributes.render_path = render_path;
+        self
+    }
+
+    pub fn with_gpu_preference(mut self, gpu_preference: GpuPreference) -> Self {
+        self.attributes.gpu_preference = gpu_preference;
+        self
+    }
+
+    /// Request a robust rendering context (`EGL_EXT_create_context_robustness`),
+    /// so that a GPU reset (e.g.: after a driver crash or a GPU hang) is
+    /// reported as a context loss instead of crashing the application. This
+    /// is meant for long-running apps (e.g.: kiosks) on unreliable drivers.
+    ///
+    /// When the driver doesn't support robust contexts, a regular context is
+    /// used and a warning is logged: a GPU reset then has the same outcome
+    /// as without this option, usually a crash or a frozen window.
+    pub fn with_robust_context(mut self, robust: bool) -> Self {
+        self.attributes.robust_context = robust;
+        self
+    }
+
+    /// Create debug rendering contexts, and log their `GL_KHR_debug`
+    /// messages (e.g.: GL errors, or the performance warnings of the driver)
+    /// with `tracing`, along with their source and type. Debug contexts are
+    /// slower, so this is only meant for diagnosing rendering issues.
+    ///
+    /// When the driver doesn't support debug contexts, regular ones are used
+    /// and a warning is logged.
+    pub fn with_gl_debug_output(mut self, enabled: bool) -> Self {
+        self.attributes.gl_debug_output = enabled;
+        self
+    }
+
+    /// Repaint and present the whole window for every frame, instead of only
+    /// the regions which changed. This is meant for debugging rendering
+    /// issues (e.g.: stale regions), as it costs more GPU time and power.
+    /// Setting the `FLUTTER_RS_FULL_DAMAGE` environment variable to `1`
+    /// does the same without rebuilding, and
+    /// `SctkApplication::set_full_damage` toggles it at runtime. Only
+    /// supported by the sctk backend.
+    pub fn with_full_damage(mut self, enabled: bool) -> Self {
+        self.attributes.full_damage = enabled;
+        self
+    }
+
+    /// Use a layer shell surface (e.g.: for status bars or lock screens)
+    /// instead of a regular window. See [`WindowRole::LayerShell`].
+    ///
+    /// ```no_run
+    /// # use dpi::LogicalSize;
+    /// # use flutter_runner::{application::Application, *};
+    /// // A 32px high bar at the top of the output, reserving its own space.
+    /// // The width is ignored since the bar is stretched horizontally.
+    /// let app = Application::builder()
+    ///     .with_inner_size(LogicalSize::new(1280, 32))
+    ///     .with_window_role(WindowRole::LayerShell {
+    ///         layer: ShellLayer::Top,
+    ///         anchors: LayerAnchors::TOP_EDGE,
+    ///         exclusive_zone: 32,
+    ///         margin: LayerMargin::default(),
+    ///         keyboard_interactivity: KeyboardInteractivity::None,
+    ///         output: None,
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn with_window_role(mut self, window_role: WindowRole) -> Self {
+        self.attributes.window_role = window_role;
+        self
+    }
+
+    /// Display used by the drm backend (see [`Backend::Drm`]). By default,
+    /// the first connected display is used in its preferred mode.
+    pub fn with_drm_output(mut self, output: DrmOutput) -> Self {
+        self.attributes.drm_output = output;
+        self
+    }
+
+    /// Pre-load the engine artifacts before creating the window (see
+    /// [`FlutterEngineBuilder::prepare`]), which mostly helps cold starts on
+    /// slow storage. The artifacts are read while the connection to the
+    /// display server is being set up, and `timeout` bounds the time spent
+    /// waiting for them afterwards.
+    ///
+    /// [`FlutterEngineBuilder::prepare`]: flutter_engine::builder::FlutterEngineBuilder::prepare
+    pub fn with_prepare_timeout(mut self, timeout: Duration) -> Self {
+        self.attributes.prepare_timeout = Some(timeout);
+        self
+    }
+
+    /// Port the Dart VM service (used by the debugger and DevTools) binds to,
+    /// e.g.: for forwarding it to a remote machine. A port of `0` picks a
+    /// random one, which is the default.
+    pub fn with_vm_service_port(mut self, port: u16) -> Self {
+        self.attributes.vm_service_port = Some(port);
+        self
+    }
+
+    /// Drive the frames with deterministic timestamps, so that animations
+    /// render identically on every run (e.g.: for golden tests). Requires
+    /// [`VsyncMode::Vsync`], the software vsync of the engine uses the real
+    /// time.
+    pub fn with_fake_time_base(mut self, time_base: FakeTimeBase) -> Self {
+        self.attributes.fake_time_base = Some(time_base);
+        self
+    }
+
+    /// Report a fixed device pixel ratio to the engine, whatever the scale
+    /// of the display (e.g.: for golden tests). Only supported by the sctk
+    /// and drm backends.
+    pub fn with_pixel_ratio(mut self, pixel_ratio: f64) -> Self {
+        self.attributes.pixel_ratio = Some(pixel_ratio);
+        self
+    }
+
+    /// Disable the authentication code of the VM service URI.
+    ///
+    /// **Security:** the VM service allows executing arbitrary code in the
+    /// application. Without the authentication code, anyone able to reach the
+    /// port can take control of it. Only use this on trusted machines and
+    /// networks, and never in production builds. See
+    /// [`FlutterEngineBuilder::with_vm_service_auth_codes_disabled`].
+    ///
+    /// [`FlutterEngineBuilder::with_vm_service_auth_codes_disabled`]: flutter_engine::builder::FlutterEngineBuilder::with_vm_service_auth_codes_disabled
+    pub fn with_vm_service_auth_codes_disabled(mut self, disabled: bool) -> Self {
+        self.attributes.vm_service_auth_codes_disabled = disabled;
+        self
+    }
+
+    /// Quit the application cleanly when the process receives `SIGTERM`
+    /// (e.g.: from the session manager on logout) or `SIGINT`, instead of
+    /// being killed mid-frame. Only supported by the sctk backend.
+    ///
+    /// This is disabled by default so that the host signal handling is left
+    /// untouched. When enabled, both signals are blocked for the platform
+    /// thread and the threads created by the engine.
+    pub fn with_termination_signals_handled(mut self, handled: bool) -> Self {
+        self.attributes.handle_termination_signals = handled;
+        self
+    }
+
+    /// Zoom the window content on Ctrl+scroll and Ctrl+plus/minus/0 (reset),
+    /// between 50% and 300%. The window keeps its size, the content is
+    /// rendered at a higher pixel ratio instead. Apps can follow the zoom (or
+    /// change it) through the `flutter-rs/window` channel (i.e.: `getZoom`,
+    /// `setZoom` and `onZoomChanged`). Only supported by the sctk backend.
+    pub fn with_content_zoom(mut self, enabled: bool) -> Self {
+        self.attributes.content_zoom = enabled;
+        self
+    }
+
+    /// Color (ARGB, e.g.: `0xff20_2020`) drawn behind the content where it
+    /// is transparent. The window is transparent by default, unless the
+    /// desktop asks for reduced transparency (e.g.: with high contrast), in
+    /// which case this color is drawn opaque. Only supported by the sctk
+    /// backend, with the compositor render path.
+    pub fn with_background_color(mut self, argb: u32) -> Self {
+        self.attributes.background_color = argb;
+        self
+    }
+
+    /// Run at most |max_tasks| of the due platform tasks (i.e.: the work
+    /// posted by Dart to the platform thread, like channel messages) per
+    /// event loop iteration, and handle the pending input events between
+    /// batches. This keeps the app responsive during bursts of work, at the
+    /// expense of the throughput of the latter.
+    ///
+    /// All the due tasks are run at once by default (or with `0`).
+    pub fn with_platform_task_batch_size(mut self, max_tasks: usize) -> Self {
+        self.attributes.platform_task_batch_size = NonZeroUsize::new(max_tasks);
+        self
+    }
+
+    /// Invoke |callback| once the root isolate is created (and again after
+    /// each hot restart). It is invoked on the engine UI thread.
+    pub fn with_root_isolate_created_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.attributes.isolate_callbacks.on_created = Some(Arc::new(callback));
+        self
+    }
+
+    /// Invoke |callback| once the root isolate has shut down, before `run`
+    /// returns. When Dart requested the exit (e.g.:
+    /// `ServicesBinding.exitApplication`), the requested exit code is passed
+    /// along, so that command-line apps can propagate it:
+    ///
+    /// ```no_run
+    /// # use std::sync::{atomic::{AtomicI32, Ordering}, Arc};
+    /// # use flutter_runner::{application::Application, IsolateExit};
+    /// let exit_code = Arc::new(AtomicI32::new(0));
+    /// let app = Application::builder()
+    ///     .with_root_isolate_shutdown_callback({
+    ///         let exit_code = exit_code.clone();
+    ///         move |exit| {
+    ///             if let IsolateExit::Requested { exit_code: code } = exit {
+    ///                 exit_code.store(code, Ordering::Relaxed);
+    ///             }
+    ///         }
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// app.run().unwrap();
+    /// std::process::exit(exit_code.load(Ordering::Relaxed));
+    /// ```
+    pub fn with_root_isolate_shutdown_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(IsolateExit) + Send + Sync + 'static,
+    {
+        self.attributes.isolate_callbacks.on_shutdown = Some(Arc::new(callback));
+        self
+    }
+
+    /// Draw a diagnostic screen (the app name, the error and the last lines
+    /// logged by Dart) when the engine fails to run, or doesn't render a
+    /// frame in time, instead of leaving a blank window. It is enabled by
+    /// default, and dismissed by the first frame (e.g.: after a hot restart
+    /// fixing the error). Only supported by the sctk backend.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use flutter_runner::{application::Application, FailureScreenConfig};
+    /// let app = Application::builder()
+    ///     .with_failure_screen(
+    ///         FailureScreenConfig::default()
+    ///             .with_first_frame_timeout(Duration::from_secs(20))
+    ///             .with_support_message("Call 555-0100 for assistance"),
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn with_failure_screen(mut self, config: FailureScreenConfig) -> Self {
+        self.attributes.failure_screen = config;
+        self
+    }
+
+    /// Buffer the pointer and keyboard input received before the app
+    /// presented its first frame, and replay it afterwards, so that early
+    /// clicks don't reach Dart before the app registered its channel
+    /// handlers. The app may end the gating earlier, or extend it until it is
+    /// initialized, with the `flutter-rs/ready` channel (bounded by
+    /// [`crate::InputGateConfig::timeout`]). Enabled by default, only supported
+    /// by the sctk backend.
+    pub fn with_gate_input_until_first_frame(mut self, enabled: bool) -> Self {
+        self.attributes.input_gate.enabled = enabled;
+        self
+    }
+
+    /// Configure a built-in plugin, replacing its previous config (see
+    /// [`PluginConfig`]). Plugins which aren't configured use their defaults.
+    ///
+    /// ```no_run
+    /// # use flutter_plugins::shared_preferences::SharedPreferencesConfig;
+    /// # use flutter_runner::application::Application;
+    /// let app = Application::builder()
+    ///     .with_plugin_config(SharedPreferencesConfig {
+    ///         path: Some("/var/lib/kiosk/preferences.json".into()),
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn with_plugin_config<C: PluginConfig>(mut self, config: C) -> Self {
+        self.attributes.plugin_configs.insert(config);
+        self
+    }
+
+    /// Share the async runtime of the application with the plugins, which
+    /// run their I/O on it instead of spawning their own (see
+    /// [`AsyncRuntime`]). The results are handed back to the platform thread.
+    ///
+    /// ```ignore
+    /// let runtime = tokio::runtime::Runtime::new().unwrap();
+    /// let handle = runtime.handle().clone();
+    /// let app = Application::builder()
+    ///     .with_async_runtime(AsyncRuntime::new(move |future| {
+    ///         handle.spawn(future);
+    ///     }))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn with_async_runtime(mut self, async_runtime: AsyncRuntime) -> Self {
+        self.attributes.async_runtime = Some(async_runtime);
+        self
+    }
+
+    pub fn with_default_paths(mut self, default_paths: DefaultPaths) -> Self {
+        self.default_paths = default_paths;
+        self
+    }
+
+    /// Base directory from which the assets and ICU data paths are derived
+    /// when not explicitly set (see [`DefaultPaths::data_dir`]).
+    pub fn with_default_data_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.default_paths.data_dir = Some(path.into());
+        self
+    }
+
+    /// See [`DefaultPaths::persistent_cache_dir`].
+    pub fn with_default_persistent_cache_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.default_paths.persistent_cache_dir = Some(path.into());
+        self
+    }
+
     #[cfg(target_os = "linux")]
     fn use_default_paths_if_empty(&mut self) {
         let app_id = self.attributes.app_id.clone().unwrap_or_default();
@@ -130,9 +817,13 @@ impl ApplicationBuilder {
         // Use `~/.cache/DESKTOP_APP_ID` as persistent cache dir if not
         // configured. This will have the effect of storing the engine cache
         // under `~/.cache/DESKTOP_APP_ID/flutter_engine`.
-        if self.attributes.persistent_cache_path.as_os_str().is_empty() && !app_id.is_empty() {
-            self.attributes.persistent_cache_path = dirs::cache_dir()
-                .map(|cache_dir| cache_dir.join(app_id))
+        if self.attributes.persistent_cache_path.as_os_str().is_empty() {
+            let template = self.default_paths.persistent_cache_dir.clone().or_else(|| {
+                dirs::cache_dir().map(|cache_dir| cache_dir.join(APP_ID_PLACEHOLDER))
+            });
+
+            self.attributes.persistent_cache_path = template
+                .and_then(|template| expand_path_template(&template, &app_id))
                 .unwrap_or_default();
         }
 
@@ -142,21 +833,52 @@ impl ApplicationBuilder {
             return;
         }
 
-        let Ok(executable_dir) = get_executable_dir() else {
-            warn!("Unable to resolve path for /proc/self/exe");
-            return;
+        let data_dir = match &self.default_paths.data_dir {
+            Some(template) => match expand_path_template(template, &app_id) {
+                Some(data_dir) => data_dir,
+                None => {
+                    warn!("Ignoring default data dir because no app id has been configured");
+                    return;
+                }
+            },
+            None => match get_executable_dir() {
+                Ok(executable_dir) => executable_dir.join("data"),
+                Err(_) => {
+                    warn!("Unable to resolve path for /proc/self/exe");
+                    return;
+                }
+            },
         };
 
         if self.attributes.assets_path.as_os_str().is_empty() {
-            self.attributes.assets_path = executable_dir.join("data").join("flutter_assets");
+            self.attributes.assets_path = data_dir.join("flutter_assets");
         }
 
         if self.attributes.icu_data_path.as_os_str().is_empty() {
-            self.attributes.icu_data_path = executable_dir.join("data").join("icudtl.dat");
+            self.attributes.icu_data_path = data_dir.join("icudtl.dat");
         }
     }
 }
 
+/// Replace the [`APP_ID_PLACEHOLDER`] in |template|. Returns `None` if the
+/// template requires an app id but none is available.
+#[cfg(target_os = "linux")]
+fn expand_path_template(template: &std::path::Path, app_id: &str) -> Option<PathBuf> {
+    let Some(template_str) = template.to_str() else {
+        return Some(template.into());
+    };
+
+    if !template_str.contains(APP_ID_PLACEHOLDER) {
+        return Some(template.into());
+    }
+
+    if app_id.is_empty() {
+        return None;
+    }
+
+    Some(template_str.replace(APP_ID_PLACEHOLDER, app_id).into())
+}
+
 #[derive(Error, Debug)]
 pub enum ApplicationBuildError {
     #[cfg(feature = "flutter-sctk")]
@@ -166,6 +888,10 @@ pub enum ApplicationBuildError {
     #[cfg(feature = "flutter-winit")]
     #[error(transparent)]
     WinitApplicationBuildError(#[from] WinitApplicationBuildError),
+
+    #[cfg(feature = "flutter-drm")]
+    #[error(transparent)]
+    DrmApplicationCreateError(#[from] DrmApplicationCreateError),
 }
 
 #[derive(Error, Debug)]
@@ -177,6 +903,10 @@ pub enum ApplicationRunError {
     #[cfg(feature = "flutter-winit")]
     #[error(transparent)]
     WinitApplicationRunError(#[from] WinitApplicationRunError),
+
+    #[cfg(feature = "flutter-drm")]
+    #[error(transparent)]
+    DrmApplicationRunError(#[from] DrmApplicationRunError),
 }
 
 #[cfg(target_os = "linux")]
@@ -187,3 +917,23 @@ pub fn get_executable_dir() -> Result<PathBuf, std::io::Error> {
             .ok_or(std::io::Error::from(ErrorKind::NotFound))
     })
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::expand_path_template;
+
+    #[test]
+    fn expands_app_id_placeholder() {
+        assert_eq!(
+            expand_path_template(Path::new("/usr/share/{app_id}"), "org.example.App"),
+            Some(PathBuf::from("/usr/share/org.example.App"))
+        );
+        assert_eq!(
+            expand_path_template(Path::new("/opt/app/data"), ""),
+            Some(PathBuf::from("/opt/app/data"))
+        );
+        assert_eq!(expand_path_template(Path::new("/usr/share/{app_id}"), ""), None);
+    }
+}
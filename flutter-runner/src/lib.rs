@@ -1,3 +1,4 @@
 pub use flutter_runner_api::*;
 
 pub mod application;
+pub mod logging;
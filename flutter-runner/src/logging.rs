@@ -0,0 +1,2 @@
+//! Runtime control of the embedder logging.
+pub use flutter_plugins::logging::{init, set_filter, LogFilterError};
@@ -0,0 +1,52 @@
+//! A canvas window along with a tool palette attached to it (see
+//! `WindowRole::Utility`), each running its own bundle. The palette stays
+//! above the canvas and moves along with it.
+//!
+//! The palette bundle moves it with `FlutterRsWindow.startDrag()` (e.g.: from
+//! the `onPanStart` of its title bar), and the windows report their stacking
+//! through `FlutterRsWindow.onStackingChanged`. The canvas is the window `0`
+//! and the palette the window `1` (see `FlutterRsWindow.getWindowId()`).
+//!
+//! `cargo run -p flutter-sctk --example attached_palette -- <canvas> <palette> <icudtl.dat>`,
+//! with the assets of both bundles.
+use std::{env, error::Error, path::PathBuf, process};
+
+use dpi::{LogicalPosition, LogicalSize};
+use flutter_engine::builder::FlutterEngineBuilder;
+use flutter_runner_api::{ApplicationAttributes, WindowRole};
+use flutter_sctk::{application::SctkApplication, engine::SctkEngineId};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let (Some(canvas_assets), Some(palette_assets), Some(icu_data)) =
+        (args.next(), args.next(), args.next())
+    else {
+        eprintln!("Usage: attached_palette <canvas assets> <palette assets> <icudtl.dat>");
+        process::exit(1);
+    };
+    let icu_data = PathBuf::from(icu_data);
+
+    let attributes = ApplicationAttributes {
+        title: Some("Canvas".into()),
+        assets_path: canvas_assets.into(),
+        icu_data_path: icu_data.clone(),
+        ..Default::default()
+    };
+    let mut app = SctkApplication::new(attributes)?;
+
+    let palette_builder = FlutterEngineBuilder::new()
+        .with_asset_path(palette_assets.into())
+        .with_icu_data_path(icu_data);
+    let palette_attributes = ApplicationAttributes {
+        inner_size: Some(LogicalSize::new(200.0, 320.0).into()),
+        window_role: WindowRole::Utility {
+            attached_to: SctkEngineId::PRIMARY.as_u64(),
+            position: LogicalPosition::new(16, 16),
+        },
+        ..Default::default()
+    };
+    app.add_engine(palette_builder, palette_attributes)?;
+
+    app.run()?;
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! State shared with Dart through `flutter_plugins::bridge`: a counter which
+//! Dart increments with commands, and whose uptime is updated from another
+//! thread.
+//!
+//! The Dart side is generated with:
+//! `cargo run -p flutter-sctk --example bridge_counter -- --dart <counter.g.dart>`
+//!
+//! And used with `CounterBridge()..load()`, e.g.: through a
+//! `ValueListenableBuilder`. Then run the app with:
+//! `cargo run -p flutter-sctk --example bridge_counter -- <assets> <icudtl.dat>`
+use std::{env, error::Error, fs, process, thread, time::Duration};
+
+use flutter_plugins::bridge::{
+    generate_dart, Bridge, BridgeError, BridgePlugin, ChannelBridge, Value,
+};
+use flutter_runner_api::ApplicationAttributes;
+use flutter_sctk::application::SctkApplication;
+
+#[derive(Debug, ChannelBridge)]
+#[bridge(channel = "example/counter", commands = CounterCommand, diff)]
+struct Counter {
+    count: i64,
+    step: i64,
+    label: Option<String>,
+    history: Vec<i64>,
+    uptime_secs: u64,
+}
+
+#[derive(Debug, ChannelBridge)]
+enum CounterCommand {
+    #[bridge(result = "int")]
+    Increment,
+    SetStep {
+        step: i64,
+    },
+    SetLabel {
+        label: Option<String>,
+    },
+    Reset,
+}
+
+async fn handle_command(
+    command: CounterCommand,
+    bridge: Bridge<Counter>,
+) -> Result<Value, BridgeError> {
+    match command {
+        CounterCommand::Increment => {
+            let count = bridge.update(|counter| {
+                counter.count += counter.step;
+                counter.history.push(counter.count);
+                counter.count
+            });
+            Ok(Value::I64(count))
+        }
+        CounterCommand::SetStep { step } if step <= 0 => Err(BridgeError::Failed(format!(
+            "The step must be positive, not {step}"
+        ))),
+        CounterCommand::SetStep { step } => {
+            bridge.update(|counter| counter.step = step);
+            Ok(Value::Null)
+        }
+        CounterCommand::SetLabel { label } => {
+            bridge.update(|counter| counter.label = label);
+            Ok(Value::Null)
+        }
+        CounterCommand::Reset => {
+            bridge.update(|counter| {
+                counter.count = 0;
+                counter.history.clear();
+            });
+            Ok(Value::Null)
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let (Some(first), Some(second)) = (args.next(), args.next()) else {
+        eprintln!("Usage: bridge_counter <assets> <icudtl.dat> | --dart <counter.g.dart>");
+        process::exit(1);
+    };
+
+    if first == "--dart" {
+        fs::write(second, generate_dart::<Counter>())?;
+        return Ok(());
+    }
+
+    let bridge = Bridge::new(Counter {
+        count: 0,
+        step: 1,
+        label: None,
+        history: Vec::new(),
+        uptime_secs: 0,
+    });
+
+    // Updates may come from any thread, they are delivered to Dart from the
+    // platform thread.
+    let ticker = bridge.clone();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        ticker.update(|counter| counter.uptime_secs += 1);
+    });
+
+    let attributes = ApplicationAttributes {
+        title: Some("Bridge counter".into()),
+        assets_path: first.into(),
+        icu_data_path: second.into(),
+        ..Default::default()
+    };
+    let app = SctkApplication::new(attributes)?;
+    app.add_plugin(BridgePlugin::new(bridge, handle_command));
+    app.run()?;
+    Ok(())
+}
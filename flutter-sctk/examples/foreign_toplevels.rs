@@ -0,0 +1,164 @@
+//! Protocol extension written the way an out-of-tree crate would (see
+//! `flutter_sctk::extension`): it lists the toplevels of the other
+//! applications with `wlr-foreign-toplevel-management` (e.g.: for a task
+//! bar), which is only supported by the wlroots-based compositors.
+//!
+//! `cargo run -p flutter-sctk --example foreign_toplevels -- <assets> <icudtl.dat>`
+use std::{collections::HashMap, env, error::Error, process};
+
+use flutter_runner_api::ApplicationAttributes;
+use flutter_sctk::{
+    application::SctkApplication,
+    extension::{SctkProtocolExtension, WaylandHandles},
+};
+use smithay_client_toolkit::reexports::protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+use wayland_client::{
+    backend::ObjectId, event_created_child, Connection, Dispatch, DispatchError, EventQueue, Proxy,
+    QueueHandle,
+};
+
+#[derive(Debug, Default)]
+struct Toplevel {
+    title: String,
+    app_id: String,
+    is_activated: bool,
+}
+
+/// State of the queue of the extension, which its `Dispatch`
+/// implementations are for.
+#[derive(Default)]
+struct ForeignToplevelState {
+    toplevels: HashMap<ObjectId, Toplevel>,
+    has_changed: bool,
+}
+
+impl ForeignToplevelState {
+    fn print(&self) {
+        println!("{} toplevels:", self.toplevels.len());
+        for toplevel in self.toplevels.values() {
+            let marker = if toplevel.is_activated { '*' } else { ' ' };
+            println!("{marker} {} ({})", toplevel.title, toplevel.app_id);
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for ForeignToplevelState {
+    fn event(
+        state: &mut Self,
+        _manager: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                state.toplevels.insert(toplevel.id(), Toplevel::default());
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                println!("The compositor stopped listing the toplevels");
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(ForeignToplevelState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for ForeignToplevelState {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let Some(toplevel) = state.toplevels.get_mut(&handle.id()) else {
+            return;
+        };
+
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => toplevel.app_id = app_id,
+            zwlr_foreign_toplevel_handle_v1::Event::State { state: states } => {
+                let activated = zwlr_foreign_toplevel_handle_v1::State::Activated as u32;
+                toplevel.is_activated = states
+                    .chunks_exact(4)
+                    .any(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()) == activated);
+            }
+            // The properties are sent atomically, followed by `done`.
+            zwlr_foreign_toplevel_handle_v1::Event::Done => state.has_changed = true,
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                state.toplevels.remove(&handle.id());
+                handle.destroy();
+                state.has_changed = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Default)]
+struct ForeignToplevelExtension {
+    queue: Option<EventQueue<ForeignToplevelState>>,
+    manager: Option<ZwlrForeignToplevelManagerV1>,
+    state: ForeignToplevelState,
+}
+
+impl SctkProtocolExtension for ForeignToplevelExtension {
+    fn name(&self) -> &str {
+        "wlr-foreign-toplevel"
+    }
+
+    fn init(&mut self, handles: &WaylandHandles) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let queue = handles.connection().new_event_queue();
+        // The toplevels are announced once bound, and dispatched with the
+        // next event loop iteration.
+        self.manager = Some(handles.bind(&queue.handle(), 1..=3, ())?);
+        self.queue = Some(queue);
+        Ok(())
+    }
+
+    fn dispatch_pending(&mut self) -> Result<(), DispatchError> {
+        let Some(queue) = &mut self.queue else {
+            return Ok(());
+        };
+
+        queue.dispatch_pending(&mut self.state)?;
+        if std::mem::take(&mut self.state.has_changed) {
+            self.state.print();
+        }
+        Ok(())
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(manager) = self.manager.take() {
+            manager.stop();
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let (Some(assets_path), Some(icu_data_path)) = (args.next(), args.next()) else {
+        eprintln!("Usage: foreign_toplevels <assets> <icudtl.dat>");
+        process::exit(1);
+    };
+
+    let attributes = ApplicationAttributes {
+        title: Some("Foreign toplevels".into()),
+        assets_path: assets_path.into(),
+        icu_data_path: icu_data_path.into(),
+        ..Default::default()
+    };
+    let mut app = SctkApplication::new(attributes)?;
+    app.register_protocol_extension(Box::<ForeignToplevelExtension>::default())?;
+    app.run()?;
+    Ok(())
+}
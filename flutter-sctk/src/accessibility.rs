@@ -0,0 +1,135 @@
+//! Accessibility settings of the desktop, read from the settings portal.
+//!
+//! High contrast is requested either through the contrast preference of the
+//! portal (`org.freedesktop.appearance`), or through the GNOME accessibility
+//! setting (`org.gnome.desktop.a11y.interface`), which older portal backends
+//! don't map to the former. There is no portable setting for reducing
+//! transparency, so transparency is reduced along with high contrast.
+use ashpd::desktop::settings::{Contrast, Settings};
+use calloop::channel::Sender;
+use flutter_engine::ffi::FlutterAccessibilityFeatures;
+use futures_lite::{stream, StreamExt};
+
+use crate::{application::SctkApplicationRequest, handler::SctkAsyncResult};
+
+const GNOME_A11Y_NAMESPACE: &str = "org.gnome.desktop.a11y.interface";
+const GNOME_HIGH_CONTRAST_KEY: &str = "high-contrast";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct SctkAccessibilitySettings {
+    contrast: Option<Contrast>,
+    gnome_high_contrast: bool,
+}
+
+/// A setting read from the portal, or a change notified by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SctkAccessibilityChange {
+    Contrast(Contrast),
+    GnomeHighContrast(bool),
+}
+
+impl SctkAccessibilitySettings {
+    /// Returns whether the settings have changed.
+    pub(crate) fn apply(&mut self, change: SctkAccessibilityChange) -> bool {
+        let previous = *self;
+        match change {
+            SctkAccessibilityChange::Contrast(contrast) => self.contrast = Some(contrast),
+            SctkAccessibilityChange::GnomeHighContrast(enabled) => {
+                self.gnome_high_contrast = enabled
+            }
+        }
+        *self != previous
+    }
+
+    pub(crate) fn is_high_contrast(&self) -> bool {
+        self.contrast == Some(Contrast::High) || self.gnome_high_contrast
+    }
+
+    /// Whether transparent windows should be made opaque.
+    pub(crate) fn is_transparency_reduced(&self) -> bool {
+        self.is_high_contrast()
+    }
+
+    pub(crate) fn features(&self) -> FlutterAccessibilityFeatures {
+        FlutterAccessibilityFeatures {
+            high_contrast: self.is_high_contrast(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Read the accessibility settings, then forward their changes to the
+/// application until the portal goes away.
+pub(crate) async fn read_and_monitor_accessibility_changes(
+    sender: Sender<SctkApplicationRequest>,
+) -> SctkAsyncResult {
+    let settings = Settings::new().await?;
+
+    // The GNOME setting is missing from other desktops, and from sandboxes
+    // which don't expose it.
+    let current_values = [
+        settings
+            .contrast()
+            .await
+            .ok()
+            .map(SctkAccessibilityChange::Contrast),
+        settings
+            .read::<bool>(GNOME_A11Y_NAMESPACE, GNOME_HIGH_CONTRAST_KEY)
+            .await
+            .ok()
+            .map(SctkAccessibilityChange::GnomeHighContrast),
+    ];
+
+    let contrast_changes = settings
+        .receive_contrast_changed()
+        .await?
+        .map(SctkAccessibilityChange::Contrast);
+    let gnome_changes = settings
+        .receive_setting_changed_with_args::<bool>(GNOME_A11Y_NAMESPACE, GNOME_HIGH_CONTRAST_KEY)
+        .await?
+        .filter_map(|enabled| enabled.ok())
+        .map(SctkAccessibilityChange::GnomeHighContrast);
+
+    let mut stream = Box::pin(
+        stream::iter(current_values.into_iter().flatten())
+            .chain(contrast_changes.or(gnome_changes)),
+    );
+
+    while let Some(change) = stream.next().await {
+        if sender
+            .send(SctkApplicationRequest::UpdateAccessibility(change))
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ashpd::desktop::settings::Contrast;
+
+    use super::{SctkAccessibilityChange, SctkAccessibilitySettings};
+
+    #[test]
+    fn either_setting_enables_high_contrast() {
+        let mut settings = SctkAccessibilitySettings::default();
+        assert!(!settings.is_high_contrast());
+
+        assert!(!settings.apply(SctkAccessibilityChange::GnomeHighContrast(false)));
+        assert!(settings.apply(SctkAccessibilityChange::Contrast(Contrast::High)));
+        assert!(settings.is_high_contrast());
+        assert!(settings.is_transparency_reduced());
+        assert!(settings.features().high_contrast);
+
+        assert!(settings.apply(SctkAccessibilityChange::GnomeHighContrast(true)));
+        assert!(settings.apply(SctkAccessibilityChange::Contrast(Contrast::NoPreference)));
+        assert!(settings.is_high_contrast());
+
+        assert!(settings.apply(SctkAccessibilityChange::GnomeHighContrast(false)));
+        assert!(!settings.is_high_contrast());
+        assert!(!settings.features().high_contrast);
+    }
+}
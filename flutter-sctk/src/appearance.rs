@@ -0,0 +1,35 @@
+//! Accent color of the desktop, read from the settings portal
+//! (`org.freedesktop.appearance accent-color`). Portal backends without the
+//! key (or older ones, which don't know about it) leave it unset, and the
+//! apps fall back to their own seed color.
+use ashpd::desktop::{settings::Settings, Color};
+use calloop::channel::Sender;
+use flutter_plugins::appearance::AccentColor;
+use futures_lite::{stream, StreamExt};
+
+use crate::{application::SctkApplicationRequest, handler::SctkAsyncResult};
+
+fn to_accent_color(color: Color) -> Option<AccentColor> {
+    AccentColor::from_rgb(color.red(), color.green(), color.blue())
+}
+
+/// Read the accent color, then forward its changes to the application until
+/// the portal goes away.
+pub(crate) async fn read_and_monitor_accent_color_changes(
+    sender: Sender<SctkApplicationRequest>,
+) -> SctkAsyncResult {
+    let settings = Settings::new().await?;
+
+    let current_value = settings.accent_color().await.ok();
+    let value_changes = settings.receive_accent_color_changed().await?;
+
+    let mut stream = Box::pin(stream::iter(current_value).chain(value_changes));
+    while let Some(color) = stream.next().await {
+        let request = SctkApplicationRequest::UpdateAccentColor(to_accent_color(color));
+        if sender.send(request).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Input emulated on behalf of the apps (see `crate::emulated_input`).
+use flutter_plugins::emulated_input::{EmulatedInputError, EmulatedInputEvent};
+use tracing::error;
+
+use super::{SctkApplicationState, SctkEngineId};
+use crate::emulated_input::SctkEmulatedInputRequest;
+
+impl SctkApplicationState {
+    #[cfg(feature = "emulated-input")]
+    pub(super) fn handle_emulated_input(&mut self, request: SctkEmulatedInputRequest) {
+        let result = match request {
+            SctkEmulatedInputRequest::RequestPermission(reply) => {
+                let Some(task) = self.emulated_input.request_permission(reply) else {
+                    return;
+                };
+                self.async_scheduler.schedule(task)
+            }
+            SctkEmulatedInputRequest::Inject(event) => {
+                let Some(task) = self.emulated_input.inject(event) else {
+                    return;
+                };
+                self.async_scheduler.schedule(task)
+            }
+            SctkEmulatedInputRequest::WarpPointer {
+                engine_id,
+                x,
+                y,
+                reply,
+            } => {
+                let Some(event) = self.warp_pointer_event(engine_id, x, y) else {
+                    return reply(Err(EmulatedInputError::PointerOutside));
+                };
+                // Failures of the delivery are reported by the next status,
+                // like for the other events.
+                reply(Ok(()));
+                let Some(task) = self.emulated_input.inject(event) else {
+                    return;
+                };
+                self.async_scheduler.schedule(task)
+            }
+        };
+
+        if let Err(err) = result {
+            error!("Failed to schedule emulated input task: {}", err);
+        }
+    }
+
+    /// Relative motion moving the pointer to |x|, |y| in the logical
+    /// coordinates of the view of the window of |engine_id| it is within.
+    fn warp_pointer_event(
+        &self,
+        engine_id: SctkEngineId,
+        x: f64,
+        y: f64,
+    ) -> Option<EmulatedInputEvent> {
+        let instance = self.engines.get(&engine_id)?;
+        instance.windows.values().find_map(|window| {
+            let position = window.pointer_position()?;
+            // Emulated motions are in surface-local coordinates, which differ
+            // from the ones of the view while the content is zoomed.
+            let content_scale = window.content_scale();
+            Some(EmulatedInputEvent::PointerMotion {
+                dx: x * content_scale - position.x,
+                dy: y * content_scale - position.y,
+            })
+        })
+    }
+}
@@ -0,0 +1,43 @@
+//! Hooks reading the presented frames back, e.g.: for golden tests (see
+//! `crate::golden`).
+use std::{path::PathBuf, time::Duration};
+
+use tracing::warn;
+
+use super::SctkApplication;
+use crate::{golden::SctkFrameRecorder, readback::SctkFrameReadback};
+
+impl SctkApplication {
+    /// Stream the frames presented in the implicit window to a callback (see
+    /// [`SctkFrameReadback`]).
+    pub fn set_frame_readback(&self, frame_readback: Option<SctkFrameReadback>) {
+        for window in self.state.windows() {
+            window.set_frame_readback(frame_readback.clone());
+        }
+    }
+
+    /// Write the next |count| frames presented in the implicit window to
+    /// numbered PNG files in |dir| (see [`SctkFrameRecorder`]), |interval|
+    /// apart in the fake time base. The frames are still paced by the
+    /// compositor, only the time seen by Dart advances by |interval|.
+    ///
+    /// Captures are only reproducible with a fake time base (see
+    /// `ApplicationAttributes::fake_time_base`) and a fixed pixel ratio (see
+    /// `ApplicationAttributes::pixel_ratio`).
+    pub fn record_frames(
+        &self,
+        count: usize,
+        interval: Duration,
+        dir: impl Into<PathBuf>,
+    ) -> SctkFrameRecorder {
+        for instance in self.state.engines.values() {
+            if !instance.engine.set_fake_frame_interval(interval) {
+                warn!("Recording frames without a fake time base, they won't be reproducible");
+            }
+        }
+
+        let recorder = SctkFrameRecorder::new(dir, count);
+        self.set_frame_readback(Some(recorder.readback()));
+        recorder
+    }
+}
@@ -0,0 +1,152 @@
+//! Mirror windows of the views of the primary engine (see `crate::mirror`).
+use dpi::LogicalSize;
+use flutter_engine::ffi::FlutterViewId;
+use flutter_engine_sys::FlutterEngineDisplayId;
+use flutter_runner_api::RenderPath;
+use smithay_client_toolkit::{
+    seat::pointer::PointerEvent,
+    shell::{xdg::window::WindowDecorations, WaylandSurface},
+};
+use wayland_backend::client::ObjectId;
+use wayland_client::Proxy;
+
+use super::SctkApplicationState;
+use crate::{
+    mirror::{
+        SctkMirrorInput, SctkMirrorTarget, SctkMirrorWindow, SctkMirrorWindowError, SctkMirrors,
+    },
+    units::Scale,
+    window::ConfigureSize,
+};
+
+impl SctkApplicationState {
+    pub(super) fn create_mirror_window(
+        &mut self,
+        source_view: FlutterViewId,
+        target_output: Option<FlutterEngineDisplayId>,
+        input: SctkMirrorInput,
+    ) -> Result<ObjectId, SctkMirrorWindowError> {
+        let source = self
+            .find_window_by_view_id(source_view)
+            .ok_or(SctkMirrorWindowError::UnknownView(source_view))?;
+
+        if source.render_path() != RenderPath::Compositor {
+            return Err(SctkMirrorWindowError::CompositorRequired);
+        }
+
+        let output = target_output
+            .map(|display_id| {
+                self.output_state
+                    .outputs()
+                    .find(|output| {
+                        FlutterEngineDisplayId::from(output.id().protocol_id()) == display_id
+                    })
+                    .ok_or(SctkMirrorWindowError::UnknownOutput(display_id))
+            })
+            .transpose()?;
+
+        // Use the size of the source window until the compositor picks one.
+        let logical_size = source
+            .physical_size()
+            .map(|size| source.scale_factor().to_logical_size(size))
+            .unwrap_or(LogicalSize::new(1280, 720));
+
+        let surface = self.compositor_state.create_surface(&self.qh);
+        let window =
+            self.xdg_shell_state
+                .create_window(surface, WindowDecorations::ServerDefault, &self.qh);
+
+        if let Some(output) = &output {
+            window.set_fullscreen(Some(output));
+        }
+        window.commit();
+
+        // The mirror surface scale factor is only known once it is shown.
+        let size = Scale::default().to_physical_size(logical_size);
+        let mirror_surface = source.mirrors().create_surface(window.wl_surface(), size)?;
+
+        let id = window.wl_surface().id();
+        source.mirrors().insert(
+            id.clone(),
+            SctkMirrorTarget::new(mirror_surface, window.clone(), self.qh.clone(), size),
+        );
+        self.mirrors.insert(
+            id.clone(),
+            SctkMirrorWindow::new(window, source_view, input, logical_size),
+        );
+
+        Ok(id)
+    }
+
+    pub(super) fn destroy_mirror_window(&mut self, id: &ObjectId) -> bool {
+        let Some(mirror) = self.mirrors.remove(id) else {
+            return false;
+        };
+
+        if let Some(source) = self.find_window_by_view_id(mirror.source_view()) {
+            source.remove_mirror(id);
+
+            // The pointers forwarded to the source window don't get a leave
+            // event when the mirror is destroyed under them.
+            for (pointer, surface) in &self.pointer_surfaces {
+                if surface == id {
+                    source.remove_pointer(pointer);
+                }
+            }
+        }
+        self.pointer_surfaces.retain(|_, surface| *surface != *id);
+
+        true
+    }
+
+    pub(super) fn configure_mirror(&mut self, id: &ObjectId, (width, height): ConfigureSize) {
+        let Some(mirror) = self.mirrors.get_mut(id) else {
+            return;
+        };
+
+        mirror.configure(width, height);
+        let size = mirror.physical_size();
+        self.update_mirror_target(id, |mirrors| mirrors.configure(id, size));
+    }
+
+    /// Update the render thread state of the |id| mirror window. The mirror
+    /// is presented when `f` returns `true`.
+    pub(super) fn update_mirror_target<F>(&self, id: &ObjectId, f: F)
+    where
+        F: FnOnce(&SctkMirrors) -> bool,
+    {
+        let Some(mirror) = self.mirrors.get(id) else {
+            return;
+        };
+
+        let Some(source) = self.find_window_by_view_id(mirror.source_view()) else {
+            return;
+        };
+
+        if f(source.mirrors()) {
+            source.schedule_mirrors_present();
+        }
+    }
+
+    /// Convert a pointer |event| of a mirror window to an event of its source
+    /// window. Returns `None` when the input of the mirror is dropped.
+    pub(super) fn mirror_pointer_event(&self, event: &PointerEvent) -> Option<PointerEvent> {
+        let mirror = self.mirrors.get(&event.surface.id())?;
+        if mirror.input() == SctkMirrorInput::Drop {
+            return None;
+        }
+
+        let source = self.find_window_by_view_id(mirror.source_view())?;
+        let position = mirror.map_to_source(
+            event.position.into(),
+            source.physical_size()?,
+            source.scale_factor(),
+        )?;
+
+        Some(PointerEvent {
+            surface: source.wl_surface(),
+            position: position.into(),
+            kind: event.kind.clone(),
+        })
+    }
+}
@@ -0,0 +1,3425 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Debug,
+    num::{NonZeroU32, NonZeroUsize},
+    ops::ControlFlow,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use calloop::futures::{Executor, Scheduler};
+use dpi::{LogicalSize, PhysicalSize};
+use flutter_engine::{
+    builder::FlutterEngineBuilder,
+    ffi::{
+        FlutterEngineDisplay, FlutterKeyEventDeviceType, FlutterKeyEventType, FlutterPointerPhase,
+        FlutterViewId,
+    },
+    plugins::{DeferredMessage, Plugin},
+    pointer_queue::PointerBackpressure,
+    prepare::PrepareError,
+    schedule::ScheduleHandle,
+    CreateError, FlutterEngine, FlutterEngineWeakRef, RunError,
+};
+use flutter_plugins::{
+    assets::AssetsPlugin, lifecycle::LifecyclePlugin, platform::PlatformHandler,
+    textinput::TextInputPlugin, window::WindowPlugin,
+};
+use flutter_plugins::{
+    anchor::PopupPlacement,
+    appearance::{AccentColor, AppearancePlugin},
+    diagnostics::ScrollSettings,
+    keyboard_repeat::{KeyRepeatInfo, KeyboardRepeatPlugin},
+    settings::SettingsPlugin,
+    text_toolbar::{TextToolbarAction, TextToolbarPlugin, TextToolbarRect},
+    window::Visibility,
+};
+use flutter_engine_sys::FlutterEngineDisplayId;
+use flutter_glutin::renderer::RendererInfo;
+use flutter_input::keyboard::{edit_text, KeyEventFallthrough};
+use flutter_runner_api::{
+    ApplicationAttributes, FrameStats, HostClock, HostContext, PumpResult,
+    StartupFailure, StartupFailureReason, WindowRole,
+};
+use tracing::{error, info, trace, warn};
+use parking_lot::Mutex;
+use smithay_client_toolkit::{
+    activation::{ActivationHandler, ActivationState, RequestData},
+    compositor::{CompositorHandler, CompositorState, SurfaceData},
+    delegate_activation, delegate_compositor, delegate_keyboard, delegate_layer, delegate_output,
+    delegate_pointer, delegate_registry, delegate_seat, delegate_shm, delegate_touch,
+    delegate_xdg_popup, delegate_xdg_shell, delegate_xdg_window,
+    output::{OutputHandler, OutputState},
+    reexports::{
+        calloop::{
+            self,
+            channel::{Event, Sender},
+            signals::{Signal, Signals},
+            timer::{TimeoutAction, Timer},
+            EventLoop, LoopHandle, LoopSignal, RegistrationToken,
+        },
+        calloop_wayland_source::WaylandSource,
+        protocols::wp::{
+            color_management::v1::client::{
+                wp_color_management_output_v1::{self, WpColorManagementOutputV1},
+                wp_color_management_surface_v1::WpColorManagementSurfaceV1,
+                wp_color_manager_v1::{self, WpColorManagerV1},
+                wp_image_description_creator_params_v1::WpImageDescriptionCreatorParamsV1,
+                wp_image_description_info_v1::{self, WpImageDescriptionInfoV1},
+                wp_image_description_v1::{self, WpImageDescriptionV1},
+            },
+            idle_inhibit::zv1::client::{
+                zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1,
+                zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+            },
+            pointer_gestures::zv1::client::{
+                zwp_pointer_gesture_pinch_v1::{self, ZwpPointerGesturePinchV1},
+                zwp_pointer_gestures_v1::ZwpPointerGesturesV1,
+            },
+            linux_dmabuf::zv1::client::{
+                zwp_linux_buffer_params_v1::{self, ZwpLinuxBufferParamsV1},
+                zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+            },
+            single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1,
+            text_input::zv3::client::{
+                zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+                zwp_text_input_v3::{self, ZwpTextInputV3},
+            },
+            viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
+        },
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers, RepeatInfo},
+        pointer::{
+            cursor_shape::CursorShapeManager, CursorIcon, PointerData, PointerEvent,
+            PointerEventKind, PointerHandler, ThemeSpec, BTN_LEFT,
+        },
+        touch::TouchHandler,
+        Capability, SeatHandler, SeatState,
+    },
+    shell::{
+        wlr_layer::{LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
+        xdg::{
+            popup::{Popup, PopupConfigure, PopupHandler},
+            window::{Window, WindowConfigure, WindowHandler},
+            XdgShell,
+        },
+        WaylandSurface,
+    },
+    shm::{Shm, ShmHandler},
+};
+use thiserror::Error;
+use wayland_backend::client::ObjectId;
+use wayland_client::{
+    delegate_noop,
+    globals::{registry_queue_init, BindError, GlobalError, GlobalList},
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_keyboard::WlKeyboard,
+        wl_output::{Transform, WlOutput},
+        wl_pointer::WlPointer,
+        wl_region::WlRegion,
+        wl_seat::WlSeat,
+        wl_subcompositor::WlSubcompositor,
+        wl_subsurface::WlSubsurface,
+        wl_surface::WlSurface,
+        wl_touch::WlTouch,
+    },
+    ConnectError, Connection, Dispatch, Proxy, QueueHandle,
+};
+
+#[cfg(feature = "autofill-freedesktop")]
+use crate::autofill::SecretServiceAutofill;
+#[cfg(feature = "emulated-input")]
+use crate::emulated_input::SctkEmulatedInput;
+#[cfg(feature = "status-notifier")]
+use crate::tray::SctkTray;
+use crate::{
+    accessibility::{self, SctkAccessibilityChange, SctkAccessibilitySettings},
+    appearance,
+    color::{SctkColorManagement, SctkImageDescriptionData, SctkImageDescriptionInfoData},
+    engine::{
+        SctkEngineInstance, SctkEngineResources, StartupState, STARTUP_TIMEOUT,
+    },
+    extension::{SctkProtocolExtension, SctkProtocolExtensionError, WaylandHandles},
+    failure::SctkFailureScreen,
+    handler::{
+        SctkAsyncResult, SctkMouseCursorHandler, SctkScreenSaverInhibitor, SctkSeatCursor,
+        SctkSettingsHandler, FRAME_INTERVAL_60_HZ_IN_NANOS,
+    },
+    input_gate::SctkGatedInput,
+    keyboard::SctkKeyEvent,
+    mirror::{SctkMirrorInput, SctkMirrorWindow, SctkMirrorWindowError},
+    occlusion::OCCLUSION_POLL_INTERVAL,
+    output::{self, SctkOutput, SctkOutputScaleTracker},
+    overlay::SctkOverlayBufferData,
+    pointer::{SctkPinchGestureData, TouchPoint, TouchPoints},
+    scroll::{self, SctkScrollChange, SctkScrollSettings},
+    shutdown::{ShutdownCoordinator, ShutdownPhase, ShutdownPoll},
+    stacking::SctkWindowStack,
+    text_input::{SctkTextInput, SctkTextInputData},
+    text_toolbar::SctkTextToolbar,
+    units::Scale,
+    window::{SctkFlutterWindow, SctkFlutterWindowCreateError},
+    window_state,
+    zoom::{ZoomAction, ZoomScroll},
+};
+
+#[cfg(feature = "emulated-input")]
+mod emulated_input;
+mod golden;
+mod mirror;
+mod occlusion;
+mod registry;
+mod routing;
+mod stacking;
+#[cfg(feature = "status-notifier")]
+mod tray;
+mod zoom;
+
+pub use registry::SctkEngineId;
+pub(crate) use registry::SctkApplicationRequest;
+use stacking::{AttachedDrag, PendingRaise};
+
+/// Timing information for a Wayland frame callback.
+#[derive(Debug, Clone, Copy)]
+pub struct SctkFrameTime {
+    /// Timestamp (in milliseconds) provided by the compositor.
+    pub time: u32,
+    pub frame_start_time_nanos: u64,
+    pub frame_target_time_nanos: u64,
+}
+
+type SctkFrameCallback = Box<dyn FnMut(&SctkFrameTime)>;
+
+type SctkVisibilityCallback = Box<dyn FnMut(&SctkFlutterWindow, Visibility)>;
+
+/// Maximum time spent waiting for events at once while shutting down, so
+/// that the shutdown tasks are polled frequently.
+const SHUTDOWN_PUMP_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct SctkApplication {
+    event_loop: EventLoop<'static, SctkApplicationState>,
+    state: SctkApplicationState,
+    request_sender: Sender<SctkApplicationRequest>,
+    shutdown_coordinator: ShutdownCoordinator<SctkApplicationState>,
+}
+
+/// Thread-safe handle used for controlling a running application.
+#[derive(Clone)]
+pub struct SctkApplicationHandle {
+    sender: Sender<SctkApplicationRequest>,
+    engine: FlutterEngineWeakRef,
+}
+
+impl SctkApplicationHandle {
+    /// Pause frame scheduling and notify the app that it is paused. When
+    /// `drop_caches` is set, the engine is also asked to release its caches.
+    pub fn suspend(&self, drop_caches: bool) {
+        self.send(SctkApplicationRequest::Suspend { drop_caches });
+    }
+
+    /// Restore the app activity state after a previous call to `suspend`.
+    pub fn resume(&self) {
+        self.send(SctkApplicationRequest::Resume);
+    }
+
+    /// Cap the frame rate below the refresh rate of the outputs, or render on
+    /// every refresh when `None`.
+    pub fn set_min_frame_interval(&self, interval: Option<Duration>) {
+        self.send(SctkApplicationRequest::SetMinFrameInterval(interval));
+    }
+
+    /// Show or hide the implicit window (e.g.: from a tray icon), see
+    /// [`SctkFlutterWindow::hide`].
+    pub fn set_window_visible(&self, visible: bool) {
+        self.send(SctkApplicationRequest::SetWindowVisible(visible));
+    }
+
+    /// Quit the application, which is then shut down (see
+    /// [`ShutdownPhase`]).
+    pub fn quit(&self) {
+        self.send(SctkApplicationRequest::Exit);
+    }
+
+    /// See [`FlutterEngine::schedule_at`]. Returns `None` if the engine is
+    /// gone.
+    pub fn schedule_at<F>(&self, engine_time_nanos: u64, f: F) -> Option<ScheduleHandle>
+    where
+        F: FnOnce(&FlutterEngine) + 'static + Send,
+    {
+        Some(self.engine.upgrade()?.schedule_at(engine_time_nanos, f))
+    }
+
+    /// See [`FlutterEngine::schedule_repeating`]. Returns `None` if the engine
+    /// is gone.
+    pub fn schedule_repeating<F>(&self, interval: Duration, f: F) -> Option<ScheduleHandle>
+    where
+        F: FnMut(&FlutterEngine) + 'static + Send,
+    {
+        Some(self.engine.upgrade()?.schedule_repeating(interval, f))
+    }
+
+    /// The engine, e.g.: for sending messages from another thread. Returns
+    /// `None` if the engine is gone.
+    pub fn engine(&self) -> Option<FlutterEngine> {
+        self.engine.upgrade()
+    }
+
+    fn send(&self, request: SctkApplicationRequest) {
+        if self.sender.send(request).is_err() {
+            warn!("Ignoring application request because the event loop is gone");
+        }
+    }
+}
+
+/// State of the event loop, which dispatches the Wayland events and the
+/// application requests. The handling of each feature (e.g.: stacking,
+/// mirrors or zoom) is implemented in a submodule.
+pub struct SctkApplicationState {
+    conn: Connection,
+    qh: QueueHandle<SctkApplicationState>,
+    loop_handle: LoopHandle<'static, SctkApplicationState>,
+    loop_signal: LoopSignal,
+    registry_state: RegistryState,
+    compositor_state: CompositorState,
+    xdg_shell_state: XdgShell,
+    shm_state: Shm,
+    output_state: OutputState,
+    output_scales: SctkOutputScaleTracker,
+    seat_state: SeatState,
+    /// Bound again for the windows of the engines added later on.
+    globals: GlobalList,
+    engines: BTreeMap<SctkEngineId, SctkEngineInstance>,
+    next_engine_id: SctkEngineId,
+    /// Surface which had the keyboard focus last. Its engine handles the
+    /// keyboard and text input (see `focused`).
+    keyboard_focus: Option<ObjectId>,
+    mirrors: HashMap<ObjectId, SctkMirrorWindow>,
+    active_state: HashMap<ObjectId, bool>,
+    pointers: HashMap<ObjectId, WlPointer>,
+    /// Surface each pointer is currently over, keyed by the pointer.
+    pointer_surfaces: HashMap<ObjectId, ObjectId>,
+    pointer_gestures: Option<ZwpPointerGesturesV1>,
+    cursor_shape_manager: Option<CursorShapeManager>,
+    /// Used for flagging the windows as urgent (`xdg_activation_v1`).
+    activation_state: Option<ActivationState>,
+    /// Windows flagged as urgent, until they are focused.
+    urgent_surfaces: HashSet<ObjectId>,
+    /// Stacking order of the windows of the engines (see `crate::stacking`).
+    window_stack: SctkWindowStack,
+    /// Toplevel being raised, until the compositor activates it.
+    pending_raise: Option<PendingRaise>,
+    /// Attached window following the pointer (see `start_attached_drag`).
+    attached_drag: Option<AttachedDrag>,
+    /// Declares the content of the windows as sRGB, and describes the
+    /// outputs (see `crate::color`).
+    color_management: Option<SctkColorManagement>,
+    pinch_gestures: HashMap<ObjectId, ZwpPointerGesturePinchV1>,
+    keyboards: HashMap<ObjectId, WlKeyboard>,
+    touches: HashMap<ObjectId, WlTouch>,
+    /// Touch points which are down, each targeting the surface it went down
+    /// on.
+    touch_points: TouchPoints<ObjectId>,
+    /// Shared by the engines, as the cursor belongs to the seat.
+    mouse_cursor_handler: Arc<Mutex<SctkMouseCursorHandler>>,
+    /// Shared with the autofill observer.
+    async_scheduler: Rc<Scheduler<SctkAsyncResult>>,
+    is_suspended: bool,
+    modifiers: Modifiers,
+    frame_callbacks: Vec<SctkFrameCallback>,
+    visibility_callbacks: Vec<SctkVisibilityCallback>,
+    text_toolbar: Option<SctkTextToolbar>,
+    text_input: Option<SctkTextInput>,
+    last_pointer_press: Option<(WlSeat, u32)>,
+    /// Surface the pointer moved over last, and its position on it.
+    last_pointer_position: Option<(ObjectId, (f64, f64))>,
+    screensaver_inhibitor: SctkScreenSaverInhibitor,
+    #[cfg(feature = "emulated-input")]
+    emulated_input: SctkEmulatedInput,
+    /// Shared by the engines, as there is a single icon per application.
+    #[cfg(feature = "status-notifier")]
+    tray: SctkTray,
+    /// Whether the zoom shortcuts are handled (see `crate::zoom`).
+    is_content_zoom_enabled: bool,
+    /// Keys of the zoom shortcuts currently pressed, which are not sent to
+    /// the engine.
+    zoom_keys: HashSet<u32>,
+    zoom_scroll: ZoomScroll,
+    accessibility: SctkAccessibilitySettings,
+    /// Accent color of the desktop, given to the engines created later on.
+    accent_color: Option<AccentColor>,
+    scroll: SctkScrollSettings,
+    /// The scroll settings reported to the diagnostics of every engine.
+    scroll_settings: Arc<Mutex<ScrollSettings>>,
+    /// Timer waking the loop up for the next platform task, and its deadline.
+    platform_task_timer: Option<(RegistrationToken, Instant)>,
+    /// See `ApplicationAttributes::platform_task_batch_size`.
+    platform_task_batch_size: Option<NonZeroUsize>,
+    /// Used by the async tasks for reporting back to the event loop.
+    request_sender: Sender<SctkApplicationRequest>,
+    /// See `crate::extension`.
+    extensions: Vec<Box<dyn SctkProtocolExtension>>,
+    /// Why the primary engine failed to start, returned by `run`.
+    startup_error: Option<SctkApplicationRunError>,
+    is_started: bool,
+    is_exiting: bool,
+}
+
+impl SctkApplication {
+    pub fn new(attributes: ApplicationAttributes) -> Result<Self, SctkApplicationCreateError> {
+        let mut engine_builder = FlutterEngineBuilder::new()
+            .with_asset_path(attributes.assets_path.clone())
+            .with_icu_data_path(attributes.icu_data_path.clone())
+            .with_embedded_icu_data(attributes.icu_data_embedded)
+            .with_persistent_cache_path(attributes.persistent_cache_path.clone())
+            .with_args(attributes.args.clone())
+            .with_vm_service_auth_codes_disabled(attributes.vm_service_auth_codes_disabled);
+        engine_builder = attributes.isolate_callbacks.register(engine_builder);
+
+        if let Some(port) = attributes.vm_service_port {
+            engine_builder = engine_builder.with_vm_service_port(port);
+        }
+
+        if let Some(time_base) = attributes.fake_time_base {
+            engine_builder = engine_builder.with_fake_time_base(time_base);
+        }
+
+        // Read the engine artifacts while the connection to the compositor is
+        // being set up, which mostly helps cold starts on slow storage.
+        let prepare = attributes
+            .prepare_timeout
+            .map(|timeout| (engine_builder.prepare_in_background(), timeout));
+
+        let conn = Connection::connect_to_env()?;
+        let (globals, event_queue) = registry_queue_init(&conn)?;
+        let qh = event_queue.handle();
+
+        let event_loop: EventLoop<SctkApplicationState> = EventLoop::try_new()?;
+        let wayland_token =
+            WaylandSource::new(conn.clone(), event_queue).insert(event_loop.handle())?;
+
+        let (async_executor, async_scheduler) = calloop::futures::executor::<SctkAsyncResult>()?;
+        event_loop.handle().insert_source(
+            async_executor,
+            |event, _metadata, _state| match event {
+                Ok(_) => {} // no-op
+                Err(err) => error!("sctk async error: {:?}", err),
+            },
+        )?;
+
+        let (request_sender, request_channel) = calloop::channel::channel();
+        event_loop
+            .handle()
+            .insert_source(request_channel, |event, _metadata, state| {
+                if let Event::Msg(request) = event {
+                    state.handle_request(request);
+                }
+            })
+            .map_err(|err| err.error)?;
+
+        if attributes.always_on_top {
+            warn!("Keeping the window above the others is not supported on Wayland");
+        }
+
+        if attributes.skip_taskbar {
+            warn!("Keeping the window out of the taskbar is not supported on Wayland");
+        }
+
+        // Block the signals before the engine creates its threads, as they
+        // inherit the signal mask of the platform thread (the signals could
+        // otherwise be delivered to any of them).
+        if attributes.handle_termination_signals {
+            let signals = Signals::new(&[Signal::SIGTERM, Signal::SIGINT])?;
+            event_loop
+                .handle()
+                .insert_source(signals, |event, _metadata, state| {
+                    info!("Received {:?}, exiting", event.signal());
+                    state.exit();
+                })
+                .map_err(|err| err.error)?;
+        }
+
+        let registry_state = RegistryState::new(&globals);
+        let output_state = OutputState::new(&globals, &qh);
+        let seat_state = SeatState::new(&globals, &qh);
+        let compositor_state = CompositorState::bind(&globals, &qh)?;
+        let xdg_shell_state = XdgShell::bind(&globals, &qh)?;
+        let shm_state = Shm::bind(&globals, &qh)?;
+        let pointer_gestures: Option<ZwpPointerGesturesV1> = globals.bind(&qh, 1..=1, ()).ok();
+        let cursor_shape_manager = CursorShapeManager::bind(&globals, &qh).ok();
+        let activation_state = ActivationState::bind(&globals, &qh).ok();
+        let text_input_manager: Option<ZwpTextInputManagerV3> = globals.bind(&qh, 1..=1, ()).ok();
+        let color_management = SctkColorManagement::bind(&globals, &qh);
+
+        let mouse_cursor_handler = Arc::new(Mutex::new(SctkMouseCursorHandler::new(conn.clone())));
+        let screensaver_inhibitor = SctkScreenSaverInhibitor::default();
+        #[cfg(feature = "emulated-input")]
+        let emulated_input = SctkEmulatedInput::new(&attributes.persistent_cache_path);
+        #[cfg(feature = "status-notifier")]
+        let tray = SctkTray::new(
+            attributes
+                .app_id
+                .as_deref()
+                .or(attributes.title.as_deref())
+                .unwrap_or("flutter-rs"),
+        );
+
+        // Surface invalid bundles before the window is shown.
+        if let Some((prepare_handle, timeout)) = prepare {
+            prepare_handle.wait(timeout)?;
+        }
+
+        let is_content_zoom_enabled = attributes.content_zoom;
+        let scroll = SctkScrollSettings::new(attributes.plugin_configs.get_or_default());
+        let scroll_settings = Arc::new(Mutex::new(scroll.effective()));
+        let platform_task_batch_size = attributes.platform_task_batch_size;
+
+        // There is no other window to attach the primary one to.
+        if let WindowRole::Utility { attached_to, .. } = attributes.window_role {
+            return Err(SctkApplicationCreateError::InvalidAttachment(attached_to));
+        }
+
+        let primary_engine = SctkEngineInstance::new(
+            SctkEngineResources {
+                conn: &conn,
+                globals: &globals,
+                qh: &qh,
+                loop_signal: &event_loop.get_signal(),
+                compositor_state: &compositor_state,
+                xdg_shell_state: &xdg_shell_state,
+                mouse_cursor_handler: &mouse_cursor_handler,
+                screensaver_inhibitor: &screensaver_inhibitor,
+                scroll_settings: &scroll_settings,
+                #[cfg(feature = "emulated-input")]
+                emulated_input: &emulated_input,
+                #[cfg(feature = "status-notifier")]
+                tray: &tray,
+                request_sender: &request_sender,
+            },
+            SctkEngineId::PRIMARY,
+            engine_builder,
+            attributes,
+            None,
+        )?;
+
+        let state = SctkApplicationState {
+            conn,
+            qh,
+            loop_handle: event_loop.handle(),
+            loop_signal: event_loop.get_signal(),
+            engines: BTreeMap::from([(SctkEngineId::PRIMARY, primary_engine)]),
+            next_engine_id: SctkEngineId::PRIMARY.next(),
+            keyboard_focus: None,
+            mirrors: HashMap::new(),
+            pointers: HashMap::new(),
+            pointer_surfaces: HashMap::new(),
+            pointer_gestures,
+            cursor_shape_manager,
+            activation_state,
+            urgent_surfaces: HashSet::new(),
+            window_stack: SctkWindowStack::new(SctkEngineId::PRIMARY),
+            pending_raise: None,
+            attached_drag: None,
+            color_management,
+            pinch_gestures: HashMap::new(),
+            keyboards: HashMap::new(),
+            touches: HashMap::new(),
+            touch_points: TouchPoints::default(),
+            active_state: HashMap::new(),
+            compositor_state,
+            xdg_shell_state,
+            shm_state,
+            registry_state,
+            output_state,
+            output_scales: Default::default(),
+            seat_state,
+            globals,
+            mouse_cursor_handler,
+            async_scheduler: Rc::new(async_scheduler),
+            is_suspended: false,
+            modifiers: Modifiers::default(),
+            frame_callbacks: Vec::new(),
+            visibility_callbacks: Vec::new(),
+            text_toolbar: None,
+            text_input: text_input_manager.map(SctkTextInput::new),
+            last_pointer_press: None,
+            last_pointer_position: None,
+            screensaver_inhibitor,
+            #[cfg(feature = "emulated-input")]
+            emulated_input,
+            #[cfg(feature = "status-notifier")]
+            tray,
+            is_content_zoom_enabled,
+            zoom_keys: HashSet::new(),
+            zoom_scroll: ZoomScroll::default(),
+            accessibility: SctkAccessibilitySettings::default(),
+            accent_color: None,
+            scroll,
+            scroll_settings,
+            platform_task_timer: None,
+            platform_task_batch_size,
+            request_sender: request_sender.clone(),
+            extensions: Vec::new(),
+            startup_error: None,
+            is_started: false,
+            is_exiting: false,
+        };
+
+        let mut shutdown_coordinator = ShutdownCoordinator::new();
+        register_shutdown_tasks(&mut shutdown_coordinator, wayland_token);
+
+        Ok(Self {
+            event_loop,
+            state,
+            request_sender,
+            shutdown_coordinator,
+        })
+    }
+
+    pub fn handle(&self) -> SctkApplicationHandle {
+        SctkApplicationHandle {
+            sender: self.request_sender.clone(),
+            engine: self.state.primary().engine.downgrade(),
+        }
+    }
+
+    /// Registers a callback invoked on the platform thread for every frame
+    /// callback received from the compositor, right after the engine has been
+    /// notified of the vsync event.
+    pub fn add_frame_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&SctkFrameTime) + 'static,
+    {
+        self.state.frame_callbacks.push(Box::new(callback));
+    }
+
+    /// Registers a callback invoked on the platform thread whenever the
+    /// estimated visibility of a window changes (see
+    /// [`SctkFlutterWindow::visibility`]), after the app has been notified.
+    pub fn add_visibility_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&SctkFlutterWindow, Visibility) + 'static,
+    {
+        self.state.visibility_callbacks.push(Box::new(callback));
+    }
+
+    /// Registers an additional plugin (e.g.: app specific platform channels or
+    /// `flutter_plugins::latency::LatencyProbePlugin`) with the primary
+    /// engine.
+    pub fn add_plugin<P>(&self, plugin: P)
+    where
+        P: Plugin + 'static,
+    {
+        self.add_engine_plugin(SctkEngineId::PRIMARY, plugin);
+    }
+
+    /// Registers an additional plugin with the |id| engine. Returns `false`
+    /// if there is no such engine.
+    pub fn add_engine_plugin<P>(&self, id: SctkEngineId, plugin: P) -> bool
+    where
+        P: Plugin + 'static,
+    {
+        let Some(instance) = self.state.engines.get(&id) else {
+            return false;
+        };
+
+        instance.plugins.write().add_plugin(&instance.engine, plugin);
+        true
+    }
+
+    /// Handles of the Wayland objects of the primary engine, also given to
+    /// its plugins. See `crate::extension` for what they may be used for.
+    pub fn wayland_handles(&self) -> WaylandHandles {
+        self.state.primary().wayland_handles.clone()
+    }
+
+    /// Initialize |extension|, and dispatch its events on the platform
+    /// thread from now on. See `crate::extension`.
+    pub fn register_protocol_extension(
+        &mut self,
+        mut extension: Box<dyn SctkProtocolExtension>,
+    ) -> Result<(), SctkProtocolExtensionError> {
+        let handles = self.wayland_handles();
+        if let Err(source) = extension.init(&handles) {
+            return Err(SctkProtocolExtensionError::InitFailed {
+                name: extension.name().to_string(),
+                source,
+            });
+        }
+
+        info!("Registered the {} protocol extension", extension.name());
+        self.state.extensions.push(extension);
+        Ok(())
+    }
+
+    /// Run an additional engine (e.g.: a separate bundle for a settings
+    /// panel) in its own window, along with its own plugins. The engine
+    /// shares the Wayland connection and the event loop of the application,
+    /// and is run right away if the application is already running.
+    ///
+    /// The |attributes| only configure the window (e.g.: its size or role)
+    /// and the rendering of the engine, the bundle and the engine arguments
+    /// are taken from |engine_builder|. The application wide attributes
+    /// (e.g.: the signal handling) are ignored. With
+    /// [`WindowRole::Utility`], the window is attached to the window of
+    /// another engine (see [`SctkEngineId::as_u64`]).
+    pub fn add_engine(
+        &mut self,
+        engine_builder: FlutterEngineBuilder,
+        attributes: ApplicationAttributes,
+    ) -> Result<SctkEngineId, SctkApplicationCreateError> {
+        self.state.add_engine(engine_builder, attributes)
+    }
+
+    /// Shut an engine added with [`Self::add_engine`] down, along with its
+    /// windows, plugins and the engines attached to it. Closing its window
+    /// does the same. Returns
+    /// `false` if there is no such engine, or for the primary engine, which
+    /// lives as long as the application.
+    pub fn remove_engine(&mut self, id: SctkEngineId) -> bool {
+        self.state.remove_engine(id)
+    }
+
+    /// Inhibit idle actions (e.g.: screen blanking) while the implicit window
+    /// is visible. See [`SctkFlutterWindow::set_idle_inhibited`].
+    pub fn set_idle_inhibited(&self, inhibited: bool) {
+        for window in self.state.windows() {
+            window.set_idle_inhibited(inhibited);
+        }
+    }
+
+    /// Timestamps of the last frame events of the implicit window of the
+    /// primary engine, see [`SctkFlutterWindow::last_frame_stats`].
+    pub fn last_frame_stats(&self) -> FrameStats {
+        self.state
+            .primary()
+            .implicit_window()
+            .map(SctkFlutterWindow::last_frame_stats)
+            .unwrap_or_default()
+    }
+
+    /// The renderer of the implicit window of the primary engine, see
+    /// [`SctkFlutterWindow::renderer`]. `None` until it is created.
+    pub fn renderer(&self) -> Option<RendererInfo> {
+        self.state
+            .primary()
+            .implicit_window()
+            .map(|window| window.renderer().clone())
+    }
+
+    /// Whether the implicit window of the primary engine renders on a GPU,
+    /// rather than with a software rasterizer (e.g.: llvmpipe).
+    pub fn is_hardware_accelerated(&self) -> bool {
+        self.state
+            .primary()
+            .implicit_window()
+            .is_some_and(SctkFlutterWindow::is_hardware_accelerated)
+    }
+
+    /// Repaint and present the whole windows for every frame, instead of
+    /// only what changed. See [`SctkFlutterWindow::set_full_damage`].
+    pub fn set_full_damage(&self, enabled: bool) {
+        for window in self.state.windows() {
+            window.set_full_damage(enabled);
+        }
+    }
+
+    /// Override the device pixel ratio reported to the engine, independently
+    /// of the scale factor of the outputs, or track the latter again when
+    /// `None`. A lower ratio fits more content on screen while a higher one
+    /// enlarges it (e.g.: for accessibility zoom).
+    ///
+    /// Note: This only changes the window metrics sent to the engine, the
+    /// window keeps rendering at its physical size.
+    pub fn set_pixel_ratio_override(&self, pixel_ratio: Option<f64>) {
+        if pixel_ratio.is_some_and(|ratio| !ratio.is_finite() || ratio <= 0.0) {
+            warn!("Ignoring invalid pixel ratio override: {:?}", pixel_ratio);
+            return;
+        }
+
+        for instance in self.state.engines.values() {
+            for window in instance.windows.values() {
+                let has_changed = window.set_pixel_ratio_override(pixel_ratio);
+
+                // The override is applied to the initial metrics otherwise.
+                if has_changed && instance.is_running() {
+                    window.send_current_window_metrics();
+                }
+            }
+        }
+    }
+
+    pub fn pixel_ratio_override(&self) -> Option<f64> {
+        self.state
+            .windows()
+            .next()
+            .and_then(|window| window.pixel_ratio_override())
+    }
+
+    /// Configure the throttling of the pointer events sent while the engine
+    /// is stalled (see [`FlutterEngine::send_pointer_event`]).
+    pub fn set_pointer_backpressure(&self, config: PointerBackpressure) {
+        for instance in self.state.engines.values() {
+            instance.engine.set_pointer_backpressure(config);
+        }
+    }
+
+    /// Offer the credentials stored in the Secret Service when a username or
+    /// password field gets the focus (see [`SecretServiceAutofill`]).
+    #[cfg(feature = "autofill-freedesktop")]
+    pub fn set_autofill(&self, autofill: SecretServiceAutofill) {
+        let plugins = self.state.primary().plugins.clone();
+        let observer =
+            autofill.into_focus_observer(self.state.async_scheduler.clone(), plugins.clone());
+
+        plugins.write().with_plugin_mut(|text_input: &mut TextInputPlugin| {
+            text_input.set_focus_observer(observer)
+        });
+    }
+
+    /// Show the content of |source_view| in an additional window, without
+    /// adding a view to the engine (e.g.: for presenter or kiosk setups). The
+    /// mirror is a regular window, or a fullscreen one when |target_output|
+    /// is set (i.e.: the id of a display reported to the engine). The latest
+    /// frame of the source view is scaled to fit the mirror window.
+    ///
+    /// Returns the id of the mirror window surface.
+    ///
+    /// Note: Mirroring requires rendering through the compositor (see
+    /// [`RenderPath`](flutter_runner_api::RenderPath)).
+    pub fn create_mirror_window(
+        &mut self,
+        source_view: FlutterViewId,
+        target_output: Option<FlutterEngineDisplayId>,
+        input: SctkMirrorInput,
+    ) -> Result<ObjectId, SctkMirrorWindowError> {
+        self.state.create_mirror_window(source_view, target_output, input)
+    }
+
+    /// Close a mirror window. The source view is not affected. Returns
+    /// `false` if |id| is not a mirror window.
+    pub fn destroy_mirror_window(&mut self, id: &ObjectId) -> bool {
+        self.state.destroy_mirror_window(id)
+    }
+
+    /// Run |task| when the application shuts down, during |phase| (e.g.: for
+    /// releasing the resources of app specific subsystems in the right
+    /// order).
+    pub fn add_shutdown_task<F>(&mut self, phase: ShutdownPhase, name: &'static str, task: F)
+    where
+        F: FnOnce() + 'static,
+    {
+        self.shutdown_coordinator
+            .register(phase, name, move |_state| task());
+    }
+
+    /// Bound the duration of a shutdown phase. Pending tasks are abandoned
+    /// once it times out, and the next phase starts.
+    pub fn set_shutdown_timeout(&mut self, phase: ShutdownPhase, timeout: Duration) {
+        self.shutdown_coordinator.set_timeout(phase, timeout);
+    }
+
+    pub fn run(self) -> Result<(), SctkApplicationRunError> {
+        self.run_with(|_context| ControlFlow::Continue(()))
+    }
+
+    /// Run the application, invoking `hook` on the platform thread once per
+    /// event loop iteration (i.e.: after every batch of dispatched events and
+    /// platform tasks). Returning [`ControlFlow::Break`] quits the
+    /// application.
+    ///
+    /// Iterations are only triggered by events. Use [`Self::pump`] when the
+    /// host needs to drive its own fixed-rate loop.
+    pub fn run_with<F>(mut self, mut hook: F) -> Result<(), SctkApplicationRunError>
+    where
+        F: FnMut(&mut HostContext) -> ControlFlow<()>,
+    {
+        if let Err(err) = self.start() {
+            self.shutdown();
+            return Err(err);
+        }
+
+        let mut clock = HostClock::default();
+        let result = self.event_loop.run(None, &mut self.state, |state| {
+            state.dispatch_protocol_extensions();
+            state.execute_platform_tasks();
+
+            let mut context = clock.tick(&state.primary().engine);
+            if hook(&mut context).is_break() || context.is_quit_requested() {
+                state.exit();
+            }
+        });
+
+        // Also shut down when the event loop failed.
+        self.shutdown();
+        if let Some(err) = self.state.startup_error.take() {
+            return Err(err);
+        }
+        Ok(result?)
+    }
+
+    /// Run a single event loop iteration: dispatch the pending events (waiting
+    /// at most `timeout` for new ones, or indefinitely if `None`) and execute
+    /// the platform tasks which are due.
+    ///
+    /// The engine relies on the platform thread for processing platform
+    /// channel messages and for scheduling frames. Therefore, hosts driving
+    /// their own loop must call this from the thread which created the
+    /// application at least once per frame (e.g.: with a zero timeout from a
+    /// 60Hz simulation loop), otherwise the app stops responding.
+    pub fn pump(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<PumpResult, SctkApplicationRunError> {
+        if self.state.is_exiting {
+            self.shutdown();
+            return Ok(PumpResult::Exit);
+        }
+
+        if let Err(err) = self.start() {
+            self.shutdown();
+            return Err(err);
+        }
+
+        if let Err(err) = self.event_loop.dispatch(timeout, &mut self.state) {
+            self.shutdown();
+            return Err(err.into());
+        }
+        self.state.dispatch_protocol_extensions();
+        self.state.execute_platform_tasks();
+
+        if self.state.is_exiting {
+            self.shutdown();
+            match self.state.startup_error.take() {
+                Some(err) => Err(err),
+                None => Ok(PumpResult::Exit),
+            }
+        } else {
+            Ok(PumpResult::Continue)
+        }
+    }
+
+    /// Shut the engine down once the application has quit, rather than
+    /// leaving it running until the process exits (e.g.: a frame being
+    /// interrupted can corrupt the shader cache). The phases run in order
+    /// (see [`ShutdownPhase`]), and only once.
+    fn shutdown(&mut self) {
+        let Self {
+            event_loop,
+            state,
+            shutdown_coordinator,
+            ..
+        } = self;
+
+        shutdown_coordinator.shutdown(state, |state, timeout| {
+            let timeout = timeout.min(SHUTDOWN_PUMP_INTERVAL);
+            if let Err(err) = event_loop.dispatch(Some(timeout), state) {
+                error!("Failed to dispatch events while shutting down: {}", err);
+            }
+            state.execute_platform_tasks();
+        });
+    }
+
+    fn start(&mut self) -> Result<(), SctkApplicationRunError> {
+        if self.state.is_started {
+            return Ok(());
+        }
+        self.state.is_started = true;
+
+        // The event loop needs to be started *prior* to running the engine (see
+        // `FlutterEngineRun` comment in `embedder.h` for additional context).
+        // Therefore, use an immediate timer source for starting the engine
+        // once the event loop is running.
+        //
+        // https://github.com/flutter/engine/blob/7c2a56a44b414f2790af277783ec27181337a6d3/shell/platform/embedder/embedder.h#L2313-L2322
+        self.state
+            .loop_handle
+            .insert_source(Timer::immediate(), |_event, _metadata, state| {
+                let ids: Vec<SctkEngineId> = state.engines.keys().copied().collect();
+                for id in ids {
+                    state.run_engine(id);
+                }
+
+                TimeoutAction::Drop
+            })?;
+
+        // The visibility of the windows is estimated from what the compositor
+        // tells over time (see `crate::occlusion`).
+        self.state.loop_handle.insert_source(
+            Timer::from_duration(OCCLUSION_POLL_INTERVAL),
+            |_event, _metadata, state| {
+                state.update_visibility();
+                TimeoutAction::ToDuration(OCCLUSION_POLL_INTERVAL)
+            },
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Drop for SctkApplication {
+    fn drop(&mut self) {
+        // The application may be dropped without being run to completion
+        // (e.g.: after an error), but don't make a panic worse.
+        if !std::thread::panicking() {
+            self.shutdown();
+        }
+    }
+}
+
+impl SctkApplicationState {
+    /// Dispatch the events read for the queues of the protocol extensions.
+    /// An extension failing to dispatch is removed, as its queue is likely
+    /// broken.
+    fn dispatch_protocol_extensions(&mut self) {
+        self.extensions.retain_mut(|extension| {
+            if let Err(err) = extension.dispatch_pending() {
+                error!(
+                    "Removing the {} protocol extension, which failed to dispatch: {}",
+                    extension.name(),
+                    err
+                );
+                return false;
+            }
+            true
+        });
+    }
+
+    fn run_engine(&mut self, id: SctkEngineId) {
+        let Some(instance) = self.engines.get(&id) else {
+            return;
+        };
+
+        // Engines added before the application is started may be run twice.
+        if instance.startup_synchronizer.state() != StartupState::WaitingForEngine {
+            return;
+        }
+
+        if let Err(err) = instance.engine.run() {
+            error!("Failed to run engine {:?}: {}", id, err);
+            let reason = StartupFailureReason::RunFailed(err.to_string());
+            if let Some(instance) = self.engines.get_mut(&id) {
+                instance.startup_synchronizer.engine_failed();
+            }
+
+            // The failure screen keeps the window open until it is closed.
+            if self.report_startup_failure(id, reason) {
+                return;
+            }
+
+            if id == SctkEngineId::PRIMARY {
+                self.startup_error = Some(SctkApplicationRunError::EngineRunFailed(err));
+                self.exit();
+            } else {
+                self.remove_engine(id);
+            }
+            return;
+        }
+
+        self.schedule_startup_timeout(id);
+
+        self.schedule_async_startup_tasks(id);
+
+        self.schedule_first_frame_check(id);
+
+        self.schedule_input_gate_timeout(id);
+
+        self.maybe_send_startup_pending_configure(id);
+
+        self.add_entered_pointers(id);
+    }
+
+    /// Fail the startup of engine |id| unless its window is configured within
+    /// `STARTUP_TIMEOUT` (e.g.: the compositor never maps it), rather than
+    /// running without a window.
+    fn schedule_startup_timeout(&mut self, id: SctkEngineId) {
+        let timer = Timer::from_duration(STARTUP_TIMEOUT);
+        let result = self.loop_handle.insert_source(timer, move |_, _, state| {
+            let Some(instance) = state.engines.get(&id) else {
+                return TimeoutAction::Drop;
+            };
+
+            if let Some(startup_state) = instance.startup_synchronizer.timed_out_state() {
+                error!(
+                    "The startup of engine {:?} timed out ({})",
+                    id, startup_state
+                );
+                if id == SctkEngineId::PRIMARY {
+                    state.startup_error = Some(SctkApplicationRunError::StartupTimeout {
+                        state: startup_state,
+                    });
+                    state.exit();
+                } else {
+                    state.remove_engine(id);
+                }
+            }
+            TimeoutAction::Drop
+        });
+
+        if let Err(err) = result {
+            error!("Failed to schedule the startup timeout: {}", err.error);
+        }
+    }
+
+    /// Report a startup failure unless the engine presents a frame within the
+    /// timeout of the failure screen.
+    fn schedule_first_frame_check(&mut self, id: SctkEngineId) {
+        let config = &self.engines[&id].failure_screen_config;
+        if !config.enabled {
+            return;
+        }
+
+        let timeout = config.first_frame_timeout;
+        let timer = Timer::from_duration(timeout);
+        let result = self.loop_handle.insert_source(timer, move |_, _, state| {
+            let has_presented_frame = state
+                .engines
+                .get(&id)
+                .and_then(|instance| instance.implicit_window())
+                .is_none_or(|window| window.has_presented_frame());
+            if !has_presented_frame {
+                state.report_startup_failure(id, StartupFailureReason::NoFirstFrame(timeout));
+            }
+            TimeoutAction::Drop
+        });
+
+        if let Err(err) = result {
+            error!("Failed to schedule the first frame check: {}", err.error);
+        }
+    }
+
+    /// Release the input of engine |id| once the timeout of the gating expired
+    /// (see `crate::input_gate`), even if the app never said it is ready.
+    fn schedule_input_gate_timeout(&mut self, id: SctkEngineId) {
+        let Some(window) = self.engines[&id].implicit_window() else {
+            return;
+        };
+        let Some(timeout) = window.input_gate().start(Instant::now()) else {
+            return;
+        };
+
+        let surface_id = window.wl_surface_id();
+        let timer = Timer::from_duration(timeout);
+        let result = self.loop_handle.insert_source(timer, move |_, _, state| {
+            let is_expired = state
+                .find_window_by_surface_id(&surface_id)
+                .is_some_and(|window| window.input_gate().is_expired(Instant::now()));
+            if is_expired {
+                warn!("The app wasn't ready for input after {timeout:?}, releasing it");
+                state.release_input(&surface_id);
+            }
+            TimeoutAction::Drop
+        });
+
+        if let Err(err) = result {
+            error!("Failed to schedule the input gate timeout: {}", err.error);
+        }
+    }
+
+    /// Report that the Dart app of engine |id| failed to start, and draw the
+    /// failure screen unless the app handles the failure itself. Returns
+    /// whether the failure was reported (i.e.: the failure screen is
+    /// enabled).
+    fn report_startup_failure(&mut self, id: SctkEngineId, reason: StartupFailureReason) -> bool {
+        let Some(instance) = self.engines.get_mut(&id) else {
+            return false;
+        };
+
+        let config = &instance.failure_screen_config;
+        if !config.enabled {
+            return false;
+        }
+
+        let failure = StartupFailure {
+            reason,
+            log_tail: instance.log_tail.lines(),
+        };
+        error!(
+            "The app of engine {:?} failed to start: {}",
+            id,
+            failure.summary()
+        );
+
+        if let Some(on_failure) = &config.on_failure {
+            if on_failure(&failure) {
+                return true;
+            }
+        }
+
+        let screen = SctkFailureScreen::new(instance.app_name.as_deref(), &failure, config);
+        instance.failure_screen = Some(screen);
+        instance.draw_failure_screen();
+        true
+    }
+
+    fn dismiss_failure_screen(&mut self, surface_id: &ObjectId) {
+        let Some(instance) = self.find_engine_by_surface_id_mut(surface_id) else {
+            return;
+        };
+
+        if instance.failure_screen.take().is_some() {
+            info!("The app presented its first frame, dismissed the failure screen");
+        }
+    }
+
+    /// Release the input once the app presented its first frame, unless it
+    /// extended the gating (see `crate::input_gate`).
+    fn release_input_on_first_frame(&self, surface_id: &ObjectId) {
+        let is_released = self
+            .find_window_by_surface_id(surface_id)
+            .is_some_and(|window| window.input_gate().first_frame_presented());
+        if is_released {
+            self.release_input(surface_id);
+        }
+    }
+
+    /// Replay the input of the window |surface_id| buffered so far, in order,
+    /// and stop gating it.
+    fn release_input(&self, surface_id: &ObjectId) {
+        let Some(instance) = self
+            .engines
+            .values()
+            .find(|instance| instance.windows.contains_key(surface_id))
+        else {
+            return;
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let inputs = instance.windows[surface_id].input_gate().open(now);
+        if !inputs.is_empty() {
+            trace!("Replaying {} events received before the app was ready", inputs.len());
+        }
+
+        // The pointer events go through the back-pressure of the engine, as
+        // if they were just received.
+        for input in inputs {
+            match input {
+                SctkGatedInput::Pointer(event) => instance.engine.send_pointer_event(event),
+                SctkGatedInput::Key(event, fallthrough) => {
+                    instance.send_key_event(event, fallthrough)
+                }
+            }
+        }
+    }
+
+    /// Draw the failure screen again after the window was resized.
+    fn redraw_failure_screen(&self, surface_id: &ObjectId) {
+        if let Some(id) = self.find_engine_id_by_surface_id(surface_id) {
+            self.engines[&id].draw_failure_screen();
+        }
+    }
+
+    fn maybe_send_startup_pending_configure(&mut self, id: SctkEngineId) {
+        let Some(instance) = self.engines.get_mut(&id) else {
+            return;
+        };
+        let pending_configure = instance.startup_synchronizer.engine_started();
+
+        let instance = &self.engines[&id];
+        self.notify_engine_display_update(instance);
+
+        // The accessibility settings are monitored once for all the engines,
+        // so engines started later on are brought up to date.
+        if id != SctkEngineId::PRIMARY {
+            let is_transparency_reduced = self.accessibility.is_transparency_reduced();
+            instance
+                .engine
+                .update_accessibility_features(self.accessibility.features());
+            for window in instance.windows.values() {
+                window.set_transparency_reduced(is_transparency_reduced);
+            }
+        }
+
+        // Send the messages coalesced during startup in a single batch
+        // (locales, settings, displays and lifecycle), followed by the window
+        // metrics.
+        let deferred_sends = instance.plugins.read().deferred_sends().clone();
+        let count = deferred_sends.flush();
+        trace!("Sent {} deferred startup messages", count);
+
+        let conn = self.conn.clone();
+        let Some(instance) = self.engines.get_mut(&id) else {
+            return;
+        };
+
+        let Some(new_size) = pending_configure else {
+            return;
+        };
+
+        if let Some(window) = instance.implicit_window_mut() {
+            window.configure(&conn, new_size);
+        };
+    }
+
+    /// Deliver the pointers which entered the windows of the |id| engine
+    /// before it was running (e.g.: a window created under the pointer).
+    fn add_entered_pointers(&self, id: SctkEngineId) {
+        let Some(instance) = self.engines.get(&id) else {
+            return;
+        };
+
+        for window in instance.windows.values() {
+            window.add_entered_pointers();
+        }
+    }
+
+    fn handle_request(&mut self, request: SctkApplicationRequest) {
+        match request {
+            SctkApplicationRequest::Suspend { drop_caches } => self.suspend(drop_caches),
+            SctkApplicationRequest::Resume => self.resume(),
+            SctkApplicationRequest::ShowTextToolbar {
+                rect,
+                placement,
+                actions,
+            } => self.show_text_toolbar(rect, placement, actions),
+            SctkApplicationRequest::HideTextToolbar => self.text_toolbar = None,
+            SctkApplicationRequest::SetScreenSaverInhibited(inhibited) => {
+                self.set_screensaver_inhibited(inhibited)
+            }
+            #[cfg(feature = "emulated-input")]
+            SctkApplicationRequest::EmulatedInput(request) => self.handle_emulated_input(request),
+            #[cfg(feature = "status-notifier")]
+            SctkApplicationRequest::Tray(request) => self.handle_tray(request),
+            SctkApplicationRequest::SetTextInputEnabled(is_enabled) => {
+                if let Some(text_input) = &mut self.text_input {
+                    text_input.set_enabled(is_enabled);
+                }
+            }
+            SctkApplicationRequest::SetTextInputFocus(focus_info) => {
+                if let Some(text_input) = &mut self.text_input {
+                    text_input.set_focus_info(focus_info);
+                }
+            }
+            SctkApplicationRequest::RetryWindowMetrics { surface, delay } => {
+                self.retry_window_metrics(surface.id(), delay)
+            }
+            SctkApplicationRequest::SetContentZoom { surface, zoom } => {
+                self.set_content_zoom(&surface.id(), zoom)
+            }
+            SctkApplicationRequest::SetUrgent { surface, urgent } => {
+                self.set_urgent(surface, urgent)
+            }
+            SctkApplicationRequest::Restack {
+                surface,
+                sibling,
+                above,
+            } => self.restack(&surface.id(), sibling, above),
+            SctkApplicationRequest::SetAttachedDrag { surface, dragging } => {
+                self.set_attached_drag(&surface, dragging)
+            }
+            SctkApplicationRequest::SetMinFrameInterval(interval) => {
+                for instance in self.engines.values() {
+                    instance.vsync_handler.lock().set_min_frame_interval(interval);
+                }
+            }
+            SctkApplicationRequest::SetWindowVisible(visible) => {
+                match (self.primary().implicit_window(), visible) {
+                    (Some(window), true) => window.show(),
+                    (Some(window), false) => window.hide(),
+                    (None, _) => warn!("Ignoring visibility request without a window"),
+                }
+            }
+            SctkApplicationRequest::UpdateAccessibility(change) => {
+                self.update_accessibility(change)
+            }
+            SctkApplicationRequest::UpdateAccentColor(accent_color) => {
+                self.update_accent_color(accent_color)
+            }
+            SctkApplicationRequest::UpdateScroll(change) => self.update_scroll(change),
+            SctkApplicationRequest::EditText {
+                keysym,
+                utf8,
+                select,
+            } => self.with_plugin_mut(|text_input: &mut TextInputPlugin| {
+                edit_text(text_input, keysym, utf8.as_deref(), select);
+            }),
+            SctkApplicationRequest::InvalidateAnchors {
+                surface,
+                invalidated,
+            } => {
+                let Some(id) = self.find_engine_id_by_surface_id(&surface.id()) else {
+                    return;
+                };
+
+                self.engines[&id]
+                    .plugins
+                    .read()
+                    .with_plugin(|window: &WindowPlugin| {
+                        window.notify_anchors_invalidated(invalidated)
+                    });
+            }
+            SctkApplicationRequest::Exit => self.exit(),
+            SctkApplicationRequest::FirstFramePresented { surface } => {
+                self.dismiss_failure_screen(&surface.id());
+                self.release_input_on_first_frame(&surface.id());
+            }
+            SctkApplicationRequest::ReleaseInput { surface } => self.release_input(&surface.id()),
+            SctkApplicationRequest::ExitWithCode(exit_code) => {
+                self.primary().engine.set_exit_code(exit_code);
+                self.exit();
+            }
+        }
+    }
+
+    fn retry_window_metrics(&mut self, surface_id: ObjectId, delay: Duration) {
+        let timer = Timer::from_duration(delay);
+        let result = self.loop_handle.insert_source(timer, move |_event, _metadata, state| {
+            // The window may have been closed in the meantime.
+            if let Some(window) = state.find_window_by_surface_id(&surface_id) {
+                window.retry_window_metrics();
+            }
+            TimeoutAction::Drop
+        });
+
+        if let Err(err) = result {
+            error!("Failed to schedule window metrics resend: {}", err.error);
+        }
+    }
+
+    fn exit(&mut self) {
+        self.is_exiting = true;
+        self.loop_signal.stop();
+    }
+
+    fn update_scroll(&mut self, change: SctkScrollChange) {
+        if !self.scroll.apply(change) {
+            return;
+        }
+        trace!("Scroll settings changed: {:?}", self.scroll);
+
+        *self.scroll_settings.lock() = self.scroll.effective();
+    }
+
+    /// Forward the accent color to the engines. Apps read it on startup, so
+    /// the engines which are not running yet aren't notified.
+    fn update_accent_color(&mut self, accent_color: Option<AccentColor>) {
+        self.accent_color = accent_color;
+        for instance in self.engines.values() {
+            let is_engine_running = instance.is_running();
+            instance
+                .plugins
+                .read()
+                .with_plugin(|plugin: &AppearancePlugin| {
+                    if plugin.set_accent_color(accent_color) && is_engine_running {
+                        plugin.notify_accent_color_changed();
+                    }
+                });
+        }
+    }
+
+    /// Forward the accessibility settings to the engine, and make the windows
+    /// opaque while transparency is reduced. Only called once the engine is
+    /// running, by the async startup tasks.
+    fn update_accessibility(&mut self, change: SctkAccessibilityChange) {
+        let previous_features = self.accessibility.features();
+        let was_transparency_reduced = self.accessibility.is_transparency_reduced();
+        if !self.accessibility.apply(change) {
+            return;
+        }
+        trace!("Accessibility settings changed: {:?}", self.accessibility);
+
+        // Engines which are not running yet are brought up to date once they
+        // are.
+        let features = self.accessibility.features();
+        let running_engines = self.engines.values().filter(|instance| instance.is_running());
+        if features != previous_features {
+            for instance in running_engines.clone() {
+                instance.engine.update_accessibility_features(features);
+            }
+        }
+
+        let is_transparency_reduced = self.accessibility.is_transparency_reduced();
+        if is_transparency_reduced == was_transparency_reduced {
+            return;
+        }
+
+        for instance in running_engines {
+            let mut has_background_changed = false;
+            for window in instance.windows.values() {
+                has_background_changed |= window.set_transparency_reduced(is_transparency_reduced);
+            }
+            if has_background_changed {
+                instance.engine.schedule_frame();
+            }
+
+            instance.plugins.read().with_plugin(|window: &WindowPlugin| {
+                window.notify_transparency_reduced_changed(is_transparency_reduced)
+            });
+        }
+    }
+
+    /// Apply the |new_scale_factor| of the window of |surface|, and hint the
+    /// scales of the outputs it touches to Dart.
+    fn window_scale_changed(
+        &mut self,
+        conn: &Connection,
+        surface: &WlSurface,
+        new_scale_factor: i32,
+    ) {
+        let Some(SctkEngineInstance {
+            engine,
+            plugins,
+            windows,
+            startup_synchronizer,
+            ..
+        }) = self.find_engine_by_surface_id_mut(&surface.id())
+        else {
+            warn!(
+                "[{}] ignoring scale change of unknown flutter window",
+                surface.id()
+            );
+            return;
+        };
+        let is_engine_running = startup_synchronizer.is_engine_running();
+        let Some(window) = windows.get_mut(&surface.id()) else {
+            return;
+        };
+
+        // The scale of an output the window touches may have changed too.
+        let effective_scale_changed =
+            window.scale_factor() != Scale::from_buffer_scale(new_scale_factor);
+        output::outputs_changed(
+            window.update_output_scales(),
+            effective_scale_changed,
+            |scales| {
+                if is_engine_running {
+                    plugins
+                        .read()
+                        .with_plugin(|assets: &AssetsPlugin| assets.notify_scales_changed(scales));
+                }
+            },
+            || {
+                if effective_scale_changed {
+                    window.scale_factor_changed(conn, surface, new_scale_factor);
+                } else {
+                    window.surface_outputs_changed(conn, surface);
+                }
+            },
+            || engine.schedule_frame(),
+        );
+        self.update_content_scale();
+    }
+
+    /// Re-evaluate the scale of the windows on |output| as soon as its scale
+    /// changes, rather than when the surfaces next enter an output.
+    fn output_scale_changed(&mut self, conn: &Connection, output: &WlOutput) {
+        let windows: Vec<_> = self
+            .windows()
+            .filter(|window| window.outputs().contains(output))
+            .map(|window| {
+                let buffer_scale = window
+                    .preferred_output_buffer_scale()
+                    .unwrap_or_else(|| window.scale_factor().buffer_scale());
+                (window.wl_surface(), buffer_scale)
+            })
+            .collect();
+
+        for (surface, buffer_scale) in windows {
+            trace!(
+                "[{}] scale of {} changed, using scale {}",
+                surface.id(),
+                output.id(),
+                buffer_scale
+            );
+            self.window_scale_changed(conn, &surface, buffer_scale);
+        }
+    }
+
+    fn execute_platform_tasks(&mut self) {
+        // When tasks are left over, the timer is armed in the past: the next
+        // batch runs right after the pending events are dispatched.
+        let batch_size = self.platform_task_batch_size;
+        let next_task_time = self
+            .engines
+            .values()
+            .filter_map(|instance| instance.engine.execute_platform_tasks_limited(batch_size))
+            .min();
+        self.arm_platform_task_timer(next_task_time);
+    }
+
+    /// Keep a single timer armed for the earliest platform task (i.e.: engine
+    /// task or scheduled closure) of all the engines, rather than one per
+    /// loop iteration.
+    fn arm_platform_task_timer(&mut self, deadline: Option<Instant>) {
+        if self.platform_task_timer.map(|(_, armed)| armed) == deadline {
+            return;
+        }
+
+        if let Some((token, _)) = self.platform_task_timer.take() {
+            self.loop_handle.remove(token);
+        }
+
+        let Some(deadline) = deadline else {
+            return;
+        };
+
+        // Waking the loop up is enough, the tasks are executed after every
+        // dispatch.
+        let result = self
+            .loop_handle
+            .insert_source(Timer::from_deadline(deadline), |_, _, state| {
+                state.platform_task_timer = None;
+                TimeoutAction::Drop
+            });
+
+        match result {
+            Ok(token) => self.platform_task_timer = Some((token, deadline)),
+            Err(err) => {
+                // The tasks are run (and the timer armed again) with the next
+                // dispatch, which happens right away rather than with the
+                // next event.
+                error!("Failed to arm the platform task timer: {}", err.error);
+                self.loop_signal.wakeup();
+            }
+        }
+    }
+
+    fn set_screensaver_inhibited(&mut self, inhibited: bool) {
+        // Prefer the native Wayland protocol and fall back to the portal.
+        if let Some(window) = self.focused().implicit_window() {
+            if window.supports_idle_inhibit() {
+                window.set_idle_inhibited(inhibited);
+                self.screensaver_inhibitor.store_inhibited(window.is_idle_inhibited());
+                return;
+            }
+        }
+
+        let Some(task) = self.screensaver_inhibitor.set_inhibited(inhibited) else {
+            return;
+        };
+
+        if let Err(err) = self.async_scheduler.schedule(task) {
+            error!("Failed to schedule screensaver inhibition task: {}", err);
+        }
+    }
+
+    fn show_text_toolbar(
+        &mut self,
+        rect: TextToolbarRect,
+        mut placement: PopupPlacement,
+        actions: Vec<TextToolbarAction>,
+    ) {
+        self.text_toolbar = None;
+
+        // Only offer pasting when the clipboard has some text.
+        let focused = self.focused();
+        let has_strings = focused
+            .platform_handler
+            .lock()
+            .get_clipboard_data("text/plain")
+            .is_ok_and(|text| !text.is_empty());
+
+        let actions: Vec<TextToolbarAction> = actions
+            .into_iter()
+            .filter(|&action| action != TextToolbarAction::Paste || has_strings)
+            .collect();
+
+        if actions.is_empty() {
+            return;
+        }
+
+        let Some(window) = focused.implicit_window() else {
+            return;
+        };
+
+        // TODO: Parent the toolbar popup to layer surfaces as well.
+        let Some(parent) = window.window() else {
+            warn!("The text toolbar is not supported on layer surfaces");
+            return;
+        };
+
+        // The rect is relative to the (possibly zoomed) content.
+        let content_scale = window.content_scale();
+        let rect = TextToolbarRect {
+            x: rect.x * content_scale,
+            y: rect.y * content_scale,
+            width: rect.width * content_scale,
+            height: rect.height * content_scale,
+        };
+        placement.offset.x *= content_scale;
+        placement.offset.y *= content_scale;
+
+        let grab = self
+            .last_pointer_press
+            .as_ref()
+            .map(|(seat, serial)| (seat, *serial));
+
+        match SctkTextToolbar::new(
+            parent,
+            rect,
+            &placement,
+            actions,
+            window.scale_factor().buffer_scale() as u32,
+            grab,
+            &self.qh,
+            &self.compositor_state,
+            &self.xdg_shell_state,
+            &self.shm_state,
+        ) {
+            Ok(text_toolbar) => self.text_toolbar = Some(text_toolbar),
+            Err(err) => error!("Failed to create text toolbar: {}", err),
+        }
+    }
+
+    fn dismiss_text_toolbar(&mut self) {
+        if self.text_toolbar.take().is_some() {
+            self.with_plugin(|text_toolbar: &TextToolbarPlugin| text_toolbar.notify_dismissed());
+        }
+    }
+
+    fn perform_text_toolbar_action(&mut self, action: TextToolbarAction) {
+        self.text_toolbar = None;
+
+        match action {
+            TextToolbarAction::Cut | TextToolbarAction::Copy => {
+                let mut selected_text = String::new();
+                self.with_plugin_mut(|text_input: &mut TextInputPlugin| {
+                    text_input.with_state(|state| {
+                        selected_text = state.get_selected_text().to_owned();
+                        if action == TextToolbarAction::Cut {
+                            state.delete_selected();
+                        }
+                    });
+
+                    if action == TextToolbarAction::Cut {
+                        text_input.notify_changes();
+                    }
+                });
+
+                if !selected_text.is_empty() {
+                    let platform_handler = &self.focused().platform_handler;
+                    let result = platform_handler.lock().set_clipboard_data(selected_text);
+                    if let Err(err) = result {
+                        warn!("Unable to write clipboard data: {}", err);
+                    }
+                }
+            }
+            TextToolbarAction::Paste => {
+                let platform_handler = &self.focused().platform_handler;
+                let text = match platform_handler.lock().get_clipboard_data("text/plain") {
+                    Ok(text) => text,
+                    Err(err) => {
+                        warn!("Unable to read clipboard data: {}", err);
+                        return;
+                    }
+                };
+
+                self.with_plugin_mut(|text_input: &mut TextInputPlugin| {
+                    text_input.with_state(|state| state.add_characters(&text));
+                    text_input.notify_changes();
+                });
+            }
+            TextToolbarAction::SelectAll => {
+                self.with_plugin_mut(|text_input: &mut TextInputPlugin| {
+                    text_input.with_state(|state| state.select_all());
+                    text_input.notify_changes();
+                });
+            }
+        }
+
+        self.with_plugin(|text_toolbar: &TextToolbarPlugin| {
+            text_toolbar.notify_action_performed(action)
+        });
+    }
+
+    /// Prefer the cursor shapes of the compositor, and only fall back to a
+    /// themed pointer (which loads the cursor theme) without them.
+    fn create_seat_cursor(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        seat: &WlSeat,
+    ) -> Option<SctkSeatCursor> {
+        if let Some(cursor_shape_manager) = &self.cursor_shape_manager {
+            let pointer = self.seat_state.get_pointer(qh, seat).ok()?;
+            let device = cursor_shape_manager.get_shape_device(&pointer, qh);
+            return Some(SctkSeatCursor::Shape { pointer, device });
+        }
+
+        let surface = self.compositor_state.create_surface(qh);
+        self.seat_state
+            .get_pointer_with_theme::<_, SurfaceData>(
+                qh,
+                seat,
+                self.shm_state.wl_shm(),
+                surface,
+                ThemeSpec::default(),
+            )
+            .map(SctkSeatCursor::Themed)
+            .ok()
+    }
+
+    /// Handle a pointer event targeting the text toolbar popup. Returns
+    /// `false` if the event is not meant for the toolbar.
+    fn text_toolbar_pointer_event(&mut self, pointer: &WlPointer, event: &PointerEvent) -> bool {
+        let Some(text_toolbar) = &mut self.text_toolbar else {
+            return false;
+        };
+
+        if text_toolbar.wl_surface().id() != event.surface.id() {
+            // Without a popup grab, clicking outside of the toolbar has to be
+            // detected manually.
+            if matches!(event.kind, PointerEventKind::Press { .. }) {
+                self.dismiss_text_toolbar();
+            }
+            return false;
+        }
+
+        match event.kind {
+            PointerEventKind::Enter { .. } => {
+                text_toolbar.pointer_moved(Some(event.position));
+                // The cursor of the content (e.g.: the text cursor) isn't
+                // meant for the buttons.
+                let seat_id = pointer.data::<PointerData>().map(|data| data.seat().id());
+                let cursor_override = seat_id.map(|seat_id| {
+                    SctkMouseCursorHandler::push_cursor_override(
+                        &self.mouse_cursor_handler,
+                        seat_id,
+                        CursorIcon::Default,
+                    )
+                });
+                text_toolbar.set_cursor_override(cursor_override);
+            }
+            PointerEventKind::Motion { .. } => text_toolbar.pointer_moved(Some(event.position)),
+            PointerEventKind::Leave { .. } => {
+                text_toolbar.pointer_moved(None);
+                text_toolbar.set_cursor_override(None);
+            }
+            PointerEventKind::Press {
+                button: BTN_LEFT, ..
+            } => {
+                let (x, y) = event.position;
+                if let Some(action) = text_toolbar.action_at(x, y) {
+                    self.perform_text_toolbar_action(action);
+                }
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    fn suspend(&mut self, drop_caches: bool) {
+        if self.is_suspended {
+            return;
+        }
+        self.is_suspended = true;
+
+        for instance in self.engines.values() {
+            instance.vsync_handler.lock().set_paused(true);
+
+            if !instance.is_running() {
+                continue;
+            }
+
+            instance
+                .plugins
+                .read()
+                .with_plugin(|lifecycle: &LifecyclePlugin| lifecycle.send_app_is_paused());
+
+            if drop_caches {
+                instance.engine.notify_low_memory_warning();
+            }
+        }
+    }
+
+    fn resume(&mut self) {
+        if !self.is_suspended {
+            return;
+        }
+        self.is_suspended = false;
+
+        for instance in self.engines.values() {
+            instance.vsync_handler.lock().set_paused(false);
+
+            if !instance.is_running() {
+                continue;
+            }
+
+            let is_active = self.is_engine_active(instance);
+            instance
+                .plugins
+                .read()
+                .with_plugin(|lifecycle: &LifecyclePlugin| match is_active {
+                    true => lifecycle.send_app_is_resumed(),
+                    false => lifecycle.send_app_is_inactive(),
+                });
+
+            for window in instance.windows.values() {
+                window.send_current_window_metrics();
+            }
+
+            instance.engine.schedule_frame();
+        }
+    }
+
+    /// Whether one of the windows of the engine is active.
+    fn is_engine_active(&self, instance: &SctkEngineInstance) -> bool {
+        instance
+            .windows
+            .keys()
+            .any(|surface_id| self.active_state.get(surface_id).copied().unwrap_or_default())
+    }
+
+    fn maybe_update_lifecycle_state(&mut self, surface_id: ObjectId, is_active: bool) {
+        let Some(id) = self.find_engine_id_by_surface_id(&surface_id) else {
+            return;
+        };
+
+        let instance = &self.engines[&id];
+        let was_active = self.is_engine_active(instance);
+
+        self.active_state.insert(surface_id, is_active);
+
+        // Note: Lifecycle messages are deferred until the engine is running.
+        if was_active != is_active && !self.is_suspended {
+            self.engines[&id]
+                .plugins
+                .read()
+                .with_plugin(|lifecycle: &LifecyclePlugin| match is_active {
+                    true => lifecycle.send_app_is_resumed(),
+                    false => lifecycle.send_app_is_inactive(),
+                })
+        }
+    }
+
+    fn schedule_async_startup_tasks(&self, id: SctkEngineId) {
+        let Some(instance) = self.engines.get(&id) else {
+            return;
+        };
+
+        instance
+            .plugins
+            .read()
+            .with_plugin(|settings: &SettingsPlugin| {
+                if let Err(err) = self.async_scheduler.schedule(
+                    SctkSettingsHandler::read_and_monitor_color_scheme_changes(settings.clone()),
+                ) {
+                    error!("Failed to schedule engine async jobs: {}", err);
+                };
+            });
+
+        instance
+            .plugins
+            .read()
+            .with_plugin(|plugin: &AppearancePlugin| {
+                plugin.set_accent_color(self.accent_color);
+            });
+
+        // The accessibility settings apply to all the engines.
+        if id != SctkEngineId::PRIMARY {
+            return;
+        }
+
+        if let Err(err) = self.async_scheduler.schedule(
+            accessibility::read_and_monitor_accessibility_changes(self.request_sender.clone()),
+        ) {
+            error!("Failed to schedule engine async jobs: {}", err);
+        }
+
+        if let Err(err) = self.async_scheduler.schedule(
+            appearance::read_and_monitor_accent_color_changes(self.request_sender.clone()),
+        ) {
+            error!("Failed to schedule engine async jobs: {}", err);
+        }
+
+        if let Err(err) = self
+            .async_scheduler
+            .schedule(scroll::read_and_monitor_scroll_changes(self.request_sender.clone()))
+        {
+            error!("Failed to schedule engine async jobs: {}", err);
+        }
+
+        #[cfg(feature = "emulated-input")]
+        if let Err(err) = self.async_scheduler.schedule(self.emulated_input.probe()) {
+            error!("Failed to schedule emulated input probe: {}", err);
+        }
+
+        #[cfg(feature = "status-notifier")]
+        if let Err(err) = self
+            .async_scheduler
+            .schedule(self.tray.run(self.request_sender.clone()))
+        {
+            error!("Failed to schedule tray task: {}", err);
+        }
+    }
+
+    /// Find the maximum refresh rate from the surface current outputs.
+    fn get_surface_refresh_rate_in_mhz(&self, surface: &WlSurface) -> Option<i32> {
+        let data = surface.data::<SurfaceData>()?;
+
+        let refresh_rate = data
+            .outputs()
+            .filter_map(|output| {
+                let info = self.output_state.info(&output)?;
+                let current_mode = info.modes.iter().find(|mode| mode.current)?;
+                Some(current_mode.refresh_rate)
+            })
+            .max()?;
+
+        Some(refresh_rate)
+    }
+
+    /// Logical size of the output new windows are most likely to be shown on.
+    /// Windows are not aware of their output until they are mapped, so the
+    /// first advertised output is used.
+    fn get_default_output_logical_size(&self) -> Option<LogicalSize<u32>> {
+        let output = self.output_state.outputs().next()?;
+        let info = self.output_state.info(&output)?;
+
+        if let Some((width, height)) = info.logical_size {
+            return Some(LogicalSize::new(
+                u32::try_from(width).ok()?,
+                u32::try_from(height).ok()?,
+            ));
+        }
+
+        let mode = info.modes.iter().find(|mode| mode.current)?;
+        let physical_size = PhysicalSize::new(
+            u32::try_from(mode.dimensions.0).ok()?,
+            u32::try_from(mode.dimensions.1).ok()?,
+        );
+
+        Some(Scale::from_buffer_scale(info.scale_factor).to_logical_size(physical_size))
+    }
+
+    fn get_surface_frame_interval_in_nanos(&self, surface: &WlSurface) -> Option<u64> {
+        let refresh_rate = self.get_surface_refresh_rate_in_mhz(surface)? as u64;
+
+        // Refresh rate could be zero if an output has no correct refresh rate,
+        // such as a virtual output.
+        if refresh_rate == 0 {
+            return None;
+        }
+
+        Some(1_000_000_000_000 / refresh_rate)
+    }
+
+    /// Declare the content of the windows as sRGB, once the compositor has
+    /// created the image description (see `crate::color`).
+    fn set_srgb_surfaces(&mut self) {
+        let Some(color_management) = &mut self.color_management else {
+            return;
+        };
+
+        for instance in self.engines.values() {
+            for window in instance.windows.values() {
+                color_management.set_srgb(&window.wl_surface(), &self.qh);
+            }
+        }
+    }
+
+    /// Store the color info of the windows, e.g.: after they have moved to
+    /// another output. The apps are notified of the changes.
+    fn update_color_info(&self) {
+        for instance in self.engines.values() {
+            for window in instance.windows.values() {
+                let color_info = self
+                    .color_management
+                    .as_ref()
+                    .map(|color_management| color_management.color_info(&window.outputs()))
+                    .unwrap_or_default();
+                if window.store_color_info(color_info) && instance.is_running() {
+                    instance
+                        .plugins
+                        .read()
+                        .with_plugin(|window_plugin: &WindowPlugin| {
+                            window_plugin.notify_color_info_changed(color_info)
+                        });
+                }
+            }
+        }
+    }
+
+    fn notify_display_update(&self) {
+        for instance in self.engines.values() {
+            self.notify_engine_display_update(instance);
+        }
+    }
+
+    fn notify_engine_display_update(&self, instance: &SctkEngineInstance) {
+        // Ignore display update events if the engine is not running. This
+        // method will be called again once the engine is running to ensure the
+        // display state is up-to-date on the engine side.
+        if !instance.is_running() {
+            return;
+        }
+
+        let output_state = &self.output_state;
+        let displays: Vec<FlutterEngineDisplay> = output_state
+            .outputs()
+            .map(|output| {
+                SctkOutput::new(output.id().protocol_id().into(), output_state.info(&output)).into()
+            })
+            .collect();
+
+        trace!("notifying engine of display update: {:?}", displays);
+
+        let engine = instance.engine.downgrade();
+        let deferred_sends = instance.plugins.read().deferred_sends().clone();
+        deferred_sends.send(DeferredMessage::Displays, move || {
+            if let Some(engine) = engine.upgrade() {
+                engine.notify_display_update(
+                    flutter_engine::ffi::FlutterEngineDisplaysUpdateType::Startup,
+                    displays,
+                );
+            }
+        });
+    }
+
+    fn send_key_event(&self, event: SctkKeyEvent) {
+        self.send_key_event_with_fallthrough(event, None);
+    }
+
+    /// Send |event| to the focused engine, telling |fallthrough| whether it
+    /// handled it. The event is buffered until the app is ready (see
+    /// `crate::input_gate`).
+    fn send_key_event_with_fallthrough(
+        &self,
+        event: SctkKeyEvent,
+        fallthrough: Option<KeyEventFallthrough>,
+    ) {
+        let instance = self.focused();
+        let input = SctkGatedInput::Key(event, fallthrough);
+        let input = match instance.implicit_window() {
+            Some(window) => window.gate_input(input),
+            None => Some(input),
+        };
+
+        if let Some(SctkGatedInput::Key(event, fallthrough)) = input {
+            instance.send_key_event(event, fallthrough);
+        }
+    }
+
+    /// Keys which the framework doesn't handle (e.g.: as a shortcut) fall
+    /// through to the text being edited, once both paths replied.
+    fn press_key_or_repeat(&mut self, event: SctkKeyEvent) {
+        let request = SctkApplicationRequest::EditText {
+            keysym: event.event.keysym,
+            utf8: event.event.utf8.clone(),
+            select: self.modifiers.shift,
+        };
+        let sender = self.request_sender.clone();
+        let fallthrough = KeyEventFallthrough::new(move || {
+            let _ = sender.send(request);
+        });
+
+        self.send_key_event_with_fallthrough(event, Some(fallthrough));
+    }
+}
+
+delegate_activation!(SctkApplicationState);
+delegate_compositor!(SctkApplicationState);
+delegate_output!(SctkApplicationState);
+delegate_shm!(SctkApplicationState);
+
+delegate_xdg_shell!(SctkApplicationState);
+delegate_xdg_window!(SctkApplicationState);
+delegate_xdg_popup!(SctkApplicationState);
+delegate_layer!(SctkApplicationState);
+
+delegate_seat!(SctkApplicationState);
+delegate_pointer!(SctkApplicationState);
+delegate_touch!(SctkApplicationState);
+delegate_keyboard!(SctkApplicationState);
+
+delegate_registry!(SctkApplicationState);
+
+delegate_noop!(SctkApplicationState: ZwpIdleInhibitManagerV1);
+delegate_noop!(SctkApplicationState: ZwpIdleInhibitorV1);
+delegate_noop!(SctkApplicationState: ZwpPointerGesturesV1);
+delegate_noop!(SctkApplicationState: ZwpTextInputManagerV3);
+delegate_noop!(SctkApplicationState: WlSubcompositor);
+delegate_noop!(SctkApplicationState: WlSubsurface);
+delegate_noop!(SctkApplicationState: WpViewporter);
+delegate_noop!(SctkApplicationState: WpViewport);
+delegate_noop!(SctkApplicationState: ignore ZwpLinuxDmabufV1);
+delegate_noop!(SctkApplicationState: WpColorManagementSurfaceV1);
+delegate_noop!(SctkApplicationState: WpImageDescriptionCreatorParamsV1);
+delegate_noop!(SctkApplicationState: WpSinglePixelBufferManagerV1);
+delegate_noop!(SctkApplicationState: WlRegion);
+// Solid-color buffers stay attached until replaced (see `crate::solid`).
+delegate_noop!(SctkApplicationState: ignore WlBuffer);
+// Overlay surfaces are never focused nor scaled: their buffers are scaled by
+// the viewport to the area of the platform view.
+delegate_noop!(SctkApplicationState: ignore WlSurface);
+
+impl Dispatch<ZwpLinuxBufferParamsV1, ()> for SctkApplicationState {
+    fn event(
+        _state: &mut Self,
+        params: &ZwpLinuxBufferParamsV1,
+        event: zwp_linux_buffer_params_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // Buffers are created with `create_immed`, so only failures are
+        // reported.
+        if let zwp_linux_buffer_params_v1::Event::Failed = event {
+            warn!("[{}] failed to create dmabuf buffer", params.id());
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, SctkOverlayBufferData> for SctkApplicationState {
+    fn event(
+        _state: &mut Self,
+        buffer: &WlBuffer,
+        event: wl_buffer::Event,
+        data: &SctkOverlayBufferData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            data.release(buffer);
+        }
+    }
+}
+
+impl Dispatch<ZwpTextInputV3, SctkTextInputData> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        text_input: &ZwpTextInputV3,
+        event: zwp_text_input_v3::Event,
+        data: &SctkTextInputData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_text_input_v3::Event::Enter { surface } => {
+                *data.surface.lock().unwrap() = Some(surface);
+                if let Some(sctk_text_input) = &state.text_input {
+                    sctk_text_input.enter(text_input);
+                }
+            }
+            zwp_text_input_v3::Event::Leave { .. } => *data.surface.lock().unwrap() = None,
+            zwp_text_input_v3::Event::PreeditString { text, .. } => data.preedit_string(text),
+            zwp_text_input_v3::Event::CommitString { text } => data.commit_string(text),
+            zwp_text_input_v3::Event::Done { .. } => {
+                let update = data.done();
+                let apply = |text_input: &mut TextInputPlugin| update.apply(text_input);
+                match &*data.surface.lock().unwrap() {
+                    Some(surface) => state.with_surface_plugin_mut(&surface.id(), apply),
+                    None => state.with_plugin_mut(apply),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WpColorManagerV1, ()> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        _manager: &WpColorManagerV1,
+        event: wp_color_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let Some(color_management) = &mut state.color_management {
+            color_management.manager_event(event, qh);
+        }
+    }
+}
+
+impl Dispatch<WpColorManagementOutputV1, ObjectId> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        color_output: &WpColorManagementOutputV1,
+        event: wp_color_management_output_v1::Event,
+        output_id: &ObjectId,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let Some(color_management) = &mut state.color_management {
+            color_management.output_event(color_output, event, output_id, qh);
+        }
+    }
+}
+
+impl Dispatch<WpImageDescriptionV1, SctkImageDescriptionData> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        description: &WpImageDescriptionV1,
+        event: wp_image_description_v1::Event,
+        data: &SctkImageDescriptionData,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let Some(color_management) = &mut state.color_management else {
+            return;
+        };
+
+        if color_management.image_description_event(description, event, data, qh) {
+            state.set_srgb_surfaces();
+        }
+    }
+}
+
+impl Dispatch<WpImageDescriptionInfoV1, SctkImageDescriptionInfoData> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        _info: &WpImageDescriptionInfoV1,
+        event: wp_image_description_info_v1::Event,
+        data: &SctkImageDescriptionInfoData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let Some(color_management) = &mut state.color_management else {
+            return;
+        };
+
+        if color_management.image_description_info_event(event, data) {
+            state.update_color_info();
+        }
+    }
+}
+
+impl Dispatch<ZwpPointerGesturePinchV1, SctkPinchGestureData> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        _pinch_gesture: &ZwpPointerGesturePinchV1,
+        event: zwp_pointer_gesture_pinch_v1::Event,
+        data: &SctkPinchGestureData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_pointer_gesture_pinch_v1::Event::Begin { surface, .. } => data.begin(surface),
+            zwp_pointer_gesture_pinch_v1::Event::Update { scale, .. } => {
+                let Some((surface, scale)) = data.update(scale) else {
+                    return;
+                };
+
+                if let Some(window) = state.find_window_by_surface_id_mut(surface.id()) {
+                    window.pinch_scale_event(&data.pointer, scale);
+                }
+            }
+            zwp_pointer_gesture_pinch_v1::Event::End { .. } => data.end(),
+            _ => {}
+        }
+    }
+}
+
+impl ProvidesRegistryState for SctkApplicationState {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    registry_handlers![OutputState, SeatState];
+}
+
+impl CompositorHandler for SctkApplicationState {
+    fn scale_factor_changed(
+        &mut self,
+        conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &WlSurface,
+        new_scale_factor: i32,
+    ) {
+        trace!(
+            "[{}] scale factor changed: {}",
+            surface.id(),
+            new_scale_factor
+        );
+
+        if let Some(mirror) = self.mirrors.get_mut(&surface.id()) {
+            mirror.scale_factor_changed(new_scale_factor);
+            let size = mirror.physical_size();
+            self.update_mirror_target(&surface.id(), |mirrors| mirrors.resize(&surface.id(), size));
+            return;
+        }
+
+        self.window_scale_changed(conn, surface, new_scale_factor);
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &WlSurface,
+        new_transform: Transform,
+    ) {
+        trace!(
+            "[{}] transform changed: {}",
+            surface.id(),
+            u32::from(new_transform),
+        );
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &WlSurface,
+        time: u32,
+    ) {
+        // Mirror windows are paced independently from the engine.
+        if self.mirrors.contains_key(&surface.id()) {
+            self.update_mirror_target(&surface.id(), |mirrors| mirrors.frame_done(&surface.id()));
+            return;
+        }
+
+        let Some(instance) = self.find_engine_by_surface_id_mut(&surface.id()) else {
+            warn!(
+                "[{}] ignoring `frame` event for unknown flutter window",
+                surface.id()
+            );
+            return;
+        };
+        let engine = instance.engine.clone();
+        let vsync_handler = instance.vsync_handler.clone();
+        // The baton of a callback requested before the surface was unmapped
+        // was already answered.
+        if !vsync_handler.lock().is_waiting_for_frame_callback() {
+            trace!("[{}] ignoring stale frame callback", surface.id());
+            return;
+        }
+
+        let baton = vsync_handler.lock().load_pending_baton();
+        trace!(
+            "[{} baton: {} time: {}] frame callback",
+            surface.id(),
+            baton,
+            time
+        );
+
+        let refresh_interval = self
+            .get_surface_frame_interval_in_nanos(surface)
+            .unwrap_or(FRAME_INTERVAL_60_HZ_IN_NANOS);
+
+        let frame_start_time_nanos = FlutterEngine::get_current_time_nanos();
+        let requested_at = vsync_handler.lock().pending_frame_callback_since();
+        if let (Some(requested_at), Some(window)) =
+            (requested_at, self.find_window_by_surface_id(&surface.id()))
+        {
+            window.occlusion().frame_callback_received(
+                frame_start_time_nanos.saturating_sub(requested_at),
+                refresh_interval,
+            );
+        }
+
+        let Some(frame_interval) = vsync_handler
+            .lock()
+            .pace_frame(frame_start_time_nanos, refresh_interval)
+        else {
+            // The frame request is answered on a later refresh.
+            surface.frame(qh, surface.clone());
+            surface.commit();
+            return;
+        };
+        let frame_target_time_nanos = frame_start_time_nanos + frame_interval;
+
+        vsync_handler.lock().frame_callback_received();
+        engine.on_vsync(baton, frame_start_time_nanos, frame_target_time_nanos);
+        if let Some(window) = self.find_window_by_surface_id_mut(surface.id()) {
+            window.on_vsync(frame_start_time_nanos);
+        }
+
+        let frame_time = SctkFrameTime {
+            time,
+            frame_start_time_nanos,
+            frame_target_time_nanos,
+        };
+
+        for callback in self.frame_callbacks.iter_mut() {
+            callback(&frame_time);
+        }
+    }
+
+    fn surface_enter(
+        &mut self,
+        conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wayland_client::protocol::wl_surface::WlSurface,
+        output: &wayland_client::protocol::wl_output::WlOutput,
+    ) {
+        trace!("[{}] entered {}", surface.id(), output.id());
+
+        if self.mirrors.contains_key(&surface.id()) {
+            return;
+        }
+
+        let Some(SctkEngineInstance {
+            plugins,
+            windows,
+            startup_synchronizer,
+            ..
+        }) = self.find_engine_by_surface_id_mut(&surface.id())
+        else {
+            warn!(
+                "[{}] ignoring `surface_enter` event for unknown flutter window",
+                surface.id()
+            );
+            return;
+        };
+        let is_engine_running = startup_synchronizer.is_engine_running();
+        let Some(window) = windows.get_mut(&surface.id()) else {
+            return;
+        };
+
+        // The scale of the window only changes with `scale_factor_changed`.
+        output::outputs_changed(
+            window.update_output_scales(),
+            false,
+            |scales| {
+                if is_engine_running {
+                    plugins
+                        .read()
+                        .with_plugin(|assets: &AssetsPlugin| assets.notify_scales_changed(scales));
+                }
+            },
+            || window.surface_outputs_changed(conn, surface),
+            || {},
+        );
+        self.update_color_info();
+    }
+
+    fn surface_leave(
+        &mut self,
+        conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wayland_client::protocol::wl_surface::WlSurface,
+        output: &wayland_client::protocol::wl_output::WlOutput,
+    ) {
+        trace!("[{}] left {}", surface.id(), output.id());
+
+        if self.mirrors.contains_key(&surface.id()) {
+            return;
+        }
+
+        let Some(SctkEngineInstance {
+            plugins,
+            windows,
+            startup_synchronizer,
+            ..
+        }) = self.find_engine_by_surface_id_mut(&surface.id())
+        else {
+            warn!(
+                "[{}] ignoring `surface_leave` event for unknown flutter window",
+                surface.id()
+            );
+            return;
+        };
+        let is_engine_running = startup_synchronizer.is_engine_running();
+        let Some(window) = windows.get_mut(&surface.id()) else {
+            return;
+        };
+
+        // The scale of the window only changes with `scale_factor_changed`.
+        output::outputs_changed(
+            window.update_output_scales(),
+            false,
+            |scales| {
+                if is_engine_running {
+                    plugins
+                        .read()
+                        .with_plugin(|assets: &AssetsPlugin| assets.notify_scales_changed(scales));
+                }
+            },
+            || window.surface_outputs_changed(conn, surface),
+            || {},
+        );
+        self.update_color_info();
+    }
+}
+
+impl ShmHandler for SctkApplicationState {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm_state
+    }
+}
+
+impl PointerHandler for SctkApplicationState {
+    fn pointer_frame(
+        &mut self,
+        conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        pointer: &WlPointer,
+        events: &[PointerEvent],
+    ) {
+        for event in events {
+            if let PointerEventKind::Press { serial, .. } = event.kind {
+                if let Some(data) = pointer.data::<PointerData>() {
+                    self.last_pointer_press = Some((data.seat().clone(), serial));
+                }
+            }
+
+            match event.kind {
+                PointerEventKind::Enter { serial } => {
+                    self.pointer_surfaces.insert(pointer.id(), event.surface.id());
+                    self.mouse_cursor_handler
+                        .lock()
+                        .pointer_entered(pointer, serial);
+                }
+                PointerEventKind::Leave { .. } => {
+                    self.pointer_surfaces.remove(&pointer.id());
+                    self.mouse_cursor_handler.lock().pointer_left(pointer);
+                }
+                PointerEventKind::Motion { .. } => {
+                    self.drag_attached(&event.surface.id(), event.position);
+                }
+                PointerEventKind::Release { .. } => self.attached_drag = None,
+                _ => {}
+            }
+
+            if matches!(
+                event.kind,
+                PointerEventKind::Enter { .. }
+                    | PointerEventKind::Motion { .. }
+                    | PointerEventKind::Press { .. }
+            ) {
+                self.last_pointer_position = Some((event.surface.id(), event.position));
+            }
+
+            if self.text_toolbar_pointer_event(pointer, event) {
+                continue;
+            }
+
+            if let PointerEventKind::Axis { vertical, .. } = event.kind {
+                let is_window = self
+                    .find_engine_id_by_surface_id(&event.surface.id())
+                    .is_some();
+                if is_window && self.is_content_zoom_shortcut() {
+                    let action = self.zoom_scroll.scroll(vertical.absolute, vertical.discrete);
+                    self.zoom_content(Some(event.surface.id()), action);
+                    continue;
+                }
+            }
+
+            let event = if self.mirrors.contains_key(&event.surface.id()) {
+                let Some(event) = self.mirror_pointer_event(event) else {
+                    continue;
+                };
+                event
+            } else {
+                event.clone()
+            };
+
+            let scroll = self.scroll;
+            let Some(instance) = self.find_engine_by_surface_id_mut(&event.surface.id()) else {
+                warn!(
+                    "[{}] ignoring pointer event for unknown flutter window",
+                    event.surface.id()
+                );
+                continue;
+            };
+            let is_engine_running = instance.is_running();
+            let Some(window) = instance.windows.get_mut(&event.surface.id()) else {
+                continue;
+            };
+
+            window.pointer_event(conn, pointer, &event, is_engine_running, scroll);
+        }
+    }
+}
+
+impl SctkApplicationState {
+    /// Send the |phases| of the touch |point| to the window it went down on.
+    /// The points are not tracked by the windows, so that their events keep
+    /// going to the same window even if they move outside of it.
+    fn send_touch_events(&self, point: &TouchPoint<ObjectId>, phases: &[FlutterPointerPhase]) {
+        let Some(id) = self.find_engine_id_by_surface_id(&point.surface) else {
+            return;
+        };
+        let instance = &self.engines[&id];
+        if !instance.is_running() {
+            return;
+        }
+
+        if let Some(window) = instance.windows.get(&point.surface) {
+            window.touch_events(point, phases);
+        }
+    }
+
+    fn cancel_touch_points(&mut self, touch: &WlTouch) {
+        use FlutterPointerPhase::*;
+
+        for point in self.touch_points.cancel(touch.id().protocol_id()) {
+            self.send_touch_events(&point, &[Cancel, Remove]);
+        }
+    }
+}
+
+impl TouchHandler for SctkApplicationState {
+    #[allow(clippy::too_many_arguments)]
+    fn down(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        touch: &WlTouch,
+        _serial: u32,
+        _time: u32,
+        surface: WlSurface,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        let point = self
+            .touch_points
+            .down(touch.id().protocol_id(), id, surface.id(), position.into())
+            .clone();
+        self.send_touch_events(&point, &[FlutterPointerPhase::Down]);
+    }
+
+    fn up(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        touch: &WlTouch,
+        _serial: u32,
+        _time: u32,
+        id: i32,
+    ) {
+        use FlutterPointerPhase::*;
+
+        if let Some(point) = self.touch_points.up(touch.id().protocol_id(), id) {
+            self.send_touch_events(&point, &[Up, Remove]);
+        }
+    }
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        touch: &WlTouch,
+        _time: u32,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        let point = self
+            .touch_points
+            .motion(touch.id().protocol_id(), id, position.into())
+            .cloned();
+        if let Some(point) = point {
+            self.send_touch_events(&point, &[FlutterPointerPhase::Move]);
+        }
+    }
+
+    fn shape(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _id: i32,
+        _major: f64,
+        _minor: f64,
+    ) {
+    }
+
+    fn orientation(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _id: i32,
+        _orientation: f64,
+    ) {
+    }
+
+    fn cancel(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, touch: &WlTouch) {
+        self.cancel_touch_points(touch);
+    }
+}
+
+impl KeyboardHandler for SctkApplicationState {
+    fn enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        surface: &WlSurface,
+        _serial: u32,
+        raw: &[u32],
+        keysyms: &[Keysym],
+    ) {
+        // The focus is kept on leave, so that the shortcuts and the text
+        // toolbar keep targeting the engine which was used last.
+        if self.find_engine_id_by_surface_id(&surface.id()).is_some() {
+            self.keyboard_focus = Some(surface.id());
+        }
+
+        let synthesized_events = self
+            .focused()
+            .keyboard_handler
+            .lock()
+            .sync_keyboard_enter_state(raw, keysyms);
+
+        for event in synthesized_events {
+            self.send_key_event(event);
+        }
+    }
+
+    fn leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _surface: &WlSurface,
+        _serial: u32,
+    ) {
+        // not implemented
+    }
+
+    fn press_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        event: KeyEvent,
+    ) {
+        trace!(
+            "key pressed: {}",
+            event.keysym.name().unwrap_or("[unknown]"),
+        );
+
+        if event.keysym == Keysym::Escape {
+            self.dismiss_text_toolbar();
+        }
+
+        // The shortcut is not sent to the app, including the key release.
+        let surface_id = self
+            .focused()
+            .implicit_window()
+            .map(|window| window.wl_surface_id());
+        if self.zoom_content(surface_id, ZoomAction::from_keysym(event.keysym)) {
+            self.zoom_keys.insert(event.raw_code);
+            return;
+        }
+
+        if self
+            .focused()
+            .keyboard_handler
+            .lock()
+            .press_key(event.clone())
+            .is_err()
+        {
+            error!(
+                "A key was pressed which was already found in internal state. Ignoring {:?}",
+                event
+            );
+            return;
+        };
+
+        self.press_key_or_repeat(SctkKeyEvent::new(
+            FlutterKeyEventDeviceType::Keyboard,
+            event,
+            FlutterKeyEventType::Down,
+            None,
+            self.modifiers,
+            false,
+        ));
+    }
+
+    fn release_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        event: KeyEvent,
+    ) {
+        trace!(
+            "key released: {}",
+            event.keysym.name().unwrap_or("[unknown]"),
+        );
+
+        if self.zoom_keys.remove(&event.raw_code) {
+            return;
+        }
+
+        let keyboard_handler = &self.focused().keyboard_handler;
+        let Ok(latched_keydown) = keyboard_handler.lock().release_key(&event) else {
+            error!(
+                "A key was released which was not found in internal state. Ignoring {:?}",
+                event
+            );
+            return;
+        };
+
+        self.send_key_event(SctkKeyEvent::new(
+            FlutterKeyEventDeviceType::Keyboard,
+            event,
+            FlutterKeyEventType::Up,
+            Some(latched_keydown),
+            self.modifiers,
+            false,
+        ));
+    }
+
+    fn update_modifiers(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        modifiers: Modifiers,
+        _layout: u32,
+    ) {
+        self.modifiers = modifiers;
+    }
+
+    fn update_repeat_info(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        info: RepeatInfo,
+    ) {
+        let repeat_info = match info {
+            RepeatInfo::Repeat { rate, delay } => KeyRepeatInfo {
+                enabled: true,
+                rate: rate.get(),
+                delay,
+            },
+            RepeatInfo::Disable => KeyRepeatInfo::disabled(),
+        };
+
+        // Before an engine is running, apps read the settings on startup
+        // instead.
+        for instance in self.engines.values() {
+            let is_engine_running = instance.is_running();
+            instance
+                .plugins
+                .read()
+                .with_plugin(|plugin: &KeyboardRepeatPlugin| {
+                    if plugin.set_repeat_info(repeat_info) && is_engine_running {
+                        plugin.notify_repeat_info_changed();
+                    }
+                });
+        }
+    }
+}
+
+impl ActivationHandler for SctkApplicationState {
+    type RequestData = RequestData;
+
+    fn new_token(&mut self, token: String, data: &RequestData) {
+        let requested_from = data.surface.as_ref().map(WlSurface::id);
+        if let Some(pending_raise) = &self.pending_raise {
+            if pending_raise.requested_from == requested_from {
+                self.activate_raised(pending_raise.target, token);
+                return;
+            }
+        }
+
+        let Some(surface) = &data.surface else {
+            return;
+        };
+
+        // The window was focused, or the flag cleared, in the meantime.
+        if !self.urgent_surfaces.contains(&surface.id()) {
+            return;
+        }
+
+        if let Some(activation_state) = &self.activation_state {
+            activation_state.activate::<Self>(surface, token);
+        }
+    }
+}
+
+impl SeatHandler for SctkApplicationState {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: WlSeat) {
+        // not needed for current implementation
+    }
+
+    fn remove_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: WlSeat) {
+        // not needed for current implementation
+    }
+
+    fn new_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Pointer {
+            let seat_cursor = self.create_seat_cursor(qh, &seat);
+
+            if let Some(pointer) = seat_cursor.as_ref().map(|cursor| cursor.pointer().clone()) {
+                if let Some(pointer_gestures) = &self.pointer_gestures {
+                    let pinch_gesture = pointer_gestures.get_pinch_gesture(
+                        &pointer,
+                        qh,
+                        SctkPinchGestureData::new(pointer.clone()),
+                    );
+                    self.pinch_gestures.insert(seat.id(), pinch_gesture);
+                }
+
+                self.pointers.insert(seat.id(), pointer);
+            } else {
+                error!("Failed to create wayland pointer");
+                self.pointers.remove(&seat.id());
+            }
+
+            self.mouse_cursor_handler
+                .lock()
+                .set_seat_cursor(seat.id(), seat_cursor);
+        }
+
+        if capability == Capability::Touch {
+            match self.seat_state.get_touch(qh, &seat) {
+                Ok(touch) => {
+                    self.touches.insert(seat.id(), touch);
+                }
+                Err(err) => error!("Failed to get touch: {}", err),
+            }
+        }
+
+        if capability == Capability::Keyboard {
+            if let Ok(keyboard) = self.seat_state.get_keyboard_with_repeat(
+                qh,
+                &seat,
+                None,
+                self.loop_handle.clone(),
+                Box::new(|state, _keyboard, event| {
+                    trace!(
+                        "key repeated: {}",
+                        event.keysym.name().unwrap_or("[unknown]"),
+                    );
+
+                    state.press_key_or_repeat(SctkKeyEvent::new(
+                        FlutterKeyEventDeviceType::Keyboard,
+                        event,
+                        FlutterKeyEventType::Repeat,
+                        None,
+                        state.modifiers,
+                        false,
+                    ));
+                }),
+            ) {
+                self.keyboards.insert(seat.id(), keyboard);
+
+                if let Some(text_input) = &mut self.text_input {
+                    text_input.add_seat(&seat, qh);
+                }
+            } else {
+                error!("Failed to get keyboard");
+                self.keyboards.remove(&seat.id());
+            }
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        seat: wayland_client::protocol::wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Pointer {
+            self.pointers.remove(&seat.id());
+
+            if let Some(pinch_gesture) = self.pinch_gestures.remove(&seat.id()) {
+                pinch_gesture.destroy();
+            }
+
+            self.mouse_cursor_handler
+                .lock()
+                .remove_cursor_for_seat(seat.id());
+        }
+
+        if capability == Capability::Touch {
+            if let Some(touch) = self.touches.remove(&seat.id()) {
+                self.cancel_touch_points(&touch);
+            }
+        }
+
+        if capability == Capability::Keyboard {
+            self.keyboards.remove(&seat.id());
+
+            if let Some(text_input) = &mut self.text_input {
+                text_input.remove_seat(&seat);
+            }
+        }
+    }
+}
+
+impl OutputHandler for SctkApplicationState {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, output: WlOutput) {
+        trace!("[{}] new output", output.id());
+
+        if let Some(info) = self.output_state.info(&output) {
+            self.output_scales
+                .update(output.id().protocol_id().into(), info.scale_factor);
+        }
+
+        if let Some(color_management) = &mut self.color_management {
+            color_management.add_output(&output, qh);
+        }
+
+        self.notify_display_update();
+    }
+
+    fn update_output(&mut self, conn: &Connection, _qh: &QueueHandle<Self>, output: WlOutput) {
+        trace!("[{}] update output", output.id());
+
+        let scale_changed = self.output_state.info(&output).is_some_and(|info| {
+            self.output_scales
+                .update(output.id().protocol_id().into(), info.scale_factor)
+        });
+
+        self.notify_display_update();
+
+        // Without a new `wl_surface` scale from the compositor (or the
+        // surfaces entering the output again), the windows would keep their
+        // previous scale until then.
+        if scale_changed {
+            self.output_scale_changed(conn, &output);
+        }
+    }
+
+    fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, output: WlOutput) {
+        trace!("[{}] output destroyed", output.id());
+
+        self.output_scales.remove(output.id().protocol_id().into());
+
+        let had_color_info = self
+            .color_management
+            .as_mut()
+            .is_some_and(|color_management| color_management.remove_output(&output));
+        if had_color_info {
+            self.update_color_info();
+        }
+
+        self.notify_display_update();
+    }
+}
+
+impl PopupHandler for SctkApplicationState {
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        popup: &Popup,
+        _config: PopupConfigure,
+    ) {
+        if let Some(text_toolbar) = &mut self.text_toolbar {
+            if text_toolbar.wl_surface() == popup.wl_surface() {
+                text_toolbar.draw();
+            }
+        }
+    }
+
+    fn done(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, popup: &Popup) {
+        let is_text_toolbar = self
+            .text_toolbar
+            .as_ref()
+            .is_some_and(|text_toolbar| text_toolbar.wl_surface() == popup.wl_surface());
+
+        if is_text_toolbar {
+            self.dismiss_text_toolbar();
+        }
+    }
+}
+
+impl WindowHandler for SctkApplicationState {
+    fn request_close(&mut self, _: &Connection, _: &QueueHandle<Self>, window: &Window) {
+        // Closing a mirror window leaves the application running.
+        if self.destroy_mirror_window(&window.wl_surface().id()) {
+            return;
+        }
+
+        self.close_engine_window(&window.wl_surface().id());
+    }
+
+    fn configure(
+        &mut self,
+        conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        window: &Window,
+        configure: WindowConfigure,
+        serial: u32,
+    ) {
+        let surface_id = window.wl_surface().id();
+        trace!(
+            "[{}] configure: {}x{}",
+            surface_id,
+            configure.new_size.0.map_or(0, |v| v.get()),
+            configure.new_size.1.map_or(0, |v| v.get()),
+        );
+
+        if self.mirrors.contains_key(&surface_id) {
+            self.configure_mirror(&surface_id, configure.new_size);
+            return;
+        }
+
+        self.maybe_update_lifecycle_state(surface_id.clone(), configure.is_activated());
+        if configure.is_activated() {
+            self.urgent_surfaces.remove(&surface_id);
+            self.window_activated(&surface_id);
+        }
+
+        let output_logical_size = self.get_default_output_logical_size();
+
+        let Some(SctkEngineInstance {
+            plugins,
+            windows,
+            startup_synchronizer,
+            ..
+        }) = self.find_engine_by_surface_id_mut(&surface_id)
+        else {
+            warn!(
+                "[{}] ignoring `configure` event for unknown flutter window",
+                surface_id,
+            );
+            return;
+        };
+        let Some(window) = windows.get_mut(&surface_id) else {
+            return;
+        };
+
+        let bounds_changed = window.update_suggested_bounds(configure.suggested_bounds);
+        if let Some(output_size) = output_logical_size {
+            window.fit_default_size_to_output(output_size);
+        }
+        let recommended_max_size = window.recommended_max_size();
+        window.store_window_state(configure.state);
+
+        // The state change is reported before the metrics of the new size,
+        // so that Dart never lays out the content with a stale state.
+        let is_engine_running = startup_synchronizer.is_engine_running();
+        let state_tracker = window.state_tracker();
+        window_state::configure_state(
+            &mut state_tracker.lock().unwrap(),
+            configure.state,
+            |state| {
+                if is_engine_running {
+                    plugins
+                        .read()
+                        .with_plugin(|window: &WindowPlugin| window.notify_state_changed(state));
+                }
+            },
+            || {
+                let new_size = startup_synchronizer.configure(configure.new_size, Some(serial));
+                if let Some(new_size) = new_size {
+                    window.configure(conn, new_size);
+                }
+            },
+        );
+        window.surface_configured();
+
+        if bounds_changed && is_engine_running {
+            plugins.read().with_plugin(|window: &WindowPlugin| {
+                window.notify_recommended_max_size_changed(recommended_max_size);
+            });
+        }
+
+        self.redraw_failure_screen(&surface_id);
+    }
+}
+
+impl LayerShellHandler for SctkApplicationState {
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, layer: &LayerSurface) {
+        // The compositor closes layer surfaces e.g.: when their output is
+        // removed. They can't be shown again, so handle them like regular
+        // windows being closed.
+        self.close_engine_window(&layer.wl_surface().id());
+    }
+
+    fn configure(
+        &mut self,
+        conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        serial: u32,
+    ) {
+        let surface_id = layer.wl_surface().id();
+        let (width, height) = configure.new_size;
+        trace!("[{}] layer configure: {}x{}", surface_id, width, height);
+
+        // Layer surfaces don't have an activated state.
+        self.maybe_update_lifecycle_state(surface_id.clone(), true);
+
+        let Some(SctkEngineInstance {
+            windows,
+            startup_synchronizer,
+            ..
+        }) = self.find_engine_by_surface_id_mut(&surface_id)
+        else {
+            warn!(
+                "[{}] ignoring `configure` event for unknown flutter layer surface",
+                surface_id,
+            );
+            return;
+        };
+        let Some(window) = windows.get_mut(&surface_id) else {
+            return;
+        };
+
+        // A `0` dimension means that the size should be picked by the client.
+        let new_size = (NonZeroU32::new(width), NonZeroU32::new(height));
+
+        if let Some(new_size) = startup_synchronizer.configure(new_size, Some(serial)) {
+            window.configure(conn, new_size);
+        }
+        window.surface_configured();
+
+        self.redraw_failure_screen(&surface_id);
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SctkApplicationCreateError {
+    #[error(transparent)]
+    CalloopError(#[from] calloop::Error),
+
+    #[error(transparent)]
+    CalloopInsertWaylandSourceError(
+        #[from] calloop::InsertError<WaylandSource<SctkApplicationState>>,
+    ),
+
+    #[error(transparent)]
+    CalloopInsertAsyncExecutorError(#[from] calloop::InsertError<Executor<SctkAsyncResult>>),
+
+    #[error(transparent)]
+    ConnectError(#[from] ConnectError),
+
+    #[error(transparent)]
+    GlobalError(#[from] GlobalError),
+
+    #[error(transparent)]
+    BindError(#[from] BindError),
+
+    #[error(transparent)]
+    SctkFlutterWindowCreateError(#[from] SctkFlutterWindowCreateError),
+
+    #[error(transparent)]
+    EngineCreateError(#[from] CreateError),
+
+    #[error(transparent)]
+    EnginePrepareError(#[from] PrepareError),
+
+    #[error("Window {0} can't be attached to: it doesn't exist, or is attached itself")]
+    InvalidAttachment(u64),
+}
+
+#[derive(Error, Debug)]
+pub enum SctkApplicationRunError {
+    #[error(transparent)]
+    DispatchError(#[from] calloop::Error),
+
+    #[error(transparent)]
+    InsertError(#[from] calloop::InsertError<Timer>),
+
+    #[error("Failed to run the engine: {0}")]
+    EngineRunFailed(#[source] RunError),
+
+    #[error("The startup timed out while {state}")]
+    StartupTimeout { state: StartupState },
+}
+
+/// Whether the frame callbacks of the engines other than |id| arrived since
+/// |since|, given the pending request time and the last callback time of
+/// each engine. The engines which don't render tell nothing, so it is also
+/// the case when there are only such engines.
+fn other_frame_callbacks_flowing(
+    frame_callbacks: &[(SctkEngineId, Option<u64>, u64)],
+    id: SctkEngineId,
+    since: u64,
+) -> bool {
+    let mut others = frame_callbacks
+        .iter()
+        .filter(|(other, pending_since, last_callback)| {
+            *other != id && (pending_since.is_some() || *last_callback >= since)
+        })
+        .peekable();
+    others.peek().is_none() || others.any(|(_, _, last_callback)| *last_callback >= since)
+}
+
+fn register_shutdown_tasks(
+    coordinator: &mut ShutdownCoordinator<SctkApplicationState>,
+    wayland_token: RegistrationToken,
+) {
+    // Number of running engines which didn't acknowledge the detached state.
+    let pending_acks = Arc::new(AtomicUsize::new(0));
+    let mut is_sent = false;
+    coordinator.register_polled(ShutdownPhase::NotifyDetached, "lifecycle", move |state| {
+        if !is_sent {
+            is_sent = true;
+            for instance in state.engines.values().filter(|instance| instance.is_running()) {
+                pending_acks.fetch_add(1, Ordering::AcqRel);
+
+                let pending_acks = pending_acks.clone();
+                let on_ack = move || {
+                    pending_acks.fetch_sub(1, Ordering::AcqRel);
+                };
+                instance
+                    .plugins
+                    .read()
+                    .with_plugin(|lifecycle: &LifecyclePlugin| {
+                        lifecycle.send_app_is_detached(on_ack)
+                    });
+            }
+        }
+
+        if pending_acks.load(Ordering::Acquire) == 0 {
+            ShutdownPoll::Ready
+        } else {
+            ShutdownPoll::Pending
+        }
+    });
+
+    coordinator.register(ShutdownPhase::DetachPlugins, "extensions", |state| {
+        for mut extension in state.extensions.drain(..) {
+            extension.shutdown();
+        }
+    });
+
+    coordinator.register(ShutdownPhase::DetachPlugins, "plugins", |state| {
+        for instance in state.engines.values() {
+            let count = instance.plugins.write().detach_all(&instance.engine);
+            instance.engine.remove_all_channels();
+            trace!("Detached {} plugins", count);
+        }
+    });
+
+    coordinator.register(ShutdownPhase::RemoveViews, "views", |state| {
+        for instance in state.engines.values() {
+            for window in instance.windows.values() {
+                instance.engine.remove_view(window.view_id());
+            }
+        }
+    });
+
+    coordinator.register(ShutdownPhase::ShutdownEngine, "engine", |state| {
+        for instance in state.engines.values() {
+            instance.engine.shutdown();
+        }
+    });
+
+    coordinator.register(ShutdownPhase::DestroyContexts, "windows", |state| {
+        state.text_toolbar = None;
+        state.mirrors.clear();
+        for instance in state.engines.values_mut() {
+            instance.windows.clear();
+            instance.wayland_handles.clear_windows();
+        }
+    });
+
+    coordinator.register(ShutdownPhase::DisconnectWayland, "wayland", move |state| {
+        state.loop_handle.remove(wayland_token);
+        if let Err(err) = state.conn.flush() {
+            warn!("Failed to flush the Wayland connection: {}", err);
+        }
+    });
+}
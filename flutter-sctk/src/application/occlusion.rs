@@ -0,0 +1,93 @@
+//! Visibility of the windows, estimated from the frame callbacks (see
+//! `crate::occlusion`).
+use flutter_engine::FlutterEngine;
+use flutter_plugins::window::WindowPlugin;
+use tracing::{info, trace};
+
+use super::{other_frame_callbacks_flowing, SctkApplicationState, SctkEngineId};
+use crate::handler::FRAME_INTERVAL_60_HZ_IN_NANOS;
+
+impl SctkApplicationState {
+    /// Update the estimated visibility of the windows (see `crate::occlusion`).
+    /// The apps are notified of the changes, the frames of the windows hidden
+    /// by the compositor are paused, and the engines whose window stays
+    /// hidden are asked to release their caches.
+    pub(super) fn update_visibility(&mut self) {
+        let now = FlutterEngine::get_current_time_nanos();
+        let frame_callbacks: Vec<(SctkEngineId, Option<u64>, u64)> = self
+            .engines
+            .iter()
+            .map(|(id, instance)| {
+                let vsync_handler = instance.vsync_handler.lock();
+                (
+                    *id,
+                    vsync_handler.pending_frame_callback_since(),
+                    vsync_handler.last_frame_callback_time(),
+                )
+            })
+            .collect();
+
+        let mut changes = Vec::new();
+        for (id, pending_since, _) in &frame_callbacks {
+            let instance = &self.engines[id];
+            let Some(window) = instance.implicit_window() else {
+                continue;
+            };
+            let refresh_interval = self
+                .get_surface_frame_interval_in_nanos(&window.wl_surface())
+                .unwrap_or(FRAME_INTERVAL_60_HZ_IN_NANOS);
+
+            let mut occlusion = window.occlusion();
+            occlusion.set_surface_visibility(window.surface_visibility());
+            match pending_since {
+                Some(since) => occlusion.frame_callback_pending(
+                    now.saturating_sub(*since),
+                    refresh_interval,
+                    other_frame_callbacks_flowing(&frame_callbacks, *id, *since),
+                ),
+                None if occlusion.should_probe(now) && instance.is_running() => {
+                    instance.engine.schedule_frame();
+                }
+                None => {}
+            }
+            let visibility = occlusion.update(now);
+            let is_hidden_by_compositor = occlusion.is_hidden_by_compositor();
+            let should_trim = occlusion.should_trim(now);
+            drop(occlusion);
+
+            instance
+                .vsync_handler
+                .lock()
+                .set_occluded(is_hidden_by_compositor);
+            if should_trim && instance.is_running() {
+                info!(
+                    "[{}] releasing the caches of the hidden window",
+                    window.wl_surface_id()
+                );
+                instance.engine.notify_low_memory_warning();
+            }
+            if let Some(visibility) = visibility {
+                changes.push((*id, visibility));
+            }
+        }
+
+        for (id, visibility) in changes {
+            let instance = &self.engines[&id];
+            let Some(window) = instance.implicit_window() else {
+                continue;
+            };
+            trace!("[{}] visibility: {:?}", window.wl_surface_id(), visibility);
+            if instance.is_running() {
+                instance
+                    .plugins
+                    .read()
+                    .with_plugin(|window_plugin: &WindowPlugin| {
+                        window_plugin.notify_visibility_changed(visibility)
+                    });
+            }
+            for callback in self.visibility_callbacks.iter_mut() {
+                callback(window, visibility);
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Registry of the engines run by the application, and of the requests
+//! sent to the event loop on their behalf (see `crate::engine`).
+use std::time::Duration;
+
+use flutter_engine::builder::FlutterEngineBuilder;
+use flutter_plugins::{
+    anchor::{AnchorsInvalidated, PopupPlacement},
+    appearance::AccentColor,
+    text_toolbar::{TextToolbarAction, TextToolbarRect},
+    textinput::TextInputFocusInfo,
+};
+use flutter_runner_api::{ApplicationAttributes, WindowRole};
+use smithay_client_toolkit::{
+    reexports::calloop::timer::{TimeoutAction, Timer},
+    seat::keyboard::Keysym,
+};
+use tracing::{error, trace, warn};
+use wayland_backend::client::ObjectId;
+use wayland_client::protocol::wl_surface::WlSurface;
+
+use super::{SctkApplicationCreateError, SctkApplicationState};
+#[cfg(feature = "emulated-input")]
+use crate::emulated_input::SctkEmulatedInputRequest;
+#[cfg(feature = "status-notifier")]
+use crate::tray::SctkTrayRequest;
+use crate::{
+    accessibility::SctkAccessibilityChange,
+    engine::{SctkEngineInstance, SctkEngineResources},
+    scroll::SctkScrollChange,
+};
+
+/// Identifies an engine of the application. The engine the application is
+/// created with is the primary one, which lives as long as the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SctkEngineId(u64);
+
+impl SctkEngineId {
+    pub const PRIMARY: SctkEngineId = SctkEngineId(0);
+
+    pub(crate) fn next(self) -> SctkEngineId {
+        SctkEngineId(self.0 + 1)
+    }
+
+    /// The identifier of the engine window reported to the app (see
+    /// `WindowHandler::window_id`), and used by `WindowRole::Utility`.
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub(crate) fn from_u64(id: u64) -> SctkEngineId {
+        SctkEngineId(id)
+    }
+}
+
+pub(crate) enum SctkApplicationRequest {
+    Suspend {
+        drop_caches: bool,
+    },
+    Resume,
+    ShowTextToolbar {
+        rect: TextToolbarRect,
+        placement: PopupPlacement,
+        actions: Vec<TextToolbarAction>,
+    },
+    HideTextToolbar,
+    SetScreenSaverInhibited(bool),
+    #[cfg(feature = "emulated-input")]
+    EmulatedInput(SctkEmulatedInputRequest),
+    #[cfg(feature = "status-notifier")]
+    Tray(SctkTrayRequest),
+    SetTextInputEnabled(bool),
+    SetTextInputFocus(TextInputFocusInfo),
+    /// Resend the window metrics rejected by the engine after `delay`.
+    RetryWindowMetrics {
+        surface: WlSurface,
+        delay: Duration,
+    },
+    /// Zoom the content of the window (already clamped).
+    SetContentZoom {
+        surface: WlSurface,
+        zoom: f64,
+    },
+    /// Flag the window as needing the user's attention, or clear the flag.
+    SetUrgent {
+        surface: WlSurface,
+        urgent: bool,
+    },
+    /// Stack the window above (or below, unless |above|) the window of the
+    /// |sibling| engine.
+    Restack {
+        surface: WlSurface,
+        sibling: SctkEngineId,
+        above: bool,
+    },
+    /// Move the attached window along with the pointer until released, or
+    /// stop doing so.
+    SetAttachedDrag {
+        surface: WlSurface,
+        dragging: bool,
+    },
+    SetMinFrameInterval(Option<Duration>),
+    /// Show or hide the implicit window of the primary engine.
+    SetWindowVisible(bool),
+    /// An accessibility setting of the desktop was read, or has changed.
+    UpdateAccessibility(SctkAccessibilityChange),
+    /// The accent color of the desktop was read, or has changed. `None` when
+    /// the desktop has none.
+    UpdateAccentColor(Option<AccentColor>),
+    /// A scroll setting of the desktop was read, or has changed.
+    UpdateScroll(SctkScrollChange),
+    /// A key press (or repeat) wasn't handled by the framework, and falls
+    /// through to the text being edited.
+    EditText {
+        keysym: Keysym,
+        utf8: Option<String>,
+        select: bool,
+    },
+    /// The metrics of the window have changed, invalidating the anchor
+    /// tokens created for the previous ones.
+    InvalidateAnchors {
+        surface: WlSurface,
+        invalidated: AnchorsInvalidated,
+    },
+    /// The engine presented its first frame to the window.
+    FirstFramePresented {
+        surface: WlSurface,
+    },
+    /// Replay the input buffered until the app was ready, and stop gating it
+    /// (see `crate::input_gate`).
+    ReleaseInput {
+        surface: WlSurface,
+    },
+    Exit,
+    /// Exit as requested by Dart, reporting |exit_code| once the engine is
+    /// shut down.
+    ExitWithCode(i32),
+}
+
+impl SctkApplicationState {
+    /// The engine the application was created with.
+    pub(super) fn primary(&self) -> &SctkEngineInstance {
+        &self.engines[&SctkEngineId::PRIMARY]
+    }
+
+    pub(super) fn find_engine_id_by_surface_id(
+        &self,
+        surface_id: &ObjectId,
+    ) -> Option<SctkEngineId> {
+        self.engines
+            .iter()
+            .find(|(_, instance)| instance.windows.contains_key(surface_id))
+            .map(|(&id, _)| id)
+    }
+
+    pub(super) fn find_engine_by_surface_id_mut(
+        &mut self,
+        surface_id: &ObjectId,
+    ) -> Option<&mut SctkEngineInstance> {
+        self.engines
+            .values_mut()
+            .find(|instance| instance.windows.contains_key(surface_id))
+    }
+
+    pub(super) fn add_engine(
+        &mut self,
+        engine_builder: FlutterEngineBuilder,
+        attributes: ApplicationAttributes,
+    ) -> Result<SctkEngineId, SctkApplicationCreateError> {
+        let (parent, attachment) = match attributes.window_role {
+            WindowRole::Utility {
+                attached_to,
+                position,
+            } => {
+                let (parent, attachment) = self.attachment(attached_to, position)?;
+                (Some(parent), Some(attachment))
+            }
+            _ => (None, None),
+        };
+
+        let id = self.next_engine_id;
+        let instance = SctkEngineInstance::new(
+            SctkEngineResources {
+                conn: &self.conn,
+                globals: &self.globals,
+                qh: &self.qh,
+                loop_signal: &self.loop_signal,
+                compositor_state: &self.compositor_state,
+                xdg_shell_state: &self.xdg_shell_state,
+                mouse_cursor_handler: &self.mouse_cursor_handler,
+                screensaver_inhibitor: &self.screensaver_inhibitor,
+                scroll_settings: &self.scroll_settings,
+                #[cfg(feature = "emulated-input")]
+                emulated_input: &self.emulated_input,
+                #[cfg(feature = "status-notifier")]
+                tray: &self.tray,
+                request_sender: &self.request_sender,
+            },
+            id,
+            engine_builder,
+            attributes,
+            attachment,
+        )?;
+
+        self.next_engine_id = id.next();
+        self.engines.insert(id, instance);
+        trace!("Added engine {:?}", id);
+        self.set_srgb_surfaces();
+
+        match parent {
+            Some(parent) => {
+                self.window_stack.add_attached(id, parent);
+                self.configure_attached(id);
+            }
+            None => self.window_stack.add_toplevel(id),
+        }
+
+        // Otherwise, the engine is run along with the primary one.
+        if self.is_started {
+            let result = self.loop_handle.insert_source(
+                Timer::immediate(),
+                move |_event, _metadata, state| {
+                    state.run_engine(id);
+                    TimeoutAction::Drop
+                },
+            );
+
+            if let Err(err) = result {
+                error!(
+                    "Failed to schedule the start of engine {:?}: {}",
+                    id, err.error
+                );
+            }
+        }
+
+        Ok(id)
+    }
+
+    pub(super) fn remove_engine(&mut self, id: SctkEngineId) -> bool {
+        if id == SctkEngineId::PRIMARY {
+            warn!("The primary engine can't be removed, quit the application instead");
+            return false;
+        }
+
+        if !self.engines.contains_key(&id) {
+            return false;
+        }
+
+        // The attached windows can't outlive their parent.
+        for attached_id in self.window_stack.remove(id) {
+            self.remove_engine(attached_id);
+        }
+
+        let Some(instance) = self.engines.remove(&id) else {
+            return false;
+        };
+        trace!("Removing engine {:?}", id);
+
+        if let Some(pending_raise) = self
+            .pending_raise
+            .take_if(|pending_raise| pending_raise.target == id)
+        {
+            self.loop_handle.remove(pending_raise.timeout);
+        }
+
+        let was_dragged = self
+            .attached_drag
+            .as_ref()
+            .is_some_and(|drag| instance.windows.contains_key(&drag.surface_id));
+        if was_dragged {
+            self.attached_drag = None;
+        }
+
+        let had_keyboard_focus = self
+            .keyboard_focus
+            .as_ref()
+            .is_some_and(|surface_id| instance.windows.contains_key(surface_id));
+        if had_keyboard_focus {
+            self.keyboard_focus = None;
+            self.text_toolbar = None;
+        }
+
+        for surface_id in instance.windows.keys() {
+            self.active_state.remove(surface_id);
+            if let Some(color_management) = &mut self.color_management {
+                color_management.remove_surface(surface_id);
+            }
+        }
+
+        instance.shutdown();
+        true
+    }
+
+    /// Closing the window of an added engine removes the engine, while
+    /// closing the window of the primary engine quits the application.
+    pub(super) fn close_engine_window(&mut self, surface_id: &ObjectId) {
+        match self.find_engine_id_by_surface_id(surface_id) {
+            Some(id) if id != SctkEngineId::PRIMARY => {
+                self.remove_engine(id);
+            }
+            _ => self.exit(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Routing of the events and plugin calls to the engine owning a surface,
+//! or to the engine with the keyboard focus.
+use flutter_engine::{ffi::FlutterViewId, plugins::Plugin};
+use wayland_backend::client::ObjectId;
+
+use super::{SctkApplicationState, SctkEngineId};
+use crate::{engine::SctkEngineInstance, window::SctkFlutterWindow};
+
+impl SctkApplicationState {
+    /// Use a plugin of the engine handling the keyboard (see `focused`). The
+    /// events of a window are routed to the engine owning it instead (see
+    /// `with_surface_plugin`).
+    pub fn with_plugin<F, P>(&self, f: F)
+    where
+        F: FnOnce(&P),
+        P: Plugin + 'static,
+    {
+        self.focused().plugins.read().with_plugin(f)
+    }
+
+    pub fn with_plugin_mut<F, P>(&self, f: F)
+    where
+        F: FnOnce(&mut P),
+        P: Plugin + 'static,
+    {
+        self.focused().plugins.write().with_plugin_mut(f)
+    }
+
+    /// Use a plugin of the engine owning the window of |surface_id|, i.e.:
+    /// the window an event comes from. Does nothing once the window is gone.
+    pub(super) fn with_surface_plugin<F, P>(&self, surface_id: &ObjectId, f: F)
+    where
+        F: FnOnce(&P),
+        P: Plugin + 'static,
+    {
+        if let Some(id) = self.find_engine_id_by_surface_id(surface_id) {
+            self.engines[&id].plugins.read().with_plugin(f)
+        }
+    }
+
+    pub(super) fn with_surface_plugin_mut<F, P>(&self, surface_id: &ObjectId, f: F)
+    where
+        F: FnOnce(&mut P),
+        P: Plugin + 'static,
+    {
+        if let Some(id) = self.find_engine_id_by_surface_id(surface_id) {
+            self.engines[&id].plugins.write().with_plugin_mut(f)
+        }
+    }
+
+    /// The engine owning the window which had the keyboard focus last, or the
+    /// primary engine. It handles the keyboard, the text input and the
+    /// shortcuts (e.g.: zoom), and owns the text toolbar.
+    pub(super) fn focused(&self) -> &SctkEngineInstance {
+        let id = self
+            .keyboard_focus
+            .as_ref()
+            .and_then(|surface_id| self.find_engine_id_by_surface_id(surface_id))
+            .unwrap_or(SctkEngineId::PRIMARY);
+
+        &self.engines[&id]
+    }
+
+    pub(super) fn windows(&self) -> impl Iterator<Item = &SctkFlutterWindow> {
+        self.engines
+            .values()
+            .flat_map(|instance| instance.windows.values())
+    }
+
+    pub(super) fn find_window_by_surface_id(
+        &self,
+        surface_id: &ObjectId,
+    ) -> Option<&SctkFlutterWindow> {
+        self.windows()
+            .find(|window| &window.wl_surface_id() == surface_id)
+    }
+
+    pub(super) fn find_window_by_surface_id_mut(
+        &mut self,
+        surface_id: ObjectId,
+    ) -> Option<&mut SctkFlutterWindow> {
+        self.engines
+            .values_mut()
+            .find_map(|instance| instance.windows.get_mut(&surface_id))
+    }
+
+    /// View ids are only unique within an engine, so mirrors are limited to
+    /// the views of the primary engine.
+    pub(super) fn find_window_by_view_id(
+        &self,
+        view_id: FlutterViewId,
+    ) -> Option<&SctkFlutterWindow> {
+        self.primary()
+            .windows
+            .values()
+            .find(|window| window.view_id() == view_id)
+    }
+}
@@ -0,0 +1,397 @@
+//! Stacking of the engine windows, and attached windows (see
+//! `crate::stacking` and `crate::attached`).
+use std::time::Duration;
+
+use dpi::LogicalPosition;
+use flutter_plugins::window::{StackingOutcome, StackingParams, WindowPlugin};
+use smithay_client_toolkit::{
+    activation::RequestData,
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        RegistrationToken,
+    },
+};
+use tracing::{error, trace, warn};
+use wayland_backend::client::ObjectId;
+use wayland_client::{protocol::wl_surface::WlSurface, Proxy};
+
+use super::{SctkApplicationCreateError, SctkApplicationState, SctkEngineId};
+use crate::{
+    attached::{self, SctkAttachment},
+    engine::SctkEngineInstance,
+    stacking::SctkRestack,
+    window::SctkFlutterWindow,
+};
+
+/// Maximum time waited for the compositor to activate a window being raised
+/// (see `SctkApplicationState::restack`), after which the raise is reported
+/// as refused.
+const RAISE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A toplevel being raised through `xdg_activation_v1`.
+pub(super) struct PendingRaise {
+    pub(super) target: SctkEngineId,
+    /// The focused surface the activation token was requested for.
+    pub(super) requested_from: Option<ObjectId>,
+    pub(super) timeout: RegistrationToken,
+}
+
+/// An attached window following the pointer.
+pub(super) struct AttachedDrag {
+    pub(super) surface_id: ObjectId,
+    parent_id: ObjectId,
+    /// Point of the window (relative to it) kept under the pointer.
+    grab: (f64, f64),
+}
+
+impl SctkApplicationState {
+    /// Where to attach the window of a new engine to the window of the
+    /// |attached_to| engine, along with the id of that engine.
+    pub(super) fn attachment(
+        &self,
+        attached_to: u64,
+        position: LogicalPosition<i32>,
+    ) -> Result<(SctkEngineId, SctkAttachment), SctkApplicationCreateError> {
+        let parent = SctkEngineId::from_u64(attached_to);
+        let parent_surface = self
+            .engines
+            .get(&parent)
+            .filter(|_| self.window_stack.parent(parent).is_none())
+            .and_then(SctkEngineInstance::implicit_window)
+            .map(SctkFlutterWindow::wl_surface)
+            .ok_or(SctkApplicationCreateError::InvalidAttachment(attached_to))?;
+
+        let attachment = SctkAttachment {
+            subcompositor: self.globals.bind(&self.qh, 1..=1, ())?,
+            parent: parent_surface,
+            position,
+        };
+        Ok((parent, attachment))
+    }
+
+    /// Subsurfaces aren't configured by the compositor, so attached windows
+    /// are configured once created, at their default size.
+    pub(super) fn configure_attached(&mut self, id: SctkEngineId) {
+        let Some(surface_id) = self
+            .engines
+            .get(&id)
+            .and_then(SctkEngineInstance::implicit_window)
+            .map(SctkFlutterWindow::wl_surface_id)
+        else {
+            return;
+        };
+        trace!("[{}] attached configure", surface_id);
+
+        // Attached windows don't have an activated state.
+        self.maybe_update_lifecycle_state(surface_id.clone(), true);
+
+        let Some(SctkEngineInstance {
+            windows,
+            startup_synchronizer,
+            ..
+        }) = self.engines.get_mut(&id)
+        else {
+            return;
+        };
+        let Some(window) = windows.get_mut(&surface_id) else {
+            return;
+        };
+
+        if let Some(new_size) = startup_synchronizer.configure((None, None), None) {
+            window.configure(&self.conn, new_size);
+        }
+        window.surface_configured();
+
+        self.redraw_failure_screen(&surface_id);
+    }
+
+    /// Ask the compositor to flag the window as urgent through a surface
+    /// activation without any user interaction, which it shows by requesting
+    /// the user's attention instead (e.g.: by flashing the taskbar entry).
+    /// Once sent, the activation can't be cancelled, so clearing the flag
+    /// only stops the pending one.
+    pub(super) fn set_urgent(&mut self, surface: WlSurface, urgent: bool) {
+        let surface_id = surface.id();
+        if !urgent {
+            self.urgent_surfaces.remove(&surface_id);
+            return;
+        }
+
+        // A focused window already has the user's attention.
+        if self.active_state.get(&surface_id).copied().unwrap_or(false) {
+            return;
+        }
+
+        let Some(activation_state) = &self.activation_state else {
+            warn!("Unable to flag the window as urgent: xdg-activation is not supported");
+            return;
+        };
+
+        if self.urgent_surfaces.insert(surface_id) {
+            activation_state.request_token(
+                &self.qh,
+                RequestData {
+                    app_id: None,
+                    seat_and_serial: None,
+                    surface: Some(surface),
+                },
+            );
+        }
+    }
+
+    /// Stack the window of |surface_id| above (or below, unless |above|) the
+    /// window of the |sibling| engine (see `crate::stacking`). The outcome is
+    /// reported to the engines, once known for the windows being raised.
+    pub(super) fn restack(&mut self, surface_id: &ObjectId, sibling: SctkEngineId, above: bool) {
+        let Some(id) = self.find_engine_id_by_surface_id(surface_id) else {
+            return;
+        };
+
+        let outcome = match self.window_stack.plan(id, sibling, above) {
+            SctkRestack::Unchanged => StackingOutcome::Unchanged,
+            SctkRestack::Unsupported => StackingOutcome::Unsupported,
+            SctkRestack::PlaceAbove { window, sibling } => {
+                self.place_attached(window, sibling, true)
+            }
+            SctkRestack::PlaceBelow { window, sibling } => {
+                self.place_attached(window, sibling, false)
+            }
+            SctkRestack::Activate(target) => match self.raise(target) {
+                Some(outcome) => outcome,
+                // Reported once the compositor activated the window, or not.
+                None => return,
+            },
+        };
+
+        self.notify_stacking_changed(outcome);
+    }
+
+    /// Stack the attached |window| above (or below, unless |above|) its
+    /// |sibling|, which applies right away.
+    pub(super) fn place_attached(
+        &mut self,
+        window: SctkEngineId,
+        sibling: SctkEngineId,
+        above: bool,
+    ) -> StackingOutcome {
+        let implicit_window = |id| {
+            self.engines
+                .get(&id)
+                .and_then(SctkEngineInstance::implicit_window)
+        };
+        let attached_surface =
+            implicit_window(window).and_then(SctkFlutterWindow::attached_surface);
+        let (Some(attached_surface), Some(sibling_window)) =
+            (attached_surface, implicit_window(sibling))
+        else {
+            return StackingOutcome::Unsupported;
+        };
+
+        attached_surface.place(&sibling_window.wl_surface(), above);
+        self.window_stack.place(window, sibling, above);
+        StackingOutcome::Applied
+    }
+
+    /// Ask the compositor to activate the |target| toplevel, which raises it.
+    /// Compositors only do so for requests following a recent user input, so
+    /// the activation token is requested on behalf of the focused window.
+    ///
+    /// Returns the outcome, unless it is only known once the compositor
+    /// activated the window (or not, after `RAISE_TIMEOUT`).
+    pub(super) fn raise(&mut self, target: SctkEngineId) -> Option<StackingOutcome> {
+        // Layer surfaces are stacked by their layer.
+        let is_toplevel = self
+            .engines
+            .get(&target)
+            .and_then(SctkEngineInstance::implicit_window)
+            .is_some_and(|window| window.window().is_some());
+        if !is_toplevel {
+            return Some(StackingOutcome::Unsupported);
+        }
+
+        if self.activation_state.is_none() {
+            warn!("Unable to raise the window: xdg-activation is not supported");
+            return Some(StackingOutcome::Unsupported);
+        }
+
+        // The pending raise is superseded.
+        if let Some(pending_raise) = self.pending_raise.take() {
+            self.loop_handle.remove(pending_raise.timeout);
+        }
+
+        let result = self.loop_handle.insert_source(
+            Timer::from_duration(RAISE_TIMEOUT),
+            move |_event, _metadata, state| {
+                state.raise_timed_out(target);
+                TimeoutAction::Drop
+            },
+        );
+        let timeout = match result {
+            Ok(timeout) => timeout,
+            Err(err) => {
+                error!("Failed to schedule the raise timeout: {}", err.error);
+                return Some(StackingOutcome::Unsupported);
+            }
+        };
+
+        let focused_surface = self
+            .keyboard_focus
+            .as_ref()
+            .and_then(|surface_id| self.find_window_by_surface_id(surface_id))
+            .map(SctkFlutterWindow::wl_surface);
+        self.pending_raise = Some(PendingRaise {
+            target,
+            requested_from: focused_surface.as_ref().map(WlSurface::id),
+            timeout,
+        });
+
+        if let Some(activation_state) = &self.activation_state {
+            activation_state.request_token(
+                &self.qh,
+                RequestData {
+                    app_id: None,
+                    seat_and_serial: self.last_pointer_press.clone(),
+                    surface: focused_surface,
+                },
+            );
+        }
+
+        None
+    }
+
+    /// Activate the |target| toplevel being raised with the |token| requested
+    /// for it.
+    pub(super) fn activate_raised(&self, target: SctkEngineId, token: String) {
+        let surface = self
+            .engines
+            .get(&target)
+            .and_then(SctkEngineInstance::implicit_window)
+            .map(SctkFlutterWindow::wl_surface);
+
+        if let (Some(activation_state), Some(surface)) = (&self.activation_state, surface) {
+            activation_state.activate::<Self>(&surface, token);
+        }
+    }
+
+    pub(super) fn raise_timed_out(&mut self, target: SctkEngineId) {
+        let is_pending = self
+            .pending_raise
+            .as_ref()
+            .is_some_and(|pending_raise| pending_raise.target == target);
+        if !is_pending {
+            return;
+        }
+
+        trace!("The compositor didn't raise engine {:?}", target);
+        self.pending_raise = None;
+        self.notify_stacking_changed(StackingOutcome::Refused);
+    }
+
+    /// The compositor activated the toplevel of |surface_id|, which raised it
+    /// above the other windows, along with its attached windows.
+    pub(super) fn window_activated(&mut self, surface_id: &ObjectId) {
+        let Some(id) = self.find_engine_id_by_surface_id(surface_id) else {
+            return;
+        };
+
+        let raise = self
+            .pending_raise
+            .take_if(|pending_raise| pending_raise.target == id);
+        if let Some(raise) = &raise {
+            self.loop_handle.remove(raise.timeout);
+        }
+
+        let is_reordered = self.window_stack.activated(id);
+        if raise.is_some() {
+            self.notify_stacking_changed(StackingOutcome::Applied);
+        } else if is_reordered {
+            self.notify_stacking_changed(StackingOutcome::Compositor);
+        }
+    }
+
+    /// Report the stacking order to the engines which are running.
+    pub(super) fn notify_stacking_changed(&self, outcome: StackingOutcome) {
+        let order = self.window_stack.order();
+        trace!("Stacking order: {:?} ({:?})", order, outcome);
+
+        for instance in self.engines.values() {
+            if !instance.is_running() {
+                continue;
+            }
+
+            let stacking = StackingParams {
+                order: order.clone(),
+                outcome,
+            };
+            instance
+                .plugins
+                .read()
+                .with_plugin(|window: &WindowPlugin| window.notify_stacking_changed(stacking));
+        }
+    }
+
+    /// Start moving the attached window of |surface| along with the pointer,
+    /// keeping the point it is over under it, or stop doing so.
+    pub(super) fn set_attached_drag(&mut self, surface: &WlSurface, dragging: bool) {
+        let surface_id = surface.id();
+        if !dragging {
+            let is_dragged = self
+                .attached_drag
+                .as_ref()
+                .is_some_and(|drag| drag.surface_id == surface_id);
+            if is_dragged {
+                self.attached_drag = None;
+            }
+            return;
+        }
+
+        // The drag starts from a press on the window.
+        let Some((_, grab)) = self
+            .last_pointer_position
+            .clone()
+            .filter(|(pointer_surface_id, _)| *pointer_surface_id == surface_id)
+        else {
+            warn!("Not dragging the window: the pointer isn't over it");
+            return;
+        };
+
+        let Some(parent_id) = self
+            .find_window_by_surface_id(&surface_id)
+            .and_then(SctkFlutterWindow::attached_surface)
+            .map(|attached_surface| attached_surface.parent().id())
+        else {
+            return;
+        };
+
+        self.attached_drag = Some(AttachedDrag {
+            surface_id,
+            parent_id,
+            grab,
+        });
+    }
+
+    /// Move the attached window being dragged to the pointer |position| on
+    /// |surface_id|.
+    pub(super) fn drag_attached(&self, surface_id: &ObjectId, position: (f64, f64)) {
+        let Some(drag) = &self.attached_drag else {
+            return;
+        };
+
+        let is_over_parent = *surface_id == drag.parent_id;
+        if *surface_id != drag.surface_id && !is_over_parent {
+            return;
+        }
+
+        let grab = drag.grab;
+        let Some(attached_surface) = self
+            .find_window_by_surface_id(&drag.surface_id)
+            .and_then(SctkFlutterWindow::attached_surface)
+        else {
+            return;
+        };
+
+        let new_position =
+            attached::drag_position(attached_surface.position(), grab, position, is_over_parent);
+        attached_surface.set_position(new_position);
+    }
+}
@@ -0,0 +1,33 @@
+//! Tray icon of the application (see `crate::tray`).
+use flutter_plugins::tray::TrayPlugin;
+use tracing::error;
+
+use super::SctkApplicationState;
+use crate::tray::SctkTrayRequest;
+
+impl SctkApplicationState {
+    #[cfg(feature = "status-notifier")]
+    pub(super) fn handle_tray(&mut self, request: SctkTrayRequest) {
+        match request {
+            SctkTrayRequest::Show(icon) => self.tray.show(icon),
+            SctkTrayRequest::Hide => self.tray.hide(),
+            // Any engine may have shown the icon.
+            SctkTrayRequest::Event(event) => {
+                for instance in self.engines.values() {
+                    instance
+                        .plugins
+                        .read()
+                        .with_plugin(|tray: &TrayPlugin| tray.notify_event(event));
+                }
+                return;
+            }
+        }
+
+        let Some(task) = self.tray.request_sync() else {
+            return;
+        };
+        if let Err(err) = self.async_scheduler.schedule(task) {
+            error!("Failed to schedule tray task: {}", err);
+        }
+    }
+}
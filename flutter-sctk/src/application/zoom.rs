@@ -0,0 +1,82 @@
+//! Content zoom of the windows (see `crate::zoom`).
+use flutter_plugins::window::WindowPlugin;
+use tracing::trace;
+use wayland_backend::client::ObjectId;
+
+use super::SctkApplicationState;
+use crate::zoom::ZoomAction;
+
+impl SctkApplicationState {
+    /// Zoom the content of the window of |surface_id|, and notify its engine
+    /// if the zoom has changed.
+    pub(super) fn set_content_zoom(&mut self, surface_id: &ObjectId, zoom: f64) {
+        let Some(id) = self.find_engine_id_by_surface_id(surface_id) else {
+            return;
+        };
+        let instance = &self.engines[&id];
+        let Some(window) = instance.windows.get(surface_id) else {
+            return;
+        };
+
+        if !window.set_content_zoom(zoom) {
+            return;
+        }
+        trace!("Content zoom changed: {}", zoom);
+
+        // The zoom is applied to the initial metrics otherwise.
+        if instance.is_running() {
+            window.send_current_window_metrics();
+        }
+
+        // The toolbar would be misplaced relative to the zoomed content.
+        self.dismiss_text_toolbar();
+        self.update_content_scale();
+
+        self.with_surface_plugin(surface_id, |window: &WindowPlugin| {
+            window.notify_zoom_changed(zoom)
+        });
+    }
+
+    /// Handle a zoom shortcut (i.e.: Ctrl+scroll or Ctrl+plus/minus/0) if
+    /// content zoom is enabled, by zooming the window of |surface_id|.
+    /// Returns whether |action| has been handled.
+    pub(super) fn zoom_content(
+        &mut self,
+        surface_id: Option<ObjectId>,
+        action: Option<ZoomAction>,
+    ) -> bool {
+        let Some(action) = action.filter(|_| self.is_content_zoom_shortcut()) else {
+            return false;
+        };
+
+        let window = surface_id
+            .as_ref()
+            .and_then(|surface_id| self.find_window_by_surface_id(surface_id));
+        if let Some(window) = window {
+            let zoom = action.apply(window.content_zoom());
+            let surface_id = window.wl_surface_id();
+            self.set_content_zoom(&surface_id, zoom);
+        }
+        true
+    }
+
+    pub(super) fn is_content_zoom_shortcut(&self) -> bool {
+        self.is_content_zoom_enabled
+            && self.modifiers.ctrl
+            && !self.modifiers.alt
+            && !self.modifiers.logo
+    }
+
+    /// Convert the rects received from Dart (e.g.: the caret position) to
+    /// surface-local coordinates, once the content scale has changed.
+    pub(super) fn update_content_scale(&mut self) {
+        let Some(window) = self.focused().implicit_window() else {
+            return;
+        };
+
+        let content_scale = window.content_scale();
+        if let Some(text_input) = &mut self.text_input {
+            text_input.set_content_scale(content_scale);
+        }
+    }
+}
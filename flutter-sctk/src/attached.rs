@@ -0,0 +1,155 @@
+//! Utility windows attached to the window of another engine (see
+//! `WindowRole::Utility`), e.g.: tool palettes.
+//!
+//! They are subsurfaces of the parent window, so they stay above it and move
+//! along with it, but they don't have decorations and can't get the keyboard
+//! focus. Being desynchronized, they present their frames independently from
+//! the parent, whose commits only apply their position and stacking.
+//!
+//! Subsurfaces aren't configured by the compositor, so the application
+//! configures them itself (see `SctkApplicationState::configure_attached`).
+use std::sync::Mutex;
+
+use dpi::LogicalPosition;
+use wayland_client::{
+    protocol::{
+        wl_subcompositor::WlSubcompositor, wl_subsurface::WlSubsurface, wl_surface::WlSurface,
+    },
+    QueueHandle,
+};
+
+use crate::application::SctkApplicationState;
+
+/// Where to attach the window of a new engine.
+pub(crate) struct SctkAttachment {
+    pub(crate) subcompositor: WlSubcompositor,
+    pub(crate) parent: WlSurface,
+    /// Position of the window relative to the parent surface.
+    pub(crate) position: LogicalPosition<i32>,
+}
+
+/// The surface of an attached window, along with its subsurface role.
+pub(crate) struct SctkAttachedSurface {
+    surface: WlSurface,
+    subsurface: WlSubsurface,
+    parent: WlSurface,
+    position: Mutex<LogicalPosition<i32>>,
+}
+
+impl SctkAttachedSurface {
+    pub(crate) fn new(
+        attachment: SctkAttachment,
+        surface: WlSurface,
+        qh: &QueueHandle<SctkApplicationState>,
+    ) -> Self {
+        let SctkAttachment {
+            subcompositor,
+            parent,
+            position,
+        } = attachment;
+
+        let subsurface = subcompositor.get_subsurface(&surface, &parent, qh, ());
+        subsurface.set_desync();
+        subsurface.set_position(position.x, position.y);
+        parent.commit();
+
+        Self {
+            surface,
+            subsurface,
+            parent,
+            position: Mutex::new(position),
+        }
+    }
+
+    pub(crate) fn wl_surface(&self) -> &WlSurface {
+        &self.surface
+    }
+
+    pub(crate) fn parent(&self) -> &WlSurface {
+        &self.parent
+    }
+
+    pub(crate) fn position(&self) -> LogicalPosition<i32> {
+        *self.position.lock().unwrap()
+    }
+
+    /// Move the window relative to its parent, which is committed so that the
+    /// new position applies right away.
+    pub(crate) fn set_position(&self, position: LogicalPosition<i32>) {
+        let mut current_position = self.position.lock().unwrap();
+        if *current_position == position {
+            return;
+        }
+
+        *current_position = position;
+        self.subsurface.set_position(position.x, position.y);
+        self.parent.commit();
+    }
+
+    /// Stack the window above (or below, unless |above|) |sibling|, a
+    /// surface attached to the same parent.
+    pub(crate) fn place(&self, sibling: &WlSurface, above: bool) {
+        if above {
+            self.subsurface.place_above(sibling);
+        } else {
+            self.subsurface.place_below(sibling);
+        }
+        self.parent.commit();
+    }
+}
+
+impl Drop for SctkAttachedSurface {
+    fn drop(&mut self) {
+        self.subsurface.destroy();
+        self.surface.destroy();
+        // Unmap the window right away, rather than on the next frame of the
+        // parent.
+        self.parent.commit();
+    }
+}
+
+/// Position of an attached window being dragged, so that the |grab| point of
+/// the window (relative to it) follows the |pointer|. The pointer position is
+/// relative to the window itself, unless |is_over_parent|, in which case it
+/// is relative to the parent (e.g.: when the pointer moved faster than the
+/// window).
+pub(crate) fn drag_position(
+    position: LogicalPosition<i32>,
+    grab: (f64, f64),
+    pointer: (f64, f64),
+    is_over_parent: bool,
+) -> LogicalPosition<i32> {
+    let origin = if is_over_parent {
+        (0.0, 0.0)
+    } else {
+        (position.x as f64, position.y as f64)
+    };
+
+    LogicalPosition::new(
+        (origin.0 + pointer.0 - grab.0).round() as i32,
+        (origin.1 + pointer.1 - grab.1).round() as i32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use dpi::LogicalPosition;
+
+    use super::drag_position;
+
+    #[test]
+    fn dragged_windows_follow_the_pointer() {
+        let position = LogicalPosition::new(100, 50);
+        let grab = (10.0, 5.0);
+
+        assert_eq!(drag_position(position, grab, grab, false), position);
+        assert_eq!(
+            drag_position(position, grab, (30.0, 5.0), false),
+            LogicalPosition::new(120, 50)
+        );
+        assert_eq!(
+            drag_position(position, grab, (80.5, 40.0), true),
+            LogicalPosition::new(71, 35)
+        );
+    }
+}
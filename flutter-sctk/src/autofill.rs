@@ -0,0 +1,191 @@
+//! Reference autofill integration using the freedesktop Secret Service API
+//! (e.g.: GNOME Keyring, KWallet, or KeePassXC with its Secret Service
+//! integration enabled).
+//!
+//! When a username or password field gets the focus, the unlocked items
+//! matching the configured attributes are looked up. The first match is
+//! offered to the application, which decides whether the field gets filled
+//! (e.g.: depending on a user setting). Accepted offers are inserted through
+//! the regular editing state updates.
+use std::{cell::Cell, collections::HashMap, fmt, rc::Rc};
+
+use ashpd::zbus::{
+    self,
+    zvariant::{OwnedObjectPath, OwnedValue, Value},
+};
+use flutter_engine::plugins::PluginRegistrar;
+use flutter_plugins::textinput::{TextInputFocusInfo, TextInputPlugin};
+use parking_lot::RwLock;
+use smithay_client_toolkit::reexports::calloop::futures::Scheduler;
+use tracing::warn;
+
+use crate::handler::SctkAsyncResult;
+
+const SERVICE_NAME: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+
+/// Item attribute holding the username, as used by most password managers.
+const USERNAME_ATTRIBUTE: &str = "username";
+
+/// Credentials found for the focused text field.
+#[derive(Clone)]
+pub struct AutofillOffer {
+    /// Label of the matching Secret Service item.
+    pub label: String,
+    pub username: Option<String>,
+    /// Whether the focused field expects the password (or the username
+    /// otherwise).
+    pub is_password: bool,
+    secret: String,
+}
+
+impl fmt::Debug for AutofillOffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutofillOffer")
+            .field("label", &self.label)
+            .field("username", &self.username)
+            .field("is_password", &self.is_password)
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct SecretServiceAutofill {
+    attributes: HashMap<String, String>,
+    on_offer: Box<dyn Fn(&AutofillOffer) -> bool>,
+}
+
+impl SecretServiceAutofill {
+    /// Look up the items matching |attributes| (e.g.: `{"service": "my-app"}`).
+    /// The field is only filled when |on_offer| returns `true`.
+    pub fn new(
+        attributes: HashMap<String, String>,
+        on_offer: impl Fn(&AutofillOffer) -> bool + 'static,
+    ) -> Self {
+        Self {
+            attributes,
+            on_offer: Box::new(on_offer),
+        }
+    }
+
+    /// Returns the focus observer of the text input plugin which looks up
+    /// credentials whenever a username or password field gets the focus.
+    pub(crate) fn into_focus_observer(
+        self,
+        scheduler: Rc<Scheduler<SctkAsyncResult>>,
+        plugins: Rc<RwLock<PluginRegistrar>>,
+    ) -> impl Fn(TextInputFocusInfo) {
+        let autofill = Rc::new(self);
+        let last_client_id = Cell::new(None);
+
+        move |info: TextInputFocusInfo| {
+            // The observer is also called when the field geometry changes.
+            if last_client_id.replace(info.client_id) == info.client_id {
+                return;
+            }
+
+            let Some(client_id) = info.client_id else {
+                return;
+            };
+
+            if !info.is_password() && !info.is_username() {
+                return;
+            }
+
+            let task = autofill
+                .clone()
+                .offer(plugins.clone(), client_id, info.is_password());
+            if scheduler.schedule(task).is_err() {
+                warn!("[autofill] Ignoring focused field because the executor is gone");
+            }
+        }
+    }
+
+    async fn offer(
+        self: Rc<Self>,
+        plugins: Rc<RwLock<PluginRegistrar>>,
+        client_id: i64,
+        is_password: bool,
+    ) -> SctkAsyncResult {
+        let offer = match self.lookup(is_password).await {
+            Ok(Some(offer)) => offer,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                warn!("[autofill] Unable to look up Secret Service items: {}", err);
+                return Ok(());
+            }
+        };
+
+        if !(self.on_offer)(&offer) {
+            return Ok(());
+        }
+
+        let text = if is_password {
+            offer.secret
+        } else {
+            let Some(username) = offer.username else {
+                return Ok(());
+            };
+            username
+        };
+
+        plugins
+            .write()
+            .with_plugin_mut(|text_input: &mut TextInputPlugin| {
+                // The focus may have moved while the lookup was in progress.
+                if text_input.focus_info().client_id != Some(client_id) {
+                    return;
+                }
+
+                text_input.with_state(|state| {
+                    state.select_all();
+                    state.add_characters(&text);
+                });
+                text_input.notify_changes();
+            });
+
+        Ok(())
+    }
+
+    async fn lookup(&self, is_password: bool) -> zbus::Result<Option<AutofillOffer>> {
+        let connection = zbus::Connection::session().await?;
+        let service =
+            zbus::Proxy::new(&connection, SERVICE_NAME, SERVICE_PATH, SERVICE_INTERFACE).await?;
+
+        // Note: The `plain` algorithm transfers the secrets unencrypted over
+        // the session bus. The session is closed along with the connection.
+        let (_output, session): (OwnedValue, OwnedObjectPath) =
+            service.call("OpenSession", &("plain", Value::from(""))).await?;
+
+        let (unlocked, _locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) =
+            service.call("SearchItems", &self.attributes).await?;
+
+        let Some(path) = unlocked.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let item = zbus::Proxy::new(&connection, SERVICE_NAME, path, ITEM_INTERFACE).await?;
+        let label: String = item.get_property("Label").await?;
+        let attributes: HashMap<String, String> = item.get_property("Attributes").await?;
+
+        let (_session, _parameters, value, _content_type): (
+            OwnedObjectPath,
+            Vec<u8>,
+            Vec<u8>,
+            String,
+        ) = item.call("GetSecret", &session).await?;
+
+        let Ok(secret) = String::from_utf8(value) else {
+            warn!("[autofill] Ignoring non UTF-8 secret of item `{}`", label);
+            return Ok(None);
+        };
+
+        Ok(Some(AutofillOffer {
+            label,
+            username: attributes.get(USERNAME_ATTRIBUTE).cloned(),
+            is_password,
+            secret,
+        }))
+    }
+}
@@ -0,0 +1,417 @@
+//! Color management of the windows, using the `wp_color_management_v1`
+//! protocol.
+//!
+//! The content of the windows is sRGB (see
+//! `flutter_glutin::builder::window_surface_attributes`), which is declared
+//! to the compositor with an sRGB image description. The compositor then
+//! converts it for the wide gamut outputs, instead of showing the sRGB values
+//! as they are (i.e.: oversaturated).
+//!
+//! The image descriptions of the outputs tell whether the windows are shown
+//! on wide gamut displays (see `ColorInfo`). Wayland doesn't give clients the
+//! EDID of the outputs, so nothing is known about them without the protocol.
+use std::{collections::HashMap, sync::Mutex};
+
+use flutter_plugins::color::{ColorInfo, Primaries};
+use smithay_client_toolkit::reexports::protocols::wp::color_management::v1::client::{
+    wp_color_management_output_v1::{self, WpColorManagementOutputV1},
+    wp_color_management_surface_v1::WpColorManagementSurfaceV1,
+    wp_color_manager_v1::{self, Feature, RenderIntent, TransferFunction, WpColorManagerV1},
+    wp_image_description_info_v1,
+    wp_image_description_v1::{self, WpImageDescriptionV1},
+};
+use tracing::{debug, trace, warn};
+use wayland_backend::client::ObjectId;
+use wayland_client::{
+    globals::GlobalList,
+    protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    Proxy, QueueHandle, WEnum,
+};
+
+use crate::application::SctkApplicationState;
+
+pub(crate) struct SctkColorManagement {
+    manager: WpColorManagerV1,
+    /// Advertised by the compositor when the manager is bound.
+    supported: SupportedFeatures,
+    srgb_description: SrgbDescription,
+    /// Color management of the window surfaces, by surface.
+    surfaces: HashMap<ObjectId, WpColorManagementSurfaceV1>,
+    outputs: HashMap<ObjectId, OutputColor>,
+}
+
+#[derive(Debug, Default)]
+struct SupportedFeatures {
+    parametric: bool,
+    perceptual: bool,
+    srgb_primaries: bool,
+    /// The one used by the sRGB image description, if any is supported.
+    transfer_function: Option<TransferFunction>,
+}
+
+impl SupportedFeatures {
+    fn add_transfer_function(&mut self, transfer_function: TransferFunction) {
+        // sRGB displays are closer to a pure 2.2 gamma than to the piecewise
+        // sRGB function, which is deprecated by the version 2.
+        match transfer_function {
+            TransferFunction::Gamma22 => self.transfer_function = Some(transfer_function),
+            TransferFunction::Srgb => {
+                self.transfer_function.get_or_insert(transfer_function);
+            }
+            _ => {}
+        }
+    }
+}
+
+enum SrgbDescription {
+    /// Until the compositor has advertised what it supports.
+    Unknown,
+    Unsupported,
+    Pending(WpImageDescriptionV1),
+    Ready(WpImageDescriptionV1),
+}
+
+struct OutputColor {
+    color_output: WpColorManagementOutputV1,
+    /// Unknown until the information of the image description is received.
+    color_info: ColorInfo,
+}
+
+/// What an image description describes.
+#[derive(Debug, Clone)]
+pub(crate) enum SctkImageDescriptionData {
+    Srgb,
+    Output(ObjectId),
+}
+
+/// Information of the image description of an output, received until `done`.
+#[derive(Debug)]
+pub(crate) struct SctkImageDescriptionInfoData {
+    output_id: ObjectId,
+    primaries: Mutex<Option<Primaries>>,
+    /// Primaries of the display itself, which may be narrower than the color
+    /// space the output expects.
+    target_primaries: Mutex<Option<Primaries>>,
+}
+
+impl SctkColorManagement {
+    pub(crate) fn bind(
+        globals: &GlobalList,
+        qh: &QueueHandle<SctkApplicationState>,
+    ) -> Option<Self> {
+        let manager = globals.bind(qh, 1..=2, ()).ok()?;
+        Some(Self {
+            manager,
+            supported: SupportedFeatures::default(),
+            srgb_description: SrgbDescription::Unknown,
+            surfaces: HashMap::new(),
+            outputs: HashMap::new(),
+        })
+    }
+
+    pub(crate) fn manager_event(
+        &mut self,
+        event: wp_color_manager_v1::Event,
+        qh: &QueueHandle<SctkApplicationState>,
+    ) {
+        match event {
+            wp_color_manager_v1::Event::SupportedIntent {
+                render_intent: WEnum::Value(RenderIntent::Perceptual),
+            } => self.supported.perceptual = true,
+            wp_color_manager_v1::Event::SupportedFeature {
+                feature: WEnum::Value(Feature::Parametric),
+            } => self.supported.parametric = true,
+            wp_color_manager_v1::Event::SupportedTfNamed {
+                tf: WEnum::Value(transfer_function),
+            } => self.supported.add_transfer_function(transfer_function),
+            wp_color_manager_v1::Event::SupportedPrimariesNamed {
+                primaries: WEnum::Value(wp_color_manager_v1::Primaries::Srgb),
+            } => self.supported.srgb_primaries = true,
+            wp_color_manager_v1::Event::Done => self.create_srgb_description(qh),
+            _ => {}
+        }
+    }
+
+    fn create_srgb_description(&mut self, qh: &QueueHandle<SctkApplicationState>) {
+        if !matches!(self.srgb_description, SrgbDescription::Unknown) {
+            return;
+        }
+
+        let supported = &self.supported;
+        let transfer_function = match supported.transfer_function {
+            Some(transfer_function)
+                if supported.parametric && supported.perceptual && supported.srgb_primaries =>
+            {
+                transfer_function
+            }
+            _ => {
+                debug!("sRGB image descriptions are not supported by the compositor");
+                self.srgb_description = SrgbDescription::Unsupported;
+                return;
+            }
+        };
+
+        let creator = self.manager.create_parametric_creator(qh, ());
+        creator.set_primaries_named(wp_color_manager_v1::Primaries::Srgb);
+        creator.set_tf_named(transfer_function);
+        let description = creator.create(qh, SctkImageDescriptionData::Srgb);
+        self.srgb_description = SrgbDescription::Pending(description);
+    }
+
+    /// Handle an event of |description|. Returns whether the sRGB image
+    /// description is now ready to be set on the surfaces.
+    pub(crate) fn image_description_event(
+        &mut self,
+        description: &WpImageDescriptionV1,
+        event: wp_image_description_v1::Event,
+        data: &SctkImageDescriptionData,
+        qh: &QueueHandle<SctkApplicationState>,
+    ) -> bool {
+        let is_ready = match event {
+            wp_image_description_v1::Event::Ready { .. }
+            | wp_image_description_v1::Event::Ready2 { .. } => true,
+            wp_image_description_v1::Event::Failed { cause, msg } => {
+                warn!("Failed to create the {data:?} image description ({cause:?}): {msg}");
+                false
+            }
+            _ => return false,
+        };
+
+        match data {
+            SctkImageDescriptionData::Srgb => {
+                let SrgbDescription::Pending(pending) = &self.srgb_description else {
+                    return false;
+                };
+                if pending != description {
+                    return false;
+                }
+
+                self.srgb_description = if is_ready {
+                    SrgbDescription::Ready(description.clone())
+                } else {
+                    description.destroy();
+                    SrgbDescription::Unsupported
+                };
+                is_ready
+            }
+            SctkImageDescriptionData::Output(output_id) => {
+                // The information outlives the description, which is only
+                // needed once.
+                if is_ready && self.outputs.contains_key(output_id) {
+                    description
+                        .get_information(qh, SctkImageDescriptionInfoData::new(output_id.clone()));
+                }
+                description.destroy();
+                false
+            }
+        }
+    }
+
+    /// Handle an event of the information of an output image description.
+    /// Returns whether the color info of the output has changed.
+    pub(crate) fn image_description_info_event(
+        &mut self,
+        event: wp_image_description_info_v1::Event,
+        data: &SctkImageDescriptionInfoData,
+    ) -> bool {
+        match event {
+            wp_image_description_info_v1::Event::Primaries {
+                r_x,
+                r_y,
+                g_x,
+                g_y,
+                b_x,
+                b_y,
+                ..
+            } => {
+                let primaries = scaled_primaries([r_x, r_y, g_x, g_y, b_x, b_y]);
+                *data.primaries.lock().unwrap() = Some(primaries);
+            }
+            wp_image_description_info_v1::Event::PrimariesNamed {
+                primaries: WEnum::Value(named),
+            } => {
+                let mut primaries = data.primaries.lock().unwrap();
+                if primaries.is_none() {
+                    *primaries = named_primaries(named);
+                }
+            }
+            wp_image_description_info_v1::Event::TargetPrimaries {
+                r_x,
+                r_y,
+                g_x,
+                g_y,
+                b_x,
+                b_y,
+                ..
+            } => {
+                let primaries = scaled_primaries([r_x, r_y, g_x, g_y, b_x, b_y]);
+                *data.target_primaries.lock().unwrap() = Some(primaries);
+            }
+            wp_image_description_info_v1::Event::Done => {
+                let primaries = data
+                    .target_primaries
+                    .lock()
+                    .unwrap()
+                    .or(*data.primaries.lock().unwrap());
+                let Some(output) = self.outputs.get_mut(&data.output_id) else {
+                    return false;
+                };
+
+                // E.g.: outputs described by an ICC profile.
+                let color_info = primaries
+                    .as_ref()
+                    .map(ColorInfo::from_primaries)
+                    .unwrap_or_default();
+                trace!("[{}] color info: {:?}", data.output_id, color_info);
+                if output.color_info != color_info {
+                    output.color_info = color_info;
+                    return true;
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    pub(crate) fn add_output(&mut self, output: &WlOutput, qh: &QueueHandle<SctkApplicationState>) {
+        let color_output = self.manager.get_output(output, qh, output.id());
+        request_output_description(&color_output, output.id(), qh);
+        self.outputs.insert(
+            output.id(),
+            OutputColor {
+                color_output,
+                color_info: ColorInfo::default(),
+            },
+        );
+    }
+
+    /// Returns whether the output had a color info.
+    pub(crate) fn remove_output(&mut self, output: &WlOutput) -> bool {
+        let Some(output) = self.outputs.remove(&output.id()) else {
+            return false;
+        };
+
+        output.color_output.destroy();
+        output.color_info != ColorInfo::default()
+    }
+
+    pub(crate) fn output_event(
+        &mut self,
+        color_output: &WpColorManagementOutputV1,
+        event: wp_color_management_output_v1::Event,
+        output_id: &ObjectId,
+        qh: &QueueHandle<SctkApplicationState>,
+    ) {
+        if let wp_color_management_output_v1::Event::ImageDescriptionChanged = event {
+            request_output_description(color_output, output_id.clone(), qh);
+        }
+    }
+
+    /// Declare the content of |surface| as sRGB, which is applied with the
+    /// next frame. Ignored until the sRGB image description is ready.
+    pub(crate) fn set_srgb(&mut self, surface: &WlSurface, qh: &QueueHandle<SctkApplicationState>) {
+        let SrgbDescription::Ready(description) = &self.srgb_description else {
+            return;
+        };
+        if self.surfaces.contains_key(&surface.id()) {
+            return;
+        }
+
+        let color_surface = self.manager.get_surface(surface, qh, ());
+        color_surface.set_image_description(description, RenderIntent::Perceptual);
+        self.surfaces.insert(surface.id(), color_surface);
+    }
+
+    pub(crate) fn remove_surface(&mut self, surface_id: &ObjectId) {
+        if let Some(color_surface) = self.surfaces.remove(surface_id) {
+            color_surface.destroy();
+        }
+    }
+
+    /// Color info of a window shown on |outputs|.
+    pub(crate) fn color_info(&self, outputs: &[WlOutput]) -> ColorInfo {
+        ColorInfo::widest(
+            outputs
+                .iter()
+                .filter_map(|output| self.outputs.get(&output.id()))
+                .map(|output| output.color_info),
+        )
+    }
+}
+
+impl SctkImageDescriptionInfoData {
+    fn new(output_id: ObjectId) -> Self {
+        Self {
+            output_id,
+            primaries: Mutex::new(None),
+            target_primaries: Mutex::new(None),
+        }
+    }
+}
+
+fn request_output_description(
+    color_output: &WpColorManagementOutputV1,
+    output_id: ObjectId,
+    qh: &QueueHandle<SctkApplicationState>,
+) {
+    color_output.get_image_description(qh, SctkImageDescriptionData::Output(output_id));
+}
+
+/// The chromaticities are sent multiplied by a million.
+fn scaled_primaries([r_x, r_y, g_x, g_y, b_x, b_y]: [i32; 6]) -> Primaries {
+    let scale = |value: i32| f64::from(value) / 1_000_000.0;
+    Primaries::new(
+        (scale(r_x), scale(r_y)),
+        (scale(g_x), scale(g_y)),
+        (scale(b_x), scale(b_y)),
+    )
+}
+
+fn named_primaries(primaries: wp_color_manager_v1::Primaries) -> Option<Primaries> {
+    use wp_color_manager_v1::Primaries as Named;
+
+    match primaries {
+        Named::Srgb => Some(Primaries::SRGB),
+        Named::PalM => Some(Primaries::new((0.67, 0.33), (0.21, 0.71), (0.14, 0.08))),
+        Named::Pal => Some(Primaries::new((0.64, 0.33), (0.29, 0.60), (0.15, 0.06))),
+        Named::Ntsc => Some(Primaries::new((0.63, 0.34), (0.31, 0.595), (0.155, 0.07))),
+        Named::GenericFilm => Some(Primaries::new(
+            (0.681, 0.319),
+            (0.243, 0.692),
+            (0.145, 0.049),
+        )),
+        Named::Bt2020 => Some(Primaries::BT2020),
+        Named::Cie1931Xyz => Some(Primaries::new((1.0, 0.0), (0.0, 1.0), (0.0, 0.0))),
+        Named::DciP3 | Named::DisplayP3 => Some(Primaries::DISPLAY_P3),
+        Named::AdobeRgb => Some(Primaries::ADOBE_RGB),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_plugins::color::{ColorGamut, ColorInfo, Primaries};
+
+    use super::{scaled_primaries, SupportedFeatures, TransferFunction};
+
+    #[test]
+    fn scales_the_primaries() {
+        let primaries = scaled_primaries([680_000, 320_000, 265_000, 690_000, 150_000, 60_000]);
+        assert_eq!(primaries, Primaries::DISPLAY_P3);
+        assert_eq!(
+            ColorInfo::from_primaries(&primaries).approx_gamut,
+            ColorGamut::DisplayP3
+        );
+    }
+
+    #[test]
+    fn prefers_the_gamma_22_transfer_function() {
+        let mut supported = SupportedFeatures::default();
+        supported.add_transfer_function(TransferFunction::St2084Pq);
+        assert_eq!(supported.transfer_function, None);
+
+        supported.add_transfer_function(TransferFunction::Gamma22);
+        supported.add_transfer_function(TransferFunction::Srgb);
+        assert_eq!(supported.transfer_function, Some(TransferFunction::Gamma22));
+    }
+}
@@ -0,0 +1,228 @@
+//! Partial repaint of the window surface (only used by the OpenGL render
+//! path, the compositor path always repaints the whole backing stores).
+//!
+//! The damage of the last frames is recorded so that the engine can bring the
+//! back buffer up to date given its age (`EGL_EXT_buffer_age`), instead of
+//! repainting it entirely. Frames are then presented with their damage
+//! (`EGL_KHR_swap_buffers_with_damage`), so that the compositor only updates
+//! what actually changed.
+//!
+//! For debugging, the partial repaints can be disabled with the
+//! `FLUTTER_RS_FULL_DAMAGE` environment variable (or at runtime, see
+//! `SctkFlutterWindow::set_full_damage`), which tells whether an artifact
+//! comes from the damage tracking.
+use std::{collections::VecDeque, env};
+
+use dpi::PhysicalSize;
+use flutter_engine_api::DamageRect;
+use glutin::surface::Rect;
+use tracing::debug;
+
+/// Number of frames whose damage is recorded. Older buffers are repainted
+/// entirely, but swap chains rarely use more than 3 buffers.
+const MAX_RECORDED_FRAMES: usize = 4;
+
+/// Number of frames between the damage statistics logs.
+const STATS_INTERVAL: u64 = 600;
+
+pub(crate) const FULL_DAMAGE_ENV_VAR: &str = "FLUTTER_RS_FULL_DAMAGE";
+
+/// Whether the full damage is forced by the environment (i.e.: set to
+/// anything but `0`).
+pub(crate) fn is_full_damage_forced_by_env() -> bool {
+    env::var_os(FULL_DAMAGE_ENV_VAR).is_some_and(|value| value != "0")
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SctkDamageHistory {
+    /// Damage of the last presented frames, most recent first.
+    frames: VecDeque<DamageRect>,
+    size: PhysicalSize<u32>,
+    stats: DamageStats,
+    is_full_damage_forced: bool,
+}
+
+impl SctkDamageHistory {
+    /// Repaint and present the whole frames, regardless of their damage.
+    pub(crate) fn set_full_damage_forced(&mut self, forced: bool) {
+        self.is_full_damage_forced = forced;
+    }
+
+    pub(crate) fn is_full_damage_forced(&self) -> bool {
+        self.is_full_damage_forced
+    }
+
+    /// The damage a back buffer of |size| accumulated over the |age| - 1
+    /// frames presented after it. Returns `None` if it is unknown, or when
+    /// the full damage is forced (i.e.: the buffer is repainted entirely).
+    pub(crate) fn existing_damage(&self, age: u32, size: PhysicalSize<u32>) -> Option<DamageRect> {
+        if self.is_full_damage_forced {
+            return None;
+        }
+
+        let age = age as usize;
+        if age == 0 || age > self.frames.len() + 1 || size != self.size {
+            return None;
+        }
+
+        Some(
+            self.frames
+                .iter()
+                .take(age - 1)
+                .fold(DamageRect::default(), |damage, frame| damage.union(frame)),
+        )
+    }
+
+    /// Record the damage of a frame of |size| which is being presented.
+    pub(crate) fn push(&mut self, size: PhysicalSize<u32>, frame_damage: &[DamageRect]) {
+        if size != self.size {
+            self.frames.clear();
+            self.size = size;
+        }
+
+        // Without damage information, the whole frame is assumed to change.
+        let full_frame = DamageRect::new(0.0, 0.0, size.width as f64, size.height as f64);
+        let damage = if frame_damage.is_empty() {
+            full_frame
+        } else {
+            frame_damage
+                .iter()
+                .fold(DamageRect::default(), |damage, rect| damage.union(rect))
+        };
+
+        self.frames.push_front(damage);
+        self.frames.truncate(MAX_RECORDED_FRAMES);
+        self.stats.record(size, frame_damage);
+    }
+}
+
+/// Share of the presented pixels which were damaged, for measuring what the
+/// partial presents save. Logged with the `debug` level.
+#[derive(Debug, Default)]
+struct DamageStats {
+    frames: u64,
+    damaged_pixels: u64,
+    total_pixels: u64,
+}
+
+impl DamageStats {
+    fn record(&mut self, size: PhysicalSize<u32>, frame_damage: &[DamageRect]) {
+        let total_pixels = size.width as u64 * size.height as u64;
+        let damaged_pixels = if frame_damage.is_empty() {
+            total_pixels
+        } else {
+            frame_damage
+                .iter()
+                .map(|rect| ((rect.right - rect.left) * (rect.bottom - rect.top)).max(0.0) as u64)
+                .sum::<u64>()
+                .min(total_pixels)
+        };
+
+        self.frames += 1;
+        self.damaged_pixels += damaged_pixels;
+        self.total_pixels += total_pixels;
+
+        if self.frames.is_multiple_of(STATS_INTERVAL) && self.total_pixels > 0 {
+            debug!(
+                "{} frames presented, {:.1}% of their pixels were damaged",
+                self.frames,
+                100.0 * self.damaged_pixels as f64 / self.total_pixels as f64
+            );
+        }
+    }
+}
+
+/// Convert the |damage| of a frame whose height is |height| to the rectangles
+/// expected by EGL, whose origin is the bottom-left corner. The rectangles
+/// are rounded outwards.
+pub(crate) fn to_egl_rects(damage: &[DamageRect], height: u32) -> Vec<Rect> {
+    damage
+        .iter()
+        .filter(|rect| !rect.is_empty())
+        .map(|rect| {
+            let left = rect.left.floor() as i32;
+            let top = rect.top.floor() as i32;
+            let right = rect.right.ceil() as i32;
+            let bottom = rect.bottom.ceil() as i32;
+            Rect::new(left, height as i32 - bottom, right - left, bottom - top)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use dpi::PhysicalSize;
+    use flutter_engine_api::DamageRect;
+    use glutin::surface::Rect;
+
+    use super::{to_egl_rects, SctkDamageHistory};
+
+    #[test]
+    fn existing_damage_depends_on_buffer_age() {
+        let size = PhysicalSize::new(800, 600);
+        let mut history = SctkDamageHistory::default();
+
+        // Nothing was presented yet.
+        assert_eq!(history.existing_damage(1, size), None);
+
+        history.push(size, &[]);
+        history.push(size, &[DamageRect::new(10.0, 10.0, 20.0, 20.0)]);
+        history.push(size, &[DamageRect::new(100.0, 50.0, 110.0, 60.0)]);
+
+        // The previous buffer is up to date.
+        assert_eq!(history.existing_damage(1, size), Some(DamageRect::default()));
+        assert_eq!(
+            history.existing_damage(2, size),
+            Some(DamageRect::new(100.0, 50.0, 110.0, 60.0))
+        );
+        assert_eq!(
+            history.existing_damage(3, size),
+            Some(DamageRect::new(10.0, 10.0, 110.0, 60.0))
+        );
+        // The first frame was fully damaged.
+        assert_eq!(
+            history.existing_damage(4, size),
+            Some(DamageRect::new(0.0, 0.0, 800.0, 600.0))
+        );
+        assert_eq!(history.existing_damage(5, size), None);
+        assert_eq!(history.existing_damage(0, size), None);
+
+        // Resizing invalidates the buffers.
+        let size = PhysicalSize::new(1024, 768);
+        assert_eq!(history.existing_damage(2, size), None);
+        history.push(size, &[DamageRect::new(0.0, 0.0, 1.0, 1.0)]);
+        assert_eq!(history.existing_damage(3, size), None);
+    }
+
+    #[test]
+    fn forced_full_damage_repaints_entirely() {
+        let size = PhysicalSize::new(800, 600);
+        let mut history = SctkDamageHistory::default();
+        history.push(size, &[DamageRect::new(10.0, 10.0, 20.0, 20.0)]);
+        history.push(size, &[DamageRect::new(10.0, 10.0, 20.0, 20.0)]);
+
+        history.set_full_damage_forced(true);
+        assert_eq!(history.existing_damage(1, size), None);
+        assert_eq!(history.existing_damage(2, size), None);
+
+        // The history is kept up to date in the meantime.
+        history.push(size, &[]);
+        history.set_full_damage_forced(false);
+        assert_eq!(
+            history.existing_damage(2, size),
+            Some(DamageRect::new(0.0, 0.0, 800.0, 600.0))
+        );
+    }
+
+    #[test]
+    fn egl_rects_start_from_the_bottom() {
+        let rects = to_egl_rects(
+            &[
+                DamageRect::new(10.5, 20.0, 30.0, 40.2),
+                DamageRect::new(5.0, 5.0, 5.0, 10.0),
+            ],
+            100,
+        );
+        assert_eq!(rects, [Rect::new(10, 59, 20, 21)]);
+    }
+}
@@ -1,7 +1,12 @@
 use std::{num::NonZeroU32, ptr::NonNull};
 
 use dpi::PhysicalSize;
-use flutter_glutin::builder::{ContextBuildError, ContextBuilder, FlutterEGLContext};
+use flutter_glutin::{
+    builder::{ContextBuildError, ContextBuilder, FlutterEGLContext},
+    context::{Context, SecondarySurface},
+    device::DevicePreference,
+};
+use flutter_runner_api::GpuPreference;
 use glutin::surface::SwapInterval;
 use raw_window_handle::{
     RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
@@ -13,6 +18,9 @@ pub(crate) trait FlutterEGLContextWaylandExt {
     fn new_wayland_context(
         surface: &wl_surface::WlSurface,
         size: PhysicalSize<u32>,
+        gpu_preference: &GpuPreference,
+        robust: bool,
+        debug_output: bool,
     ) -> Result<FlutterEGLContext, CreateWaylandContextError>;
 }
 
@@ -20,6 +28,9 @@ impl FlutterEGLContextWaylandExt for FlutterEGLContext {
     fn new_wayland_context(
         surface: &wl_surface::WlSurface,
         size: PhysicalSize<u32>,
+        gpu_preference: &GpuPreference,
+        robust: bool,
+        debug_output: bool,
     ) -> Result<FlutterEGLContext, CreateWaylandContextError> {
         let display = NonNull::new(
             surface
@@ -33,22 +44,64 @@ impl FlutterEGLContextWaylandExt for FlutterEGLContext {
         let wl_display_handle = WaylandDisplayHandle::new(display);
         let raw_display_handle = RawDisplayHandle::Wayland(wl_display_handle);
 
-        let surface = NonNull::new(surface.id().as_ptr().cast())
-            .ok_or(CreateWaylandContextError::ConnectionClosed)?;
-        let wl_window_handle = WaylandWindowHandle::new(surface);
-        let raw_window_handle = RawWindowHandle::Wayland(wl_window_handle);
-
         let (context, resource_context) = ContextBuilder::new()
             .with_raw_display_handle(raw_display_handle)
-            .with_raw_window_handle(raw_window_handle)
+            .with_raw_window_handle(wayland_window_handle(surface)?)
             .with_swap_interval(SwapInterval::DontWait)
             .with_size(size.non_zero())
+            .with_device_preference(gpu_preference.to_device_preference())
+            .with_robustness(robust)
+            .with_debug_output(debug_output)
             .build()?;
 
         Ok((context, resource_context))
     }
 }
 
+pub(crate) trait GpuPreferenceExt {
+    fn to_device_preference(&self) -> DevicePreference;
+}
+
+impl GpuPreferenceExt for GpuPreference {
+    fn to_device_preference(&self) -> DevicePreference {
+        match self {
+            GpuPreference::Default => DevicePreference::Default,
+            GpuPreference::HighPerformance => DevicePreference::HighPerformance,
+            GpuPreference::LowPower => DevicePreference::LowPower,
+            GpuPreference::Named(name) => DevicePreference::Named(name.clone()),
+        }
+    }
+}
+
+/// Create a surface for |surface| sharing the render context of |context|.
+/// Like the main surface, it never blocks on buffer swaps.
+pub(crate) fn new_wayland_secondary_surface(
+    context: &Context,
+    surface: &wl_surface::WlSurface,
+    size: PhysicalSize<u32>,
+) -> Result<SecondarySurface, CreateWaylandContextError> {
+    let size = size.non_zero().ok_or(ContextBuildError::InvalidSize)?;
+
+    let secondary_surface = context
+        .create_secondary_surface(
+            wayland_window_handle(surface)?,
+            size,
+            Some(SwapInterval::DontWait),
+        )
+        .map_err(ContextBuildError::from)?;
+
+    Ok(secondary_surface)
+}
+
+fn wayland_window_handle(
+    surface: &wl_surface::WlSurface,
+) -> Result<RawWindowHandle, CreateWaylandContextError> {
+    let surface = NonNull::new(surface.id().as_ptr().cast())
+        .ok_or(CreateWaylandContextError::ConnectionClosed)?;
+    let wl_window_handle = WaylandWindowHandle::new(surface);
+    Ok(RawWindowHandle::Wayland(wl_window_handle))
+}
+
 #[derive(Error, Debug)]
 pub enum CreateWaylandContextError {
     #[error("Connection has been closed")]
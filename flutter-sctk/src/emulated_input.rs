@@ -0,0 +1,497 @@
+//! Input emulation through the remote desktop XDG portal, which lets the
+//! compositor ask the user before the application moves the pointer or
+//! injects input events.
+//!
+//! The portal requests are asynchronous, so the plugin handler forwards its
+//! requests to the event loop, where they are applied from a single task at a
+//! time (which keeps the injected events in order).
+//!
+//! A granted permission is remembered with the restore token of the portal,
+//! stored in the persistent cache path, so that the next session starts
+//! without asking the user again (as long as the compositor supports it).
+//! The portal bindings don't expose the persistence options, so the device
+//! selection and the start of the session call the portal directly.
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fs,
+    future::Future,
+    mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use ashpd::{
+    desktop::{
+        remote_desktop::{DeviceType, KeyState, RemoteDesktop},
+        ResponseError, Session,
+    },
+    enumflags2::BitFlags,
+    zbus::{
+        self,
+        export::serde::Serialize,
+        zvariant::{DynamicType, OwnedValue, Value},
+    },
+};
+use calloop::channel::Sender;
+use flutter_plugins::emulated_input::{
+    EmulatedInputError, EmulatedInputEvent, EmulatedInputHandler, EmulatedInputPermission,
+    EmulatedInputPermissionReply, EmulatedInputStatus, EmulatedInputWarpReply,
+};
+use futures_lite::StreamExt;
+use parking_lot::Mutex;
+use tracing::warn;
+
+use crate::{
+    application::{SctkApplicationRequest, SctkEngineId},
+    handler::SctkAsyncResult,
+};
+
+const RESTORE_TOKEN_FILE_NAME: &str = "emulated_input_restore_token";
+
+const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+/// Keep the permission until the user revokes it.
+const PERSIST_MODE_PERSISTENT: u32 = 2;
+
+/// Results of a portal request, see `org.freedesktop.portal.Request`.
+type PortalResults = HashMap<String, OwnedValue>;
+
+/// Requests forwarded by the plugin handler to the event loop.
+pub(crate) enum SctkEmulatedInputRequest {
+    RequestPermission(EmulatedInputPermissionReply),
+    Inject(EmulatedInputEvent),
+    /// Move the pointer to |x|, |y| in the logical coordinates of a view of
+    /// |engine_id|.
+    WarpPointer {
+        engine_id: SctkEngineId,
+        x: f64,
+        y: f64,
+        reply: EmulatedInputWarpReply,
+    },
+}
+
+struct SctkEmulatedInputSession {
+    proxy: RemoteDesktop<'static>,
+    session: Session<'static>,
+}
+
+#[derive(Default)]
+struct SctkEmulatedInputState {
+    session: Option<Rc<SctkEmulatedInputSession>>,
+    pending_replies: Vec<EmulatedInputPermissionReply>,
+    is_requesting: bool,
+    events: VecDeque<EmulatedInputEvent>,
+    is_injecting: bool,
+    /// Where the restore token is stored, unless the persistent cache path
+    /// is not set.
+    restore_token_path: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+pub(crate) struct SctkEmulatedInput {
+    state: Rc<RefCell<SctkEmulatedInputState>>,
+    status: Arc<Mutex<EmulatedInputStatus>>,
+}
+
+impl SctkEmulatedInput {
+    /// The restore token is stored in |cache_path|, if set.
+    pub(crate) fn new(cache_path: &Path) -> Self {
+        let restore_token_path =
+            (!cache_path.as_os_str().is_empty()).then(|| cache_path.join(RESTORE_TOKEN_FILE_NAME));
+        let status = EmulatedInputStatus {
+            can_restore: restore_token_path.as_deref().is_some_and(Path::is_file),
+            ..Default::default()
+        };
+
+        Self {
+            state: Rc::new(RefCell::new(SctkEmulatedInputState {
+                restore_token_path,
+                ..Default::default()
+            })),
+            status: Arc::new(Mutex::new(status)),
+        }
+    }
+
+    pub(crate) fn create_handler(
+        &self,
+        sender: Sender<SctkApplicationRequest>,
+        engine_id: SctkEngineId,
+    ) -> SctkEmulatedInputHandler {
+        SctkEmulatedInputHandler {
+            sender,
+            engine_id,
+            status: self.status.clone(),
+        }
+    }
+
+    /// Returns the task checking whether the portal offers input emulation.
+    pub(crate) fn probe(&self) -> impl Future<Output = SctkAsyncResult> {
+        let status = self.status.clone();
+        async move {
+            let device_types = match available_device_types().await {
+                Ok(device_types) => device_types,
+                Err(err) => {
+                    warn!("[plugin: emulated_input] Input emulation is unavailable: {}", err);
+                    BitFlags::empty()
+                }
+            };
+
+            status.lock().is_available =
+                device_types.intersects(DeviceType::Pointer | DeviceType::Keyboard);
+            Ok(())
+        }
+    }
+
+    /// Returns the task that needs to be scheduled for answering |reply|, if
+    /// one is not already running.
+    pub(crate) fn request_permission(
+        &self,
+        reply: EmulatedInputPermissionReply,
+    ) -> Option<impl Future<Output = SctkAsyncResult>> {
+        let mut state = self.state.borrow_mut();
+        state.pending_replies.push(reply);
+
+        if state.is_requesting {
+            return None;
+        }
+        state.is_requesting = true;
+
+        Some(self.clone().sync_permission())
+    }
+
+    /// Returns the task that needs to be scheduled for injecting |event|, if
+    /// one is not already running.
+    pub(crate) fn inject(
+        &self,
+        event: EmulatedInputEvent,
+    ) -> Option<impl Future<Output = SctkAsyncResult>> {
+        let mut state = self.state.borrow_mut();
+        state.events.push_back(event);
+
+        if state.is_injecting {
+            return None;
+        }
+        state.is_injecting = true;
+
+        Some(self.clone().drain_events())
+    }
+
+    async fn sync_permission(self) -> SctkAsyncResult {
+        let result = self
+            .try_request_permission()
+            .await
+            .map(|()| self.status.lock().clone())
+            .map_err(|err| EmulatedInputError::Failed(err.to_string()));
+
+        let replies = {
+            let mut state = self.state.borrow_mut();
+            state.is_requesting = false;
+            mem::take(&mut state.pending_replies)
+        };
+        for reply in replies {
+            reply(result.clone());
+        }
+        Ok(())
+    }
+
+    async fn try_request_permission(&self) -> Result<(), ashpd::Error> {
+        if self.state.borrow().session.is_some() {
+            return Ok(());
+        }
+
+        let proxy = RemoteDesktop::new().await?;
+        let device_types = proxy.available_device_types().await?;
+        let device_types = device_types & (DeviceType::Pointer | DeviceType::Keyboard);
+        self.status.lock().is_available = !device_types.is_empty();
+        if device_types.is_empty() {
+            return Ok(());
+        }
+
+        let session = proxy.create_session().await?;
+        let restore_token = self.load_restore_token();
+        select_devices(&proxy, &session, device_types, restore_token.as_deref()).await?;
+
+        // Answered once the user accepted or dismissed the portal dialog, or
+        // right away when the session was restored.
+        let results = match start(&proxy, &session).await {
+            Ok(results) => results,
+            Err(ashpd::Error::Response(ResponseError::Cancelled)) => {
+                self.status.lock().permission = EmulatedInputPermission::Denied;
+                self.store_restore_token(None);
+                session.close().await.ok();
+                return Ok(());
+            }
+            Err(err) => {
+                session.close().await.ok();
+                return Err(err);
+            }
+        };
+
+        let devices = results
+            .get("devices")
+            .and_then(|devices| u32::try_from(devices).ok())
+            .map(BitFlags::<DeviceType>::from_bits_truncate)
+            .unwrap_or_default();
+        // Restore tokens are single use, the portal sends a new one with
+        // every session (unless it doesn't support persistence).
+        let restore_token = results
+            .get("restore_token")
+            .and_then(|token| <&str>::try_from(token).ok());
+        self.store_restore_token(restore_token);
+
+        {
+            let mut status = self.status.lock();
+            status.permission = EmulatedInputPermission::Granted;
+            status.pointer = devices.contains(DeviceType::Pointer);
+            status.keyboard = devices.contains(DeviceType::Keyboard);
+        }
+        let session = SctkEmulatedInputSession { proxy, session };
+        self.state.borrow_mut().session = Some(Rc::new(session));
+        Ok(())
+    }
+
+    fn load_restore_token(&self) -> Option<String> {
+        let state = self.state.borrow();
+        let path = state.restore_token_path.as_ref()?;
+        fs::read_to_string(path)
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+    }
+
+    /// Remember |token| for the next session, or forget the previous one if
+    /// there is none (e.g.: the user dismissed the dialog).
+    fn store_restore_token(&self, token: Option<&str>) {
+        let state = self.state.borrow();
+        let Some(path) = &state.restore_token_path else {
+            return;
+        };
+
+        let result = match token {
+            Some(token) => path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(path, token)),
+            None if path.exists() => fs::remove_file(path),
+            None => Ok(()),
+        };
+        match result {
+            Ok(()) => self.status.lock().can_restore = token.is_some(),
+            Err(err) => warn!(
+                "[plugin: emulated_input] Failed to update the restore token at {:?}: {}",
+                path, err
+            ),
+        }
+    }
+
+    async fn drain_events(self) -> SctkAsyncResult {
+        loop {
+            let (event, session) = {
+                let mut state = self.state.borrow_mut();
+                let Some(event) = state.events.pop_front() else {
+                    state.is_injecting = false;
+                    return Ok(());
+                };
+                (event, state.session.clone())
+            };
+
+            // The session may have been lost while the event was queued.
+            let Some(session) = session else {
+                warn!("[plugin: emulated_input] Dropping event without a session");
+                continue;
+            };
+
+            if let Err(err) = session.notify(&event).await {
+                warn!("[plugin: emulated_input] Failed to inject event: {}", err);
+                self.close_session();
+                session.session.close().await.ok();
+            }
+        }
+    }
+
+    /// Forget the session, so that Dart is asked to request the permission
+    /// again.
+    fn close_session(&self) {
+        let mut state = self.state.borrow_mut();
+        state.session = None;
+        state.events.clear();
+
+        let mut status = self.status.lock();
+        status.permission = EmulatedInputPermission::NotDetermined;
+        status.pointer = false;
+        status.keyboard = false;
+    }
+}
+
+impl SctkEmulatedInputSession {
+    async fn notify(&self, event: &EmulatedInputEvent) -> Result<(), ashpd::Error> {
+        let proxy = &self.proxy;
+        let session = &self.session;
+        match *event {
+            EmulatedInputEvent::PointerMotion { dx, dy } => {
+                proxy.notify_pointer_motion(session, dx, dy).await
+            }
+            EmulatedInputEvent::PointerButton { button, is_pressed } => {
+                proxy
+                    .notify_pointer_button(session, button, key_state(is_pressed))
+                    .await
+            }
+            EmulatedInputEvent::Scroll { dx, dy } => {
+                proxy.notify_pointer_axis(session, dx, dy, true).await
+            }
+            EmulatedInputEvent::Key {
+                keycode,
+                is_pressed,
+            } => {
+                proxy
+                    .notify_keyboard_keycode(session, keycode, key_state(is_pressed))
+                    .await
+            }
+        }
+    }
+}
+
+/// Select the |device_types| of |session|, restoring the permission of a
+/// previous session if |restore_token| is still valid.
+async fn select_devices(
+    proxy: &RemoteDesktop<'static>,
+    session: &Session<'static>,
+    device_types: BitFlags<DeviceType>,
+    restore_token: Option<&str>,
+) -> Result<PortalResults, ashpd::Error> {
+    let handle_token = next_handle_token();
+    let mut options = HashMap::from([
+        ("handle_token", Value::from(handle_token.as_str())),
+        ("types", Value::from(device_types.bits())),
+        ("persist_mode", Value::from(PERSIST_MODE_PERSISTENT)),
+    ]);
+    if let Some(restore_token) = restore_token {
+        options.insert("restore_token", Value::from(restore_token));
+    }
+
+    portal_request(proxy, "SelectDevices", &handle_token, &(session, options)).await
+}
+
+async fn start(
+    proxy: &RemoteDesktop<'static>,
+    session: &Session<'static>,
+) -> Result<PortalResults, ashpd::Error> {
+    let handle_token = next_handle_token();
+    let options = HashMap::from([("handle_token", Value::from(handle_token.as_str()))]);
+    // The dialog isn't attached to a window, like with the portal bindings.
+    portal_request(proxy, "Start", &handle_token, &(session, "", options)).await
+}
+
+fn next_handle_token() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    format!("flutter_rs_{}", COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Call |method| of the portal with |body|, and wait for the response of the
+/// request identified by |handle_token|.
+async fn portal_request<B>(
+    proxy: &RemoteDesktop<'static>,
+    method: &str,
+    handle_token: &str,
+    body: &B,
+) -> Result<PortalResults, ashpd::Error>
+where
+    B: Serialize + DynamicType,
+{
+    let connection = proxy.connection();
+    let sender = connection.unique_name().ok_or(ashpd::Error::ParseError(
+        "Missing unique name of the connection",
+    ))?;
+    let path = format!(
+        "/org/freedesktop/portal/desktop/request/{}/{}",
+        sender.trim_start_matches(':').replace('.', "_"),
+        handle_token
+    );
+
+    // Subscribe before the call, so that the response can't be missed.
+    let request = zbus::Proxy::new(
+        connection,
+        PORTAL_DESTINATION,
+        path,
+        PORTAL_REQUEST_INTERFACE,
+    )
+    .await?;
+    let mut responses = request.receive_signal("Response").await?;
+    proxy.call_method(method, body).await?;
+
+    let message = responses
+        .next()
+        .await
+        .ok_or(ashpd::Error::Response(ResponseError::Other))?;
+    let (response, results): (u32, PortalResults) = message.body().deserialize()?;
+    match response {
+        0 => Ok(results),
+        1 => Err(ashpd::Error::Response(ResponseError::Cancelled)),
+        _ => Err(ashpd::Error::Response(ResponseError::Other)),
+    }
+}
+
+async fn available_device_types() -> Result<BitFlags<DeviceType>, ashpd::Error> {
+    RemoteDesktop::new().await?.available_device_types().await
+}
+
+fn key_state(is_pressed: bool) -> KeyState {
+    if is_pressed {
+        KeyState::Pressed
+    } else {
+        KeyState::Released
+    }
+}
+
+pub struct SctkEmulatedInputHandler {
+    sender: Sender<SctkApplicationRequest>,
+    engine_id: SctkEngineId,
+    status: Arc<Mutex<EmulatedInputStatus>>,
+}
+
+impl EmulatedInputHandler for SctkEmulatedInputHandler {
+    fn status(&mut self) -> EmulatedInputStatus {
+        self.status.lock().clone()
+    }
+
+    fn request_permission(&mut self, reply: EmulatedInputPermissionReply) {
+        let request = SctkEmulatedInputRequest::RequestPermission(reply);
+        if let Err(err) = self.sender.send(SctkApplicationRequest::EmulatedInput(request)) {
+            if let SctkApplicationRequest::EmulatedInput(
+                SctkEmulatedInputRequest::RequestPermission(reply),
+            ) = err.0
+            {
+                reply(Err(EmulatedInputError::Failed("The event loop is gone".into())));
+            }
+        }
+    }
+
+    fn inject(&mut self, event: EmulatedInputEvent) -> Result<(), EmulatedInputError> {
+        let request = SctkEmulatedInputRequest::Inject(event);
+        self.sender
+            .send(SctkApplicationRequest::EmulatedInput(request))
+            .map_err(|_| EmulatedInputError::Failed("The event loop is gone".into()))
+    }
+
+    fn warp_pointer(&mut self, x: f64, y: f64, reply: EmulatedInputWarpReply) {
+        let request = SctkEmulatedInputRequest::WarpPointer {
+            engine_id: self.engine_id,
+            x,
+            y,
+            reply,
+        };
+        if let Err(err) = self.sender.send(SctkApplicationRequest::EmulatedInput(request)) {
+            if let SctkApplicationRequest::EmulatedInput(
+                SctkEmulatedInputRequest::WarpPointer { reply, .. },
+            ) = err.0
+            {
+                reply(Err(EmulatedInputError::Failed("The event loop is gone".into())));
+            }
+        }
+    }
+}
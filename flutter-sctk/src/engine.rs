@@ -0,0 +1,587 @@
+//! Engines run by an application, each one with its own plugins and windows.
+//!
+//! The Wayland connection, the seats and the outputs are shared by all the
+//! engines, which are all driven by the same event loop. Events targeting a
+//! surface are routed to the engine owning the window, while keyboard input
+//! goes to the engine owning the window with the keyboard focus.
+use std::{collections::HashMap, fmt, rc::Rc, sync::Arc, time::Duration};
+
+use calloop::{channel::Sender, LoopSignal};
+use flutter_engine::{builder::FlutterEngineBuilder, plugins::PluginRegistrar, FlutterEngine};
+use flutter_input::keyboard::KeyEventFallthrough;
+use flutter_plugins::{
+    appearance::AppearancePlugin,
+    assets::AssetsPlugin,
+    diagnostics::{DiagnosticsPlugin, ScrollSettings},
+    fonts::FontsPlugin,
+    isolate::IsolatePlugin,
+    keyboard::KeyboardPlugin,
+    keyboard_repeat::KeyboardRepeatPlugin,
+    keyevent::KeyEventPlugin,
+    lifecycle::LifecyclePlugin,
+    localization::LocalizationPlugin,
+    logging::LoggingPlugin,
+    mousecursor::MouseCursorPlugin,
+    navigation::NavigationPlugin,
+    platform::PlatformPlugin,
+    ready::ReadyPlugin,
+    screensaver::ScreenSaverPlugin,
+    settings::SettingsPlugin,
+    shared_preferences::SharedPreferencesPlugin,
+    system::SystemPlugin,
+    text_toolbar::TextToolbarPlugin,
+    textinput::TextInputPlugin,
+    window::WindowPlugin,
+};
+use flutter_runner_api::{ApplicationAttributes, FailureScreenConfig, RenderPath, VsyncMode};
+use parking_lot::{Mutex, RwLock};
+use smithay_client_toolkit::{compositor::CompositorState, shell::xdg::XdgShell};
+use tracing::{error, info, trace};
+use wayland_backend::client::ObjectId;
+use wayland_client::{globals::GlobalList, Connection, QueueHandle};
+
+use crate::{
+    application::{SctkApplicationCreateError, SctkApplicationRequest, SctkApplicationState},
+    attached::SctkAttachment,
+    extension::WaylandHandles,
+    failure::{SctkFailureScreen, SctkLogTail},
+    handler::{
+        SctkKeyboardHandler, SctkMouseCursorHandler, SctkPlatformHandler, SctkPlatformTaskHandler,
+        SctkScreenSaverInhibitor, SctkTextInputHandler, SctkTextToolbarHandler, SctkVsyncHandler,
+    },
+    keyboard::SctkKeyEvent,
+    layer_shell::SctkLayerShell,
+    overlay::SctkOverlayGlobals,
+    solid::SctkSolidColorGlobals,
+    window::{ConfigureSize, SctkFlutterWindow},
+};
+pub use crate::application::SctkEngineId;
+#[cfg(feature = "emulated-input")]
+use crate::emulated_input::SctkEmulatedInput;
+#[cfg(feature = "emulated-input")]
+use flutter_plugins::emulated_input::EmulatedInputPlugin;
+#[cfg(feature = "status-notifier")]
+use crate::tray::SctkTray;
+#[cfg(feature = "status-notifier")]
+use flutter_plugins::tray::TrayPlugin;
+
+/// Application-wide resources the engines are created with.
+pub(crate) struct SctkEngineResources<'a> {
+    pub(crate) conn: &'a Connection,
+    pub(crate) globals: &'a GlobalList,
+    pub(crate) qh: &'a QueueHandle<SctkApplicationState>,
+    pub(crate) loop_signal: &'a LoopSignal,
+    pub(crate) compositor_state: &'a CompositorState,
+    pub(crate) xdg_shell_state: &'a XdgShell,
+    pub(crate) mouse_cursor_handler: &'a Arc<Mutex<SctkMouseCursorHandler>>,
+    pub(crate) screensaver_inhibitor: &'a SctkScreenSaverInhibitor,
+    pub(crate) scroll_settings: &'a Arc<Mutex<ScrollSettings>>,
+    #[cfg(feature = "emulated-input")]
+    pub(crate) emulated_input: &'a SctkEmulatedInput,
+    #[cfg(feature = "status-notifier")]
+    pub(crate) tray: &'a SctkTray,
+    pub(crate) request_sender: &'a Sender<SctkApplicationRequest>,
+}
+
+pub(crate) struct SctkEngineInstance {
+    pub(crate) engine: FlutterEngine,
+    pub(crate) plugins: Rc<RwLock<PluginRegistrar>>,
+    pub(crate) windows: HashMap<ObjectId, SctkFlutterWindow>,
+    pub(crate) startup_synchronizer: ImplicitWindowStartupSynchronizer,
+    pub(crate) keyboard_handler: Arc<Mutex<SctkKeyboardHandler>>,
+    pub(crate) vsync_handler: Arc<Mutex<SctkVsyncHandler>>,
+    pub(crate) platform_handler: Arc<Mutex<SctkPlatformHandler>>,
+    pub(crate) failure_screen_config: FailureScreenConfig,
+    /// Shown on the failure screen, see `crate::failure`.
+    pub(crate) app_name: Option<String>,
+    pub(crate) log_tail: SctkLogTail,
+    /// Drawn over the implicit window until the app presents a frame.
+    pub(crate) failure_screen: Option<SctkFailureScreen>,
+    /// Shared with the plugins, see `crate::extension`.
+    pub(crate) wayland_handles: WaylandHandles,
+}
+
+impl SctkEngineInstance {
+    /// Build the engine along with its implicit window, and register the
+    /// default plugins. The engine is not run yet. The window is attached to
+    /// another one when |attachment| is set (see `WindowRole::Utility`).
+    pub(crate) fn new(
+        resources: SctkEngineResources,
+        id: SctkEngineId,
+        engine_builder: FlutterEngineBuilder,
+        attributes: ApplicationAttributes,
+        attachment: Option<SctkAttachment>,
+    ) -> Result<Self, SctkApplicationCreateError> {
+        let SctkEngineResources {
+            conn,
+            globals,
+            qh,
+            loop_signal,
+            compositor_state,
+            xdg_shell_state,
+            mouse_cursor_handler,
+            screensaver_inhibitor,
+            scroll_settings,
+            #[cfg(feature = "emulated-input")]
+            emulated_input,
+            #[cfg(feature = "status-notifier")]
+            tray,
+            request_sender,
+        } = resources;
+
+        let layer_shell = SctkLayerShell::bind(conn, globals, qh, &attributes.window_role)?;
+        let idle_inhibit_manager = globals.bind(qh, 1..=1, ()).ok();
+        let overlay_globals = SctkOverlayGlobals::bind(globals, qh, compositor_state);
+        let solid_color_globals = SctkSolidColorGlobals::bind(globals, qh, compositor_state);
+
+        let platform_task_handler = Arc::new(SctkPlatformTaskHandler::new(loop_signal.clone()));
+        let vsync_handler = Arc::new(Mutex::new(SctkVsyncHandler::new(qh.clone())));
+        vsync_handler
+            .lock()
+            .set_min_frame_interval(attributes.min_frame_interval);
+
+        let mut engine_builder = engine_builder
+            .with_compositor_enabled(attributes.render_path == RenderPath::Compositor)
+            .with_platform_handler(platform_task_handler);
+
+        // When the vsync handler is omitted, the engine falls back to its
+        // internal timer for scheduling frames.
+        if attributes.vsync_mode == VsyncMode::Vsync {
+            engine_builder = engine_builder.with_vsync_handler(vsync_handler.clone());
+        }
+
+        // Keep the Dart log for reporting the startup failures.
+        let log_tail = SctkLogTail::new();
+        let log_tag = attributes.log_tag.clone().or(attributes.app_id.clone());
+        if attributes.failure_screen.enabled || log_tag.is_some() {
+            let log_tail = log_tail.clone();
+            engine_builder = engine_builder.with_log_message_callback(move |tag, message| {
+                // The engine no longer prints the messages itself.
+                let tag = log_tag.as_deref().unwrap_or(tag);
+                for line in message.split('\n') {
+                    info!(target: "flutter", "{tag}: {line}");
+                }
+                log_tail.push(message);
+            });
+        }
+
+        let engine = engine_builder.build()?;
+        let plugin_configs = attributes.plugin_configs.clone();
+        let async_runtime = attributes.async_runtime.clone();
+        let failure_screen_config = attributes.failure_screen.clone();
+        let app_name = attributes.title.clone().or(attributes.app_id.clone());
+
+        let implicit_window = SctkFlutterWindow::new(
+            engine.downgrade(),
+            qh,
+            compositor_state,
+            xdg_shell_state,
+            layer_shell.as_ref(),
+            attachment,
+            vsync_handler.clone(),
+            idle_inhibit_manager,
+            overlay_globals,
+            solid_color_globals,
+            request_sender.clone(),
+            attributes,
+        )?;
+
+        engine.add_view(implicit_window.create_flutter_view());
+
+        let wayland_handles = WaylandHandles::new(conn, globals.registry());
+        wayland_handles.add_window(&implicit_window);
+
+        vsync_handler
+            .lock()
+            .init(engine.downgrade(), implicit_window.wl_surface());
+
+        let noop_isolate_cb = || trace!("[isolate-plugin] isolate has been created");
+        let platform_handler =
+            unsafe { SctkPlatformHandler::new(conn.display(), implicit_window.xdg_toplevel()) }
+                .with_request_sender(request_sender.clone());
+        let platform_handler = Arc::new(Mutex::new(platform_handler));
+        let text_input_handler = Arc::new(Mutex::new(SctkTextInputHandler::new(
+            request_sender.clone(),
+        )));
+        let keyboard_handler = Arc::new(Mutex::new(SctkKeyboardHandler::new()));
+        let window_handler = Arc::new(Mutex::new(
+            implicit_window.create_window_handler(request_sender.clone(), id),
+        ));
+        let logging_handler = Arc::new(Mutex::new(
+            implicit_window.create_logging_handler(vsync_handler.clone()),
+        ));
+        let text_toolbar_handler = Arc::new(Mutex::new(SctkTextToolbarHandler::new(
+            request_sender.clone(),
+        )));
+        let anchors = implicit_window.anchors();
+        let screensaver_handler = Arc::new(Mutex::new(
+            screensaver_inhibitor.create_handler(request_sender.clone()),
+        ));
+        let ready_handler = Arc::new(Mutex::new(
+            implicit_window.create_ready_handler(request_sender.clone()),
+        ));
+
+        let mut plugins = PluginRegistrar::with_configs(plugin_configs)
+            .with_async_runtime(async_runtime)
+            .with_platform_handles(Arc::new(wayland_handles.clone()));
+        // Coalesce the messages sent until the engine is running, as each of
+        // them delays the first frame (see `maybe_send_startup_pending_configure`).
+        plugins.deferred_sends().defer();
+        plugins.add_plugin(&engine, AppearancePlugin::default());
+        plugins.add_plugin(
+            &engine,
+            DiagnosticsPlugin::default().with_scroll_settings(scroll_settings.clone()),
+        );
+        plugins.add_plugin(&engine, FontsPlugin::default());
+        plugins.add_plugin(&engine, IsolatePlugin::new(noop_isolate_cb));
+        plugins.add_plugin(&engine, KeyEventPlugin::new());
+        plugins.add_plugin(&engine, TextInputPlugin::new(text_input_handler));
+        plugins.add_plugin(&engine, KeyboardPlugin::new(keyboard_handler.clone()));
+        plugins.add_plugin(&engine, KeyboardRepeatPlugin::default());
+        plugins.add_plugin(&engine, LifecyclePlugin::default());
+        plugins.add_plugin(&engine, LocalizationPlugin::default());
+        plugins.add_plugin(&engine, NavigationPlugin::default());
+        plugins.add_plugin(&engine, PlatformPlugin::new(platform_handler.clone()));
+        plugins.add_plugin(&engine, ReadyPlugin::new(ready_handler));
+        plugins.add_plugin(&engine, SettingsPlugin::default());
+        plugins.add_plugin(&engine, SharedPreferencesPlugin::default());
+        plugins.add_plugin(&engine, SystemPlugin::default());
+        plugins.add_plugin(
+            &engine,
+            WindowPlugin::new(window_handler).with_anchors(anchors.clone()),
+        );
+        plugins.add_plugin(
+            &engine,
+            TextToolbarPlugin::new(text_toolbar_handler).with_anchors(anchors),
+        );
+        plugins.add_plugin(&engine, ScreenSaverPlugin::new(screensaver_handler));
+        #[cfg(feature = "emulated-input")]
+        plugins.add_plugin(
+            &engine,
+            EmulatedInputPlugin::new(Arc::new(Mutex::new(
+                emulated_input.create_handler(request_sender.clone(), id),
+            ))),
+        );
+        #[cfg(feature = "status-notifier")]
+        plugins.add_plugin(
+            &engine,
+            TrayPlugin::new(Arc::new(Mutex::new(
+                tray.create_handler(request_sender.clone()),
+            ))),
+        );
+        plugins.add_plugin(&engine, AssetsPlugin::default());
+        plugins.add_plugin(&engine, LoggingPlugin::new(logging_handler.clone()));
+        plugins.add_plugin(
+            &engine,
+            MouseCursorPlugin::new(mouse_cursor_handler.clone()),
+        );
+        plugins.log_unused_configs();
+
+        // Read when the state is dumped, so that the plugins added later on
+        // (e.g.: by the application) are reported too.
+        logging_handler
+            .lock()
+            .set_plugin_names(plugins.shared_plugin_names());
+
+        Ok(Self {
+            engine,
+            plugins: Rc::new(RwLock::new(plugins)),
+            windows: HashMap::from([(implicit_window.wl_surface_id(), implicit_window)]),
+            startup_synchronizer: ImplicitWindowStartupSynchronizer::new(),
+            keyboard_handler,
+            vsync_handler,
+            platform_handler,
+            failure_screen_config,
+            app_name,
+            log_tail,
+            failure_screen: None,
+            wayland_handles,
+        })
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.startup_synchronizer.is_engine_running()
+    }
+
+    /// The window of the implicit view.
+    pub(crate) fn implicit_window(&self) -> Option<&SctkFlutterWindow> {
+        self.windows.values().last()
+    }
+
+    pub(crate) fn implicit_window_mut(&mut self) -> Option<&mut SctkFlutterWindow> {
+        self.windows.values_mut().last()
+    }
+
+    /// Send |event| on both the key data and the legacy paths, telling
+    /// |fallthrough| whether each of them handled it.
+    pub(crate) fn send_key_event(
+        &self,
+        event: SctkKeyEvent,
+        fallthrough: Option<KeyEventFallthrough>,
+    ) {
+        let engine = &self.engine;
+        match fallthrough.clone() {
+            Some(fallthrough) => engine
+                .send_key_event_with_callback(event.clone().into(), move |handled| {
+                    fallthrough.respond(handled)
+                }),
+            None => engine.send_key_event(event.clone().into()),
+        }
+
+        // The `flutter/keyevent`'s are considered legacy but they are still
+        // required for now [0][1], so the current implementation is mostly
+        // using them as a "flush" event for `flutter/keydata` messages.
+        //
+        // TODO: Remove `KeyEventPlugin` once it is no longer *required* for
+        // keyboard handling (planned for Q4 2024 [2]).
+        //
+        // [0](https://github.com/flutter/flutter/pull/132533)
+        // [1](https://github.com/flutter/flutter/issues/136419)
+        // [2](https://github.com/flutter/flutter/issues/136419)
+        let plugins = self.plugins.read();
+        plugins.with_plugin(|keyevent: &KeyEventPlugin| match fallthrough {
+            Some(fallthrough) => keyevent
+                .key_action_with_reply(event.into(), move |handled| fallthrough.respond(handled)),
+            None => keyevent.key_action(event.into()),
+        });
+    }
+
+    /// Draw the failure screen over the implicit window, if shown.
+    pub(crate) fn draw_failure_screen(&self) {
+        let (Some(screen), Some(window)) = (&self.failure_screen, self.implicit_window()) else {
+            return;
+        };
+
+        if !window.present_failure_screen(screen) {
+            error!("Failed to present the failure screen");
+        }
+    }
+
+    /// Tear the engine down, after its windows and plugins. Unlike the
+    /// application shutdown (see `crate::shutdown`), Dart is not waited for.
+    pub(crate) fn shutdown(mut self) {
+        let count = self.plugins.write().detach_all(&self.engine);
+        self.engine.remove_all_channels();
+        trace!("Detached {} plugins", count);
+
+        for window in self.windows.values() {
+            window.remove_pointers();
+            self.engine.remove_view(window.view_id());
+        }
+        self.engine.shutdown();
+        self.windows.clear();
+        self.wayland_handles.clear_windows();
+    }
+}
+
+// Trying to send a `WindowMetricsEvent` before the engine is running results in
+// a `Viewport metrics were invalid` [embedder error][0]. This could happen when
+// the first `window.configure` event arrives before the engine is fully
+// running.
+//
+// The `ImplicitWindowStartupSynchronizer` is used as a way to synchronize the
+// engine startup events in order to make sure that the initial window metrics
+// event is only sent once a) the engine is running and b) the first configure
+// event has been received.
+//
+// TODO: Get rid of this hack once Flutter supports disabling the implicit view
+// as part of the [multi-view embedder APIs][1].
+//
+// [0]: https://github.com/flutter/engine/blob/e76c956498841e1ab458577d3892003e553e4f3c/shell/platform/embedder/embedder.cc#L2173-L2174
+// [1]: https://github.com/flutter/flutter/issues/144806
+//
+// The synchronizer is a state machine, so that a startup which never
+// completes (e.g.: the engine failed to run, or the compositor never
+// configured the window) is reported instead of leaving the application
+// without a window (see `STARTUP_TIMEOUT`).
+pub(crate) struct ImplicitWindowStartupSynchronizer {
+    state: StartupState,
+    /// Latest configure received before the engine was running.
+    pending_configure: Option<PendingConfigure>,
+}
+
+/// Time allowed for an engine to run and for its window to be configured.
+pub(crate) const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Progress of the startup of an engine, see `SctkApplicationRunError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupState {
+    /// The engine isn't running yet. The window may already be configured.
+    WaitingForEngine,
+    /// The engine is running, but the window wasn't configured yet.
+    WaitingForConfigure,
+    /// The first window metrics were sent to the engine.
+    Ready,
+    /// The engine failed to run.
+    Failed,
+}
+
+impl fmt::Display for StartupState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WaitingForEngine => write!(f, "waiting for the engine to run"),
+            Self::WaitingForConfigure => write!(f, "waiting for the window to be configured"),
+            Self::Ready => write!(f, "ready"),
+            Self::Failed => write!(f, "the engine failed to run"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PendingConfigure {
+    size: ConfigureSize,
+    /// Serial of the latest configure, for tracing (the configures are
+    /// acknowledged by sctk as they are received).
+    serial: Option<u32>,
+}
+
+impl ImplicitWindowStartupSynchronizer {
+    fn new() -> Self {
+        Self {
+            state: StartupState::WaitingForEngine,
+            pending_configure: None,
+        }
+    }
+
+    pub(crate) fn state(&self) -> StartupState {
+        self.state
+    }
+
+    pub(crate) fn is_engine_running(&self) -> bool {
+        matches!(
+            self.state,
+            StartupState::WaitingForConfigure | StartupState::Ready
+        )
+    }
+
+    /// The engine is running. Returns the size of the latest configure
+    /// received in the meantime, which must now be sent to the engine.
+    pub(crate) fn engine_started(&mut self) -> Option<ConfigureSize> {
+        if self.state != StartupState::WaitingForEngine {
+            return None;
+        }
+
+        let Some(pending) = self.pending_configure.take() else {
+            self.state = StartupState::WaitingForConfigure;
+            return None;
+        };
+
+        trace!(
+            "Sending the pending configure (serial: {:?})",
+            pending.serial
+        );
+        self.state = StartupState::Ready;
+        Some(pending.size)
+    }
+
+    pub(crate) fn engine_failed(&mut self) {
+        self.state = StartupState::Failed;
+        self.pending_configure = None;
+    }
+
+    /// The window was configured with |size|. Returns the size if it must be
+    /// sent to the engine now, otherwise only the latest configure is kept
+    /// until the engine is running.
+    pub(crate) fn configure(
+        &mut self,
+        size: ConfigureSize,
+        serial: Option<u32>,
+    ) -> Option<ConfigureSize> {
+        match self.state {
+            StartupState::WaitingForEngine => {
+                trace!("Deferring the window metrics event, the engine is not running");
+                self.pending_configure = Some(PendingConfigure { size, serial });
+                None
+            }
+            StartupState::WaitingForConfigure | StartupState::Ready => {
+                self.state = StartupState::Ready;
+                Some(size)
+            }
+            StartupState::Failed => None,
+        }
+    }
+
+    /// The prerequisite which is still missing once the startup timed out,
+    /// if any.
+    pub(crate) fn timed_out_state(&self) -> Option<StartupState> {
+        match self.state {
+            StartupState::WaitingForEngine | StartupState::WaitingForConfigure => Some(self.state),
+            StartupState::Ready | StartupState::Failed => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::{ImplicitWindowStartupSynchronizer, StartupState};
+    use crate::window::ConfigureSize;
+
+    fn size(width: u32, height: u32) -> ConfigureSize {
+        (NonZeroU32::new(width), NonZeroU32::new(height))
+    }
+
+    #[test]
+    fn sends_the_configure_received_before_the_engine_runs() {
+        let mut synchronizer = ImplicitWindowStartupSynchronizer::new();
+        assert_eq!(synchronizer.configure(size(800, 600), Some(1)), None);
+        assert!(!synchronizer.is_engine_running());
+        assert_eq!(
+            synchronizer.timed_out_state(),
+            Some(StartupState::WaitingForEngine)
+        );
+
+        assert_eq!(synchronizer.engine_started(), Some(size(800, 600)));
+        assert_eq!(synchronizer.state(), StartupState::Ready);
+        assert_eq!(synchronizer.timed_out_state(), None);
+        // Starting again is a no-op.
+        assert_eq!(synchronizer.engine_started(), None);
+    }
+
+    #[test]
+    fn waits_for_the_configure_once_the_engine_runs() {
+        let mut synchronizer = ImplicitWindowStartupSynchronizer::new();
+        assert_eq!(synchronizer.engine_started(), None);
+        assert_eq!(synchronizer.state(), StartupState::WaitingForConfigure);
+        assert!(synchronizer.is_engine_running());
+        assert_eq!(
+            synchronizer.timed_out_state(),
+            Some(StartupState::WaitingForConfigure)
+        );
+
+        assert_eq!(
+            synchronizer.configure(size(800, 600), None),
+            Some(size(800, 600))
+        );
+        assert_eq!(synchronizer.state(), StartupState::Ready);
+        assert_eq!(
+            synchronizer.configure(size(1024, 768), Some(2)),
+            Some(size(1024, 768))
+        );
+    }
+
+    #[test]
+    fn keeps_the_latest_configure() {
+        let mut synchronizer = ImplicitWindowStartupSynchronizer::new();
+        synchronizer.configure(size(800, 600), Some(1));
+        synchronizer.configure(size(0, 0), Some(2));
+        synchronizer.configure(size(1024, 768), Some(3));
+
+        assert_eq!(synchronizer.engine_started(), Some(size(1024, 768)));
+    }
+
+    #[test]
+    fn ignores_the_configures_once_failed() {
+        let mut synchronizer = ImplicitWindowStartupSynchronizer::new();
+        synchronizer.configure(size(800, 600), Some(1));
+        synchronizer.engine_failed();
+
+        assert_eq!(synchronizer.state(), StartupState::Failed);
+        assert!(!synchronizer.is_engine_running());
+        assert_eq!(synchronizer.configure(size(1024, 768), Some(2)), None);
+        assert_eq!(synchronizer.engine_started(), None);
+        // A failed startup is reported by the run error, not as a timeout.
+        assert_eq!(synchronizer.timed_out_state(), None);
+    }
+}
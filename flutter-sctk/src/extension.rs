@@ -0,0 +1,226 @@
+//! Escape hatch for plugins and applications integrating with a specific
+//! compositor (e.g.: custom protocols, screencopy or the foreign toplevels),
+//! without forking the embedder.
+//!
+//! [`WaylandHandles`] gives access to the connection, the globals and the
+//! surfaces of the windows. They are handed to the plugins (see
+//! `PluginContext::platform_handles`) and are also available from
+//! `SctkApplication::wayland_handles`.
+//!
+//! The events of the objects created by an extension can't be handled by
+//! `SctkApplicationState`, whose `Dispatch` implementations are fixed at
+//! compile time. Instead, an extension owns an event queue (see
+//! `Connection::new_event_queue`) along with the state its own `Dispatch`
+//! implementations are for, and binds its globals with that queue. The
+//! application reads the events of every queue from the connection, and
+//! dispatches the queues of the extensions registered with
+//! `SctkApplication::register_protocol_extension` on the platform thread,
+//! once per event loop iteration.
+//!
+//! Extensions must uphold the following invariants, which the embedder
+//! relies on:
+//! - Never block on the connection (e.g.: `roundtrip` or
+//!   `blocking_dispatch`) outside of `SctkProtocolExtension::init`, and
+//!   never from the render thread. The application reads the connection
+//!   itself, so blocking stalls its event loop and the frames of the engine.
+//! - Never commit the surfaces of the windows, nor change their state
+//!   (e.g.: attach a buffer, or set their role). The embedder commits them
+//!   along with the frames of the engine. Sub-surfaces or protocol objects
+//!   created *for* a surface are fine.
+//! - Never destroy the objects which aren't owned by the extension.
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    ops::RangeInclusive,
+    sync::Arc,
+    thread::{self, ThreadId},
+};
+
+use flutter_engine::ffi::FlutterViewId;
+use parking_lot::RwLock;
+use smithay_client_toolkit::reexports::protocols::xdg::shell::client::xdg_toplevel::XdgToplevel;
+use thiserror::Error;
+use tracing::warn;
+use wayland_backend::client::ObjectId;
+use wayland_client::{
+    globals::{BindError, Global, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_surface::WlSurface},
+    Connection, Dispatch, DispatchError, Proxy, QueueHandle,
+};
+
+use crate::window::SctkFlutterWindow;
+
+struct WindowSurface {
+    wl_surface: WlSurface,
+    xdg_toplevel: Option<XdgToplevel>,
+}
+
+/// Handles of the Wayland objects of an engine, shared with its plugins.
+/// Cloning is cheap.
+#[derive(Clone)]
+pub struct WaylandHandles {
+    conn: Connection,
+    registry: WlRegistry,
+    surfaces: Arc<RwLock<HashMap<FlutterViewId, WindowSurface>>>,
+    platform_thread: ThreadId,
+}
+
+impl WaylandHandles {
+    /// Must be created on the platform thread.
+    pub(crate) fn new(conn: &Connection, registry: &WlRegistry) -> Self {
+        Self {
+            conn: conn.clone(),
+            registry: registry.clone(),
+            surfaces: Default::default(),
+            platform_thread: thread::current().id(),
+        }
+    }
+
+    pub(crate) fn add_window(&self, window: &SctkFlutterWindow) {
+        let surface = WindowSurface {
+            wl_surface: window.wl_surface(),
+            xdg_toplevel: window.xdg_toplevel(),
+        };
+        self.surfaces.write().insert(window.view_id(), surface);
+    }
+
+    pub(crate) fn clear_windows(&self) {
+        self.surfaces.write().clear();
+    }
+
+    /// The connection of the application, for creating the event queue of
+    /// an extension. See the module documentation for what it must not be
+    /// used for.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The globals currently advertised by the compositor.
+    pub fn globals(&self) -> Vec<Global> {
+        self.registry
+            .data::<GlobalListContents>()
+            .map(GlobalListContents::clone_list)
+            .unwrap_or_default()
+    }
+
+    /// Bind a global with the queue of |qh|, like `GlobalList::bind`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the maximum requested version is greater than the version
+    /// known to the protocol bindings.
+    pub fn bind<I, U, State>(
+        &self,
+        qh: &QueueHandle<State>,
+        version: RangeInclusive<u32>,
+        udata: U,
+    ) -> Result<I, BindError>
+    where
+        I: Proxy + 'static,
+        U: Send + Sync + 'static,
+        State: Dispatch<I, U> + 'static,
+    {
+        let interface = I::interface();
+        assert!(
+            *version.end() <= interface.version,
+            "Maximum version ({}) of {} is higher than the known version ({})",
+            version.end(),
+            interface.name,
+            interface.version,
+        );
+
+        let (name, advertised_version) = self
+            .globals()
+            .into_iter()
+            .find(|global| global.interface == interface.name)
+            .map(|global| (global.name, global.version))
+            .ok_or(BindError::NotPresent)?;
+        if advertised_version < *version.start() {
+            return Err(BindError::UnsupportedVersion);
+        }
+
+        let version = advertised_version.min(*version.end());
+        Ok(self.registry.bind(name, version, qh, udata))
+    }
+
+    /// Views of the windows of the engine.
+    pub fn view_ids(&self) -> Vec<FlutterViewId> {
+        self.surfaces.read().keys().copied().collect()
+    }
+
+    pub fn surface_id(&self, view_id: FlutterViewId) -> Option<ObjectId> {
+        let surfaces = self.surfaces.read();
+        surfaces
+            .get(&view_id)
+            .map(|surface| surface.wl_surface.id())
+    }
+
+    /// Id of the `xdg_toplevel` of the window, if it has this role (e.g.: not
+    /// for a layer surface).
+    pub fn toplevel_id(&self, view_id: FlutterViewId) -> Option<ObjectId> {
+        let surfaces = self.surfaces.read();
+        let surface = surfaces.get(&view_id)?;
+        surface.xdg_toplevel.as_ref().map(Proxy::id)
+    }
+
+    /// Run |f| with the surface of the window of |view_id|, e.g.: for
+    /// creating protocol objects for it. Returns `None` if there is no such
+    /// window, or when not called on the platform thread (where the window
+    /// may concurrently be destroyed).
+    ///
+    /// Note: The surface must not be committed, see the module
+    /// documentation.
+    pub fn with_surface<F, R>(&self, view_id: FlutterViewId, f: F) -> Option<R>
+    where
+        F: FnOnce(&WlSurface) -> R,
+    {
+        if thread::current().id() != self.platform_thread {
+            warn!(
+                "Ignoring an access to the surface of the {view_id} view off the platform thread"
+            );
+            return None;
+        }
+
+        let surfaces = self.surfaces.read();
+        surfaces.get(&view_id).map(|surface| f(&surface.wl_surface))
+    }
+}
+
+impl fmt::Debug for WaylandHandles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaylandHandles")
+            .field("registry", &self.registry.id())
+            .field("view_ids", &self.view_ids())
+            .finish()
+    }
+}
+
+/// Protocol handled outside of the embedder, see the module documentation.
+/// Its methods are invoked on the platform thread.
+pub trait SctkProtocolExtension {
+    /// Used for logging.
+    fn name(&self) -> &str;
+
+    /// Create the event queue of the extension and bind its globals (e.g.:
+    /// with [`WaylandHandles::bind`]). This is the only place where the
+    /// extension may roundtrip.
+    fn init(&mut self, handles: &WaylandHandles) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Dispatch the pending events of the queue of the extension, without
+    /// blocking (i.e.: `EventQueue::dispatch_pending`). Invoked once per
+    /// event loop iteration. The extension is removed if this fails.
+    fn dispatch_pending(&mut self) -> Result<(), DispatchError>;
+
+    /// Invoked when the application shuts down, before the engines.
+    fn shutdown(&mut self) {}
+}
+
+#[derive(Error, Debug)]
+pub enum SctkProtocolExtensionError {
+    #[error("Failed to initialize the {name} protocol extension: {source}")]
+    InitFailed {
+        name: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
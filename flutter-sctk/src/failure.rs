@@ -0,0 +1,286 @@
+//! Failure screen drawn by the embedder when the Dart app fails to start (see
+//! `ApplicationBuilder::with_failure_screen`), so that a broken bundle
+//! doesn't just leave a blank window.
+//!
+//! Two failures are detected: the engine failing to run (e.g.: incompatible
+//! or corrupt bundle), and the engine running without presenting a frame
+//! within the timeout (e.g.: `main` threw before `runApp`). The error text
+//! comes from the Dart log, which the engine hands to the embedder instead of
+//! printing it once a log callback is installed.
+//!
+//! The screen is drawn with the built-in bitmap font (see
+//! `crate::bitmap_font`) and blitted to the window with GL, and it is
+//! dismissed by the first frame of the app (e.g.: after a hot restart).
+use std::{collections::VecDeque, sync::Arc};
+
+use flutter_runner_api::{FailureScreenConfig, StartupFailure};
+use parking_lot::Mutex;
+
+use crate::{
+    bitmap_font::{self, Canvas, GLYPH_HEIGHT},
+    readback::flip_rows,
+};
+
+/// Lines of the Dart log kept for the report, while the screen only shows
+/// the last `FailureScreenConfig::log_lines` (the error may be followed by a
+/// long stack trace).
+const LOG_TAIL_CAPACITY: usize = 100;
+
+const BACKGROUND_COLOR: u32 = 0xFF20_2124;
+const TITLE_COLOR: u32 = 0xFFFF_8A80;
+const TEXT_COLOR: u32 = 0xFFE8_EAED;
+const LOG_COLOR: u32 = 0xFF9A_A0A6;
+
+/// Sizes (in logical pixels) of a glyph "pixel".
+const TITLE_GLYPH_SCALE: u32 = 4;
+const TEXT_GLYPH_SCALE: u32 = 2;
+const MARGIN: u32 = 32;
+/// Space between lines, in glyph "pixels".
+const LINE_SPACING: u32 = 3;
+/// Space between sections, in lines of text.
+const SECTION_SPACING: u32 = 1;
+
+/// Last lines logged by Dart, shared with the log callback of the engine
+/// (which is invoked on an engine thread).
+#[derive(Clone)]
+pub(crate) struct SctkLogTail {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl SctkLogTail {
+    pub(crate) fn new() -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(LOG_TAIL_CAPACITY))),
+        }
+    }
+
+    pub(crate) fn push(&self, message: &str) {
+        let mut lines = self.lines.lock();
+        for line in message.lines() {
+            if lines.len() == LOG_TAIL_CAPACITY {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.lines.lock().iter().cloned().collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextStyle {
+    Title,
+    Text,
+    Log,
+}
+
+impl TextStyle {
+    fn glyph_scale(self) -> u32 {
+        match self {
+            Self::Title => TITLE_GLYPH_SCALE,
+            Self::Text | Self::Log => TEXT_GLYPH_SCALE,
+        }
+    }
+
+    fn color(self) -> u32 {
+        match self {
+            Self::Title => TITLE_COLOR,
+            Self::Text => TEXT_COLOR,
+            Self::Log => LOG_COLOR,
+        }
+    }
+}
+
+/// Content of the failure screen, laid out again for each size.
+pub(crate) struct SctkFailureScreen {
+    sections: Vec<(TextStyle, Vec<String>)>,
+}
+
+impl SctkFailureScreen {
+    pub(crate) fn new(
+        app_name: Option<&str>,
+        failure: &StartupFailure,
+        config: &FailureScreenConfig,
+    ) -> Self {
+        let title = format!("{} failed to start", app_name.unwrap_or("The app"));
+        let mut sections = vec![
+            (TextStyle::Title, vec![title]),
+            (TextStyle::Text, vec![failure.summary()]),
+        ];
+        if let Some(support_message) = &config.support_message {
+            sections.push((TextStyle::Text, vec![support_message.clone()]));
+        }
+
+        let skipped = failure.log_tail.len().saturating_sub(config.log_lines);
+        let log_tail = failure.log_tail[skipped..].to_vec();
+        if !log_tail.is_empty() {
+            sections.push((TextStyle::Log, log_tail));
+        }
+
+        Self { sections }
+    }
+
+    /// Lines of text which fit in |width| physical pixels, along with their
+    /// style. Sections are separated by empty lines.
+    fn layout(&self, width: u32, scale: u32) -> Vec<(TextStyle, String)> {
+        let mut lines = Vec::new();
+        for (index, (style, texts)) in self.sections.iter().enumerate() {
+            if index > 0 {
+                for _ in 0..SECTION_SPACING {
+                    lines.push((*style, String::new()));
+                }
+            }
+
+            let advance = (bitmap_font::GLYPH_WIDTH + 1) * style.glyph_scale() * scale;
+            let columns = (width.saturating_sub(2 * MARGIN * scale) / advance).max(1) as usize;
+            for text in texts {
+                for line in wrap(text, columns) {
+                    lines.push((*style, line));
+                }
+            }
+        }
+        lines
+    }
+
+    /// Render the screen at |width|x|height| physical pixels, with |scale|
+    /// physical pixels per logical one. Returns RGBA pixels with the bottom
+    /// row first, as expected by `glTexImage2D`.
+    pub(crate) fn render(&self, width: u32, height: u32, scale: u32) -> Vec<u8> {
+        let mut data = vec![0; (width * height * 4) as usize];
+        let mut canvas = Canvas {
+            data: &mut data,
+            width,
+            height,
+        };
+        canvas.fill_rect(0, 0, width, height, BACKGROUND_COLOR);
+
+        let mut y = MARGIN * scale;
+        for (style, line) in self.layout(width, scale) {
+            let pixel_size = style.glyph_scale() * scale;
+            canvas.draw_text(MARGIN * scale, y, pixel_size, &line, style.color());
+            y += (GLYPH_HEIGHT + LINE_SPACING) * pixel_size;
+            if y >= height {
+                break;
+            }
+        }
+
+        // `Argb8888` is stored in little-endian order (i.e.: BGRA).
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        flip_rows(&mut data, (width * 4) as usize);
+        data
+    }
+}
+
+/// Wrap |text| at word boundaries into lines of at most |columns|
+/// characters. Words longer than a line are split.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.replace('\t', " ").split(' ') {
+            let mut word: Vec<char> = word.chars().collect();
+            let line_len = line.chars().count();
+            if line_len > 0 && line_len + 1 + word.len() > columns {
+                lines.push(std::mem::take(&mut line));
+            } else if line_len > 0 {
+                line.push(' ');
+            }
+
+            while line.is_empty() && word.len() > columns {
+                lines.push(word.drain(..columns).collect());
+            }
+            line.extend(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use flutter_runner_api::{FailureScreenConfig, StartupFailure, StartupFailureReason};
+
+    use super::{wrap, SctkFailureScreen, SctkLogTail, TextStyle, BACKGROUND_COLOR};
+
+    /// What the Dart log of a bundle whose `main` throws immediately looks
+    /// like.
+    fn throwing_main_log() -> SctkLogTail {
+        let log_tail = SctkLogTail::new();
+        log_tail.push("Initializing the configuration");
+        log_tail.push(
+            "Unhandled Exception: Bad state: No configuration found\n\
+             #0      main (package:kiosk/main.dart:12:3)\n\
+             #1      _runMain (dart:ui/hooks.dart:301:23)",
+        );
+        log_tail
+    }
+
+    #[test]
+    fn wraps_at_word_boundaries() {
+        assert_eq!(
+            wrap("no frame was rendered", 10),
+            ["no frame", "was", "rendered"]
+        );
+        assert_eq!(wrap("a\nb", 10), ["a", "b"]);
+        assert_eq!(
+            wrap("package:kiosk/main.dart", 10),
+            ["package:ki", "osk/main.d", "art"]
+        );
+    }
+
+    #[test]
+    fn reports_the_dart_error() {
+        let failure = StartupFailure {
+            reason: StartupFailureReason::NoFirstFrame(Duration::from_secs(10)),
+            log_tail: throwing_main_log().lines(),
+        };
+        assert!(failure
+            .summary()
+            .contains("Unhandled Exception: Bad state: No configuration found"));
+
+        let config = FailureScreenConfig::default()
+            .with_support_message("Call 555-0100")
+            .with_log_lines(2);
+        let screen = SctkFailureScreen::new(Some("Kiosk"), &failure, &config);
+        let lines = screen.layout(2000, 1);
+
+        assert_eq!(
+            lines[0],
+            (TextStyle::Title, "Kiosk failed to start".to_string())
+        );
+        assert!(lines.contains(&(TextStyle::Text, "Call 555-0100".to_string())));
+        // Only the last lines of the log are shown.
+        let log: Vec<_> = lines
+            .iter()
+            .filter(|(style, line)| *style == TextStyle::Log && !line.is_empty())
+            .collect();
+        assert_eq!(log.len(), 2);
+        assert!(log[1].1.contains("_runMain"));
+    }
+
+    #[test]
+    fn renders_flipped_rgba_pixels() {
+        let failure = StartupFailure {
+            reason: StartupFailureReason::RunFailed("invalid arguments".into()),
+            log_tail: vec![],
+        };
+        let screen = SctkFailureScreen::new(None, &failure, &FailureScreenConfig::default());
+        let (width, height) = (320, 240);
+        let pixels = screen.render(width, height, 1);
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+
+        let [_, red, green, blue] = BACKGROUND_COLOR.to_be_bytes();
+        let background = [red, green, blue, 0xFF];
+        // The bottom row comes first, and only has the background.
+        assert_eq!(&pixels[..4], &background);
+        // The title is drawn at the top.
+        let top_rows = &pixels[pixels.len() - (width * 64 * 4) as usize..];
+        assert!(top_rows.chunks_exact(4).any(|pixel| pixel != background));
+    }
+}
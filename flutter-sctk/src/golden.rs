@@ -0,0 +1,470 @@
+//! Capture of frame sequences to disk, and comparison of captures, for golden
+//! tests of full bundles (i.e.: including the embedder rendering path).
+//!
+//! Captures are only reproducible with deterministic frame times (see
+//! `ApplicationBuilder::with_fake_time_base`), otherwise the animations
+//! progress with the actual rendering speed, and with a fixed pixel ratio
+//! (see `ApplicationBuilder::with_pixel_ratio`).
+//!
+//! There is no headless backend: the frames are captured from a regular
+//! window, whose size is given by the compositor. Captures are compared
+//! across runs of the same compositor setup (e.g.: a headless compositor
+//! with a fixed output scale), and require a running engine, so the
+//! repository doesn't include golden captures of a bundle.
+//!
+//! A capture directory contains the `frame-NNNN.png` files and a
+//! `manifest.txt` listing the size and hash of each frame, which is small
+//! enough to be committed and compared against.
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+use tracing::error;
+
+use crate::readback::{SctkFrameBuffer, SctkFrameReadback};
+
+const MANIFEST_FILE_NAME: &str = "manifest.txt";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Error, Debug)]
+pub enum GoldenError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Encoding(#[from] png::EncodingError),
+
+    #[error(transparent)]
+    Decoding(#[from] png::DecodingError),
+
+    #[error("Unsupported image format (expected 8 bits RGBA): {0}")]
+    UnsupportedImage(PathBuf),
+
+    #[error("Invalid manifest line: {0}")]
+    InvalidManifest(String),
+}
+
+/// A recorded frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenFrame {
+    pub file_name: String,
+    pub width: u32,
+    pub height: u32,
+    /// FNV-1a hash of the size and the pixels. Not a cryptographic hash.
+    pub hash: u64,
+}
+
+/// The frames of a capture, in presentation order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GoldenManifest {
+    pub frames: Vec<GoldenFrame>,
+}
+
+impl GoldenManifest {
+    pub fn read(dir: &Path) -> Result<Self, GoldenError> {
+        let content = fs::read_to_string(dir.join(MANIFEST_FILE_NAME))?;
+        let frames = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(parse_manifest_line)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { frames })
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<(), GoldenError> {
+        let content: String = self
+            .frames
+            .iter()
+            .map(|frame| {
+                format!(
+                    "{} {}x{} {:016x}\n",
+                    frame.file_name, frame.width, frame.height, frame.hash
+                )
+            })
+            .collect();
+        fs::write(dir.join(MANIFEST_FILE_NAME), content)?;
+        Ok(())
+    }
+}
+
+fn parse_manifest_line(line: &str) -> Result<GoldenFrame, GoldenError> {
+    let invalid = || GoldenError::InvalidManifest(line.to_string());
+
+    let mut fields = line.split_whitespace();
+    let (Some(file_name), Some(size), Some(hash), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err(invalid());
+    };
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+
+    Ok(GoldenFrame {
+        file_name: file_name.to_string(),
+        width: width.parse().map_err(|_| invalid())?,
+        height: height.parse().map_err(|_| invalid())?,
+        hash: u64::from_str_radix(hash, 16).map_err(|_| invalid())?,
+    })
+}
+
+#[derive(Default)]
+struct RecorderState {
+    manifest: GoldenManifest,
+    error: Option<GoldenError>,
+}
+
+/// Writes the first `count` presented frames of a window to numbered PNG
+/// files, along with their manifest.
+///
+/// ```ignore
+/// let recorder = SctkFrameRecorder::new("target/goldens/counter", 3);
+/// app.set_frame_readback(Some(recorder.readback()));
+/// // Once `recorder.is_done()`:
+/// let manifest = recorder.finish()?;
+/// ```
+#[derive(Clone)]
+pub struct SctkFrameRecorder {
+    dir: PathBuf,
+    count: usize,
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl SctkFrameRecorder {
+    pub fn new(dir: impl Into<PathBuf>, count: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            count,
+            state: Default::default(),
+        }
+    }
+
+    /// Readback writing the frames. The frames are still presented on screen,
+    /// so that the compositor keeps sending frame callbacks.
+    pub fn readback(&self) -> SctkFrameReadback {
+        let recorder = self.clone();
+        SctkFrameReadback::new(move |frame| recorder.record(frame))
+    }
+
+    /// Whether all the frames were recorded, or the recording failed.
+    pub fn is_done(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.error.is_some() || state.manifest.frames.len() >= self.count
+    }
+
+    /// Write the manifest of the frames recorded so far, or report the error
+    /// which stopped the recording.
+    pub fn finish(&self) -> Result<GoldenManifest, GoldenError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(err) = state.error.take() {
+            return Err(err);
+        }
+        state.manifest.write(&self.dir)?;
+        Ok(state.manifest.clone())
+    }
+
+    // Note: This callback is executed on the *render* thread.
+    fn record(&self, frame: &SctkFrameBuffer) {
+        let mut state = self.state.lock().unwrap();
+        if state.error.is_some() || state.manifest.frames.len() >= self.count {
+            return;
+        }
+
+        let image = GoldenImage::from_frame(frame);
+        let file_name = format!("frame-{:04}.png", state.manifest.frames.len());
+        let result = fs::create_dir_all(&self.dir)
+            .map_err(GoldenError::from)
+            .and_then(|()| image.write(&self.dir.join(&file_name)));
+
+        match result {
+            Ok(()) => state.manifest.frames.push(GoldenFrame {
+                file_name,
+                width: image.width,
+                height: image.height,
+                hash: image.hash(),
+            }),
+            Err(err) => {
+                error!("Failed to record {}: {}", file_name, err);
+                state.error = Some(err);
+            }
+        }
+    }
+}
+
+/// Tightly packed RGBA pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GoldenImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl GoldenImage {
+    fn from_frame(frame: &SctkFrameBuffer) -> Self {
+        let row_len = frame.width as usize * frame.format.bytes_per_pixel() as usize;
+        let data = frame
+            .data
+            .chunks(frame.stride as usize)
+            .take(frame.height as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect();
+
+        Self {
+            width: frame.width,
+            height: frame.height,
+            data,
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self, GoldenError> {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(GoldenError::UnsupportedImage(path.to_path_buf()));
+        }
+        data.truncate(info.buffer_size());
+
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    fn write(&self, path: &Path) -> Result<(), GoldenError> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn hash(&self) -> u64 {
+        let size = [self.width.to_le_bytes(), self.height.to_le_bytes()];
+        let bytes = size.iter().flatten().chain(&self.data);
+        bytes.fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+        })
+    }
+}
+
+/// Outcome of the comparison of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoldenFrameResult {
+    Match,
+    /// The frame only exists in one of the captures.
+    Missing,
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// |pixels| differ by more than the tolerance. |diff_image| highlights
+    /// them in red, over a dimmed grayscale version of the actual frame.
+    Mismatch { pixels: usize, diff_image: PathBuf },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GoldenComparison {
+    /// Result of each frame, by file name.
+    pub frames: Vec<(String, GoldenFrameResult)>,
+}
+
+impl GoldenComparison {
+    pub fn is_match(&self) -> bool {
+        self.frames
+            .iter()
+            .all(|(_, result)| *result == GoldenFrameResult::Match)
+    }
+}
+
+/// Compare the frames captured in |expected_dir| with the ones captured in
+/// |actual_dir|. Pixels match when none of their channels differ by more than
+/// |tolerance|. The diff images of the mismatching frames are written to
+/// |actual_dir| (e.g.: `diff-0002.png` for `frame-0002.png`).
+pub fn compare(
+    expected_dir: &Path,
+    actual_dir: &Path,
+    tolerance: u8,
+) -> Result<GoldenComparison, GoldenError> {
+    let mut file_names = frame_file_names(expected_dir)?;
+    file_names.extend(frame_file_names(actual_dir)?);
+    file_names.sort();
+    file_names.dedup();
+
+    let mut comparison = GoldenComparison::default();
+    for file_name in file_names {
+        let expected_path = expected_dir.join(&file_name);
+        let actual_path = actual_dir.join(&file_name);
+        if !expected_path.is_file() || !actual_path.is_file() {
+            comparison.frames.push((file_name, GoldenFrameResult::Missing));
+            continue;
+        }
+
+        let expected = GoldenImage::read(&expected_path)?;
+        let actual = GoldenImage::read(&actual_path)?;
+        let diff_path = actual_dir.join(file_name.replacen("frame-", "diff-", 1));
+        let result = compare_images(&expected, &actual, tolerance, diff_path)?;
+        comparison.frames.push((file_name, result));
+    }
+
+    Ok(comparison)
+}
+
+fn frame_file_names(dir: &Path) -> Result<Vec<String>, GoldenError> {
+    let mut file_names = vec![];
+    for entry in fs::read_dir(dir)? {
+        let file_name = entry?.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with("frame-") && file_name.ends_with(".png") {
+            file_names.push(file_name);
+        }
+    }
+    Ok(file_names)
+}
+
+fn compare_images(
+    expected: &GoldenImage,
+    actual: &GoldenImage,
+    tolerance: u8,
+    diff_path: PathBuf,
+) -> Result<GoldenFrameResult, GoldenError> {
+    if (expected.width, expected.height) != (actual.width, actual.height) {
+        return Ok(GoldenFrameResult::SizeMismatch {
+            expected: (expected.width, expected.height),
+            actual: (actual.width, actual.height),
+        });
+    }
+
+    let mut pixels = 0;
+    let mut diff = Vec::with_capacity(actual.data.len());
+    for (expected, actual) in expected.data.chunks_exact(4).zip(actual.data.chunks_exact(4)) {
+        let is_different = expected
+            .iter()
+            .zip(actual)
+            .any(|(expected, actual)| expected.abs_diff(*actual) > tolerance);
+
+        if is_different {
+            pixels += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (u32::from(actual[0]) * 299
+                + u32::from(actual[1]) * 587
+                + u32::from(actual[2]) * 114)
+                / 1000;
+            let dimmed = (luma / 3) as u8;
+            diff.extend_from_slice(&[dimmed, dimmed, dimmed, 255]);
+        }
+    }
+
+    if pixels == 0 {
+        return Ok(GoldenFrameResult::Match);
+    }
+
+    let diff_image = GoldenImage {
+        width: actual.width,
+        height: actual.height,
+        data: diff,
+    };
+    diff_image.write(&diff_path)?;
+
+    Ok(GoldenFrameResult::Mismatch {
+        pixels,
+        diff_image: diff_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use crate::readback::{SctkFrameBuffer, SctkPixelFormat};
+
+    use super::{compare, GoldenFrameResult, GoldenImage, GoldenManifest, SctkFrameRecorder};
+
+    fn capture_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "flutter-rs-golden-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn record(dir: &Path, frames: &[[u8; 4]]) -> GoldenManifest {
+        let recorder = SctkFrameRecorder::new(dir, 2);
+        for pixel in frames {
+            // 2x2 frame, with a padded stride.
+            let mut data = vec![];
+            for _ in 0..2 {
+                data.extend_from_slice(pixel);
+                data.extend_from_slice(pixel);
+                data.extend_from_slice(&[0; 4]);
+            }
+            recorder.record(&SctkFrameBuffer {
+                width: 2,
+                height: 2,
+                stride: 12,
+                format: SctkPixelFormat::Rgba8888,
+                data: &data,
+            });
+        }
+        assert!(recorder.is_done());
+        recorder.finish().unwrap()
+    }
+
+    #[test]
+    fn records_frames_and_manifest() {
+        let dir = capture_dir("record");
+        let manifest = record(&dir, &[[10, 20, 30, 255], [40, 50, 60, 255], [0; 4]]);
+
+        assert_eq!(manifest.frames.len(), 2);
+        assert_eq!(manifest.frames[0].file_name, "frame-0000.png");
+        assert_eq!((manifest.frames[1].width, manifest.frames[1].height), (2, 2));
+        assert_ne!(manifest.frames[0].hash, manifest.frames[1].hash);
+        assert_eq!(GoldenManifest::read(&dir).unwrap(), manifest);
+        assert!(!dir.join("frame-0002.png").exists());
+
+        let image = GoldenImage::read(&dir.join("frame-0001.png")).unwrap();
+        assert_eq!(image.data, [40, 50, 60, 255].repeat(4));
+        assert_eq!(image.hash(), manifest.frames[1].hash);
+    }
+
+    #[test]
+    fn compares_captures() {
+        let expected = capture_dir("expected");
+        let actual = capture_dir("actual");
+        record(&expected, &[[10, 20, 30, 255], [40, 50, 60, 255]]);
+        record(&actual, &[[12, 20, 30, 255], [40, 90, 60, 255]]);
+
+        let comparison = compare(&expected, &actual, 2).unwrap();
+        assert!(!comparison.is_match());
+        assert_eq!(comparison.frames[0].1, GoldenFrameResult::Match);
+        let GoldenFrameResult::Mismatch { pixels, diff_image } = &comparison.frames[1].1 else {
+            panic!("Unexpected result: {:?}", comparison.frames[1]);
+        };
+        assert_eq!(*pixels, 4);
+        assert_eq!(*diff_image, actual.join("diff-0001.png"));
+        let diff = GoldenImage::read(diff_image).unwrap();
+        assert_eq!(diff.data, [255, 0, 0, 255].repeat(4));
+
+        assert!(compare(&expected, &actual, 40).unwrap().is_match());
+
+        fs::remove_file(actual.join("frame-0001.png")).unwrap();
+        let comparison = compare(&expected, &actual, 40).unwrap();
+        assert_eq!(comparison.frames[1].1, GoldenFrameResult::Missing);
+    }
+}
@@ -1,69 +1,112 @@
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
     ffi::{c_void, CStr, CString},
+    future::Future,
     iter::zip,
     num::NonZeroU32,
+    rc::Rc,
     sync::{
-        atomic::{AtomicBool, AtomicIsize, Ordering},
+        atomic::{AtomicBool, AtomicIsize, AtomicU64, Ordering},
         Arc, Mutex, RwLock, Weak,
     },
     time::Duration,
 };
 
-use ashpd::desktop::settings::{ColorScheme, Settings};
-use dpi::PhysicalSize;
+use ashpd::{
+    desktop::{
+        inhibit::{InhibitFlags, InhibitProxy},
+        settings::{ColorScheme, Settings},
+        Request,
+    },
+    WindowIdentifier,
+};
+use dpi::{LogicalPosition, LogicalSize, PhysicalSize};
 use flutter_engine::{
+    codec::Value,
     compositor::{
         CompositorCollectBackingStoreError, CompositorCreateBackingStoreError,
         CompositorPresentError, FlutterCompositorHandler,
     },
     ffi::{
         FlutterBackingStore, FlutterBackingStoreConfig, FlutterBackingStoreDescription,
-        FlutterKeyEventDeviceType, FlutterKeyEventType, FlutterLogicalKey,
-        FlutterOpenGLBackingStore, FlutterOpenGLBackingStoreFramebuffer, FlutterOpenGLFramebuffer,
-        FlutterPhysicalKey, FlutterPresentViewInfo,
+        FlutterKeyEventDeviceType, FlutterKeyEventType, FlutterLayer, FlutterLayerContent,
+        FlutterLogicalKey, FlutterOpenGLBackingStore, FlutterOpenGLBackingStoreFramebuffer,
+        FlutterOpenGLFramebuffer, FlutterPhysicalKey, FlutterPresentViewInfo,
     },
+    plugins::PluginNames,
     tasks::TaskRunnerHandler,
-    FlutterEngineWeakRef, FlutterVsyncHandler,
+    FlutterEngine, FlutterEngineWeakRef, FlutterVsyncHandler,
 };
-use flutter_engine_api::FlutterOpenGLHandler;
-use flutter_engine_sys::FlutterEngineGetCurrentTime;
+use flutter_engine_api::{DamageRect, FlutterOpenGLHandler, FlutterPresentInfo};
 use flutter_glutin::{
     context::{Context, ResourceContext},
     gl,
 };
+use flutter_input::keyboard::{XkbLogicalKey, XkbPhysicalKey};
 use flutter_plugins::{
+    anchor::PopupPlacement,
+    color::ColorInfo,
     keyboard::{KeyboardStateError, KeyboardStateHandler},
+    logging::LoggingHandler,
     mousecursor::{MouseCursorError, MouseCursorHandler, SystemMouseCursor},
-    platform::{AppSwitcherDescription, MimeError, PlatformHandler},
+    platform::{AppSwitcherDescription, PlatformError, PlatformHandler},
+    screensaver::ScreenSaverHandler,
     settings::{PlatformBrightness, SettingsPlugin},
-    textinput::TextInputHandler,
+    text_toolbar::{TextToolbarAction, TextToolbarHandler, TextToolbarRect},
+    textinput::{TextInputFocusInfo, TextInputHandler},
+    window::{
+        MarginParams, PositionParams, SizeParams, Visibility, WindowCapabilities, WindowHandler,
+    },
 };
+use flutter_runner_api::LayerMargin;
 use futures_lite::StreamExt;
 use smithay_client_toolkit::{
-    reexports::{calloop::LoopSignal, protocols::xdg::shell::client::xdg_toplevel::XdgToplevel},
+    output::OutputData,
+    reexports::{
+        calloop::{channel::Sender, LoopSignal},
+        csd_frame::WindowState,
+        protocols::{
+            wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::{
+                Shape, WpCursorShapeDeviceV1,
+            },
+            xdg::shell::client::xdg_toplevel::XdgToplevel,
+        },
+    },
     seat::{
         keyboard::{KeyEvent, Keysym, Modifiers},
-        pointer::{CursorIcon, PointerData, PointerDataExt, ThemedPointer},
+        pointer::{CursorIcon, ThemedPointer},
     },
+    shell::xdg::window::Window,
 };
 use smithay_clipboard::Clipboard;
 use thiserror::Error;
 use tracing::{error, trace, warn};
 use wayland_backend::client::ObjectId;
 use wayland_client::{
-    protocol::{wl_display::WlDisplay, wl_surface::WlSurface},
+    protocol::{
+        wl_display::WlDisplay, wl_output::WlOutput, wl_pointer::WlPointer, wl_surface::WlSurface,
+    },
     Connection, Proxy, QueueHandle,
 };
 
 use crate::{
-    application::SctkApplicationState,
-    keyboard::{SctkKeyEvent, SctkLogicalKey, SctkPhysicalKey},
+    application::{SctkApplicationRequest, SctkApplicationState},
+    damage::{to_egl_rects, SctkDamageHistory},
+    engine::SctkEngineId,
+    keyboard::SctkKeyEvent,
+    mirror::SctkMirrors,
+    overlay::{plan_platform_view, OverlayRect, SctkOverlayGlobals, SctkVideoOverlays},
+    pointer::mouse_device_id,
+    quad::{QuadOrigin, SctkQuadProgram},
+    readback::{flip_rows, SctkFrameBuffer, SctkFrameReadback, SctkPixelFormat},
+    window_state::WindowStateRequest,
+    zoom::clamp_zoom,
 };
 
 use crate::window::SctkFlutterWindowInner;
 
-const WINDOW_FRAMEBUFFER_ID: u32 = 0;
+pub(crate) const WINDOW_FRAMEBUFFER_ID: u32 = 0;
 
 pub(crate) const FRAME_INTERVAL_60_HZ_IN_NANOS: u64 = 1_000_000_000 / 60; // 60Hz per second in nanos
 
@@ -73,6 +116,7 @@ pub(crate) struct SctkOpenGLHandler {
     context: Arc<Mutex<Context>>,
     resource_context: Arc<Mutex<ResourceContext>>,
     current_frame_size: Arc<RwLock<PhysicalSize<u32>>>,
+    damage_history: Arc<Mutex<SctkDamageHistory>>,
 }
 
 impl SctkOpenGLHandler {
@@ -86,6 +130,7 @@ impl SctkOpenGLHandler {
             context,
             resource_context,
             current_frame_size: Default::default(),
+            damage_history: Default::default(),
         }
     }
 
@@ -97,28 +142,55 @@ impl SctkOpenGLHandler {
     fn load_current_frame_size(&self) -> PhysicalSize<u32> {
         *self.current_frame_size.read().unwrap()
     }
+
+    /// See `crate::damage`.
+    pub(crate) fn set_full_damage_forced(&self, forced: bool) {
+        self.damage_history
+            .lock()
+            .unwrap()
+            .set_full_damage_forced(forced);
+    }
 }
 
 // Note: These callbacks are executed on the *render* thread.
 impl FlutterOpenGLHandler for SctkOpenGLHandler {
     fn present(&self) -> bool {
+        // Without damage, the whole frame is presented.
+        self.present_with_info(FlutterPresentInfo::default())
+    }
+
+    fn present_with_info(&self, info: FlutterPresentInfo) -> bool {
         let frame_size = self.load_current_frame_size();
+        let window = self.window.upgrade().unwrap();
         // Check if this frame can be presented. This resizes the surface if a
         // resize is pending and |frame_size| matches the target size.
-        if !self
-            .window
-            .upgrade()
-            .unwrap()
-            .on_frame_generated(frame_size)
-        {
+        if !window.on_frame_generated(frame_size) {
             return false;
         }
 
-        if !self.context.lock().unwrap().present() {
+        let Some(_visible) = window.lock_visible() else {
+            window.on_frame_discarded();
+            return true;
+        };
+
+        let is_full_damage_forced = self.damage_history.lock().unwrap().is_full_damage_forced();
+        // An empty damage presents the whole frame.
+        let frame_damage: &[DamageRect] = if is_full_damage_forced {
+            &[]
+        } else {
+            info.frame_damage.as_slice()
+        };
+
+        let damage = to_egl_rects(frame_damage, frame_size.height);
+        if !self.context.lock().unwrap().present_with_damage(&damage) {
             return false;
         }
+        self.damage_history
+            .lock()
+            .unwrap()
+            .push(frame_size, frame_damage);
 
-        self.window.upgrade().unwrap().on_frame_presented();
+        window.on_frame_presented();
 
         true
     }
@@ -138,6 +210,15 @@ impl FlutterOpenGLHandler for SctkOpenGLHandler {
         0
     }
 
+    fn existing_damage(&self, _fbo_id: u32) -> Option<DamageRect> {
+        // The engine always renders into the back buffer of the surface.
+        let age = self.context.lock().unwrap().buffer_age();
+        self.damage_history
+            .lock()
+            .unwrap()
+            .existing_damage(age, self.load_current_frame_size())
+    }
+
     fn make_resource_current(&self) -> bool {
         self.resource_context.lock().unwrap().make_current()
     }
@@ -153,10 +234,29 @@ pub struct SctkCompositorHandler {
     context: Arc<Mutex<Context>>,
     gl: gl::Gl,
     format: u32,
+    max_texture_size: u32,
+    /// Set from the platform thread, while its callback is called from the
+    /// *raster* thread when presenting (see `Self::read_frame`).
+    frame_readback: Arc<RwLock<Option<SctkFrameReadback>>>,
+    readback_buffer: Arc<Mutex<Vec<u8>>>,
+    mirrors: SctkMirrors,
+    overlays: SctkVideoOverlays,
+    /// Created on first use, i.e.: once a view contains several layers.
+    quad_program: Arc<Mutex<Option<SctkQuadProgram>>>,
 }
 
+// `gl::Gl` only holds the function pointers loaded in `new`, which are never
+// changed afterwards, so sharing the handler between threads is fine.
+unsafe impl Sync for SctkCompositorHandler {}
+
 impl SctkCompositorHandler {
-    pub fn new(window: Weak<SctkFlutterWindowInner>, context: Arc<Mutex<Context>>) -> Self {
+    pub(crate) fn new(
+        window: Weak<SctkFlutterWindowInner>,
+        context: Arc<Mutex<Context>>,
+        engine: FlutterEngineWeakRef,
+        qh: QueueHandle<SctkApplicationState>,
+        overlay_globals: Option<SctkOverlayGlobals>,
+    ) -> Self {
         context.lock().unwrap().make_current();
 
         let gl = gl::Gl::load_with(|symbol| {
@@ -164,17 +264,263 @@ impl SctkCompositorHandler {
             context.lock().unwrap().get_proc_address(proc.as_c_str())
         });
 
+        let mut max_texture_size = 0;
+        unsafe { gl.GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_texture_size) };
+
         context.lock().unwrap().make_not_current();
 
         Self {
             window,
+            mirrors: SctkMirrors::new(context.clone(), gl.clone()),
+            overlays: SctkVideoOverlays::new(overlay_globals, qh, engine, gl.clone()),
+            quad_program: Default::default(),
             context,
             gl,
             // TODO: Use similar logic for detecting supported formats as the
             // Windows embedder:
             // https://github.com/flutter/engine/blob/a6acfa4/shell/platform/windows/compositor_opengl.cc#L23-L34
             format: gl::RGBA8,
+            max_texture_size: u32::try_from(max_texture_size).unwrap_or_default(),
+            frame_readback: Default::default(),
+            readback_buffer: Default::default(),
+        }
+    }
+
+    /// Maximum texture size supported by the GL implementation, or `0` if it
+    /// could not be queried.
+    pub(crate) fn max_texture_size(&self) -> u32 {
+        self.max_texture_size
+    }
+
+    /// Note: The callback of |frame_readback| is executed on the *raster*
+    /// thread, inside `present`, which is why it must be `Send` and `Sync`.
+    pub(crate) fn set_frame_readback(&self, frame_readback: Option<SctkFrameReadback>) {
+        *self.frame_readback.write().unwrap() = frame_readback;
+    }
+
+    pub(crate) fn mirrors(&self) -> &SctkMirrors {
+        &self.mirrors
+    }
+
+    fn load_frame_readback(&self) -> Option<SctkFrameReadback> {
+        self.frame_readback.read().unwrap().clone()
+    }
+
+    /// Read the content of the |source_id| framebuffer and hand it to the
+    /// readback callback. The context must be current.
+    fn read_frame(
+        &self,
+        frame_readback: &SctkFrameReadback,
+        source_id: u32,
+        size: PhysicalSize<u32>,
+    ) {
+        let format = SctkPixelFormat::Rgba8888;
+        let stride = size.width * format.bytes_per_pixel();
+
+        let mut buffer = self.readback_buffer.lock().unwrap();
+        buffer.resize(stride as usize * size.height as usize, 0);
+
+        unsafe {
+            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, source_id);
+            self.gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+            self.gl.ReadPixels(
+                0,
+                0,
+                size.width as i32,
+                size.height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                buffer.as_mut_ptr() as *mut c_void,
+            );
+        }
+
+        flip_rows(&mut buffer, stride as usize);
+
+        (frame_readback.callback)(&SctkFrameBuffer {
+            width: size.width,
+            height: size.height,
+            stride,
+            format,
+            data: &buffer,
+        });
+    }
+
+    /// Present |pixels| (RGBA, bottom row first) of |size| to the window, for
+    /// content drawn by the embedder itself (see `crate::failure`). It must
+    /// not be called while the engine renders to the window.
+    pub(crate) fn present_pixels(&self, pixels: &[u8], size: PhysicalSize<u32>) -> bool {
+        if !self.context.lock().unwrap().make_current() {
+            error!("Unable to make context current");
+            return false;
+        }
+
+        let mut texture_id = 0;
+        let mut framebuffer_id = 0;
+        unsafe {
+            self.gl.GenTextures(1, &mut texture_id);
+            self.gl.BindTexture(gl::TEXTURE_2D, texture_id);
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            self.gl.TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8.try_into().unwrap(),
+                size.width as i32,
+                size.height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const c_void,
+            );
+            self.gl.BindTexture(gl::TEXTURE_2D, 0);
+
+            self.gl.GenFramebuffers(1, &mut framebuffer_id);
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_id);
+            self.gl.FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture_id,
+                0,
+            );
+        }
+
+        self.blit_to_window(framebuffer_id, size);
+
+        unsafe {
+            self.gl
+                .BindFramebuffer(gl::FRAMEBUFFER, WINDOW_FRAMEBUFFER_ID);
+            self.gl.DeleteFramebuffers(1, &framebuffer_id);
+            self.gl.DeleteTextures(1, &texture_id);
+        }
+
+        let mut context = self.context.lock().unwrap();
+        let presented = context.present();
+        context.make_not_current();
+        presented
+    }
+
+    /// Copy the content of the |source_id| framebuffer to the window. The
+    /// context must be current.
+    fn blit_to_window(&self, source_id: u32, size: PhysicalSize<u32>) {
+        unsafe {
+            // Disable the scissor test as it can affect blit operations.
+            // Prevents regressions like: https://github.com/flutter/flutter/issues/140828
+            // See OpenGL specification version 4.6, section 18.3.1.
+            self.gl.Disable(gl::SCISSOR_TEST);
+
+            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, source_id);
+            self.gl
+                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, WINDOW_FRAMEBUFFER_ID);
+
+            let width = size.width as i32;
+            let height = size.height as i32;
+
+            self.gl.BlitFramebuffer(
+                0,                    // srcX0
+                0,                    // srcY0
+                width,                // srcX1
+                height,               // srcY1
+                0,                    // dstX0
+                0,                    // dstY0
+                width,                // dstX1
+                height,               // dstY1
+                gl::COLOR_BUFFER_BIT, // mask
+                gl::NEAREST,          // filter
+            );
+        }
+    }
+
+    /// Composite |layers| to the window, from bottom to top. Platform views
+    /// showing dmabuf frames are either promoted to a subsurface (see
+    /// `overlay`), or drawn like the backing stores. The context must be
+    /// current.
+    fn composite_layers(
+        &self,
+        window: &SctkFlutterWindowInner,
+        layers: &[FlutterLayer],
+        frame_size: PhysicalSize<u32>,
+        can_promote: bool,
+    ) -> Result<(), CompositorPresentError> {
+        let mut quad_program = self.quad_program.lock().unwrap();
+        if quad_program.is_none() {
+            *quad_program = SctkQuadProgram::new(&self.gl);
+        }
+        let quad_program = quad_program.as_ref().ok_or(CompositorPresentError::PresentFailed(
+            "Unable to create the program compositing the layers".into(),
+        ))?;
+
+        unsafe {
+            self.gl.Disable(gl::SCISSOR_TEST);
+            self.gl
+                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, WINDOW_FRAMEBUFFER_ID);
+            let [red, green, blue, alpha] = window.clear_color().unwrap_or_default();
+            self.gl.ClearColor(red, green, blue, alpha);
+            self.gl.Clear(gl::COLOR_BUFFER_BIT);
+        }
+
+        let mut promoted = HashSet::new();
+        let mut drawn = HashSet::new();
+        for (index, layer) in layers.iter().enumerate() {
+            let rect = OverlayRect::new(layer.offset, layer.size);
+
+            let platform_view = match &layer.content {
+                FlutterLayerContent::BackingStore(_) => {
+                    let texture_id = layer
+                        .content
+                        .get_opengl_backing_store_texture_id()
+                        .ok_or(CompositorPresentError::PresentFailed(
+                            "Unable to retrieve texture from layer".into(),
+                        ))?;
+
+                    quad_program.draw(
+                        &self.gl,
+                        texture_id,
+                        QuadOrigin::BottomLeft,
+                        rect,
+                        frame_size,
+                        1.0,
+                    );
+                    continue;
+                }
+                FlutterLayerContent::PlatformView(platform_view) => platform_view,
+            };
+
+            // Only the platform views showing dmabuf frames are supported.
+            let identifier = platform_view.identifier;
+            let Some(frame) = self.overlays.frame(identifier) else {
+                continue;
+            };
+
+            let occluders = painted_rects(&layers[index + 1..]);
+            let plan = plan_platform_view(rect, &platform_view.mutations, &occluders);
+
+            if plan.promote && can_promote && self.overlays.can_promote() {
+                let scale = window.load_current_scale_factor();
+                self.overlays
+                    .promote(identifier, window.wl_surface(), frame, rect, scale);
+                punch_hole(&self.gl, rect, frame_size);
+                promoted.insert(identifier);
+                continue;
+            }
+
+            let context = self.context.lock().unwrap();
+            if self.overlays.draw(
+                &context,
+                quad_program,
+                identifier,
+                frame,
+                rect,
+                &plan,
+                frame_size,
+            ) {
+                drawn.insert(identifier);
+            } else {
+                trace!("Unable to draw the frame of platform view {}", identifier);
+            }
         }
+
+        self.overlays.finish(&promoted, &drawn);
+        Ok(())
     }
 
     fn clear(&self) -> Result<(), CompositorPresentError> {
@@ -186,14 +532,24 @@ impl SctkCompositorHandler {
             ));
         }
 
+        let Some(_visible) = window.lock_visible() else {
+            window.on_frame_discarded();
+            return Ok(());
+        };
+
         if !self.context.lock().unwrap().make_current() {
             return Err(CompositorPresentError::PresentFailed(
                 "Unable to make context current".into(),
             ));
         }
 
+        if !self.overlays.is_empty() {
+            self.overlays.finish(&HashSet::new(), &HashSet::new());
+        }
+
         unsafe {
-            self.gl.ClearColor(0.0, 0.0, 0.0, 0.0);
+            let [red, green, blue, alpha] = window.clear_color().unwrap_or_default();
+            self.gl.ClearColor(red, green, blue, alpha);
             self.gl
                 .Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
         };
@@ -215,25 +571,36 @@ impl FlutterCompositorHandler for SctkCompositorHandler {
             return self.clear();
         }
 
-        // TODO: Support compositing layers and platform views.
-        debug_assert_eq!(info.layers.len(), 1);
-        let layer = info.layers.first().unwrap();
-        debug_assert!(layer.offset.x == 0.0 && layer.offset.y == 0.0);
+        let window = self.window.upgrade().unwrap();
 
-        let source_id = layer
-            .content
-            .get_opengl_backing_store_framebuffer_name()
-            .ok_or(CompositorPresentError::PresentFailed(
-                "Unable to retrieve framebuffer name from layer".into(),
-            ))?;
+        // A single backing store is blitted to the window, while platform
+        // views, or a background color, require compositing the layers.
+        let single_source_id = match info.layers.as_slice() {
+            [layer] if window.clear_color().is_none() => {
+                debug_assert!(layer.offset.x == 0.0 && layer.offset.y == 0.0);
+                layer.content.get_opengl_backing_store_framebuffer_name()
+            }
+            _ => None,
+        };
 
         // TODO: Investigate if conversion to `u32` is correct
-        let frame_size = PhysicalSize::<u32>::new(
-            layer.size.width.round() as u32,
-            layer.size.height.round() as u32,
-        );
-
-        let window = self.window.upgrade().unwrap();
+        let frame_size = info
+            .layers
+            .iter()
+            .find(|layer| matches!(layer.content, FlutterLayerContent::BackingStore(_)))
+            .map(|layer| {
+                PhysicalSize::<u32>::new(
+                    layer.size.width.round() as u32,
+                    layer.size.height.round() as u32,
+                )
+            })
+            .or_else(|| {
+                let size = window.non_zero_physical_size()?;
+                Some(PhysicalSize::new(size.width.get(), size.height.get()))
+            })
+            .ok_or(CompositorPresentError::PresentFailed(
+                "Unable to determine the frame size".into(),
+            ))?;
 
         if !window.on_frame_generated(frame_size) {
             return Err(CompositorPresentError::PresentFailed(
@@ -241,45 +608,61 @@ impl FlutterCompositorHandler for SctkCompositorHandler {
             ));
         }
 
+        // Hidden windows discard their frames (including for the readback
+        // and the mirrors).
+        let Some(_visible) = window.lock_visible() else {
+            window.on_frame_discarded();
+            return Ok(());
+        };
+
         if !self.context.lock().unwrap().make_current() {
             return Err(CompositorPresentError::PresentFailed(
                 "Unable to make context current".into(),
             ));
         }
 
-        unsafe {
-            // Disable the scissor test as it can affect blit operations.
-            // Prevents regressions like: https://github.com/flutter/flutter/issues/140828
-            // See OpenGL specification version 4.6, section 18.3.1.
-            self.gl.Disable(gl::SCISSOR_TEST);
-
-            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, source_id);
-            self.gl
-                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, WINDOW_FRAMEBUFFER_ID);
+        let frame_readback = self.load_frame_readback();
+        let present_on_screen = frame_readback
+            .as_ref()
+            .is_none_or(|frame_readback| frame_readback.present_on_screen);
 
-            let width = layer.size.width.round() as i32;
-            let height = layer.size.height.round() as i32;
+        let source_id = match single_source_id {
+            Some(source_id) => {
+                if !self.overlays.is_empty() {
+                    self.overlays.finish(&HashSet::new(), &HashSet::new());
+                }
+                source_id
+            }
+            None => {
+                // Promoted platform views are not part of the window content,
+                // so they would be missing from the readback and the mirrors.
+                let can_promote = frame_readback.is_none() && self.mirrors.is_empty();
+                self.composite_layers(&window, &info.layers, frame_size, can_promote)?;
+                WINDOW_FRAMEBUFFER_ID
+            }
+        };
 
-            self.gl.BlitFramebuffer(
-                0,                    // srcX0
-                0,                    // srcY0
-                width,                // srcX1
-                height,               // srcY1
-                0,                    // dstX0
-                0,                    // dstY0
-                width,                // dstX1
-                height,               // dstY1
-                gl::COLOR_BUFFER_BIT, // mask
-                gl::NEAREST,          // filter
-            );
+        if let Some(frame_readback) = &frame_readback {
+            self.read_frame(frame_readback, source_id, frame_size);
         }
 
-        if !self.context.lock().unwrap().present() {
-            return Err(CompositorPresentError::PresentFailed(
-                "Present failed".into(),
-            ));
+        if present_on_screen {
+            if source_id != WINDOW_FRAMEBUFFER_ID {
+                self.blit_to_window(source_id, frame_size);
+            }
+
+            if !self.context.lock().unwrap().present() {
+                return Err(CompositorPresentError::PresentFailed(
+                    "Present failed".into(),
+                ));
+            }
         }
 
+        // Mirrors are presented after the source view so that they never
+        // delay it.
+        self.mirrors.copy_frame(source_id, frame_size);
+        self.mirrors.present();
+
         window.on_frame_presented();
         Ok(())
     }
@@ -375,6 +758,44 @@ impl FlutterCompositorHandler for SctkCompositorHandler {
     }
 }
 
+/// Areas of |layers| which contain Flutter contents or platform views.
+fn painted_rects(layers: &[FlutterLayer]) -> Vec<OverlayRect> {
+    let mut rects = Vec::new();
+    for layer in layers {
+        let layer_rect = OverlayRect::new(layer.offset, layer.size);
+        match (&layer.content, &layer.backing_store_present_info) {
+            (FlutterLayerContent::BackingStore(_), Some(present_info)) => {
+                rects.extend(present_info.paint_region.rects.iter().map(|rect| {
+                    let mut rect = OverlayRect::from(*rect);
+                    rect.x += layer_rect.x;
+                    rect.y += layer_rect.y;
+                    rect
+                }));
+            }
+            _ => rects.push(layer_rect),
+        }
+    }
+    rects
+}
+
+/// Make |rect| of the window transparent, so that the subsurface below it
+/// shows through. The context must be current.
+fn punch_hole(gl: &gl::Gl, rect: OverlayRect, frame_size: PhysicalSize<u32>) {
+    unsafe {
+        // OpenGL coordinates start from the bottom-left corner.
+        gl.Enable(gl::SCISSOR_TEST);
+        gl.Scissor(
+            rect.x.round() as i32,
+            (frame_size.height as f64 - rect.y - rect.height).round() as i32,
+            rect.width.round() as i32,
+            rect.height.round() as i32,
+        );
+        gl.ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl.Clear(gl::COLOR_BUFFER_BIT);
+        gl.Disable(gl::SCISSOR_TEST);
+    }
+}
+
 // TODO(multi-view): Add support for multi-view vsync once it is supported
 // upstream:
 // https://github.com/flutter/flutter/issues/142845#issuecomment-1955345110
@@ -383,7 +804,21 @@ pub struct SctkVsyncHandler {
     engine: FlutterEngineWeakRef,
     implicit_window_surface: Option<WlSurface>,
     pending_baton: AtomicIsize,
+    /// Whether the pending baton waits for a frame callback.
+    has_frame_callback: AtomicBool,
+    /// Engine time at which the pending frame callback was requested.
+    frame_requested_at: AtomicU64,
+    /// Set once the surface is unmapped, until a frame is presented again.
+    is_unmapped: AtomicBool,
     can_schedule_frames: AtomicBool,
+    is_paused: AtomicBool,
+    /// Set while the compositor seems to hide the surface.
+    is_occluded: AtomicBool,
+    has_deferred_frame_request: AtomicBool,
+    /// In nanoseconds, `0` when the frames aren't capped.
+    min_frame_interval: AtomicU64,
+    /// Engine time at which the last frame callback was answered.
+    last_frame_start_time: AtomicU64,
 }
 
 impl SctkVsyncHandler {
@@ -393,7 +828,15 @@ impl SctkVsyncHandler {
             engine: Default::default(),
             implicit_window_surface: Default::default(),
             pending_baton: Default::default(),
+            has_frame_callback: Default::default(),
+            frame_requested_at: Default::default(),
+            is_unmapped: Default::default(),
             can_schedule_frames: Default::default(),
+            is_paused: Default::default(),
+            is_occluded: Default::default(),
+            has_deferred_frame_request: Default::default(),
+            min_frame_interval: Default::default(),
+            last_frame_start_time: Default::default(),
         }
     }
 
@@ -415,6 +858,126 @@ impl SctkVsyncHandler {
 
     pub(crate) fn notify_present(&self) {
         self.can_schedule_frames.store(true, Ordering::Relaxed);
+        self.is_unmapped.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether a frame callback is expected for the pending baton (e.g.: not
+    /// for a callback requested before the surface was unmapped).
+    pub(crate) fn is_waiting_for_frame_callback(&self) -> bool {
+        self.has_frame_callback.load(Ordering::Relaxed)
+    }
+
+    /// Engine time at which the frame callback expected for the pending
+    /// baton was requested, `None` when no frame callback is expected.
+    pub(crate) fn pending_frame_callback_since(&self) -> Option<u64> {
+        self.is_waiting_for_frame_callback()
+            .then(|| self.frame_requested_at.load(Ordering::Relaxed))
+    }
+
+    /// Engine time at which the last frame callback was answered.
+    pub(crate) fn last_frame_callback_time(&self) -> u64 {
+        self.last_frame_start_time.load(Ordering::Relaxed)
+    }
+
+    /// The pending baton is answered on the frame callback.
+    pub(crate) fn frame_callback_received(&self) {
+        self.has_frame_callback.store(false, Ordering::Relaxed);
+    }
+
+    /// The surface was unmapped (or created hidden), so frame callbacks no
+    /// longer fire until a frame is presented again. The baton waiting for
+    /// one is answered right away.
+    pub(crate) fn surface_unmapped(&self) {
+        self.can_schedule_frames.store(false, Ordering::Relaxed);
+        self.is_unmapped.store(true, Ordering::Relaxed);
+        if !self.has_frame_callback.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        let Some(engine) = self.engine.upgrade() else {
+            error!("Engine upgrade failed while unmapping the surface");
+            return;
+        };
+
+        let baton = self.pending_baton.load(Ordering::Relaxed);
+        engine.run_on_platform_thread(move |engine| {
+            let (frame_start_time_nanos, frame_target_time_nanos) =
+                get_flutter_frame_time_nanos(FRAME_INTERVAL_60_HZ_IN_NANOS);
+            engine.on_vsync(baton, frame_start_time_nanos, frame_target_time_nanos);
+        });
+    }
+
+    /// Cap the frame rate below the refresh rate of the output (e.g.: to
+    /// save power on high refresh rate displays). Every refresh may be
+    /// rendered when `None`.
+    pub(crate) fn set_min_frame_interval(&self, interval: Option<Duration>) {
+        let interval = interval.map_or(0, |interval| interval.as_nanos() as u64);
+        self.min_frame_interval.store(interval, Ordering::Relaxed);
+    }
+
+    /// Called on frame callbacks, received at `frame_start_time_nanos`. Returns
+    /// the interval of the frame to render, or `None` when the frame request
+    /// must wait for a later callback because of the minimum frame interval.
+    pub(crate) fn pace_frame(
+        &self,
+        frame_start_time_nanos: u64,
+        refresh_interval: u64,
+    ) -> Option<u64> {
+        let last_frame_start_time = self.last_frame_start_time.load(Ordering::Relaxed);
+        let frame_interval = paced_frame_interval(
+            frame_start_time_nanos.saturating_sub(last_frame_start_time),
+            refresh_interval,
+            self.min_frame_interval.load(Ordering::Relaxed),
+        );
+
+        let Some(frame_interval) = frame_interval else {
+            // The frame request waits for the next callback.
+            self.frame_requested_at
+                .store(frame_start_time_nanos, Ordering::Relaxed);
+            return None;
+        };
+
+        self.last_frame_start_time.store(frame_start_time_nanos, Ordering::Relaxed);
+        Some(frame_interval)
+    }
+
+    /// While paused, frame requests are deferred until the handler is resumed.
+    pub(crate) fn set_paused(&self, paused: bool) {
+        self.is_paused.store(paused, Ordering::Relaxed);
+        self.resume_deferred_frame_request();
+    }
+
+    /// While the surface seems hidden by the compositor, frame requests are
+    /// deferred as if paused, rather than waiting for frame callbacks which
+    /// may never fire.
+    pub(crate) fn set_occluded(&self, occluded: bool) {
+        if self.is_occluded.swap(occluded, Ordering::Relaxed) != occluded {
+            self.resume_deferred_frame_request();
+        }
+    }
+
+    fn defers_frame_requests(&self) -> bool {
+        self.is_paused.load(Ordering::Relaxed) || self.is_occluded.load(Ordering::Relaxed)
+    }
+
+    fn resume_deferred_frame_request(&self) {
+        if self.defers_frame_requests()
+            || !self.has_deferred_frame_request.swap(false, Ordering::Relaxed)
+        {
+            return;
+        }
+
+        let Some(engine) = self.engine.upgrade() else {
+            error!("Engine upgrade failed while resuming vsync handler");
+            return;
+        };
+
+        let baton = self.pending_baton.load(Ordering::Relaxed);
+        engine.run_on_platform_thread(move |engine| {
+            let (frame_start_time_nanos, frame_target_time_nanos) =
+                get_flutter_frame_time_nanos(FRAME_INTERVAL_60_HZ_IN_NANOS);
+            engine.on_vsync(baton, frame_start_time_nanos, frame_target_time_nanos);
+        });
     }
 }
 
@@ -425,6 +988,12 @@ impl FlutterVsyncHandler for SctkVsyncHandler {
 
         self.pending_baton.store(baton, Ordering::Relaxed);
 
+        if self.defers_frame_requests() {
+            trace!("[baton: {}] deferring frame callback while paused", baton);
+            self.has_deferred_frame_request.store(true, Ordering::Relaxed);
+            return;
+        }
+
         let Some(engine) = self.engine.upgrade() else {
             error!("Engine upgrade failed while requesting frame callback");
             return;
@@ -435,14 +1004,23 @@ impl FlutterVsyncHandler for SctkVsyncHandler {
         // until the surface is mapped (e.g.: until the first `present()`).
         let can_schedule_frames = self.can_schedule_frames.load(Ordering::Relaxed);
         if !can_schedule_frames {
-            engine.run_on_platform_thread(move |engine| {
+            let on_vsync = move |engine: &FlutterEngine| {
                 // Once the surface is mapped, the `wl_output`'s refresh rate
                 // will be used for determining the frame interval. But until
                 // then, 60hz seems like a reasonable default.
                 let (frame_start_time_nanos, frame_target_time_nanos) =
                     get_flutter_frame_time_nanos(FRAME_INTERVAL_60_HZ_IN_NANOS);
                 engine.on_vsync(baton, frame_start_time_nanos, frame_target_time_nanos);
-            });
+            };
+
+            // A hidden window may stay unmapped for long, so its animations
+            // are paced instead of being rendered as fast as possible.
+            if self.is_unmapped.load(Ordering::Relaxed) {
+                let time = FlutterEngine::get_current_time_nanos() + FRAME_INTERVAL_60_HZ_IN_NANOS;
+                engine.schedule_at(time, on_vsync);
+            } else {
+                engine.run_on_platform_thread(on_vsync);
+            }
             return;
         }
 
@@ -453,6 +1031,9 @@ impl FlutterVsyncHandler for SctkVsyncHandler {
 
         let qh = self.qh.clone();
 
+        self.frame_requested_at
+            .store(FlutterEngine::get_current_time_nanos(), Ordering::Relaxed);
+        self.has_frame_callback.store(true, Ordering::Relaxed);
         engine.run_on_platform_thread(move |_engine| {
             surface.frame(&qh, surface.clone());
             surface.commit();
@@ -479,8 +1060,10 @@ impl TaskRunnerHandler for SctkPlatformTaskHandler {
 // TODO(multi-view): Add support for multi-view once the `flutter/platform`
 // plugin supports it.
 pub struct SctkPlatformHandler {
-    implicit_xdg_toplevel: XdgToplevel,
+    /// `None` when the implicit window is a layer surface.
+    implicit_xdg_toplevel: Option<XdgToplevel>,
     clipboard: Clipboard,
+    request_sender: Option<Sender<SctkApplicationRequest>>,
 }
 
 impl SctkPlatformHandler {
@@ -488,93 +1071,350 @@ impl SctkPlatformHandler {
     ///
     /// `display` must be a valid `*mut wl_display` pointer, and it must remain
     /// valid for as long as `Clipboard` object is alive.
-    pub unsafe fn new(display: WlDisplay, xdg_toplevel: XdgToplevel) -> Self {
+    pub unsafe fn new(display: WlDisplay, xdg_toplevel: Option<XdgToplevel>) -> Self {
         Self {
             implicit_xdg_toplevel: xdg_toplevel,
             clipboard: Clipboard::new(display.id().as_ptr() as *mut _),
+            request_sender: None,
         }
     }
+
+    /// Handle the exit requests from Dart by quitting the application.
+    pub(crate) fn with_request_sender(
+        mut self,
+        request_sender: Sender<SctkApplicationRequest>,
+    ) -> Self {
+        self.request_sender = Some(request_sender);
+        self
+    }
 }
 
 impl PlatformHandler for SctkPlatformHandler {
     fn set_application_switcher_description(&mut self, description: AppSwitcherDescription) {
-        self.implicit_xdg_toplevel.set_title(description.label);
+        // Layer surfaces are not listed in application switchers.
+        if let Some(xdg_toplevel) = &self.implicit_xdg_toplevel {
+            xdg_toplevel.set_title(description.label);
+        }
     }
 
-    fn set_clipboard_data(&mut self, text: String) {
+    fn set_clipboard_data(&mut self, text: String) -> Result<(), PlatformError> {
         // TODO: Is updating *both* clipboards a reasonable thing to do here?
         self.clipboard.store(text.clone());
         self.clipboard.store_primary(text);
+        Ok(())
+    }
+
+    fn get_clipboard_data(&mut self, _mime: &str) -> Result<String, PlatformError> {
+        self.clipboard
+            .load()
+            .map_err(|err| PlatformError::Clipboard(err.to_string()))
     }
 
-    fn get_clipboard_data(&mut self, _mime: &str) -> Result<String, MimeError> {
-        self.clipboard.load().map_err(|_| MimeError {})
+    fn exit_application(&mut self, exit_code: i32) -> bool {
+        let Some(request_sender) = &self.request_sender else {
+            return false;
+        };
+
+        request_sender
+            .send(SctkApplicationRequest::ExitWithCode(exit_code))
+            .is_ok()
     }
 }
 
+/// Keeps one cursor per seat, so that each seat's cursor can show a different
+/// icon.
 pub struct SctkMouseCursorHandler {
     conn: Connection,
-    themed_pointer: Option<ThemedPointer>,
+    cursors: HashMap<ObjectId, (SctkSeatCursor, SctkCursorStack)>,
+    next_override_id: u64,
 }
 
 impl SctkMouseCursorHandler {
     pub fn new(conn: Connection) -> Self {
         Self {
             conn,
-            themed_pointer: None,
+            cursors: HashMap::new(),
+            next_override_id: 0,
         }
     }
 
-    pub(crate) fn set_themed_pointer(&mut self, themed_pointer: Option<ThemedPointer>) {
-        self.themed_pointer = themed_pointer;
+    pub(crate) fn set_seat_cursor(&mut self, seat_id: ObjectId, cursor: Option<SctkSeatCursor>) {
+        match cursor {
+            Some(cursor) => self.cursors.insert(seat_id, (cursor, SctkCursorStack::new())),
+            None => self.cursors.remove(&seat_id),
+        };
     }
 
-    pub(crate) fn remove_themed_pointer_for_seat(&mut self, seat_id: ObjectId) {
-        let themed_pointer_belongs_to_seat = self
-            .themed_pointer
-            .as_ref()
-            .and_then(|themed_pointer| {
-                themed_pointer
-                    .pointer()
-                    .data::<PointerData>()
-                    .map(|data| data.pointer_data().seat().id() == seat_id)
-            })
-            .unwrap_or_default();
+    pub(crate) fn remove_cursor_for_seat(&mut self, seat_id: ObjectId) {
+        self.cursors.remove(&seat_id);
+    }
+
+    /// The compositor resets the cursor when the pointer enters a surface, so
+    /// the cursor is applied again with the serial of the enter.
+    pub(crate) fn pointer_entered(&mut self, pointer: &WlPointer, serial: u32) {
+        let cursor = self
+            .cursors
+            .values_mut()
+            .find(|(seat_cursor, _)| seat_cursor.pointer() == pointer);
+        let Some((seat_cursor, stack)) = cursor else {
+            return;
+        };
+
+        let (serial, cursor) = stack.pointer_entered(serial);
+        if seat_cursor.set_cursor(&self.conn, serial, &cursor).is_err() {
+            warn!("Unable to restore the cursor on pointer enter");
+        }
+    }
+
+    pub(crate) fn pointer_left(&mut self, pointer: &WlPointer) {
+        let cursor = self
+            .cursors
+            .values_mut()
+            .find(|(seat_cursor, _)| seat_cursor.pointer() == pointer);
+        if let Some((_, stack)) = cursor {
+            stack.pointer_left();
+        }
+    }
 
-        if themed_pointer_belongs_to_seat {
-            self.themed_pointer = None;
+    /// Show |icon| on the seat of |seat_id| regardless of the cursor requested
+    /// by the widgets, until the returned guard is dropped (e.g.: the grabbing
+    /// cursor for the duration of a drag). The latest override wins.
+    pub(crate) fn push_cursor_override(
+        handler: &Arc<parking_lot::Mutex<Self>>,
+        seat_id: ObjectId,
+        icon: CursorIcon,
+    ) -> CursorOverrideGuard {
+        let mut this = handler.lock();
+        let id = this.next_override_id;
+        this.next_override_id += 1;
+        // Without a pointer on the seat, the override has no effect.
+        let _ = this.update_cursor(&seat_id, |stack| stack.push_override(id, icon.into()));
+
+        CursorOverrideGuard {
+            handler: Arc::downgrade(handler),
+            seat_id,
+            id,
+        }
+    }
+
+    fn update_cursor<F>(&mut self, seat_id: &ObjectId, f: F) -> Result<(), MouseCursorError>
+    where
+        F: FnOnce(&mut SctkCursorStack),
+    {
+        let Some((seat_cursor, stack)) = self.cursors.get_mut(seat_id) else {
+            return Err(MouseCursorError);
+        };
+
+        match stack.update(f) {
+            Some((serial, cursor)) => seat_cursor.set_cursor(&self.conn, serial, &cursor),
+            None => Ok(()),
         }
     }
 }
 
 impl MouseCursorHandler for SctkMouseCursorHandler {
     fn activate_system_cursor(&mut self, kind: SystemMouseCursor) -> Result<(), MouseCursorError> {
-        let Some(themed_pointer) = self.themed_pointer.as_ref() else {
-            warn!("[plugin: mousecursor] Unable to update cursor: themed pointer is empty");
+        if self.cursors.is_empty() {
+            warn!("[plugin: mousecursor] Unable to update cursor: no pointer");
+            return Err(MouseCursorError);
+        }
+
+        let cursor: SctkMouseCursor = kind.into();
+
+        // Without a target device, the cursor of every seat is updated.
+        let seat_ids: Vec<_> = self.cursors.keys().cloned().collect();
+        seat_ids.iter().try_for_each(|seat_id| {
+            self.update_cursor(seat_id, |stack| stack.set_base(cursor.clone()))
+        })
+    }
+
+    fn activate_system_cursor_for_device(
+        &mut self,
+        device: i32,
+        kind: SystemMouseCursor,
+    ) -> Result<(), MouseCursorError> {
+        let seat_id = self
+            .cursors
+            .iter()
+            .find(|(_, (seat_cursor, _))| mouse_device_id(seat_cursor.pointer()) == device)
+            .map(|(seat_id, _)| seat_id.clone());
+
+        let Some(seat_id) = seat_id else {
+            warn!(
+                "[plugin: mousecursor] Unable to update cursor: unknown pointer device {}",
+                device
+            );
             return Err(MouseCursorError);
         };
 
         let cursor: SctkMouseCursor = kind.into();
+        self.update_cursor(&seat_id, |stack| stack.set_base(cursor))
+    }
+}
+
+/// Removes a cursor override when dropped, see
+/// `SctkMouseCursorHandler::push_cursor_override`.
+///
+/// Note: Must not be dropped while the handler is locked.
+pub(crate) struct CursorOverrideGuard {
+    handler: Weak<parking_lot::Mutex<SctkMouseCursorHandler>>,
+    seat_id: ObjectId,
+    id: u64,
+}
+
+impl Drop for CursorOverrideGuard {
+    fn drop(&mut self) {
+        let Some(handler) = self.handler.upgrade() else {
+            return;
+        };
+
+        // The seat may have been removed since.
+        let _ = handler
+            .lock()
+            .update_cursor(&self.seat_id, |stack| stack.remove_override(self.id));
+    }
+}
+
+/// Cursor state of a seat: the cursor requested by the widgets (through the
+/// `flutter/mousecursor` plugin), below the overrides held by the embedder.
+#[derive(Debug)]
+struct SctkCursorStack {
+    base: SctkMouseCursor,
+    overrides: Vec<(u64, SctkMouseCursor)>,
+    /// Serial of the latest enter of the pointer, `None` while the pointer
+    /// is outside of the surfaces of the application.
+    enter_serial: Option<u32>,
+}
+
+impl SctkCursorStack {
+    fn new() -> Self {
+        Self {
+            base: SystemMouseCursor::Basic.into(),
+            overrides: Vec::new(),
+            enter_serial: None,
+        }
+    }
+
+    fn effective(&self) -> &SctkMouseCursor {
+        self.overrides
+            .last()
+            .map_or(&self.base, |(_, cursor)| cursor)
+    }
+
+    /// Returns the cursor to apply, along with the serial of the enter.
+    fn pointer_entered(&mut self, serial: u32) -> (u32, SctkMouseCursor) {
+        self.enter_serial = Some(serial);
+        (serial, self.effective().clone())
+    }
+
+    /// The changes are applied on the next enter.
+    fn pointer_left(&mut self) {
+        self.enter_serial = None;
+    }
+
+    fn set_base(&mut self, cursor: SctkMouseCursor) {
+        self.base = cursor;
+    }
+
+    fn push_override(&mut self, id: u64, cursor: SctkMouseCursor) {
+        self.overrides.push((id, cursor));
+    }
+
+    fn remove_override(&mut self, id: u64) {
+        self.overrides.retain(|(override_id, _)| *override_id != id);
+    }
+
+    /// Apply |f|, and return the cursor to apply (along with the serial of
+    /// the enter) if it changed the effective cursor of a pointer currently
+    /// over the application.
+    fn update<F>(&mut self, f: F) -> Option<(u32, SctkMouseCursor)>
+    where
+        F: FnOnce(&mut Self),
+    {
+        let previous = self.effective().clone();
+        f(self);
+
+        let serial = self.enter_serial?;
+        let cursor = self.effective();
+        (*cursor != previous).then(|| (serial, cursor.clone()))
+    }
+}
+
+/// Cursor of a seat's pointer.
+pub(crate) enum SctkSeatCursor {
+    /// The compositor draws the cursor from its shape (`wp_cursor_shape_v1`),
+    /// which matches the cursors of the other applications and doesn't need
+    /// to load a cursor theme.
+    Shape {
+        pointer: WlPointer,
+        device: WpCursorShapeDeviceV1,
+    },
+
+    /// Fallback drawing the cursor theme into a surface of the pointer.
+    Themed(ThemedPointer),
+}
+
+impl SctkSeatCursor {
+    pub(crate) fn pointer(&self) -> &WlPointer {
+        match self {
+            SctkSeatCursor::Shape { pointer, .. } => pointer,
+            SctkSeatCursor::Themed(themed_pointer) => themed_pointer.pointer(),
+        }
+    }
 
-        match cursor.icon {
-            Some(icon) => themed_pointer
-                .set_cursor(&self.conn, icon)
-                .or(Err(MouseCursorError)),
-            None => themed_pointer.hide_cursor().or(Err(MouseCursorError)),
+    /// |serial| is the serial of the latest enter of the pointer, which the
+    /// themed pointer tracks itself.
+    fn set_cursor(
+        &self,
+        conn: &Connection,
+        serial: u32,
+        cursor: &SctkMouseCursor,
+    ) -> Result<(), MouseCursorError> {
+        match self {
+            SctkSeatCursor::Shape { pointer, device } => {
+                match cursor.shape {
+                    Some(shape) => device.set_shape(serial, shape),
+                    None => pointer.set_cursor(serial, None, 0, 0),
+                }
+                Ok(())
+            }
+            SctkSeatCursor::Themed(themed_pointer) => match cursor.icon {
+                Some(icon) => themed_pointer
+                    .set_cursor(conn, icon)
+                    .or(Err(MouseCursorError)),
+                None => themed_pointer.hide_cursor().or(Err(MouseCursorError)),
+            },
         }
     }
 }
 
+impl Drop for SctkSeatCursor {
+    fn drop(&mut self) {
+        // The themed pointer releases its own objects.
+        if let SctkSeatCursor::Shape { pointer, device } = self {
+            device.destroy();
+            if pointer.version() >= 3 {
+                pointer.release();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 struct SctkMouseCursor {
+    /// Icon of the themed pointer.
     icon: Option<CursorIcon>,
+    /// Shape of the `wp_cursor_shape_v1` pointer.
+    shape: Option<Shape>,
 }
 
 impl From<SystemMouseCursor> for SctkMouseCursor {
     fn from(kind: SystemMouseCursor) -> Self {
+        let shape = cursor_shape(&kind);
         let icon = match kind {
             SystemMouseCursor::Click => Some(CursorIcon::Pointer),
             SystemMouseCursor::Alias => Some(CursorIcon::Alias),
-            SystemMouseCursor::AllScroll => Some(CursorIcon::Default),
+            SystemMouseCursor::AllScroll => Some(CursorIcon::AllScroll),
             SystemMouseCursor::Basic => Some(CursorIcon::Default),
             SystemMouseCursor::Cell => Some(CursorIcon::Cell),
             SystemMouseCursor::ContextMenu => Some(CursorIcon::ContextMenu),
@@ -610,23 +1450,583 @@ impl From<SystemMouseCursor> for SctkMouseCursor {
             SystemMouseCursor::ZoomOut => Some(CursorIcon::ZoomOut),
         };
 
-        Self { icon }
+        Self { icon, shape }
     }
 }
 
-#[derive(Default)]
-pub struct SctkTextInputHandler {}
+impl From<CursorIcon> for SctkMouseCursor {
+    fn from(icon: CursorIcon) -> Self {
+        let shape = match icon {
+            CursorIcon::Default => Shape::Default,
+            CursorIcon::ContextMenu => Shape::ContextMenu,
+            CursorIcon::Help => Shape::Help,
+            CursorIcon::Pointer => Shape::Pointer,
+            CursorIcon::Progress => Shape::Progress,
+            CursorIcon::Wait => Shape::Wait,
+            CursorIcon::Cell => Shape::Cell,
+            CursorIcon::Crosshair => Shape::Crosshair,
+            CursorIcon::Text => Shape::Text,
+            CursorIcon::VerticalText => Shape::VerticalText,
+            CursorIcon::Alias => Shape::Alias,
+            CursorIcon::Copy => Shape::Copy,
+            CursorIcon::Move => Shape::Move,
+            CursorIcon::NoDrop => Shape::NoDrop,
+            CursorIcon::NotAllowed => Shape::NotAllowed,
+            CursorIcon::Grab => Shape::Grab,
+            CursorIcon::Grabbing => Shape::Grabbing,
+            CursorIcon::EResize => Shape::EResize,
+            CursorIcon::NResize => Shape::NResize,
+            CursorIcon::NeResize => Shape::NeResize,
+            CursorIcon::NwResize => Shape::NwResize,
+            CursorIcon::SResize => Shape::SResize,
+            CursorIcon::SeResize => Shape::SeResize,
+            CursorIcon::SwResize => Shape::SwResize,
+            CursorIcon::WResize => Shape::WResize,
+            CursorIcon::EwResize => Shape::EwResize,
+            CursorIcon::NsResize => Shape::NsResize,
+            CursorIcon::NeswResize => Shape::NeswResize,
+            CursorIcon::NwseResize => Shape::NwseResize,
+            CursorIcon::ColResize => Shape::ColResize,
+            CursorIcon::RowResize => Shape::RowResize,
+            CursorIcon::AllScroll => Shape::AllScroll,
+            CursorIcon::ZoomIn => Shape::ZoomIn,
+            CursorIcon::ZoomOut => Shape::ZoomOut,
+            // Not in the first version of the protocol.
+            _ => Shape::Default,
+        };
+
+        Self {
+            icon: Some(icon),
+            shape: Some(shape),
+        }
+    }
+}
+
+/// The shapes of the first version of the protocol, which is the one bound.
+fn cursor_shape(kind: &SystemMouseCursor) -> Option<Shape> {
+    let shape = match kind {
+        SystemMouseCursor::Click => Shape::Pointer,
+        SystemMouseCursor::Alias => Shape::Alias,
+        SystemMouseCursor::AllScroll => Shape::AllScroll,
+        SystemMouseCursor::Basic => Shape::Default,
+        SystemMouseCursor::Cell => Shape::Cell,
+        SystemMouseCursor::ContextMenu => Shape::ContextMenu,
+        SystemMouseCursor::Copy => Shape::Copy,
+        SystemMouseCursor::Disappearing => Shape::Default, // fallback
+        SystemMouseCursor::Forbidden => Shape::NotAllowed,
+        SystemMouseCursor::Grab => Shape::Grab,
+        SystemMouseCursor::Grabbing => Shape::Grabbing,
+        SystemMouseCursor::Help => Shape::Help,
+        SystemMouseCursor::Move => Shape::Move,
+        SystemMouseCursor::NoDrop => Shape::NoDrop,
+        SystemMouseCursor::None => return None,
+        SystemMouseCursor::Precise => Shape::Crosshair,
+        SystemMouseCursor::Progress => Shape::Progress,
+        SystemMouseCursor::ResizeColumn => Shape::ColResize,
+        SystemMouseCursor::ResizeDown => Shape::SResize,
+        SystemMouseCursor::ResizeDownLeft => Shape::SwResize,
+        SystemMouseCursor::ResizeDownRight => Shape::SeResize,
+        SystemMouseCursor::ResizeLeft => Shape::WResize,
+        SystemMouseCursor::ResizeLeftRight => Shape::EwResize,
+        SystemMouseCursor::ResizeRight => Shape::EResize,
+        SystemMouseCursor::ResizeRow => Shape::RowResize,
+        SystemMouseCursor::ResizeUp => Shape::NResize,
+        SystemMouseCursor::ResizeUpDown => Shape::NsResize,
+        SystemMouseCursor::ResizeUpLeft => Shape::NwResize,
+        SystemMouseCursor::ResizeUpLeftDownRight => Shape::NwseResize,
+        SystemMouseCursor::ResizeUpRight => Shape::NeResize,
+        SystemMouseCursor::ResizeUpRightDownLeft => Shape::NeswResize,
+        SystemMouseCursor::Text => Shape::Text,
+        SystemMouseCursor::VerticalText => Shape::VerticalText,
+        SystemMouseCursor::Wait => Shape::Wait,
+        SystemMouseCursor::ZoomIn => Shape::ZoomIn,
+        SystemMouseCursor::ZoomOut => Shape::ZoomOut,
+    };
+    Some(shape)
+}
+
+// TODO(multi-view): Add support for multi-view once the `flutter-rs/window`
+// plugin supports it.
+pub struct SctkWindowHandler {
+    window: Weak<SctkFlutterWindowInner>,
+    sender: Sender<SctkApplicationRequest>,
+    /// The engine owning the window, whose id identifies the window.
+    engine_id: SctkEngineId,
+}
+
+impl SctkWindowHandler {
+    pub(crate) fn new(
+        window: Weak<SctkFlutterWindowInner>,
+        sender: Sender<SctkApplicationRequest>,
+        engine_id: SctkEngineId,
+    ) -> Self {
+        Self {
+            window,
+            sender,
+            engine_id,
+        }
+    }
+
+    /// Send a request about the window surface, which |action| describes
+    /// when the event loop is gone.
+    fn send_window_request<F>(&self, action: &str, f: F) -> bool
+    where
+        F: FnOnce(WlSurface) -> SctkApplicationRequest,
+    {
+        let Some(window) = self.window.upgrade() else {
+            error!("[plugin: window] Unable to upgrade weak window");
+            return false;
+        };
+
+        let request = f(window.wl_surface().clone());
+        if self.sender.send(request).is_err() {
+            warn!("[plugin: window] Unable to {action} because the event loop is gone");
+            return false;
+        }
+        true
+    }
+
+    fn with_window<F>(&self, f: F)
+    where
+        F: FnOnce(&Window),
+    {
+        let Some(window) = self.window.upgrade() else {
+            error!("[plugin: window] Unable to upgrade weak window");
+            return;
+        };
+
+        match window.window() {
+            Some(window) => f(window),
+            None => warn!("[plugin: window] Operation not supported by layer surfaces"),
+        }
+    }
+
+    fn is_attached(&self) -> bool {
+        self.window
+            .upgrade()
+            .is_some_and(|window| window.attached_surface().is_some())
+    }
+
+    fn window_state(&self) -> WindowState {
+        self.window
+            .upgrade()
+            .map(|window| window.load_window_state())
+            .unwrap_or(WindowState::empty())
+    }
+
+    fn request_state(&self, request: WindowStateRequest) -> bool {
+        self.window
+            .upgrade()
+            .is_some_and(|window| window.request_state(request))
+    }
+}
+
+impl WindowHandler for SctkWindowHandler {
+    fn close(&mut self) {
+        if self.sender.send(SctkApplicationRequest::Exit).is_err() {
+            warn!("[plugin: window] Unable to close window because the event loop is gone");
+        }
+    }
+
+    fn show(&mut self) {
+        if let Some(window) = self.window.upgrade() {
+            window.show();
+        }
+    }
+
+    fn hide(&mut self) {
+        if let Some(window) = self.window.upgrade() {
+            window.hide();
+        }
+    }
+
+    fn maximize(&mut self) {
+        if self.request_state(WindowStateRequest::Maximize) {
+            self.with_window(|window| window.set_maximized());
+        }
+    }
+
+    fn iconify(&mut self) {
+        self.with_window(|window| window.set_minimized());
+    }
+
+    fn restore(&mut self) {
+        if self.request_state(WindowStateRequest::Unmaximize) {
+            self.with_window(|window| window.unset_maximized());
+        }
+    }
+
+    fn is_maximized(&mut self) -> bool {
+        self.window_state().contains(WindowState::MAXIMIZED)
+    }
+
+    fn is_fullscreen(&mut self) -> bool {
+        self.window_state().contains(WindowState::FULLSCREEN)
+    }
+
+    fn is_iconified(&mut self) -> bool {
+        // The minimized state is not reported by `xdg_toplevel`.
+        false
+    }
+
+    fn is_visible(&mut self) -> bool {
+        self.window
+            .upgrade()
+            .is_some_and(|window| window.is_visible())
+    }
+
+    fn set_pos(&mut self, pos: PositionParams) {
+        // Attached windows are positioned relative to their parent.
+        let window = self.window.upgrade();
+        let Some(attached_surface) = window.as_ref().and_then(|window| window.attached_surface())
+        else {
+            warn!("[plugin: window] Setting the window position is not supported on Wayland");
+            return;
+        };
+
+        let position = LogicalPosition::new(pos.x.round() as i32, pos.y.round() as i32);
+        attached_surface.set_position(position);
+    }
+
+    fn get_pos(&mut self) -> PositionParams {
+        let window = self.window.upgrade();
+        match window.as_ref().and_then(|window| window.attached_surface()) {
+            Some(attached_surface) => {
+                let position = attached_surface.position();
+                PositionParams {
+                    x: position.x as f32,
+                    y: position.y as f32,
+                }
+            }
+            // Wayland clients are not aware of their global position.
+            None => PositionParams { x: 0.0, y: 0.0 },
+        }
+    }
+
+    fn start_drag(&mut self) {
+        if !self.is_attached() {
+            warn!("[plugin: window] Window dragging is not supported yet");
+            return;
+        }
+
+        self.send_window_request("drag the window", |surface| {
+            SctkApplicationRequest::SetAttachedDrag {
+                surface,
+                dragging: true,
+            }
+        });
+    }
+
+    fn end_drag(&mut self) {
+        if self.is_attached() {
+            self.send_window_request("end the drag", |surface| {
+                SctkApplicationRequest::SetAttachedDrag {
+                    surface,
+                    dragging: false,
+                }
+            });
+        }
+    }
+
+    fn get_recommended_max_size(&mut self) -> Option<SizeParams> {
+        self.window.upgrade()?.recommended_max_size()
+    }
+
+    fn set_exclusive_zone(&mut self, exclusive_zone: i32) {
+        let is_layer_surface = self
+            .window
+            .upgrade()
+            .is_some_and(|window| window.set_exclusive_zone(exclusive_zone));
+
+        if !is_layer_surface {
+            warn!("[plugin: window] The exclusive zone is only supported by layer surfaces");
+        }
+    }
+
+    fn set_margin(&mut self, margin: MarginParams) {
+        let margin = LayerMargin {
+            top: margin.top,
+            right: margin.right,
+            bottom: margin.bottom,
+            left: margin.left,
+        };
+
+        let is_layer_surface = self
+            .window
+            .upgrade()
+            .is_some_and(|window| window.set_margin(margin));
+
+        if !is_layer_surface {
+            warn!("[plugin: window] Margins are only supported by layer surfaces");
+        }
+    }
+
+    fn get_zoom(&mut self) -> f64 {
+        self.window
+            .upgrade()
+            .map_or(1.0, |window| window.load_content_zoom())
+    }
+
+    fn set_zoom(&mut self, zoom: f64) {
+        let Some(zoom) = clamp_zoom(zoom) else {
+            warn!("[plugin: window] Ignoring invalid zoom: {}", zoom);
+            return;
+        };
+
+        self.send_window_request("zoom", |surface| SctkApplicationRequest::SetContentZoom {
+            surface,
+            zoom,
+        });
+    }
+
+    fn is_transparency_reduced(&mut self) -> bool {
+        self.window
+            .upgrade()
+            .is_some_and(|window| window.is_transparency_reduced())
+    }
+
+    fn color_info(&mut self) -> ColorInfo {
+        self.window
+            .upgrade()
+            .map(|window| window.load_color_info())
+            .unwrap_or_default()
+    }
+
+    fn visibility(&mut self) -> Visibility {
+        self.window
+            .upgrade()
+            .map_or(Visibility::Visible, |window| window.load_visibility())
+    }
+
+    fn set_urgent(&mut self, urgent: bool) {
+        self.send_window_request("flag the window", |surface| {
+            SctkApplicationRequest::SetUrgent { surface, urgent }
+        });
+    }
+
+    fn window_id(&mut self) -> u64 {
+        self.engine_id.as_u64()
+    }
+
+    fn raise_above(&mut self, other: u64) -> bool {
+        self.send_window_request("raise the window", |surface| {
+            SctkApplicationRequest::Restack {
+                surface,
+                sibling: SctkEngineId::from_u64(other),
+                above: true,
+            }
+        })
+    }
+
+    fn lower_below(&mut self, other: u64) -> bool {
+        self.send_window_request("lower the window", |surface| {
+            SctkApplicationRequest::Restack {
+                surface,
+                sibling: SctkEngineId::from_u64(other),
+                above: false,
+            }
+        })
+    }
+
+    fn capabilities(&mut self) -> WindowCapabilities {
+        let is_toplevel = self
+            .window
+            .upgrade()
+            .is_some_and(|window| window.window().is_some());
+
+        // Wayland leaves the stacking, the taskbar and the position of the
+        // windows to the compositor, except for the attached windows.
+        WindowCapabilities {
+            transparency: true,
+            always_on_top: false,
+            skip_taskbar: false,
+            min_max_size: is_toplevel,
+            maximize: is_toplevel,
+            fullscreen: is_toplevel,
+            position: self.is_attached(),
+        }
+    }
+
+    fn set_always_on_top(&mut self, _always_on_top: bool) {
+        warn!("[plugin: window] Keeping the window above the others is not supported on Wayland");
+    }
+
+    fn set_min_size(&mut self, size: Option<SizeParams>) {
+        let size = size.map(|size| (size.width as u32, size.height as u32));
+        self.with_window(|window| window.set_min_size(size));
+    }
+
+    fn set_max_size(&mut self, size: Option<SizeParams>) {
+        let size = size.map(|size| (size.width as u32, size.height as u32));
+        if let Some(window) = self.window.upgrade() {
+            window.store_max_size(size.map(LogicalSize::from));
+        }
+        self.with_window(|window| window.set_max_size(size));
+    }
+
+    fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.with_window(|window| {
+            if fullscreen {
+                window.set_fullscreen(None);
+            } else {
+                window.unset_fullscreen();
+            }
+        });
+    }
+}
+
+pub struct SctkLoggingHandler {
+    window: Weak<SctkFlutterWindowInner>,
+    vsync_handler: Arc<parking_lot::Mutex<SctkVsyncHandler>>,
+    plugin_names: PluginNames,
+}
+
+impl SctkLoggingHandler {
+    pub(crate) fn new(
+        window: Weak<SctkFlutterWindowInner>,
+        vsync_handler: Arc<parking_lot::Mutex<SctkVsyncHandler>>,
+    ) -> Self {
+        Self {
+            window,
+            vsync_handler,
+            plugin_names: PluginNames::default(),
+        }
+    }
+
+    /// Report the plugins of the engine, as registered when the state is
+    /// dumped.
+    pub(crate) fn set_plugin_names(&mut self, plugin_names: PluginNames) {
+        self.plugin_names = plugin_names;
+    }
+
+    fn window_state(&self) -> Value {
+        let Some(window) = self.window.upgrade() else {
+            return Value::Null;
+        };
+
+        let scale_factor = window.load_current_scale_factor();
+        let size = window
+            .load_current_size()
+            .map(|size| scale_factor.to_logical_size::<f64>(size));
+
+        Value::Map(HashMap::from([
+            ("id".into(), Value::I64(window.id())),
+            (
+                "width".into(),
+                size.map_or(Value::Null, |size| Value::F64(size.width)),
+            ),
+            (
+                "height".into(),
+                size.map_or(Value::Null, |size| Value::F64(size.height)),
+            ),
+            ("scaleFactor".into(), Value::F64(scale_factor.get())),
+            (
+                "state".into(),
+                Value::String(format!("{:?}", window.load_window_state())),
+            ),
+            (
+                "suggestedBounds".into(),
+                Value::String(format!("{:?}", window.load_suggested_bounds())),
+            ),
+            (
+                "resizeState".into(),
+                Value::String(format!("{:?}", window.load_resize_status())),
+            ),
+            (
+                "outputs".into(),
+                Value::List(window.outputs().iter().map(output_state).collect()),
+            ),
+            ("visible".into(), Value::Boolean(window.is_visible())),
+        ]))
+    }
+}
+
+fn output_state(output: &WlOutput) -> Value {
+    let Some(data) = output.data::<OutputData>() else {
+        return Value::Null;
+    };
+
+    data.with_output_info(|info| {
+        let mode = info.modes.iter().find(|mode| mode.current);
+        Value::Map(HashMap::from([
+            (
+                "name".into(),
+                info.name.clone().map_or(Value::Null, Value::String),
+            ),
+            (
+                "description".into(),
+                info.description.clone().map_or(Value::Null, Value::String),
+            ),
+            ("scaleFactor".into(), Value::I64(info.scale_factor.into())),
+            (
+                "width".into(),
+                mode.map_or(Value::Null, |mode| Value::I64(mode.dimensions.0.into())),
+            ),
+            (
+                "height".into(),
+                mode.map_or(Value::Null, |mode| Value::I64(mode.dimensions.1.into())),
+            ),
+            (
+                "refreshRate".into(),
+                mode.map_or(Value::Null, |mode| {
+                    Value::F64(mode.refresh_rate as f64 / 1000.0)
+                }),
+            ),
+        ]))
+    })
+}
+
+impl LoggingHandler for SctkLoggingHandler {
+    fn dump_state(&mut self) -> Value {
+        let pending_baton = self.vsync_handler.lock().load_pending_baton();
+
+        Value::Map(HashMap::from([
+            ("windows".into(), Value::List(vec![self.window_state()])),
+            ("pendingBaton".into(), Value::I64(pending_baton as i64)),
+            (
+                "plugins".into(),
+                Value::List(
+                    self.plugin_names
+                        .get()
+                        .into_iter()
+                        .map(|name| Value::String(name.into()))
+                        .collect(),
+                ),
+            ),
+        ]))
+    }
+}
+
+/// Forwards the text input state to the application state, which owns the
+/// `zwp_text_input_v3` objects.
+pub struct SctkTextInputHandler {
+    sender: Sender<SctkApplicationRequest>,
+}
 
 impl SctkTextInputHandler {
-    pub fn new() -> Self {
-        Default::default()
+    pub(crate) fn new(sender: Sender<SctkApplicationRequest>) -> Self {
+        Self { sender }
+    }
+
+    fn send(&self, request: SctkApplicationRequest) {
+        if self.sender.send(request).is_err() {
+            warn!("[plugin: textinput] Ignoring request because the event loop is gone");
+        }
     }
 }
 
 impl TextInputHandler for SctkTextInputHandler {
-    fn show(&mut self) {}
+    fn show(&mut self) {
+        self.send(SctkApplicationRequest::SetTextInputEnabled(true));
+    }
 
-    fn hide(&mut self) {}
+    fn hide(&mut self) {
+        self.send(SctkApplicationRequest::SetTextInputEnabled(false));
+    }
+
+    fn focus_changed(&mut self, info: &TextInputFocusInfo) {
+        self.send(SctkApplicationRequest::SetTextInputFocus(info.clone()));
+    }
 }
 
 #[derive(Error, Debug)]
@@ -646,7 +2046,7 @@ impl SctkKeyboardHandler {
     }
 
     pub(crate) fn press_key(&mut self, event: KeyEvent) -> Result<(), SctkPressedStateError> {
-        let physical = SctkPhysicalKey::new(event.raw_code);
+        let physical = XkbPhysicalKey::new(event.raw_code);
 
         match self.pressed_state.insert(physical.into(), event) {
             Some(_) => Err(SctkPressedStateError::InconsistentState),
@@ -658,7 +2058,7 @@ impl SctkKeyboardHandler {
         &mut self,
         event: &KeyEvent,
     ) -> Result<Keysym, SctkPressedStateError> {
-        let physical = SctkPhysicalKey::new(event.raw_code);
+        let physical = XkbPhysicalKey::new(event.raw_code);
 
         match self.pressed_state.remove(&physical.into()) {
             Some(event) => Ok(event.keysym),
@@ -671,7 +2071,7 @@ impl SctkKeyboardHandler {
         raw: &[u32],
         keysyms: &[Keysym],
     ) -> Vec<SctkKeyEvent> {
-        let current_time = unsafe { FlutterEngineGetCurrentTime() };
+        let current_time = FlutterEngine::get_current_time_nanos();
         let time = Duration::from_nanos(current_time).as_millis() as u32;
 
         let pressed_keys: Vec<_> = zip(raw, keysyms)
@@ -709,7 +2109,7 @@ impl SctkKeyboardHandler {
             .filter_map(|event| {
                 if self
                     .pressed_state
-                    .insert(SctkPhysicalKey::new(event.raw_code).into(), event.clone())
+                    .insert(XkbPhysicalKey::new(event.raw_code).into(), event.clone())
                     .is_some()
                 {
                     return None;
@@ -740,7 +2140,7 @@ impl KeyboardStateHandler for SctkKeyboardHandler {
             .map(|(physical_key, event)| {
                 (
                     physical_key.clone(),
-                    SctkLogicalKey::new(event.keysym).into(),
+                    XkbLogicalKey::new(event.keysym).into(),
                 )
             })
             .collect();
@@ -749,8 +2149,34 @@ impl KeyboardStateHandler for SctkKeyboardHandler {
     }
 }
 
+/// Frames are rendered every `n` refreshes, with `n` being the smallest
+/// number of refreshes lasting at least `min_frame_interval`. This keeps the
+/// frames aligned on the refreshes (e.g.: every 4th one for a 60Hz cap on a
+/// 240Hz display).
+fn paced_frame_interval(
+    elapsed: u64,
+    refresh_interval: u64,
+    min_frame_interval: u64,
+) -> Option<u64> {
+    if refresh_interval == 0 {
+        return Some(refresh_interval);
+    }
+
+    // Leave some slack for the rounding of the intervals.
+    let refreshes = min_frame_interval
+        .saturating_sub(refresh_interval / 100)
+        .div_ceil(refresh_interval);
+    if refreshes <= 1 {
+        return Some(refresh_interval);
+    }
+
+    // The callbacks are received with some jitter.
+    let frame_interval = refreshes * refresh_interval;
+    (elapsed + refresh_interval / 4 >= frame_interval).then_some(frame_interval)
+}
+
 pub(crate) fn get_flutter_frame_time_nanos(frame_interval: u64) -> (u64, u64) {
-    let current_time = unsafe { FlutterEngineGetCurrentTime() };
+    let current_time = FlutterEngine::get_current_time_nanos();
     let frame_start_time_nanos = current_time;
     let frame_target_time_nanos = frame_start_time_nanos + frame_interval;
 
@@ -818,3 +2244,291 @@ impl SctkSettingsHandler {
         Ok(())
     }
 }
+
+/// Forwards the toolbar requests to the application state, which owns the
+/// Wayland objects needed for creating the popup.
+pub struct SctkTextToolbarHandler {
+    sender: Sender<SctkApplicationRequest>,
+}
+
+impl SctkTextToolbarHandler {
+    pub(crate) fn new(sender: Sender<SctkApplicationRequest>) -> Self {
+        Self { sender }
+    }
+
+    fn send(&self, request: SctkApplicationRequest) {
+        if self.sender.send(request).is_err() {
+            warn!("[plugin: text_toolbar] Ignoring request because the event loop is gone");
+        }
+    }
+}
+
+impl TextToolbarHandler for SctkTextToolbarHandler {
+    fn show(
+        &mut self,
+        rect: TextToolbarRect,
+        placement: PopupPlacement,
+        actions: Vec<TextToolbarAction>,
+    ) {
+        self.send(SctkApplicationRequest::ShowTextToolbar {
+            rect,
+            placement,
+            actions,
+        });
+    }
+
+    fn hide(&mut self) {
+        self.send(SctkApplicationRequest::HideTextToolbar);
+    }
+}
+
+/// Inhibits the screensaver using the XDG desktop portal. The portal requests
+/// are asynchronous, so the inhibitor tracks the last requested state and
+/// converges to it from a single task at a time.
+#[derive(Clone, Default)]
+pub(crate) struct SctkScreenSaverInhibitor {
+    state: Rc<RefCell<SctkScreenSaverInhibitorState>>,
+    is_inhibited: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct SctkScreenSaverInhibitorState {
+    requested: bool,
+    request: Option<Request<()>>,
+    is_syncing: bool,
+}
+
+impl SctkScreenSaverInhibitor {
+    pub(crate) fn create_handler(
+        &self,
+        sender: Sender<SctkApplicationRequest>,
+    ) -> SctkScreenSaverHandler {
+        SctkScreenSaverHandler {
+            sender,
+            is_inhibited: self.is_inhibited.clone(),
+        }
+    }
+
+    /// Store the state applied by another inhibition mechanism.
+    pub(crate) fn store_inhibited(&self, inhibited: bool) {
+        self.is_inhibited.store(inhibited, Ordering::Relaxed);
+    }
+
+    /// Returns the task that needs to be scheduled for applying the requested
+    /// state, if one is not already running.
+    pub(crate) fn set_inhibited(
+        &self,
+        inhibited: bool,
+    ) -> Option<impl Future<Output = SctkAsyncResult>> {
+        let mut state = self.state.borrow_mut();
+        state.requested = inhibited;
+
+        if state.is_syncing {
+            return None;
+        }
+        state.is_syncing = true;
+
+        Some(self.clone().sync())
+    }
+
+    async fn sync(self) -> SctkAsyncResult {
+        if let Err(err) = self.try_sync().await {
+            warn!("[plugin: screensaver] Unable to update screensaver inhibition: {}", err);
+        }
+
+        self.state.borrow_mut().is_syncing = false;
+        Ok(())
+    }
+
+    async fn try_sync(&self) -> Result<(), ashpd::Error> {
+        loop {
+            let requested = self.state.borrow().requested;
+            if requested == self.state.borrow().request.is_some() {
+                return Ok(());
+            }
+
+            if requested {
+                let proxy = InhibitProxy::new().await?;
+                let request = proxy
+                    .inhibit(
+                        &WindowIdentifier::default(),
+                        InhibitFlags::Idle.into(),
+                        "Inhibited by the application",
+                    )
+                    .await?;
+
+                self.state.borrow_mut().request = Some(request);
+                self.is_inhibited.store(true, Ordering::Relaxed);
+            } else {
+                let request = self.state.borrow_mut().request.take();
+                self.is_inhibited.store(false, Ordering::Relaxed);
+
+                if let Some(request) = request {
+                    request.close().await?;
+                }
+            }
+        }
+    }
+}
+
+pub struct SctkScreenSaverHandler {
+    sender: Sender<SctkApplicationRequest>,
+    is_inhibited: Arc<AtomicBool>,
+}
+
+impl ScreenSaverHandler for SctkScreenSaverHandler {
+    fn set_inhibited(&mut self, inhibited: bool) {
+        let request = SctkApplicationRequest::SetScreenSaverInhibited(inhibited);
+        if self.sender.send(request).is_err() {
+            warn!("[plugin: screensaver] Ignoring request because the event loop is gone");
+        }
+    }
+
+    fn is_inhibited(&mut self) -> bool {
+        self.is_inhibited.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_plugins::mousecursor::SystemMouseCursor;
+    use smithay_client_toolkit::{
+        reexports::protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape,
+        seat::pointer::CursorIcon,
+    };
+
+    use super::{paced_frame_interval, SctkCursorStack, SctkMouseCursor};
+
+    const HZ_60: u64 = 1_000_000_000 / 60;
+    const HZ_144: u64 = 1_000_000_000 / 144;
+    const HZ_165: u64 = 1_000_000_000 / 165;
+    const HZ_240: u64 = 1_000_000_000 / 240;
+
+    #[test]
+    fn renders_every_refresh_without_cap() {
+        assert_eq!(paced_frame_interval(HZ_240, HZ_240, 0), Some(HZ_240));
+        // Early callbacks (e.g.: after moving to another output) are answered.
+        assert_eq!(paced_frame_interval(0, HZ_240, 0), Some(HZ_240));
+        assert_eq!(paced_frame_interval(0, HZ_60, HZ_240), Some(HZ_60));
+        assert_eq!(paced_frame_interval(0, HZ_60, HZ_60), Some(HZ_60));
+    }
+
+    #[test]
+    fn skips_refreshes_below_min_frame_interval() {
+        let frames: Vec<_> = (1..=5)
+            .map(|refreshes| paced_frame_interval(refreshes * HZ_240, HZ_240, HZ_60))
+            .collect();
+        let frame_interval = 4 * HZ_240;
+        assert_eq!(frames, [None, None, None, Some(frame_interval), Some(frame_interval)]);
+
+        // Tolerates the jitter of the callbacks.
+        let elapsed = 4 * HZ_240 - 500_000;
+        assert_eq!(paced_frame_interval(elapsed, HZ_240, HZ_60), Some(frame_interval));
+    }
+
+    #[test]
+    fn never_exceeds_the_cap() {
+        // Every other refresh, since every refresh would exceed 144Hz.
+        assert_eq!(paced_frame_interval(HZ_165, HZ_165, HZ_144), None);
+        assert_eq!(paced_frame_interval(2 * HZ_165, HZ_165, HZ_144), Some(2 * HZ_165));
+    }
+
+    #[test]
+    fn maps_system_cursors_to_shapes() {
+        let cursor = SctkMouseCursor::from(SystemMouseCursor::AllScroll);
+        assert_eq!(cursor.icon, Some(CursorIcon::AllScroll));
+        assert_eq!(cursor.shape, Some(Shape::AllScroll));
+
+        let cursor = SctkMouseCursor::from(SystemMouseCursor::ResizeUpLeftDownRight);
+        assert_eq!(cursor.shape, Some(Shape::NwseResize));
+
+        let hidden = SctkMouseCursor::from(SystemMouseCursor::None);
+        assert_eq!(hidden, SctkMouseCursor { icon: None, shape: None });
+    }
+
+    /// Cursor shown by the compositor, which resets it on every enter and
+    /// ignores the requests with a stale serial.
+    #[derive(Default)]
+    struct Compositor {
+        enter_serial: u32,
+        cursor: Option<SctkMouseCursor>,
+    }
+
+    impl Compositor {
+        fn enter(&mut self, stack: &mut SctkCursorStack) {
+            self.enter_serial += 1;
+            self.cursor = None;
+            let (serial, cursor) = stack.pointer_entered(self.enter_serial);
+            self.set_cursor(Some((serial, cursor)));
+        }
+
+        fn leave(&mut self, stack: &mut SctkCursorStack) {
+            stack.pointer_left();
+        }
+
+        fn set_cursor(&mut self, request: Option<(u32, SctkMouseCursor)>) {
+            match request {
+                Some((serial, cursor)) if serial == self.enter_serial => self.cursor = Some(cursor),
+                _ => {}
+            }
+        }
+
+        fn shape(&self) -> Option<Shape> {
+            self.cursor.as_ref().and_then(|cursor| cursor.shape)
+        }
+    }
+
+    #[test]
+    fn restores_the_cursor_on_enter() {
+        let mut compositor = Compositor::default();
+        let mut stack = SctkCursorStack::new();
+
+        // The widget under the pointer requests its cursor before the enter.
+        let request = stack.update(|stack| stack.set_base(SystemMouseCursor::Text.into()));
+        assert_eq!(request, None);
+        compositor.enter(&mut stack);
+        assert_eq!(compositor.shape(), Some(Shape::Text));
+
+        // Changes made while the pointer is outside are applied on the enter.
+        compositor.leave(&mut stack);
+        let request = stack.update(|stack| stack.set_base(SystemMouseCursor::Click.into()));
+        assert_eq!(request, None);
+        compositor.enter(&mut stack);
+        assert_eq!(compositor.shape(), Some(Shape::Pointer));
+
+        let request = stack.update(|stack| stack.set_base(SystemMouseCursor::Basic.into()));
+        compositor.set_cursor(request);
+        assert_eq!(compositor.shape(), Some(Shape::Default));
+    }
+
+    #[test]
+    fn keeps_the_override_during_a_drag() {
+        let mut compositor = Compositor::default();
+        let mut stack = SctkCursorStack::new();
+        compositor.enter(&mut stack);
+        let request = stack.update(|stack| stack.set_base(SystemMouseCursor::Grab.into()));
+        compositor.set_cursor(request);
+        assert_eq!(compositor.shape(), Some(Shape::Grab));
+
+        let request = stack.update(|stack| stack.push_override(0, CursorIcon::Grabbing.into()));
+        compositor.set_cursor(request);
+        assert_eq!(compositor.shape(), Some(Shape::Grabbing));
+
+        // The widgets under the dragged item don't change the cursor.
+        let request = stack.update(|stack| stack.set_base(SystemMouseCursor::Text.into()));
+        assert_eq!(request, None);
+        compositor.leave(&mut stack);
+        compositor.enter(&mut stack);
+        assert_eq!(compositor.shape(), Some(Shape::Grabbing));
+
+        // The latest override wins, regardless of the order they are removed.
+        let request = stack.update(|stack| stack.push_override(1, CursorIcon::NoDrop.into()));
+        compositor.set_cursor(request);
+        assert_eq!(compositor.shape(), Some(Shape::NoDrop));
+        let request = stack.update(|stack| stack.remove_override(0));
+        assert_eq!(request, None);
+        let request = stack.update(|stack| stack.remove_override(1));
+        compositor.set_cursor(request);
+        assert_eq!(compositor.shape(), Some(Shape::Text));
+    }
+}
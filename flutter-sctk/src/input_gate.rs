@@ -0,0 +1,393 @@
+//! Gating of the user input until the Dart app is ready (see
+//! `ApplicationBuilder::with_gate_input_until_first_frame`).
+//!
+//! The pointer and key events received once the engine is running are
+//! buffered until the app presents its first frame, so that an early click
+//! doesn't reach Dart before the app registered its channel handlers. The app
+//! may also end the gating earlier, or extend it until it is initialized,
+//! with the `flutter-rs/ready` channel. The gating always ends after the
+//! timeout of the config, so that a buggy app can't lock the input forever.
+//!
+//! The buffered events are replayed in order, through the pointer
+//! back-pressure of the engine (see `FlutterEngine::send_pointer_event`).
+//! The add and hover events are synthesized by the window as usual (see
+//! `Pointer::enter_events`), so the gate never drops them: only the motion
+//! superseded by a later one is coalesced.
+use std::{
+    collections::VecDeque,
+    sync::Weak,
+    time::{Duration, Instant},
+};
+
+use calloop::channel::Sender;
+use flutter_engine::ffi::FlutterPointerEvent;
+use flutter_input::keyboard::KeyEventFallthrough;
+use flutter_plugins::ready::ReadyHandler;
+use flutter_runner_api::InputGateConfig;
+use tracing::warn;
+
+use crate::{
+    application::SctkApplicationRequest, keyboard::SctkKeyEvent, window::SctkFlutterWindowInner,
+};
+
+/// Maximum number of buffered events, the gating ends once exceeded.
+pub(crate) const MAX_GATED_INPUTS: usize = 256;
+
+pub(crate) enum SctkGatedInput {
+    Pointer(FlutterPointerEvent),
+    Key(SctkKeyEvent, Option<KeyEventFallthrough>),
+}
+
+pub(crate) enum SctkGatedPush {
+    /// The input isn't gated, it must be sent right away.
+    Send(SctkGatedInput),
+    Buffered,
+    /// The input was buffered, but the buffer is full: the gating must end.
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GateState {
+    /// Until the first frame, and until `ready` once extended.
+    Closed {
+        deadline: Option<Instant>,
+        is_extended: bool,
+    },
+    Open,
+}
+
+pub(crate) struct SctkInputGate {
+    state: GateState,
+    timeout: Duration,
+    inputs: VecDeque<SctkGatedInput>,
+}
+
+impl SctkInputGate {
+    pub(crate) fn new(config: InputGateConfig) -> Self {
+        let state = if config.enabled {
+            GateState::Closed {
+                deadline: None,
+                is_extended: false,
+            }
+        } else {
+            GateState::Open
+        };
+
+        Self {
+            state,
+            timeout: config.timeout,
+            inputs: VecDeque::new(),
+        }
+    }
+
+    /// The engine is running. Returns the timeout after which the gating
+    /// must end, if the input is gated.
+    pub(crate) fn start(&mut self, now: Instant) -> Option<Duration> {
+        let GateState::Closed { deadline, .. } = &mut self.state else {
+            return None;
+        };
+
+        *deadline = Some(now + self.timeout);
+        Some(self.timeout)
+    }
+
+    pub(crate) fn push(&mut self, input: SctkGatedInput) -> SctkGatedPush {
+        if self.state == GateState::Open {
+            return SctkGatedPush::Send(input);
+        }
+
+        if let SctkGatedInput::Pointer(event) = &input {
+            self.coalesce_motion(event);
+        }
+        self.inputs.push_back(input);
+
+        if self.inputs.len() > MAX_GATED_INPUTS {
+            SctkGatedPush::Full
+        } else {
+            SctkGatedPush::Buffered
+        }
+    }
+
+    /// Drop the previous motion of the device of |event| if |event| is a
+    /// motion superseding it, i.e.: without any discrete event of the device
+    /// in between (e.g.: a down, or a scroll).
+    fn coalesce_motion(&mut self, event: &FlutterPointerEvent) {
+        if !event.is_motion() {
+            return;
+        }
+
+        let previous = self.inputs.iter().rposition(|input| match input {
+            SctkGatedInput::Pointer(previous) => previous.device() == event.device(),
+            SctkGatedInput::Key(..) => false,
+        });
+        let Some(index) = previous else {
+            return;
+        };
+        let SctkGatedInput::Pointer(previous) = &self.inputs[index] else {
+            return;
+        };
+
+        if previous.is_motion()
+            && previous.phase() == event.phase()
+            && previous.view_id() == event.view_id()
+        {
+            self.inputs.remove(index);
+        }
+    }
+
+    /// The app presented its first frame. Returns whether the gating must
+    /// end, i.e.: unless the app extended it.
+    pub(crate) fn first_frame_presented(&self) -> bool {
+        matches!(
+            self.state,
+            GateState::Closed {
+                is_extended: false,
+                ..
+            }
+        )
+    }
+
+    /// Keep gating the input past the first frame. Returns `false` when the
+    /// gating already ended.
+    pub(crate) fn extend(&mut self) -> bool {
+        let GateState::Closed { is_extended, .. } = &mut self.state else {
+            return false;
+        };
+
+        *is_extended = true;
+        true
+    }
+
+    /// Whether the timeout of the gating expired at |now|.
+    pub(crate) fn is_expired(&self, now: Instant) -> bool {
+        match self.state {
+            GateState::Closed { deadline, .. } => deadline.is_some_and(|deadline| now >= deadline),
+            GateState::Open => false,
+        }
+    }
+
+    /// End the gating, returning the buffered inputs to replay in order.
+    ///
+    /// The pointer events are shifted so that the latest one happens at |now|
+    /// (the time elapsed since the Unix epoch, like their timestamps), which
+    /// keeps the intervals between them (e.g.: for telling a tap from a long
+    /// press, or for the fling velocity). The key events are timestamped when
+    /// sent.
+    pub(crate) fn open(&mut self, now: Duration) -> Vec<SctkGatedInput> {
+        self.state = GateState::Open;
+
+        let latest = self
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                SctkGatedInput::Pointer(event) => Some(event.timestamp()),
+                SctkGatedInput::Key(..) => None,
+            })
+            .max();
+        let offset = latest.map_or(Duration::ZERO, |latest| now.saturating_sub(latest));
+
+        self.inputs
+            .drain(..)
+            .map(|input| match input {
+                SctkGatedInput::Pointer(event) => {
+                    let timestamp = event.timestamp() + offset;
+                    SctkGatedInput::Pointer(event.with_timestamp(timestamp))
+                }
+                key => key,
+            })
+            .collect()
+    }
+}
+
+pub struct SctkReadyHandler {
+    window: Weak<SctkFlutterWindowInner>,
+    sender: Sender<SctkApplicationRequest>,
+}
+
+impl SctkReadyHandler {
+    pub(crate) fn new(
+        window: Weak<SctkFlutterWindowInner>,
+        sender: Sender<SctkApplicationRequest>,
+    ) -> Self {
+        Self { window, sender }
+    }
+}
+
+impl ReadyHandler for SctkReadyHandler {
+    fn extend(&mut self) -> bool {
+        self.window
+            .upgrade()
+            .is_some_and(|window| window.input_gate().extend())
+    }
+
+    fn ready(&mut self) {
+        let Some(window) = self.window.upgrade() else {
+            return;
+        };
+
+        // The keys are replayed by the application, along with the pointers.
+        let request = SctkApplicationRequest::ReleaseInput {
+            surface: window.wl_surface().clone(),
+        };
+        if self.sender.send(request).is_err() {
+            warn!("[plugin: ready] Ignoring request because the event loop is gone");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use dpi::PhysicalPosition;
+    use flutter_engine::ffi::{
+        FlutterPointerDeviceKind, FlutterPointerEvent, FlutterPointerMouseButtons,
+        FlutterPointerPhase, FlutterPointerSignalKind, IMPLICIT_VIEW_ID,
+    };
+    use flutter_runner_api::InputGateConfig;
+
+    use super::{SctkGatedInput, SctkGatedPush, SctkInputGate, MAX_GATED_INPUTS};
+
+    const MOUSE: i32 = 3;
+
+    fn event(phase: FlutterPointerPhase, x: f64, millis: u64) -> SctkGatedInput {
+        let buttons = match phase {
+            FlutterPointerPhase::Down | FlutterPointerPhase::Move => {
+                FlutterPointerMouseButtons::Primary
+            }
+            _ => FlutterPointerMouseButtons::None,
+        };
+        let event = FlutterPointerEvent::new(
+            MOUSE,
+            phase,
+            PhysicalPosition::new(x, 0.0),
+            FlutterPointerSignalKind::None,
+            (0.0, 0.0),
+            FlutterPointerDeviceKind::Mouse,
+            buttons,
+            IMPLICIT_VIEW_ID,
+        )
+        .with_timestamp(Duration::from_millis(millis));
+        SctkGatedInput::Pointer(event)
+    }
+
+    fn push(gate: &mut SctkInputGate, input: SctkGatedInput) {
+        assert!(matches!(gate.push(input), SctkGatedPush::Buffered));
+    }
+
+    /// The phases and the timestamps (in milliseconds) of the replay.
+    fn replay(gate: &mut SctkInputGate, now_millis: u64) -> Vec<(FlutterPointerPhase, u128)> {
+        gate.open(Duration::from_millis(now_millis))
+            .into_iter()
+            .filter_map(|input| match input {
+                SctkGatedInput::Pointer(event) => {
+                    Some((event.phase(), event.timestamp().as_millis()))
+                }
+                SctkGatedInput::Key(..) => None,
+            })
+            .collect()
+    }
+
+    fn started_gate(now: Instant) -> SctkInputGate {
+        let mut gate = SctkInputGate::new(InputGateConfig::default());
+        assert_eq!(gate.start(now), Some(InputGateConfig::default().timeout));
+        gate
+    }
+
+    #[test]
+    fn replays_a_click_before_the_first_frame() {
+        use FlutterPointerPhase::*;
+
+        let mut gate = started_gate(Instant::now());
+        push(&mut gate, event(Add, 1.0, 1000));
+        push(&mut gate, event(Hover, 2.0, 1010));
+        push(&mut gate, event(Hover, 3.0, 1020));
+        push(&mut gate, event(Down, 3.0, 1030));
+        push(&mut gate, event(Move, 4.0, 1040));
+        push(&mut gate, event(Move, 5.0, 1050));
+        push(&mut gate, event(Up, 5.0, 1100));
+
+        assert!(gate.first_frame_presented());
+        // The superseded motion is coalesced, and the intervals are kept.
+        assert_eq!(
+            replay(&mut gate, 2000),
+            [
+                (Add, 1900),
+                (Hover, 1920),
+                (Down, 1930),
+                (Move, 1950),
+                (Up, 2000)
+            ]
+        );
+
+        assert!(matches!(
+            gate.push(event(Hover, 6.0, 2010)),
+            SctkGatedPush::Send(_)
+        ));
+    }
+
+    #[test]
+    fn ends_the_gating_on_timeout() {
+        let now = Instant::now();
+        let mut gate = started_gate(now);
+        push(&mut gate, event(FlutterPointerPhase::Add, 1.0, 1000));
+
+        // The app extended the gating, but never said it is ready.
+        assert!(gate.extend());
+        assert!(!gate.first_frame_presented());
+        assert!(!gate.is_expired(now + Duration::from_secs(1)));
+
+        assert!(gate.is_expired(now + InputGateConfig::default().timeout));
+        assert_eq!(replay(&mut gate, 8000), [(FlutterPointerPhase::Add, 8000)]);
+        assert!(!gate.extend());
+    }
+
+    #[test]
+    fn ends_the_gating_when_the_app_is_ready() {
+        let now = Instant::now();
+        let mut gate = started_gate(now);
+        push(&mut gate, event(FlutterPointerPhase::Add, 1.0, 1000));
+        push(&mut gate, event(FlutterPointerPhase::Down, 1.0, 1010));
+
+        // The app said it is ready before presenting its first frame.
+        assert_eq!(
+            replay(&mut gate, 1500),
+            [
+                (FlutterPointerPhase::Add, 1490),
+                (FlutterPointerPhase::Down, 1500)
+            ]
+        );
+        assert!(!gate.first_frame_presented());
+        assert!(!gate.is_expired(now + InputGateConfig::default().timeout));
+    }
+
+    #[test]
+    fn ends_the_gating_once_full() {
+        let mut gate = started_gate(Instant::now());
+        for millis in 0..MAX_GATED_INPUTS as u64 {
+            let phase = if millis % 2 == 0 {
+                FlutterPointerPhase::Down
+            } else {
+                FlutterPointerPhase::Up
+            };
+            push(&mut gate, event(phase, 0.0, millis));
+        }
+
+        let result = gate.push(event(FlutterPointerPhase::Down, 0.0, 1000));
+        assert!(matches!(result, SctkGatedPush::Full));
+        assert_eq!(replay(&mut gate, 2000).len(), MAX_GATED_INPUTS + 1);
+    }
+
+    #[test]
+    fn does_not_gate_when_disabled() {
+        let mut gate = SctkInputGate::new(InputGateConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        assert_eq!(gate.start(Instant::now()), None);
+        assert!(matches!(
+            gate.push(event(FlutterPointerPhase::Add, 0.0, 0)),
+            SctkGatedPush::Send(_)
+        ));
+    }
+}
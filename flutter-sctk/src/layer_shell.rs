@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use dpi::LogicalSize;
+use flutter_runner_api::{
+    KeyboardInteractivity as FlutterKeyboardInteractivity, LayerAnchors, LayerMargin, ShellLayer,
+    WindowRole,
+};
+use smithay_client_toolkit::shell::{
+    wlr_layer::{Anchor, KeyboardInteractivity, Layer, LayerShell, LayerSurface},
+    WaylandSurface,
+};
+use tracing::warn;
+use wayland_backend::client::ObjectId;
+use wayland_client::{
+    globals::{BindError, GlobalList},
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+use crate::application::SctkApplicationState;
+
+const DEFAULT_NAMESPACE: &str = "flutter";
+
+/// The `zwlr_layer_shell_v1` global, along with the options of the layer
+/// surface requested through [`WindowRole::LayerShell`].
+pub(crate) struct SctkLayerShell {
+    layer_shell: LayerShell,
+    layer: Layer,
+    anchor: Anchor,
+    exclusive_zone: i32,
+    margin: LayerMargin,
+    keyboard_interactivity: KeyboardInteractivity,
+    output: Option<WlOutput>,
+}
+
+impl SctkLayerShell {
+    /// Bind the layer shell global when the `role` requires it. Returns
+    /// `Ok(None)` for regular windows.
+    pub(crate) fn bind(
+        conn: &Connection,
+        globals: &GlobalList,
+        qh: &QueueHandle<SctkApplicationState>,
+        role: &WindowRole,
+    ) -> Result<Option<Self>, BindError> {
+        let WindowRole::LayerShell {
+            layer,
+            anchors,
+            exclusive_zone,
+            margin,
+            keyboard_interactivity,
+            output,
+        } = role
+        else {
+            return Ok(None);
+        };
+
+        let layer_shell = LayerShell::bind(globals, qh)?;
+
+        let output = output.as_deref().and_then(|name| {
+            let output = find_output_by_name(conn, globals, name);
+            if output.is_none() {
+                warn!("Output `{}` not found, letting the compositor pick one", name);
+            }
+            output
+        });
+
+        Ok(Some(Self {
+            layer_shell,
+            layer: to_sctk_layer(*layer),
+            anchor: to_sctk_anchor(*anchors),
+            exclusive_zone: *exclusive_zone,
+            margin: *margin,
+            keyboard_interactivity: to_sctk_keyboard_interactivity(*keyboard_interactivity),
+            output,
+        }))
+    }
+
+    /// Create the layer surface and commit its initial state. The compositor
+    /// replies with the first configure event.
+    pub(crate) fn create_layer_surface(
+        &self,
+        qh: &QueueHandle<SctkApplicationState>,
+        surface: WlSurface,
+        namespace: Option<String>,
+        size: LogicalSize<u32>,
+    ) -> LayerSurface {
+        let layer_surface = self.layer_shell.create_layer_surface(
+            qh,
+            surface,
+            self.layer,
+            Some(namespace.unwrap_or_else(|| DEFAULT_NAMESPACE.into())),
+            self.output.as_ref(),
+        );
+
+        let (width, height) = layer_surface_size(self.anchor, size);
+        layer_surface.set_size(width, height);
+        layer_surface.set_anchor(self.anchor);
+        layer_surface.set_exclusive_zone(self.exclusive_zone);
+        set_margin(&layer_surface, self.margin);
+        layer_surface.set_keyboard_interactivity(self.keyboard_interactivity);
+        layer_surface.commit();
+
+        layer_surface
+    }
+}
+
+pub(crate) fn set_margin(layer_surface: &LayerSurface, margin: LayerMargin) {
+    layer_surface.set_margin(margin.top, margin.right, margin.bottom, margin.left);
+}
+
+/// Size requested for a layer surface. A `0` dimension lets the compositor
+/// pick it, which is required along the axes anchored to both edges.
+fn layer_surface_size(anchor: Anchor, size: LogicalSize<u32>) -> (u32, u32) {
+    let width = if anchor.contains(Anchor::LEFT | Anchor::RIGHT) {
+        0
+    } else {
+        size.width
+    };
+
+    let height = if anchor.contains(Anchor::TOP | Anchor::BOTTOM) {
+        0
+    } else {
+        size.height
+    };
+
+    (width, height)
+}
+
+fn to_sctk_layer(layer: ShellLayer) -> Layer {
+    match layer {
+        ShellLayer::Background => Layer::Background,
+        ShellLayer::Bottom => Layer::Bottom,
+        ShellLayer::Top => Layer::Top,
+        ShellLayer::Overlay => Layer::Overlay,
+    }
+}
+
+fn to_sctk_anchor(anchors: LayerAnchors) -> Anchor {
+    let mut anchor = Anchor::empty();
+    anchor.set(Anchor::TOP, anchors.top);
+    anchor.set(Anchor::BOTTOM, anchors.bottom);
+    anchor.set(Anchor::LEFT, anchors.left);
+    anchor.set(Anchor::RIGHT, anchors.right);
+    anchor
+}
+
+fn to_sctk_keyboard_interactivity(
+    keyboard_interactivity: FlutterKeyboardInteractivity,
+) -> KeyboardInteractivity {
+    match keyboard_interactivity {
+        FlutterKeyboardInteractivity::None => KeyboardInteractivity::None,
+        FlutterKeyboardInteractivity::Exclusive => KeyboardInteractivity::Exclusive,
+        FlutterKeyboardInteractivity::OnDemand => KeyboardInteractivity::OnDemand,
+    }
+}
+
+#[derive(Default)]
+struct OutputNames(HashMap<ObjectId, String>);
+
+impl Dispatch<WlOutput, ()> for OutputNames {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: wl_output::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            state.0.insert(output.id(), name);
+        }
+    }
+}
+
+/// Layer surfaces are bound to an output when created, which happens before
+/// the application event queue has been dispatched. The output names are
+/// therefore retrieved using a dedicated queue.
+fn find_output_by_name(conn: &Connection, globals: &GlobalList, name: &str) -> Option<WlOutput> {
+    let mut queue = conn.new_event_queue::<OutputNames>();
+    let qh = queue.handle();
+
+    // The `name` event requires version 4 of `wl_output`.
+    let outputs: Vec<WlOutput> = globals.contents().with_list(|list| {
+        list.iter()
+            .filter(|global| global.interface == WlOutput::interface().name && global.version >= 4)
+            .map(|global| globals.registry().bind(global.name, 4, &qh, ()))
+            .collect()
+    });
+
+    let mut output_names = OutputNames::default();
+    if let Err(err) = queue.roundtrip(&mut output_names) {
+        warn!("Failed to retrieve the output names: {}", err);
+        return None;
+    }
+
+    let mut found = None;
+    for output in outputs {
+        if found.is_none() && output_names.0.get(&output.id()).is_some_and(|n| n == name) {
+            found = Some(output);
+        } else {
+            output.release();
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use dpi::LogicalSize;
+    use flutter_runner_api::LayerAnchors;
+
+    use super::{layer_surface_size, to_sctk_anchor};
+
+    #[test]
+    fn layer_surface_size_is_picked_by_the_compositor_along_stretched_axes() {
+        let size = LogicalSize::new(800, 32);
+
+        let anchor = to_sctk_anchor(LayerAnchors::TOP_EDGE);
+        assert_eq!(layer_surface_size(anchor, size), (0, 32));
+
+        let anchor = to_sctk_anchor(LayerAnchors::FILL);
+        assert_eq!(layer_surface_size(anchor, size), (0, 0));
+
+        let anchor = to_sctk_anchor(LayerAnchors {
+            top: true,
+            ..Default::default()
+        });
+        assert_eq!(layer_surface_size(anchor, size), (800, 32));
+    }
+}
@@ -1,8 +1,39 @@
+mod accessibility;
+mod appearance;
 pub mod application;
+mod attached;
+#[cfg(feature = "autofill-freedesktop")]
+pub mod autofill;
+mod bitmap_font;
+mod color;
+mod damage;
 mod egl;
+#[cfg(feature = "emulated-input")]
+mod emulated_input;
+pub mod engine;
+pub mod extension;
+mod failure;
+pub mod golden;
 mod handler;
-mod key_mapping_gen;
+mod input_gate;
 mod keyboard;
+mod layer_shell;
+pub mod mirror;
+mod occlusion;
 mod output;
+mod overlay;
 mod pointer;
+mod quad;
+pub mod readback;
+mod scroll;
+pub mod shutdown;
+mod solid;
+mod stacking;
+mod text_input;
+mod text_toolbar;
+#[cfg(feature = "status-notifier")]
+mod tray;
+mod units;
 pub mod window;
+mod window_state;
+mod zoom;
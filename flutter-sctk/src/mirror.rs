@@ -0,0 +1,627 @@
+//! Mirror windows show the content of a Flutter view on an additional surface
+//! (e.g.: on a secondary output for presenter or kiosk setups) without adding
+//! a view to the engine.
+//!
+//! Every frame presented in the source view is copied to a framebuffer owned
+//! by the mirror. The mirror then presents the latest copied frame whenever
+//! its own surface is ready for one (i.e.: paced by its own frame callbacks),
+//! so a mirror shown on a slower output never delays the source view.
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+};
+
+use dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
+use flutter_engine::ffi::FlutterViewId;
+use flutter_engine_sys::FlutterEngineDisplayId;
+use flutter_glutin::{
+    context::{Context, SecondarySurface},
+    gl,
+};
+use smithay_client_toolkit::shell::{xdg::window::Window, WaylandSurface};
+use thiserror::Error;
+use tracing::warn;
+use wayland_backend::client::ObjectId;
+use wayland_client::{protocol::wl_surface::WlSurface, QueueHandle};
+
+use crate::{
+    application::SctkApplicationState,
+    egl::{new_wayland_secondary_surface, CreateWaylandContextError, NonZeroU32PhysicalSize},
+    handler::WINDOW_FRAMEBUFFER_ID,
+    units::Scale,
+};
+
+/// What to do with the pointer input received by a mirror window.
+///
+/// Note: Keyboard input is not affected, key events are always sent to the
+/// engine regardless of the focused surface.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SctkMirrorInput {
+    /// Ignore the pointer input of the mirror window.
+    #[default]
+    Drop,
+    /// Send the pointer input to the source view, as if it happened at the
+    /// matching position of the source window.
+    MapToSource,
+}
+
+/// Area of a mirror surface the source content is drawn to, in physical
+/// pixels (origin at the top-left corner).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MirrorViewport {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+}
+
+impl MirrorViewport {
+    /// Scale |source| to fit |target| while preserving its aspect ratio. The
+    /// content is centered and the remaining area is left for the bars.
+    pub(crate) fn letterbox(source: PhysicalSize<u32>, target: PhysicalSize<u32>) -> Option<Self> {
+        if source.width == 0 || source.height == 0 || target.width == 0 || target.height == 0 {
+            return None;
+        }
+
+        let (source_width, source_height) = (source.width as u64, source.height as u64);
+        let (target_width, target_height) = (target.width as u64, target.height as u64);
+
+        // Compare the aspect ratios using integers to avoid rounding issues
+        // when they are the same.
+        let (width, height) = if target_width * source_height <= target_height * source_width {
+            let height = (target_width * source_height + source_width / 2) / source_width;
+            (target_width, height.clamp(1, target_height))
+        } else {
+            let width = (target_height * source_width + source_height / 2) / source_height;
+            (width.clamp(1, target_width), target_height)
+        };
+
+        Some(Self {
+            x: ((target_width - width) / 2) as i32,
+            y: ((target_height - height) / 2) as i32,
+            width: width as i32,
+            height: height as i32,
+        })
+    }
+
+    /// Map |position| (in physical pixels of the mirror surface) to the
+    /// matching position of a |source| sized view. Positions inside the bars
+    /// are clamped to the closest edge of the content.
+    pub(crate) fn map_to_source(
+        &self,
+        source: PhysicalSize<u32>,
+        position: PhysicalPosition<f64>,
+    ) -> PhysicalPosition<f64> {
+        let x = (position.x - self.x as f64).clamp(0.0, self.width as f64);
+        let y = (position.y - self.y as f64).clamp(0.0, self.height as f64);
+
+        PhysicalPosition::new(
+            x * source.width as f64 / self.width as f64,
+            y * source.height as f64 / self.height as f64,
+        )
+    }
+}
+
+/// Tracks when a mirror surface should be presented.
+#[derive(Debug, Default, Clone, Copy)]
+struct MirrorPacing {
+    /// The surface can't be presented before its first configure event.
+    is_configured: bool,
+    /// A frame callback has been requested and not received yet.
+    is_frame_pending: bool,
+    /// The content changed since the last present.
+    is_dirty: bool,
+}
+
+impl MirrorPacing {
+    fn needs_present(&self) -> bool {
+        self.is_configured && !self.is_frame_pending && self.is_dirty
+    }
+
+    fn configured(&mut self) {
+        self.is_configured = true;
+        self.is_dirty = true;
+    }
+
+    fn content_changed(&mut self) {
+        self.is_dirty = true;
+    }
+
+    fn frame_done(&mut self) {
+        self.is_frame_pending = false;
+    }
+
+    fn presented(&mut self) {
+        self.is_frame_pending = true;
+        self.is_dirty = false;
+    }
+}
+
+/// Copy of the latest frame of the source view.
+struct MirrorFrame {
+    framebuffer_id: u32,
+    texture_id: u32,
+    size: PhysicalSize<u32>,
+}
+
+/// Render thread side of a mirror window.
+pub(crate) struct SctkMirrorTarget {
+    // Note: The EGL surface must be dropped before the Wayland surface of the
+    // window (i.e.: keep the field order).
+    surface: SecondarySurface,
+    window: Window,
+    qh: QueueHandle<SctkApplicationState>,
+    size: PhysicalSize<u32>,
+    surface_size: PhysicalSize<u32>,
+    frame: Option<MirrorFrame>,
+    pacing: MirrorPacing,
+}
+
+impl SctkMirrorTarget {
+    pub(crate) fn new(
+        surface: SecondarySurface,
+        window: Window,
+        qh: QueueHandle<SctkApplicationState>,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        Self {
+            surface,
+            window,
+            qh,
+            size,
+            surface_size: size,
+            frame: None,
+            pacing: MirrorPacing::default(),
+        }
+    }
+
+    /// Copy the content of the |source_id| framebuffer. The context must be
+    /// current.
+    fn copy_frame(&mut self, gl: &gl::Gl, source_id: u32, size: PhysicalSize<u32>) {
+        let frame = match self.frame.take() {
+            Some(frame) if frame.size == size => frame,
+            frame => {
+                if let Some(frame) = frame {
+                    delete_frame(gl, &frame);
+                }
+                create_frame(gl, size)
+            }
+        };
+
+        unsafe {
+            gl.BindFramebuffer(gl::READ_FRAMEBUFFER, source_id);
+            gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, frame.framebuffer_id);
+            gl.BlitFramebuffer(
+                0,
+                0,
+                size.width as i32,
+                size.height as i32,
+                0,
+                0,
+                size.width as i32,
+                size.height as i32,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+        }
+
+        self.frame = Some(frame);
+        self.pacing.content_changed();
+    }
+
+    /// Draw the latest copied frame to the mirror surface and present it.
+    /// This leaves the mirror surface current.
+    fn present(&mut self, gl: &gl::Gl, context: &Context) -> bool {
+        if !self.surface.make_current(context) {
+            return false;
+        }
+
+        if self.surface_size != self.size {
+            if let Some(size) = self.size.non_zero() {
+                self.surface.resize(context, size);
+                self.surface_size = self.size;
+            }
+        }
+
+        unsafe {
+            gl.Disable(gl::SCISSOR_TEST);
+            gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, WINDOW_FRAMEBUFFER_ID);
+            gl.ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl.Clear(gl::COLOR_BUFFER_BIT);
+
+            let frame = self.frame.as_ref().and_then(|frame| {
+                let viewport = MirrorViewport::letterbox(frame.size, self.size)?;
+                Some((frame, viewport))
+            });
+
+            if let Some((frame, viewport)) = frame {
+                // OpenGL coordinates start from the bottom-left corner.
+                let y = self.size.height as i32 - viewport.y - viewport.height;
+
+                gl.BindFramebuffer(gl::READ_FRAMEBUFFER, frame.framebuffer_id);
+                gl.BlitFramebuffer(
+                    0,
+                    0,
+                    frame.size.width as i32,
+                    frame.size.height as i32,
+                    viewport.x,
+                    y,
+                    viewport.x + viewport.width,
+                    y + viewport.height,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::LINEAR,
+                );
+            }
+        }
+
+        // Swapping the buffers commits the surface, so the frame callback has
+        // to be requested beforehand.
+        let wl_surface = self.window.wl_surface();
+        wl_surface.frame(&self.qh, wl_surface.clone());
+
+        let presented = self.surface.present(context);
+        if presented {
+            self.pacing.presented();
+        }
+        presented
+    }
+}
+
+fn create_frame(gl: &gl::Gl, size: PhysicalSize<u32>) -> MirrorFrame {
+    let mut frame = MirrorFrame {
+        framebuffer_id: 0,
+        texture_id: 0,
+        size,
+    };
+
+    unsafe {
+        gl.GenTextures(1, &mut frame.texture_id);
+        gl.GenFramebuffers(1, &mut frame.framebuffer_id);
+
+        gl.BindTexture(gl::TEXTURE_2D, frame.texture_id);
+        gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl.TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA8 as i32,
+            size.width as i32,
+            size.height as i32,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            std::ptr::null(),
+        );
+        gl.BindTexture(gl::TEXTURE_2D, 0);
+
+        gl.BindFramebuffer(gl::FRAMEBUFFER, frame.framebuffer_id);
+        gl.FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            frame.texture_id,
+            0,
+        );
+    }
+
+    frame
+}
+
+fn delete_frame(gl: &gl::Gl, frame: &MirrorFrame) {
+    unsafe {
+        gl.DeleteFramebuffers(1, &frame.framebuffer_id);
+        gl.DeleteTextures(1, &frame.texture_id);
+    }
+}
+
+/// The mirrors of a view, shared between the platform and render threads.
+#[derive(Clone)]
+pub(crate) struct SctkMirrors {
+    context: Arc<Mutex<Context>>,
+    gl: gl::Gl,
+    targets: Arc<Mutex<HashMap<ObjectId, SctkMirrorTarget>>>,
+}
+
+impl SctkMirrors {
+    pub(crate) fn new(context: Arc<Mutex<Context>>, gl: gl::Gl) -> Self {
+        Self {
+            context,
+            gl,
+            targets: Default::default(),
+        }
+    }
+
+    pub(crate) fn create_surface(
+        &self,
+        surface: &WlSurface,
+        size: PhysicalSize<u32>,
+    ) -> Result<SecondarySurface, CreateWaylandContextError> {
+        new_wayland_secondary_surface(&self.context.lock().unwrap(), surface, size)
+    }
+
+    pub(crate) fn insert(&self, id: ObjectId, target: SctkMirrorTarget) {
+        self.targets.lock().unwrap().insert(id, target);
+    }
+
+    pub(crate) fn remove(&self, id: &ObjectId) -> Option<SctkMirrorTarget> {
+        self.targets.lock().unwrap().remove(id)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.targets.lock().unwrap().is_empty()
+    }
+
+    /// Handle the configure event of a mirror window. Returns whether the
+    /// mirror should be presented.
+    pub(crate) fn configure(&self, id: &ObjectId, size: PhysicalSize<u32>) -> bool {
+        let mut targets = self.targets.lock().unwrap();
+        let Some(target) = targets.get_mut(id) else {
+            return false;
+        };
+
+        target.size = size;
+        target.pacing.configured();
+        target.pacing.needs_present()
+    }
+
+    /// Handle a size change of a mirror window (e.g.: when its scale factor
+    /// changes). Returns whether the mirror should be presented.
+    pub(crate) fn resize(&self, id: &ObjectId, size: PhysicalSize<u32>) -> bool {
+        let mut targets = self.targets.lock().unwrap();
+        let Some(target) = targets.get_mut(id) else {
+            return false;
+        };
+
+        target.size = size;
+        target.pacing.content_changed();
+        target.pacing.needs_present()
+    }
+
+    /// Handle the frame callback of a mirror window. Returns whether the
+    /// mirror should be presented.
+    pub(crate) fn frame_done(&self, id: &ObjectId) -> bool {
+        let mut targets = self.targets.lock().unwrap();
+        let Some(target) = targets.get_mut(id) else {
+            return false;
+        };
+
+        target.pacing.frame_done();
+        target.pacing.needs_present()
+    }
+
+    // Note: The methods below are executed on the *render* thread.
+
+    /// Copy the frame presented in the source view. The context must be
+    /// current.
+    pub(crate) fn copy_frame(&self, source_id: u32, size: PhysicalSize<u32>) {
+        for target in self.targets.lock().unwrap().values_mut() {
+            target.copy_frame(&self.gl, source_id, size);
+        }
+    }
+
+    /// Present the mirrors whose surface is ready for a new frame. This never
+    /// waits for the slower mirrors, which simply skip frames.
+    pub(crate) fn present(&self) {
+        let mut targets = self.targets.lock().unwrap();
+        if !targets.values().any(|target| target.pacing.needs_present()) {
+            return;
+        }
+
+        let mut context = self.context.lock().unwrap();
+        for (id, target) in targets.iter_mut() {
+            if target.pacing.needs_present() && !target.present(&self.gl, &context) {
+                warn!("[{}] Failed to present mirror window", id);
+            }
+        }
+
+        // The engine expects the main surface to be current.
+        context.make_current();
+    }
+
+    /// Release the GL resources of a removed mirror, then destroy its
+    /// surfaces.
+    pub(crate) fn release(&self, target: SctkMirrorTarget) {
+        let mut context = self.context.lock().unwrap();
+        if context.make_current() {
+            if let Some(frame) = &target.frame {
+                delete_frame(&self.gl, frame);
+            }
+        } else {
+            warn!("Leaking mirror frame because the context could not be made current");
+        }
+
+        drop(target);
+    }
+}
+
+/// Platform thread side of a mirror window.
+pub(crate) struct SctkMirrorWindow {
+    window: Window,
+    source_view: FlutterViewId,
+    input: SctkMirrorInput,
+    logical_size: LogicalSize<u32>,
+    scale_factor: Scale,
+}
+
+impl SctkMirrorWindow {
+    pub(crate) fn new(
+        window: Window,
+        source_view: FlutterViewId,
+        input: SctkMirrorInput,
+        logical_size: LogicalSize<u32>,
+    ) -> Self {
+        Self {
+            window,
+            source_view,
+            input,
+            logical_size,
+            scale_factor: Default::default(),
+        }
+    }
+
+    pub(crate) fn source_view(&self) -> FlutterViewId {
+        self.source_view
+    }
+
+    pub(crate) fn input(&self) -> SctkMirrorInput {
+        self.input
+    }
+
+    pub(crate) fn physical_size(&self) -> PhysicalSize<u32> {
+        self.scale_factor.to_physical_size(self.logical_size)
+    }
+
+    /// Store the size suggested by the compositor. Dimensions left to the
+    /// client keep their current value.
+    pub(crate) fn configure(&mut self, width: Option<NonZeroU32>, height: Option<NonZeroU32>) {
+        if let Some(width) = width {
+            self.logical_size.width = width.get();
+        }
+
+        if let Some(height) = height {
+            self.logical_size.height = height.get();
+        }
+    }
+
+    pub(crate) fn scale_factor_changed(&mut self, new_scale_factor: i32) {
+        self.scale_factor = Scale::from_buffer_scale(new_scale_factor);
+        self.window
+            .wl_surface()
+            .set_buffer_scale(self.scale_factor.buffer_scale());
+    }
+
+    /// Map |position| (in logical coordinates of the mirror surface) to the
+    /// logical coordinates of a source view of |source_size| physical pixels.
+    pub(crate) fn map_to_source(
+        &self,
+        position: LogicalPosition<f64>,
+        source_size: PhysicalSize<u32>,
+        source_scale_factor: Scale,
+    ) -> Option<LogicalPosition<f64>> {
+        let viewport = MirrorViewport::letterbox(source_size, self.physical_size())?;
+
+        let position = self.scale_factor.to_physical_position(position);
+        let position = viewport.map_to_source(source_size, position);
+
+        Some(source_scale_factor.to_logical_position(position))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SctkMirrorWindowError {
+    #[error("No window found for view {0}")]
+    UnknownView(FlutterViewId),
+
+    #[error("No output found for display {0}")]
+    UnknownOutput(FlutterEngineDisplayId),
+
+    #[error("Mirroring requires rendering through the compositor")]
+    CompositorRequired,
+
+    #[error("Failed to create the mirror surface")]
+    CreateSurfaceError(#[from] CreateWaylandContextError),
+}
+
+#[cfg(test)]
+mod tests {
+    use dpi::{PhysicalPosition, PhysicalSize};
+
+    use super::{MirrorPacing, MirrorViewport};
+
+    #[test]
+    fn letterboxes_to_fit_the_target() {
+        let source = PhysicalSize::new(1920, 1080);
+
+        // Same aspect ratio.
+        let viewport = MirrorViewport::letterbox(source, PhysicalSize::new(1280, 720)).unwrap();
+        assert_eq!(
+            viewport,
+            MirrorViewport {
+                x: 0,
+                y: 0,
+                width: 1280,
+                height: 720
+            }
+        );
+
+        // Taller target: bars at the top and bottom.
+        let viewport = MirrorViewport::letterbox(source, PhysicalSize::new(1024, 768)).unwrap();
+        assert_eq!(
+            viewport,
+            MirrorViewport {
+                x: 0,
+                y: 96,
+                width: 1024,
+                height: 576
+            }
+        );
+
+        // Wider target: bars on the sides.
+        let viewport = MirrorViewport::letterbox(source, PhysicalSize::new(2560, 1080)).unwrap();
+        assert_eq!(
+            viewport,
+            MirrorViewport {
+                x: 320,
+                y: 0,
+                width: 1920,
+                height: 1080
+            }
+        );
+
+        assert_eq!(MirrorViewport::letterbox(source, PhysicalSize::new(0, 720)), None);
+        assert_eq!(MirrorViewport::letterbox(PhysicalSize::new(0, 0), source), None);
+    }
+
+    #[test]
+    fn maps_positions_to_the_source() {
+        let source = PhysicalSize::new(1920, 1080);
+        let viewport = MirrorViewport::letterbox(source, PhysicalSize::new(1024, 768)).unwrap();
+
+        assert_eq!(
+            viewport.map_to_source(source, PhysicalPosition::new(512.0, 384.0)),
+            PhysicalPosition::new(960.0, 540.0)
+        );
+        assert_eq!(
+            viewport.map_to_source(source, PhysicalPosition::new(0.0, 96.0)),
+            PhysicalPosition::new(0.0, 0.0)
+        );
+
+        // Positions inside the bars are clamped to the content.
+        assert_eq!(
+            viewport.map_to_source(source, PhysicalPosition::new(1024.0, 768.0)),
+            PhysicalPosition::new(1920.0, 1080.0)
+        );
+        assert_eq!(
+            viewport.map_to_source(source, PhysicalPosition::new(256.0, 10.0)),
+            PhysicalPosition::new(480.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn presents_the_latest_frame_once_ready() {
+        let mut pacing = MirrorPacing::default();
+
+        // Frames are only copied until the surface is configured.
+        pacing.content_changed();
+        assert!(!pacing.needs_present());
+
+        pacing.configured();
+        assert!(pacing.needs_present());
+        pacing.presented();
+
+        // The source keeps presenting while the mirror waits for its frame
+        // callback.
+        pacing.content_changed();
+        pacing.content_changed();
+        assert!(!pacing.needs_present());
+
+        pacing.frame_done();
+        assert!(pacing.needs_present());
+        pacing.presented();
+
+        // Nothing to present without a new frame.
+        pacing.frame_done();
+        assert!(!pacing.needs_present());
+    }
+}
@@ -0,0 +1,470 @@
+//! Estimate of whether a window is seen by the user, reported to the apps
+//! (see `onVisibilityChanged`) so that they can pause expensive work (e.g.:
+//! video decoding) while their window is hidden behind others.
+//!
+//! Wayland doesn't tell clients whether their windows are occluded, so the
+//! estimate combines what the compositor does tell, from the most to the
+//! least reliable:
+//!
+//! - The window is unmapped, or on no output (e.g.: the output was turned
+//!   off): it is hidden.
+//! - The `suspended` state of xdg toplevels (xdg-shell v6), which the
+//!   compositors supporting it set while the window is e.g. fully occluded
+//!   or on another workspace: it is hidden.
+//! - The frame callbacks, which compositors throttle (or stop) for the
+//!   windows that aren't seen: a callback arriving several refresh intervals
+//!   late while the callbacks of the other windows of the app keep flowing
+//!   suggests that the window is at least partly obscured, and no callback at
+//!   all for a while that it is hidden.
+//!
+//! Every change is only reported once it held for a while, so that the
+//! estimate doesn't flap (e.g.: a throttled window still receives a callback
+//! now and then).
+//!
+//! Limitations:
+//!
+//! - The frame callbacks are only a hint: compositors also throttle them for
+//!   other reasons (e.g.: when they are busy), and some never throttle them,
+//!   in which case only the first two signals are available.
+//! - Partial occlusion can't be told from full occlusion by the callbacks,
+//!   hence [`Visibility::PartiallyObscuredLikely`].
+//! - The callbacks are only observed while the app renders. While the
+//!   estimate relies on them, a frame is scheduled now and then to probe the
+//!   compositor, so a window is reported visible again up to a second after
+//!   being uncovered.
+//! - Without other windows to compare with, a compositor which stops sending
+//!   callbacks altogether (e.g.: it hangs) makes the window look hidden.
+use std::time::Duration;
+
+use flutter_plugins::window::Visibility;
+
+/// A frame callback arriving later than this many refresh intervals after it
+/// was requested is considered throttled.
+const THROTTLED_INTERVALS: u64 = 4;
+
+/// Waiting this long for a frame callback means that the compositor stopped
+/// sending them.
+const STARVED_AFTER: Duration = Duration::from_secs(1);
+
+/// Number of consecutive frame callbacks arriving in time after which the
+/// callbacks are considered flowing again.
+const FLOWING_CALLBACKS: u32 = 2;
+
+/// How long a less visible estimate must hold before being reported.
+const HIDE_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// How long a more visible estimate must hold before being reported.
+const SHOW_SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// How long a window stays hidden before the engine is asked to release its
+/// caches, so that they aren't dropped when the user merely glances at
+/// another window.
+const TRIM_DELAY: Duration = Duration::from_secs(30);
+
+/// Interval at which the estimates of the windows are updated.
+pub(crate) const OCCLUSION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Interval at which frames are scheduled to probe the compositor, while the
+/// estimate relies on the frame callbacks.
+const OCCLUSION_PROBE_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameCallbacks {
+    Flowing,
+    Throttled,
+    Starved,
+}
+
+/// What the compositor tells about the surface of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SctkSurfaceVisibility {
+    pub(crate) mapped: bool,
+    pub(crate) suspended: bool,
+    pub(crate) on_outputs: bool,
+}
+
+impl SctkSurfaceVisibility {
+    fn is_hidden(&self) -> bool {
+        !self.mapped || self.suspended || !self.on_outputs
+    }
+}
+
+impl Default for SctkSurfaceVisibility {
+    fn default() -> Self {
+        Self {
+            mapped: true,
+            suspended: false,
+            on_outputs: true,
+        }
+    }
+}
+
+/// Visibility of a window, as estimated from what the compositor tells (see
+/// the module documentation). Times are engine times, in nanoseconds.
+#[derive(Debug)]
+pub(crate) struct SctkOcclusionEstimator {
+    surface: SctkSurfaceVisibility,
+    frame_callbacks: FrameCallbacks,
+    /// Frame callbacks received in time in a row.
+    callbacks_in_time: u32,
+    reported: Visibility,
+    /// Estimate differing from the reported visibility, and since when.
+    pending: Option<(Visibility, u64)>,
+    /// Since when the window is reported hidden, and whether the caches were
+    /// released since.
+    hidden_since: Option<(u64, bool)>,
+    /// When the last probe frame was scheduled.
+    last_probe: u64,
+}
+
+impl Default for SctkOcclusionEstimator {
+    fn default() -> Self {
+        Self {
+            surface: Default::default(),
+            frame_callbacks: FrameCallbacks::Flowing,
+            callbacks_in_time: 0,
+            reported: Visibility::Visible,
+            pending: None,
+            hidden_since: None,
+            last_probe: 0,
+        }
+    }
+}
+
+impl SctkOcclusionEstimator {
+    /// The reported visibility.
+    pub(crate) fn visibility(&self) -> Visibility {
+        self.reported
+    }
+
+    pub(crate) fn set_surface_visibility(&mut self, surface: SctkSurfaceVisibility) {
+        self.surface = surface;
+    }
+
+    /// A frame callback arrived |waited| nanoseconds after being requested.
+    pub(crate) fn frame_callback_received(&mut self, waited: u64, refresh_interval: u64) {
+        if waited > THROTTLED_INTERVALS * refresh_interval {
+            self.callbacks_in_time = 0;
+            // It arrived nonetheless.
+            self.frame_callbacks = FrameCallbacks::Throttled;
+            return;
+        }
+
+        self.callbacks_in_time += 1;
+        if self.callbacks_in_time >= FLOWING_CALLBACKS {
+            self.frame_callbacks = FrameCallbacks::Flowing;
+        }
+    }
+
+    /// A frame callback has been waited for |waited| nanoseconds. It only
+    /// tells something when |others_flowing| (i.e.: the callbacks of the
+    /// other windows arrived meanwhile, or there is no other window).
+    pub(crate) fn frame_callback_pending(
+        &mut self,
+        waited: u64,
+        refresh_interval: u64,
+        others_flowing: bool,
+    ) {
+        if !others_flowing {
+            return;
+        }
+
+        if waited >= STARVED_AFTER.as_nanos() as u64 {
+            self.callbacks_in_time = 0;
+            self.frame_callbacks = FrameCallbacks::Starved;
+        } else if waited > THROTTLED_INTERVALS * refresh_interval {
+            self.callbacks_in_time = 0;
+            if self.frame_callbacks == FrameCallbacks::Flowing {
+                self.frame_callbacks = FrameCallbacks::Throttled;
+            }
+        }
+    }
+
+    /// Whether the estimate relies on the frame callbacks, so that frames
+    /// should be scheduled to keep observing them.
+    fn needs_probe(&self) -> bool {
+        !self.surface.is_hidden() && self.frame_callbacks != FrameCallbacks::Flowing
+    }
+
+    /// Whether a frame should be scheduled at |now| to probe the compositor,
+    /// while the app doesn't render.
+    pub(crate) fn should_probe(&mut self, now: u64) -> bool {
+        let interval = OCCLUSION_PROBE_INTERVAL.as_nanos() as u64;
+        if !self.needs_probe() || now.saturating_sub(self.last_probe) < interval {
+            return false;
+        }
+
+        self.last_probe = now;
+        true
+    }
+
+    /// Whether the window is reported hidden by the compositor itself, rather
+    /// than because of the frame callbacks. The frames are then paused. Not
+    /// when the window is unmapped, its frames being paced already.
+    pub(crate) fn is_hidden_by_compositor(&self) -> bool {
+        self.reported == Visibility::Hidden && self.surface.mapped && self.surface.is_hidden()
+    }
+
+    fn estimate(&self) -> Visibility {
+        if self.surface.is_hidden() {
+            return Visibility::Hidden;
+        }
+
+        match self.frame_callbacks {
+            FrameCallbacks::Flowing => Visibility::Visible,
+            FrameCallbacks::Throttled => Visibility::PartiallyObscuredLikely,
+            FrameCallbacks::Starved => Visibility::Hidden,
+        }
+    }
+
+    /// Update the estimate at |now|. Returns the visibility to report if it
+    /// has changed and held long enough.
+    pub(crate) fn update(&mut self, now: u64) -> Option<Visibility> {
+        let estimate = self.estimate();
+        if estimate == self.reported {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match self.pending {
+            Some((pending, since)) if pending == estimate => since,
+            _ => {
+                self.pending = Some((estimate, now));
+                now
+            }
+        };
+
+        let settle_time = if rank(estimate) < rank(self.reported) {
+            SHOW_SETTLE_TIME
+        } else {
+            HIDE_SETTLE_TIME
+        };
+        if now.saturating_sub(since) < settle_time.as_nanos() as u64 {
+            return None;
+        }
+
+        self.pending = None;
+        self.reported = estimate;
+        self.hidden_since = (estimate == Visibility::Hidden).then_some((now, false));
+        Some(estimate)
+    }
+
+    /// Whether the engine should be asked to release its caches at |now|,
+    /// once per period during which the window is hidden.
+    pub(crate) fn should_trim(&mut self, now: u64) -> bool {
+        match &mut self.hidden_since {
+            Some((since, trimmed))
+                if !*trimmed && now.saturating_sub(*since) >= TRIM_DELAY.as_nanos() as u64 =>
+            {
+                *trimmed = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// How hidden the |visibility| is.
+fn rank(visibility: Visibility) -> u8 {
+    match visibility {
+        Visibility::Visible => 0,
+        Visibility::PartiallyObscuredLikely => 1,
+        Visibility::Hidden => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_plugins::window::Visibility;
+
+    use super::{SctkOcclusionEstimator, SctkSurfaceVisibility, OCCLUSION_POLL_INTERVAL};
+
+    const MS: u64 = 1_000_000;
+    const REFRESH_INTERVAL: u64 = 16 * MS;
+
+    /// Stands in for the application: drives the estimator with the frame
+    /// callbacks and configures of a window, on the poll timer.
+    #[derive(Default)]
+    struct Harness {
+        estimator: SctkOcclusionEstimator,
+        surface: SctkSurfaceVisibility,
+        now: u64,
+        /// When the pending frame callback was requested.
+        requested_at: Option<u64>,
+        reported: Vec<(u64, Visibility)>,
+    }
+
+    impl Harness {
+        fn request_frame(&mut self) {
+            self.requested_at.get_or_insert(self.now);
+        }
+
+        fn receive_frame(&mut self) {
+            let requested_at = self.requested_at.take().expect("no frame requested");
+            self.estimator
+                .frame_callback_received(self.now - requested_at, REFRESH_INTERVAL);
+            self.poll();
+        }
+
+        fn configure(&mut self, suspended: bool) {
+            self.surface.suspended = suspended;
+            self.poll();
+        }
+
+        fn poll(&mut self) {
+            self.estimator.set_surface_visibility(self.surface);
+            if let Some(requested_at) = self.requested_at {
+                self.estimator.frame_callback_pending(
+                    self.now - requested_at,
+                    REFRESH_INTERVAL,
+                    true,
+                );
+            }
+            if let Some(visibility) = self.estimator.update(self.now) {
+                self.reported.push((self.now, visibility));
+            }
+        }
+
+        /// Advance the time by |duration|, polling on the way.
+        fn wait(&mut self, duration: u64) {
+            let end = self.now + duration;
+            let poll_interval = OCCLUSION_POLL_INTERVAL.as_nanos() as u64;
+            while self.now + poll_interval <= end {
+                self.now += poll_interval;
+                self.poll();
+            }
+            self.now = end;
+        }
+
+        /// Render |count| frames, each callback arriving after |delay|.
+        fn render(&mut self, count: usize, delay: u64) {
+            for _ in 0..count {
+                self.request_frame();
+                self.wait(delay);
+                self.receive_frame();
+            }
+        }
+
+        fn take_reported(&mut self) -> Vec<Visibility> {
+            self.reported
+                .drain(..)
+                .map(|(_, visibility)| visibility)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn flowing_callbacks_keep_the_window_visible() {
+        let mut harness = Harness::default();
+        harness.render(200, REFRESH_INTERVAL);
+        assert!(harness.take_reported().is_empty());
+        assert_eq!(harness.estimator.visibility(), Visibility::Visible);
+    }
+
+    #[test]
+    fn throttled_callbacks_are_reported_without_flapping() {
+        let mut harness = Harness::default();
+        harness.render(10, REFRESH_INTERVAL);
+
+        // The compositor throttles the window to one callback per 800ms,
+        // e.g.: because it is covered by another window.
+        harness.render(10, 800 * MS);
+        assert_eq!(
+            harness.take_reported(),
+            [Visibility::PartiallyObscuredLikely]
+        );
+
+        // Uncovered.
+        harness.render(10, REFRESH_INTERVAL);
+        assert_eq!(harness.take_reported(), [Visibility::Visible]);
+    }
+
+    #[test]
+    fn starved_callbacks_hide_the_window() {
+        let mut harness = Harness::default();
+        harness.render(10, REFRESH_INTERVAL);
+
+        harness.request_frame();
+        harness.wait(900 * MS);
+        assert_eq!(
+            harness.take_reported(),
+            [Visibility::PartiallyObscuredLikely]
+        );
+        harness.wait(1_000 * MS);
+        assert_eq!(harness.take_reported(), [Visibility::Hidden]);
+
+        // A single callback isn't enough to tell that the window is visible
+        // again.
+        harness.receive_frame();
+        harness.wait(REFRESH_INTERVAL);
+        harness.render(1, REFRESH_INTERVAL);
+        harness.wait(500 * MS);
+        assert_eq!(
+            harness.take_reported(),
+            [Visibility::PartiallyObscuredLikely]
+        );
+
+        harness.render(2, REFRESH_INTERVAL);
+        harness.wait(300 * MS);
+        assert_eq!(harness.take_reported(), [Visibility::Visible]);
+    }
+
+    #[test]
+    fn callbacks_only_tell_something_while_the_others_flow() {
+        let mut estimator = SctkOcclusionEstimator::default();
+        estimator.frame_callback_pending(2_000 * MS, REFRESH_INTERVAL, false);
+        assert_eq!(estimator.update(0), None);
+        assert_eq!(estimator.update(2_000 * MS), None);
+        assert!(!estimator.needs_probe());
+    }
+
+    #[test]
+    fn suspended_configures_are_debounced() {
+        let mut harness = Harness::default();
+        harness.render(10, REFRESH_INTERVAL);
+
+        // Suspended for a moment (e.g.: while switching workspaces).
+        harness.configure(true);
+        harness.wait(300 * MS);
+        harness.configure(false);
+        harness.wait(1_000 * MS);
+        assert!(harness.take_reported().is_empty());
+
+        harness.configure(true);
+        harness.wait(600 * MS);
+        assert_eq!(harness.take_reported(), [Visibility::Hidden]);
+        assert!(harness.estimator.is_hidden_by_compositor());
+        assert!(!harness.estimator.needs_probe());
+
+        // Showing the window again is reported sooner.
+        harness.configure(false);
+        harness.wait(300 * MS);
+        assert_eq!(harness.take_reported(), [Visibility::Visible]);
+        assert!(!harness.estimator.is_hidden_by_compositor());
+    }
+
+    #[test]
+    fn leaving_all_outputs_hides_the_window() {
+        let mut harness = Harness::default();
+        harness.surface.on_outputs = false;
+        harness.wait(800 * MS);
+        assert_eq!(harness.take_reported(), [Visibility::Hidden]);
+    }
+
+    #[test]
+    fn caches_are_released_once_per_hidden_period() {
+        let mut harness = Harness::default();
+        harness.configure(true);
+        harness.wait(600 * MS);
+        assert!(!harness.estimator.should_trim(harness.now));
+
+        harness.wait(30_000 * MS);
+        assert!(harness.estimator.should_trim(harness.now));
+        assert!(!harness.estimator.should_trim(harness.now));
+
+        harness.configure(false);
+        harness.wait(300 * MS);
+        harness.wait(60_000 * MS);
+        assert!(!harness.estimator.should_trim(harness.now));
+    }
+}
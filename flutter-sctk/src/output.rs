@@ -1,14 +1,18 @@
+use std::collections::HashMap;
+
 use dpi::PhysicalSize;
 use flutter_engine::ffi::FlutterEngineDisplay;
 use flutter_engine_sys::FlutterEngineDisplayId;
 use smithay_client_toolkit::output::OutputInfo;
 
+use crate::units::Scale;
+
 #[derive(Debug, Clone)]
 pub(crate) struct SctkOutput {
     pub(crate) display_id: FlutterEngineDisplayId,
     pub(crate) refresh_rate: f64,
     pub(crate) size: PhysicalSize<usize>,
-    pub(crate) device_pixel_ratio: f64,
+    pub(crate) device_pixel_ratio: Scale,
 }
 
 impl SctkOutput {
@@ -18,11 +22,11 @@ impl SctkOutput {
                 display_id,
                 refresh_rate: 0.0,
                 size: PhysicalSize::new(0, 0),
-                device_pixel_ratio: 1.0,
+                device_pixel_ratio: Default::default(),
             };
         };
 
-        let device_pixel_ratio = info.scale_factor as f64;
+        let device_pixel_ratio = Scale::from_buffer_scale(info.scale_factor);
 
         let current_mode = info.modes.iter().find(|mode| mode.current);
 
@@ -57,7 +61,175 @@ impl From<SctkOutput> for FlutterEngineDisplay {
             single_display: false,
             refresh_rate: output.refresh_rate,
             size: output.size,
-            device_pixel_ratio: output.device_pixel_ratio,
+            device_pixel_ratio: output.device_pixel_ratio.get(),
+        }
+    }
+}
+
+/// Scales of the outputs a window touches, as hinted to Dart for picking
+/// resolution-aware asset variants (see `flutter_plugins::assets`).
+#[derive(Debug, Default)]
+pub(crate) struct SctkOutputScales {
+    scales: Vec<f64>,
+}
+
+impl SctkOutputScales {
+    /// Store the |buffer_scales| of the outputs the window currently touches.
+    /// Returns the scales (sorted, without duplicates) if they have changed.
+    pub(crate) fn update(
+        &mut self,
+        buffer_scales: impl IntoIterator<Item = i32>,
+    ) -> Option<Vec<f64>> {
+        let mut scales: Vec<f64> = buffer_scales
+            .into_iter()
+            .map(|buffer_scale| Scale::from_buffer_scale(buffer_scale).get())
+            .collect();
+        scales.sort_by(f64::total_cmp);
+        scales.dedup();
+
+        if scales == self.scales {
+            return None;
+        }
+
+        self.scales = scales.clone();
+        Some(scales)
+    }
+}
+
+/// Last known buffer scale of each output, to tell the output updates which
+/// change the scale (e.g.: from the display settings) from the others.
+#[derive(Debug, Default)]
+pub(crate) struct SctkOutputScaleTracker {
+    scales: HashMap<FlutterEngineDisplayId, i32>,
+}
+
+impl SctkOutputScaleTracker {
+    /// Store the |buffer_scale| of the |output|. Returns whether it differs
+    /// from the previous one (the first scale of an output isn't a change).
+    pub(crate) fn update(&mut self, output: FlutterEngineDisplayId, buffer_scale: i32) -> bool {
+        self.scales
+            .insert(output, buffer_scale)
+            .is_some_and(|previous| previous != buffer_scale)
+    }
+
+    pub(crate) fn remove(&mut self, output: FlutterEngineDisplayId) {
+        self.scales.remove(&output);
+    }
+}
+
+/// Handle a change of the outputs of a window, or of their scale: the new
+/// |scales| (if any) are passed to |notify|, before |send_metrics| is called.
+/// A frame is then scheduled if the |effective_scale_changed|, so that the
+/// content is rebuilt with the asset variants of the new scale right away.
+pub(crate) fn outputs_changed<N, M, F>(
+    scales: Option<Vec<f64>>,
+    effective_scale_changed: bool,
+    notify: N,
+    send_metrics: M,
+    schedule_frame: F,
+) where
+    N: FnOnce(Vec<f64>),
+    M: FnOnce(),
+    F: FnOnce(),
+{
+    if let Some(scales) = scales {
+        notify(scales);
+    }
+    send_metrics();
+    if effective_scale_changed {
+        schedule_frame();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::{outputs_changed, SctkOutputScaleTracker, SctkOutputScales};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        ScalesChanged(Vec<f64>),
+        Metrics,
+        Frame,
+    }
+
+    /// Stands in for the window: records what is sent to the engine.
+    #[derive(Default)]
+    struct Harness {
+        scales: SctkOutputScales,
+        scale: i32,
+        events: RefCell<Vec<Event>>,
+    }
+
+    impl Harness {
+        /// The window now touches outputs of |buffer_scales|, and the
+        /// compositor asks for |scale|.
+        fn outputs(&mut self, buffer_scales: &[i32], scale: i32) {
+            let scales = self.scales.update(buffer_scales.iter().copied());
+            let effective_scale_changed = std::mem::replace(&mut self.scale, scale) != scale;
+            outputs_changed(
+                scales,
+                effective_scale_changed,
+                |scales| self.events.borrow_mut().push(Event::ScalesChanged(scales)),
+                || self.events.borrow_mut().push(Event::Metrics),
+                || self.events.borrow_mut().push(Event::Frame),
+            );
+        }
+
+        fn take_events(&self) -> Vec<Event> {
+            self.events.take()
         }
     }
+
+    #[test]
+    fn scales_are_sorted_and_deduplicated() {
+        let mut scales = SctkOutputScales::default();
+        assert_eq!(scales.update([2, 1, 2]), Some(vec![1.0, 2.0]));
+        assert_eq!(scales.update([1, 2]), None);
+        assert_eq!(scales.update([]), Some(vec![]));
+    }
+
+    #[test]
+    fn crossing_a_scale_boundary() {
+        let mut harness = Harness {
+            scale: 1,
+            ..Default::default()
+        };
+        harness.outputs(&[1], 1);
+        harness.take_events();
+
+        // Dragged across the edge of a HiDPI output: the scales are hinted,
+        // but the content keeps the scale of the output it mostly is on.
+        harness.outputs(&[1, 2], 1);
+        assert_eq!(
+            harness.take_events(),
+            [Event::ScalesChanged(vec![1.0, 2.0]), Event::Metrics]
+        );
+
+        // The compositor switches the window to the HiDPI scale.
+        harness.outputs(&[1, 2], 2);
+        assert_eq!(harness.take_events(), [Event::Metrics, Event::Frame]);
+
+        // Fully moved to the HiDPI output.
+        harness.outputs(&[2], 2);
+        assert_eq!(
+            harness.take_events(),
+            [Event::ScalesChanged(vec![2.0]), Event::Metrics]
+        );
+    }
+
+    #[test]
+    fn only_scale_changes_are_reported() {
+        let mut tracker = SctkOutputScaleTracker::default();
+        assert!(!tracker.update(1, 1));
+        // E.g.: the mode of the output changed.
+        assert!(!tracker.update(1, 1));
+        assert!(tracker.update(1, 2));
+        assert!(!tracker.update(2, 2));
+
+        // A new output with the same id isn't a change either.
+        tracker.remove(1);
+        assert!(!tracker.update(1, 1));
+    }
 }
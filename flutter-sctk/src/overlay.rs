@@ -0,0 +1,877 @@
+//! Video overlays show the dmabuf frames posted to a texture (see
+//! `Texture::post_dmabuf_frame`) in the platform view whose identifier is the
+//! id of that texture.
+//!
+//! When the platform view is only translated and scaled, fully opaque, not
+//! clipped and not covered by any Flutter content, its frames are attached to
+//! a subsurface placed below the window surface, in which a hole is punched.
+//! The compositor may then scan the frames out directly (e.g.: on a hardware
+//! plane), which avoids any copy of the video by the embedder.
+//!
+//! Otherwise, the frames are imported as EGL images and composited with the
+//! layers of the view. This path only supports single plane RGB formats, and
+//! approximates rotations and rounded clips by their bounding rectangle.
+//!
+//! Note: Buffers are synchronized implicitly (i.e.: through the fences of the
+//! dmabufs), explicit synchronization is not implemented.
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{c_void, CStr},
+    mem,
+    os::fd::{AsFd, AsRawFd},
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use dpi::{PhysicalPosition, PhysicalSize};
+use flutter_engine::{
+    ffi::FlutterPlatformViewMutation, texture_registry::DmabufTextureFrame, FlutterEngineWeakRef,
+};
+use flutter_glutin::{context::Context, gl};
+use smithay_client_toolkit::{
+    compositor::CompositorState,
+    reexports::protocols::wp::{
+        linux_dmabuf::zv1::client::{
+            zwp_linux_buffer_params_v1::Flags, zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+        },
+        viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
+    },
+};
+use tracing::{trace, warn};
+use wayland_client::{
+    globals::GlobalList,
+    protocol::{
+        wl_buffer::WlBuffer, wl_compositor::WlCompositor, wl_subcompositor::WlSubcompositor,
+        wl_subsurface::WlSubsurface, wl_surface::WlSurface,
+    },
+    Proxy, QueueHandle,
+};
+
+use crate::{
+    application::SctkApplicationState,
+    quad::{QuadOrigin, SctkQuadProgram},
+    units::Scale,
+};
+
+/// Slack (in physical pixels) when comparing the bounds of a platform view
+/// with its clips, which may differ due to rounding.
+const CLIP_TOLERANCE: f64 = 0.5;
+
+/// A rectangle in physical pixels of a view (origin at the top-left corner).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct OverlayRect {
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) width: f64,
+    pub(crate) height: f64,
+}
+
+impl OverlayRect {
+    pub(crate) fn new(position: PhysicalPosition<f64>, size: PhysicalSize<f64>) -> Self {
+        Self {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        }
+    }
+
+    fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    fn intersects(&self, other: &OverlayRect) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    fn contains(&self, other: &OverlayRect) -> bool {
+        self.x <= other.x + CLIP_TOLERANCE
+            && self.y <= other.y + CLIP_TOLERANCE
+            && self.right() >= other.right() - CLIP_TOLERANCE
+            && self.bottom() >= other.bottom() - CLIP_TOLERANCE
+    }
+
+    fn intersection(&self, other: &OverlayRect) -> OverlayRect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        OverlayRect {
+            x,
+            y,
+            width: (self.right().min(other.right()) - x).max(0.0),
+            height: (self.bottom().min(other.bottom()) - y).max(0.0),
+        }
+    }
+
+    fn inset(&self, dx: f64, dy: f64) -> OverlayRect {
+        OverlayRect {
+            x: self.x + dx,
+            y: self.y + dy,
+            width: self.width - 2.0 * dx,
+            height: self.height - 2.0 * dy,
+        }
+    }
+}
+
+impl From<flutter_engine_sys::FlutterRect> for OverlayRect {
+    fn from(rect: flutter_engine_sys::FlutterRect) -> Self {
+        Self {
+            x: rect.left,
+            y: rect.top,
+            width: rect.right - rect.left,
+            height: rect.bottom - rect.top,
+        }
+    }
+}
+
+/// 2D affine part of a `FlutterTransformation`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Affine {
+    scale_x: f64,
+    skew_x: f64,
+    trans_x: f64,
+    skew_y: f64,
+    scale_y: f64,
+    trans_y: f64,
+}
+
+impl Affine {
+    const IDENTITY: Affine = Affine {
+        scale_x: 1.0,
+        skew_x: 0.0,
+        trans_x: 0.0,
+        skew_y: 0.0,
+        scale_y: 1.0,
+        trans_y: 0.0,
+    };
+
+    /// Apply |other| first, then this transformation.
+    fn then(&self, other: &Affine) -> Affine {
+        Affine {
+            scale_x: self.scale_x * other.scale_x + self.skew_x * other.skew_y,
+            skew_x: self.scale_x * other.skew_x + self.skew_x * other.scale_y,
+            trans_x: self.scale_x * other.trans_x + self.skew_x * other.trans_y + self.trans_x,
+            skew_y: self.skew_y * other.scale_x + self.scale_y * other.skew_y,
+            scale_y: self.skew_y * other.skew_x + self.scale_y * other.scale_y,
+            trans_y: self.skew_y * other.trans_x + self.scale_y * other.trans_y + self.trans_y,
+        }
+    }
+
+    fn map_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.scale_x * x + self.skew_x * y + self.trans_x,
+            self.skew_y * x + self.scale_y * y + self.trans_y,
+        )
+    }
+
+    /// Bounding box of the transformed |rect|.
+    fn map_rect(&self, rect: &OverlayRect) -> OverlayRect {
+        let corners = [
+            self.map_point(rect.x, rect.y),
+            self.map_point(rect.right(), rect.y),
+            self.map_point(rect.x, rect.bottom()),
+            self.map_point(rect.right(), rect.bottom()),
+        ];
+
+        let (mut left, mut top) = corners[0];
+        let (mut right, mut bottom) = corners[0];
+        for (x, y) in corners {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+
+        OverlayRect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+}
+
+impl From<flutter_engine_sys::FlutterTransformation> for Affine {
+    fn from(transformation: flutter_engine_sys::FlutterTransformation) -> Self {
+        Self {
+            scale_x: transformation.scaleX,
+            skew_x: transformation.skewX,
+            trans_x: transformation.transX,
+            skew_y: transformation.skewY,
+            scale_y: transformation.scaleY,
+            trans_y: transformation.transY,
+        }
+    }
+}
+
+/// Whether |transformation| only translates and scales (without flipping).
+fn is_axis_aligned(transformation: &flutter_engine_sys::FlutterTransformation) -> bool {
+    transformation.skewX == 0.0
+        && transformation.skewY == 0.0
+        && transformation.scaleX > 0.0
+        && transformation.scaleY > 0.0
+        && transformation.pers0 == 0.0
+        && transformation.pers1 == 0.0
+        && transformation.pers2 == 1.0
+}
+
+/// How a platform view should be shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PlatformViewPlan {
+    /// Whether the platform view can be shown on a subsurface.
+    pub(crate) promote: bool,
+    pub(crate) opacity: f64,
+    /// Bounding box of the clips of the platform view, if any.
+    pub(crate) clip: Option<OverlayRect>,
+}
+
+/// Decide how to show a platform view laid out in |rect| (i.e.: the bounds
+/// of the platform view layer). |occluders| are the areas painted by the
+/// layers above the platform view.
+pub(crate) fn plan_platform_view(
+    rect: OverlayRect,
+    mutations: &[FlutterPlatformViewMutation],
+    occluders: &[OverlayRect],
+) -> PlatformViewPlan {
+    let mut transform = Affine::IDENTITY;
+    let mut is_transform_axis_aligned = true;
+    let mut opacity = 1.0;
+    let mut clip: Option<OverlayRect> = None;
+    let mut is_clipped = false;
+
+    // Each mutation is expressed in the coordinate space resulting from the
+    // mutations before it.
+    for mutation in mutations {
+        match mutation {
+            FlutterPlatformViewMutation::Opacity(value) => opacity *= value,
+            FlutterPlatformViewMutation::ClipRect(clip_rect) => {
+                let clip_rect = transform.map_rect(&(*clip_rect).into());
+                is_clipped |= !clip_rect.contains(&rect);
+                clip = Some(clip.map_or(clip_rect, |clip| clip.intersection(&clip_rect)));
+            }
+            FlutterPlatformViewMutation::ClipRoundedRect(clip_rounded_rect) => {
+                let clip_rect = transform.map_rect(&clip_rounded_rect.rect.into());
+
+                // The rounded corners don't cut into the platform view if it
+                // fits in the clip without its corners.
+                let radius = [
+                    clip_rounded_rect.upper_left_corner_radius,
+                    clip_rounded_rect.upper_right_corner_radius,
+                    clip_rounded_rect.lower_right_corner_radius,
+                    clip_rounded_rect.lower_left_corner_radius,
+                ]
+                .iter()
+                .fold((0.0f64, 0.0f64), |(width, height), radius| {
+                    (width.max(radius.width), height.max(radius.height))
+                });
+                let inner_rect = clip_rect.inset(
+                    radius.0 * transform.scale_x.abs(),
+                    radius.1 * transform.scale_y.abs(),
+                );
+                is_clipped |= !inner_rect.contains(&rect);
+                clip = Some(clip.map_or(clip_rect, |clip| clip.intersection(&clip_rect)));
+            }
+            FlutterPlatformViewMutation::Transformation(transformation) => {
+                is_transform_axis_aligned &= is_axis_aligned(transformation);
+                transform = transform.then(&(*transformation).into());
+            }
+        }
+    }
+
+    let is_occluded = occluders.iter().any(|occluder| occluder.intersects(&rect));
+
+    PlatformViewPlan {
+        promote: is_transform_axis_aligned && opacity >= 1.0 && !is_clipped && !is_occluded,
+        opacity,
+        clip,
+    }
+}
+
+/// Globals required for showing platform views on subsurfaces.
+#[derive(Debug, Clone)]
+pub struct SctkOverlayGlobals {
+    compositor: WlCompositor,
+    subcompositor: WlSubcompositor,
+    viewporter: WpViewporter,
+    dmabuf: ZwpLinuxDmabufV1,
+}
+
+impl SctkOverlayGlobals {
+    /// Returns `None` if any of the globals is not supported by the
+    /// compositor.
+    pub(crate) fn bind(
+        globals: &GlobalList,
+        qh: &QueueHandle<SctkApplicationState>,
+        compositor_state: &CompositorState,
+    ) -> Option<Self> {
+        Some(Self {
+            compositor: compositor_state.wl_compositor().clone(),
+            subcompositor: globals.bind(qh, 1..=1, ()).ok()?,
+            viewporter: globals.bind(qh, 1..=1, ()).ok()?,
+            // `create_immed` requires version 2.
+            dmabuf: globals.bind(qh, 2..=3, ()).ok()?,
+        })
+    }
+}
+
+/// User data of the buffers attached to the overlay subsurfaces.
+pub(crate) struct SctkOverlayBufferData {
+    frame: Mutex<Option<Arc<DmabufTextureFrame>>>,
+}
+
+impl SctkOverlayBufferData {
+    /// Handle the release of |buffer| by the compositor.
+    pub(crate) fn release(&self, buffer: &WlBuffer) {
+        trace!("[{}] overlay buffer released", buffer.id());
+        self.frame.lock().unwrap().take();
+        buffer.destroy();
+    }
+}
+
+/// A subsurface showing the frames of a platform view.
+struct OverlaySubsurface {
+    surface: WlSurface,
+    subsurface: WlSubsurface,
+    viewport: WpViewport,
+    /// The frame of the attached buffer, if any.
+    frame: Option<Arc<DmabufTextureFrame>>,
+    position: Option<(i32, i32)>,
+    destination: Option<(i32, i32)>,
+}
+
+impl OverlaySubsurface {
+    fn new(
+        globals: &SctkOverlayGlobals,
+        parent: &WlSurface,
+        qh: &QueueHandle<SctkApplicationState>,
+    ) -> Self {
+        let surface = globals.compositor.create_surface(qh, ());
+        let subsurface = globals.subcompositor.get_subsurface(&surface, parent, qh, ());
+        let viewport = globals.viewporter.get_viewport(&surface, qh, ());
+
+        subsurface.place_below(parent);
+
+        Self {
+            surface,
+            subsurface,
+            viewport,
+            frame: None,
+            position: None,
+            destination: None,
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        self.frame.is_some()
+    }
+
+    /// Show |frame| in |rect| of the parent surface. The changes are applied
+    /// with the next commit of the parent surface.
+    fn show(
+        &mut self,
+        globals: &SctkOverlayGlobals,
+        qh: &QueueHandle<SctkApplicationState>,
+        frame: Arc<DmabufTextureFrame>,
+        rect: OverlayRect,
+        scale: Scale,
+    ) {
+        let position = scale.to_logical_position(PhysicalPosition::new(rect.x, rect.y));
+        let position = (position.x.round() as i32, position.y.round() as i32);
+        if self.position != Some(position) {
+            self.subsurface.set_position(position.0, position.1);
+            self.position = Some(position);
+        }
+
+        let size = scale.to_logical_size::<f64>(PhysicalSize::new(rect.width, rect.height));
+        let destination = (
+            (size.width.round() as i32).max(1),
+            (size.height.round() as i32).max(1),
+        );
+        let mut needs_commit = false;
+        if self.destination != Some(destination) {
+            self.viewport.set_destination(destination.0, destination.1);
+            self.destination = Some(destination);
+            needs_commit = true;
+        }
+
+        let is_new_frame = !self
+            .frame
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &frame));
+        if is_new_frame {
+            let buffer = create_buffer(globals, qh, frame.clone());
+            self.surface.attach(Some(&buffer), 0, 0);
+            self.surface.damage_buffer(0, 0, i32::MAX, i32::MAX);
+            self.frame = Some(frame);
+            needs_commit = true;
+        }
+
+        // The subsurface is synchronized, so this commit only takes effect
+        // along with the parent surface.
+        if needs_commit {
+            self.surface.commit();
+        }
+    }
+
+    fn hide(&mut self) {
+        if self.frame.take().is_some() {
+            self.surface.attach(None, 0, 0);
+            self.surface.commit();
+        }
+    }
+}
+
+impl Drop for OverlaySubsurface {
+    fn drop(&mut self) {
+        self.viewport.destroy();
+        self.subsurface.destroy();
+        self.surface.destroy();
+    }
+}
+
+/// Wrap |frame| in a `wl_buffer`, which keeps the frame alive until the
+/// compositor releases it.
+fn create_buffer(
+    globals: &SctkOverlayGlobals,
+    qh: &QueueHandle<SctkApplicationState>,
+    frame: Arc<DmabufTextureFrame>,
+) -> WlBuffer {
+    let params = globals.dmabuf.create_params(qh, ());
+    for (index, plane) in frame.planes.iter().enumerate() {
+        params.add(
+            plane.fd.as_fd(),
+            index as u32,
+            plane.offset,
+            plane.stride,
+            (frame.modifier >> 32) as u32,
+            frame.modifier as u32,
+        );
+    }
+
+    let buffer = params.create_immed(
+        frame.width as i32,
+        frame.height as i32,
+        frame.fourcc,
+        Flags::empty(),
+        qh,
+        SctkOverlayBufferData {
+            frame: Mutex::new(Some(frame)),
+        },
+    );
+    params.destroy();
+    buffer
+}
+
+const EGL_NONE: i32 = 0x3038;
+const EGL_WIDTH: i32 = 0x3057;
+const EGL_HEIGHT: i32 = 0x3056;
+const EGL_LINUX_DMA_BUF_EXT: u32 = 0x3270;
+const EGL_LINUX_DRM_FOURCC_EXT: i32 = 0x3271;
+const EGL_DMA_BUF_PLANE0_FD_EXT: i32 = 0x3272;
+const EGL_DMA_BUF_PLANE0_OFFSET_EXT: i32 = 0x3273;
+const EGL_DMA_BUF_PLANE0_PITCH_EXT: i32 = 0x3274;
+const EGL_DMA_BUF_PLANE0_MODIFIER_LO_EXT: i32 = 0x3443;
+const EGL_DMA_BUF_PLANE0_MODIFIER_HI_EXT: i32 = 0x3444;
+const DRM_FORMAT_MOD_INVALID: u64 = 0x00ff_ffff_ffff_ffff;
+
+type EglCreateImageKhr = unsafe extern "C" fn(
+    display: *const c_void,
+    context: *const c_void,
+    target: u32,
+    buffer: *const c_void,
+    attributes: *const i32,
+) -> *const c_void;
+type EglDestroyImageKhr = unsafe extern "C" fn(display: *const c_void, image: *const c_void) -> u32;
+type GlEglImageTargetTexture2dOes = unsafe extern "C" fn(target: u32, image: *const c_void);
+
+/// Imports dmabufs as GL textures through `EGL_EXT_image_dma_buf_import`.
+struct EglImageImporter {
+    display: *const c_void,
+    create_image: EglCreateImageKhr,
+    destroy_image: EglDestroyImageKhr,
+    image_target_texture: GlEglImageTargetTexture2dOes,
+}
+
+impl EglImageImporter {
+    fn new(context: &Context) -> Option<Self> {
+        let display = context.egl_display()?;
+        let load = |name: &[u8]| {
+            let name = CStr::from_bytes_with_nul(name).unwrap();
+            let proc = context.get_proc_address(name);
+            (!proc.is_null()).then_some(proc)
+        };
+
+        let create_image = load(b"eglCreateImageKHR\0")?;
+        let destroy_image = load(b"eglDestroyImageKHR\0")?;
+        let image_target_texture = load(b"glEGLImageTargetTexture2DOES\0")?;
+
+        unsafe {
+            Some(Self {
+                display,
+                create_image: mem::transmute::<*const c_void, EglCreateImageKhr>(create_image),
+                destroy_image: mem::transmute::<*const c_void, EglDestroyImageKhr>(destroy_image),
+                image_target_texture: mem::transmute::<*const c_void, GlEglImageTargetTexture2dOes>(
+                    image_target_texture,
+                ),
+            })
+        }
+    }
+
+    /// Import |frame| as a 2D texture. The context must be current.
+    fn import(&self, gl: &gl::Gl, frame: Arc<DmabufTextureFrame>) -> Option<ImportedFrame> {
+        // Multi-planar formats (e.g.: YUV) can only be sampled as external
+        // textures, which OpenGL doesn't support.
+        let [plane] = frame.planes.as_slice() else {
+            return None;
+        };
+
+        let mut attributes = vec![
+            EGL_WIDTH,
+            frame.width as i32,
+            EGL_HEIGHT,
+            frame.height as i32,
+            EGL_LINUX_DRM_FOURCC_EXT,
+            frame.fourcc as i32,
+            EGL_DMA_BUF_PLANE0_FD_EXT,
+            plane.fd.as_raw_fd(),
+            EGL_DMA_BUF_PLANE0_OFFSET_EXT,
+            plane.offset as i32,
+            EGL_DMA_BUF_PLANE0_PITCH_EXT,
+            plane.stride as i32,
+        ];
+        if frame.modifier != DRM_FORMAT_MOD_INVALID {
+            attributes.extend([
+                EGL_DMA_BUF_PLANE0_MODIFIER_LO_EXT,
+                frame.modifier as u32 as i32,
+                EGL_DMA_BUF_PLANE0_MODIFIER_HI_EXT,
+                (frame.modifier >> 32) as u32 as i32,
+            ]);
+        }
+        attributes.push(EGL_NONE);
+
+        unsafe {
+            let image = (self.create_image)(
+                self.display,
+                ptr::null(),
+                EGL_LINUX_DMA_BUF_EXT,
+                ptr::null(),
+                attributes.as_ptr(),
+            );
+            if image.is_null() {
+                return None;
+            }
+
+            let mut texture_id = 0;
+            gl.GenTextures(1, &mut texture_id);
+            gl.BindTexture(gl::TEXTURE_2D, texture_id);
+            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            (self.image_target_texture)(gl::TEXTURE_2D, image);
+            gl.BindTexture(gl::TEXTURE_2D, 0);
+
+            Some(ImportedFrame {
+                frame,
+                image,
+                texture_id,
+            })
+        }
+    }
+
+    /// The context must be current.
+    fn release(&self, gl: &gl::Gl, imported: ImportedFrame) {
+        unsafe {
+            gl.DeleteTextures(1, &imported.texture_id);
+            (self.destroy_image)(self.display, imported.image);
+        }
+    }
+}
+
+struct ImportedFrame {
+    frame: Arc<DmabufTextureFrame>,
+    image: *const c_void,
+    texture_id: u32,
+}
+
+#[derive(Default)]
+struct OverlayState {
+    subsurfaces: HashMap<i64, OverlaySubsurface>,
+    imported_frames: HashMap<i64, ImportedFrame>,
+    /// Loaded on first use, `None` if dmabufs can't be imported.
+    importer: Option<Option<EglImageImporter>>,
+}
+
+// The EGL image pointers are only used on the render thread.
+unsafe impl Send for OverlayState {}
+
+/// The video overlays of a view, shared between the platform and render
+/// threads.
+#[derive(Clone)]
+pub(crate) struct SctkVideoOverlays {
+    globals: Option<SctkOverlayGlobals>,
+    qh: QueueHandle<SctkApplicationState>,
+    engine: FlutterEngineWeakRef,
+    gl: gl::Gl,
+    state: Arc<Mutex<OverlayState>>,
+}
+
+impl SctkVideoOverlays {
+    pub(crate) fn new(
+        globals: Option<SctkOverlayGlobals>,
+        qh: QueueHandle<SctkApplicationState>,
+        engine: FlutterEngineWeakRef,
+        gl: gl::Gl,
+    ) -> Self {
+        Self {
+            globals,
+            qh,
+            engine,
+            gl,
+            state: Default::default(),
+        }
+    }
+
+    // Note: The methods below are executed on the *render* thread.
+
+    /// The latest frame posted for the |identifier| platform view.
+    pub(crate) fn frame(&self, identifier: i64) -> Option<Arc<DmabufTextureFrame>> {
+        self.engine.upgrade()?.dmabuf_texture_frame(identifier)
+    }
+
+    /// Whether platform views can be shown on subsurfaces.
+    pub(crate) fn can_promote(&self) -> bool {
+        self.globals.is_some()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.subsurfaces.is_empty() && state.imported_frames.is_empty()
+    }
+
+    /// Show |frame| on the subsurface of the |identifier| platform view, below
+    /// |parent|. The caller is responsible for punching a hole in |rect| of
+    /// the parent surface.
+    pub(crate) fn promote(
+        &self,
+        identifier: i64,
+        parent: &WlSurface,
+        frame: Arc<DmabufTextureFrame>,
+        rect: OverlayRect,
+        scale: Scale,
+    ) {
+        let Some(globals) = &self.globals else {
+            return;
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state
+            .subsurfaces
+            .entry(identifier)
+            .or_insert_with(|| OverlaySubsurface::new(globals, parent, &self.qh))
+            .show(globals, &self.qh, frame, rect, scale);
+    }
+
+    /// Draw |frame| into |rect| of the bound draw framebuffer. The context
+    /// must be current. Returns `false` if the frame could not be imported.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw(
+        &self,
+        context: &Context,
+        quad: &SctkQuadProgram,
+        identifier: i64,
+        frame: Arc<DmabufTextureFrame>,
+        rect: OverlayRect,
+        plan: &PlatformViewPlan,
+        target_size: PhysicalSize<u32>,
+    ) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        let Some(importer) = state
+            .importer
+            .get_or_insert_with(|| EglImageImporter::new(context))
+        else {
+            return false;
+        };
+
+        let is_imported = state
+            .imported_frames
+            .get(&identifier)
+            .is_some_and(|imported| Arc::ptr_eq(&imported.frame, &frame));
+        if !is_imported {
+            if let Some(imported) = state.imported_frames.remove(&identifier) {
+                importer.release(&self.gl, imported);
+            }
+
+            let Some(imported) = importer.import(&self.gl, frame) else {
+                return false;
+            };
+            state.imported_frames.insert(identifier, imported);
+        }
+
+        let texture_id = state.imported_frames[&identifier].texture_id;
+        unsafe {
+            if let Some(clip) = plan.clip {
+                // OpenGL coordinates start from the bottom-left corner.
+                self.gl.Enable(gl::SCISSOR_TEST);
+                self.gl.Scissor(
+                    clip.x.floor() as i32,
+                    (target_size.height as f64 - clip.bottom()).floor() as i32,
+                    clip.width.ceil() as i32,
+                    clip.height.ceil() as i32,
+                );
+            }
+
+            quad.draw(
+                &self.gl,
+                texture_id,
+                QuadOrigin::TopLeft,
+                rect,
+                target_size,
+                plan.opacity,
+            );
+
+            self.gl.Disable(gl::SCISSOR_TEST);
+        }
+
+        true
+    }
+
+    /// Hide the subsurfaces of the platform views which were not promoted
+    /// in the last frame, and release the resources of the platform views
+    /// which are not shown anymore. The context must be current.
+    pub(crate) fn finish(&self, promoted: &HashSet<i64>, drawn: &HashSet<i64>) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        // Hidden subsurfaces are destroyed once the hide request has been
+        // applied (i.e.: with the following frame).
+        state.subsurfaces.retain(|identifier, subsurface| {
+            if promoted.contains(identifier) {
+                return true;
+            }
+
+            let was_visible = subsurface.is_visible();
+            subsurface.hide();
+            was_visible
+        });
+
+        let stale: Vec<i64> = state
+            .imported_frames
+            .keys()
+            .filter(|identifier| !drawn.contains(identifier))
+            .copied()
+            .collect();
+        for identifier in stale {
+            let imported = state.imported_frames.remove(&identifier).unwrap();
+            match &state.importer {
+                Some(Some(importer)) => importer.release(&self.gl, imported),
+                _ => warn!("Leaking imported frame of platform view {}", identifier),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dpi::{PhysicalPosition, PhysicalSize};
+    use flutter_engine::ffi::FlutterPlatformViewMutation;
+    use flutter_engine_sys::{FlutterRect, FlutterTransformation};
+
+    use super::{plan_platform_view, OverlayRect};
+
+    fn transformation(scale: f64, skew: f64, trans_x: f64, trans_y: f64) -> FlutterTransformation {
+        FlutterTransformation {
+            scaleX: scale,
+            skewX: skew,
+            transX: trans_x,
+            skewY: -skew,
+            scaleY: scale,
+            transY: trans_y,
+            pers0: 0.0,
+            pers1: 0.0,
+            pers2: 1.0,
+        }
+    }
+
+    fn clip_rect(left: f64, top: f64, right: f64, bottom: f64) -> FlutterPlatformViewMutation {
+        FlutterPlatformViewMutation::ClipRect(FlutterRect {
+            left,
+            top,
+            right,
+            bottom,
+        })
+    }
+
+    // A 640x360 logical view at (10, 20), shown with a device pixel ratio of
+    // 2.
+    fn rect() -> OverlayRect {
+        OverlayRect::new(PhysicalPosition::new(20.0, 40.0), PhysicalSize::new(1280.0, 720.0))
+    }
+
+    fn root_transformation() -> FlutterPlatformViewMutation {
+        FlutterPlatformViewMutation::Transformation(transformation(2.0, 0.0, 0.0, 0.0))
+    }
+
+    fn offset() -> FlutterPlatformViewMutation {
+        FlutterPlatformViewMutation::Transformation(transformation(1.0, 0.0, 10.0, 20.0))
+    }
+
+    #[test]
+    fn promotes_unobstructed_views() {
+        let plan = plan_platform_view(rect(), &[root_transformation(), offset()], &[]);
+        assert!(plan.promote);
+        assert_eq!(plan.opacity, 1.0);
+
+        // Clips which don't cut into the view (e.g.: a scrollable viewport
+        // larger than the view) are fine.
+        let mutations = [root_transformation(), clip_rect(0.0, 0.0, 800.0, 600.0), offset()];
+        let plan = plan_platform_view(rect(), &mutations, &[]);
+        assert!(plan.promote);
+        assert_eq!(
+            plan.clip,
+            Some(OverlayRect::new(
+                PhysicalPosition::new(0.0, 0.0),
+                PhysicalSize::new(1600.0, 1200.0)
+            ))
+        );
+
+        // Flutter content next to the view.
+        let occluder =
+            OverlayRect::new(PhysicalPosition::new(0.0, 760.0), PhysicalSize::new(1600.0, 100.0));
+        let plan = plan_platform_view(rect(), &[root_transformation(), offset()], &[occluder]);
+        assert!(plan.promote);
+    }
+
+    #[test]
+    fn composites_transformed_or_obstructed_views() {
+        let rotation =
+            FlutterPlatformViewMutation::Transformation(transformation(0.9, 0.1, 0.0, 0.0));
+        let plan = plan_platform_view(rect(), &[root_transformation(), rotation, offset()], &[]);
+        assert!(!plan.promote);
+
+        let opacity = FlutterPlatformViewMutation::Opacity(0.5);
+        let plan = plan_platform_view(rect(), &[root_transformation(), opacity, offset()], &[]);
+        assert!(!plan.promote);
+        assert_eq!(plan.opacity, 0.5);
+
+        // The clip cuts the bottom of the view.
+        let mutations = [root_transformation(), clip_rect(0.0, 0.0, 800.0, 300.0), offset()];
+        let plan = plan_platform_view(rect(), &mutations, &[]);
+        assert!(!plan.promote);
+
+        // Flutter content (e.g.: playback controls) above the view.
+        let occluder =
+            OverlayRect::new(PhysicalPosition::new(40.0, 600.0), PhysicalSize::new(400.0, 100.0));
+        let plan = plan_platform_view(rect(), &[root_transformation(), offset()], &[occluder]);
+        assert!(!plan.promote);
+    }
+}
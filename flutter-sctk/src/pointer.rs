@@ -1,33 +1,268 @@
-use std::time::SystemTimeError;
+use std::{collections::HashMap, sync::Mutex, time::SystemTimeError};
 
 use dpi::LogicalPosition;
 use flutter_engine::ffi::{
-    FlutterPointerDeviceKind, FlutterPointerMouseButtons, FlutterPointerPhase,
-    FlutterPointerSignalKind,
+    FlutterPointerButtons, FlutterPointerDeviceKind, FlutterPointerPhase, FlutterPointerSignalKind,
 };
 use flutter_engine::ffi::{FlutterPointerEvent, FlutterViewId};
-use smithay_client_toolkit::seat::pointer::{
-    PointerEvent, PointerEventKind, BTN_BACK, BTN_EXTRA, BTN_FORWARD, BTN_LEFT, BTN_RIGHT, BTN_SIDE,
-};
+use flutter_input::pointer::{motion_phase, mouse_button_from_evdev, press_phase, release_phase};
+use smithay_client_toolkit::seat::pointer::{PointerEvent, PointerEventKind};
 use thiserror::Error;
+use wayland_client::{
+    protocol::{wl_pointer::WlPointer, wl_surface::WlSurface},
+    Proxy,
+};
+
+use crate::{scroll::SctkScrollSettings, units::Scale};
+
+/// Flutter tracks the gestures per device, so devices of different kinds must
+/// not share ids (e.g.: the touch points of a convertible laptop while a mouse
+/// is connected). The ids from this base are reserved for the touch points.
+pub(crate) const TOUCH_DEVICE_ID_BASE: i32 = 1 << 30;
+
+/// Device id of the events of |pointer|, i.e.: its protocol id, which the
+/// `flutter/mousecursor` plugin targets.
+pub(crate) fn mouse_device_id(pointer: &WlPointer) -> i32 {
+    mouse_device_id_from_protocol_id(pointer.id().protocol_id())
+}
+
+/// The protocol ids are below the touch devices in practice, but are wrapped
+/// into their range so that they never overlap.
+fn mouse_device_id_from_protocol_id(protocol_id: u32) -> i32 {
+    (protocol_id % TOUCH_DEVICE_ID_BASE as u32) as i32
+}
+
+/// A touch point, from down to up.
+#[derive(Debug, Clone)]
+pub(crate) struct TouchPoint<S> {
+    pub(crate) device: i32,
+    /// The surface the point went down on, which gets all its events.
+    pub(crate) surface: S,
+    /// Last known position, in surface-local logical coordinates.
+    pub(crate) position: LogicalPosition<f64>,
+}
+
+/// Maps the touch points of the `wl_touch` objects to device ids.
+///
+/// The ids of the points are only unique per `wl_touch` while they are down,
+/// and are reused afterwards. Each point gets the lowest free device id from
+/// [`TOUCH_DEVICE_ID_BASE`] instead, so that the devices never collide with
+/// the mice, nor with the points of other seats.
+#[derive(Debug)]
+pub(crate) struct TouchPoints<S> {
+    /// Keyed by the protocol id of the `wl_touch` and the id of the point.
+    points: HashMap<(u32, i32), TouchPoint<S>>,
+}
+
+impl<S> Default for TouchPoints<S> {
+    fn default() -> Self {
+        Self {
+            points: HashMap::new(),
+        }
+    }
+}
+
+impl<S> TouchPoints<S> {
+    pub(crate) fn down(
+        &mut self,
+        touch: u32,
+        id: i32,
+        surface: S,
+        position: LogicalPosition<f64>,
+    ) -> &TouchPoint<S> {
+        // A point which is still down is replaced, e.g.: if its up was missed.
+        self.points.remove(&(touch, id));
+        let device = (TOUCH_DEVICE_ID_BASE..)
+            .find(|&device| self.points.values().all(|point| point.device != device))
+            .unwrap_or(TOUCH_DEVICE_ID_BASE);
+
+        self.points.entry((touch, id)).or_insert(TouchPoint {
+            device,
+            surface,
+            position,
+        })
+    }
+
+    pub(crate) fn motion(
+        &mut self,
+        touch: u32,
+        id: i32,
+        position: LogicalPosition<f64>,
+    ) -> Option<&TouchPoint<S>> {
+        let point = self.points.get_mut(&(touch, id))?;
+        point.position = position;
+        Some(point)
+    }
+
+    /// Release the point, whose device id can then be reused.
+    pub(crate) fn up(&mut self, touch: u32, id: i32) -> Option<TouchPoint<S>> {
+        self.points.remove(&(touch, id))
+    }
+
+    /// Release all the points of |touch|, e.g.: when the compositor cancels
+    /// the touch sequence.
+    pub(crate) fn cancel(&mut self, touch: u32) -> Vec<TouchPoint<S>> {
+        let ids: Vec<_> = self
+            .points
+            .keys()
+            .filter(|(point_touch, _)| *point_touch == touch)
+            .copied()
+            .collect();
+        ids.into_iter()
+            .filter_map(|key| self.points.remove(&key))
+            .collect()
+    }
+}
+
+/// Build the event sent to the engine for |point| in |phase|. The engine adds
+/// the device along with its down, but it must be removed after its up (or
+/// cancel), since each point is a device of its own.
+pub(crate) fn touch_event<S>(
+    view_id: FlutterViewId,
+    point: &TouchPoint<S>,
+    phase: FlutterPointerPhase,
+    scale_factor: Scale,
+) -> FlutterPointerEvent {
+    FlutterPointerEvent::new(
+        point.device,
+        phase,
+        scale_factor.to_physical_position(point.position),
+        FlutterPointerSignalKind::None,
+        (0.0, 0.0),
+        FlutterPointerDeviceKind::Touch,
+        FlutterPointerButtons::default(),
+        view_id,
+    )
+}
 
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct Pointer {
     pub(crate) device: i32,
-    pub(crate) pressed: u32,
+    /// Buttons currently held down, sent along with every event.
+    pub(crate) buttons: FlutterPointerButtons,
+    /// Last known position, in surface-local logical coordinates.
+    pub(crate) position: LogicalPosition<f64>,
+    /// Whether the pointer is within the window, i.e.: entered it and didn't
+    /// leave it since.
+    is_inside: bool,
+    /// Whether the engine knows about the pointer, i.e.: it was added to the
+    /// view and not removed since.
+    is_added: bool,
 }
 
 impl Pointer {
     pub(crate) fn new(device: i32) -> Self {
-        Self { device, pressed: 0 }
+        Self {
+            device,
+            buttons: FlutterPointerButtons::default(),
+            position: LogicalPosition::new(0.0, 0.0),
+            is_inside: false,
+            is_added: false,
+        }
+    }
+
+    #[cfg(feature = "emulated-input")]
+    pub(crate) fn is_inside(&self) -> bool {
+        self.is_inside
     }
 
-    pub(crate) fn increment_pressed(&mut self) {
-        self.pressed += 1;
+    /// Update the state of the pointer for |event|, whether or not the event
+    /// is sent to the engine.
+    pub(crate) fn track(&mut self, event: &PointerEvent) {
+        match event.kind {
+            PointerEventKind::Press { button, .. } => self.press(button),
+            PointerEventKind::Release { button, .. } => self.release(button),
+            _ => {}
+        }
+        self.is_inside = !matches!(event.kind, PointerEventKind::Leave { .. });
+        self.position = event.position.into();
     }
 
-    pub(crate) fn decrement_pressed(&mut self) {
-        self.pressed -= 1;
+    /// Add the pointer to the view if it is within the window but unknown to
+    /// the engine (e.g.: the window was created under the pointer before the
+    /// engine was running), followed by a hover at its current position so
+    /// that hover effects apply without moving the mouse.
+    pub(crate) fn enter_events(
+        &mut self,
+        view_id: FlutterViewId,
+        scale_factor: Scale,
+    ) -> Vec<FlutterPointerEvent> {
+        if !self.is_inside {
+            return Vec::new();
+        }
+
+        let mut events: Vec<_> = self.add_event(view_id, scale_factor).into_iter().collect();
+        events.push(self.synthesized_event(self.phase(), view_id, scale_factor));
+        events
+    }
+
+    /// Remove the pointer from the view, if the engine knows about it (e.g.:
+    /// when the window is destroyed under the pointer, which doesn't get a
+    /// leave event).
+    pub(crate) fn remove_event(
+        &mut self,
+        view_id: FlutterViewId,
+        scale_factor: Scale,
+    ) -> Option<FlutterPointerEvent> {
+        if !std::mem::replace(&mut self.is_added, false) {
+            return None;
+        }
+
+        Some(self.synthesized_event(FlutterPointerPhase::Remove, view_id, scale_factor))
+    }
+
+    fn add_event(
+        &mut self,
+        view_id: FlutterViewId,
+        scale_factor: Scale,
+    ) -> Option<FlutterPointerEvent> {
+        if std::mem::replace(&mut self.is_added, true) {
+            return None;
+        }
+
+        Some(self.synthesized_event(FlutterPointerPhase::Add, view_id, scale_factor))
+    }
+
+    fn synthesized_event(
+        &self,
+        phase: FlutterPointerPhase,
+        view_id: FlutterViewId,
+        scale_factor: Scale,
+    ) -> FlutterPointerEvent {
+        FlutterPointerEvent::new(
+            self.device,
+            phase,
+            scale_factor.to_physical_position(self.position),
+            FlutterPointerSignalKind::None,
+            (0.0, 0.0),
+            FlutterPointerDeviceKind::Mouse,
+            self.buttons,
+            view_id,
+        )
+    }
+
+    fn phase(&self) -> FlutterPointerPhase {
+        motion_phase(self.buttons)
+    }
+
+    pub(crate) fn press(&mut self, button: u32) {
+        self.buttons.insert(mouse_button_from_evdev(button));
+    }
+
+    pub(crate) fn release(&mut self, button: u32) {
+        self.buttons.remove(mouse_button_from_evdev(button));
+    }
+
+    /// Phase of the event sent for a press of |button|, once the pointer
+    /// state has been updated.
+    fn press_phase(&self, button: u32) -> FlutterPointerPhase {
+        press_phase(self.buttons, button)
+    }
+
+    /// Phase of the event sent for a release, once the pointer state has
+    /// been updated.
+    fn release_phase(&self) -> FlutterPointerPhase {
+        release_phase(self.buttons)
     }
 }
 
@@ -41,16 +276,23 @@ pub enum PointerConversionError {
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct SctkPointerEvent(FlutterViewId, PointerEvent, Pointer, f64);
+pub(crate) struct SctkPointerEvent(
+    FlutterViewId,
+    PointerEvent,
+    Pointer,
+    Scale,
+    SctkScrollSettings,
+);
 
 impl SctkPointerEvent {
     pub(crate) fn new(
         view_id: FlutterViewId,
         event: PointerEvent,
         pointer: Pointer,
-        scale_factor: f64,
+        scale_factor: Scale,
+        scroll: SctkScrollSettings,
     ) -> Self {
-        Self(view_id, event, pointer, scale_factor)
+        Self(view_id, event, pointer, scale_factor, scroll)
     }
 }
 
@@ -58,99 +300,564 @@ impl TryFrom<SctkPointerEvent> for FlutterPointerEvent {
     type Error = PointerConversionError;
 
     fn try_from(
-        SctkPointerEvent(view_id, event, pointer, scale_factor): SctkPointerEvent,
+        SctkPointerEvent(view_id, event, pointer, scale_factor, scroll): SctkPointerEvent,
     ) -> Result<Self, Self::Error> {
         use PointerEventKind::*;
 
         // Convert pointer coordinates from logical to physical pixels
-        let physical_position =
-            LogicalPosition::<f64>::from(event.position).to_physical::<f64>(scale_factor);
-        let (x, y) = (physical_position.x, physical_position.y);
+        let position = scale_factor.to_physical_position(event.position.into());
 
         match event.kind {
             Enter { .. } => Ok(FlutterPointerEvent::new(
                 pointer.device,
                 FlutterPointerPhase::Add,
-                (x, y),
+                position,
                 FlutterPointerSignalKind::None,
                 (0.0, 0.0),
                 FlutterPointerDeviceKind::Mouse,
-                FlutterPointerMouseButtons::None,
+                pointer.buttons,
                 view_id,
             )),
             Leave { .. } => Ok(FlutterPointerEvent::new(
                 pointer.device,
                 FlutterPointerPhase::Remove,
-                (x, y),
+                position,
                 FlutterPointerSignalKind::None,
                 (0.0, 0.0),
                 FlutterPointerDeviceKind::Mouse,
-                FlutterPointerMouseButtons::None,
+                pointer.buttons,
                 view_id,
             )),
             Motion { .. } => Ok(FlutterPointerEvent::new(
                 pointer.device,
-                if pointer.pressed > 0 {
-                    FlutterPointerPhase::Move
-                } else {
-                    FlutterPointerPhase::Hover
-                },
-                (x, y),
+                pointer.phase(),
+                position,
                 FlutterPointerSignalKind::None,
                 (0.0, 0.0),
                 FlutterPointerDeviceKind::Mouse,
-                FlutterPointerMouseButtons::None,
+                pointer.buttons,
                 view_id,
             )),
             Press { button, .. } => Ok(FlutterPointerEvent::new(
                 pointer.device,
-                FlutterPointerPhase::Down,
-                (x, y),
+                pointer.press_phase(button),
+                position,
                 FlutterPointerSignalKind::None,
                 (0.0, 0.0),
                 FlutterPointerDeviceKind::Mouse,
-                pointer_mouse_buttons_from_wayland(button),
+                pointer.buttons,
                 view_id,
             )),
-            Release { button, .. } => Ok(FlutterPointerEvent::new(
+            Release { .. } => Ok(FlutterPointerEvent::new(
                 pointer.device,
-                FlutterPointerPhase::Up,
-                (x, y),
+                pointer.release_phase(),
+                position,
                 FlutterPointerSignalKind::None,
                 (0.0, 0.0),
                 FlutterPointerDeviceKind::Mouse,
-                pointer_mouse_buttons_from_wayland(button),
+                pointer.buttons,
                 view_id,
             )),
             Axis {
                 horizontal,
                 vertical,
+                source,
                 ..
             } => Ok(FlutterPointerEvent::new(
                 pointer.device,
-                if pointer.pressed > 0 {
-                    FlutterPointerPhase::Move
-                } else {
-                    FlutterPointerPhase::Hover
-                },
-                (x, y),
+                pointer.phase(),
+                position,
                 FlutterPointerSignalKind::Scroll,
-                (horizontal.discrete as f64, vertical.discrete as f64),
+                (
+                    scroll.delta(&horizontal, source) * scale_factor.get(),
+                    scroll.delta(&vertical, source) * scale_factor.get(),
+                ),
                 FlutterPointerDeviceKind::Mouse,
-                // TODO: Are these values correct?
-                FlutterPointerMouseButtons::None,
+                pointer.buttons,
                 view_id,
             )),
         }
     }
 }
 
-fn pointer_mouse_buttons_from_wayland(button: u32) -> FlutterPointerMouseButtons {
-    match button {
-        BTN_LEFT => FlutterPointerMouseButtons::Primary,
-        BTN_RIGHT => FlutterPointerMouseButtons::Secondary,
-        BTN_BACK | BTN_SIDE => FlutterPointerMouseButtons::Back,
-        BTN_FORWARD | BTN_EXTRA => FlutterPointerMouseButtons::Forward,
-        _ => FlutterPointerMouseButtons::None,
+/// Build the events sent to the engine for |event|, once the state of
+/// |pointer| has been updated (see `Pointer::track`).
+///
+/// The engine ignores the events of pointers which were not added to the
+/// view. The add is synthesized when the pointer enters the window, or along
+/// with the first event of the pointer otherwise (e.g.: when the enter event
+/// arrived before the window was known).
+///
+/// Positions and scroll deltas are converted with the |scale_factor| of the
+/// surface rather than the pixel ratio reported to the engine: the latter
+/// only maps the physical pixels to the logical coordinates of the view
+/// (e.g.: when zoomed or overridden), while the window keeps its physical
+/// size.
+pub(crate) fn pointer_events(
+    view_id: FlutterViewId,
+    event: &PointerEvent,
+    pointer: &mut Pointer,
+    scale_factor: Scale,
+    scroll: SctkScrollSettings,
+) -> Result<Vec<FlutterPointerEvent>, PointerConversionError> {
+    match event.kind {
+        PointerEventKind::Enter { .. } => Ok(pointer.enter_events(view_id, scale_factor)),
+        PointerEventKind::Leave { .. } => {
+            Ok(pointer.remove_event(view_id, scale_factor).into_iter().collect())
+        }
+        _ => {
+            let sctk_event =
+                SctkPointerEvent::new(view_id, event.clone(), *pointer, scale_factor, scroll);
+            let event = FlutterPointerEvent::try_from(sctk_event)?;
+
+            let mut events: Vec<_> = pointer.add_event(view_id, scale_factor).into_iter().collect();
+            events.push(event);
+            Ok(events)
+        }
+    }
+}
+
+/// Build the scale signal sent for a pinch gesture update. |scale| is relative
+/// to the previous update.
+pub(crate) fn pinch_scale_event(
+    view_id: FlutterViewId,
+    pointer: Pointer,
+    scale_factor: Scale,
+    scale: f64,
+) -> FlutterPointerEvent {
+    FlutterPointerEvent::new(
+        pointer.device,
+        pointer.phase(),
+        scale_factor.to_physical_position(pointer.position),
+        FlutterPointerSignalKind::Scale,
+        (0.0, 0.0),
+        FlutterPointerDeviceKind::Mouse,
+        pointer.buttons,
+        view_id,
+    )
+    .with_scale(scale)
+}
+
+/// User data of a `zwp_pointer_gesture_pinch_v1` object.
+pub(crate) struct SctkPinchGestureData {
+    pub(crate) pointer: WlPointer,
+    /// Surface and scale (relative to the initial finger position) of the
+    /// pinch gesture in progress.
+    active: Mutex<Option<(WlSurface, f64)>>,
+}
+
+impl SctkPinchGestureData {
+    pub(crate) fn new(pointer: WlPointer) -> Self {
+        Self {
+            pointer,
+            active: Default::default(),
+        }
+    }
+
+    pub(crate) fn begin(&self, surface: WlSurface) {
+        *self.active.lock().unwrap() = Some((surface, 1.0));
+    }
+
+    /// Returns the surface of the gesture and the scale relative to the
+    /// previous update, as expected by Flutter scale signals.
+    pub(crate) fn update(&self, scale: f64) -> Option<(WlSurface, f64)> {
+        let mut active = self.active.lock().unwrap();
+        let (surface, last_scale) = active.as_mut()?;
+
+        let relative_scale = scale / *last_scale;
+        *last_scale = scale;
+
+        relative_scale
+            .is_normal()
+            .then(|| (surface.clone(), relative_scale))
+    }
+
+    pub(crate) fn end(&self) {
+        *self.active.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, os::unix::net::UnixStream};
+
+    use dpi::LogicalPosition;
+    use flutter_engine::ffi::{FlutterViewId, IMPLICIT_VIEW_ID};
+
+    use flutter_engine::ffi::{FlutterPointerMouseButtons, FlutterPointerPhase};
+    use smithay_client_toolkit::seat::pointer::{
+        AxisScroll, PointerEvent, PointerEventKind, BTN_LEFT, BTN_RIGHT,
+    };
+    use wayland_backend::client::Backend;
+    use wayland_client::{
+        protocol::{wl_pointer::AxisSource, wl_surface::WlSurface},
+        Proxy,
+    };
+
+    use super::{
+        mouse_device_id_from_protocol_id, pinch_scale_event, pointer_events, touch_event, Pointer,
+        TouchPoints, TOUCH_DEVICE_ID_BASE,
+    };
+    use crate::{scroll::SctkScrollSettings, units::Scale};
+
+    const VIEW_A: FlutterViewId = 1;
+    const VIEW_B: FlutterViewId = 2;
+
+    /// Stands in for the windows of an engine: records the events sent to it
+    /// for a single pointer.
+    struct Harness {
+        backend: Backend,
+        pointers: HashMap<FlutterViewId, Pointer>,
+        is_engine_running: bool,
+        scale_factor: Scale,
+        scroll: SctkScrollSettings,
+        events: Vec<(FlutterViewId, FlutterPointerPhase, (f64, f64))>,
+    }
+
+    impl Harness {
+        fn new(is_engine_running: bool) -> Self {
+            let (stream, _) = UnixStream::pair().unwrap();
+            Self {
+                backend: Backend::connect(stream).unwrap(),
+                pointers: HashMap::new(),
+                is_engine_running,
+                scale_factor: Scale::default(),
+                scroll: SctkScrollSettings::default(),
+                events: Vec::new(),
+            }
+        }
+
+        /// See `SctkFlutterWindow::pointer_event`.
+        fn pointer_event(
+            &mut self,
+            view_id: FlutterViewId,
+            kind: PointerEventKind,
+            position: (f64, f64),
+        ) {
+            let event = PointerEvent {
+                surface: WlSurface::inert(self.backend.downgrade()),
+                position,
+                kind,
+            };
+
+            let pointer = self.pointers.entry(view_id).or_insert_with(|| Pointer::new(1));
+            pointer.track(&event);
+            if !self.is_engine_running {
+                return;
+            }
+
+            let events =
+                pointer_events(view_id, &event, pointer, self.scale_factor, self.scroll).unwrap();
+            self.record(events);
+        }
+
+        fn enter(&mut self, view_id: FlutterViewId, position: (f64, f64)) {
+            self.pointer_event(view_id, PointerEventKind::Enter { serial: 0 }, position);
+        }
+
+        fn leave(&mut self, view_id: FlutterViewId) {
+            self.pointer_event(view_id, PointerEventKind::Leave { serial: 0 }, (0.0, 0.0));
+        }
+
+        fn motion(&mut self, view_id: FlutterViewId, position: (f64, f64)) {
+            self.pointer_event(view_id, PointerEventKind::Motion { time: 0 }, position);
+        }
+
+        fn click(&mut self, view_id: FlutterViewId, position: (f64, f64)) {
+            let (time, button, serial) = (0, BTN_LEFT, 0);
+            let press = PointerEventKind::Press { time, button, serial };
+            self.pointer_event(view_id, press, position);
+            let release = PointerEventKind::Release { time, button, serial };
+            self.pointer_event(view_id, release, position);
+        }
+
+        /// See `SctkFlutterWindow::add_entered_pointers`.
+        fn start_engine(&mut self) {
+            self.is_engine_running = true;
+            let events: Vec<_> = self
+                .pointers
+                .iter_mut()
+                .flat_map(|(&view_id, pointer)| pointer.enter_events(view_id, self.scale_factor))
+                .collect();
+            self.record(events);
+        }
+
+        /// See `SctkFlutterWindow::remove_pointers`.
+        fn destroy_window(&mut self, view_id: FlutterViewId) {
+            let mut pointer = self.pointers.remove(&view_id).unwrap();
+            let events = pointer.remove_event(view_id, self.scale_factor);
+            self.record(events.into_iter().collect());
+        }
+
+        fn record(&mut self, events: Vec<flutter_engine::ffi::FlutterPointerEvent>) {
+            for event in events {
+                let view_id = event.view_id();
+                let phase = event.phase();
+                let event: flutter_engine_sys::FlutterPointerEvent = event.into();
+                self.events.push((view_id, phase, (event.x, event.y)));
+            }
+        }
+
+        fn take_events(&mut self) -> Vec<(FlutterViewId, FlutterPointerPhase, (f64, f64))> {
+            std::mem::take(&mut self.events)
+        }
+    }
+
+    #[test]
+    fn window_created_under_cursor() {
+        use FlutterPointerPhase::*;
+
+        // The enter event arrives before the engine is running.
+        let mut harness = Harness::new(false);
+        harness.enter(VIEW_A, (5.0, 0.0));
+        harness.motion(VIEW_A, (7.0, 0.0));
+        assert_eq!(harness.take_events(), []);
+
+        harness.start_engine();
+        assert_eq!(
+            harness.take_events(),
+            [(VIEW_A, Add, (7.0, 0.0)), (VIEW_A, Hover, (7.0, 0.0))]
+        );
+
+        harness.motion(VIEW_A, (8.0, 0.0));
+        assert_eq!(harness.take_events(), [(VIEW_A, Hover, (8.0, 0.0))]);
+
+        // The enter event is missed (e.g.: the window was not known yet).
+        harness.motion(VIEW_B, (3.0, 0.0));
+        assert_eq!(
+            harness.take_events(),
+            [(VIEW_B, Add, (3.0, 0.0)), (VIEW_B, Hover, (3.0, 0.0))]
+        );
+    }
+
+    #[test]
+    fn window_destroyed_under_cursor() {
+        use FlutterPointerPhase::*;
+
+        let mut harness = Harness::new(true);
+        harness.enter(VIEW_A, (1.0, 0.0));
+        harness.motion(VIEW_A, (2.0, 0.0));
+        harness.destroy_window(VIEW_A);
+        assert_eq!(
+            harness.take_events(),
+            [
+                (VIEW_A, Add, (1.0, 0.0)),
+                (VIEW_A, Hover, (1.0, 0.0)),
+                (VIEW_A, Hover, (2.0, 0.0)),
+                (VIEW_A, Remove, (2.0, 0.0))
+            ]
+        );
+
+        // Pointers which left the window are not removed twice.
+        harness.enter(VIEW_B, (1.0, 0.0));
+        harness.leave(VIEW_B);
+        harness.destroy_window(VIEW_B);
+        assert_eq!(
+            harness.take_events(),
+            [
+                (VIEW_B, Add, (1.0, 0.0)),
+                (VIEW_B, Hover, (1.0, 0.0)),
+                (VIEW_B, Remove, (0.0, 0.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn bouncing_between_adjacent_windows() {
+        use FlutterPointerPhase::*;
+
+        let mut harness = Harness::new(true);
+        // Compositors may send the enter of the next surface before the
+        // leave of the previous one, within the same frame.
+        harness.enter(VIEW_A, (99.0, 0.0));
+        harness.enter(VIEW_B, (0.0, 0.0));
+        harness.leave(VIEW_A);
+        harness.leave(VIEW_B);
+        harness.enter(VIEW_A, (99.0, 0.0));
+        // A duplicate leave is ignored.
+        harness.leave(VIEW_B);
+        assert_eq!(
+            harness.take_events(),
+            [
+                (VIEW_A, Add, (99.0, 0.0)),
+                (VIEW_A, Hover, (99.0, 0.0)),
+                (VIEW_B, Add, (0.0, 0.0)),
+                (VIEW_B, Hover, (0.0, 0.0)),
+                (VIEW_A, Remove, (0.0, 0.0)),
+                (VIEW_B, Remove, (0.0, 0.0)),
+                (VIEW_A, Add, (99.0, 0.0)),
+                (VIEW_A, Hover, (99.0, 0.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn pointer_positions_are_sent_in_physical_pixels() {
+        let pointer = Pointer {
+            position: LogicalPosition::new(10.5, 3.25),
+            ..Pointer::new(1)
+        };
+
+        let event: flutter_engine_sys::FlutterPointerEvent =
+            pinch_scale_event(IMPLICIT_VIEW_ID, pointer, Scale::from_buffer_scale(2), 1.5).into();
+
+        assert_eq!((event.x, event.y), (21.0, 6.5));
+        assert_eq!(event.scale, 1.5);
+    }
+
+    #[test]
+    fn held_buttons_are_tracked_during_drags() {
+        let mut pointer = Pointer::new(1);
+        assert_eq!(pointer.phase(), FlutterPointerPhase::Hover);
+
+        pointer.press(BTN_LEFT);
+        assert_eq!(pointer.press_phase(BTN_LEFT), FlutterPointerPhase::Down);
+        assert_eq!(pointer.phase(), FlutterPointerPhase::Move);
+
+        // Pressing another button while dragging only updates the mask.
+        pointer.press(BTN_RIGHT);
+        assert_eq!(pointer.press_phase(BTN_RIGHT), FlutterPointerPhase::Move);
+        assert!(pointer.buttons.contains(FlutterPointerMouseButtons::Primary));
+        assert!(pointer.buttons.contains(FlutterPointerMouseButtons::Secondary));
+
+        pointer.release(BTN_LEFT);
+        assert_eq!(pointer.release_phase(), FlutterPointerPhase::Move);
+        assert!(!pointer.buttons.contains(FlutterPointerMouseButtons::Primary));
+
+        pointer.release(BTN_RIGHT);
+        assert_eq!(pointer.release_phase(), FlutterPointerPhase::Up);
+        assert_eq!(pointer.phase(), FlutterPointerPhase::Hover);
+
+        let event: flutter_engine_sys::FlutterPointerEvent =
+            pinch_scale_event(IMPLICIT_VIEW_ID, pointer, Scale::default(), 1.0).into();
+        assert_eq!(event.buttons, 0);
+    }
+
+    #[test]
+    fn pointer_positions_follow_fractional_scales() {
+        use FlutterPointerPhase::*;
+
+        // Fractional scales are only reported through the pixel ratio for
+        // now, which doesn't change the conversion.
+        let mut harness = Harness::new(true);
+        harness.scale_factor = Scale::default().pixel_ratio(Some(1.5), 1.0);
+
+        harness.enter(VIEW_A, (10.5, 3.25));
+        harness.click(VIEW_A, (101.0, 67.0));
+        harness.motion(VIEW_A, (0.5, 0.25));
+        assert_eq!(
+            harness.take_events(),
+            [
+                (VIEW_A, Add, (15.75, 4.875)),
+                (VIEW_A, Hover, (15.75, 4.875)),
+                (VIEW_A, Down, (151.5, 100.5)),
+                (VIEW_A, Up, (151.5, 100.5)),
+                (VIEW_A, Hover, (0.75, 0.375))
+            ]
+        );
+
+        // Scale changes apply to the next events.
+        harness.scale_factor = Scale::from_buffer_scale(2);
+        harness.motion(VIEW_A, (10.5, 3.25));
+        assert_eq!(harness.take_events(), [(VIEW_A, Hover, (21.0, 6.5))]);
+    }
+
+    #[test]
+    fn scroll_deltas_are_sent_in_physical_pixels() {
+        let harness = Harness::new(true);
+        let event = PointerEvent {
+            surface: WlSurface::inert(harness.backend.downgrade()),
+            position: (1.0, 1.0),
+            kind: PointerEventKind::Axis {
+                time: 0,
+                horizontal: AxisScroll::default(),
+                vertical: AxisScroll {
+                    absolute: 2.5,
+                    ..Default::default()
+                },
+                source: Some(AxisSource::Finger),
+            },
+        };
+
+        let mut pointer = Pointer::new(1);
+        let scale_factor = Scale::from_buffer_scale(2);
+        let events =
+            pointer_events(VIEW_A, &event, &mut pointer, scale_factor, harness.scroll).unwrap();
+        let event = events.into_iter().last().unwrap();
+        let event: flutter_engine_sys::FlutterPointerEvent = event.into();
+        assert_eq!((event.scroll_delta_x, event.scroll_delta_y), (0.0, 5.0));
+    }
+
+    #[test]
+    fn touch_points_get_the_lowest_free_device() {
+        let mut points = TouchPoints::default();
+        let position = LogicalPosition::new(1.0, 2.0);
+        let first = points.down(3, 0, (), position).device;
+        let second = points.down(3, 1, (), position).device;
+        // Another seat reusing the same point id.
+        let other_seat = points.down(4, 0, (), position).device;
+        assert_eq!(
+            [first, second, other_seat],
+            [0, 1, 2].map(|slot| TOUCH_DEVICE_ID_BASE + slot)
+        );
+
+        let moved = points.motion(3, 1, LogicalPosition::new(4.0, 5.0)).unwrap();
+        assert_eq!(
+            (moved.device, moved.position),
+            (second, LogicalPosition::new(4.0, 5.0))
+        );
+        assert!(points.motion(3, 7, position).is_none());
+
+        // The device of a point which is up is reused.
+        assert_eq!(points.up(3, 0).unwrap().device, first);
+        assert!(points.up(3, 0).is_none());
+        assert_eq!(points.down(3, 0, (), position).device, first);
+
+        let mut cancelled: Vec<_> = points.cancel(3).iter().map(|point| point.device).collect();
+        cancelled.sort();
+        assert_eq!(cancelled, [first, second]);
+        assert_eq!(points.cancel(4).len(), 1);
+        assert!(points.cancel(4).is_empty());
+    }
+
+    #[test]
+    fn mouse_and_touch_devices_never_collide() {
+        let mut points = TouchPoints::default();
+        let position = LogicalPosition::new(0.0, 0.0);
+        let touch_devices: Vec<_> = (0..64)
+            .map(|id| points.down(1, id, (), position).device)
+            .collect();
+
+        for protocol_id in [0, 1, 42, 1 << 30, 0xff00_0000, u32::MAX] {
+            let mouse = mouse_device_id_from_protocol_id(protocol_id);
+            assert!((0..TOUCH_DEVICE_ID_BASE).contains(&mouse), "{protocol_id}");
+            assert!(!touch_devices.contains(&mouse), "{protocol_id}");
+        }
+        assert!(touch_devices
+            .iter()
+            .all(|&device| device >= TOUCH_DEVICE_ID_BASE));
+    }
+
+    #[test]
+    fn touch_events_are_sent_as_touch() {
+        let mut points = TouchPoints::default();
+        let position = LogicalPosition::new(2.0, 3.25);
+        let point = points.down(1, 0, (), position).clone();
+        let event = touch_event(
+            VIEW_A,
+            &point,
+            FlutterPointerPhase::Down,
+            Scale::from_buffer_scale(2),
+        );
+        assert_eq!(event.device(), point.device);
+        assert_eq!(event.phase(), FlutterPointerPhase::Down);
+
+        let event: flutter_engine_sys::FlutterPointerEvent = event.into();
+        assert_eq!((event.x, event.y), (4.0, 6.5));
+        assert_eq!(event.buttons, 0);
+        assert_eq!(
+            event.device_kind,
+            flutter_engine_sys::FlutterPointerDeviceKind::kFlutterPointerDeviceKindTouch
+        );
     }
 }
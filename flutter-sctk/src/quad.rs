@@ -0,0 +1,219 @@
+//! Textured quads for compositing the layers of a view which can't simply be
+//! blitted to the window (e.g.: the backing stores above a platform view have
+//! to be blended with the layers below them).
+use std::{ffi::CStr, mem, ptr};
+
+use dpi::PhysicalSize;
+use flutter_glutin::gl;
+use tracing::error;
+
+use crate::overlay::OverlayRect;
+
+const VERTEX_SHADER: &str = "
+attribute vec2 position;
+attribute vec2 tex_coord;
+varying vec2 v_tex_coord;
+
+void main() {
+    v_tex_coord = tex_coord;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+";
+
+const FRAGMENT_SHADER: &str = "
+uniform sampler2D source;
+uniform float opacity;
+varying vec2 v_tex_coord;
+
+void main() {
+    gl_FragColor = texture2D(source, v_tex_coord) * opacity;
+}
+";
+
+const POSITION_LOCATION: u32 = 0;
+const TEX_COORD_LOCATION: u32 = 1;
+
+/// How the rows of a texture are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QuadOrigin {
+    /// The first row is at the bottom (e.g.: framebuffers rendered by the
+    /// engine).
+    BottomLeft,
+    /// The first row is at the top (e.g.: imported dmabufs).
+    TopLeft,
+}
+
+/// Draws premultiplied textures with blending. The context must be current
+/// for every method.
+pub(crate) struct SctkQuadProgram {
+    program: u32,
+    vertex_array: u32,
+    vertex_buffer: u32,
+    source_location: i32,
+    opacity_location: i32,
+}
+
+impl SctkQuadProgram {
+    pub(crate) fn new(gl: &gl::Gl) -> Option<Self> {
+        // The context may either be OpenGL or OpenGL ES.
+        let is_gles = unsafe {
+            let version = gl.GetString(gl::VERSION);
+            !version.is_null()
+                && CStr::from_ptr(version as *const _)
+                    .to_bytes()
+                    .starts_with(b"OpenGL ES")
+        };
+        let header = if is_gles {
+            "#version 100\nprecision mediump float;\n"
+        } else {
+            "#version 120\n"
+        };
+
+        unsafe {
+            let vertex_shader = compile_shader(gl, gl::VERTEX_SHADER, header, VERTEX_SHADER)?;
+            let fragment_shader =
+                compile_shader(gl, gl::FRAGMENT_SHADER, header, FRAGMENT_SHADER)?;
+
+            let program = gl.CreateProgram();
+            gl.AttachShader(program, vertex_shader);
+            gl.AttachShader(program, fragment_shader);
+            gl.BindAttribLocation(program, POSITION_LOCATION, c_str(b"position\0"));
+            gl.BindAttribLocation(program, TEX_COORD_LOCATION, c_str(b"tex_coord\0"));
+            gl.LinkProgram(program);
+            gl.DeleteShader(vertex_shader);
+            gl.DeleteShader(fragment_shader);
+
+            let mut status = 0;
+            gl.GetProgramiv(program, gl::LINK_STATUS, &mut status);
+            if status == 0 {
+                error!("Failed to link the quad program");
+                gl.DeleteProgram(program);
+                return None;
+            }
+
+            // Vertex arrays are required by core profiles but don't exist in
+            // OpenGL ES 2.0.
+            let mut vertex_array = 0;
+            if gl.GenVertexArrays.is_loaded() {
+                gl.GenVertexArrays(1, &mut vertex_array);
+            }
+
+            let mut vertex_buffer = 0;
+            gl.GenBuffers(1, &mut vertex_buffer);
+
+            Some(Self {
+                program,
+                vertex_array,
+                vertex_buffer,
+                source_location: gl.GetUniformLocation(program, c_str(b"source\0")),
+                opacity_location: gl.GetUniformLocation(program, c_str(b"opacity\0")),
+            })
+        }
+    }
+
+    /// Blend the |texture_id| 2D texture into |rect| of the bound draw
+    /// framebuffer, whose size is |target_size|.
+    pub(crate) fn draw(
+        &self,
+        gl: &gl::Gl,
+        texture_id: u32,
+        origin: QuadOrigin,
+        rect: OverlayRect,
+        target_size: PhysicalSize<u32>,
+        opacity: f64,
+    ) {
+        if target_size.width == 0 || target_size.height == 0 {
+            return;
+        }
+
+        // Normalized device coordinates start from the bottom-left corner.
+        let left = (2.0 * rect.x / target_size.width as f64 - 1.0) as f32;
+        let right = (2.0 * (rect.x + rect.width) / target_size.width as f64 - 1.0) as f32;
+        let top = (1.0 - 2.0 * rect.y / target_size.height as f64) as f32;
+        let bottom = (1.0 - 2.0 * (rect.y + rect.height) / target_size.height as f64) as f32;
+
+        let (top_t, bottom_t) = match origin {
+            QuadOrigin::BottomLeft => (1.0, 0.0),
+            QuadOrigin::TopLeft => (0.0, 1.0),
+        };
+
+        #[rustfmt::skip]
+        let vertices: [f32; 16] = [
+            left, bottom, 0.0, bottom_t,
+            right, bottom, 1.0, bottom_t,
+            left, top, 0.0, top_t,
+            right, top, 1.0, top_t,
+        ];
+        let stride = (4 * mem::size_of::<f32>()) as i32;
+
+        unsafe {
+            gl.UseProgram(self.program);
+            if self.vertex_array != 0 {
+                gl.BindVertexArray(self.vertex_array);
+            }
+
+            gl.BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer);
+            gl.BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(&vertices) as isize,
+                vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
+            gl.VertexAttribPointer(POSITION_LOCATION, 2, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl.VertexAttribPointer(
+                TEX_COORD_LOCATION,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (2 * mem::size_of::<f32>()) as *const _,
+            );
+            gl.EnableVertexAttribArray(POSITION_LOCATION);
+            gl.EnableVertexAttribArray(TEX_COORD_LOCATION);
+
+            gl.ActiveTexture(gl::TEXTURE0);
+            gl.BindTexture(gl::TEXTURE_2D, texture_id);
+            gl.Uniform1i(self.source_location, 0);
+            gl.Uniform1f(self.opacity_location, opacity as f32);
+
+            gl.Enable(gl::BLEND);
+            gl.BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+            gl.Viewport(0, 0, target_size.width as i32, target_size.height as i32);
+            gl.DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+
+            gl.Disable(gl::BLEND);
+            gl.DisableVertexAttribArray(POSITION_LOCATION);
+            gl.DisableVertexAttribArray(TEX_COORD_LOCATION);
+            gl.BindTexture(gl::TEXTURE_2D, 0);
+            gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+            if self.vertex_array != 0 {
+                gl.BindVertexArray(0);
+            }
+            gl.UseProgram(0);
+        }
+    }
+}
+
+fn c_str(name: &'static [u8]) -> *const gl::types::GLchar {
+    debug_assert_eq!(name.last(), Some(&0));
+    name.as_ptr() as *const _
+}
+
+unsafe fn compile_shader(gl: &gl::Gl, kind: u32, header: &str, source: &str) -> Option<u32> {
+    let sources = [header.as_ptr() as *const _, source.as_ptr() as *const _];
+    let lengths = [header.len() as i32, source.len() as i32];
+
+    let shader = gl.CreateShader(kind);
+    gl.ShaderSource(shader, 2, sources.as_ptr(), lengths.as_ptr());
+    gl.CompileShader(shader);
+
+    let mut status = 0;
+    gl.GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+    if status == 0 {
+        error!("Failed to compile the quad shader {:#x}", kind);
+        gl.DeleteShader(shader);
+        return None;
+    }
+
+    Some(shader)
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+/// Pixel layout of a [`SctkFrameBuffer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SctkPixelFormat {
+    /// 8 bits per channel in `R, G, B, A` byte order, with premultiplied
+    /// alpha.
+    Rgba8888,
+}
+
+impl SctkPixelFormat {
+    pub fn bytes_per_pixel(&self) -> u32 {
+        match self {
+            SctkPixelFormat::Rgba8888 => 4,
+        }
+    }
+}
+
+/// A frame rendered by the engine, read back from the GPU.
+///
+/// Rows are stored top to bottom, each row being `stride` bytes long.
+#[derive(Debug)]
+pub struct SctkFrameBuffer<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+    pub format: SctkPixelFormat,
+    pub data: &'a [u8],
+}
+
+pub type SctkFrameReadbackCallback = Arc<dyn Fn(&SctkFrameBuffer) + Send + Sync>;
+
+/// Configuration used for streaming every presented frame to a callback
+/// (e.g.: for screen sharing or thin-client display).
+#[derive(Clone)]
+pub struct SctkFrameReadback {
+    pub(crate) callback: SctkFrameReadbackCallback,
+    pub(crate) present_on_screen: bool,
+}
+
+impl SctkFrameReadback {
+    /// Note: The callback is executed on the *render* thread and the buffer is
+    /// only valid for the duration of the call.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&SctkFrameBuffer) + Send + Sync + 'static,
+    {
+        Self {
+            callback: Arc::new(callback),
+            present_on_screen: true,
+        }
+    }
+
+    /// Whether frames should also be presented to the Wayland surface
+    /// (defaults to `true`).
+    ///
+    /// When disabled, the compositor stops sending frame callbacks for the
+    /// window, so the application should use
+    /// [`VsyncMode::Software`](flutter_runner_api::VsyncMode::Software).
+    pub fn with_present_on_screen(mut self, present_on_screen: bool) -> Self {
+        self.present_on_screen = present_on_screen;
+        self
+    }
+}
+
+/// OpenGL reads pixels starting from the bottom-left corner. Flip the rows so
+/// that the buffer is laid out top to bottom.
+pub(crate) fn flip_rows(data: &mut [u8], stride: usize) {
+    if stride == 0 {
+        return;
+    }
+
+    let rows = data.len() / stride;
+    for row in 0..rows / 2 {
+        let (top, bottom) = data.split_at_mut((rows - row - 1) * stride);
+        top[row * stride..(row + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::flip_rows;
+
+    #[test]
+    fn flips_rows() {
+        let mut data = vec![1, 1, 2, 2, 3, 3];
+        flip_rows(&mut data, 2);
+        assert_eq!(data, vec![3, 3, 2, 2, 1, 1]);
+
+        let mut data = vec![1, 1, 2, 2];
+        flip_rows(&mut data, 2);
+        assert_eq!(data, vec![2, 2, 1, 1]);
+    }
+}
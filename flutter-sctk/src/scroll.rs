@@ -0,0 +1,266 @@
+//! Scroll settings, and the conversion of the axis events to the scroll
+//! deltas sent to the engine.
+//!
+//! The natural scrolling setting of the desktop is read from the settings
+//! portal (`org.gnome.desktop.peripherals.*`). Compositors already apply it
+//! to the axis events (through libinput), so the deltas are only inverted
+//! when the application overrides the direction (see [`PointerConfig`]) with
+//! a different one. Desktops don't share any scroll speed setting through
+//! the portal, and the compositors which have one apply it themselves, so
+//! only the factor of the application is applied on top of the events.
+use ashpd::desktop::settings::Settings;
+use calloop::channel::Sender;
+use flutter_plugins::{
+    diagnostics::ScrollSettings,
+    mousecursor::{PointerConfig, ScrollDirection},
+};
+use futures_lite::{stream, StreamExt};
+use smithay_client_toolkit::seat::pointer::AxisScroll;
+use wayland_client::protocol::wl_pointer::AxisSource;
+
+use crate::{application::SctkApplicationRequest, handler::SctkAsyncResult};
+
+const GNOME_MOUSE_NAMESPACE: &str = "org.gnome.desktop.peripherals.mouse";
+const GNOME_TOUCHPAD_NAMESPACE: &str = "org.gnome.desktop.peripherals.touchpad";
+const GNOME_NATURAL_SCROLL_KEY: &str = "natural-scroll";
+
+/// Logical pixels scrolled per wheel step, like the GTK embedder.
+const WHEEL_STEP_DISTANCE: f64 = 53.0;
+
+/// A setting read from the portal, or a change notified by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SctkScrollChange {
+    MouseNaturalScroll(bool),
+    TouchpadNaturalScroll(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScrollDevice {
+    Wheel,
+    Touchpad,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct SctkScrollSettings {
+    config: PointerConfig,
+    /// Natural scrolling of the desktop, already applied by the compositor.
+    mouse_natural_scroll: bool,
+    touchpad_natural_scroll: bool,
+}
+
+impl SctkScrollSettings {
+    pub(crate) fn new(config: PointerConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether the settings have changed.
+    pub(crate) fn apply(&mut self, change: SctkScrollChange) -> bool {
+        let previous = *self;
+        match change {
+            SctkScrollChange::MouseNaturalScroll(enabled) => self.mouse_natural_scroll = enabled,
+            SctkScrollChange::TouchpadNaturalScroll(enabled) => {
+                self.touchpad_natural_scroll = enabled
+            }
+        }
+        *self != previous
+    }
+
+    /// The settings applied to the events, as reported to the diagnostics.
+    pub(crate) fn effective(&self) -> ScrollSettings {
+        ScrollSettings {
+            mouse_natural_scroll: self.is_natural(ScrollDevice::Wheel),
+            touchpad_natural_scroll: self.is_natural(ScrollDevice::Touchpad),
+            factor: self.config.scroll_factor,
+        }
+    }
+
+    fn is_natural(&self, device: ScrollDevice) -> bool {
+        match self.config.scroll_direction {
+            ScrollDirection::Desktop => self.is_desktop_natural(device),
+            ScrollDirection::Natural => true,
+            ScrollDirection::Traditional => false,
+        }
+    }
+
+    fn is_desktop_natural(&self, device: ScrollDevice) -> bool {
+        match device {
+            ScrollDevice::Wheel => self.mouse_natural_scroll,
+            ScrollDevice::Touchpad => self.touchpad_natural_scroll,
+        }
+    }
+
+    /// Scroll delta along |axis|, in logical pixels. Wheels scroll a fixed
+    /// distance per step, while the distance of continuous sources (e.g.:
+    /// touchpads) is used as is.
+    pub(crate) fn delta(&self, axis: &AxisScroll, source: Option<AxisSource>) -> f64 {
+        let steps = f64::from(axis.discrete);
+
+        let device = match source {
+            Some(AxisSource::Wheel | AxisSource::WheelTilt) => ScrollDevice::Wheel,
+            Some(_) => ScrollDevice::Touchpad,
+            // Older compositors don't report the source.
+            None if steps != 0.0 => ScrollDevice::Wheel,
+            None => ScrollDevice::Touchpad,
+        };
+
+        let distance = match device {
+            ScrollDevice::Wheel if steps != 0.0 => steps * WHEEL_STEP_DISTANCE,
+            _ => axis.absolute,
+        };
+
+        let is_inverted = self.is_natural(device) != self.is_desktop_natural(device);
+        let direction = if is_inverted { -1.0 } else { 1.0 };
+        distance * direction * self.config.scroll_factor
+    }
+}
+
+/// Read the natural scrolling settings, then forward their changes to the
+/// application until the portal goes away.
+pub(crate) async fn read_and_monitor_scroll_changes(
+    sender: Sender<SctkApplicationRequest>,
+) -> SctkAsyncResult {
+    let settings = Settings::new().await?;
+
+    // The GNOME settings are missing from other desktops, and from sandboxes
+    // which don't expose them.
+    let current_values = [
+        settings
+            .read::<bool>(GNOME_MOUSE_NAMESPACE, GNOME_NATURAL_SCROLL_KEY)
+            .await
+            .ok()
+            .map(SctkScrollChange::MouseNaturalScroll),
+        settings
+            .read::<bool>(GNOME_TOUCHPAD_NAMESPACE, GNOME_NATURAL_SCROLL_KEY)
+            .await
+            .ok()
+            .map(SctkScrollChange::TouchpadNaturalScroll),
+    ];
+
+    let mouse_changes = settings
+        .receive_setting_changed_with_args::<bool>(GNOME_MOUSE_NAMESPACE, GNOME_NATURAL_SCROLL_KEY)
+        .await?
+        .filter_map(|enabled| enabled.ok())
+        .map(SctkScrollChange::MouseNaturalScroll);
+    let touchpad_changes = settings
+        .receive_setting_changed_with_args::<bool>(
+            GNOME_TOUCHPAD_NAMESPACE,
+            GNOME_NATURAL_SCROLL_KEY,
+        )
+        .await?
+        .filter_map(|enabled| enabled.ok())
+        .map(SctkScrollChange::TouchpadNaturalScroll);
+
+    let mut stream = Box::pin(
+        stream::iter(current_values.into_iter().flatten())
+            .chain(mouse_changes.or(touchpad_changes)),
+    );
+
+    while let Some(change) = stream.next().await {
+        if sender
+            .send(SctkApplicationRequest::UpdateScroll(change))
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_plugins::mousecursor::{PointerConfig, ScrollDirection};
+    use smithay_client_toolkit::seat::pointer::AxisScroll;
+    use wayland_client::protocol::wl_pointer::AxisSource;
+
+    use super::{SctkScrollChange, SctkScrollSettings};
+
+    fn wheel(discrete: i32) -> AxisScroll {
+        AxisScroll {
+            absolute: 10.0 * f64::from(discrete),
+            discrete,
+            ..Default::default()
+        }
+    }
+
+    fn touchpad(absolute: f64) -> AxisScroll {
+        AxisScroll {
+            absolute,
+            ..Default::default()
+        }
+    }
+
+    fn settings(scroll_direction: ScrollDirection, scroll_factor: f64) -> SctkScrollSettings {
+        SctkScrollSettings::new(PointerConfig {
+            scroll_direction,
+            scroll_factor,
+        })
+    }
+
+    #[test]
+    fn wheels_scroll_a_distance_per_step() {
+        let settings = SctkScrollSettings::default();
+        let wheel_source = Some(AxisSource::Wheel);
+
+        assert_eq!(settings.delta(&wheel(1), wheel_source), 53.0);
+        assert_eq!(settings.delta(&wheel(-2), None), -106.0);
+        // Without any step, the distance reported by the compositor is used.
+        assert_eq!(settings.delta(&touchpad(4.0), wheel_source), 4.0);
+    }
+
+    #[test]
+    fn continuous_sources_scroll_their_distance() {
+        let settings = SctkScrollSettings::default();
+
+        assert_eq!(settings.delta(&touchpad(7.5), Some(AxisSource::Finger)), 7.5);
+        assert_eq!(settings.delta(&touchpad(-3.0), Some(AxisSource::Continuous)), -3.0);
+        assert_eq!(settings.delta(&touchpad(-3.0), None), -3.0);
+    }
+
+    #[test]
+    fn only_overridden_directions_are_inverted() {
+        let finger = Some(AxisSource::Finger);
+        let wheel_source = Some(AxisSource::Wheel);
+
+        // The compositor already applied the desktop setting.
+        let mut desktop = settings(ScrollDirection::Desktop, 1.0);
+        assert!(desktop.apply(SctkScrollChange::TouchpadNaturalScroll(true)));
+        assert!(!desktop.apply(SctkScrollChange::TouchpadNaturalScroll(true)));
+        assert_eq!(desktop.delta(&touchpad(5.0), finger), 5.0);
+        assert_eq!(desktop.delta(&wheel(1), wheel_source), 53.0);
+        assert!(desktop.effective().touchpad_natural_scroll);
+        assert!(!desktop.effective().mouse_natural_scroll);
+
+        let mut natural = settings(ScrollDirection::Natural, 1.0);
+        assert_eq!(natural.delta(&touchpad(5.0), finger), -5.0);
+        assert_eq!(natural.delta(&wheel(1), wheel_source), -53.0);
+        natural.apply(SctkScrollChange::TouchpadNaturalScroll(true));
+        assert_eq!(natural.delta(&touchpad(5.0), finger), 5.0);
+        assert_eq!(natural.delta(&wheel(1), wheel_source), -53.0);
+
+        let mut traditional = settings(ScrollDirection::Traditional, 1.0);
+        assert_eq!(traditional.delta(&touchpad(5.0), finger), 5.0);
+        traditional.apply(SctkScrollChange::MouseNaturalScroll(true));
+        assert_eq!(traditional.delta(&wheel(1), wheel_source), -53.0);
+        assert!(!traditional.effective().mouse_natural_scroll);
+    }
+
+    #[test]
+    fn factor_scales_every_source() {
+        for factor in [0.5, 1.0, 2.0, 3.5] {
+            let settings = settings(ScrollDirection::Desktop, factor);
+            let wheel_delta = settings.delta(&wheel(1), Some(AxisSource::Wheel));
+            let touchpad_delta = settings.delta(&touchpad(-8.0), Some(AxisSource::Finger));
+
+            assert_eq!(wheel_delta, 53.0 * factor);
+            assert_eq!(touchpad_delta, -8.0 * factor);
+            assert_eq!(settings.effective().factor, factor);
+        }
+
+        let inverted = settings(ScrollDirection::Natural, 2.0);
+        assert_eq!(inverted.delta(&wheel(-1), Some(AxisSource::Wheel)), 106.0);
+    }
+}
@@ -0,0 +1,326 @@
+//! Ordered teardown of the application.
+//!
+//! Each subsystem registers its cleanup for one of the [`ShutdownPhase`]s
+//! rather than relying on the drop order. The phases always run in the same
+//! order, each of them being bounded by a timeout, and the sequence runs at
+//! most once whichever path triggered it (e.g.: the app quitting, the
+//! compositor closing the window, or a fatal error).
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tracing::{trace, warn};
+
+/// Steps of the shutdown sequence, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ShutdownPhase {
+    /// Notify Dart that the app is detached, and wait for the acknowledgment.
+    NotifyDetached,
+    /// Stop handling the channel messages and detach the plugins.
+    DetachPlugins,
+    /// Remove the views from the engine.
+    RemoveViews,
+    ShutdownEngine,
+    /// Destroy the windows, along with their GL contexts and surfaces.
+    DestroyContexts,
+    DisconnectWayland,
+}
+
+impl ShutdownPhase {
+    pub const ALL: [ShutdownPhase; 6] = [
+        ShutdownPhase::NotifyDetached,
+        ShutdownPhase::DetachPlugins,
+        ShutdownPhase::RemoveViews,
+        ShutdownPhase::ShutdownEngine,
+        ShutdownPhase::DestroyContexts,
+        ShutdownPhase::DisconnectWayland,
+    ];
+
+    fn default_timeout(self) -> Duration {
+        match self {
+            // Dart may be busy (or paused in a debugger), so don't delay the
+            // exit for too long.
+            ShutdownPhase::NotifyDetached => Duration::from_millis(500),
+            _ => Duration::from_secs(1),
+        }
+    }
+}
+
+/// Progress of a polled shutdown task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownPoll {
+    Ready,
+    Pending,
+}
+
+/// Outcome of a shutdown phase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownPhaseReport {
+    pub phase: ShutdownPhase,
+    /// Tasks which were still pending when the phase timed out.
+    pub timed_out_tasks: Vec<&'static str>,
+    pub elapsed: Duration,
+}
+
+type ShutdownTask<T> = Box<dyn FnMut(&mut T) -> ShutdownPoll>;
+
+struct RegisteredTask<T> {
+    phase: ShutdownPhase,
+    name: &'static str,
+    task: ShutdownTask<T>,
+}
+
+/// Runs the shutdown tasks registered for the |T| subsystems, phase by phase.
+///
+/// Tasks must not block: the ones waiting for something (e.g.: a reply from
+/// Dart) are polled instead, pumping the event loop in between, until they
+/// are ready or their phase times out.
+pub struct ShutdownCoordinator<T> {
+    tasks: Vec<RegisteredTask<T>>,
+    timeouts: HashMap<ShutdownPhase, Duration>,
+    reports: Option<Vec<ShutdownPhaseReport>>,
+}
+
+impl<T> Default for ShutdownCoordinator<T> {
+    fn default() -> Self {
+        Self {
+            tasks: Vec::new(),
+            timeouts: HashMap::new(),
+            reports: None,
+        }
+    }
+}
+
+impl<T> ShutdownCoordinator<T> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn timeout(&self, phase: ShutdownPhase) -> Duration {
+        self.timeouts
+            .get(&phase)
+            .copied()
+            .unwrap_or_else(|| phase.default_timeout())
+    }
+
+    pub fn set_timeout(&mut self, phase: ShutdownPhase, timeout: Duration) {
+        self.timeouts.insert(phase, timeout);
+    }
+
+    /// Run |task| once during |phase|. Tasks of the same phase run in the
+    /// order they were registered.
+    pub fn register<F>(&mut self, phase: ShutdownPhase, name: &'static str, task: F)
+    where
+        F: FnOnce(&mut T) + 'static,
+    {
+        let mut task = Some(task);
+        self.register_polled(phase, name, move |target| {
+            if let Some(task) = task.take() {
+                task(target);
+            }
+            ShutdownPoll::Ready
+        });
+    }
+
+    /// Poll |task| during |phase| until it is ready, or until the phase times
+    /// out.
+    pub fn register_polled<F>(&mut self, phase: ShutdownPhase, name: &'static str, task: F)
+    where
+        F: FnMut(&mut T) -> ShutdownPoll + 'static,
+    {
+        if self.is_shut_down() {
+            warn!("Ignoring the {name} shutdown task registered after the shutdown");
+            return;
+        }
+
+        self.tasks.push(RegisteredTask {
+            phase,
+            name,
+            task: Box::new(task),
+        });
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.reports.is_some()
+    }
+
+    /// Run all the phases in order. While tasks are pending, |pump| is called
+    /// with the time left before the phase times out.
+    ///
+    /// Only the first call runs the tasks, the next ones return the same
+    /// reports.
+    pub fn shutdown<P>(&mut self, target: &mut T, mut pump: P) -> &[ShutdownPhaseReport]
+    where
+        P: FnMut(&mut T, Duration),
+    {
+        if self.reports.is_none() {
+            let mut tasks = std::mem::take(&mut self.tasks);
+            let mut reports = Vec::with_capacity(ShutdownPhase::ALL.len());
+
+            for phase in ShutdownPhase::ALL {
+                let (phase_tasks, other_tasks) =
+                    tasks.into_iter().partition(|task| task.phase == phase);
+                tasks = other_tasks;
+                reports.push(self.run_phase(phase, phase_tasks, target, &mut pump));
+            }
+
+            self.reports = Some(reports);
+        }
+
+        self.reports.as_deref().unwrap_or_default()
+    }
+
+    fn run_phase<P>(
+        &self,
+        phase: ShutdownPhase,
+        mut tasks: Vec<RegisteredTask<T>>,
+        target: &mut T,
+        pump: &mut P,
+    ) -> ShutdownPhaseReport
+    where
+        P: FnMut(&mut T, Duration),
+    {
+        trace!("Running the {phase:?} shutdown phase");
+        let start = Instant::now();
+        let deadline = start + self.timeout(phase);
+
+        loop {
+            tasks.retain_mut(|task| (task.task)(target) == ShutdownPoll::Pending);
+
+            let now = Instant::now();
+            if tasks.is_empty() || now >= deadline {
+                break;
+            }
+            pump(target, deadline - now);
+        }
+
+        let timed_out_tasks: Vec<_> = tasks.iter().map(|task| task.name).collect();
+        if !timed_out_tasks.is_empty() {
+            warn!("The {phase:?} shutdown phase timed out: {timed_out_tasks:?}");
+        }
+
+        ShutdownPhaseReport {
+            phase,
+            timed_out_tasks,
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ShutdownCoordinator, ShutdownPhase, ShutdownPoll};
+
+    /// Stands in for the application state: records what happens to the
+    /// subsystems, and answers the detached notification after a few pumps.
+    #[derive(Default)]
+    struct Harness {
+        log: Vec<String>,
+        pumps: usize,
+        is_engine_running: bool,
+    }
+
+    impl Harness {
+        fn running() -> Self {
+            Self {
+                is_engine_running: true,
+                ..Default::default()
+            }
+        }
+    }
+
+    fn coordinator() -> ShutdownCoordinator<Harness> {
+        let mut coordinator = ShutdownCoordinator::<Harness>::new();
+
+        // Registered in a different order than the phases run.
+        coordinator.register(ShutdownPhase::DisconnectWayland, "wayland", |harness| {
+            harness.log.push("wayland".into())
+        });
+        coordinator.register(ShutdownPhase::ShutdownEngine, "engine", |harness| {
+            harness.is_engine_running = false;
+            harness.log.push("engine".into());
+        });
+        coordinator.register(ShutdownPhase::DestroyContexts, "windows", |harness| {
+            assert!(!harness.is_engine_running);
+            harness.log.push("contexts".into());
+        });
+        coordinator.register(ShutdownPhase::RemoveViews, "views", |harness| {
+            assert!(harness.is_engine_running);
+            harness.log.push("views".into());
+        });
+        coordinator.register(ShutdownPhase::DetachPlugins, "plugins", |harness| {
+            harness.log.push("plugins".into())
+        });
+        coordinator.register_polled(ShutdownPhase::NotifyDetached, "lifecycle", |harness| {
+            if harness.pumps < 3 {
+                return ShutdownPoll::Pending;
+            }
+            harness.log.push("detached".into());
+            ShutdownPoll::Ready
+        });
+
+        coordinator
+    }
+
+    fn pump(harness: &mut Harness, _timeout: Duration) {
+        harness.pumps += 1;
+    }
+
+    #[test]
+    fn runs_phases_in_order() {
+        let mut coordinator = coordinator();
+        let mut harness = Harness::running();
+
+        let reports = coordinator.shutdown(&mut harness, pump);
+
+        let phases: Vec<_> = reports.iter().map(|report| report.phase).collect();
+        assert_eq!(phases, ShutdownPhase::ALL);
+        assert!(reports.iter().all(|report| report.timed_out_tasks.is_empty()));
+        assert_eq!(
+            harness.log,
+            ["detached", "plugins", "views", "engine", "contexts", "wayland"]
+        );
+        assert_eq!(harness.pumps, 3);
+    }
+
+    #[test]
+    fn hanging_phase_times_out() {
+        let mut coordinator = coordinator();
+        coordinator.set_timeout(ShutdownPhase::RemoveViews, Duration::from_millis(20));
+        coordinator.register_polled(ShutdownPhase::RemoveViews, "hanging", |_| {
+            ShutdownPoll::Pending
+        });
+
+        let mut harness = Harness::running();
+        let reports = coordinator.shutdown(&mut harness, pump);
+
+        let report = &reports[2];
+        assert_eq!(report.phase, ShutdownPhase::RemoveViews);
+        assert_eq!(report.timed_out_tasks, ["hanging"]);
+        assert!(report.elapsed >= Duration::from_millis(20));
+
+        // The next phases still run.
+        assert_eq!(harness.log[3..], ["engine", "contexts", "wayland"]);
+    }
+
+    #[test]
+    fn double_shutdown_is_idempotent() {
+        let mut coordinator = coordinator();
+        let mut harness = Harness::running();
+
+        let first_reports = coordinator.shutdown(&mut harness, pump).to_vec();
+        let log = harness.log.clone();
+        let second_reports = coordinator.shutdown(&mut harness, pump).to_vec();
+
+        assert!(coordinator.is_shut_down());
+        assert_eq!(first_reports, second_reports);
+        assert_eq!(harness.log, log);
+
+        // Tasks registered too late are ignored.
+        coordinator.register(ShutdownPhase::ShutdownEngine, "late", |_| unreachable!());
+        coordinator.shutdown(&mut harness, pump);
+    }
+}
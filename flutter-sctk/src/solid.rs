@@ -0,0 +1,270 @@
+//! Subsurfaces filled with a solid color, e.g.: the letterbox bars around the
+//! content of a fullscreen window kept within its maximum size.
+//!
+//! Their buffer is a single pixel stretched by `wp_viewport`. It comes from
+//! `wp_single_pixel_buffer_manager_v1` when the compositor supports it, which
+//! doesn't allocate any memory, and from a 1x1 shm buffer otherwise.
+use dpi::LogicalSize;
+use smithay_client_toolkit::{
+    compositor::CompositorState,
+    reexports::protocols::wp::{
+        single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1,
+        viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
+    },
+    shm::{raw::RawPool, Shm},
+};
+use tracing::{trace, warn};
+use wayland_client::{
+    globals::GlobalList,
+    protocol::{
+        wl_buffer::WlBuffer, wl_compositor::WlCompositor, wl_shm,
+        wl_subcompositor::WlSubcompositor, wl_subsurface::WlSubsurface, wl_surface::WlSurface,
+    },
+    QueueHandle,
+};
+
+use crate::application::SctkApplicationState;
+
+/// Globals required for showing solid-color subsurfaces.
+#[derive(Debug)]
+pub(crate) struct SctkSolidColorGlobals {
+    compositor: WlCompositor,
+    subcompositor: WlSubcompositor,
+    viewporter: WpViewporter,
+    /// `None` when the compositor doesn't support single-pixel buffers, in
+    /// which case shm buffers are used.
+    single_pixel_buffer_manager: Option<WpSinglePixelBufferManagerV1>,
+    shm: Shm,
+}
+
+impl SctkSolidColorGlobals {
+    /// Returns `None` if the compositor doesn't support subsurfaces or
+    /// viewports.
+    pub(crate) fn bind(
+        globals: &GlobalList,
+        qh: &QueueHandle<SctkApplicationState>,
+        compositor_state: &CompositorState,
+    ) -> Option<Self> {
+        let single_pixel_buffer_manager = globals.bind(qh, 1..=1, ()).ok();
+        if single_pixel_buffer_manager.is_none() {
+            trace!("Single-pixel buffers are not supported, using shm buffers instead");
+        }
+
+        Some(Self {
+            compositor: compositor_state.wl_compositor().clone(),
+            subcompositor: globals.bind(qh, 1..=1, ()).ok()?,
+            viewporter: globals.bind(qh, 1..=1, ()).ok()?,
+            single_pixel_buffer_manager,
+            shm: Shm::bind(globals, qh).ok()?,
+        })
+    }
+
+    /// A buffer made of a single pixel of the |argb| color.
+    fn create_buffer(&self, qh: &QueueHandle<SctkApplicationState>, argb: u32) -> Option<WlBuffer> {
+        let [alpha, red, green, blue] = premultiplied(argb);
+
+        if let Some(manager) = &self.single_pixel_buffer_manager {
+            // The channels range over the whole `u32` values.
+            let channel = |value: u8| value as u32 * 0x0101_0101;
+            return Some(manager.create_u32_rgba_buffer(
+                channel(red),
+                channel(green),
+                channel(blue),
+                channel(alpha),
+                qh,
+                (),
+            ));
+        }
+
+        let mut pool = match RawPool::new(4, &self.shm) {
+            Ok(pool) => pool,
+            Err(err) => {
+                warn!("Failed to create the pool of a solid-color buffer: {}", err);
+                return None;
+            }
+        };
+        // `Argb8888` pixels are stored in little-endian order.
+        pool.mmap()
+            .copy_from_slice(&u32::from_be_bytes([alpha, red, green, blue]).to_le_bytes());
+
+        // The buffer outlives the pool, which is destroyed when dropped.
+        Some(pool.create_buffer(0, 1, 1, 4, wl_shm::Format::Argb8888, (), qh))
+    }
+}
+
+/// A subsurface filled with a solid color, placed below its parent. It
+/// doesn't take any input.
+struct SolidColorSurface {
+    surface: WlSurface,
+    subsurface: WlSubsurface,
+    viewport: WpViewport,
+    /// The attached buffer, and its color.
+    buffer: Option<(u32, WlBuffer)>,
+    position: Option<(i32, i32)>,
+    destination: Option<(i32, i32)>,
+}
+
+impl SolidColorSurface {
+    fn new(
+        globals: &SctkSolidColorGlobals,
+        parent: &WlSurface,
+        qh: &QueueHandle<SctkApplicationState>,
+    ) -> Self {
+        let surface = globals.compositor.create_surface(qh, ());
+        let subsurface = globals
+            .subcompositor
+            .get_subsurface(&surface, parent, qh, ());
+        let viewport = globals.viewporter.get_viewport(&surface, qh, ());
+
+        subsurface.place_below(parent);
+
+        let input_region = globals.compositor.create_region(qh, ());
+        surface.set_input_region(Some(&input_region));
+        input_region.destroy();
+
+        Self {
+            surface,
+            subsurface,
+            viewport,
+            buffer: None,
+            position: None,
+            destination: None,
+        }
+    }
+
+    /// Fill the logical rect at |position| of the parent surface with the
+    /// |argb| color. The changes are applied with the next commit of the
+    /// parent surface.
+    fn show(
+        &mut self,
+        globals: &SctkSolidColorGlobals,
+        qh: &QueueHandle<SctkApplicationState>,
+        argb: u32,
+        position: (i32, i32),
+        size: (i32, i32),
+    ) {
+        if self.position != Some(position) {
+            self.subsurface.set_position(position.0, position.1);
+            self.position = Some(position);
+        }
+
+        let mut needs_commit = false;
+        if self.destination != Some(size) {
+            self.viewport.set_destination(size.0, size.1);
+            self.destination = Some(size);
+            needs_commit = true;
+        }
+
+        if self.buffer.as_ref().map(|(color, _)| *color) != Some(argb) {
+            let Some(buffer) = globals.create_buffer(qh, argb) else {
+                return;
+            };
+            self.surface.attach(Some(&buffer), 0, 0);
+            self.surface.damage_buffer(0, 0, 1, 1);
+            if let Some((_, previous)) = self.buffer.replace((argb, buffer)) {
+                previous.destroy();
+            }
+            needs_commit = true;
+        }
+
+        // The subsurface is synchronized, so this commit only takes effect
+        // along with the parent surface.
+        if needs_commit {
+            self.surface.commit();
+        }
+    }
+
+    fn hide(&mut self) {
+        if let Some((_, buffer)) = self.buffer.take() {
+            self.surface.attach(None, 0, 0);
+            self.surface.commit();
+            buffer.destroy();
+        }
+    }
+}
+
+impl Drop for SolidColorSurface {
+    fn drop(&mut self) {
+        if let Some((_, buffer)) = self.buffer.take() {
+            buffer.destroy();
+        }
+        self.viewport.destroy();
+        self.subsurface.destroy();
+        self.surface.destroy();
+    }
+}
+
+/// Bars of a solid color around content which doesn't fill the area of its
+/// window, e.g.: a fullscreen window kept within its maximum size. Without
+/// the required globals, the compositor fills that area instead.
+pub(crate) struct SctkLetterbox {
+    globals: Option<SctkSolidColorGlobals>,
+    bars: Option<SolidColorSurface>,
+}
+
+impl SctkLetterbox {
+    pub(crate) fn new(globals: Option<SctkSolidColorGlobals>) -> Self {
+        Self {
+            globals,
+            bars: None,
+        }
+    }
+
+    /// Center |content| in |area| (both logical sizes) of the |parent|
+    /// surface, with bars of the |argb| color around it. The bars are hidden
+    /// when the content fills the area.
+    pub(crate) fn update(
+        &mut self,
+        parent: &WlSurface,
+        qh: &QueueHandle<SctkApplicationState>,
+        area: LogicalSize<u32>,
+        content: LogicalSize<u32>,
+        argb: u32,
+    ) {
+        let Some(globals) = &self.globals else {
+            return;
+        };
+
+        if content == area {
+            if let Some(bars) = &mut self.bars {
+                bars.hide();
+            }
+            return;
+        }
+
+        // The bars fill the whole area below the content, whose origin is
+        // the one of the parent surface.
+        let position = (
+            -(area.width.saturating_sub(content.width) as i32 / 2),
+            -(area.height.saturating_sub(content.height) as i32 / 2),
+        );
+        let size = (area.width as i32, area.height as i32);
+        self.bars
+            .get_or_insert_with(|| SolidColorSurface::new(globals, parent, qh))
+            .show(globals, qh, argb, position, size);
+    }
+}
+
+/// The channels of an |argb| color, premultiplied by its alpha.
+fn premultiplied(argb: u32) -> [u8; 4] {
+    let [alpha, red, green, blue] = argb.to_be_bytes();
+    let premultiply = |channel: u8| ((channel as u32 * alpha as u32 + 127) / 255) as u8;
+    [
+        alpha,
+        premultiply(red),
+        premultiply(green),
+        premultiply(blue),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::premultiplied;
+
+    #[test]
+    fn premultiplies_the_channels() {
+        assert_eq!(premultiplied(0xff20_4080), [0xff, 0x20, 0x40, 0x80]);
+        assert_eq!(premultiplied(0x80ff_8000), [0x80, 0x80, 0x40, 0x00]);
+        assert_eq!(premultiplied(0x00ff_ffff), [0; 4]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Stacking order of the windows of the application, which Wayland doesn't
+//! expose: clients can neither query it nor restack their toplevels, beyond
+//! asking the compositor to activate (and thus raise) one of them through
+//! `xdg_activation_v1`, which it may refuse.
+//!
+//! The order is therefore tracked from what the application knows: new
+//! windows are mapped on top, and activated windows are raised above the
+//! others. Attached windows (see `WindowRole::Utility`) are subsurfaces of
+//! their parent, which the application restacks itself, always above the
+//! parent.
+use std::collections::BTreeMap;
+
+use crate::engine::SctkEngineId;
+
+/// How to stack a window above or below another one, see
+/// [`SctkWindowStack::plan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SctkRestack {
+    /// The windows are already stacked as requested.
+    Unchanged,
+    /// Activate the toplevel, which raises it (along with its attached
+    /// windows) above the others.
+    Activate(SctkEngineId),
+    /// Place the attached |window| above its |sibling|, attached to the same
+    /// parent.
+    PlaceAbove {
+        window: SctkEngineId,
+        sibling: SctkEngineId,
+    },
+    /// Place the attached |window| below its |sibling|, attached to the same
+    /// parent.
+    PlaceBelow {
+        window: SctkEngineId,
+        sibling: SctkEngineId,
+    },
+    /// The windows can't be stacked as requested (e.g.: an attached window
+    /// below its parent).
+    Unsupported,
+}
+
+/// The windows of the application (one per engine), topmost first.
+#[derive(Debug)]
+pub(crate) struct SctkWindowStack {
+    /// Windows which aren't attached to another one, topmost first.
+    toplevels: Vec<SctkEngineId>,
+    /// Windows attached to each toplevel, topmost first.
+    attached: BTreeMap<SctkEngineId, Vec<SctkEngineId>>,
+}
+
+impl SctkWindowStack {
+    pub(crate) fn new(primary: SctkEngineId) -> Self {
+        Self {
+            toplevels: vec![primary],
+            attached: BTreeMap::new(),
+        }
+    }
+
+    /// New toplevels are mapped above the others.
+    pub(crate) fn add_toplevel(&mut self, id: SctkEngineId) {
+        self.toplevels.insert(0, id);
+    }
+
+    /// New subsurfaces are stacked above their parent and siblings.
+    pub(crate) fn add_attached(&mut self, id: SctkEngineId, parent: SctkEngineId) {
+        self.attached.entry(parent).or_default().insert(0, id);
+    }
+
+    /// Returns the windows which were attached to |id|.
+    pub(crate) fn remove(&mut self, id: SctkEngineId) -> Vec<SctkEngineId> {
+        self.toplevels.retain(|&toplevel| toplevel != id);
+        for siblings in self.attached.values_mut() {
+            siblings.retain(|&sibling| sibling != id);
+        }
+        self.attached.remove(&id).unwrap_or_default()
+    }
+
+    /// The window |id| is attached to, if any.
+    pub(crate) fn parent(&self, id: SctkEngineId) -> Option<SctkEngineId> {
+        self.attached
+            .iter()
+            .find(|(_, siblings)| siblings.contains(&id))
+            .map(|(&parent, _)| parent)
+    }
+
+    pub(crate) fn contains(&self, id: SctkEngineId) -> bool {
+        self.toplevels.contains(&id) || self.parent(id).is_some()
+    }
+
+    /// Identifiers of the windows, topmost first. Attached windows come
+    /// right before their parent.
+    pub(crate) fn order(&self) -> Vec<u64> {
+        let mut order = Vec::new();
+        for toplevel in &self.toplevels {
+            if let Some(siblings) = self.attached.get(toplevel) {
+                order.extend(siblings.iter().map(|sibling| sibling.as_u64()));
+            }
+            order.push(toplevel.as_u64());
+        }
+        order
+    }
+
+    /// The toplevel |id| (or the one it is attached to) was activated, and
+    /// thus raised. Returns whether the order changed.
+    pub(crate) fn activated(&mut self, id: SctkEngineId) -> bool {
+        let root = self.root(id);
+        match self.toplevels.iter().position(|&toplevel| toplevel == root) {
+            Some(0) | None => false,
+            Some(index) => {
+                self.toplevels.remove(index);
+                self.toplevels.insert(0, root);
+                true
+            }
+        }
+    }
+
+    /// How to stack |window| directly above (or below, unless |above|) the
+    /// |sibling| window.
+    ///
+    /// Toplevels can only be raised, so lowering a window below another one
+    /// raises the other one instead.
+    pub(crate) fn plan(
+        &self,
+        window: SctkEngineId,
+        sibling: SctkEngineId,
+        above: bool,
+    ) -> SctkRestack {
+        if window == sibling || !self.contains(window) || !self.contains(sibling) {
+            return SctkRestack::Unsupported;
+        }
+
+        let window_root = self.root(window);
+        let sibling_root = self.root(sibling);
+        if window_root != sibling_root {
+            let is_above = self.toplevel_index(window_root) < self.toplevel_index(sibling_root);
+            return match (above, is_above) {
+                (true, true) | (false, false) => SctkRestack::Unchanged,
+                (true, false) => SctkRestack::Activate(window_root),
+                (false, true) => SctkRestack::Activate(sibling_root),
+            };
+        }
+
+        // Attached windows are always above their parent.
+        if sibling == sibling_root || window == window_root {
+            let is_above = sibling == sibling_root;
+            return if above == is_above {
+                SctkRestack::Unchanged
+            } else {
+                SctkRestack::Unsupported
+            };
+        }
+
+        let siblings = &self.attached[&window_root];
+        let index = |id| siblings.iter().position(|&sibling| sibling == id);
+        let is_above = index(window) < index(sibling);
+        match (above, is_above) {
+            (true, true) | (false, false) => SctkRestack::Unchanged,
+            (true, false) => SctkRestack::PlaceAbove { window, sibling },
+            (false, true) => SctkRestack::PlaceBelow { window, sibling },
+        }
+    }
+
+    /// Move the attached |window| next to its |sibling|, once placed above
+    /// (or below, unless |above|) it.
+    pub(crate) fn place(&mut self, window: SctkEngineId, sibling: SctkEngineId, above: bool) {
+        let Some(siblings) = self
+            .parent(window)
+            .and_then(|parent| self.attached.get_mut(&parent))
+        else {
+            return;
+        };
+
+        siblings.retain(|&id| id != window);
+        let Some(index) = siblings.iter().position(|&id| id == sibling) else {
+            siblings.insert(0, window);
+            return;
+        };
+        siblings.insert(if above { index } else { index + 1 }, window);
+    }
+
+    /// The toplevel |id| is attached to, or |id| itself.
+    fn root(&self, id: SctkEngineId) -> SctkEngineId {
+        self.parent(id).unwrap_or(id)
+    }
+
+    fn toplevel_index(&self, id: SctkEngineId) -> Option<usize> {
+        self.toplevels.iter().position(|&toplevel| toplevel == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SctkRestack, SctkWindowStack};
+    use crate::engine::SctkEngineId;
+
+    fn id(id: u64) -> SctkEngineId {
+        SctkEngineId::from_u64(id)
+    }
+
+    /// Canvases 0 and 1, and palettes 2 and 3 attached to the canvas 0.
+    fn stack() -> SctkWindowStack {
+        let mut stack = SctkWindowStack::new(id(0));
+        stack.add_toplevel(id(1));
+        stack.add_attached(id(2), id(0));
+        stack.add_attached(id(3), id(0));
+        stack
+    }
+
+    #[test]
+    fn attached_windows_are_stacked_above_their_parent() {
+        let mut stack = stack();
+        assert_eq!(stack.order(), [1, 3, 2, 0]);
+
+        assert!(stack.activated(id(2)));
+        assert_eq!(stack.order(), [3, 2, 0, 1]);
+        assert!(!stack.activated(id(0)));
+
+        assert_eq!(stack.remove(id(0)), [id(3), id(2)]);
+        assert_eq!(stack.order(), [1]);
+    }
+
+    #[test]
+    fn toplevels_are_raised_by_activating_them() {
+        let stack = stack();
+        assert_eq!(stack.plan(id(0), id(1), true), SctkRestack::Activate(id(0)));
+        assert_eq!(stack.plan(id(2), id(1), true), SctkRestack::Activate(id(0)));
+        assert_eq!(stack.plan(id(1), id(0), true), SctkRestack::Unchanged);
+        assert_eq!(
+            stack.plan(id(1), id(2), false),
+            SctkRestack::Activate(id(0))
+        );
+        assert_eq!(stack.plan(id(0), id(1), false), SctkRestack::Unchanged);
+    }
+
+    #[test]
+    fn attached_windows_are_restacked_among_their_siblings() {
+        let mut stack = stack();
+        assert_eq!(stack.plan(id(3), id(2), true), SctkRestack::Unchanged);
+        assert_eq!(
+            stack.plan(id(2), id(3), true),
+            SctkRestack::PlaceAbove {
+                window: id(2),
+                sibling: id(3),
+            }
+        );
+
+        stack.place(id(2), id(3), true);
+        assert_eq!(stack.order(), [1, 2, 3, 0]);
+        stack.place(id(2), id(3), false);
+        assert_eq!(stack.order(), [1, 3, 2, 0]);
+
+        assert_eq!(stack.plan(id(2), id(0), true), SctkRestack::Unchanged);
+        assert_eq!(stack.plan(id(2), id(0), false), SctkRestack::Unsupported);
+        assert_eq!(stack.plan(id(0), id(2), true), SctkRestack::Unsupported);
+        assert_eq!(stack.plan(id(0), id(0), true), SctkRestack::Unsupported);
+        assert_eq!(stack.plan(id(0), id(7), true), SctkRestack::Unsupported);
+    }
+}
@@ -0,0 +1,291 @@
+//! Input method support using the `zwp_text_input_v3` protocol. The
+//! compositor is told about the focused text field (i.e.: its content type and
+//! caret position) so that input method popups and on-screen keyboards can be
+//! configured and positioned accordingly. Text committed by the input method
+//! is inserted through the regular editing state updates, and the text being
+//! composed is shown as the composing region until then.
+use std::{collections::HashMap, sync::Mutex};
+
+use flutter_input::keyboard::{commit_text, compose_text};
+use flutter_plugins::textinput::{TextInputFocusInfo, TextInputPlugin, TextInputRect};
+use smithay_client_toolkit::reexports::protocols::wp::text_input::zv3::client::{
+    zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+    zwp_text_input_v3::{ContentHint, ContentPurpose, ZwpTextInputV3},
+};
+use wayland_backend::client::ObjectId;
+use wayland_client::{
+    protocol::{wl_seat::WlSeat, wl_surface::WlSurface},
+    Proxy, QueueHandle,
+};
+
+use crate::application::SctkApplicationState;
+
+pub(crate) struct SctkTextInput {
+    manager: ZwpTextInputManagerV3,
+    /// Text inputs of the seats with a keyboard.
+    text_inputs: HashMap<ObjectId, ZwpTextInputV3>,
+    is_enabled: bool,
+    focus_info: TextInputFocusInfo,
+    /// Ratio between the surface-local coordinates and the logical ones of
+    /// the view (e.g.: when the content is zoomed).
+    content_scale: f64,
+}
+
+impl SctkTextInput {
+    pub(crate) fn new(manager: ZwpTextInputManagerV3) -> Self {
+        Self {
+            manager,
+            text_inputs: Default::default(),
+            is_enabled: false,
+            focus_info: Default::default(),
+            content_scale: 1.0,
+        }
+    }
+
+    pub(crate) fn add_seat(&mut self, seat: &WlSeat, qh: &QueueHandle<SctkApplicationState>) {
+        let text_input = self
+            .manager
+            .get_text_input(seat, qh, SctkTextInputData::default());
+        self.text_inputs.insert(seat.id(), text_input);
+    }
+
+    pub(crate) fn remove_seat(&mut self, seat: &WlSeat) {
+        if let Some(text_input) = self.text_inputs.remove(&seat.id()) {
+            text_input.destroy();
+        }
+    }
+
+    /// Enable the input method while a text field is being edited (i.e.:
+    /// between the `show` and `hide` requests of the framework).
+    pub(crate) fn set_enabled(&mut self, is_enabled: bool) {
+        if self.is_enabled == is_enabled {
+            return;
+        }
+        self.is_enabled = is_enabled;
+
+        for text_input in self.text_inputs.values() {
+            if has_focus(text_input) {
+                self.apply(text_input);
+            }
+        }
+    }
+
+    pub(crate) fn set_focus_info(&mut self, focus_info: TextInputFocusInfo) {
+        self.focus_info = focus_info;
+        self.apply_to_focused();
+    }
+
+    pub(crate) fn set_content_scale(&mut self, content_scale: f64) {
+        if self.content_scale == content_scale {
+            return;
+        }
+        self.content_scale = content_scale;
+        self.apply_to_focused();
+    }
+
+    fn apply_to_focused(&self) {
+        if !self.is_enabled {
+            return;
+        }
+
+        for text_input in self.text_inputs.values() {
+            if has_focus(text_input) {
+                self.apply(text_input);
+            }
+        }
+    }
+
+    /// Send the current state to a text input which has just received the
+    /// focus. The state is reset by the compositor whenever the focus changes.
+    pub(crate) fn enter(&self, text_input: &ZwpTextInputV3) {
+        if self.is_enabled {
+            self.apply(text_input);
+        }
+    }
+
+    fn apply(&self, text_input: &ZwpTextInputV3) {
+        if !self.is_enabled {
+            text_input.disable();
+            text_input.commit();
+            return;
+        }
+
+        let (hint, purpose) = content_type(&self.focus_info);
+
+        text_input.enable();
+        text_input.set_content_type(hint, purpose);
+
+        // The logical coordinates of the view match the surface-local ones,
+        // unless the content is zoomed.
+        let rect = self.focus_info.caret_rect.or(self.focus_info.editable_rect);
+        if let Some(TextInputRect {
+            x,
+            y,
+            width,
+            height,
+        }) = rect
+        {
+            let scale = self.content_scale;
+            text_input.set_cursor_rectangle(
+                (x * scale).round() as i32,
+                (y * scale).round() as i32,
+                (width * scale).round() as i32,
+                (height * scale).round() as i32,
+            );
+        }
+
+        text_input.commit();
+    }
+}
+
+fn has_focus(text_input: &ZwpTextInputV3) -> bool {
+    text_input
+        .data::<SctkTextInputData>()
+        .is_some_and(|data| data.surface.lock().unwrap().is_some())
+}
+
+fn content_type(focus_info: &TextInputFocusInfo) -> (ContentHint, ContentPurpose) {
+    if focus_info.is_password() {
+        return (
+            ContentHint::SensitiveData | ContentHint::HiddenText,
+            ContentPurpose::Password,
+        );
+    }
+
+    let purpose = if focus_info.has_hint("email") {
+        ContentPurpose::Email
+    } else if focus_info.has_hint("telephoneNumber") {
+        ContentPurpose::Phone
+    } else if focus_info.has_hint("url") {
+        ContentPurpose::Url
+    } else if focus_info.has_hint("name") {
+        ContentPurpose::Name
+    } else {
+        ContentPurpose::Normal
+    };
+
+    (ContentHint::None, purpose)
+}
+
+/// User data of a `zwp_text_input_v3` object.
+#[derive(Default)]
+pub(crate) struct SctkTextInputData {
+    /// Surface which has the text input focus.
+    pub(crate) surface: Mutex<Option<WlSurface>>,
+    /// Text committed by the input method, applied on the next `done` event.
+    pending_commit: Mutex<Option<String>>,
+    /// Text being composed, replacing the previous one on the next `done`
+    /// event (which removes it when there is none).
+    pending_preedit: Mutex<Option<String>>,
+}
+
+impl SctkTextInputData {
+    pub(crate) fn commit_string(&self, text: Option<String>) {
+        *self.pending_commit.lock().unwrap() = text;
+    }
+
+    pub(crate) fn preedit_string(&self, text: Option<String>) {
+        *self.pending_preedit.lock().unwrap() = text;
+    }
+
+    /// Take the pending state on the `done` event.
+    pub(crate) fn done(&self) -> SctkTextInputUpdate {
+        SctkTextInputUpdate {
+            commit: self.pending_commit.lock().unwrap().take(),
+            preedit: self
+                .pending_preedit
+                .lock()
+                .unwrap()
+                .take()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// State of the input method applied by a `done` event.
+pub(crate) struct SctkTextInputUpdate {
+    commit: Option<String>,
+    preedit: String,
+}
+
+impl SctkTextInputUpdate {
+    /// Replace the text being composed with the committed one, followed by
+    /// the new text being composed (in the order of the protocol).
+    pub(crate) fn apply(&self, text_input: &mut TextInputPlugin) {
+        if let Some(text) = &self.commit {
+            commit_text(text_input, text);
+        }
+        compose_text(text_input, &self.preedit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_input::keyboard::edit_text;
+    use flutter_plugins::textinput::{
+        conformance::{self, ConformanceBackend, ConformanceKey},
+        TextInputFocusInfo, TextInputPlugin, AUTOFILL_HINT_USERNAME,
+    };
+    use smithay_client_toolkit::seat::keyboard::Keysym;
+
+    use super::{content_type, ContentHint, ContentPurpose, SctkTextInputData};
+
+    /// The input path of the application: the keys which fall through to the
+    /// text being edited, and the events of the `zwp_text_input_v3` object.
+    #[derive(Default)]
+    struct SctkBackend {
+        data: SctkTextInputData,
+    }
+
+    impl SctkBackend {
+        fn done(&self, text_input: &mut TextInputPlugin) {
+            self.data.done().apply(text_input);
+        }
+    }
+
+    impl ConformanceBackend for SctkBackend {
+        fn key_pressed(&mut self, text_input: &mut TextInputPlugin, key: &ConformanceKey) {
+            edit_text(
+                text_input,
+                Keysym::new(key.keysym),
+                key.utf8.as_deref(),
+                key.shift,
+            );
+        }
+
+        fn preedit(&mut self, text_input: &mut TextInputPlugin, text: &str) {
+            self.data.preedit_string(Some(text.to_owned()));
+            self.done(text_input);
+        }
+
+        fn commit(&mut self, text_input: &mut TextInputPlugin, text: &str) {
+            self.data.commit_string(Some(text.to_owned()));
+            self.done(text_input);
+        }
+    }
+
+    #[test]
+    fn text_editing_conforms() {
+        if let Err(err) = conformance::check_all(&mut SctkBackend::default()) {
+            panic!("{}", err);
+        }
+    }
+
+    #[test]
+    fn content_type_follows_the_autofill_hints() {
+        let info = TextInputFocusInfo {
+            obscure_text: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            content_type(&info),
+            (ContentHint::SensitiveData | ContentHint::HiddenText, ContentPurpose::Password)
+        );
+
+        let info = TextInputFocusInfo {
+            hints: vec![AUTOFILL_HINT_USERNAME.into(), "email".into()],
+            ..Default::default()
+        };
+        assert_eq!(content_type(&info), (ContentHint::None, ContentPurpose::Email));
+    }
+}
@@ -0,0 +1,271 @@
+use flutter_plugins::{
+    anchor::{PopupAnchor, PopupConstraintAdjustment, PopupGravity, PopupPlacement},
+    text_toolbar::{TextToolbarAction, TextToolbarRect},
+};
+use smithay_client_toolkit::{
+    compositor::CompositorState,
+    reexports::protocols::xdg::shell::client::xdg_positioner::{
+        Anchor, ConstraintAdjustment, Gravity,
+    },
+    shell::xdg::{popup::Popup, window::Window, XdgPositioner, XdgShell, XdgSurface},
+    shm::{slot::SlotPool, Shm},
+};
+use thiserror::Error;
+use tracing::error;
+use wayland_client::{
+    protocol::{wl_seat::WlSeat, wl_shm, wl_surface::WlSurface},
+    QueueHandle,
+};
+
+use crate::{
+    application::SctkApplicationState,
+    bitmap_font::{self, Canvas, GLYPH_HEIGHT},
+    handler::CursorOverrideGuard,
+};
+
+/// Size (in logical pixels) of a glyph "pixel".
+const GLYPH_SCALE: u32 = 2;
+const BUTTON_PADDING_X: u32 = 12;
+const BUTTON_PADDING_Y: u32 = 9;
+const BUTTON_HEIGHT: u32 = GLYPH_HEIGHT * GLYPH_SCALE + 2 * BUTTON_PADDING_Y;
+const SEPARATOR_WIDTH: u32 = 1;
+
+const BACKGROUND_COLOR: u32 = 0xFF30_3030;
+const HOVER_COLOR: u32 = 0xFF50_5050;
+const SEPARATOR_COLOR: u32 = 0xFF5A_5A5A;
+const TEXT_COLOR: u32 = 0xFFFF_FFFF;
+
+#[derive(Error, Debug)]
+pub(crate) enum SctkTextToolbarError {
+    #[error(transparent)]
+    Global(#[from] smithay_client_toolkit::error::GlobalError),
+
+    #[error(transparent)]
+    CreatePool(#[from] smithay_client_toolkit::shm::CreatePoolError),
+
+    #[error(transparent)]
+    CreateBuffer(#[from] smithay_client_toolkit::shm::slot::CreateBufferError),
+
+    #[error(transparent)]
+    ActivateSlot(#[from] smithay_client_toolkit::shm::slot::ActivateSlotError),
+}
+
+/// Text selection toolbar presented as an `xdg_popup` anchored to the
+/// selection rect, so that it is not clipped by the window edges.
+///
+/// The buttons are rendered by the embedder using the built-in bitmap font
+/// (see `crate::bitmap_font`).
+pub(crate) struct SctkTextToolbar {
+    popup: Popup,
+    pool: SlotPool,
+    buttons: Vec<(TextToolbarAction, u32, u32)>,
+    width: u32,
+    scale: u32,
+    hovered: Option<usize>,
+    /// Held while the pointer is over the toolbar.
+    cursor_override: Option<CursorOverrideGuard>,
+}
+
+impl SctkTextToolbar {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        parent: &Window,
+        rect: TextToolbarRect,
+        placement: &PopupPlacement,
+        actions: Vec<TextToolbarAction>,
+        scale: u32,
+        grab: Option<(&WlSeat, u32)>,
+        qh: &QueueHandle<SctkApplicationState>,
+        compositor_state: &CompositorState,
+        xdg_shell_state: &XdgShell,
+        shm_state: &Shm,
+    ) -> Result<Self, SctkTextToolbarError> {
+        let mut buttons = Vec::with_capacity(actions.len());
+        let mut width = 0;
+        for action in actions {
+            if !buttons.is_empty() {
+                width += SEPARATOR_WIDTH;
+            }
+            let button_width = label_width(action.label()) + 2 * BUTTON_PADDING_X;
+            buttons.push((action, width, width + button_width));
+            width += button_width;
+        }
+
+        let positioner = XdgPositioner::new(xdg_shell_state)?;
+        positioner.set_size(width as i32, BUTTON_HEIGHT as i32);
+        // The anchor rect must have a non-zero size (e.g.: collapsed
+        // selections).
+        positioner.set_anchor_rect(
+            rect.x.round() as i32,
+            rect.y.round() as i32,
+            (rect.width.round() as i32).max(1),
+            (rect.height.round() as i32).max(1),
+        );
+        set_placement(&positioner, placement);
+
+        let popup = Popup::new(
+            parent.xdg_surface(),
+            &positioner,
+            qh,
+            compositor_state,
+            xdg_shell_state,
+        )?;
+
+        // The grab makes the compositor dismiss the popup when clicking
+        // outside of it. It must happen before the popup is mapped.
+        if let Some((seat, serial)) = grab {
+            popup.xdg_popup().grab(seat, serial);
+        }
+
+        let pool = SlotPool::new((width * BUTTON_HEIGHT * scale * scale * 4) as usize, shm_state)?;
+
+        Ok(Self {
+            popup,
+            pool,
+            buttons,
+            width,
+            scale,
+            hovered: None,
+            cursor_override: None,
+        })
+    }
+
+    pub(crate) fn wl_surface(&self) -> &WlSurface {
+        self.popup.wl_surface()
+    }
+
+    /// Returns the action of the button at the (surface-local) position.
+    pub(crate) fn action_at(&self, x: f64, y: f64) -> Option<TextToolbarAction> {
+        self.button_index_at(x, y).map(|index| self.buttons[index].0)
+    }
+
+    pub(crate) fn pointer_moved(&mut self, position: Option<(f64, f64)>) {
+        let hovered = position.and_then(|(x, y)| self.button_index_at(x, y));
+        if hovered != self.hovered {
+            self.hovered = hovered;
+            self.draw();
+        }
+    }
+
+    pub(crate) fn set_cursor_override(&mut self, cursor_override: Option<CursorOverrideGuard>) {
+        self.cursor_override = cursor_override;
+    }
+
+    fn button_index_at(&self, x: f64, y: f64) -> Option<usize> {
+        if y < 0.0 || y >= BUTTON_HEIGHT as f64 {
+            return None;
+        }
+
+        self.buttons
+            .iter()
+            .position(|&(_, start, end)| x >= start as f64 && x < end as f64)
+    }
+
+    pub(crate) fn draw(&mut self) {
+        if let Err(err) = self.try_draw() {
+            error!("Failed to draw text toolbar: {}", err);
+        }
+    }
+
+    fn try_draw(&mut self) -> Result<(), SctkTextToolbarError> {
+        let scale = self.scale;
+        let width = self.width * scale;
+        let height = BUTTON_HEIGHT * scale;
+        let stride = width * 4;
+
+        let (buffer, canvas) = self.pool.create_buffer(
+            width as i32,
+            height as i32,
+            stride as i32,
+            wl_shm::Format::Argb8888,
+        )?;
+
+        let mut canvas = Canvas {
+            data: canvas,
+            width,
+            height,
+        };
+        canvas.fill_rect(0, 0, width, height, BACKGROUND_COLOR);
+
+        for (index, (action, start, end)) in self.buttons.iter().enumerate() {
+            if index > 0 {
+                canvas.fill_rect(
+                    (start - SEPARATOR_WIDTH) * scale,
+                    0,
+                    SEPARATOR_WIDTH * scale,
+                    height,
+                    SEPARATOR_COLOR,
+                );
+            }
+
+            if self.hovered == Some(index) {
+                canvas.fill_rect(start * scale, 0, (end - start) * scale, height, HOVER_COLOR);
+            }
+
+            canvas.draw_text(
+                (start + BUTTON_PADDING_X) * scale,
+                BUTTON_PADDING_Y * scale,
+                GLYPH_SCALE * scale,
+                action.label(),
+                TEXT_COLOR,
+            );
+        }
+
+        let surface = self.popup.wl_surface();
+        surface.set_buffer_scale(scale as i32);
+        surface.damage_buffer(0, 0, width as i32, height as i32);
+        buffer.attach_to(surface)?;
+        surface.commit();
+
+        Ok(())
+    }
+}
+
+fn set_placement(positioner: &XdgPositioner, placement: &PopupPlacement) {
+    positioner.set_anchor(match placement.anchor {
+        PopupAnchor::None => Anchor::None,
+        PopupAnchor::Top => Anchor::Top,
+        PopupAnchor::Bottom => Anchor::Bottom,
+        PopupAnchor::Left => Anchor::Left,
+        PopupAnchor::Right => Anchor::Right,
+        PopupAnchor::TopLeft => Anchor::TopLeft,
+        PopupAnchor::BottomLeft => Anchor::BottomLeft,
+        PopupAnchor::TopRight => Anchor::TopRight,
+        PopupAnchor::BottomRight => Anchor::BottomRight,
+    });
+    positioner.set_gravity(match placement.gravity {
+        PopupGravity::None => Gravity::None,
+        PopupGravity::Top => Gravity::Top,
+        PopupGravity::Bottom => Gravity::Bottom,
+        PopupGravity::Left => Gravity::Left,
+        PopupGravity::Right => Gravity::Right,
+        PopupGravity::TopLeft => Gravity::TopLeft,
+        PopupGravity::BottomLeft => Gravity::BottomLeft,
+        PopupGravity::TopRight => Gravity::TopRight,
+        PopupGravity::BottomRight => Gravity::BottomRight,
+    });
+    positioner.set_offset(
+        placement.offset.x.round() as i32,
+        placement.offset.y.round() as i32,
+    );
+
+    let constraint_adjustment = placement.constraint_adjustment.iter().fold(
+        ConstraintAdjustment::empty(),
+        |adjustment, constraint| {
+            adjustment
+                | match constraint {
+                    PopupConstraintAdjustment::SlideX => ConstraintAdjustment::SlideX,
+                    PopupConstraintAdjustment::SlideY => ConstraintAdjustment::SlideY,
+                    PopupConstraintAdjustment::FlipX => ConstraintAdjustment::FlipX,
+                    PopupConstraintAdjustment::FlipY => ConstraintAdjustment::FlipY,
+                    PopupConstraintAdjustment::ResizeX => ConstraintAdjustment::ResizeX,
+                    PopupConstraintAdjustment::ResizeY => ConstraintAdjustment::ResizeY,
+                }
+        },
+    );
+    positioner.set_constraint_adjustment(constraint_adjustment);
+}
+
+fn label_width(label: &str) -> u32 {
+    bitmap_font::text_width(label, GLYPH_SCALE)
+}
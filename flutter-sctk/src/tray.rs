@@ -0,0 +1,927 @@
+//! Tray icon (see the flutter-rs/tray channel) implementing the
+//! `StatusNotifierItem` D-Bus specification, which is supported by the KDE
+//! and wlroots-based panels (e.g.: Waybar), and by GNOME with the
+//! AppIndicator extension. The menu is exported with `com.canonical.dbusmenu`,
+//! and shown by the tray itself.
+//!
+//! The icon is announced to the `StatusNotifierWatcher`, which forwards it to
+//! the trays (i.e.: the hosts), by owning a bus name and registering it. The
+//! icon is registered again whenever the watcher is restarted (e.g.: along
+//! with the panel), and it is hidden by releasing the name.
+//!
+//! The D-Bus objects are served from the executor of zbus, so they read the
+//! icon from a shared model and forward the clicks to the event loop. The
+//! other requests are applied from a single task at a time, which converges
+//! to the last requested state.
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    future::Future,
+    io::BufReader,
+    mem, process,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use calloop::channel::Sender;
+use flutter_plugins::tray::{TrayEvent, TrayHandler, TrayIcon, TrayMenuItem, TrayStatus};
+use futures_lite::StreamExt;
+use parking_lot::Mutex;
+use tracing::warn;
+use zbus::{
+    fdo,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Structure, Value},
+    Connection, MatchRule, Message, MessageStream, SignalContext,
+};
+
+use crate::{application::SctkApplicationRequest, handler::SctkAsyncResult};
+
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+const WATCHER_INTERFACE: &str = "org.kde.StatusNotifierWatcher";
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+
+/// Id of the root item of the menu, whose children are the items of
+/// `TrayIcon::menu`.
+const ROOT_MENU_ITEM_ID: i32 = 0;
+
+/// Width, height and ARGB32 pixels (in network byte order) of an icon.
+type Pixmap = (i32, i32, Vec<u8>);
+
+/// Id, properties and children of a menu item, as expected by `GetLayout`.
+type MenuLayout = (i32, HashMap<String, Value<'static>>, Vec<Value<'static>>);
+
+/// Requests forwarded to the event loop, by the plugin handler and by the
+/// D-Bus objects.
+pub(crate) enum SctkTrayRequest {
+    Show(TrayIcon),
+    Hide,
+    Event(TrayEvent),
+}
+
+/// Icon read by the D-Bus objects.
+#[derive(Default)]
+struct SctkTrayModel {
+    icon: TrayIcon,
+    /// Decoded from `TrayIcon::icon_path`.
+    pixmaps: Vec<Pixmap>,
+    /// Revision of the menu layout, incremented whenever the menu changes.
+    revision: u32,
+}
+
+impl SctkTrayModel {
+    fn status(&self) -> &'static str {
+        match self.icon.status {
+            TrayStatus::Passive => "Passive",
+            TrayStatus::Active => "Active",
+            TrayStatus::NeedsAttention => "NeedsAttention",
+        }
+    }
+}
+
+/// Properties announced with a signal, so that the trays read them again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct SctkTrayChanges {
+    title: bool,
+    icon: bool,
+    tooltip: bool,
+    status: bool,
+    menu: bool,
+}
+
+impl SctkTrayChanges {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.title |= other.title;
+        self.icon |= other.icon;
+        self.tooltip |= other.tooltip;
+        self.status |= other.status;
+        self.menu |= other.menu;
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum SctkTraySyncStep {
+    RequestName,
+    ReleaseName,
+    Register,
+    Notify(SctkTrayChanges),
+}
+
+#[derive(Default)]
+struct SctkTrayState {
+    /// Set once the D-Bus objects are served.
+    connection: Option<Connection>,
+    /// Requested by the app.
+    is_shown: bool,
+    owns_name: bool,
+    needs_registration: bool,
+    changes: SctkTrayChanges,
+    is_syncing: bool,
+}
+
+impl SctkTrayState {
+    /// The next step towards the requested state. The state is updated
+    /// beforehand, so that a failing step isn't retried until the watcher is
+    /// restarted or the app makes another request.
+    fn next_step(&mut self) -> Option<SctkTraySyncStep> {
+        if self.is_shown != self.owns_name {
+            self.owns_name = self.is_shown;
+            self.needs_registration = self.is_shown;
+            return Some(if self.is_shown {
+                SctkTraySyncStep::RequestName
+            } else {
+                SctkTraySyncStep::ReleaseName
+            });
+        }
+
+        if !self.is_shown {
+            self.changes = SctkTrayChanges::default();
+            return None;
+        }
+
+        // The trays read every property of a newly registered icon.
+        if mem::take(&mut self.needs_registration) {
+            self.changes = SctkTrayChanges::default();
+            return Some(SctkTraySyncStep::Register);
+        }
+
+        let changes = mem::take(&mut self.changes);
+        (!changes.is_empty()).then_some(SctkTraySyncStep::Notify(changes))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SctkTray {
+    state: Rc<RefCell<SctkTrayState>>,
+    model: Arc<Mutex<SctkTrayModel>>,
+    is_available: Arc<AtomicBool>,
+    /// Used by the trays for telling the apps apart (e.g.: for remembering
+    /// whether the icon is hidden).
+    id: Arc<str>,
+    bus_name: Rc<str>,
+}
+
+impl SctkTray {
+    pub(crate) fn new(id: &str) -> Self {
+        Self {
+            state: Default::default(),
+            model: Default::default(),
+            is_available: Default::default(),
+            id: id.into(),
+            bus_name: format!("org.kde.StatusNotifierItem-{}-1", process::id()).into(),
+        }
+    }
+
+    pub(crate) fn create_handler(&self, sender: Sender<SctkApplicationRequest>) -> SctkTrayHandler {
+        SctkTrayHandler {
+            sender,
+            is_available: self.is_available.clone(),
+        }
+    }
+
+    /// Returns the task serving the D-Bus objects, and following the
+    /// restarts of the watcher. Runs as long as the application.
+    pub(crate) fn run(
+        &self,
+        sender: Sender<SctkApplicationRequest>,
+    ) -> impl Future<Output = SctkAsyncResult> {
+        let tray = self.clone();
+        async move {
+            if let Err(err) = tray.serve(sender).await {
+                warn!("[plugin: tray] The tray is unavailable: {}", err);
+            }
+            Ok(())
+        }
+    }
+
+    pub(crate) fn show(&self, icon: TrayIcon) {
+        let pixmaps = match &icon.icon_path {
+            Some(path) => load_pixmap(path)
+                .map(|pixmap| vec![pixmap])
+                .unwrap_or_else(|err| {
+                    warn!("[plugin: tray] Failed to load the icon {}: {}", path, err);
+                    Vec::new()
+                }),
+            None => Vec::new(),
+        };
+
+        let changes = {
+            let mut model = self.model.lock();
+            let changes = SctkTrayChanges {
+                title: model.icon.title != icon.title,
+                icon: model.icon.icon_name != icon.icon_name || model.pixmaps != pixmaps,
+                tooltip: model.icon.tooltip != icon.tooltip,
+                status: model.icon.status != icon.status,
+                menu: model.icon.menu != icon.menu,
+            };
+            if changes.menu {
+                model.revision = model.revision.wrapping_add(1);
+            }
+            model.icon = icon;
+            model.pixmaps = pixmaps;
+            changes
+        };
+
+        let mut state = self.state.borrow_mut();
+        state.is_shown = true;
+        state.changes.merge(changes);
+    }
+
+    pub(crate) fn hide(&self) {
+        self.state.borrow_mut().is_shown = false;
+    }
+
+    /// Returns the task that needs to be scheduled for applying the requested
+    /// state, if one is not already running. The state is applied once the
+    /// D-Bus objects are served otherwise.
+    pub(crate) fn request_sync(&self) -> Option<impl Future<Output = SctkAsyncResult>> {
+        self.start_sync().then(|| self.clone().sync())
+    }
+
+    fn start_sync(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        if state.is_syncing || state.connection.is_none() {
+            return false;
+        }
+        state.is_syncing = true;
+        true
+    }
+
+    async fn sync(self) -> SctkAsyncResult {
+        self.apply_pending().await;
+        Ok(())
+    }
+
+    async fn apply_pending(&self) {
+        loop {
+            let (step, connection) = {
+                let mut state = self.state.borrow_mut();
+                let step = state.next_step();
+                match (step, state.connection.clone()) {
+                    (Some(step), Some(connection)) => (step, connection),
+                    _ => {
+                        state.is_syncing = false;
+                        return;
+                    }
+                }
+            };
+
+            if let Err(err) = self.apply(&connection, step).await {
+                warn!("[plugin: tray] Failed to update the tray icon: {}", err);
+            }
+        }
+    }
+
+    async fn apply(&self, connection: &Connection, step: SctkTraySyncStep) -> zbus::Result<()> {
+        match step {
+            SctkTraySyncStep::RequestName => connection.request_name(&*self.bus_name).await,
+            SctkTraySyncStep::ReleaseName => {
+                connection.release_name(&*self.bus_name).await?;
+                Ok(())
+            }
+            SctkTraySyncStep::Register => {
+                connection
+                    .call_method(
+                        Some(WATCHER_NAME),
+                        WATCHER_PATH,
+                        Some(WATCHER_INTERFACE),
+                        "RegisterStatusNotifierItem",
+                        &(&*self.bus_name,),
+                    )
+                    .await?;
+                Ok(())
+            }
+            SctkTraySyncStep::Notify(changes) => self.notify(connection, changes).await,
+        }
+    }
+
+    async fn notify(&self, connection: &Connection, changes: SctkTrayChanges) -> zbus::Result<()> {
+        let item = SignalContext::new(connection, ITEM_PATH)?;
+        if changes.title {
+            StatusNotifierItem::new_title(&item).await?;
+        }
+        if changes.icon {
+            StatusNotifierItem::new_icon(&item).await?;
+        }
+        if changes.tooltip {
+            StatusNotifierItem::new_tool_tip(&item).await?;
+        }
+        if changes.status {
+            let status = self.model.lock().status();
+            StatusNotifierItem::new_status(&item, status).await?;
+        }
+        if changes.menu {
+            let revision = self.model.lock().revision;
+            let menu = SignalContext::new(connection, MENU_PATH)?;
+            DbusMenu::layout_updated(&menu, revision, ROOT_MENU_ITEM_ID).await?;
+        }
+        Ok(())
+    }
+
+    async fn serve(self, sender: Sender<SctkApplicationRequest>) -> zbus::Result<()> {
+        let item = StatusNotifierItem {
+            model: self.model.clone(),
+            id: self.id.clone(),
+            sender: sender.clone(),
+        };
+        let menu = DbusMenu {
+            model: self.model.clone(),
+            sender,
+        };
+        let connection = zbus::connection::Builder::session()?
+            .serve_at(ITEM_PATH, item)?
+            .serve_at(MENU_PATH, menu)?
+            .build()
+            .await?;
+
+        let watcher_owner_changes = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .sender("org.freedesktop.DBus")?
+            .interface("org.freedesktop.DBus")?
+            .member("NameOwnerChanged")?
+            .arg(0, WATCHER_NAME)?
+            .build();
+        let host_registrations = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .sender(WATCHER_NAME)?
+            .interface(WATCHER_INTERFACE)?
+            .member("StatusNotifierHostRegistered")?
+            .build();
+        let mut messages = MessageStream::for_match_rule(watcher_owner_changes, &connection, None)
+            .await?
+            .or(MessageStream::for_match_rule(host_registrations, &connection, None).await?);
+
+        self.state.borrow_mut().connection = Some(connection.clone());
+        self.update_availability(&connection).await;
+        if self.start_sync() {
+            self.apply_pending().await;
+        }
+
+        while let Some(message) = messages.next().await {
+            let Ok(message) = message else {
+                continue;
+            };
+
+            if is_watcher_started(&message) {
+                let mut state = self.state.borrow_mut();
+                state.needs_registration = state.owns_name;
+            }
+            self.update_availability(&connection).await;
+            if self.start_sync() {
+                self.apply_pending().await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_availability(&self, connection: &Connection) {
+        let is_available = is_host_registered(connection).await.unwrap_or(false);
+        self.is_available.store(is_available, Ordering::Relaxed);
+    }
+}
+
+/// Whether |message| is a `NameOwnerChanged` signal for a new watcher.
+fn is_watcher_started(message: &Message) -> bool {
+    let header = message.header();
+    if header.member().map(|member| member.as_str()) != Some("NameOwnerChanged") {
+        return false;
+    }
+
+    let body = message.body();
+    body.deserialize::<(String, String, String)>()
+        .is_ok_and(|(_, _, new_owner)| !new_owner.is_empty())
+}
+
+async fn is_host_registered(connection: &Connection) -> zbus::Result<bool> {
+    let reply = connection
+        .call_method(
+            Some(WATCHER_NAME),
+            WATCHER_PATH,
+            Some("org.freedesktop.DBus.Properties"),
+            "Get",
+            &(WATCHER_INTERFACE, "IsStatusNotifierHostRegistered"),
+        )
+        .await?;
+    let value: OwnedValue = reply.body().deserialize()?;
+    Ok(bool::try_from(value)?)
+}
+
+/// Decode the PNG file at |path|.
+fn load_pixmap(path: &str) -> Result<Pixmap, Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    data.truncate(info.buffer_size());
+
+    let argb = to_argb(&data, info.color_type).ok_or("Unsupported color type")?;
+    Ok((info.width as i32, info.height as i32, argb))
+}
+
+/// Convert the 8-bit |data| of |color_type| to ARGB32 in network byte order.
+fn to_argb(data: &[u8], color_type: png::ColorType) -> Option<Vec<u8>> {
+    let argb = match color_type {
+        png::ColorType::Rgba => data
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[3], pixel[0], pixel[1], pixel[2]])
+            .collect(),
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|pixel| [0xFF, pixel[0], pixel[1], pixel[2]])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[1], pixel[0], pixel[0], pixel[0]])
+            .collect(),
+        png::ColorType::Grayscale => data
+            .iter()
+            .flat_map(|&gray| [0xFF, gray, gray, gray])
+            .collect(),
+        png::ColorType::Indexed => return None,
+    };
+    Some(argb)
+}
+
+fn find_menu_item(items: &[TrayMenuItem], id: i32) -> Option<&TrayMenuItem> {
+    items.iter().find_map(|item| {
+        if item.id == id {
+            Some(item)
+        } else {
+            find_menu_item(&item.children, id)
+        }
+    })
+}
+
+fn menu_item_properties(item: &TrayMenuItem) -> HashMap<String, Value<'static>> {
+    let mut properties = HashMap::new();
+    if item.is_separator {
+        properties.insert("type".into(), Value::from("separator"));
+        return properties;
+    }
+
+    // Underscores mark the access key otherwise.
+    let label = item.label.replace('_', "__");
+    properties.insert("label".into(), Value::from(label));
+    properties.insert("enabled".into(), Value::from(item.is_enabled));
+    if let Some(is_checked) = item.is_checked {
+        properties.insert("toggle-type".into(), Value::from("checkmark"));
+        properties.insert("toggle-state".into(), Value::from(i32::from(is_checked)));
+    }
+    if !item.children.is_empty() {
+        properties.insert("children-display".into(), Value::from("submenu"));
+    }
+    properties
+}
+
+/// Layout of |children| up to |depth| levels, or all of them if negative.
+fn menu_layout(
+    id: i32,
+    properties: HashMap<String, Value<'static>>,
+    children: &[TrayMenuItem],
+    depth: i32,
+) -> MenuLayout {
+    let children = if depth == 0 {
+        Vec::new()
+    } else {
+        children
+            .iter()
+            .map(|child| {
+                let layout = menu_layout(
+                    child.id,
+                    menu_item_properties(child),
+                    &child.children,
+                    depth.saturating_sub(1),
+                );
+                Value::from(Structure::from(layout))
+            })
+            .collect()
+    };
+    (id, properties, children)
+}
+
+fn root_menu_properties() -> HashMap<String, Value<'static>> {
+    HashMap::from([("children-display".to_string(), Value::from("submenu"))])
+}
+
+struct StatusNotifierItem {
+    model: Arc<Mutex<SctkTrayModel>>,
+    id: Arc<str>,
+    sender: Sender<SctkApplicationRequest>,
+}
+
+impl StatusNotifierItem {
+    fn send(&self, event: TrayEvent) {
+        let request = SctkApplicationRequest::Tray(SctkTrayRequest::Event(event));
+        if self.sender.send(request).is_err() {
+            warn!("[plugin: tray] Ignoring event because the event loop is gone");
+        }
+    }
+}
+
+#[zbus::interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    fn activate(&self, x: i32, y: i32) {
+        self.send(TrayEvent::Activate { x, y });
+    }
+
+    fn secondary_activate(&self, x: i32, y: i32) {
+        self.send(TrayEvent::SecondaryActivate { x, y });
+    }
+
+    /// The trays show the exported menu instead.
+    fn context_menu(&self, _x: i32, _y: i32) {}
+
+    fn scroll(&self, delta: i32, orientation: String) {
+        let is_horizontal = orientation.eq_ignore_ascii_case("horizontal");
+        self.send(TrayEvent::Scroll {
+            delta,
+            is_horizontal,
+        });
+    }
+
+    #[zbus(property)]
+    fn category(&self) -> String {
+        "ApplicationStatus".into()
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> String {
+        self.model.lock().icon.title.clone()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        self.model.lock().status().into()
+    }
+
+    #[zbus(property)]
+    fn window_id(&self) -> i32 {
+        0
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> String {
+        String::new()
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        self.model.lock().icon.icon_name.clone().unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn icon_pixmap(&self) -> Vec<Pixmap> {
+        self.model.lock().pixmaps.clone()
+    }
+
+    #[zbus(property)]
+    fn overlay_icon_name(&self) -> String {
+        String::new()
+    }
+
+    #[zbus(property)]
+    fn overlay_icon_pixmap(&self) -> Vec<Pixmap> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn attention_icon_name(&self) -> String {
+        String::new()
+    }
+
+    #[zbus(property)]
+    fn attention_icon_pixmap(&self) -> Vec<Pixmap> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn attention_movie_name(&self) -> String {
+        String::new()
+    }
+
+    /// Icon name, icon pixmaps, title and description of the tooltip.
+    #[zbus(property)]
+    fn tool_tip(&self) -> (String, Vec<Pixmap>, String, String) {
+        let tooltip = self.model.lock().icon.tooltip.clone();
+        (
+            String::new(),
+            Vec::new(),
+            tooltip.unwrap_or_default(),
+            String::new(),
+        )
+    }
+
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        ObjectPath::from_static_str_unchecked(MENU_PATH).into()
+    }
+
+    #[zbus(signal)]
+    async fn new_title(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_icon(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_tool_tip(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_status(ctxt: &SignalContext<'_>, status: &str) -> zbus::Result<()>;
+}
+
+struct DbusMenu {
+    model: Arc<Mutex<SctkTrayModel>>,
+    sender: Sender<SctkApplicationRequest>,
+}
+
+impl DbusMenu {
+    fn properties(&self, id: i32) -> Option<HashMap<String, Value<'static>>> {
+        if id == ROOT_MENU_ITEM_ID {
+            return Some(root_menu_properties());
+        }
+
+        let model = self.model.lock();
+        find_menu_item(&model.icon.menu, id).map(menu_item_properties)
+    }
+
+    /// Returns whether the item of |id| exists.
+    fn handle_event(&self, id: i32, event_id: &str) -> bool {
+        let is_enabled = {
+            let model = self.model.lock();
+            match find_menu_item(&model.icon.menu, id) {
+                Some(item) => item.is_enabled && !item.is_separator,
+                None => return id == ROOT_MENU_ITEM_ID,
+            }
+        };
+
+        if event_id == "clicked" && is_enabled {
+            let event = TrayEvent::MenuItemClicked { id };
+            let request = SctkApplicationRequest::Tray(SctkTrayRequest::Event(event));
+            if self.sender.send(request).is_err() {
+                warn!("[plugin: tray] Ignoring event because the event loop is gone");
+            }
+        }
+        true
+    }
+}
+
+#[zbus::interface(name = "com.canonical.dbusmenu")]
+impl DbusMenu {
+    /// The properties of the items are always sent in full, as allowed by the
+    /// specification.
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> fdo::Result<(u32, MenuLayout)> {
+        let model = self.model.lock();
+        let layout = if parent_id == ROOT_MENU_ITEM_ID {
+            menu_layout(
+                parent_id,
+                root_menu_properties(),
+                &model.icon.menu,
+                recursion_depth,
+            )
+        } else {
+            let Some(item) = find_menu_item(&model.icon.menu, parent_id) else {
+                let message = format!("Unknown menu item: {parent_id}");
+                return Err(fdo::Error::InvalidArgs(message));
+            };
+            menu_layout(
+                parent_id,
+                menu_item_properties(item),
+                &item.children,
+                recursion_depth,
+            )
+        };
+        Ok((model.revision, layout))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, Value<'static>>)> {
+        ids.into_iter()
+            .filter_map(|id| Some((id, self.properties(id)?)))
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: String) -> fdo::Result<Value<'static>> {
+        let Some(mut properties) = self.properties(id) else {
+            return Err(fdo::Error::InvalidArgs(format!("Unknown menu item: {id}")));
+        };
+        properties
+            .remove(&name)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unknown property: {name}")))
+    }
+
+    fn event(
+        &self,
+        id: i32,
+        event_id: String,
+        _data: OwnedValue,
+        _timestamp: u32,
+    ) -> fdo::Result<()> {
+        if !self.handle_event(id, &event_id) {
+            return Err(fdo::Error::InvalidArgs(format!("Unknown menu item: {id}")));
+        }
+        Ok(())
+    }
+
+    /// Returns the ids of the unknown items.
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        events
+            .into_iter()
+            .filter(|(id, event_id, _, _)| !self.handle_event(*id, event_id))
+            .map(|(id, _, _, _)| id)
+            .collect()
+    }
+
+    /// The menu is always up to date.
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    /// Returns the ids of the items needing an update, and of the unknown
+    /// items.
+    fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        (Vec::new(), Vec::new())
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> String {
+        "ltr".into()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        "normal".into()
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(signal)]
+    async fn layout_updated(
+        ctxt: &SignalContext<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
+
+pub struct SctkTrayHandler {
+    sender: Sender<SctkApplicationRequest>,
+    is_available: Arc<AtomicBool>,
+}
+
+impl SctkTrayHandler {
+    fn send(&self, request: SctkTrayRequest) {
+        if self
+            .sender
+            .send(SctkApplicationRequest::Tray(request))
+            .is_err()
+        {
+            warn!("[plugin: tray] Ignoring request because the event loop is gone");
+        }
+    }
+}
+
+impl TrayHandler for SctkTrayHandler {
+    fn is_available(&mut self) -> bool {
+        self.is_available.load(Ordering::Relaxed)
+    }
+
+    fn show(&mut self, icon: TrayIcon) {
+        self.send(SctkTrayRequest::Show(icon));
+    }
+
+    fn hide(&mut self) {
+        self.send(SctkTrayRequest::Hide);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_plugins::tray::TrayMenuItem;
+    use zbus::zvariant::Value;
+
+    use super::{
+        menu_item_properties, menu_layout, root_menu_properties, to_argb, SctkTrayChanges,
+        SctkTrayState, SctkTraySyncStep,
+    };
+
+    fn item(id: i32, label: &str, children: Vec<TrayMenuItem>) -> TrayMenuItem {
+        TrayMenuItem {
+            id,
+            label: label.into(),
+            is_enabled: true,
+            is_separator: false,
+            is_checked: None,
+            children,
+        }
+    }
+
+    #[test]
+    fn converges_to_the_requested_state() {
+        let mut state = SctkTrayState {
+            is_shown: true,
+            ..Default::default()
+        };
+        assert_eq!(state.next_step(), Some(SctkTraySyncStep::RequestName));
+        assert_eq!(state.next_step(), Some(SctkTraySyncStep::Register));
+        assert_eq!(state.next_step(), None);
+
+        let changes = SctkTrayChanges {
+            menu: true,
+            ..Default::default()
+        };
+        state.changes.merge(changes);
+        assert_eq!(state.next_step(), Some(SctkTraySyncStep::Notify(changes)));
+        assert_eq!(state.next_step(), None);
+
+        // Registered again once the watcher is restarted.
+        state.needs_registration = state.owns_name;
+        assert_eq!(state.next_step(), Some(SctkTraySyncStep::Register));
+
+        state.is_shown = false;
+        state.changes.title = true;
+        assert_eq!(state.next_step(), Some(SctkTraySyncStep::ReleaseName));
+        assert_eq!(state.next_step(), None);
+        assert!(state.changes.is_empty());
+    }
+
+    #[test]
+    fn lays_out_the_menu() {
+        let mut quit = item(3, "_Quit", vec![]);
+        quit.is_checked = Some(true);
+        let menu = vec![item(1, "Open", vec![item(2, "Recent", vec![])]), quit];
+
+        let (id, _, children) = menu_layout(0, root_menu_properties(), &menu, -1);
+        assert_eq!(id, 0);
+        assert_eq!(children.len(), 2);
+        let Value::Structure(open) = &children[0] else {
+            panic!("Unexpected layout: {:?}", children[0]);
+        };
+        assert_eq!(open.fields()[0], Value::from(1));
+        assert_eq!(open.signature().as_str(), "(ia{sv}av)");
+
+        // Only the direct children.
+        let (_, _, children) = menu_layout(0, root_menu_properties(), &menu, 1);
+        let Value::Structure(open) = &children[0] else {
+            panic!("Unexpected layout: {:?}", children[0]);
+        };
+        let Value::Array(grandchildren) = &open.fields()[2] else {
+            panic!("Unexpected children: {:?}", open.fields()[2]);
+        };
+        assert!(grandchildren.is_empty());
+
+        let properties = menu_item_properties(&menu[1]);
+        assert_eq!(properties["label"], Value::from("__Quit"));
+        assert_eq!(properties["toggle-state"], Value::from(1));
+        assert!(!properties.contains_key("children-display"));
+    }
+
+    #[test]
+    fn converts_to_argb() {
+        let rgba = [0x10, 0x20, 0x30, 0x80];
+        assert_eq!(
+            to_argb(&rgba, png::ColorType::Rgba),
+            Some(vec![0x80, 0x10, 0x20, 0x30])
+        );
+        assert_eq!(
+            to_argb(&[0x40, 0x80], png::ColorType::GrayscaleAlpha),
+            Some(vec![0x80, 0x40, 0x40, 0x40])
+        );
+        assert_eq!(to_argb(&[0], png::ColorType::Indexed), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! Conversions between the coordinate spaces used by the embedder.
+//!
+//! Wayland sizes and positions are expressed in surface-local logical
+//! coordinates, while the engine expects physical pixels along with the device
+//! pixel ratio. All the conversions between the two go through [`Scale`].
+//!
+//! Rounding policy: physical sizes are rounded to the nearest integer (halves
+//! are rounded away from zero, as done by the `dpi` crate). Positions are
+//! never rounded.
+use dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize, Pixel, Size};
+
+/// Ratio between physical pixels and surface-local logical coordinates (i.e.:
+/// the device pixel ratio reported to the engine).
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub(crate) struct Scale(f64);
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl Scale {
+    /// Scale advertised by the compositor for a surface (e.g.: through
+    /// `wl_surface.preferred_buffer_scale`). Invalid values fall back to `1`.
+    pub(crate) fn from_buffer_scale(buffer_scale: i32) -> Self {
+        Self(buffer_scale.max(1).into())
+    }
+
+    pub(crate) fn get(self) -> f64 {
+        self.0
+    }
+
+    /// Integer scale of the buffers attached to a surface. Fractional scales
+    /// are rounded up, so that buffers are never rendered at a lower
+    /// resolution than the surface.
+    pub(crate) fn buffer_scale(self) -> i32 {
+        self.0.ceil() as i32
+    }
+
+    /// Device pixel ratio reported to the engine for a surface at this scale,
+    /// once the |pixel_ratio_override| (if any) and the content |zoom| are
+    /// applied.
+    pub(crate) fn pixel_ratio(self, pixel_ratio_override: Option<f64>, zoom: f64) -> Scale {
+        Scale(pixel_ratio_override.unwrap_or(self.0) * zoom)
+    }
+
+    /// Ratio between the surface-local coordinates and the logical ones of
+    /// the view (i.e.: as seen by Dart), given the |pixel_ratio| reported to
+    /// the engine.
+    pub(crate) fn content_scale(self, pixel_ratio: f64) -> f64 {
+        pixel_ratio / self.0
+    }
+
+    pub(crate) fn to_physical_size(self, size: impl Into<Size>) -> PhysicalSize<u32> {
+        size.into().to_physical(self.0)
+    }
+
+    pub(crate) fn to_logical_size<P: Pixel>(self, size: impl Into<Size>) -> LogicalSize<P> {
+        size.into().to_logical(self.0)
+    }
+
+    pub(crate) fn to_physical_position(
+        self,
+        position: LogicalPosition<f64>,
+    ) -> PhysicalPosition<f64> {
+        position.to_physical(self.0)
+    }
+
+    pub(crate) fn to_logical_position(
+        self,
+        position: PhysicalPosition<f64>,
+    ) -> LogicalPosition<f64> {
+        position.to_logical(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
+
+    use super::Scale;
+
+    #[test]
+    fn physical_sizes_are_rounded_to_the_nearest_pixel() {
+        let scale = Scale(1.5);
+        assert_eq!(
+            scale.to_physical_size(LogicalSize::new(101, 33)),
+            PhysicalSize::new(152, 50)
+        );
+
+        let scale = Scale(1.25);
+        assert_eq!(
+            scale.to_physical_size(LogicalSize::new(1001, 3)),
+            PhysicalSize::new(1251, 4)
+        );
+
+        // Physical sizes are left as is.
+        assert_eq!(
+            scale.to_physical_size(PhysicalSize::new(1001, 3)),
+            PhysicalSize::new(1001, 3)
+        );
+    }
+
+    #[test]
+    fn logical_sizes_round_trip_at_integer_scales() {
+        let scale = Scale::from_buffer_scale(2);
+        let size = LogicalSize::new(640, 481);
+
+        let physical_size = scale.to_physical_size(size);
+        assert_eq!(physical_size, PhysicalSize::new(1280, 962));
+        assert_eq!(scale.to_logical_size::<u32>(physical_size), size);
+    }
+
+    #[test]
+    fn positions_are_not_rounded() {
+        let scale = Scale(1.25);
+        let position = LogicalPosition::new(10.5, 3.0);
+
+        let physical_position = scale.to_physical_position(position);
+        assert_eq!(physical_position, PhysicalPosition::new(13.125, 3.75));
+        assert_eq!(scale.to_logical_position(physical_position), position);
+    }
+
+    #[test]
+    fn zoom_composes_with_fractional_scales() {
+        let scale = Scale(1.25);
+        let pixel_ratio = scale.pixel_ratio(None, 1.5);
+        assert_eq!(pixel_ratio, Scale(1.875));
+        assert_eq!(scale.content_scale(pixel_ratio.get()), 1.5);
+
+        // The buffer keeps its size, only the logical size of the view
+        // shrinks.
+        let physical_size = scale.to_physical_size(LogicalSize::new(1000, 600));
+        assert_eq!(physical_size, PhysicalSize::new(1250, 750));
+        assert_eq!(
+            pixel_ratio.to_logical_size::<f64>(physical_size),
+            LogicalSize::new(1250.0 / 1.875, 400.0)
+        );
+
+        // Pointer positions are sent in physical pixels, which map to the
+        // zoomed logical coordinates of the view.
+        let position = scale.to_physical_position(LogicalPosition::new(300.0, 120.0));
+        assert_eq!(position, PhysicalPosition::new(375.0, 150.0));
+        assert_eq!(
+            pixel_ratio.to_logical_position(position),
+            LogicalPosition::new(200.0, 80.0)
+        );
+
+        // The zoom applies on top of the overridden pixel ratio.
+        let pixel_ratio = scale.pixel_ratio(Some(2.0), 0.5);
+        assert_eq!(pixel_ratio, Scale(1.0));
+        assert_eq!(scale.content_scale(pixel_ratio.get()), 0.8);
+        assert_eq!(scale.pixel_ratio(None, 1.0), scale);
+    }
+
+    #[test]
+    fn buffer_scale_is_rounded_up() {
+        assert_eq!(Scale(1.0).buffer_scale(), 1);
+        assert_eq!(Scale(1.25).buffer_scale(), 2);
+        assert_eq!(Scale::from_buffer_scale(0), Scale(1.0));
+        assert_eq!(Scale::from_buffer_scale(3).buffer_scale(), 3);
+    }
+}
@@ -1,26 +1,45 @@
 use std::{
     collections::HashMap,
     num::NonZeroU32,
-    sync::{Arc, Mutex, RwLock},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, RwLock, Weak,
+    },
+    time::Duration,
 };
 
 use dpi::{LogicalSize, PhysicalSize, Size};
 use flutter_engine::{
-    ffi::{FlutterPointerEvent, FlutterViewId, IMPLICIT_VIEW_ID},
+    ffi::{FlutterPointerEvent, FlutterPointerPhase, FlutterViewId, IMPLICIT_VIEW_ID},
     view::FlutterView,
-    FlutterEngineWeakRef,
+    FlutterEngine, FlutterEngineWeakRef, WindowMetricsError,
 };
 use flutter_engine_sys::FlutterEngineDisplayId;
-use flutter_glutin::builder::FlutterEGLContext;
-use flutter_runner_api::ApplicationAttributes;
-use tracing::{error, trace, warn};
+use flutter_glutin::{builder::FlutterEGLContext, renderer::RendererInfo};
+use flutter_plugins::{
+    anchor::{AnchorRegistry, ViewGeometry},
+    color::ColorInfo,
+    window::{SizeParams, Visibility},
+};
+use flutter_runner_api::{ApplicationAttributes, FrameStats, LayerMargin, RenderPath};
+use tracing::{error, info, trace, warn};
 use smithay_client_toolkit::{
     compositor::{CompositorState, SurfaceData},
-    reexports::protocols::xdg::shell::client::xdg_toplevel::XdgToplevel,
-    seat::pointer::{PointerEvent, PointerEventKind},
+    output::OutputData,
+    reexports::calloop::channel::Sender,
+    reexports::csd_frame::WindowState,
+    reexports::protocols::{
+        wp::idle_inhibit::zv1::client::{
+            zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1,
+            zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+        },
+        xdg::shell::client::xdg_toplevel::XdgToplevel,
+    },
+    seat::pointer::PointerEvent,
     shell::{
+        wlr_layer::LayerSurface,
         xdg::{
-            window::{Window, WindowConfigure, WindowDecorations},
+            window::{Window, WindowDecorations},
             XdgShell,
         },
         WaylandSurface,
@@ -29,21 +48,60 @@ use smithay_client_toolkit::{
 use thiserror::Error;
 use wayland_backend::client::ObjectId;
 use wayland_client::{
-    protocol::{wl_pointer::WlPointer, wl_surface::WlSurface},
+    protocol::{wl_output::WlOutput, wl_pointer::WlPointer, wl_surface::WlSurface},
     Connection, Proxy, QueueHandle,
 };
 
 use crate::{
-    application::SctkApplicationState,
+    application::{SctkApplicationRequest, SctkApplicationState},
+    attached::{SctkAttachedSurface, SctkAttachment},
+    damage,
     egl::CreateWaylandContextError,
-    handler::{SctkCompositorHandler, SctkOpenGLHandler, SctkVsyncHandler},
-    pointer::SctkPointerEvent,
+    engine::SctkEngineId,
+    failure::SctkFailureScreen,
+    handler::{
+        SctkCompositorHandler, SctkLoggingHandler, SctkOpenGLHandler, SctkVsyncHandler,
+        SctkWindowHandler,
+    },
+    input_gate::{SctkGatedInput, SctkGatedPush, SctkInputGate, SctkReadyHandler},
+    layer_shell::{self, SctkLayerShell},
+    mirror::SctkMirrors,
+    occlusion::{SctkOcclusionEstimator, SctkSurfaceVisibility},
+    output::SctkOutputScales,
+    overlay::SctkOverlayGlobals,
+    readback::SctkFrameReadback,
+    scroll::SctkScrollSettings,
+    solid::{SctkLetterbox, SctkSolidColorGlobals},
+    units::Scale,
+    window_state::{SctkWindowStateTracker, WindowStateRequest},
 };
 use crate::{
     egl::{FlutterEGLContextWaylandExt, NonZeroU32PhysicalSize},
-    pointer::Pointer,
+    pointer::{
+        mouse_device_id, pinch_scale_event, pointer_events, touch_event, Pointer, TouchPoint,
+    },
 };
 
+/// Fractions of the available area (the compositor suggested bounds or the
+/// output logical size) used as the initial window size when no size has been
+/// explicitly requested.
+const DEFAULT_SIZE_WIDTH_RATIO: f64 = 0.6;
+const DEFAULT_SIZE_HEIGHT_RATIO: f64 = 0.7;
+
+/// Limits applied to the computed default size. The available area always
+/// takes precedence over the minimum size.
+const MIN_DEFAULT_SIZE: LogicalSize<f64> = LogicalSize::new(640.0, 480.0);
+const MAX_DEFAULT_SIZE: LogicalSize<f64> = LogicalSize::new(2560.0, 1600.0);
+
+/// Upper bound (in physical pixels) of the window surface dimensions. The
+/// maximum texture size reported by GL is used instead when it is lower.
+const MAX_SURFACE_DIMENSION: u32 = 16384;
+
+/// Window metrics rejected by the engine are resent (from the current
+/// geometry) with an exponential backoff, at most this many times.
+const MAX_METRICS_RETRIES: u32 = 5;
+const METRICS_RETRY_BASE_DELAY: Duration = Duration::from_millis(16);
+
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
 /// States a resize event can be in.
 pub(crate) enum ResizeState {
@@ -59,37 +117,279 @@ pub(crate) enum ResizeState {
     FrameGenerated,
 }
 
+/// Whether the frames of the window are presented, see
+/// `SctkFlutterWindow::hide`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum VisibilityState {
+    /// The surface is mapped by the frames presented to it.
+    Visible,
+    /// The surface is unmapped and the frames are discarded. It must be
+    /// configured (again, once unmapped) before a buffer is attached to it.
+    Hidden { is_configured: bool },
+    /// Shown again, but the frames are discarded until the surface is
+    /// configured.
+    WaitingForConfigure,
+}
+
+/// Wayland role of the window surface.
+enum SctkWindowRole {
+    Toplevel(Window),
+    /// Desktop shell component (see `WindowRole::LayerShell`).
+    Layer(LayerSurface),
+    /// Utility window attached to another one (see `WindowRole::Utility`).
+    Attached(SctkAttachedSurface),
+}
+
+impl SctkWindowRole {
+    fn wl_surface(&self) -> &WlSurface {
+        match self {
+            SctkWindowRole::Toplevel(window) => window.wl_surface(),
+            SctkWindowRole::Layer(layer_surface) => layer_surface.wl_surface(),
+            SctkWindowRole::Attached(attached_surface) => attached_surface.wl_surface(),
+        }
+    }
+
+    /// Whether the surface must be configured by the compositor before being
+    /// mapped, which isn't the case of subsurfaces.
+    fn is_configured_by_compositor(&self) -> bool {
+        !matches!(self, SctkWindowRole::Attached(_))
+    }
+}
+
+/// Window metrics which were rejected by the engine and are waiting to be
+/// resent.
+#[derive(Copy, Clone, Debug)]
+struct PendingMetricsRetry {
+    /// Number of resends which have been rejected so far.
+    attempts: u32,
+    rejected_size: PhysicalSize<NonZeroU32>,
+    rejected_scale_factor: Scale,
+}
+
 pub(crate) struct SctkFlutterWindowInner {
     id: FlutterViewId,
-    window: Window,
+    role: SctkWindowRole,
     engine: FlutterEngineWeakRef,
     current_size: RwLock<Option<Size>>,
-    current_scale_factor: RwLock<f64>,
-    default_size: Size,
+    current_scale_factor: RwLock<Scale>,
+    pixel_ratio_override: RwLock<Option<f64>>,
+    /// Zoom of the content (see `crate::zoom`), `1.0` by default.
+    content_zoom: RwLock<f64>,
+    /// ARGB color drawn behind the content.
+    background_color: u32,
+    /// The renderer of the context, and whether it is a GPU.
+    renderer: RendererInfo,
+    is_hardware_accelerated: bool,
+    /// Whether the background is made opaque (see `crate::accessibility`).
+    is_transparency_reduced: AtomicBool,
+    /// Set once the engine presented a frame to the window, or rendered one
+    /// while it is hidden.
+    has_presented_frame: AtomicBool,
+    /// Locked while presenting, so that the window isn't hidden meanwhile.
+    visibility: Mutex<VisibilityState>,
+    frame_stats: SctkFrameStats,
+    sizing: RwLock<SctkWindowSizing>,
+    /// Maximum size of the window, which fullscreen windows are letterboxed
+    /// to.
+    max_size: RwLock<Option<LogicalSize<u32>>>,
+    window_state: RwLock<WindowState>,
+    state_tracker: Arc<Mutex<SctkWindowStateTracker>>,
     pointers: RwLock<HashMap<ObjectId, Pointer>>,
+    /// Buffers the input until the app is ready, see `crate::input_gate`.
+    input_gate: Mutex<SctkInputGate>,
     opengl_handler: SctkOpenGLHandler,
     compositor_handler: SctkCompositorHandler,
+    render_path: RenderPath,
     vsync_handler: Arc<parking_lot::Mutex<SctkVsyncHandler>>,
     resize_mutex: Mutex<()>,
     resize_status: RwLock<ResizeState>,
     pending_size: RwLock<Option<PhysicalSize<NonZeroU32>>>,
+    metrics_retry: Mutex<Option<PendingMetricsRetry>>,
+    request_sender: Sender<SctkApplicationRequest>,
+    idle_inhibit_manager: Option<ZwpIdleInhibitManagerV1>,
+    idle_inhibitor: Mutex<Option<ZwpIdleInhibitorV1>>,
+    /// Window this one is a transient child of (see `set_parent`).
+    parent: Mutex<Option<Weak<SctkFlutterWindowInner>>>,
+    output_scales: Mutex<SctkOutputScales>,
+    /// Color characteristics of the outputs the window touches (see
+    /// `crate::color`).
+    color_info: Mutex<ColorInfo>,
+    /// Whether the window is seen by the user (see `crate::occlusion`).
+    occlusion: Mutex<SctkOcclusionEstimator>,
+    /// Anchor rects of the popups, invalidated when the metrics change.
+    anchors: AnchorRegistry,
+    qh: QueueHandle<SctkApplicationState>,
 }
 
 impl SctkFlutterWindowInner {
-    pub(super) fn store_current_scale_factor(&self, new_scale_factor: f64) {
+    pub(super) fn store_current_scale_factor(&self, new_scale_factor: Scale) {
         let mut current_scale_factor = self.current_scale_factor.write().unwrap();
         *current_scale_factor = new_scale_factor;
     }
 
-    pub(super) fn load_current_scale_factor(&self) -> f64 {
+    pub(super) fn load_current_scale_factor(&self) -> Scale {
         *self.current_scale_factor.read().unwrap()
     }
 
+    /// Pixel ratio reported to the engine, which differs from the scale
+    /// factor of the surface when overridden or zoomed.
+    fn load_pixel_ratio(&self, scale_factor: Scale) -> f64 {
+        let pixel_ratio_override = *self.pixel_ratio_override.read().unwrap();
+        let content_zoom = *self.content_zoom.read().unwrap();
+        scale_factor
+            .pixel_ratio(pixel_ratio_override, content_zoom)
+            .get()
+    }
+
+    pub(crate) fn load_content_zoom(&self) -> f64 {
+        *self.content_zoom.read().unwrap()
+    }
+
+    pub(crate) fn is_transparency_reduced(&self) -> bool {
+        self.is_transparency_reduced.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn load_color_info(&self) -> ColorInfo {
+        *self.color_info.lock().unwrap()
+    }
+
+    pub(crate) fn load_visibility(&self) -> Visibility {
+        self.occlusion.lock().unwrap().visibility()
+    }
+
+    /// Premultiplied RGBA color the window is cleared to before drawing the
+    /// content, or `None` if the background is fully transparent.
+    pub(crate) fn clear_color(&self) -> Option<[f32; 4]> {
+        background_clear_color(self.background_color, self.is_transparency_reduced())
+    }
+
     pub(super) fn store_current_size(&self, new_size: Size) {
         let mut current_size = self.current_size.write().unwrap();
         *current_size = Some(new_size);
     }
 
+    pub(super) fn load_current_size(&self) -> Option<Size> {
+        *self.current_size.read().unwrap()
+    }
+
+    fn load_default_size(&self) -> Size {
+        self.sizing.read().unwrap().default_size
+    }
+
+    pub(crate) fn load_max_size(&self) -> Option<LogicalSize<u32>> {
+        *self.max_size.read().unwrap()
+    }
+
+    pub(crate) fn store_max_size(&self, max_size: Option<LogicalSize<u32>>) {
+        *self.max_size.write().unwrap() = max_size;
+    }
+
+    pub(crate) fn load_suggested_bounds(&self) -> Option<LogicalSize<u32>> {
+        self.sizing.read().unwrap().suggested_bounds
+    }
+
+    pub(crate) fn load_window_state(&self) -> WindowState {
+        *self.window_state.read().unwrap()
+    }
+
+    /// Returns whether |request| should be sent to the compositor (see
+    /// `crate::window_state`).
+    pub(crate) fn request_state(&self, request: WindowStateRequest) -> bool {
+        self.state_tracker.lock().unwrap().request(request)
+    }
+
+    pub(crate) fn wl_surface(&self) -> &WlSurface {
+        self.role.wl_surface()
+    }
+
+    pub(crate) fn input_gate(&self) -> MutexGuard<'_, SctkInputGate> {
+        self.input_gate.lock().unwrap()
+    }
+
+    /// Returns |input| if it must be sent right away, i.e.: unless it is
+    /// buffered until the app is ready (see `crate::input_gate`).
+    fn gate_input(&self, input: SctkGatedInput) -> Option<SctkGatedInput> {
+        match self.input_gate().push(input) {
+            SctkGatedPush::Send(input) => Some(input),
+            SctkGatedPush::Buffered => None,
+            SctkGatedPush::Full => {
+                warn!("Too much input received before the app was ready, releasing it");
+                let request = SctkApplicationRequest::ReleaseInput {
+                    surface: self.wl_surface().clone(),
+                };
+                if self.request_sender.send(request).is_err() {
+                    warn!("Not releasing the input because the event loop is gone");
+                }
+                None
+            }
+        }
+    }
+
+    pub(crate) fn id(&self) -> FlutterViewId {
+        self.id
+    }
+
+    /// Returns the xdg window, unless this is a layer surface or an attached
+    /// window.
+    pub(crate) fn window(&self) -> Option<&Window> {
+        match &self.role {
+            SctkWindowRole::Toplevel(window) => Some(window),
+            SctkWindowRole::Layer(_) | SctkWindowRole::Attached(_) => None,
+        }
+    }
+
+    pub(crate) fn layer_surface(&self) -> Option<&LayerSurface> {
+        match &self.role {
+            SctkWindowRole::Layer(layer_surface) => Some(layer_surface),
+            SctkWindowRole::Toplevel(_) | SctkWindowRole::Attached(_) => None,
+        }
+    }
+
+    pub(crate) fn attached_surface(&self) -> Option<&SctkAttachedSurface> {
+        match &self.role {
+            SctkWindowRole::Attached(attached_surface) => Some(attached_surface),
+            SctkWindowRole::Toplevel(_) | SctkWindowRole::Layer(_) => None,
+        }
+    }
+
+    /// Update the exclusive zone of a layer surface at runtime.
+    pub(crate) fn set_exclusive_zone(&self, exclusive_zone: i32) -> bool {
+        let Some(layer_surface) = self.layer_surface() else {
+            return false;
+        };
+
+        layer_surface.set_exclusive_zone(exclusive_zone);
+        layer_surface.commit();
+        true
+    }
+
+    /// Update the margin of a layer surface at runtime.
+    pub(crate) fn set_margin(&self, margin: LayerMargin) -> bool {
+        let Some(layer_surface) = self.layer_surface() else {
+            return false;
+        };
+
+        layer_shell::set_margin(layer_surface, margin);
+        layer_surface.commit();
+        true
+    }
+
+    /// Outputs the window currently touches.
+    pub(crate) fn outputs(&self) -> Vec<WlOutput> {
+        self.role
+            .wl_surface()
+            .data::<SurfaceData>()
+            .map(|data| data.outputs().collect())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn recommended_max_size(&self) -> Option<SizeParams> {
+        self.load_suggested_bounds().map(|bounds| SizeParams {
+            width: bounds.width.into(),
+            height: bounds.height.into(),
+        })
+    }
+
     fn store_resize_status(&self, new_resize_status: ResizeState) {
         let mut resize_status = self.resize_status.write().unwrap();
         *resize_status = new_resize_status;
@@ -108,24 +408,33 @@ impl SctkFlutterWindowInner {
         *self.pending_size.read().unwrap()
     }
 
-    pub(super) fn scale_internal_size(&self, new_scale_factor: f64) {
+    pub(super) fn scale_internal_size(&self, new_scale_factor: Scale) {
         self.store_current_scale_factor(new_scale_factor);
 
         let mut current_size = self.current_size.write().unwrap();
-        *current_size = current_size.map(|size| size.to_logical::<u32>(new_scale_factor).into());
+        *current_size =
+            current_size.map(|size| new_scale_factor.to_logical_size::<u32>(size).into());
     }
 
     pub(super) fn non_zero_physical_size(&self) -> Option<PhysicalSize<NonZeroU32>> {
-        let scale_factor = self.current_scale_factor.read().unwrap();
-        self.current_size
-            .read()
-            .unwrap()
-            .and_then(|size| size.to_physical::<u32>(*scale_factor).non_zero())
+        let scale_factor = self.load_current_scale_factor();
+        let max_dimension = self.max_surface_dimension();
+        self.current_size.read().unwrap().and_then(|size| {
+            clamp_physical_size(scale_factor.to_physical_size(size), max_dimension).non_zero()
+        })
+    }
+
+    fn max_surface_dimension(&self) -> u32 {
+        match self.compositor_handler.max_texture_size() {
+            0 => MAX_SURFACE_DIMENSION,
+            max_texture_size => max_texture_size.min(MAX_SURFACE_DIMENSION),
+        }
     }
 
     // Note: This callback is executed on the *render* thread.
     pub(super) fn on_frame_generated(&self, size: PhysicalSize<u32>) -> bool {
         trace!("window frame generated: {}x{}", size.width, size.height);
+        self.frame_stats.frame_generated();
         let _resize_mutex = self.resize_mutex.lock().unwrap();
 
         let resize_status = self.load_resize_status();
@@ -156,6 +465,7 @@ impl SctkFlutterWindowInner {
     // Note: This callback is executed on the *render* thread.
     pub(super) fn on_empty_frame_generated(&self) -> bool {
         trace!("window empty frame generated");
+        self.frame_stats.frame_generated();
         let _resize_mutex = self.resize_mutex.lock().unwrap();
 
         let resize_status = self.load_resize_status();
@@ -170,6 +480,10 @@ impl SctkFlutterWindowInner {
     // Note: This callback is executed on the *render* thread.
     pub(super) fn on_frame_presented(&self) {
         trace!("window frame presented");
+        self.frame_stats.frame_presented();
+        self.state_tracker.lock().unwrap().frame_presented();
+        self.report_first_frame();
+
         let _resize_mutex = self.resize_mutex.lock().unwrap();
 
         self.vsync_handler.lock().notify_present();
@@ -195,248 +509,1385 @@ impl SctkFlutterWindowInner {
         }
     }
 
+    // Note: This callback is executed on the *render* thread.
+    pub(super) fn on_frame_discarded(&self) {
+        trace!("window frame discarded, the window is hidden");
+        // The app is ready, even though it isn't shown.
+        self.report_first_frame();
+
+        let _resize_mutex = self.resize_mutex.lock().unwrap();
+        if self.load_resize_status() == ResizeState::FrameGenerated {
+            self.store_resize_status(ResizeState::Done);
+        }
+    }
+
+    fn report_first_frame(&self) {
+        if self.has_presented_frame.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let request = SctkApplicationRequest::FirstFramePresented {
+            surface: self.role.wl_surface().clone(),
+        };
+        if self.request_sender.send(request).is_err() {
+            warn!("Not reporting the first frame because the event loop is gone");
+        }
+    }
+
+    /// Lock the visibility of the window while presenting a frame, so that
+    /// the surface isn't unmapped meanwhile. Returns `None` when the frame
+    /// must be discarded instead.
+    pub(super) fn lock_visible(&self) -> Option<MutexGuard<'_, VisibilityState>> {
+        let visibility = self.visibility.lock().unwrap();
+        (*visibility == VisibilityState::Visible).then_some(visibility)
+    }
+
+    /// Whether the window is shown, or about to be.
+    pub(crate) fn is_visible(&self) -> bool {
+        !matches!(
+            *self.visibility.lock().unwrap(),
+            VisibilityState::Hidden { .. }
+        )
+    }
+
+    pub(crate) fn show(&self) {
+        let mut visibility = self.visibility.lock().unwrap();
+        match *visibility {
+            VisibilityState::Visible | VisibilityState::WaitingForConfigure => {}
+            VisibilityState::Hidden {
+                is_configured: true,
+            } => {
+                *visibility = VisibilityState::Visible;
+                drop(visibility);
+                self.schedule_mapping_frame();
+            }
+            VisibilityState::Hidden {
+                is_configured: false,
+            } => {
+                // Like for the initial commit, the compositor configures an
+                // unmapped surface once committed without a buffer.
+                *visibility = VisibilityState::WaitingForConfigure;
+                self.role.wl_surface().commit();
+            }
+        }
+    }
+
+    pub(crate) fn hide(&self) {
+        let mut visibility = self.visibility.lock().unwrap();
+        if matches!(*visibility, VisibilityState::Hidden { .. }) {
+            return;
+        }
+
+        // Subsurfaces are shown again without being configured.
+        *visibility = VisibilityState::Hidden {
+            is_configured: !self.role.is_configured_by_compositor(),
+        };
+        // Frame callbacks don't fire for unmapped surfaces.
+        self.vsync_handler.lock().surface_unmapped();
+
+        let surface = self.role.wl_surface();
+        surface.attach(None, 0, 0);
+        surface.commit();
+    }
+
+    /// The surface was configured, which it must be before being mapped.
+    fn surface_configured(&self) {
+        let mut visibility = self.visibility.lock().unwrap();
+        match *visibility {
+            VisibilityState::Visible => {}
+            VisibilityState::Hidden { .. } => {
+                *visibility = VisibilityState::Hidden {
+                    is_configured: true,
+                };
+            }
+            VisibilityState::WaitingForConfigure => {
+                *visibility = VisibilityState::Visible;
+                drop(visibility);
+                self.schedule_mapping_frame();
+            }
+        }
+    }
+
+    /// Render a frame for mapping the surface, unless the engine doesn't have
+    /// the metrics of the window yet (i.e.: its first frame maps it).
+    fn schedule_mapping_frame(&self) {
+        if self.load_current_size().is_none() {
+            return;
+        }
+
+        if let Some(engine) = self.engine.upgrade() {
+            engine.schedule_frame();
+        }
+    }
+
     /// A surface can be present on multiple outputs, but currently Flutter only
     /// supports passing a single `display_id` as part of the window metrics
     /// event. Therefore, the current implementation just picks the id of the
     /// first output.
     fn get_display_id(&self) -> Option<FlutterEngineDisplayId> {
-        let data = self.window.wl_surface().data::<SurfaceData>()?;
+        let data = self.role.wl_surface().data::<SurfaceData>()?;
         let display_id = data.outputs().next()?.id().protocol_id();
         Some(display_id.into())
     }
 }
 
+/// Timestamps (in engine time) of the last frame events, recorded by the
+/// platform and render threads without locking. `0` when unknown.
+#[derive(Default)]
+struct SctkFrameStats {
+    presented_frames: AtomicU64,
+    vsync_time: AtomicU64,
+    frame_generated_time: AtomicU64,
+    frame_presented_time: AtomicU64,
+}
+
+impl SctkFrameStats {
+    fn vsync(&self, frame_start_time_nanos: u64) {
+        self.vsync_time
+            .store(frame_start_time_nanos, Ordering::Relaxed);
+    }
+
+    fn frame_generated(&self) {
+        let now = FlutterEngine::get_current_time_nanos();
+        self.frame_generated_time.store(now, Ordering::Relaxed);
+    }
+
+    fn frame_presented(&self) {
+        let now = FlutterEngine::get_current_time_nanos();
+        self.frame_presented_time.store(now, Ordering::Relaxed);
+        self.presented_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> FrameStats {
+        let load_time =
+            |time: &AtomicU64| Some(time.load(Ordering::Relaxed)).filter(|&time| time > 0);
+        FrameStats {
+            presented_frames: self.presented_frames.load(Ordering::Relaxed),
+            vsync_time_nanos: load_time(&self.vsync_time),
+            frame_generated_time_nanos: load_time(&self.frame_generated_time),
+            frame_presented_time_nanos: load_time(&self.frame_presented_time),
+        }
+    }
+}
+
 pub struct SctkFlutterWindow {
     inner: Arc<SctkFlutterWindowInner>,
+    /// Bars around the content of a fullscreen window kept within its
+    /// maximum size.
+    letterbox: SctkLetterbox,
 }
 
 impl SctkFlutterWindow {
-    pub fn new(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
         engine: FlutterEngineWeakRef,
         qh: &QueueHandle<SctkApplicationState>,
         compositor_state: &CompositorState,
         xdg_shell_state: &XdgShell,
+        layer_shell: Option<&SctkLayerShell>,
+        attachment: Option<SctkAttachment>,
         vsync_handler: Arc<parking_lot::Mutex<SctkVsyncHandler>>,
+        idle_inhibit_manager: Option<ZwpIdleInhibitManagerV1>,
+        overlay_globals: Option<SctkOverlayGlobals>,
+        solid_color_globals: Option<SctkSolidColorGlobals>,
+        request_sender: Sender<SctkApplicationRequest>,
         attributes: ApplicationAttributes,
     ) -> Result<Self, SctkFlutterWindowCreateError> {
+        let requested_size = attributes.inner_size;
+        let max_size = attributes
+            .max_inner_size
+            .map(|size| Scale::default().to_logical_size::<u32>(size));
+        let default_size =
+            requested_size.unwrap_or(Size::Logical(LogicalSize::<f64>::new(1280.0, 720.0)));
+
+        let visibility = if attributes.start_hidden {
+            // The initial commit is still done, so that the engine renders
+            // the content at the configured size.
+            VisibilityState::Hidden {
+                is_configured: false,
+            }
+        } else {
+            VisibilityState::Visible
+        };
+
         let surface = compositor_state.create_surface(qh);
-        let window = xdg_shell_state.create_window(surface, WindowDecorations::ServerDefault, qh);
+        let role = match (layer_shell, attachment) {
+            (_, Some(attachment)) => {
+                SctkWindowRole::Attached(SctkAttachedSurface::new(attachment, surface, qh))
+            }
+            (Some(layer_shell), None) => SctkWindowRole::Layer(layer_shell.create_layer_surface(
+                qh,
+                surface,
+                attributes.app_id,
+                Scale::default().to_logical_size(default_size),
+            )),
+            (None, None) => {
+                let window =
+                    xdg_shell_state.create_window(surface, WindowDecorations::ServerDefault, qh);
+
+                if let Some(title) = attributes.title {
+                    window.set_title(title);
+                }
+
+                if let Some(app_id) = attributes.app_id {
+                    window.set_app_id(app_id);
+                }
+
+                let min_size = attributes.min_inner_size.map_or((256, 256), |size| {
+                    Scale::default().to_logical_size::<u32>(size).into()
+                });
+                window.set_min_size(Some(min_size));
+                if let Some(max_size) = max_size {
+                    window.set_max_size(Some(max_size.into()));
+                }
+
+                if attributes.maximized {
+                    window.set_maximized();
+                }
+
+                if attributes.fullscreen {
+                    window.set_fullscreen(None);
+                }
+
+                window.commit();
+
+                SctkWindowRole::Toplevel(window)
+            }
+        };
 
-        if let Some(title) = attributes.title {
-            window.set_title(title);
-        }
+        let (context, resource_context) = FlutterEGLContext::new_wayland_context(
+            role.wl_surface(),
+            Scale::default().to_physical_size(default_size),
+            &attributes.gpu_preference,
+            attributes.robust_context,
+            attributes.gl_debug_output,
+        )?;
 
-        if let Some(app_id) = attributes.app_id {
-            window.set_app_id(app_id);
+        match context.device() {
+            Some(device) => info!("Rendering on {device}"),
+            None => trace!("Rendering on the default device"),
         }
 
-        window.set_min_size(Some((256, 256)));
-        window.commit();
+        if attributes.robust_context && !context.is_robust() {
+            warn!("Robust contexts are not supported by the driver, using a regular one");
+        }
 
-        let default_size = attributes
-            .inner_size
-            .unwrap_or(Size::Logical(LogicalSize::<f64>::new(1280.0, 720.0)));
+        if attributes.gl_debug_output && !context.has_debug_output() {
+            warn!("GL debug output is not supported by the driver, not logging it");
+        }
 
-        let (context, resource_context) = FlutterEGLContext::new_wayland_context(
-            window.wl_surface(),
-            default_size.to_physical::<u32>(1.0),
-        )?;
+        let renderer = context.renderer().clone();
+        let is_hardware_accelerated = context.is_hardware_accelerated();
+        if is_hardware_accelerated {
+            info!("Rendering with {renderer}");
+        } else {
+            warn!("Rendering with {renderer}, which is not hardware accelerated");
+        }
 
         let context = Arc::new(Mutex::new(context));
         let resource_context = Arc::new(Mutex::new(resource_context));
 
         let inner = Arc::new_cyclic(|inner| SctkFlutterWindowInner {
             id: IMPLICIT_VIEW_ID,
-            window,
-            engine,
+            role,
             opengl_handler: SctkOpenGLHandler::new(
                 inner.clone(),
                 context.clone(),
                 resource_context,
             ),
-            compositor_handler: SctkCompositorHandler::new(inner.clone(), context),
+            compositor_handler: SctkCompositorHandler::new(
+                inner.clone(),
+                context,
+                engine.clone(),
+                qh.clone(),
+                overlay_globals,
+            ),
+            engine,
+            render_path: attributes.render_path,
             vsync_handler,
             resize_mutex: Default::default(),
             resize_status: Default::default(),
             pointers: Default::default(),
+            input_gate: Mutex::new(SctkInputGate::new(attributes.input_gate)),
             current_size: Default::default(),
-            current_scale_factor: RwLock::new(1.0),
+            current_scale_factor: Default::default(),
+            pixel_ratio_override: RwLock::new(
+                attributes
+                    .pixel_ratio
+                    .filter(|ratio| ratio.is_finite() && *ratio > 0.0),
+            ),
+            content_zoom: RwLock::new(1.0),
+            background_color: attributes.background_color,
+            renderer,
+            is_hardware_accelerated,
+            is_transparency_reduced: Default::default(),
+            has_presented_frame: Default::default(),
+            visibility: Mutex::new(visibility),
+            frame_stats: Default::default(),
             pending_size: Default::default(),
-            default_size,
+            metrics_retry: Default::default(),
+            request_sender,
+            sizing: RwLock::new(SctkWindowSizing::new(requested_size, default_size)),
+            max_size: RwLock::new(max_size),
+            window_state: RwLock::new(WindowState::empty()),
+            state_tracker: Default::default(),
+            idle_inhibit_manager,
+            idle_inhibitor: Default::default(),
+            parent: Default::default(),
+            output_scales: Default::default(),
+            color_info: Default::default(),
+            occlusion: Default::default(),
+            anchors: Default::default(),
+            qh: qh.clone(),
         });
 
-        Ok(Self { inner })
+        if attributes.start_hidden {
+            inner.vsync_handler.lock().surface_unmapped();
+        }
+
+        if attributes.full_damage || damage::is_full_damage_forced_by_env() {
+            info!("Forcing the full damage of the frames");
+            inner.opengl_handler.set_full_damage_forced(true);
+        }
+
+        Ok(Self {
+            inner,
+            letterbox: SctkLetterbox::new(solid_color_globals),
+        })
+    }
+
+    /// Repaint and present the whole window for every frame, instead of only
+    /// what changed (see `ApplicationBuilder::with_full_damage`). Only
+    /// affects the OpenGL render path.
+    pub fn set_full_damage(&self, enabled: bool) {
+        self.inner.opengl_handler.set_full_damage_forced(enabled);
     }
 
-    pub fn xdg_toplevel_id(&self) -> ObjectId {
-        self.inner.window.xdg_toplevel().id()
+    /// Returns `None` for layer surfaces and attached windows.
+    pub fn xdg_toplevel_id(&self) -> Option<ObjectId> {
+        self.xdg_toplevel().map(|xdg_toplevel| xdg_toplevel.id())
     }
 
     pub fn wl_surface(&self) -> WlSurface {
-        self.inner.window.wl_surface().clone()
+        self.inner.role.wl_surface().clone()
     }
 
     pub fn wl_surface_id(&self) -> ObjectId {
-        self.inner.window.wl_surface().id()
+        self.inner.role.wl_surface().id()
     }
 
-    pub fn xdg_toplevel(&self) -> XdgToplevel {
-        self.inner.window.xdg_toplevel().clone()
+    /// Registry of the anchor tokens handed out for this window's view.
+    pub(crate) fn anchors(&self) -> AnchorRegistry {
+        self.inner.anchors.clone()
     }
 
-    pub(crate) fn create_flutter_view(&self) -> FlutterView {
-        FlutterView::new_with_compositor(
-            self.inner.id,
-            self.inner.opengl_handler.clone(),
-            self.inner.compositor_handler.clone(),
-        )
+    /// Returns `None` for layer surfaces and attached windows.
+    pub fn xdg_toplevel(&self) -> Option<XdgToplevel> {
+        self.inner
+            .window()
+            .map(|window| window.xdg_toplevel().clone())
     }
 
-    pub(crate) fn scale_factor_changed(
-        &mut self,
-        _conn: &Connection,
-        surface: &WlSurface,
-        new_scale_factor: i32,
-    ) {
-        let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
+    /// Stream every frame presented in this window to the given callback. Use
+    /// `None` to stop reading frames back.
+    ///
+    /// Note: Frames are only read back when rendering through the compositor
+    /// (see [`RenderPath`]). The callback is executed on the *raster* thread
+    /// as each frame is presented, so it must not block.
+    pub fn set_frame_readback(&self, frame_readback: Option<SctkFrameReadback>) {
+        if frame_readback.is_some() && self.inner.render_path != RenderPath::Compositor {
+            warn!("Frame readback is only supported when rendering through the compositor");
+        }
 
-        self.inner.scale_internal_size(new_scale_factor.into());
+        self.inner
+            .compositor_handler
+            .set_frame_readback(frame_readback);
+    }
 
-        let Some(physical_size) = self.inner.non_zero_physical_size() else {
-            error!("Invalid physical size while handling `scale_factor_changed` event");
-            return;
-        };
+    pub(crate) fn window(&self) -> Option<&Window> {
+        self.inner.window()
+    }
 
-        self.inner.store_resize_status(ResizeState::ResizeStarted);
-        self.inner.store_pending_size(Some(physical_size));
+    pub(crate) fn attached_surface(&self) -> Option<&SctkAttachedSurface> {
+        self.inner.attached_surface()
+    }
 
-        // Note: Comment related to `opengl_handler.resize()` call from the
-        // `SctkFlutterWindow.configure()` method also applies here.
-        self.inner.opengl_handler.resize(physical_size);
-        surface.set_buffer_scale(new_scale_factor);
+    pub(crate) fn view_id(&self) -> FlutterViewId {
+        self.inner.id()
+    }
 
-        let display_id = self.inner.get_display_id().unwrap_or_default();
+    pub(crate) fn render_path(&self) -> RenderPath {
+        self.inner.render_path
+    }
 
-        if let Some(engine) = self.inner.engine.upgrade() {
-            engine.send_window_metrics_event(
-                self.inner.id,
-                usize::try_from(physical_size.width.get()).unwrap(),
-                usize::try_from(physical_size.height.get()).unwrap(),
-                new_scale_factor as f64,
-                display_id,
-            );
-        }
+    pub(crate) fn physical_size(&self) -> Option<PhysicalSize<u32>> {
+        let size = self.inner.non_zero_physical_size()?;
+        Some(PhysicalSize::new(size.width.get(), size.height.get()))
     }
 
-    pub(crate) fn configure(
-        &mut self,
-        _conn: &Connection,
-        configure: WindowConfigure,
-        _serial: u32,
-    ) {
-        let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
+    pub(crate) fn mirrors(&self) -> &SctkMirrors {
+        self.inner.compositor_handler.mirrors()
+    }
 
-        let new_logical_size = WindowLogicalSize::try_from(configure.new_size)
-            .map(|size| size.into())
-            .unwrap_or(self.inner.default_size);
+    /// Present the mirrors of this window which are ready for a new frame.
+    pub(crate) fn schedule_mirrors_present(&self) {
+        let Some(engine) = self.inner.engine.upgrade() else {
+            return;
+        };
 
-        self.inner.store_current_size(new_logical_size);
+        let mirrors = self.mirrors().clone();
+        engine.run_on_render_thread(move |_| mirrors.present());
+    }
 
-        let scale_factor = self.inner.load_current_scale_factor();
+    /// Stop mirroring this window to the |id| surface. The mirror resources
+    /// are released on the render thread.
+    pub(crate) fn remove_mirror(&self, id: &ObjectId) {
+        let Some(target) = self.mirrors().remove(id) else {
+            return;
+        };
 
-        let Some(physical_size) = new_logical_size.to_physical(scale_factor).non_zero() else {
-            error!("Unable to convert window configure event to a physical size");
+        let Some(engine) = self.inner.engine.upgrade() else {
+            warn!("[{}] Dropping mirror without releasing it, the engine is gone", id);
             return;
         };
 
-        self.inner.store_resize_status(ResizeState::ResizeStarted);
-        self.inner.store_pending_size(Some(physical_size));
+        let mirrors = self.mirrors().clone();
+        engine.run_on_render_thread(move |_| mirrors.release(target));
+    }
 
-        // The resize logic is based on Flutter's Windows embedder
-        // implementation. However, one notable difference between the two is
-        // that the Windows implementation resizes the EGL surface *after*
-        // sending the window metrics event to the engine (e.g.: as part of the
-        // `CompositorOpenGL::Present` callback [0]), while flutter-sctk's
-        // implementation resizes it *prior* to sending the window metrics
-        // event.
-        //
-        // This change in flutter-sctk's implementation was done to avoid some
-        // visual glitches that were observed when the EGL surface was resized
-        // too late (e.g.: as part of the `on_frame_generated` callback).
-        //
-        // TODO: Investigate when is the *correct* time to resize the EGL
-        // surface and update the implementation if needed.
-        //
-        // [0]: https://github.com/flutter/engine/blob/605b3f3/shell/platform/windows/flutter_windows_view.cc#L701-L711
-        self.inner.opengl_handler.resize(physical_size);
+    /// Unmap the window (e.g.: for tray applications, see
+    /// `ApplicationAttributes::start_hidden`). The engine keeps running, but
+    /// its frames are discarded until the window is shown again.
+    pub fn hide(&self) {
+        self.inner.hide();
+    }
 
-        let display_id = self.inner.get_display_id().unwrap_or_default();
+    /// Map the window again, with the next frame of the engine once the
+    /// compositor configured it.
+    pub fn show(&self) {
+        self.inner.show();
+    }
 
-        if let Some(engine) = self.inner.engine.upgrade() {
-            engine.send_window_metrics_event(
-                self.inner.id,
-                usize::try_from(physical_size.width.get()).unwrap(),
-                usize::try_from(physical_size.height.get()).unwrap(),
-                scale_factor,
-                display_id,
-            );
-        }
+    /// Whether the window is shown, or about to be (i.e.: `show` was called
+    /// last).
+    pub fn is_visible(&self) -> bool {
+        self.inner.is_visible()
     }
 
-    pub(crate) fn surface_outputs_changed(&mut self, _conn: &Connection, _surface: &WlSurface) {
-        let scale_factor = self.inner.load_current_scale_factor();
+    /// Invoked for each configure event, after the new size was handled.
+    pub(crate) fn surface_configured(&self) {
+        self.inner.surface_configured();
+    }
 
-        let Some(physical_size) = self.inner.non_zero_physical_size() else {
-            error!("Invalid physical size while handling `surface_outputs_changed` event");
+    /// Whether the compositor supports inhibiting idle actions (e.g.: screen
+    /// blanking) while the window is visible.
+    pub fn supports_idle_inhibit(&self) -> bool {
+        self.inner.idle_inhibit_manager.is_some()
+    }
+
+    /// Prevent the screen from blanking or locking while the window is
+    /// visible, using the `zwp_idle_inhibit_manager_v1` protocol. This is a
+    /// no-op when the protocol is not supported by the compositor.
+    pub fn set_idle_inhibited(&self, inhibited: bool) {
+        let Some(manager) = &self.inner.idle_inhibit_manager else {
+            warn!("Ignoring idle inhibit request because `zwp_idle_inhibit_manager_v1` is not supported");
             return;
         };
 
-        let display_id = self.inner.get_display_id().unwrap_or_default();
-
-        if let Some(engine) = self.inner.engine.upgrade() {
-            engine.send_window_metrics_event(
-                self.inner.id,
-                usize::try_from(physical_size.width.get()).unwrap(),
-                usize::try_from(physical_size.height.get()).unwrap(),
-                scale_factor,
-                display_id,
-            );
+        let mut idle_inhibitor = self.inner.idle_inhibitor.lock().unwrap();
+        match (inhibited, idle_inhibitor.take()) {
+            (true, None) => {
+                let surface = self.inner.role.wl_surface();
+                *idle_inhibitor = Some(manager.create_inhibitor(surface, &self.inner.qh, ()));
+            }
+            (true, Some(inhibitor)) => *idle_inhibitor = Some(inhibitor),
+            (false, Some(inhibitor)) => inhibitor.destroy(),
+            (false, None) => {}
         }
     }
 
-    pub(crate) fn pointer_event(
-        &mut self,
-        _conn: &Connection,
-        pointer: &WlPointer,
+    pub fn is_idle_inhibited(&self) -> bool {
+        self.inner.idle_inhibitor.lock().unwrap().is_some()
+    }
+
+    /// Whether the window is maximized, as of the latest configure event
+    /// (i.e.: the state reported to Dart). Always false for layer surfaces.
+    pub fn is_maximized(&self) -> bool {
+        self.inner
+            .load_window_state()
+            .contains(WindowState::MAXIMIZED)
+    }
+
+    /// Whether the window is fullscreen, as of the latest configure event.
+    /// Always false for layer surfaces.
+    pub fn is_fullscreen(&self) -> bool {
+        self.inner
+            .load_window_state()
+            .contains(WindowState::FULLSCREEN)
+    }
+
+    /// Whether the window is activated by the compositor (i.e.: it has the
+    /// focus), as of the latest configure event. This drives the lifecycle
+    /// state reported to Dart, so layer surfaces, which don't have such a
+    /// state, are always considered focused.
+    pub fn is_focused(&self) -> bool {
+        match self.window() {
+            Some(_) => self
+                .inner
+                .load_window_state()
+                .contains(WindowState::ACTIVATED),
+            None => true,
+        }
+    }
+
+    /// Make the window a transient child of |parent| (e.g.: a dialog of its
+    /// owner window), so that the compositor stacks it above its parent, and
+    /// usually positions it relative to it. The parent can be changed at any
+    /// time, and `None` makes it a regular window again.
+    ///
+    /// Only supported by xdg windows.
+    pub fn set_parent(&self, parent: Option<&SctkFlutterWindow>) -> Result<(), SctkSetParentError> {
+        let window = self.window().ok_or(SctkSetParentError::NotAWindow)?;
+
+        let parent_window = match parent {
+            Some(parent) => {
+                if parent.has_ancestor(self) {
+                    return Err(SctkSetParentError::Cycle);
+                }
+                Some(parent.window().ok_or(SctkSetParentError::NotAWindow)?)
+            }
+            None => None,
+        };
+
+        // Not double-buffered, so there's nothing to commit.
+        window.set_parent(parent_window);
+        *self.inner.parent.lock().unwrap() = parent.map(|parent| Arc::downgrade(&parent.inner));
+        Ok(())
+    }
+
+    /// Whether |ancestor| is this window, or one of its (transitive) parents.
+    fn has_ancestor(&self, ancestor: &SctkFlutterWindow) -> bool {
+        let mut window = Some(self.inner.clone());
+        while let Some(current) = window {
+            if Arc::ptr_eq(&current, &ancestor.inner) {
+                return true;
+            }
+            window = current.parent.lock().unwrap().as_ref().and_then(Weak::upgrade);
+        }
+        false
+    }
+
+    pub(crate) fn scale_factor(&self) -> Scale {
+        self.inner.load_current_scale_factor()
+    }
+
+    /// Scales of the outputs the window currently touches, sorted and without
+    /// duplicates. Unlike the scale factor of the window, which follows a
+    /// single output, this tells which asset variants may be needed soon.
+    pub fn current_output_scales(&self) -> Vec<f64> {
+        let mut scales = SctkOutputScales::default();
+        scales
+            .update(self.output_buffer_scales())
+            .unwrap_or_default()
+    }
+
+    /// Store the current output scales. Returns them if they have changed
+    /// since the last call.
+    pub(crate) fn update_output_scales(&self) -> Option<Vec<f64>> {
+        self.inner
+            .output_scales
+            .lock()
+            .unwrap()
+            .update(self.output_buffer_scales())
+    }
+
+    /// Buffer scale of the window according to the outputs it touches (i.e.:
+    /// the highest of their scales). `None` when the compositor tells the
+    /// preferred scale of the surface itself (`wl_surface` v6), or when the
+    /// window touches no output.
+    pub(crate) fn preferred_output_buffer_scale(&self) -> Option<i32> {
+        if self.inner.wl_surface().version() >= 6 {
+            return None;
+        }
+        self.output_buffer_scales().into_iter().max()
+    }
+
+    fn output_buffer_scales(&self) -> Vec<i32> {
+        self.outputs()
+            .iter()
+            .filter_map(|output| output.data::<OutputData>().map(OutputData::scale_factor))
+            .collect()
+    }
+
+    /// Outputs the window currently touches.
+    pub(crate) fn outputs(&self) -> Vec<WlOutput> {
+        self.inner
+            .wl_surface()
+            .data::<SurfaceData>()
+            .map(|data| data.outputs().collect())
+            .unwrap_or_default()
+    }
+
+    /// Color characteristics of the outputs the window touches: wide gamut
+    /// as soon as one of them is. Unknown unless the compositor supports
+    /// `wp_color_management_v1`.
+    pub fn color_info(&self) -> ColorInfo {
+        self.inner.load_color_info()
+    }
+
+    /// Whether the window is seen by the user, as estimated from what the
+    /// compositor tells (see `crate::occlusion`).
+    pub fn visibility(&self) -> Visibility {
+        self.inner.load_visibility()
+    }
+
+    pub(crate) fn occlusion(&self) -> MutexGuard<'_, SctkOcclusionEstimator> {
+        self.inner.occlusion.lock().unwrap()
+    }
+
+    /// What the compositor tells about the visibility of the surface. Until
+    /// the first frame is presented, the surface is on no output yet.
+    pub(crate) fn surface_visibility(&self) -> SctkSurfaceVisibility {
+        SctkSurfaceVisibility {
+            mapped: self.is_visible(),
+            suspended: self.inner.load_window_state().contains(WindowState::SUSPENDED),
+            on_outputs: !self.has_presented_frame() || !self.outputs().is_empty(),
+        }
+    }
+
+    /// Returns whether |color_info| differs from the stored one.
+    pub(crate) fn store_color_info(&self, color_info: ColorInfo) -> bool {
+        let mut current = self.inner.color_info.lock().unwrap();
+        if *current == color_info {
+            return false;
+        }
+        *current = color_info;
+        true
+    }
+
+    /// Report |pixel_ratio| to the engine instead of the scale factor of the
+    /// surface, or restore the latter when `None`. Returns whether the ratio
+    /// reported to the engine has changed.
+    ///
+    /// The window keeps its physical size: a lower ratio makes the content
+    /// smaller (i.e.: more fits on screen), while a higher one enlarges it
+    /// (e.g.: for accessibility zoom).
+    pub(crate) fn set_pixel_ratio_override(&self, pixel_ratio: Option<f64>) -> bool {
+        let scale_factor = self.inner.load_current_scale_factor();
+        let previous = self.inner.load_pixel_ratio(scale_factor);
+
+        *self.inner.pixel_ratio_override.write().unwrap() = pixel_ratio;
+
+        self.inner.load_pixel_ratio(scale_factor) != previous
+    }
+
+    pub(crate) fn pixel_ratio_override(&self) -> Option<f64> {
+        *self.inner.pixel_ratio_override.read().unwrap()
+    }
+
+    /// Zoom the content by |zoom| (see `crate::zoom`). Returns whether the
+    /// zoom has changed.
+    pub(crate) fn set_content_zoom(&self, zoom: f64) -> bool {
+        let mut content_zoom = self.inner.content_zoom.write().unwrap();
+        let has_changed = *content_zoom != zoom;
+        *content_zoom = zoom;
+        has_changed
+    }
+
+    pub(crate) fn content_zoom(&self) -> f64 {
+        self.inner.load_content_zoom()
+    }
+
+    /// Make the background opaque while |reduced| is set. Returns whether the
+    /// background has changed.
+    pub(crate) fn set_transparency_reduced(&self, reduced: bool) -> bool {
+        let was_reduced = self
+            .inner
+            .is_transparency_reduced
+            .swap(reduced, Ordering::Relaxed);
+        was_reduced != reduced && self.inner.background_color >> 24 != 0xff
+    }
+
+    /// Ratio between the surface-local coordinates and the logical ones of
+    /// the view (e.g.: for positioning popups relative to the content).
+    pub(crate) fn content_scale(&self) -> f64 {
+        let scale_factor = self.inner.load_current_scale_factor();
+        let pixel_ratio = self.inner.load_pixel_ratio(scale_factor);
+        scale_factor.content_scale(pixel_ratio)
+    }
+
+    /// Surface-local position of a pointer within the window, if any.
+    #[cfg(feature = "emulated-input")]
+    pub(crate) fn pointer_position(&self) -> Option<dpi::LogicalPosition<f64>> {
+        let pointers = self.inner.pointers.read().unwrap();
+        pointers
+            .values()
+            .find(|pointer| pointer.is_inside())
+            .map(|pointer| pointer.position)
+    }
+
+    pub(crate) fn has_presented_frame(&self) -> bool {
+        self.inner.has_presented_frame.load(Ordering::Relaxed)
+    }
+
+    /// The renderer of the window's context, queried when it was created.
+    pub fn renderer(&self) -> &RendererInfo {
+        &self.inner.renderer
+    }
+
+    /// Whether the window renders on a GPU, rather than with a software
+    /// rasterizer (e.g.: llvmpipe).
+    pub fn is_hardware_accelerated(&self) -> bool {
+        self.inner.is_hardware_accelerated
+    }
+
+    /// Timestamps of the last frame events, see [`FrameStats`]. Cheap enough
+    /// to be polled for every frame.
+    pub fn last_frame_stats(&self) -> FrameStats {
+        self.inner.frame_stats.load()
+    }
+
+    // Note: This callback is executed on the *platform* thread.
+    pub(crate) fn on_vsync(&self, frame_start_time_nanos: u64) {
+        self.inner.frame_stats.vsync(frame_start_time_nanos);
+    }
+
+    /// Draw |screen| over the whole window, while the engine isn't rendering
+    /// to it (see `crate::failure`).
+    pub(crate) fn present_failure_screen(&self, screen: &SctkFailureScreen) -> bool {
+        let Some(_visible) = self.inner.lock_visible() else {
+            // The failure is still reported, but drawing would map the
+            // window.
+            trace!("Not drawing the failure screen of a hidden window");
+            return true;
+        };
+
+        let scale_factor = self.inner.load_current_scale_factor();
+        // Until the first configure is applied, the surface has the default
+        // size.
+        let size = match self.inner.non_zero_physical_size() {
+            Some(size) => PhysicalSize::new(size.width.get(), size.height.get()),
+            None => scale_factor.to_physical_size(self.inner.load_default_size()),
+        };
+
+        let scale = scale_factor.buffer_scale().max(1) as u32;
+        let pixels = screen.render(size.width, size.height, scale);
+        self.inner.compositor_handler.present_pixels(&pixels, size)
+    }
+
+    pub(crate) fn create_window_handler(
+        &self,
+        sender: Sender<SctkApplicationRequest>,
+        engine_id: SctkEngineId,
+    ) -> SctkWindowHandler {
+        SctkWindowHandler::new(Arc::downgrade(&self.inner), sender, engine_id)
+    }
+
+    pub(crate) fn create_ready_handler(
+        &self,
+        sender: Sender<SctkApplicationRequest>,
+    ) -> SctkReadyHandler {
+        SctkReadyHandler::new(Arc::downgrade(&self.inner), sender)
+    }
+
+    pub(crate) fn input_gate(&self) -> MutexGuard<'_, SctkInputGate> {
+        self.inner.input_gate()
+    }
+
+    /// Returns |input| if it must be sent right away, i.e.: unless it is
+    /// buffered until the app is ready (see `crate::input_gate`).
+    pub(crate) fn gate_input(&self, input: SctkGatedInput) -> Option<SctkGatedInput> {
+        self.inner.gate_input(input)
+    }
+
+    pub(crate) fn create_logging_handler(
+        &self,
+        vsync_handler: Arc<parking_lot::Mutex<SctkVsyncHandler>>,
+    ) -> SctkLoggingHandler {
+        SctkLoggingHandler::new(Arc::downgrade(&self.inner), vsync_handler)
+    }
+
+    pub(crate) fn recommended_max_size(&self) -> Option<SizeParams> {
+        self.inner.recommended_max_size()
+    }
+
+    pub(crate) fn store_window_state(&self, state: WindowState) {
+        *self.inner.window_state.write().unwrap() = state;
+    }
+
+    pub(crate) fn state_tracker(&self) -> Arc<Mutex<SctkWindowStateTracker>> {
+        self.inner.state_tracker.clone()
+    }
+
+    /// Store the bounds suggested by the compositor and return whether they
+    /// have changed.
+    ///
+    /// Bounds received prior to the first configure event are used to fit the
+    /// initial window size. Later changes are only stored (and reported to
+    /// Dart by the caller), but they don't resize the window.
+    pub(crate) fn update_suggested_bounds(&self, bounds: Option<(u32, u32)>) -> bool {
+        let is_configured = self.inner.load_current_size().is_some();
+        let scale_factor = self.inner.load_current_scale_factor();
+        self.inner.sizing.write().unwrap().update_suggested_bounds(
+            bounds,
+            is_configured,
+            scale_factor,
+        )
+    }
+
+    /// Fit the initial window size to the size of the output the window is
+    /// likely to be shown on. Only used when the compositor did not suggest
+    /// any bounds prior to the first configure event.
+    pub(crate) fn fit_default_size_to_output(&self, output_size: LogicalSize<u32>) {
+        let is_configured = self.inner.load_current_size().is_some();
+        let scale_factor = self.inner.load_current_scale_factor();
+        self.inner
+            .sizing
+            .write()
+            .unwrap()
+            .fit_default_size_to_output(output_size, is_configured, scale_factor);
+    }
+
+    pub(crate) fn create_flutter_view(&self) -> FlutterView {
+        match self.inner.render_path {
+            RenderPath::Compositor => FlutterView::new_with_compositor(
+                self.inner.id,
+                self.inner.opengl_handler.clone(),
+                self.inner.compositor_handler.clone(),
+            ),
+            RenderPath::OpenGL => FlutterView::new_without_compositor(
+                self.inner.id,
+                self.inner.opengl_handler.clone(),
+            ),
+        }
+    }
+
+    pub(crate) fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        surface: &WlSurface,
+        new_scale_factor: i32,
+    ) {
+        let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
+
+        let new_scale_factor = Scale::from_buffer_scale(new_scale_factor);
+        self.inner.scale_internal_size(new_scale_factor);
+
+        let Some(physical_size) = self.inner.non_zero_physical_size() else {
+            error!("Invalid physical size while handling `scale_factor_changed` event");
+            return;
+        };
+
+        self.inner.store_resize_status(ResizeState::ResizeStarted);
+        self.inner.store_pending_size(Some(physical_size));
+
+        // Note: Comment related to `opengl_handler.resize()` call from the
+        // `SctkFlutterWindow.configure()` method also applies here.
+        self.inner.opengl_handler.resize(physical_size);
+        surface.set_buffer_scale(new_scale_factor.buffer_scale());
+
+        self.send_window_metrics(physical_size, new_scale_factor);
+    }
+
+    /// Handle the configure event of either an xdg window or a layer
+    /// surface (both are acked by sctk prior to dispatching them).
+    pub(crate) fn configure(&mut self, _conn: &Connection, new_size: ConfigureSize) {
+        // Locked through a clone of `inner`, as the letterbox is updated
+        // while holding the lock.
+        let inner = self.inner.clone();
+        let _resize_mutex = inner.resize_mutex.lock().unwrap();
+
+        let scale_factor = self.inner.load_current_scale_factor();
+
+        // Missing dimensions mean that the size should be picked by the
+        // client. Keep the current size when there is one, so that transient
+        // configures (e.g.: during tiling transitions) don't resize the window.
+        let new_logical_size = resolve_configure_size(
+            new_size,
+            self.inner
+                .load_current_size()
+                .map(|size| scale_factor.to_logical_size(size)),
+            scale_factor.to_logical_size(self.inner.load_default_size()),
+        );
+        let new_logical_size = self.letterbox_fullscreen_content(new_logical_size);
+
+        let max_dimension = self.inner.max_surface_dimension();
+        let new_physical_size = scale_factor.to_physical_size(new_logical_size);
+        if clamp_physical_size(new_physical_size, max_dimension) != new_physical_size {
+            warn!(
+                "Window configure size {}x{} exceeds the maximum surface size ({}), clamping it",
+                new_physical_size.width, new_physical_size.height, max_dimension,
+            );
+        }
+
+        self.inner.store_current_size(new_logical_size.into());
+
+        // Neither the EGL surface nor the engine accept empty sizes. Do not
+        // start a resize for a size that is not propagated, otherwise the
+        // resize would wait forever for a matching frame.
+        let Some(physical_size) = self.inner.non_zero_physical_size() else {
+            warn!("Ignoring window configure event with an empty size until a valid size arrives");
+            return;
+        };
+
+        self.inner.store_resize_status(ResizeState::ResizeStarted);
+        self.inner.store_pending_size(Some(physical_size));
+
+        // The resize logic is based on Flutter's Windows embedder
+        // implementation. However, one notable difference between the two is
+        // that the Windows implementation resizes the EGL surface *after*
+        // sending the window metrics event to the engine (e.g.: as part of the
+        // `CompositorOpenGL::Present` callback [0]), while flutter-sctk's
+        // implementation resizes it *prior* to sending the window metrics
+        // event.
+        //
+        // This change in flutter-sctk's implementation was done to avoid some
+        // visual glitches that were observed when the EGL surface was resized
+        // too late (e.g.: as part of the `on_frame_generated` callback).
+        //
+        // TODO: Investigate when is the *correct* time to resize the EGL
+        // surface and update the implementation if needed.
+        //
+        // [0]: https://github.com/flutter/engine/blob/605b3f3/shell/platform/windows/flutter_windows_view.cc#L701-L711
+        self.inner.opengl_handler.resize(physical_size);
+
+        self.send_window_metrics(physical_size, scale_factor);
+    }
+
+    /// Keep the content of a fullscreen window within its maximum size. It is
+    /// then centered in the |configured| area, with bars of the background
+    /// color around it.
+    fn letterbox_fullscreen_content(&mut self, configured: LogicalSize<u32>) -> LogicalSize<u32> {
+        let max_size = self.inner.load_max_size().filter(|_| self.is_fullscreen());
+        let content = fit_fullscreen_content(configured, max_size);
+        self.letterbox.update(
+            self.inner.wl_surface(),
+            &self.inner.qh,
+            configured,
+            content,
+            letterbox_color(self.inner.background_color),
+        );
+        content
+    }
+
+    pub(crate) fn surface_outputs_changed(&mut self, _conn: &Connection, _surface: &WlSurface) {
+        self.send_current_window_metrics();
+    }
+
+    /// Re-send the current window metrics to the engine.
+    pub(crate) fn send_current_window_metrics(&self) {
+        let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
+
+        let scale_factor = self.inner.load_current_scale_factor();
+
+        let Some(physical_size) = self.inner.non_zero_physical_size() else {
+            error!("Invalid physical size while sending current window metrics");
+            return;
+        };
+
+        self.send_window_metrics(physical_size, scale_factor);
+    }
+
+    /// Resend the current window metrics after the engine rejected the
+    /// previous ones. The resend goes through the same resize steps as a
+    /// configure event, and a frame is scheduled once the metrics have been
+    /// accepted.
+    pub(crate) fn retry_window_metrics(&self) {
+        let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
+
+        // Metrics sent in the meantime (e.g.: by a new configure event) have
+        // been accepted already.
+        let Some(retry) = self.inner.metrics_retry.lock().unwrap().take() else {
+            return;
+        };
+
+        let scale_factor = self.inner.load_current_scale_factor();
+        let Some(physical_size) = self.inner.non_zero_physical_size() else {
+            warn!("Window has no valid size yet, postponing the window metrics resend");
+            self.schedule_metrics_retry(PendingMetricsRetry {
+                attempts: retry.attempts + 1,
+                ..retry
+            });
+            return;
+        };
+
+        self.inner.store_resize_status(ResizeState::ResizeStarted);
+        self.inner.store_pending_size(Some(physical_size));
+        self.inner.opengl_handler.resize(physical_size);
+
+        if !self.send_window_metrics_attempt(physical_size, scale_factor, retry.attempts + 1) {
+            return;
+        }
+
+        warn!(
+            view_id = self.inner.id,
+            rejected_width = retry.rejected_size.width.get(),
+            rejected_height = retry.rejected_size.height.get(),
+            rejected_scale_factor = retry.rejected_scale_factor.get(),
+            width = physical_size.width.get(),
+            height = physical_size.height.get(),
+            scale_factor = scale_factor.get(),
+            attempts = retry.attempts + 1,
+            "Window metrics accepted after resend"
+        );
+
+        if let Some(engine) = self.inner.engine.upgrade() {
+            engine.schedule_frame();
+        }
+    }
+
+    /// Note: The caller must hold the resize mutex.
+    fn send_window_metrics(&self, physical_size: PhysicalSize<NonZeroU32>, scale_factor: Scale) {
+        self.send_window_metrics_attempt(physical_size, scale_factor, 0);
+    }
+
+    /// Send the window metrics to the engine and return whether they have
+    /// been accepted. `attempts` is the number of resends already made for
+    /// previously rejected metrics.
+    fn send_window_metrics_attempt(
+        &self,
+        physical_size: PhysicalSize<NonZeroU32>,
+        scale_factor: Scale,
+        attempts: u32,
+    ) -> bool {
+        let Some(engine) = self.inner.engine.upgrade() else {
+            return false;
+        };
+
+        let display_id = self.inner.get_display_id().unwrap_or_default();
+
+        let pixel_ratio = self.inner.load_pixel_ratio(scale_factor);
+        let result = engine.send_window_metrics_event(
+            self.inner.id,
+            PhysicalSize::new(physical_size.width.get(), physical_size.height.get()),
+            pixel_ratio,
+            display_id,
+        );
+
+        match result {
+            Ok(()) => {
+                *self.inner.metrics_retry.lock().unwrap() = None;
+                self.invalidate_anchors(ViewGeometry {
+                    width: physical_size.width.get(),
+                    height: physical_size.height.get(),
+                    pixel_ratio,
+                });
+                true
+            }
+            Err(WindowMetricsError::InvalidMetrics) => {
+                warn!(
+                    view_id = self.inner.id,
+                    width = physical_size.width.get(),
+                    height = physical_size.height.get(),
+                    scale_factor = scale_factor.get(),
+                    display_id,
+                    attempts,
+                    "Engine rejected the window metrics"
+                );
+
+                // No frame is ever generated for rejected metrics. Abort the
+                // resize so that frames of the current size keep being
+                // presented until the metrics are accepted.
+                self.inner.store_resize_status(ResizeState::Done);
+                self.inner.store_pending_size(None);
+
+                self.schedule_metrics_retry(PendingMetricsRetry {
+                    attempts,
+                    rejected_size: physical_size,
+                    rejected_scale_factor: scale_factor,
+                });
+                false
+            }
+            Err(err) => {
+                error!("Failed to send window metrics: {}", err);
+                false
+            }
+        }
+    }
+
+    /// Invalidate the anchor tokens created for the previous metrics. The
+    /// app is notified from the event loop, after the new metrics.
+    fn invalidate_anchors(&self, geometry: ViewGeometry) {
+        let Some(invalidated) = self.inner.anchors.update_view(self.inner.id, geometry) else {
+            return;
+        };
+
+        let request = SctkApplicationRequest::InvalidateAnchors {
+            surface: self.wl_surface(),
+            invalidated,
+        };
+        if self.inner.request_sender.send(request).is_err() {
+            warn!("Not reporting the invalidated anchors because the event loop is gone");
+        }
+    }
+
+    fn schedule_metrics_retry(&self, retry: PendingMetricsRetry) {
+        let mut metrics_retry = self.inner.metrics_retry.lock().unwrap();
+
+        // Debounce: the scheduled resend always uses the latest geometry.
+        if let Some(pending) = metrics_retry.as_mut() {
+            pending.rejected_size = retry.rejected_size;
+            pending.rejected_scale_factor = retry.rejected_scale_factor;
+            return;
+        }
+
+        if retry.attempts >= MAX_METRICS_RETRIES {
+            error!(
+                "Giving up resending window metrics after {} rejected attempts",
+                retry.attempts
+            );
+            return;
+        }
+
+        let delay = metrics_retry_delay(retry.attempts);
+        let request = SctkApplicationRequest::RetryWindowMetrics {
+            surface: self.inner.wl_surface().clone(),
+            delay,
+        };
+        if self.inner.request_sender.send(request).is_err() {
+            warn!("Not resending window metrics because the event loop is gone");
+            return;
+        }
+
+        *metrics_retry = Some(retry);
+    }
+
+    /// Until the engine is running, only the pointer state is tracked. The
+    /// pointers within the window are added once it is (see
+    /// `add_entered_pointers`).
+    pub(crate) fn pointer_event(
+        &mut self,
+        _conn: &Connection,
+        pointer: &WlPointer,
         event: &PointerEvent,
+        is_engine_running: bool,
+        scroll: SctkScrollSettings,
     ) {
-        let sctk_pointer_event = {
+        let events = {
             let mut pointers = self.inner.pointers.write().unwrap();
             let pointer = pointers
                 .entry(pointer.id())
-                .or_insert_with(|| Pointer::new(pointer.id().protocol_id() as i32));
+                .or_insert_with(|| Pointer::new(mouse_device_id(pointer)));
 
-            match event.kind {
-                PointerEventKind::Press { .. } => pointer.increment_pressed(),
-                PointerEventKind::Release { .. } => pointer.decrement_pressed(),
-                _ => {}
+            pointer.track(event);
+            if !is_engine_running {
+                return;
             }
 
             let scale_factor = self.inner.load_current_scale_factor();
-            SctkPointerEvent::new(self.inner.id, event.clone(), *pointer, scale_factor)
+            let events = pointer_events(self.inner.id, event, pointer, scale_factor, scroll);
+            let Ok(events) = events else {
+                error!("Unable to convert wayland pointer event to flutter pointer event");
+                return;
+            };
+            events
         };
 
-        let Ok(event) = FlutterPointerEvent::try_from(sctk_pointer_event) else {
-            error!("Unable to convert wayland pointer event to flutter pointer event");
+        self.send_pointer_events(events);
+    }
+
+    /// Send the |phases| of the touch |point|, which went down on this window.
+    pub(crate) fn touch_events(
+        &self,
+        point: &TouchPoint<ObjectId>,
+        phases: &[FlutterPointerPhase],
+    ) {
+        let scale_factor = self.inner.load_current_scale_factor();
+        let events = phases
+            .iter()
+            .map(|&phase| touch_event(self.inner.id, point, phase, scale_factor))
+            .collect();
+
+        self.send_pointer_events(events);
+    }
+
+    /// Add the pointers which entered the window before the engine was
+    /// running, and hover them at their current position.
+    pub(crate) fn add_entered_pointers(&self) {
+        let scale_factor = self.inner.load_current_scale_factor();
+        let events: Vec<_> = self
+            .inner
+            .pointers
+            .write()
+            .unwrap()
+            .values_mut()
+            .flat_map(|pointer| pointer.enter_events(self.inner.id, scale_factor))
+            .collect();
+
+        self.send_pointer_events(events);
+    }
+
+    /// Remove the |pointer| from the view, e.g.: when it was forwarded from a
+    /// mirror window which has been destroyed under it.
+    pub(crate) fn remove_pointer(&self, pointer: &ObjectId) {
+        let scale_factor = self.inner.load_current_scale_factor();
+        let event = self
+            .inner
+            .pointers
+            .write()
+            .unwrap()
+            .get_mut(pointer)
+            .and_then(|pointer| pointer.remove_event(self.inner.id, scale_factor));
+
+        self.send_pointer_events(event.into_iter().collect());
+    }
+
+    /// Remove all the pointers from the view, before the window is destroyed
+    /// (no leave event is received in this case).
+    pub(crate) fn remove_pointers(&self) {
+        let scale_factor = self.inner.load_current_scale_factor();
+        let events: Vec<_> = self
+            .inner
+            .pointers
+            .write()
+            .unwrap()
+            .values_mut()
+            .filter_map(|pointer| pointer.remove_event(self.inner.id, scale_factor))
+            .collect();
+
+        self.send_pointer_events(events);
+    }
+
+    /// Send |events| to the engine, unless they are buffered until the app is
+    /// ready (see `crate::input_gate`).
+    fn send_pointer_events(&self, events: Vec<FlutterPointerEvent>) {
+        if events.is_empty() {
             return;
-        };
+        }
 
         let Some(engine) = self.inner.engine.upgrade() else {
-            error!("Unable to upgrade weak engine while sending pointer event");
+            error!("Unable to upgrade weak engine while sending pointer events");
             return;
         };
 
-        engine.send_pointer_event(event);
+        for event in events {
+            if let Some(SctkGatedInput::Pointer(event)) =
+                self.inner.gate_input(SctkGatedInput::Pointer(event))
+            {
+                engine.send_pointer_event(event);
+            }
+        }
+    }
+
+    /// Send a scale signal for a pinch gesture update of |pointer|.
+    pub(crate) fn pinch_scale_event(&mut self, pointer: &WlPointer, scale: f64) {
+        let event = {
+            let pointers = self.inner.pointers.read().unwrap();
+            let Some(pointer) = pointers.get(&pointer.id()) else {
+                warn!("Ignoring pinch gesture for a pointer outside of the window");
+                return;
+            };
+
+            let scale_factor = self.inner.load_current_scale_factor();
+            pinch_scale_event(self.inner.id, *pointer, scale_factor, scale)
+        };
+
+        self.send_pointer_events(vec![event]);
+    }
+}
+
+/// Compute the initial window size for the available area. An explicitly
+/// requested size is only shrunk to fit within |bounds|.
+fn fit_size_to_bounds(
+    requested_size: Option<LogicalSize<f64>>,
+    bounds: LogicalSize<f64>,
+) -> LogicalSize<f64> {
+    match requested_size {
+        Some(size) => LogicalSize::new(
+            size.width.min(bounds.width),
+            size.height.min(bounds.height),
+        ),
+        None => LogicalSize::new(
+            default_dimension(
+                bounds.width,
+                DEFAULT_SIZE_WIDTH_RATIO,
+                MIN_DEFAULT_SIZE.width,
+                MAX_DEFAULT_SIZE.width,
+            ),
+            default_dimension(
+                bounds.height,
+                DEFAULT_SIZE_HEIGHT_RATIO,
+                MIN_DEFAULT_SIZE.height,
+                MAX_DEFAULT_SIZE.height,
+            ),
+        ),
+    }
+}
+
+fn default_dimension(available: f64, ratio: f64, min: f64, max: f64) -> f64 {
+    (available * ratio).clamp(min, max).min(available)
+}
+
+/// Size of a window before its first configure event, and the bounds
+/// suggested by the compositor.
+#[derive(Debug)]
+struct SctkWindowSizing {
+    requested_size: Option<Size>,
+    default_size: Size,
+    suggested_bounds: Option<LogicalSize<u32>>,
+}
+
+impl SctkWindowSizing {
+    fn new(requested_size: Option<Size>, default_size: Size) -> Self {
+        Self {
+            requested_size,
+            default_size,
+            suggested_bounds: None,
+        }
+    }
+
+    /// Store the suggested |bounds| and return whether they have changed.
+    /// The default size is only fitted to them until the window
+    /// |is_configured|.
+    fn update_suggested_bounds(
+        &mut self,
+        bounds: Option<(u32, u32)>,
+        is_configured: bool,
+        scale_factor: Scale,
+    ) -> bool {
+        // A `0x0` size means that the bounds are unknown.
+        let bounds = bounds
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .map(|(width, height)| LogicalSize::new(width, height));
+
+        let changed = self.suggested_bounds != bounds;
+        self.suggested_bounds = bounds;
+
+        if let Some(bounds) = bounds {
+            if !is_configured {
+                self.fit_default_size_to_bounds(bounds, scale_factor);
+            }
+        }
+
+        changed
+    }
+
+    fn fit_default_size_to_output(
+        &mut self,
+        output_size: LogicalSize<u32>,
+        is_configured: bool,
+        scale_factor: Scale,
+    ) {
+        if is_configured || self.suggested_bounds.is_some() {
+            return;
+        }
+
+        if output_size.width > 0 && output_size.height > 0 {
+            self.fit_default_size_to_bounds(output_size, scale_factor);
+        }
+    }
+
+    /// Fit the size used for the initial configure event within |bounds|
+    /// (e.g.: the bounds suggested by the compositor). When no size was
+    /// explicitly requested, a fraction of the bounds is used instead of the
+    /// hardcoded default size.
+    fn fit_default_size_to_bounds(&mut self, bounds: LogicalSize<u32>, scale_factor: Scale) {
+        let requested_size = self
+            .requested_size
+            .map(|size| scale_factor.to_logical_size::<f64>(size));
+
+        let size = fit_size_to_bounds(requested_size, bounds.cast());
+        self.default_size = size.into();
     }
 }
 
@@ -446,30 +1897,277 @@ pub enum SctkFlutterWindowCreateError {
     CreateWaylandContextError(#[from] CreateWaylandContextError),
 }
 
-type ConfigureSize = (Option<NonZeroU32>, Option<NonZeroU32>);
+#[derive(Error, Debug)]
+pub enum SctkSetParentError {
+    #[error("Only xdg windows can have a parent, or be a parent")]
+    NotAWindow,
 
-struct WindowLogicalSize(LogicalSize<u32>);
+    #[error("The parent is the window itself, or one of its children")]
+    Cycle,
+}
 
-impl TryFrom<ConfigureSize> for WindowLogicalSize {
-    type Error = SizeConversionError;
+/// Size of a configure event. Missing dimensions are picked by the client.
+pub(crate) type ConfigureSize = (Option<NonZeroU32>, Option<NonZeroU32>);
+
+/// Resolve the size of a configure event. Each missing dimension falls back
+/// to the current size, and then to the default size.
+fn resolve_configure_size(
+    new_size: ConfigureSize,
+    current_size: Option<LogicalSize<u32>>,
+    default_size: LogicalSize<u32>,
+) -> LogicalSize<u32> {
+    let fallback_size = current_size.unwrap_or(default_size);
+    let (width, height) = new_size;
+
+    LogicalSize::new(
+        width.map_or(fallback_size.width, NonZeroU32::get),
+        height.map_or(fallback_size.height, NonZeroU32::get),
+    )
+}
 
-    fn try_from(value: ConfigureSize) -> Result<Self, Self::Error> {
-        let (Some(width), Some(height)) = value else {
-            return Err(SizeConversionError::Invalid);
-        };
+/// Size of the content of a fullscreen window configured to |configured|,
+/// given its |max_size|.
+fn fit_fullscreen_content(
+    configured: LogicalSize<u32>,
+    max_size: Option<LogicalSize<u32>>,
+) -> LogicalSize<u32> {
+    max_size.map_or(configured, |max_size| {
+        LogicalSize::new(
+            configured.width.min(max_size.width),
+            configured.height.min(max_size.height),
+        )
+    })
+}
 
-        Ok(Self(LogicalSize::new(width.get(), height.get())))
+/// Color of the letterbox bars: the |background_color| of the window, or
+/// black if it is fully transparent.
+fn letterbox_color(background_color: u32) -> u32 {
+    if background_color >> 24 == 0 {
+        0xff00_0000
+    } else {
+        background_color
     }
 }
 
-impl From<WindowLogicalSize> for Size {
-    fn from(val: WindowLogicalSize) -> Self {
-        val.0.into()
+fn metrics_retry_delay(attempts: u32) -> Duration {
+    METRICS_RETRY_BASE_DELAY * 2u32.saturating_pow(attempts)
+}
+
+fn clamp_physical_size(size: PhysicalSize<u32>, max_dimension: u32) -> PhysicalSize<u32> {
+    PhysicalSize::new(size.width.min(max_dimension), size.height.min(max_dimension))
+}
+
+/// Premultiplied RGBA color of an |argb| background, made opaque when
+/// |is_transparency_reduced| is set. Returns `None` if it is fully transparent.
+fn background_clear_color(argb: u32, is_transparency_reduced: bool) -> Option<[f32; 4]> {
+    let alpha = if is_transparency_reduced {
+        1.0
+    } else {
+        (argb >> 24) as f32 / 255.0
+    };
+    if alpha == 0.0 {
+        return None;
     }
+
+    let channel = |shift: u32| ((argb >> shift) & 0xff) as f32 / 255.0 * alpha;
+    Some([channel(16), channel(8), channel(0), alpha])
 }
 
-#[derive(Error, Debug)]
-pub enum SizeConversionError {
-    #[error("Invalid size")]
-    Invalid,
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use dpi::{LogicalSize, PhysicalSize, Size};
+
+    use std::time::Duration;
+
+    use super::{
+        background_clear_color, clamp_physical_size, fit_fullscreen_content, fit_size_to_bounds,
+        metrics_retry_delay, resolve_configure_size, ConfigureSize, SctkWindowSizing,
+    };
+    use crate::units::Scale;
+
+    /// Follows the configure events of a window through `SctkWindowSizing`
+    /// (as done by `SctkFlutterWindow::configure`), recording the metrics
+    /// sent to the engine.
+    struct Harness {
+        sizing: SctkWindowSizing,
+        scale_factor: Scale,
+        current_size: Option<LogicalSize<u32>>,
+        metrics: Vec<(PhysicalSize<u32>, f64)>,
+    }
+
+    impl Harness {
+        fn new(requested_size: Option<Size>, buffer_scale: i32) -> Self {
+            let default_size = requested_size.unwrap_or(LogicalSize::new(1280.0, 720.0).into());
+            Self {
+                sizing: SctkWindowSizing::new(requested_size, default_size),
+                scale_factor: Scale::from_buffer_scale(buffer_scale),
+                current_size: None,
+                metrics: Vec::new(),
+            }
+        }
+
+        /// Returns whether the suggested bounds have changed.
+        fn configure(&mut self, new_size: ConfigureSize, bounds: Option<(u32, u32)>) -> bool {
+            let scale_factor = self.scale_factor;
+            let changed = self.sizing.update_suggested_bounds(
+                bounds,
+                self.current_size.is_some(),
+                scale_factor,
+            );
+
+            let size = resolve_configure_size(
+                new_size,
+                self.current_size,
+                scale_factor.to_logical_size(self.sizing.default_size),
+            );
+            self.current_size = Some(size);
+            self.metrics.push((
+                scale_factor.to_physical_size(size),
+                scale_factor.pixel_ratio(None, 1.0).get(),
+            ));
+            changed
+        }
+    }
+
+    // Bounds of a 1366x768 output with a 32px panel.
+    const PANEL_BOUNDS: (u32, u32) = (1366, 768 - 32);
+
+    #[test]
+    fn initial_metrics_fit_the_suggested_bounds() {
+        let mut harness = Harness::new(None, 1);
+        assert!(harness.configure((None, None), Some(PANEL_BOUNDS)));
+        assert_eq!(harness.metrics, [(PhysicalSize::new(820, 515), 1.0)]);
+
+        let mut harness = Harness::new(None, 2);
+        assert!(harness.configure((None, None), Some(PANEL_BOUNDS)));
+        assert_eq!(harness.metrics, [(PhysicalSize::new(1640, 1030), 2.0)]);
+    }
+
+    #[test]
+    fn requested_size_is_shrunk_to_the_suggested_bounds() {
+        let mut harness = Harness::new(Some(LogicalSize::new(1600.0, 900.0).into()), 1);
+        harness.configure((None, None), Some(PANEL_BOUNDS));
+        assert_eq!(harness.metrics, [(PhysicalSize::new(1366, 736), 1.0)]);
+
+        let mut harness = Harness::new(Some(LogicalSize::new(800.0, 600.0).into()), 1);
+        harness.configure((None, None), Some(PANEL_BOUNDS));
+        assert_eq!(harness.metrics, [(PhysicalSize::new(800, 600), 1.0)]);
+    }
+
+    #[test]
+    fn later_bounds_do_not_resize_the_window() {
+        let mut harness = Harness::new(None, 1);
+        harness.configure((None, None), Some(PANEL_BOUNDS));
+
+        // The panel was hidden.
+        assert!(harness.configure((None, None), Some((1366, 768))));
+        assert!(!harness.configure((None, None), Some((1366, 768))));
+        assert_eq!(
+            harness.sizing.suggested_bounds,
+            Some(LogicalSize::new(1366, 768))
+        );
+        assert_eq!(harness.metrics, [(PhysicalSize::new(820, 515), 1.0); 3]);
+    }
+
+    #[test]
+    fn unknown_bounds_keep_the_default_size() {
+        let mut harness = Harness::new(None, 1);
+        assert!(!harness.configure((None, None), Some((0, 0))));
+        assert_eq!(harness.sizing.suggested_bounds, None);
+        assert_eq!(harness.metrics, [(PhysicalSize::new(1280, 720), 1.0)]);
+    }
+
+    #[test]
+    fn default_size_is_a_fraction_of_a_hidpi_output() {
+        // 3840x2160 output at scale 2.
+        let size = fit_size_to_bounds(None, LogicalSize::new(1920.0, 1080.0));
+        assert_eq!(size, LogicalSize::new(1152.0, 756.0));
+    }
+
+    #[test]
+    fn default_size_is_clamped() {
+        let size = fit_size_to_bounds(None, LogicalSize::new(7680.0, 4320.0));
+        assert_eq!(size, LogicalSize::new(2560.0, 1600.0));
+
+        let size = fit_size_to_bounds(None, LogicalSize::new(800.0, 600.0));
+        assert_eq!(size, LogicalSize::new(640.0, 480.0));
+
+        // The available area takes precedence over the minimum size.
+        let size = fit_size_to_bounds(None, LogicalSize::new(600.0, 400.0));
+        assert_eq!(size, LogicalSize::new(600.0, 400.0));
+    }
+
+    #[test]
+    fn requested_size_larger_than_bounds_is_shrunk() {
+        let requested_size = Some(LogicalSize::new(3000.0, 900.0));
+        let size = fit_size_to_bounds(requested_size, LogicalSize::new(1366.0, 768.0));
+        assert_eq!(size, LogicalSize::new(1366.0, 768.0));
+
+        let requested_size = Some(LogicalSize::new(800.0, 600.0));
+        let size = fit_size_to_bounds(requested_size, LogicalSize::new(1366.0, 768.0));
+        assert_eq!(size, LogicalSize::new(800.0, 600.0));
+    }
+
+    #[test]
+    fn empty_configure_keeps_current_size() {
+        let current_size = Some(LogicalSize::new(1024, 768));
+        let default_size = LogicalSize::new(1280, 720);
+
+        let size = resolve_configure_size((None, None), current_size, default_size);
+        assert_eq!(size, LogicalSize::new(1024, 768));
+
+        // Initial configure: fall back to the default size.
+        let size = resolve_configure_size((None, None), None, default_size);
+        assert_eq!(size, LogicalSize::new(1280, 720));
+
+        let new_size = (NonZeroU32::new(800), None);
+        let size = resolve_configure_size(new_size, current_size, default_size);
+        assert_eq!(size, LogicalSize::new(800, 768));
+    }
+
+    #[test]
+    fn oversized_configure_is_clamped() {
+        let size = clamp_physical_size(PhysicalSize::new(32768, 1024), 16384);
+        assert_eq!(size, PhysicalSize::new(16384, 1024));
+
+        let size = clamp_physical_size(PhysicalSize::new(1, 1), 16384);
+        assert_eq!(size, PhysicalSize::new(1, 1));
+    }
+
+    #[test]
+    fn fullscreen_content_is_kept_within_the_max_size() {
+        let configured = LogicalSize::new(1920, 1080);
+
+        let size = fit_fullscreen_content(configured, Some(LogicalSize::new(1280, 720)));
+        assert_eq!(size, LogicalSize::new(1280, 720));
+
+        let size = fit_fullscreen_content(configured, Some(LogicalSize::new(2560, 900)));
+        assert_eq!(size, LogicalSize::new(1920, 900));
+
+        let size = fit_fullscreen_content(configured, None);
+        assert_eq!(size, configured);
+    }
+
+    #[test]
+    fn metrics_retry_backs_off_exponentially() {
+        assert_eq!(metrics_retry_delay(0), Duration::from_millis(16));
+        assert_eq!(metrics_retry_delay(1), Duration::from_millis(32));
+        assert_eq!(metrics_retry_delay(4), Duration::from_millis(256));
+    }
+
+    #[test]
+    fn reduced_transparency_makes_the_background_opaque() {
+        assert_eq!(background_clear_color(0x00ff_ffff, false), None);
+        assert_eq!(
+            background_clear_color(0x00ff_ffff, true),
+            Some([1.0, 1.0, 1.0, 1.0])
+        );
+        assert_eq!(
+            background_clear_color(0x8000_00ff, false),
+            Some([0.0, 0.0, 128.0 / 255.0, 128.0 / 255.0])
+        );
+        assert_eq!(background_clear_color(0x0000_0000, true), Some([0.0, 0.0, 0.0, 1.0]));
+    }
 }
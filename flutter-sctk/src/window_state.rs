@@ -0,0 +1,281 @@
+//! State of the window (e.g.: maximized, fullscreen) as reported to Dart.
+//!
+//! The state changes either on request of the app, or on the compositor's
+//! own initiative (e.g.: a keybinding, or dragging the window to the top
+//! edge). Both only take effect with a configure event, whose state is
+//! reported to Dart *before* the window metrics of the new size are sent, so
+//! that apps never lay out the content at the new size with a stale state.
+//!
+//! The compositor has the last word: app requests conflicting with a change
+//! it initiated, which Dart could not have seen yet, are ignored, and the
+//! reported state is always the one of the latest configure.
+use flutter_plugins::window::{WindowStateInitiator, WindowStateParams};
+use smithay_client_toolkit::reexports::csd_frame::WindowState;
+use tracing::debug;
+
+/// State change requested by the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WindowStateRequest {
+    Maximize,
+    Unmaximize,
+}
+
+impl WindowStateRequest {
+    fn is_satisfied_by(self, state: WindowState) -> bool {
+        match self {
+            WindowStateRequest::Maximize => state.contains(WindowState::MAXIMIZED),
+            WindowStateRequest::Unmaximize => !state.contains(WindowState::MAXIMIZED),
+        }
+    }
+
+    /// Whether the request would revert a |changed| state.
+    fn reverts(self, changed: WindowState) -> bool {
+        changed.contains(WindowState::MAXIMIZED)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct SctkWindowStateTracker {
+    /// Reported state, from the latest configure.
+    state: WindowState,
+    has_configured: bool,
+    /// Request sent to the compositor which didn't take effect yet.
+    pending_request: Option<WindowStateRequest>,
+    /// State changed by the compositor, which Dart may not have seen yet
+    /// (i.e.: until the next frame is presented).
+    unseen_compositor_change: Option<WindowState>,
+}
+
+impl Default for SctkWindowStateTracker {
+    fn default() -> Self {
+        Self {
+            state: WindowState::empty(),
+            has_configured: false,
+            pending_request: None,
+            unseen_compositor_change: None,
+        }
+    }
+}
+
+impl SctkWindowStateTracker {
+    /// Returns whether |request| should be sent to the compositor.
+    pub(crate) fn request(&mut self, request: WindowStateRequest) -> bool {
+        if let Some(changed) = self.unseen_compositor_change {
+            if request.reverts(changed) && !request.is_satisfied_by(self.state) {
+                debug!("Ignoring {request:?} conflicting with a state set by the compositor");
+                return false;
+            }
+        }
+
+        self.pending_request = Some(request);
+        true
+    }
+
+    /// Store the |state| of a configure event. Returns the state to report to
+    /// Dart if it has changed.
+    ///
+    /// The state of the first configure is not reported, apps query it
+    /// instead.
+    pub(crate) fn configure(&mut self, state: WindowState) -> Option<WindowStateParams> {
+        let state = state & (WindowState::MAXIMIZED | WindowState::FULLSCREEN | WindowState::TILED);
+        let changed = state ^ self.state;
+        self.state = state;
+
+        if !std::mem::replace(&mut self.has_configured, true) || changed.is_empty() {
+            return None;
+        }
+
+        // Requests are fulfilled by the first configure changing the state.
+        // Otherwise, the compositor either changed the state on its own in
+        // the meantime, or ignored the request.
+        let initiator = match self.pending_request.take() {
+            Some(request) if request.is_satisfied_by(state) => WindowStateInitiator::App,
+            _ => {
+                self.unseen_compositor_change = Some(changed);
+                WindowStateInitiator::Compositor
+            }
+        };
+
+        Some(WindowStateParams {
+            maximized: state.contains(WindowState::MAXIMIZED),
+            fullscreen: state.contains(WindowState::FULLSCREEN),
+            tiled: state.intersects(WindowState::TILED),
+            initiator,
+        })
+    }
+
+    /// Dart had the chance to handle the state changes reported so far.
+    pub(crate) fn frame_presented(&mut self) {
+        self.unseen_compositor_change = None;
+    }
+}
+
+/// Handle the |state| of a configure event: the change (if any) is passed
+/// to |notify| before |apply_size| is called, both within the same platform
+/// task.
+pub(crate) fn configure_state<N, A>(
+    tracker: &mut SctkWindowStateTracker,
+    state: WindowState,
+    notify: N,
+    apply_size: A,
+) where
+    N: FnOnce(WindowStateParams),
+    A: FnOnce(),
+{
+    if let Some(params) = tracker.configure(state) {
+        notify(params);
+    }
+    apply_size();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use flutter_plugins::window::{WindowStateInitiator, WindowStateParams};
+    use smithay_client_toolkit::reexports::csd_frame::WindowState;
+
+    use super::{configure_state, SctkWindowStateTracker, WindowStateRequest};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        StateChanged(WindowStateParams),
+        Metrics,
+    }
+
+    /// Stands in for the window: records what is sent to the engine.
+    #[derive(Default)]
+    struct Harness {
+        tracker: SctkWindowStateTracker,
+        events: RefCell<Vec<Event>>,
+    }
+
+    impl Harness {
+        fn configured() -> Self {
+            let mut harness = Self::default();
+            harness.configure(WindowState::ACTIVATED);
+            harness.events.borrow_mut().clear();
+            harness
+        }
+
+        fn configure(&mut self, state: WindowState) {
+            configure_state(
+                &mut self.tracker,
+                state,
+                |params| self.events.borrow_mut().push(Event::StateChanged(params)),
+                || self.events.borrow_mut().push(Event::Metrics),
+            );
+        }
+
+        fn take_events(&self) -> Vec<Event> {
+            self.events.take()
+        }
+    }
+
+    fn state(maximized: bool, fullscreen: bool, initiator: WindowStateInitiator) -> Event {
+        Event::StateChanged(WindowStateParams {
+            maximized,
+            fullscreen,
+            tiled: false,
+            initiator,
+        })
+    }
+
+    #[test]
+    fn compositor_initiated_maximize_is_reported_before_metrics() {
+        let mut harness = Harness::configured();
+
+        harness.configure(WindowState::ACTIVATED | WindowState::MAXIMIZED);
+        assert_eq!(
+            harness.take_events(),
+            [
+                state(true, false, WindowStateInitiator::Compositor),
+                Event::Metrics
+            ]
+        );
+
+        // Activation changes aren't reported.
+        harness.configure(WindowState::MAXIMIZED);
+        assert_eq!(harness.take_events(), [Event::Metrics]);
+    }
+
+    #[test]
+    fn fullscreen_via_keybinding() {
+        let mut harness = Harness::configured();
+
+        harness.configure(WindowState::FULLSCREEN);
+        harness.configure(WindowState::empty());
+        assert_eq!(
+            harness.take_events(),
+            [
+                state(false, true, WindowStateInitiator::Compositor),
+                Event::Metrics,
+                state(false, false, WindowStateInitiator::Compositor),
+                Event::Metrics
+            ]
+        );
+    }
+
+    #[test]
+    fn app_requests_are_reported_as_such() {
+        let mut harness = Harness::configured();
+
+        assert!(harness.tracker.request(WindowStateRequest::Maximize));
+        // Unrelated configure before the compositor handles the request.
+        harness.configure(WindowState::ACTIVATED);
+        harness.configure(WindowState::MAXIMIZED);
+        assert_eq!(
+            harness.take_events(),
+            [
+                Event::Metrics,
+                state(true, false, WindowStateInitiator::App),
+                Event::Metrics
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicting_app_request_resolves_to_compositor_state() {
+        let mut harness = Harness::configured();
+        assert!(harness.tracker.request(WindowStateRequest::Maximize));
+        harness.configure(WindowState::MAXIMIZED);
+        harness.tracker.frame_presented();
+        harness.take_events();
+
+        // Dart requests unmaximize while the compositor maximizes the window
+        // to fullscreen: the compositor state wins.
+        assert!(harness.tracker.request(WindowStateRequest::Unmaximize));
+        harness.configure(WindowState::MAXIMIZED | WindowState::FULLSCREEN);
+        assert_eq!(
+            harness.take_events(),
+            [
+                state(true, true, WindowStateInitiator::Compositor),
+                Event::Metrics
+            ]
+        );
+
+        // The compositor unmaximizes the window (e.g.: a keybinding) just
+        // before Dart, unaware of it, asks for maximizing it again.
+        harness.configure(WindowState::empty());
+        assert!(!harness.tracker.request(WindowStateRequest::Maximize));
+        assert_eq!(
+            harness.take_events(),
+            [
+                state(false, false, WindowStateInitiator::Compositor),
+                Event::Metrics
+            ]
+        );
+
+        // Once Dart has seen the change, its requests go through again.
+        harness.tracker.frame_presented();
+        assert!(harness.tracker.request(WindowStateRequest::Maximize));
+        harness.configure(WindowState::MAXIMIZED);
+        assert_eq!(
+            harness.take_events(),
+            [
+                state(true, false, WindowStateInitiator::App),
+                Event::Metrics
+            ]
+        );
+    }
+}
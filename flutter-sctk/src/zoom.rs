@@ -0,0 +1,144 @@
+//! Content zoom handled by the embedder (e.g.: Ctrl+scroll, Ctrl+plus), like
+//! in browsers.
+//!
+//! The zoom multiplies the device pixel ratio reported to the engine (see
+//! [`Scale::pixel_ratio`]), so the content gets bigger while the window, and
+//! its buffers, keep the same size.
+//!
+//! [`Scale::pixel_ratio`]: crate::units::Scale::pixel_ratio
+use smithay_client_toolkit::seat::keyboard::Keysym;
+
+pub(crate) const MIN_ZOOM: f64 = 0.5;
+pub(crate) const MAX_ZOOM: f64 = 3.0;
+
+/// Zoom levels stepped through by the shortcuts.
+const ZOOM_LEVELS: [f64; 13] = [
+    0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0,
+];
+
+/// Distance (in logical pixels) scrolled with a touchpad for a zoom step.
+const SCROLL_DISTANCE_PER_STEP: f64 = 50.0;
+
+/// Clamp a zoom requested by the app. Returns `None` for invalid values.
+pub(crate) fn clamp_zoom(zoom: f64) -> Option<f64> {
+    (zoom.is_finite() && zoom > 0.0).then(|| zoom.clamp(MIN_ZOOM, MAX_ZOOM))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ZoomAction {
+    In,
+    Out,
+    Reset,
+}
+
+impl ZoomAction {
+    /// Action of a key pressed along with Ctrl.
+    pub(crate) fn from_keysym(keysym: Keysym) -> Option<Self> {
+        match keysym {
+            Keysym::equal | Keysym::plus | Keysym::KP_Add => Some(ZoomAction::In),
+            Keysym::minus | Keysym::KP_Subtract => Some(ZoomAction::Out),
+            Keysym::_0 | Keysym::KP_0 => Some(ZoomAction::Reset),
+            _ => None,
+        }
+    }
+
+    /// Returns the zoom following |zoom|. Zooming from a level set by the app
+    /// moves to the closest level in that direction.
+    pub(crate) fn apply(self, zoom: f64) -> f64 {
+        // Tolerate the rounding of the levels (e.g.: `0.67`).
+        const EPSILON: f64 = 0.001;
+
+        match self {
+            ZoomAction::In => ZOOM_LEVELS
+                .into_iter()
+                .find(|&level| level > zoom + EPSILON)
+                .unwrap_or(MAX_ZOOM),
+            ZoomAction::Out => ZOOM_LEVELS
+                .into_iter()
+                .rev()
+                .find(|&level| level < zoom - EPSILON)
+                .unwrap_or(MIN_ZOOM),
+            ZoomAction::Reset => 1.0,
+        }
+    }
+}
+
+/// Turns Ctrl+scroll into zoom steps: one per wheel notch, or one for every
+/// [`SCROLL_DISTANCE_PER_STEP`] scrolled with a touchpad.
+#[derive(Debug, Default)]
+pub(crate) struct ZoomScroll {
+    distance: f64,
+}
+
+impl ZoomScroll {
+    /// |absolute| and |discrete| are the vertical scroll of an axis event.
+    /// Scrolling up zooms in.
+    pub(crate) fn scroll(&mut self, absolute: f64, discrete: i32) -> Option<ZoomAction> {
+        if discrete != 0 {
+            self.distance = 0.0;
+            return Some(if discrete < 0 {
+                ZoomAction::In
+            } else {
+                ZoomAction::Out
+            });
+        }
+
+        self.distance += absolute;
+        if self.distance.abs() < SCROLL_DISTANCE_PER_STEP {
+            return None;
+        }
+
+        let action = if self.distance < 0.0 {
+            ZoomAction::In
+        } else {
+            ZoomAction::Out
+        };
+        self.distance = 0.0;
+        Some(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clamp_zoom, ZoomAction, ZoomScroll, MAX_ZOOM, MIN_ZOOM};
+
+    #[test]
+    fn zoom_steps_are_clamped_and_reset() {
+        let mut zoom = 1.0;
+        for _ in 0..20 {
+            zoom = ZoomAction::In.apply(zoom);
+        }
+        assert_eq!(zoom, MAX_ZOOM);
+
+        for _ in 0..20 {
+            zoom = ZoomAction::Out.apply(zoom);
+        }
+        assert_eq!(zoom, MIN_ZOOM);
+
+        // Levels set by the app snap to the closest level on the next step.
+        assert_eq!(ZoomAction::In.apply(1.3), 1.5);
+        assert_eq!(ZoomAction::Out.apply(1.3), 1.25);
+        assert_eq!(ZoomAction::Out.apply(0.67), 0.5);
+
+        // Ctrl+0 restores the default size, whatever the current zoom.
+        assert_eq!(ZoomAction::Reset.apply(2.5), 1.0);
+        assert_eq!(ZoomAction::Reset.apply(1.3), 1.0);
+        assert_eq!(ZoomAction::Reset.apply(MIN_ZOOM), 1.0);
+
+        assert_eq!(clamp_zoom(10.0), Some(MAX_ZOOM));
+        assert_eq!(clamp_zoom(0.0), None);
+        assert_eq!(clamp_zoom(f64::NAN), None);
+    }
+
+    #[test]
+    fn touchpad_scroll_is_accumulated() {
+        let mut scroll = ZoomScroll::default();
+
+        assert_eq!(scroll.scroll(-10.0, -1), Some(ZoomAction::In));
+        assert_eq!(scroll.scroll(10.0, 1), Some(ZoomAction::Out));
+
+        assert_eq!(scroll.scroll(-30.0, 0), None);
+        assert_eq!(scroll.scroll(-30.0, 0), Some(ZoomAction::In));
+        assert_eq!(scroll.scroll(-30.0, 0), None);
+    }
+}
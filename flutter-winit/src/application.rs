@@ -1,22 +1,35 @@
 use ashpd::desktop::settings::{ColorScheme, Settings};
 use async_executor::LocalExecutor;
 use flutter_engine::builder::FlutterEngineBuilder;
-use flutter_engine::{CreateError, FlutterEngine, RunError};
+use flutter_engine::plugins::PluginRegistrar;
+use flutter_engine::prepare::PrepareError;
+use flutter_engine::schedule::ScheduleHandle;
+use flutter_engine::{CreateError, FlutterEngine, FlutterEngineWeakRef, RunError};
+use flutter_glutin::device::{self, DevicePreference};
+use flutter_plugins::lifecycle::LifecyclePlugin;
 use flutter_plugins::localization::LocalizationPlugin;
 use flutter_plugins::settings::{PlatformBrightness, SettingsPlugin};
-use flutter_runner_api::ApplicationAttributes;
+use flutter_runner_api::{
+    ApplicationAttributes, HostClock, HostContext, HostHook, PumpResult, WindowRole,
+};
 use futures_lite::future;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::Duration;
 use sys_locale::get_locale;
 use thiserror::Error;
+use tracing::{info, warn};
 use winit::application::ApplicationHandler;
-use winit::dpi::PhysicalSize;
+use winit::dpi::{PhysicalSize, Size};
 use winit::error::EventLoopError;
 use winit::event::WindowEvent;
-use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
-use winit::platform::wayland::WindowAttributesExtWayland;
-use winit::window::{WindowAttributes, WindowId};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy};
+use winit::platform::pump_events::{EventLoopExtPumpEvents, PumpStatus};
+use winit::platform::wayland::{EventLoopExtWayland, WindowAttributesExtWayland};
+use winit::platform::x11::{self, WindowType};
+use winit::window::{Fullscreen, WindowAttributes, WindowId, WindowLevel};
 
+use crate::egl::GpuPreferenceExt;
 use crate::pointer::Pointers;
 use crate::view::WinitControllerError;
 use crate::window::{resize, FlutterEvent};
@@ -31,29 +44,165 @@ pub struct WinitApplicationState {
     implicit_view: FlutterViewWinit,
     engine: FlutterEngine,
     pointers: Pointers,
+    is_suspended: bool,
+    is_started: bool,
+    has_exited: bool,
+    host_hook: Option<(HostClock, HostHook)>,
+    /// See `ApplicationAttributes::platform_task_batch_size`.
+    platform_task_batch_size: Option<NonZeroUsize>,
+}
+
+/// Thread-safe handle used for controlling a running application.
+#[derive(Clone)]
+pub struct WinitApplicationHandle {
+    proxy: EventLoopProxy<FlutterEvent>,
+    engine: FlutterEngineWeakRef,
+}
+
+impl WinitApplicationHandle {
+    /// Notify the app that it is paused. When `drop_caches` is set, the engine
+    /// is also asked to release its caches.
+    pub fn suspend(&self, drop_caches: bool) {
+        self.send(FlutterEvent::Suspend { drop_caches });
+    }
+
+    /// Restore the app activity state after a previous call to `suspend`.
+    pub fn resume(&self) {
+        self.send(FlutterEvent::Resume);
+    }
+
+    pub fn quit(&self) {
+        self.send(FlutterEvent::Quit);
+    }
+
+    /// See [`FlutterEngine::schedule_at`]. Returns `None` if the engine is
+    /// gone.
+    pub fn schedule_at<F>(&self, engine_time_nanos: u64, f: F) -> Option<ScheduleHandle>
+    where
+        F: FnOnce(&FlutterEngine) + 'static + Send,
+    {
+        Some(self.engine.upgrade()?.schedule_at(engine_time_nanos, f))
+    }
+
+    /// See [`FlutterEngine::schedule_repeating`]. Returns `None` if the engine
+    /// is gone.
+    pub fn schedule_repeating<F>(&self, interval: Duration, f: F) -> Option<ScheduleHandle>
+    where
+        F: FnMut(&FlutterEngine) + 'static + Send,
+    {
+        Some(self.engine.upgrade()?.schedule_repeating(interval, f))
+    }
+
+    /// The engine, e.g.: for sending messages from another thread. Returns
+    /// `None` if the engine is gone.
+    pub fn engine(&self) -> Option<FlutterEngine> {
+        self.engine.upgrade()
+    }
+
+    fn send(&self, event: FlutterEvent) {
+        if self.proxy.send_event(event).is_err() {
+            warn!("Ignoring application request because the event loop is gone");
+        }
+    }
 }
 
 impl WinitApplication {
     pub fn new(
         attributes: ApplicationAttributes,
     ) -> Result<WinitApplication, WinitApplicationBuildError> {
+        let mut engine_builder = FlutterEngineBuilder::new()
+            .with_asset_path(attributes.assets_path.clone())
+            .with_icu_data_path(attributes.icu_data_path.clone())
+            .with_embedded_icu_data(attributes.icu_data_embedded)
+            .with_persistent_cache_path(attributes.persistent_cache_path.clone())
+            .with_args(attributes.args.clone())
+            .with_vm_service_auth_codes_disabled(attributes.vm_service_auth_codes_disabled);
+        engine_builder = attributes.isolate_callbacks.register(engine_builder);
+
+        if let Some(port) = attributes.vm_service_port {
+            engine_builder = engine_builder.with_vm_service_port(port);
+        }
+
+        if let Some(time_base) = attributes.fake_time_base {
+            engine_builder = engine_builder.with_fake_time_base(time_base);
+        }
+
+        // Read the engine artifacts while the event loop is being set up.
+        let prepare = attributes
+            .prepare_timeout
+            .map(|timeout| (engine_builder.prepare_in_background(), timeout));
+
         let event_loop = EventLoop::with_user_event().build()?;
 
-        let window_attributes = WinitWindowAttributes::from(attributes.clone()).0;
+        if attributes.window_role != WindowRole::Toplevel {
+            warn!("Layer shell surfaces are not supported by the winit backend, using a window");
+        }
+
+        if attributes.handle_termination_signals {
+            warn!("Termination signals are not handled by the winit backend");
+        }
+
+        if attributes.content_zoom {
+            warn!("Content zoom is not supported by the winit backend");
+        }
+
+        if attributes.background_color != 0 {
+            warn!("Background colors are not supported by the winit backend");
+        }
+
+        if attributes.min_frame_interval.is_some() {
+            warn!("Frame rate caps are not supported by the winit backend");
+        }
+
+        if event_loop.is_wayland() && attributes.always_on_top {
+            warn!("Keeping the window above the others is not supported on Wayland");
+        }
+
+        if event_loop.is_wayland() && attributes.skip_taskbar {
+            warn!("Keeping the window out of the taskbar is not supported on Wayland");
+        }
+
+        // The device must be selected before the display is created by the
+        // view. Unlike the sctk backend, the display isn't recreated on the
+        // default device if the context creation fails.
+        let device_preference = attributes.gpu_preference.to_device_preference();
+        match device::select_device(&device_preference) {
+            Some(device) => info!("Rendering on {device}"),
+            None if device_preference != DevicePreference::Default => {
+                warn!("No device matches {device_preference:?}, using the default one");
+            }
+            None => {}
+        }
+
+        let vsync_mode = attributes.vsync_mode;
+        let robust_context = attributes.robust_context;
+        let gl_debug_output = attributes.gl_debug_output;
+        let platform_task_batch_size = attributes.platform_task_batch_size;
+        let plugins = PluginRegistrar::with_configs(attributes.plugin_configs.clone())
+            .with_async_runtime(attributes.async_runtime.clone());
+        let window_attributes = WinitWindowAttributes::from(attributes).0;
 
         let platform_task_handler =
             Arc::new(WinitPlatformTaskHandler::new(event_loop.create_proxy()));
 
-        let engine = FlutterEngineBuilder::new()
-            .with_platform_handler(platform_task_handler)
-            .with_asset_path(attributes.assets_path)
-            .with_icu_data_path(attributes.icu_data_path)
-            .with_persistent_cache_path(attributes.persistent_cache_path.clone())
-            .with_args(attributes.args)
-            .build()?;
+        let engine_builder = engine_builder.with_platform_handler(platform_task_handler);
 
-        let implicit_view =
-            FlutterViewWinit::new_implicit(&event_loop, engine.clone(), window_attributes)?;
+        // Surface invalid bundles before the window is shown.
+        if let Some((prepare_handle, timeout)) = prepare {
+            prepare_handle.wait(timeout)?;
+        }
+
+        let engine = engine_builder.build()?;
+
+        let implicit_view = FlutterViewWinit::new_implicit(
+            &event_loop,
+            engine.clone(),
+            window_attributes,
+            vsync_mode,
+            robust_context,
+            gl_debug_output,
+            plugins,
+        )?;
 
         let pointers = Pointers::new(engine.clone());
 
@@ -63,26 +212,89 @@ impl WinitApplication {
             implicit_view,
             engine,
             pointers,
+            is_suspended: false,
+            is_started: false,
+            has_exited: false,
+            host_hook: None,
+            platform_task_batch_size,
         };
 
         Ok(WinitApplication { event_loop, state })
     }
 
+    pub fn handle(&self) -> WinitApplicationHandle {
+        WinitApplicationHandle {
+            proxy: self.event_loop.create_proxy(),
+            engine: self.state.engine.downgrade(),
+        }
+    }
+
     pub fn run(self) -> Result<(), WinitApplicationRunError> {
         let mut state = self.state;
+        state.start()?;
+
+        Ok(self.event_loop.run_app(&mut state)?)
+    }
+
+    /// Run the application, invoking `hook` on the platform thread once per
+    /// event loop iteration (i.e.: right before the event loop goes to
+    /// sleep). Returning [`std::ops::ControlFlow::Break`] quits the
+    /// application.
+    pub fn run_with<F>(self, hook: F) -> Result<(), WinitApplicationRunError>
+    where
+        F: FnMut(&mut HostContext) -> std::ops::ControlFlow<()> + 'static,
+    {
+        let mut state = self.state;
+        state.host_hook = Some((HostClock::default(), Box::new(hook)));
+        state.start()?;
+
+        Ok(self.event_loop.run_app(&mut state)?)
+    }
+
+    /// Run a single event loop iteration: dispatch the pending events (waiting
+    /// at most `timeout` for new ones, or indefinitely if `None`) and execute
+    /// the platform tasks which are due.
+    ///
+    /// Hosts driving their own loop must call this from the thread which
+    /// created the application at least once per frame, otherwise the app
+    /// stops responding. See [`EventLoopExtPumpEvents::pump_app_events`] for
+    /// the platform limitations.
+    pub fn pump(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<PumpResult, WinitApplicationRunError> {
+        if self.state.has_exited {
+            return Ok(PumpResult::Exit);
+        }
+
+        self.state.start()?;
+
+        match self.event_loop.pump_app_events(timeout, &mut self.state) {
+            PumpStatus::Continue => Ok(PumpResult::Continue),
+            PumpStatus::Exit(_) => Ok(PumpResult::Exit),
+        }
+    }
+}
+
+impl WinitApplicationState {
+    fn start(&mut self) -> Result<(), RunError> {
+        if self.is_started {
+            return Ok(());
+        }
+        self.is_started = true;
 
         // Warning: The current logic does not support `custom_task_runners`.
         //
         // TODO: Start event loop *prior* to running the engine. See
         // `FlutterEngineRun` comment in `embedder.h` for additional context.
-        state.engine.run()?;
+        self.engine.run()?;
 
-        let window = state.implicit_view.window();
+        let window = self.implicit_view.window();
         let context = window.context();
 
         resize(
             window.view_id(),
-            &state.engine,
+            &self.engine,
             &context,
             &window.window(),
             0,
@@ -115,7 +327,42 @@ impl WinitApplication {
                 .send();
         });
 
-        Ok(self.event_loop.run_app(&mut state)?)
+        Ok(())
+    }
+
+    fn suspend(&mut self, drop_caches: bool) {
+        if self.is_suspended {
+            return;
+        }
+        self.is_suspended = true;
+
+        self.implicit_view
+            .window()
+            .with_plugin(|lifecycle: &LifecyclePlugin| lifecycle.send_app_is_paused());
+
+        if drop_caches {
+            self.engine.notify_low_memory_warning();
+        }
+    }
+
+    fn resume(&mut self) {
+        if !self.is_suspended {
+            return;
+        }
+        self.is_suspended = false;
+
+        let window = self.implicit_view.window();
+        window.with_plugin(|lifecycle: &LifecyclePlugin| lifecycle.send_app_is_resumed());
+
+        resize(
+            window.view_id(),
+            &self.engine,
+            &window.context(),
+            &window.window(),
+            0,
+        );
+
+        self.engine.schedule_frame();
     }
 }
 
@@ -136,6 +383,13 @@ impl ApplicationHandler<FlutterEvent> for WinitApplicationState {
             FlutterEvent::WindowCloseRequested(_) => event_loop.exit(),
             FlutterEvent::WakePlatformThread => {} // no-op
             FlutterEvent::IsolateCreated => {}     // no-op
+            FlutterEvent::Suspend { drop_caches } => self.suspend(drop_caches),
+            FlutterEvent::Resume => self.resume(),
+            FlutterEvent::Quit => event_loop.exit(),
+            FlutterEvent::ExitWithCode(exit_code) => {
+                self.engine.set_exit_code(exit_code);
+                event_loop.exit();
+            }
         }
     }
 
@@ -145,7 +399,20 @@ impl ApplicationHandler<FlutterEvent> for WinitApplicationState {
             return;
         }
 
-        let next_task_time = self.engine.execute_platform_tasks();
+        // Left over tasks make the loop wait until a past time, i.e. poll the
+        // pending events before running the next batch.
+        let next_task_time = self
+            .engine
+            .execute_platform_tasks_limited(self.platform_task_batch_size);
+
+        if let Some((clock, hook)) = &mut self.host_hook {
+            let mut context = clock.tick(&self.engine);
+            if hook(&mut context).is_break() || context.is_quit_requested() {
+                event_loop.exit();
+                return;
+            }
+        }
+
         let control_flow = next_task_time.map_or(ControlFlow::Wait, ControlFlow::WaitUntil);
         event_loop.set_control_flow(control_flow)
     }
@@ -155,6 +422,7 @@ impl ApplicationHandler<FlutterEvent> for WinitApplicationState {
     }
 
     fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.has_exited = true;
         self.engine.shutdown()
     }
 }
@@ -164,6 +432,9 @@ pub enum WinitApplicationBuildError {
     #[error(transparent)]
     CreateEngineError(#[from] CreateError),
 
+    #[error(transparent)]
+    PrepareEngineError(#[from] PrepareError),
+
     #[error(transparent)]
     WindowBuildFailure(#[from] WinitControllerError),
 
@@ -187,13 +458,33 @@ impl From<ApplicationAttributes> for WinitWindowAttributes {
         let mut attributes =
             WindowAttributes::default().with_title(value.title.unwrap_or_default());
 
-        attributes.inner_size = value.inner_size.as_ref().map(|size| {
+        let to_winit_size = |size: &Size| -> Size {
             PhysicalSize::new(
                 size.to_physical::<u32>(1.0).width,
                 size.to_physical::<u32>(1.0).height,
             )
             .into()
-        });
+        };
+        attributes.inner_size = value.inner_size.as_ref().map(to_winit_size);
+        attributes.min_inner_size = value.min_inner_size.as_ref().map(to_winit_size);
+        attributes.max_inner_size = value.max_inner_size.as_ref().map(to_winit_size);
+
+        let mut attributes = attributes
+            .with_maximized(value.maximized)
+            .with_fullscreen(value.fullscreen.then_some(Fullscreen::Borderless(None)))
+            .with_transparent(value.transparent)
+            .with_visible(!value.start_hidden);
+        if value.always_on_top {
+            attributes = attributes.with_window_level(WindowLevel::AlwaysOnTop);
+        }
+        // Ignored by the other windowing systems, see `WinitApplication::new`.
+        // Not imported, as its `with_name` conflicts with the Wayland one.
+        if value.skip_taskbar {
+            attributes = x11::WindowAttributesExtX11::with_x11_window_type(
+                attributes,
+                vec![WindowType::Utility],
+            );
+        }
 
         let attributes = value.app_id.map_or(attributes.clone(), |app_id| {
             attributes.with_name(app_id, "")
@@ -2,10 +2,12 @@ use std::{error::Error, num::NonZeroU32};
 
 use dpi::PhysicalSize;
 use flutter_glutin::{
-    builder::ContextBuilder,
+    builder::{self, ContextBuilder},
     context::{Context, ResourceContext},
+    device::DevicePreference,
 };
-use glutin::config::ConfigTemplateBuilder;
+use flutter_runner_api::{GpuPreference, VsyncMode};
+use glutin::{config::GlConfig, surface::SwapInterval};
 use glutin_winit::{ApiPreference, DisplayBuilder};
 use raw_window_handle::HasWindowHandle;
 use thiserror::Error;
@@ -16,16 +18,47 @@ use winit::{
 
 use crate::window::FlutterEvent;
 
+pub(crate) trait GpuPreferenceExt {
+    fn to_device_preference(&self) -> DevicePreference;
+}
+
+impl GpuPreferenceExt for GpuPreference {
+    fn to_device_preference(&self) -> DevicePreference {
+        match self {
+            GpuPreference::Default => DevicePreference::Default,
+            GpuPreference::HighPerformance => DevicePreference::HighPerformance,
+            GpuPreference::LowPower => DevicePreference::LowPower,
+            GpuPreference::Named(name) => DevicePreference::Named(name.clone()),
+        }
+    }
+}
+
+pub(crate) struct WindowContexts {
+    pub window: Window,
+    pub context: Context,
+    pub resource_context: ResourceContext,
+    /// Whether the config has an alpha channel, i.e.: the content of a
+    /// transparent window is actually composited over what is behind it.
+    pub has_alpha: bool,
+}
+
+/// Create the window along with its contexts. The config and the contexts
+/// are selected like for the sctk backend (see `flutter_glutin::builder`).
 pub(crate) fn create_window_contexts(
     window_attributes: WindowAttributes,
+    vsync_mode: VsyncMode,
+    robust_context: bool,
+    gl_debug_output: bool,
     event_loop: &EventLoop<FlutterEvent>,
-) -> Result<(Window, Context, ResourceContext), Box<dyn Error>> {
+) -> Result<WindowContexts, Box<dyn Error>> {
+    let config_template =
+        builder::config_template().with_transparency(window_attributes.transparent);
     let (window, config) = DisplayBuilder::new()
         .with_preference(ApiPreference::PreferEgl)
         .with_window_attributes(Some(window_attributes))
-        .build(event_loop, ConfigTemplateBuilder::new(), |configs| {
-            // TODO: Find out what's the correct way of choosing a config
-            configs.last().unwrap()
+        .build(event_loop, config_template, |configs| {
+            // The display fails to find configs rather than returning none.
+            builder::choose_config(configs).expect("No available config was found")
         })?;
 
     let Some(window) = window else {
@@ -36,13 +69,37 @@ pub(crate) fn create_window_contexts(
         return Err(ContextError::InvalidWindow.into());
     };
 
+    let has_alpha = config.alpha_size() >= 8;
     let (context, resource_context) = ContextBuilder::new()
         .with_raw_window_handle(window_handle.as_raw())
         .with_config(config)
+        .with_swap_interval(vsync_mode.to_swap_interval())
         .with_size(window.inner_size().non_zero())
+        .with_robustness(robust_context)
+        .with_debug_output(gl_debug_output)
         .build()?;
 
-    Ok((window, context, resource_context))
+    Ok(WindowContexts {
+        window,
+        context,
+        resource_context,
+        has_alpha,
+    })
+}
+
+trait VsyncModeExt {
+    fn to_swap_interval(self) -> SwapInterval;
+}
+
+impl VsyncModeExt for VsyncMode {
+    /// Without frame callbacks, the engine frames are throttled by the buffer
+    /// swaps, unless they are scheduled by the software timer.
+    fn to_swap_interval(self) -> SwapInterval {
+        match self {
+            VsyncMode::Vsync => SwapInterval::Wait(NonZeroU32::MIN),
+            VsyncMode::Software => SwapInterval::DontWait,
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -2,16 +2,16 @@ use crate::window::FlutterEvent;
 use copypasta::nop_clipboard::NopClipboardContext;
 use copypasta::ClipboardProvider;
 use flutter_engine::tasks::TaskRunnerHandler;
-use flutter_plugins::platform::{AppSwitcherDescription, MimeError, PlatformHandler};
+use flutter_plugins::platform::{AppSwitcherDescription, PlatformError, PlatformHandler};
 use flutter_plugins::textinput::TextInputHandler;
-use flutter_plugins::window::{PositionParams, WindowHandler};
+use flutter_plugins::window::{PositionParams, SizeParams, WindowCapabilities, WindowHandler};
 use parking_lot::Mutex;
 use std::error::Error;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tracing::error;
+use winit::dpi::{LogicalPosition, LogicalSize};
 use winit::event_loop::EventLoopProxy;
-use winit::window::Window;
+use winit::window::{Fullscreen, Window, WindowLevel};
 
 // TODO: Investigate removing mutex
 pub struct WinitPlatformTaskHandler {
@@ -39,13 +39,18 @@ pub struct WinitPlatformHandler {
     // TODO(vially): Bring back clipboard context implementation
     clipboard: NopClipboardContext,
     window: Arc<Mutex<Window>>,
+    proxy: EventLoopProxy<FlutterEvent>,
 }
 
 impl WinitPlatformHandler {
-    pub fn new(window: Arc<Mutex<Window>>) -> Result<Self, Box<dyn Error>> {
+    pub fn new(
+        window: Arc<Mutex<Window>>,
+        proxy: EventLoopProxy<FlutterEvent>,
+    ) -> Result<Self, Box<dyn Error>> {
         Ok(Self {
             clipboard: NopClipboardContext,
             window,
+            proxy,
         })
     }
 }
@@ -55,21 +60,25 @@ impl PlatformHandler for WinitPlatformHandler {
         self.window.lock().set_title(&description.label);
     }
 
-    fn set_clipboard_data(&mut self, text: String) {
-        if let Err(err) = self.clipboard.set_contents(text) {
-            error!("{}", err);
-        }
+    fn set_clipboard_data(&mut self, text: String) -> Result<(), PlatformError> {
+        self.clipboard
+            .set_contents(text)
+            .map_err(|err| PlatformError::Clipboard(err.to_string()))
     }
 
-    fn get_clipboard_data(&mut self, mime: &str) -> Result<String, MimeError> {
+    fn get_clipboard_data(&mut self, mime: &str) -> Result<String, PlatformError> {
         if mime != "text/plain" {
-            return Err(MimeError);
-        }
-        let result = self.clipboard.get_contents();
-        if let Err(err) = &result {
-            error!("{}", err);
+            return Err(PlatformError::UnsupportedFormat(mime.to_string()));
         }
-        Ok(result.unwrap_or_default())
+        self.clipboard
+            .get_contents()
+            .map_err(|err| PlatformError::Clipboard(err.to_string()))
+    }
+
+    fn exit_application(&mut self, exit_code: i32) -> bool {
+        self.proxy
+            .send_event(FlutterEvent::ExitWithCode(exit_code))
+            .is_ok()
     }
 }
 
@@ -78,15 +87,26 @@ pub struct WinitWindowHandler {
     maximized: bool,
     visible: bool,
     close: Arc<AtomicBool>,
+    capabilities: WindowCapabilities,
 }
 
 impl WinitWindowHandler {
-    pub fn new(window: Arc<Mutex<Window>>, close: Arc<AtomicBool>) -> Self {
+    pub fn new(
+        window: Arc<Mutex<Window>>,
+        close: Arc<AtomicBool>,
+        capabilities: WindowCapabilities,
+    ) -> Self {
+        // The window may be created maximized (see
+        // `ApplicationAttributes::maximized`).
+        let maximized = window.lock().is_maximized();
+        // Or hidden (see `ApplicationAttributes::start_hidden`).
+        let visible = window.lock().is_visible().unwrap_or(true);
         Self {
             window,
-            maximized: false,
-            visible: false,
+            maximized,
+            visible,
             close,
+            capabilities,
         }
     }
 }
@@ -130,22 +150,79 @@ impl WindowHandler for WinitWindowHandler {
         false
     }
 
-    fn set_pos(&mut self, _pos: PositionParams) {}
+    fn is_fullscreen(&mut self) -> bool {
+        self.window.lock().fullscreen().is_some()
+    }
+
+    fn set_pos(&mut self, pos: PositionParams) {
+        let position = LogicalPosition::new(pos.x, pos.y);
+        self.window.lock().set_outer_position(position);
+    }
 
     fn get_pos(&mut self) -> PositionParams {
-        PositionParams { x: 0.0, y: 0.0 }
+        let window = self.window.lock();
+        // Not available on Wayland, where clients don't know their position.
+        let Ok(position) = window.outer_position() else {
+            return PositionParams { x: 0.0, y: 0.0 };
+        };
+
+        let position = position.to_logical::<f32>(window.scale_factor());
+        PositionParams {
+            x: position.x,
+            y: position.y,
+        }
     }
 
     fn start_drag(&mut self) {}
 
     fn end_drag(&mut self) {}
+
+    fn capabilities(&mut self) -> WindowCapabilities {
+        self.capabilities
+    }
+
+    fn set_always_on_top(&mut self, always_on_top: bool) {
+        let level = if always_on_top {
+            WindowLevel::AlwaysOnTop
+        } else {
+            WindowLevel::Normal
+        };
+        self.window.lock().set_window_level(level);
+    }
+
+    fn set_min_size(&mut self, size: Option<SizeParams>) {
+        let size = size.map(|size| LogicalSize::new(size.width, size.height));
+        self.window.lock().set_min_inner_size(size);
+    }
+
+    fn set_max_size(&mut self, size: Option<SizeParams>) {
+        let size = size.map(|size| LogicalSize::new(size.width, size.height));
+        self.window.lock().set_max_inner_size(size);
+    }
+
+    fn set_fullscreen(&mut self, fullscreen: bool) {
+        let fullscreen = fullscreen.then_some(Fullscreen::Borderless(None));
+        self.window.lock().set_fullscreen(fullscreen);
+    }
+}
+
+pub struct WinitTextInputHandler {
+    window: Arc<Mutex<Window>>,
 }
 
-#[derive(Default)]
-pub struct WinitTextInputHandler {}
+impl WinitTextInputHandler {
+    pub fn new(window: Arc<Mutex<Window>>) -> Self {
+        Self { window }
+    }
+}
 
 impl TextInputHandler for WinitTextInputHandler {
-    fn show(&mut self) {}
+    fn show(&mut self) {
+        // Input methods only send their events while they are allowed.
+        self.window.lock().set_ime_allowed(true);
+    }
 
-    fn hide(&mut self) {}
+    fn hide(&mut self) {
+        self.window.lock().set_ime_allowed(false);
+    }
 }
@@ -1,3 +1,6 @@
+use flutter_input::keyboard::{commit_text, compose_text};
+use flutter_plugins::textinput::TextInputPlugin;
+use winit::event::Ime;
 use winit::keyboard::{Key, NamedKey};
 
 // Emulates glfw key numbers
@@ -72,3 +75,129 @@ pub fn raw_key(key: Key) -> Option<u32> {
     };
     Some(code)
 }
+
+/// Apply the pressed |key| to the text being edited.
+// TODO(vially): Fix text input logic to handle *all* named keys
+pub(crate) fn edit_text(text_input: &mut TextInputPlugin, key: &Key) {
+    match key {
+        Key::Named(key) => match key {
+            NamedKey::Enter => {
+                text_input.with_state(|state| {
+                    state.add_characters("\n");
+                });
+                text_input.notify_changes();
+            }
+            NamedKey::Backspace => {
+                text_input.with_state(|state| {
+                    state.backspace();
+                });
+                text_input.notify_changes();
+            }
+            _ => {}
+        },
+        Key::Character(ch) => {
+            text_input.with_state(|state| {
+                state.add_characters(ch);
+            });
+            text_input.notify_changes();
+        }
+        _ => {}
+    }
+}
+
+/// Apply the |ime| event of the input method to the text being edited.
+pub(crate) fn ime_event(text_input: &mut TextInputPlugin, ime: &Ime) {
+    match ime {
+        Ime::Preedit(text, _) => compose_text(text_input, text),
+        Ime::Commit(text) => commit_text(text_input, text),
+        Ime::Disabled => compose_text(text_input, ""),
+        Ime::Enabled => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flutter_plugins::textinput::{
+        conformance::{self, ConformanceBackend, ConformanceKey},
+        TextInputPlugin,
+    };
+    use winit::event::Ime;
+    use winit::keyboard::{Key, NamedKey};
+    use xkeysym::Keysym;
+
+    use super::{edit_text, ime_event, raw_key};
+
+    /// Fixtures whose expectations, recorded from the xkb backends, winit
+    /// doesn't meet yet. The window drops the keys without a glfw key code
+    /// along with their text, and Enter always inserts a newline instead of
+    /// performing the input action.
+    const EXPECTED_FAILURES: &[&str] = &[
+        // Uppercase letters and spaces are dropped, Enter inserts a newline.
+        "ascii_typing",
+        // Enter inserts a newline.
+        "client_switching",
+        // Accented letters are dropped.
+        "dead_keys",
+        // Delete is ignored.
+        "delta_model",
+        // Emoji typed as keys are dropped.
+        "emoji_surrogate_pairs",
+        // Enter inserts a newline without performing the newline action.
+        "multiline_newline",
+        // Enter inserts a newline.
+        "obscured_field",
+        // Uppercase letters are dropped.
+        "selection_replacement",
+    ];
+
+    /// The input path of the winit window.
+    struct WinitBackend;
+
+    impl ConformanceBackend for WinitBackend {
+        fn key_pressed(&mut self, text_input: &mut TextInputPlugin, key: &ConformanceKey) {
+            let key = winit_key(key);
+            // Like the window, which drops the keys without a glfw key code.
+            if raw_key(key.clone()).is_some() {
+                edit_text(text_input, &key);
+            }
+        }
+
+        fn preedit(&mut self, text_input: &mut TextInputPlugin, text: &str) {
+            ime_event(text_input, &Ime::Preedit(text.to_owned(), None));
+        }
+
+        fn commit(&mut self, text_input: &mut TextInputPlugin, text: &str) {
+            ime_event(text_input, &Ime::Commit(text.to_owned()));
+        }
+    }
+
+    /// The logical key winit reports for the |key| of a fixture.
+    fn winit_key(key: &ConformanceKey) -> Key {
+        let named = match Keysym::new(key.keysym) {
+            Keysym::Return | Keysym::KP_Enter => Some(NamedKey::Enter),
+            Keysym::BackSpace => Some(NamedKey::Backspace),
+            Keysym::Delete => Some(NamedKey::Delete),
+            Keysym::Left => Some(NamedKey::ArrowLeft),
+            Keysym::Right => Some(NamedKey::ArrowRight),
+            Keysym::Up => Some(NamedKey::ArrowUp),
+            Keysym::Down => Some(NamedKey::ArrowDown),
+            Keysym::Home => Some(NamedKey::Home),
+            Keysym::End => Some(NamedKey::End),
+            Keysym::Shift_L | Keysym::Shift_R => Some(NamedKey::Shift),
+            Keysym::Escape => Some(NamedKey::Escape),
+            Keysym::Tab => Some(NamedKey::Tab),
+            Keysym::space => Some(NamedKey::Space),
+            _ => None,
+        };
+        match (named, key.utf8.as_deref()) {
+            (Some(named), _) => Key::Named(named),
+            (None, Some(text)) => Key::Character(text.into()),
+            (None, None) => Key::Dead(None),
+        }
+    }
+
+    #[test]
+    fn conformance() {
+        conformance::check_all_except(&mut WinitBackend, EXPECTED_FAILURES).unwrap();
+    }
+}
@@ -8,7 +8,10 @@ mod pointer;
 mod view;
 mod window;
 
-pub use application::{WinitApplication, WinitApplicationBuildError, WinitApplicationRunError};
+pub use application::{
+    WinitApplication, WinitApplicationBuildError, WinitApplicationHandle,
+    WinitApplicationRunError,
+};
 pub use handler::WinitPlatformTaskHandler;
 pub use window::FlutterWindow;
 pub use winit::event_loop::{ControlFlow, EventLoop, EventLoopBuilder};
@@ -1,3 +1,4 @@
+use dpi::PhysicalPosition;
 use flutter_engine::ffi::{
     FlutterPointerDeviceKind, FlutterPointerEvent, FlutterPointerMouseButtons, FlutterPointerPhase,
     FlutterPointerSignalKind, FlutterViewId,
@@ -8,7 +9,7 @@ use winit::event::{DeviceId, ElementState, MouseButton, TouchPhase};
 pub struct Pointer {
     device_id: DeviceId,
     touch: bool,
-    position: (f64, f64),
+    position: PhysicalPosition<f64>,
     pressed: u32,
 }
 
@@ -17,7 +18,7 @@ impl Pointer {
         Self {
             device_id,
             touch,
-            position: (0.0, 0.0),
+            position: PhysicalPosition::new(0.0, 0.0),
             pressed: 0,
         }
     }
@@ -80,7 +81,12 @@ impl Pointers {
         ));
     }
 
-    pub fn moved(&mut self, view_id: FlutterViewId, device_id: DeviceId, position: (f64, f64)) {
+    pub fn moved(
+        &mut self,
+        view_id: FlutterViewId,
+        device_id: DeviceId,
+        position: PhysicalPosition<f64>,
+    ) {
         let device = self.index(device_id, false);
         self.pointers[device].position = position;
         let pointer = &self.pointers[device];
@@ -163,7 +169,7 @@ impl Pointers {
         view_id: FlutterViewId,
         device_id: DeviceId,
         phase: TouchPhase,
-        position: (f64, f64),
+        position: PhysicalPosition<f64>,
     ) {
         let device = self.index(device_id, true);
         let phase = match phase {
@@ -1,8 +1,10 @@
 use flutter_engine::{
     ffi::{FlutterViewId, IMPLICIT_VIEW_ID},
+    plugins::PluginRegistrar,
     view::FlutterView,
     FlutterEngine,
 };
+use flutter_runner_api::VsyncMode;
 use std::error::Error as StdError;
 use thiserror::Error;
 use winit::{event_loop::EventLoop, window::WindowAttributes};
@@ -19,9 +21,22 @@ impl FlutterViewWinit {
         event_loop: &EventLoop<FlutterEvent>,
         engine: FlutterEngine,
         attributes: WindowAttributes,
+        vsync_mode: VsyncMode,
+        robust_context: bool,
+        gl_debug_output: bool,
+        plugins: PluginRegistrar,
     ) -> Result<Self, WinitControllerError> {
         let view_id = IMPLICIT_VIEW_ID;
-        let window = FlutterWindow::new(view_id, event_loop, engine, attributes)?;
+        let window = FlutterWindow::new(
+            view_id,
+            event_loop,
+            engine,
+            attributes,
+            vsync_mode,
+            robust_context,
+            gl_debug_output,
+            plugins,
+        )?;
 
         Ok(Self::new(view_id, window))
     }
@@ -1,6 +1,6 @@
-use crate::egl::create_window_contexts;
+use crate::egl::{create_window_contexts, WindowContexts};
 use crate::handler::{WinitPlatformHandler, WinitTextInputHandler, WinitWindowHandler};
-use crate::keyboard::raw_key;
+use crate::keyboard::{edit_text, ime_event, raw_key};
 use crate::pointer::Pointers;
 use dpi::PhysicalSize;
 use flutter_engine::channel::Channel;
@@ -11,6 +11,7 @@ use flutter_engine::{FlutterEngine, FlutterEngineWeakRef};
 use flutter_engine_sys::FlutterEngineDisplayId;
 use flutter_glutin::context::{Context, ResourceContext};
 use flutter_glutin::handler::GlutinOpenGLHandler;
+use flutter_plugins::diagnostics::DiagnosticsPlugin;
 use flutter_plugins::isolate::IsolatePlugin;
 use flutter_plugins::keyevent::{KeyAction, KeyActionType, KeyEventPlugin};
 use flutter_plugins::lifecycle::LifecyclePlugin;
@@ -18,25 +19,32 @@ use flutter_plugins::localization::LocalizationPlugin;
 use flutter_plugins::navigation::NavigationPlugin;
 use flutter_plugins::platform::PlatformPlugin;
 use flutter_plugins::settings::SettingsPlugin;
+use flutter_plugins::shared_preferences::SharedPreferencesPlugin;
 use flutter_plugins::system::SystemPlugin;
 use flutter_plugins::textinput::TextInputPlugin;
-use flutter_plugins::window::WindowPlugin;
+use flutter_plugins::window::{WindowCapabilities, WindowPlugin};
+use flutter_runner_api::VsyncMode;
 use parking_lot::{Mutex, RwLock};
 use std::error::Error;
 use std::num::NonZeroU32;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tracing::trace;
+use tracing::{trace, warn};
 use winit::event::{ElementState, KeyEvent, MouseScrollDelta, Touch, WindowEvent};
 use winit::event_loop::{EventLoop, EventLoopProxy};
-use winit::keyboard::{Key, NamedKey};
+use winit::platform::wayland::EventLoopExtWayland;
 use winit::window::{Window, WindowAttributes, WindowId};
 
 pub enum FlutterEvent {
     WakePlatformThread,
     IsolateCreated,
     WindowCloseRequested(WindowId),
+    Suspend { drop_caches: bool },
+    Resume,
+    Quit,
+    /// Quit as requested by Dart, with this exit code.
+    ExitWithCode(i32),
 }
 
 pub struct FlutterWindow {
@@ -51,13 +59,36 @@ pub struct FlutterWindow {
 }
 
 impl FlutterWindow {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         view_id: FlutterViewId,
         event_loop: &EventLoop<FlutterEvent>,
         engine: FlutterEngine,
         attributes: WindowAttributes,
+        vsync_mode: VsyncMode,
+        robust_context: bool,
+        gl_debug_output: bool,
+        mut plugins: PluginRegistrar,
     ) -> Result<Self, Box<dyn Error>> {
-        let (window, context, resource_context) = create_window_contexts(attributes, event_loop)?;
+        let transparent = attributes.transparent;
+        let WindowContexts {
+            window,
+            context,
+            resource_context,
+            has_alpha,
+        } = create_window_contexts(
+            attributes,
+            vsync_mode,
+            robust_context,
+            gl_debug_output,
+            event_loop,
+        )?;
+        if robust_context && !context.is_robust() {
+            warn!("Robust contexts are not supported by the driver, using a regular one");
+        }
+        if gl_debug_output && !context.has_debug_output() {
+            warn!("GL debug output is not supported by the driver, not logging it");
+        }
         let context = Arc::new(std::sync::Mutex::new(context));
         let resource_context = Arc::new(std::sync::Mutex::new(resource_context));
         let window = Arc::new(Mutex::new(window));
@@ -66,15 +97,34 @@ impl FlutterWindow {
         let isolate_cb = move || {
             proxy.send_event(FlutterEvent::IsolateCreated).ok();
         };
-        let platform_handler = Arc::new(Mutex::new(WinitPlatformHandler::new(window.clone())?));
+        let platform_handler = Arc::new(Mutex::new(WinitPlatformHandler::new(
+            window.clone(),
+            event_loop.create_proxy(),
+        )?));
         let close = Arc::new(AtomicBool::new(false));
+        if transparent && !has_alpha {
+            warn!("No config with an alpha channel is available, the window is opaque");
+        }
+        // Wayland leaves the stacking, the taskbar and the position of the
+        // windows to the compositor.
+        let is_wayland = event_loop.is_wayland();
+        let capabilities = WindowCapabilities {
+            transparency: transparent && has_alpha,
+            always_on_top: !is_wayland,
+            skip_taskbar: !is_wayland,
+            min_max_size: true,
+            maximize: true,
+            fullscreen: true,
+            position: !is_wayland,
+        };
         let window_handler = Arc::new(Mutex::new(WinitWindowHandler::new(
             window.clone(),
             close.clone(),
+            capabilities,
         )));
-        let textinput_handler = Arc::new(Mutex::new(WinitTextInputHandler::default()));
+        let textinput_handler = Arc::new(Mutex::new(WinitTextInputHandler::new(window.clone())));
 
-        let mut plugins = PluginRegistrar::new();
+        plugins.add_plugin(&engine, DiagnosticsPlugin::default());
         plugins.add_plugin(&engine, IsolatePlugin::new(isolate_cb));
         plugins.add_plugin(&engine, KeyEventPlugin::default());
         plugins.add_plugin(&engine, LifecyclePlugin::default());
@@ -82,9 +132,11 @@ impl FlutterWindow {
         plugins.add_plugin(&engine, NavigationPlugin::default());
         plugins.add_plugin(&engine, PlatformPlugin::new(platform_handler));
         plugins.add_plugin(&engine, SettingsPlugin::default());
+        plugins.add_plugin(&engine, SharedPreferencesPlugin::default());
         plugins.add_plugin(&engine, SystemPlugin::default());
         plugins.add_plugin(&engine, TextInputPlugin::new(textinput_handler));
         plugins.add_plugin(&engine, WindowPlugin::new(window_handler));
+        plugins.log_unused_configs();
 
         Ok(Self {
             view_id,
@@ -195,7 +247,7 @@ impl FlutterWindow {
                 position,
                 ..
             } => {
-                pointers.moved(self.view_id, device_id, position.into());
+                pointers.moved(self.view_id, device_id, position);
             }
             WindowEvent::MouseInput {
                 device_id,
@@ -223,7 +275,7 @@ impl FlutterWindow {
                 location,
                 ..
             }) => {
-                pointers.touch(self.view_id, device_id, phase, location.into());
+                pointers.touch(self.view_id, device_id, phase, location);
             }
             WindowEvent::KeyboardInput {
                 event: KeyEvent {
@@ -245,33 +297,9 @@ impl FlutterWindow {
 
                 match state {
                     ElementState::Pressed => {
-                        self.with_plugin_mut(
-                            // TODO(vially): Fix text input logic to handle *all* named keys
-                            |text_input: &mut TextInputPlugin| match logical_key {
-                                Key::Named(key) => match key {
-                                    NamedKey::Enter => {
-                                        text_input.with_state(|state| {
-                                            state.add_characters("\n");
-                                        });
-                                        text_input.notify_changes();
-                                    }
-                                    NamedKey::Backspace => {
-                                        text_input.with_state(|state| {
-                                            state.backspace();
-                                        });
-                                        text_input.notify_changes();
-                                    }
-                                    _ => {}
-                                },
-                                Key::Character(ch) => {
-                                    text_input.with_state(|state| {
-                                        state.add_characters(&ch.to_string());
-                                    });
-                                    text_input.notify_changes();
-                                }
-                                _ => {}
-                            },
-                        );
+                        self.with_plugin_mut(|text_input: &mut TextInputPlugin| {
+                            edit_text(text_input, &logical_key)
+                        });
 
                         self.with_plugin(|keyevent: &KeyEventPlugin| {
                             keyevent.key_action(KeyAction {
@@ -304,6 +332,11 @@ impl FlutterWindow {
                     }
                 }
             }
+            WindowEvent::Ime(ime) => {
+                self.with_plugin_mut(|text_input: &mut TextInputPlugin| {
+                    ime_event(text_input, &ime)
+                });
+            }
             _ => {}
         };
     }
@@ -331,11 +364,10 @@ pub(crate) fn resize(
         NonZeroU32::new(size.height).expect("Resize height needs to be higher than 0"),
     );
     context.lock().unwrap().resize(context_size);
-    engine.send_window_metrics_event(
-        view_id,
-        size.width as usize,
-        size.height as usize,
-        dpi,
-        display_id,
-    );
+    if let Err(err) = engine.send_window_metrics_event(view_id, size, dpi, display_id) {
+        warn!(
+            "Failed to send window metrics ({}x{} @ {}): {}",
+            size.width, size.height, dpi, err
+        );
+    }
 }